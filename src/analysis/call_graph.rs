@@ -0,0 +1,397 @@
+use std::{
+	collections::{BTreeMap, BTreeSet, VecDeque},
+	fmt::Write};
+
+use strum_macros::Display;
+
+use crate::{
+	analysis::{
+		hierarchy::{ClassHierarchy, MethodLocation},
+		json::Json},
+	class::{
+		access::ClassAccessPropertyFlags,
+		class::Class,
+		constant_pool::ConstantPoolItem},
+	error::DecodeError,
+	isa::{instruction, opcode::Opcode}};
+
+const LAMBDA_METAFACTORY: &str = "java/lang/invoke/LambdaMetafactory";
+const CLASS_INITIALIZER: &str = "<clinit>";
+const CLASS_INITIALIZER_DESCRIPTOR: &str = "()V";
+
+/// How the possible targets of `invokevirtual` and `invokeinterface` are computed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Algorithm {
+	/// Class hierarchy analysis: any concrete subtype of the owner may be the receiver.
+	#[default]
+	Cha,
+	/// Rapid type analysis: only classes instantiated by reachable code may be the receiver.
+	Rta,
+}
+
+#[derive(Clone, Copy, Debug, Display, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[strum(serialize_all = "lowercase")]
+pub enum CallKind {
+	Static,
+	Special,
+	Virtual,
+	Interface,
+	/// A call through an `invokedynamic` site: the lambda implementation method for
+	/// LambdaMetafactory bootstraps, otherwise the bootstrap method itself.
+	Dynamic,
+	/// The implicit call to `<clinit>` when a class is first used (JVMS17 5.5).
+	Initialization,
+}
+
+/// A call from one method to another at a given bytecode offset of the caller.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct CallEdge {
+	pub caller: MethodLocation,
+	pub callee: MethodLocation,
+	pub kind: CallKind,
+	pub pc: u32,
+}
+
+/// The methods reachable from a set of entry points and the calls between them.
+///
+/// Callees outside the hierarchy (e.g. JDK methods) are nodes with no outgoing edges.
+#[derive(Debug, Default)]
+pub struct CallGraph {
+	entry_points: BTreeSet<MethodLocation>,
+	nodes: BTreeSet<MethodLocation>,
+	edges: BTreeSet<CallEdge>,
+}
+
+/// Builds a `CallGraph` over the classes of a `ClassHierarchy`.
+pub struct CallGraphBuilder<'a> {
+	hierarchy: &'a ClassHierarchy,
+	algorithm: Algorithm,
+	entry_points: Vec<MethodLocation>,
+}
+
+impl<'a> CallGraphBuilder<'a> {
+	pub fn new(hierarchy: &'a ClassHierarchy) -> CallGraphBuilder<'a> {
+		CallGraphBuilder { hierarchy, algorithm: Algorithm::default(), entry_points: Vec::new() }
+	}
+
+	pub fn algorithm(mut self, algorithm: Algorithm) -> CallGraphBuilder<'a> {
+		self.algorithm = algorithm;
+		self
+	}
+
+	/// Add a root of the graph. Without any, every method in the hierarchy is a root.
+	pub fn entry_point(mut self, method: MethodLocation) -> CallGraphBuilder<'a> {
+		self.entry_points.push(method);
+		self
+	}
+
+	pub fn entry_points<I: IntoIterator<Item = MethodLocation>>(mut self, methods: I) -> CallGraphBuilder<'a> {
+		self.entry_points.extend(methods);
+		self
+	}
+
+	pub fn build(&self) -> Result<CallGraph, DecodeError> {
+		let entry_points: BTreeSet<MethodLocation> = if self.entry_points.is_empty() {
+			self.hierarchy.classes().flat_map(|class| {
+				let class_name = class.name();
+				class.methods.methods.iter().filter_map(move |method| {
+					Some(MethodLocation { class_name: class_name.clone(), name: class.get_utf8(method.name_index)?, descriptor: class.get_utf8(method.descriptor_index)? })
+				}).collect::<Vec<_>>()
+			}).collect()
+		} else {
+			self.entry_points.iter().cloned().collect()
+		};
+
+		// Under RTA, the set of instantiated classes grows as more code becomes reachable, which in
+		// turn may make more code reachable; iterate until it stops changing.
+		let mut instantiated = BTreeSet::new();
+		loop {
+			let (graph, found) = self.traverse(&entry_points, &instantiated)?;
+			if self.algorithm == Algorithm::Cha || found == instantiated {
+				return Ok(graph);
+			}
+			instantiated = found;
+		}
+	}
+
+	fn traverse(&self, entry_points: &BTreeSet<MethodLocation>, instantiated: &BTreeSet<String>) -> Result<(CallGraph, BTreeSet<String>), DecodeError> {
+		let mut graph = CallGraph { entry_points: entry_points.clone(), ..CallGraph::default() };
+		let mut found = BTreeSet::new();
+		let mut worklist: VecDeque<MethodLocation> = entry_points.iter().cloned().collect();
+		while let Some(caller) = worklist.pop_front() {
+			if !graph.nodes.insert(caller.clone()) {
+				continue;
+			}
+			for edge in self.calls_from(&caller, instantiated, &mut found)? {
+				if !graph.nodes.contains(&edge.callee) {
+					worklist.push_back(edge.callee.clone());
+				}
+				graph.edges.insert(edge);
+			}
+		}
+		Ok((graph, found))
+	}
+
+	fn calls_from(&self, caller: &MethodLocation, instantiated: &BTreeSet<String>, found: &mut BTreeSet<String>) -> Result<Vec<CallEdge>, DecodeError> {
+		let Some(class) = self.hierarchy.get(&caller.class_name) else {
+			return Ok(vec![]);
+		};
+		let Some(code) = class.find_method(&caller.name, &caller.descriptor).and_then(|method| method.code()) else {
+			return Ok(vec![]);
+		};
+		let mut edges = Vec::new();
+		let mut edge = |callee: MethodLocation, kind: CallKind, pc: u32| {
+			edges.push(CallEdge { caller: caller.clone(), callee, kind, pc });
+		};
+		for (pc, instruction) in instruction::decode(&code.code)? {
+			let Some(index) = instruction.constant_pool_index() else {
+				continue;
+			};
+			match instruction.opcode() {
+				Opcode::New => {
+					if let Some(class_name) = class.get_class_name_at(index) {
+						if let Some(initializer) = self.class_initializer(&class_name) {
+							edge(initializer, CallKind::Initialization, pc);
+						}
+						found.insert(class_name);
+					}
+				}
+				Opcode::GetStatic | Opcode::PutStatic => {
+					if let Some(field) = class.get_member_ref(index)
+						&& let Some(initializer) = self.class_initializer(&field.class_name) {
+						edge(initializer, CallKind::Initialization, pc);
+					}
+				}
+				opcode @ (Opcode::InvokeStatic | Opcode::InvokeSpecial) => {
+					let Some(method) = class.get_member_ref(index) else {
+						continue;
+					};
+					if opcode == Opcode::InvokeStatic && let Some(initializer) = self.class_initializer(&method.class_name) {
+						edge(initializer, CallKind::Initialization, pc);
+					}
+					let kind = if opcode == Opcode::InvokeStatic { CallKind::Static } else { CallKind::Special };
+					edge(self.resolve(&method.class_name, &method.name, &method.descriptor), kind, pc);
+				}
+				opcode @ (Opcode::InvokeVirtual | Opcode::InvokeInterface) => {
+					let Some(method) = class.get_member_ref(index) else {
+						continue;
+					};
+					let kind = if opcode == Opcode::InvokeVirtual { CallKind::Virtual } else { CallKind::Interface };
+					for callee in self.dispatch(&method.class_name, &method.name, &method.descriptor, instantiated) {
+						edge(callee, kind, pc);
+					}
+				}
+				Opcode::InvokeDynamic => {
+					if let Some(callee) = dynamic_target(class, index) {
+						edge(callee, CallKind::Dynamic, pc);
+					}
+				}
+				_ => {}
+			}
+		}
+		Ok(edges)
+	}
+
+	fn class_initializer(&self, class_name: &str) -> Option<MethodLocation> {
+		self.hierarchy.get(class_name)?.find_method(CLASS_INITIALIZER, CLASS_INITIALIZER_DESCRIPTOR)?;
+		Some(MethodLocation::new(class_name, CLASS_INITIALIZER, CLASS_INITIALIZER_DESCRIPTOR))
+	}
+
+	/// The declaration a method reference resolves to, or the reference itself if it can't be resolved.
+	fn resolve(&self, owner: &str, name: &str, descriptor: &str) -> MethodLocation {
+		let resolved = match self.hierarchy.get(owner) {
+			Some(class) if class.is_interface() => self.hierarchy.resolve_interface_method(owner, name, descriptor),
+			Some(_) => self.hierarchy.resolve_method(owner, name, descriptor),
+			None => Ok(None),
+		};
+		resolved.ok().flatten().unwrap_or_else(|| MethodLocation::new(owner, name, descriptor))
+	}
+
+	/// The methods a virtual or interface call may dispatch to.
+	fn dispatch(&self, owner: &str, name: &str, descriptor: &str, instantiated: &BTreeSet<String>) -> BTreeSet<MethodLocation> {
+		let resolved = self.resolve(owner, name, descriptor);
+		if !self.hierarchy.contains(owner) {
+			return BTreeSet::from([resolved]);
+		}
+		let mut targets = BTreeSet::new();
+		for receiver in std::iter::once(owner.to_string()).chain(self.hierarchy.subtypes(owner)) {
+			let Some(class) = self.hierarchy.get(&receiver) else {
+				continue;
+			};
+			if class.is_interface() || class.flags.contains(&ClassAccessPropertyFlags::Abstract) {
+				continue;
+			}
+			if self.algorithm == Algorithm::Rta && !instantiated.contains(&receiver) {
+				continue;
+			}
+			// An incomplete hierarchy above the receiver leaves dispatch unknown; keep the declaration.
+			match self.hierarchy.select_method(&receiver, &resolved) {
+				Ok(Some(selected)) => { targets.insert(selected); }
+				Ok(None) => {}
+				Err(_) => { targets.insert(resolved.clone()); }
+			}
+		}
+		if targets.is_empty() && self.algorithm == Algorithm::Cha {
+			targets.insert(resolved);
+		}
+		targets
+	}
+}
+
+/// The method an `invokedynamic` site calls: for LambdaMetafactory bootstraps the implementation
+/// method handle (the second static argument), otherwise the bootstrap method.
+pub(crate) fn dynamic_target(class: &Class, index: u16) -> Option<MethodLocation> {
+	let ConstantPoolItem::InvokeDynamic(call_site) = class.constant_pool.get(&index)? else {
+		return None;
+	};
+	let bootstrap = class.bootstrap_methods()?.bootstrap_methods.get(call_site.bootstrap_method_attr_index as usize)?;
+	let bootstrap_method = method_handle_target(class, bootstrap.bootstrap_method_ref)?;
+	if bootstrap_method.class_name == LAMBDA_METAFACTORY
+		&& (bootstrap_method.name == "metafactory" || bootstrap_method.name == "altMetafactory")
+		&& let Some(implementation) = bootstrap.bootstrap_arguments.get(1).and_then(|index| method_handle_target(class, *index)) {
+		return Some(implementation);
+	}
+	Some(bootstrap_method)
+}
+
+fn method_handle_target(class: &Class, index: u16) -> Option<MethodLocation> {
+	let ConstantPoolItem::MethodHandle(handle) = class.constant_pool.get(&index)? else {
+		return None;
+	};
+	let member = class.get_member_ref(handle.reference_index)?;
+	Some(MethodLocation { class_name: member.class_name, name: member.name, descriptor: member.descriptor })
+}
+
+fn method_label(method: &MethodLocation) -> String {
+	format!("{}.{}{}", method.class_name, method.name, method.descriptor)
+}
+
+impl CallGraph {
+	pub fn entry_points(&self) -> &BTreeSet<MethodLocation> {
+		&self.entry_points
+	}
+
+	/// Every method reachable from the entry points, including the entry points themselves.
+	pub fn nodes(&self) -> &BTreeSet<MethodLocation> {
+		&self.nodes
+	}
+
+	pub fn edges(&self) -> &BTreeSet<CallEdge> {
+		&self.edges
+	}
+
+	pub fn contains(&self, method: &MethodLocation) -> bool {
+		self.nodes.contains(method)
+	}
+
+	pub fn callees(&self, method: &MethodLocation) -> BTreeSet<&MethodLocation> {
+		self.edges.iter().filter(|edge| &edge.caller == method).map(|edge| &edge.callee).collect()
+	}
+
+	pub fn callers(&self, method: &MethodLocation) -> BTreeSet<&MethodLocation> {
+		self.edges.iter().filter(|edge| &edge.callee == method).map(|edge| &edge.caller).collect()
+	}
+
+	/// Every method that may call `method` directly or indirectly, i.e. the methods affected
+	/// by a change to it.
+	pub fn transitive_callers(&self, method: &MethodLocation) -> BTreeSet<&MethodLocation> {
+		let mut callers = BTreeSet::new();
+		let mut worklist = vec![method];
+		while let Some(callee) = worklist.pop() {
+			for caller in self.callers(callee) {
+				if callers.insert(caller) {
+					worklist.push(caller);
+				}
+			}
+		}
+		callers
+	}
+
+	/// Render in Graphviz DOT format. Entry points are drawn with a double border.
+	pub fn to_dot(&self) -> String {
+		let ids: BTreeMap<&MethodLocation, usize> = self.nodes.iter().enumerate().map(|(id, method)| (method, id)).collect();
+		let mut dot = String::from("digraph calls {\n");
+		for (method, id) in &ids {
+			let shape = if self.entry_points.contains(method) { ", peripheries=2" } else { "" };
+			let _ = writeln!(dot, "\tn{} [label={}{}];", id, Json::from(method_label(method)), shape);
+		}
+		for edge in &self.edges {
+			let _ = writeln!(dot, "\tn{} -> n{} [label=\"{}@{}\"];", ids[&edge.caller], ids[&edge.callee], edge.kind, edge.pc);
+		}
+		dot.push_str("}\n");
+		dot
+	}
+
+	pub fn to_json(&self) -> Json {
+		let method = |method: &MethodLocation| Json::object([
+			("class", Json::from(&method.class_name)),
+			("name", Json::from(&method.name)),
+			("descriptor", Json::from(&method.descriptor)),
+		]);
+		Json::object([
+			("entry_points", Json::Array(self.entry_points.iter().map(method).collect())),
+			("nodes", Json::Array(self.nodes.iter().map(method).collect())),
+			("edges", Json::Array(self.edges.iter().map(|edge| Json::object([
+				("caller", method(&edge.caller)),
+				("callee", method(&edge.callee)),
+				("kind", Json::from(edge.kind.to_string())),
+				("pc", Json::from(edge.pc)),
+			])).collect())),
+		])
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::BTreeSet, path::Path};
+
+	use crate::analysis::{
+		call_graph::{Algorithm, CallGraph, CallGraphBuilder, CallKind},
+		hierarchy::{ClassHierarchy, MethodLocation}};
+
+	const GREET: &str = "(Ljava/lang/String;)Ljava/lang/String;";
+
+	fn build(algorithm: Algorithm) -> CallGraph {
+		let hierarchy = ClassHierarchy::from_path(Path::new("tests/resources/calls")).expect("Couldn't read classes");
+		CallGraphBuilder::new(&hierarchy)
+			.algorithm(algorithm)
+			.entry_point(MethodLocation::new("App", "main", "([Ljava/lang/String;)V"))
+			.build()
+			.expect("Couldn't build call graph")
+	}
+
+	#[test]
+	fn test_cha() {
+		let graph = build(Algorithm::Cha);
+		let main = MethodLocation::new("App", "main", "([Ljava/lang/String;)V");
+		assert!(graph.contains(&MethodLocation::new("English", "greet", GREET)));
+		assert!(graph.contains(&MethodLocation::new("French", "greet", GREET)));
+		assert!(graph.contains(&MethodLocation::new("java/io/PrintStream", "println", "(Ljava/lang/String;)V")));
+		assert!(!graph.contains(&MethodLocation::new("App", "unused", "()V")));
+
+		let lambda = MethodLocation::new("App", "lambda$main$0", "()Ljava/lang/String;");
+		assert!(graph.edges().iter().any(|edge| edge.caller == main && edge.callee == lambda && edge.kind == CallKind::Dynamic && edge.pc == 22));
+		let shout = MethodLocation::new("App", "shout", GREET);
+		assert_eq!(graph.callees(&lambda), BTreeSet::from([&shout]));
+		assert_eq!(graph.transitive_callers(&shout), BTreeSet::from([&lambda, &main]));
+	}
+
+	#[test]
+	fn test_rta() {
+		let graph = build(Algorithm::Rta);
+		assert!(graph.contains(&MethodLocation::new("English", "greet", GREET)));
+		assert!(!graph.contains(&MethodLocation::new("French", "greet", GREET)));
+	}
+
+	#[test]
+	fn test_export() {
+		let graph = build(Algorithm::Rta);
+		let dot = graph.to_dot();
+		assert!(dot.starts_with("digraph calls {\n"));
+		assert!(dot.contains("[label=\"App.main([Ljava/lang/String;)V\", peripheries=2];"));
+		assert!(dot.contains("[label=\"interface@14\"];"));
+		let json = graph.to_json().to_string();
+		assert!(json.contains("{\"class\":\"English\",\"name\":\"greet\",\"descriptor\":\"(Ljava/lang/String;)Ljava/lang/String;\"}"));
+		assert!(json.contains("\"kind\":\"dynamic\",\"pc\":22"));
+	}
+}
@@ -0,0 +1,441 @@
+use std::{
+	collections::BTreeSet,
+	fmt::{self, Display, Formatter},
+	path::Path};
+
+use strum_macros::Display;
+
+use crate::{
+	analysis::{
+		hierarchy::ClassHierarchy,
+		json::Json},
+	class::{
+		access::{ClassAccessPropertyFlags, MethodAccessPropertyFlags},
+		archive::ArchiveError,
+		attribute::{Attribute, AttributeInfo},
+		class::Class},
+};
+
+/// A kind of change that breaks binaries compiled against the old version of a library (JLS17
+/// chapter 13).
+#[derive(Clone, Copy, Debug, Display, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ProblemKind {
+	/// JLS17 13.4.1
+	ClassRemoved,
+	/// JLS17 13.4.1, 13.4.3
+	AccessNarrowed,
+	/// JLS17 13.4.1
+	ClassMadeAbstract,
+	/// JLS17 13.4.2
+	ClassMadeFinal,
+	/// JLS17 13.4.1
+	ClassKindChanged,
+	/// JLS17 13.4.4
+	SuperclassRemoved,
+	/// JLS17 13.4.4
+	InterfaceRemoved,
+	/// JLS17 13.4.8
+	FieldRemoved,
+	/// JLS17 13.4.8
+	FieldTypeChanged,
+	/// JLS17 13.4.9
+	FieldMadeFinal,
+	/// JLS17 13.4.9: callers keep the inlined old value.
+	ConstantValueChanged,
+	/// JLS17 13.4.12
+	MethodRemoved,
+	/// JLS17 13.4.14
+	DescriptorChanged,
+	/// JLS17 13.4.16
+	MethodMadeAbstract,
+	/// JLS17 13.4.17
+	MethodMadeFinal,
+	/// JLS17 13.4.10, 13.4.19
+	StaticChanged,
+}
+
+/// The access level of a member, ordered from least to most accessible.
+#[derive(Clone, Copy, Debug, Display, Eq, Ord, PartialEq, PartialOrd)]
+#[strum(serialize_all = "lowercase")]
+pub enum Access {
+	Private,
+	Package,
+	Protected,
+	Public,
+}
+
+impl Access {
+	/// The access level of field or method flags, which share their access bits (JVMS17 4.5, 4.6).
+	pub fn of(flags: u16) -> Access {
+		if has_flag(flags, MethodAccessPropertyFlags::Public) {
+			Access::Public
+		} else if has_flag(flags, MethodAccessPropertyFlags::Protected) {
+			Access::Protected
+		} else if has_flag(flags, MethodAccessPropertyFlags::Private) {
+			Access::Private
+		} else {
+			Access::Package
+		}
+	}
+}
+
+/// A binary-incompatible change to a class or one of its members. `member` is `name:descriptor`
+/// for fields, `name` + `descriptor` for methods, as in `run()V`, and None for the class itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+	pub kind: ProblemKind,
+	pub class_name: String,
+	pub member: Option<String>,
+	pub detail: Option<String>,
+}
+
+impl Display for Problem {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{} {}", self.kind, self.class_name)?;
+		if let Some(member) = &self.member {
+			write!(f, ".{}", member)?;
+		}
+		if let Some(detail) = &self.detail {
+			write!(f, ": {}", detail)?;
+		}
+		Ok(())
+	}
+}
+
+/// The binary-incompatible changes between two versions of a library.
+///
+/// Only the library's API is checked: public classes and their public members, plus protected
+/// members of classes that can be subclassed. A member that moved to a supertype is still found
+/// by lookup and so is not reported as removed. Supertypes outside the library (the JDK, other
+/// dependencies) are taken by name only.
+#[derive(Debug, Default)]
+pub struct CompatibilityReport {
+	pub classes_checked: usize,
+	pub problems: Vec<Problem>,
+}
+
+/// A field or method as the checker sees it.
+struct Member {
+	name: String,
+	descriptor: String,
+	flags: u16,
+	constant_value: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MemberKind {
+	Field,
+	Method,
+}
+
+fn has_flag(flags: u16, flag: MethodAccessPropertyFlags) -> bool {
+	flags & flag as u16 != 0
+}
+
+fn members(class: &Class, kind: MemberKind) -> Vec<Member> {
+	let member = |name_index: u16, descriptor_index: u16, flags: u16, attributes: &[Attribute]| {
+		let constant_value = attributes.iter().find_map(|attribute| match &attribute.attribute_info {
+			AttributeInfo::ConstantValue(value) => Some(class.render_constant(value.constant_value_index)),
+			_ => None,
+		});
+		Some(Member { name: class.get_utf8(name_index)?, descriptor: class.get_utf8(descriptor_index)?, flags, constant_value })
+	};
+	match kind {
+		MemberKind::Field => class.fields.fields.iter()
+			.filter_map(|field| member(field.name_index, field.descriptor_index, field.access_flags, &field.attributes))
+			.collect(),
+		MemberKind::Method => class.methods.methods.iter()
+			.filter_map(|method| member(method.name_index, method.descriptor_index, method.access_flags, &method.attributes))
+			.collect(),
+	}
+}
+
+/// The superclass chain of a class, nearest first, as far as the hierarchy knows it. The first
+/// superclass missing from the hierarchy ends the chain.
+fn superclasses(hierarchy: &ClassHierarchy, name: &str) -> Vec<String> {
+	let mut chain: Vec<String> = Vec::new();
+	let mut current = hierarchy.get(name).and_then(Class::super_class_name);
+	while let Some(super_name) = current {
+		if chain.contains(&super_name) {
+			break;
+		}
+		current = hierarchy.get(&super_name).and_then(Class::super_class_name);
+		chain.push(super_name);
+	}
+	chain
+}
+
+/// Every interface a class implements, directly or through the supertypes the hierarchy knows.
+fn interfaces(hierarchy: &ClassHierarchy, name: &str) -> BTreeSet<String> {
+	let mut interfaces: BTreeSet<String> = BTreeSet::new();
+	let mut pending: Vec<String> = vec![name.to_string()];
+	pending.extend(superclasses(hierarchy, name));
+	while let Some(current) = pending.pop() {
+		for interface in hierarchy.get(&current).map(Class::interface_names).unwrap_or_default() {
+			if interfaces.insert(interface.clone()) {
+				pending.push(interface);
+			}
+		}
+	}
+	interfaces
+}
+
+/// Look a member up as a linker would: in the class, its superclasses, then its interfaces.
+/// Private members of supertypes and static interface methods are not inherited.
+fn find_member(hierarchy: &ClassHierarchy, class_name: &str, name: &str, descriptor: &str, kind: MemberKind) -> Option<Member> {
+	let superclasses = superclasses(hierarchy, class_name).into_iter().map(|name| (name, false));
+	let interfaces = interfaces(hierarchy, class_name).into_iter().map(|name| (name, true));
+	let candidates = std::iter::once((class_name.to_string(), false)).chain(superclasses).chain(interfaces);
+	for (owner, is_interface) in candidates {
+		let Some(class) = hierarchy.get(&owner) else {
+			continue;
+		};
+		let inherited = owner != class_name;
+		let found = members(class, kind).into_iter().find(|member| {
+			member.name == name && member.descriptor == descriptor &&
+				!(inherited && Access::of(member.flags) == Access::Private) &&
+				!(is_interface && kind == MemberKind::Method && has_flag(member.flags, MethodAccessPropertyFlags::Static))
+		});
+		if found.is_some() {
+			return found;
+		}
+	}
+	None
+}
+
+impl CompatibilityReport {
+
+	/// Check every public class of `old` against its counterpart in `new`.
+	pub fn new(old: &ClassHierarchy, new: &ClassHierarchy) -> CompatibilityReport {
+		let mut names: Vec<String> = old.classes()
+			.filter(|class| class.flags.contains(&ClassAccessPropertyFlags::Public))
+			.map(Class::name)
+			.collect();
+		names.sort();
+		let mut report = CompatibilityReport { classes_checked: names.len(), problems: Vec::new() };
+		for name in names {
+			if let Some(class) = old.get(&name) {
+				report.check_class(class, old, new);
+			}
+		}
+		report
+	}
+
+	/// Compare two versions of a library, each a directory of class files or a JAR.
+	pub fn from_paths(old: &Path, new: &Path) -> Result<CompatibilityReport, ArchiveError> {
+		Ok(CompatibilityReport::new(&ClassHierarchy::from_path(old)?, &ClassHierarchy::from_path(new)?))
+	}
+
+	pub fn is_compatible(&self) -> bool {
+		self.problems.is_empty()
+	}
+
+	fn report(&mut self, kind: ProblemKind, class_name: &str, member: Option<String>, detail: Option<String>) {
+		self.problems.push(Problem { kind, class_name: class_name.to_string(), member, detail });
+	}
+
+	fn check_class(&mut self, old_class: &Class, old: &ClassHierarchy, new: &ClassHierarchy) {
+		let name = old_class.name();
+		let Some(new_class) = new.get(&name) else {
+			self.report(ProblemKind::ClassRemoved, &name, None, None);
+			return;
+		};
+		if !new_class.flags.contains(&ClassAccessPropertyFlags::Public) {
+			self.report(ProblemKind::AccessNarrowed, &name, None, Some("public -> package".to_string()));
+			return;
+		}
+		if old_class.is_interface() != new_class.is_interface() {
+			let kind = |class: &Class| if class.is_interface() { "interface" } else { "class" };
+			self.report(ProblemKind::ClassKindChanged, &name, None, Some(format!("{} -> {}", kind(old_class), kind(new_class))));
+			return;
+		}
+		let added = |flag: ClassAccessPropertyFlags| !old_class.flags.contains(&flag) && new_class.flags.contains(&flag);
+		if !old_class.is_interface() && added(ClassAccessPropertyFlags::Abstract) {
+			self.report(ProblemKind::ClassMadeAbstract, &name, None, None);
+		}
+		if added(ClassAccessPropertyFlags::Final) {
+			self.report(ProblemKind::ClassMadeFinal, &name, None, None);
+		}
+		let new_superclasses = superclasses(new, &name);
+		for superclass in superclasses(old, &name) {
+			if !new_superclasses.contains(&superclass) {
+				self.report(ProblemKind::SuperclassRemoved, &name, None, Some(superclass));
+			}
+		}
+		let new_interfaces = interfaces(new, &name);
+		for interface in interfaces(old, &name) {
+			if !new_interfaces.contains(&interface) {
+				self.report(ProblemKind::InterfaceRemoved, &name, None, Some(interface));
+			}
+		}
+
+		// protected members are only reachable through subclasses
+		let subclassable = !old_class.flags.contains(&ClassAccessPropertyFlags::Final);
+		let is_api = |member: &Member| match Access::of(member.flags) {
+			Access::Public => true,
+			Access::Protected => subclassable,
+			_ => false,
+		} && !has_flag(member.flags, MethodAccessPropertyFlags::Synthetic);
+		for kind in [MemberKind::Field, MemberKind::Method] {
+			let old_members = members(old_class, kind);
+			for old_member in old_members.iter().filter(|member| is_api(member) && member.name != "<clinit>") {
+				let label = match kind {
+					MemberKind::Field => format!("{}:{}", old_member.name, old_member.descriptor),
+					MemberKind::Method => format!("{}{}", old_member.name, old_member.descriptor),
+				};
+				match find_member(new, &name, &old_member.name, &old_member.descriptor, kind) {
+					Some(new_member) => self.check_member(&name, label, old_member, &new_member, kind, subclassable),
+					None => {
+						// a same-named member the old version didn't have is taken to be its replacement
+						let replacements: Vec<String> = members(new_class, kind).into_iter()
+							.filter(|member| member.name == old_member.name)
+							.filter(|member| !old_members.iter().any(|old| old.name == member.name && old.descriptor == member.descriptor))
+							.map(|member| member.descriptor)
+							.collect();
+						let changed = match kind {
+							MemberKind::Field => ProblemKind::FieldTypeChanged,
+							MemberKind::Method => ProblemKind::DescriptorChanged,
+						};
+						if replacements.is_empty() {
+							let removed = match kind {
+								MemberKind::Field => ProblemKind::FieldRemoved,
+								MemberKind::Method => ProblemKind::MethodRemoved,
+							};
+							self.report(removed, &name, Some(label), None);
+						} else {
+							self.report(changed, &name, Some(label), Some(format!("{} -> {}", old_member.descriptor, replacements.join(", "))));
+						}
+					}
+				}
+			}
+		}
+	}
+
+	fn check_member(&mut self, class_name: &str, label: String, old: &Member, new: &Member, kind: MemberKind, subclassable: bool) {
+		let added = |flag: MethodAccessPropertyFlags| !has_flag(old.flags, flag) && has_flag(new.flags, flag);
+		let (old_access, new_access) = (Access::of(old.flags), Access::of(new.flags));
+		if new_access < old_access {
+			self.report(ProblemKind::AccessNarrowed, class_name, Some(label.clone()), Some(format!("{} -> {}", old_access, new_access)));
+		}
+		let (old_static, new_static) = (has_flag(old.flags, MethodAccessPropertyFlags::Static), has_flag(new.flags, MethodAccessPropertyFlags::Static));
+		if old_static != new_static {
+			let detail = if new_static { "now static" } else { "no longer static" };
+			self.report(ProblemKind::StaticChanged, class_name, Some(label.clone()), Some(detail.to_string()));
+		}
+		match kind {
+			MemberKind::Field => {
+				if added(MethodAccessPropertyFlags::Final) {
+					self.report(ProblemKind::FieldMadeFinal, class_name, Some(label.clone()), None);
+				}
+				if old.constant_value.is_some() && old.constant_value != new.constant_value {
+					let detail = format!("{} -> {}", old.constant_value.as_deref().unwrap_or_default(), new.constant_value.as_deref().unwrap_or("not constant"));
+					self.report(ProblemKind::ConstantValueChanged, class_name, Some(label), Some(detail));
+				}
+			}
+			MemberKind::Method => {
+				if subclassable && added(MethodAccessPropertyFlags::Final) {
+					self.report(ProblemKind::MethodMadeFinal, class_name, Some(label.clone()), None);
+				}
+				if added(MethodAccessPropertyFlags::Abstract) {
+					self.report(ProblemKind::MethodMadeAbstract, class_name, Some(label), None);
+				}
+			}
+		}
+	}
+
+	pub fn to_json(&self) -> Json {
+		Json::object([
+			("classes_checked", Json::from(self.classes_checked)),
+			("compatible", Json::from(self.is_compatible())),
+			("problems", Json::Array(self.problems.iter().map(|problem| Json::object([
+				("kind", Json::from(problem.kind.to_string())),
+				("class", Json::from(&problem.class_name)),
+				("member", Json::from(problem.member.as_ref())),
+				("detail", Json::from(problem.detail.as_ref())),
+			])).collect())),
+		])
+	}
+}
+
+impl Display for CompatibilityReport {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{} classes checked, {} breaking changes", self.classes_checked, self.problems.len())?;
+		for problem in &self.problems {
+			writeln!(f, "{}", problem)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+
+	use crate::analysis::compatibility::{Access, CompatibilityReport, ProblemKind};
+
+	const RESOURCES: &str = "tests/resources/compat";
+
+	fn get_report(old: &str, new: &str) -> CompatibilityReport {
+		CompatibilityReport::from_paths(&Path::new(RESOURCES).join(old), &Path::new(RESOURCES).join(new)).expect("Couldn't read classes")
+	}
+
+	#[test]
+	fn test_same_version() {
+		let report = get_report("old", "old");
+		assert_eq!(report.classes_checked, 6);
+		assert!(report.is_compatible());
+		assert_eq!(report.to_string(), "6 classes checked, 0 breaking changes\n");
+	}
+
+	#[test]
+	fn test_breaking_changes() {
+		let report = get_report("old", "new");
+		let problems: Vec<String> = report.problems.iter().map(ToString::to_string).collect();
+		assert_eq!(problems, vec![
+			"constant-value-changed lib/Api.LIMIT:I: 10 -> 20",
+			"field-type-changed lib/Api.size:I: I -> J",
+			"field-made-final lib/Api.label:Ljava/lang/String;",
+			"access-narrowed lib/Api.stamp:J: protected -> package",
+			"descriptor-changed lib/Api.resize(I)V: (I)V -> (J)V",
+			"static-changed lib/Api.count()I: now static",
+			"method-removed lib/Api.close()V",
+			"method-made-final lib/Api.hook()V",
+			"class-made-abstract lib/Factory",
+			"method-made-abstract lib/Factory.make()V",
+			"class-removed lib/Gone",
+			"class-kind-changed lib/Shape: class -> interface",
+			"class-made-final lib/Widget",
+			"superclass-removed lib/Widget: lib/Base",
+			"interface-removed lib/Widget: java/lang/Runnable",
+		]);
+		// Api.inherited() moved to Base, and Api.internal() and Hidden were never API
+		assert!(!problems.iter().any(|problem| problem.contains("inherited") || problem.contains("internal") || problem.contains("Hidden")));
+	}
+
+	#[test]
+	fn test_reversed() {
+		let report = get_report("new", "old");
+		let kinds: Vec<ProblemKind> = report.problems.iter().map(|problem| problem.kind).collect();
+		// widening access, removing final and dropping constants the old version never had are fine
+		assert!(!kinds.contains(&ProblemKind::AccessNarrowed));
+		assert!(!kinds.contains(&ProblemKind::ClassMadeFinal));
+		assert!(report.problems.iter().any(|problem| problem.kind == ProblemKind::MethodRemoved && problem.member.as_deref() == Some("extra()V")));
+		assert!(report.problems.iter().any(|problem| problem.kind == ProblemKind::StaticChanged && problem.detail.as_deref() == Some("no longer static")));
+	}
+
+	#[test]
+	fn test_access() {
+		assert_eq!(Access::of(0x0001), Access::Public);
+		assert_eq!(Access::of(0x0004 | 0x0008), Access::Protected);
+		assert_eq!(Access::of(0x0000), Access::Package);
+		assert!(Access::of(0x0002) < Access::Package);
+	}
+
+	#[test]
+	fn test_json() {
+		let json = get_report("old", "new").to_json().to_string();
+		assert!(json.starts_with("{\"classes_checked\":6,\"compatible\":false,"));
+		assert!(json.contains("{\"kind\":\"class-removed\",\"class\":\"lib/Gone\",\"member\":null,\"detail\":null}"));
+	}
+}
@@ -0,0 +1,399 @@
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::{self, Display, Formatter, Write},
+	path::Path};
+
+use strum_macros::Display;
+
+use crate::{
+	analysis::{hierarchy::package_of, json::Json},
+	class::{
+		archive::{self, ArchiveError},
+		attribute::{Annotation, Attribute, AttributeInfo, ElementValue},
+		class::Class,
+		constant_pool::ConstantPoolItem,
+		signature},
+};
+
+/// The archive JDK classes are attributed to when the JDK itself was not analysed.
+pub const JDK_ARCHIVE: &str = "JDK";
+
+/// The archive of classes that are neither analysed nor part of the JDK.
+pub const NOT_FOUND: &str = "not found";
+
+/// Package prefixes of the JDK's own namespaces.
+const JDK_PACKAGES: [&str; 6] = ["java/", "javax/", "jdk/", "sun/", "com/sun/", "org/w3c/dom/"];
+
+/// Packages of the JDK that are not exported for use by applications. The exported exceptions
+/// below are checked first.
+const JDK_INTERNAL_PACKAGES: [&str; 3] = ["sun/", "com/sun/", "jdk/internal/"];
+
+/// Exported packages under the internal prefixes above.
+const JDK_EXPORTED_PACKAGES: [&str; 8] = [
+	"com/sun/jdi/",
+	"com/sun/management/",
+	"com/sun/net/httpserver/",
+	"com/sun/nio/sctp/",
+	"com/sun/security/auth/",
+	"com/sun/security/jgss/",
+	"com/sun/source/",
+	"com/sun/tools/attach/",
+];
+
+/// Whether a class belongs to the JDK's own namespaces.
+pub fn is_jdk_class(class_name: &str) -> bool {
+	JDK_PACKAGES.iter().any(|prefix| class_name.starts_with(prefix))
+}
+
+/// Whether a class belongs to a JDK package that is not exported to applications, such as
+/// sun/misc or jdk/internal/misc.
+pub fn is_jdk_internal(class_name: &str) -> bool {
+	!JDK_EXPORTED_PACKAGES.iter().any(|prefix| class_name.starts_with(prefix)) &&
+		JDK_INTERNAL_PACKAGES.iter().any(|prefix| class_name.starts_with(prefix))
+}
+
+/// How finely a `DependencyGraph` groups classes.
+#[derive(Clone, Copy, Debug, Display, Eq, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum Granularity {
+	Class,
+	Package,
+	Archive,
+}
+
+/// The internal names of the classes a descriptor or signature mentions, in order of appearance.
+/// Nested classes of a parameterized type are named with `$`.
+pub fn signature_classes(signature: &str) -> Vec<String> {
+	let mut classes = Vec::new();
+	signature::map_classes(signature, |class_name| {
+		classes.push(class_name.to_string());
+		class_name.to_string()
+	});
+	classes
+}
+
+/// Every class a class refers to, other than itself: constant pool class entries (which cover
+/// member references, exception handlers, bootstrap method handles and so on), descriptors of
+/// members and call sites, generic signatures and annotations.
+pub fn referenced_classes(class: &Class) -> BTreeSet<String> {
+	let mut classes: BTreeSet<String> = BTreeSet::new();
+	let add_signature = |classes: &mut BTreeSet<String>, index: u16| {
+		if let Some(signature) = class.get_utf8(index) {
+			classes.extend(signature_classes(&signature));
+		}
+	};
+	for item in class.constant_pool.values() {
+		match item {
+			ConstantPoolItem::Class(info) => if let Some(name) = class.get_utf8(info.index) {
+				if name.starts_with('[') {
+					classes.extend(signature_classes(&name));
+				} else {
+					classes.insert(name);
+				}
+			},
+			ConstantPoolItem::NameAndType(name_and_type) => add_signature(&mut classes, name_and_type.type_index),
+			ConstantPoolItem::MethodType(method_type) => add_signature(&mut classes, method_type.descriptor_index),
+			_ => {}
+		}
+	}
+	for field in &class.fields.fields {
+		add_signature(&mut classes, field.descriptor_index);
+		attribute_classes(class, &field.attributes, &mut classes);
+	}
+	for method in &class.methods.methods {
+		add_signature(&mut classes, method.descriptor_index);
+		attribute_classes(class, &method.attributes, &mut classes);
+	}
+	attribute_classes(class, &class.attributes.attributes, &mut classes);
+	classes.remove(&class.name());
+	classes
+}
+
+fn attribute_classes(class: &Class, attributes: &[Attribute], classes: &mut BTreeSet<String>) {
+	let annotations = |annotations: &[Annotation], classes: &mut BTreeSet<String>| for annotation in annotations {
+		annotation_classes(class, annotation, classes);
+	};
+	for attribute in attributes {
+		match &attribute.attribute_info {
+			AttributeInfo::Signature(signature) => if let Some(signature) = class.get_utf8(signature.signature_index) {
+				classes.extend(signature_classes(&signature));
+			},
+			AttributeInfo::RuntimeVisibleAnnotations(list) | AttributeInfo::RuntimeInvisibleAnnotations(list) =>
+				annotations(&list.annotations, classes),
+			AttributeInfo::RuntimeVisibleParameterAnnotations(parameters) | AttributeInfo::RuntimeInvisibleParameterAnnotations(parameters) =>
+				for list in &parameters.parameter_annotations {
+					annotations(&list.annotations, classes);
+				},
+			AttributeInfo::AnnotationDefault(value) => element_value_classes(class, value, classes),
+			AttributeInfo::Code(code) => attribute_classes(class, &code.attributes, classes),
+			_ => {}
+		}
+	}
+}
+
+fn annotation_classes(class: &Class, annotation: &Annotation, classes: &mut BTreeSet<String>) {
+	if let Some(descriptor) = class.get_utf8(annotation.type_index) {
+		classes.extend(signature_classes(&descriptor));
+	}
+	for pair in &annotation.element_value_pairs {
+		element_value_classes(class, &pair.value, classes);
+	}
+}
+
+fn element_value_classes(class: &Class, value: &ElementValue, classes: &mut BTreeSet<String>) {
+	let descriptor = match value {
+		ElementValue::Enum { type_name_index, .. } => *type_name_index,
+		ElementValue::Class { class_info_index } => *class_info_index,
+		ElementValue::Annotation(annotation) => return annotation_classes(class, annotation, classes),
+		ElementValue::Array { values, .. } => {
+			for value in values {
+				element_value_classes(class, value, classes);
+			}
+			return;
+		}
+		_ => return,
+	};
+	if let Some(descriptor) = class.get_utf8(descriptor) {
+		classes.extend(signature_classes(&descriptor));
+	}
+}
+
+/// The class-level dependencies of a set of classes, each attributed to the archive (JAR or
+/// directory) it was read from.
+#[derive(Debug, Default)]
+pub struct DependencyAnalysis {
+	archives: BTreeMap<String, String>,
+	references: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// A dependency between two classes, packages or archives. `jdk_internal` is set when any of the
+/// class references behind it names a JDK-internal class.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Dependency {
+	pub from: String,
+	pub to: String,
+	pub jdk_internal: bool,
+}
+
+/// The dependencies of a `DependencyAnalysis` grouped at one granularity. Nodes are the analysed
+/// classes, packages or archives; edges to the same node are left out.
+#[derive(Debug)]
+pub struct DependencyGraph {
+	pub granularity: Granularity,
+	pub nodes: BTreeSet<String>,
+	pub edges: Vec<Dependency>,
+}
+
+impl DependencyAnalysis {
+
+	pub fn new() -> DependencyAnalysis {
+		DependencyAnalysis::default()
+	}
+
+	/// Add a class read from `archive`. As on a class path, the first class added under a name
+	/// wins; returns whether the class was added.
+	pub fn add_class(&mut self, class: &Class, archive: &str) -> bool {
+		let name = class.name();
+		if self.archives.contains_key(&name) {
+			return false;
+		}
+		self.references.insert(name.clone(), referenced_classes(class));
+		self.archives.insert(name, archive.to_string());
+		true
+	}
+
+	/// Add every class in a directory or JAR, naming the archive after the last path component.
+	pub fn add_path(&mut self, path: &Path) -> Result<(), ArchiveError> {
+		let archive = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| path.display().to_string());
+		for class in archive::read_classes(path)? {
+			self.add_class(&class, &archive);
+		}
+		Ok(())
+	}
+
+	pub fn classes(&self) -> impl Iterator<Item = &String> {
+		self.references.keys()
+	}
+
+	/// The classes an analysed class refers to, or None if it wasn't analysed.
+	pub fn references(&self, class_name: &str) -> Option<&BTreeSet<String>> {
+		self.references.get(class_name)
+	}
+
+	/// The archive a class was read from; `JDK_ARCHIVE` or `NOT_FOUND` for classes not analysed.
+	pub fn archive_of(&self, class_name: &str) -> &str {
+		match self.archives.get(class_name) {
+			Some(archive) => archive,
+			None if is_jdk_class(class_name) => JDK_ARCHIVE,
+			None => NOT_FOUND,
+		}
+	}
+
+	/// Every reference from an analysed class to a JDK-internal class, as (from, to) pairs.
+	pub fn jdk_internal_references(&self) -> Vec<(&str, &str)> {
+		self.references.iter()
+			.flat_map(|(from, references)| references.iter().map(move |to| (from.as_str(), to.as_str())))
+			.filter(|(_, to)| is_jdk_internal(to))
+			.collect()
+	}
+
+	fn group<'a>(&'a self, class_name: &'a str, granularity: Granularity) -> &'a str {
+		match granularity {
+			Granularity::Class => class_name,
+			Granularity::Package => package_of(class_name),
+			Granularity::Archive => self.archive_of(class_name),
+		}
+	}
+
+	pub fn graph(&self, granularity: Granularity) -> DependencyGraph {
+		let mut nodes: BTreeSet<String> = BTreeSet::new();
+		let mut edges: BTreeMap<(&str, &str), bool> = BTreeMap::new();
+		for (from, references) in &self.references {
+			let from_group = self.group(from, granularity);
+			nodes.insert(from_group.to_string());
+			for to in references {
+				let to_group = self.group(to, granularity);
+				if from_group != to_group {
+					*edges.entry((from_group, to_group)).or_default() |= is_jdk_internal(to);
+				}
+			}
+		}
+		let edges = edges.into_iter()
+			.map(|((from, to), jdk_internal)| Dependency { from: from.to_string(), to: to.to_string(), jdk_internal })
+			.collect();
+		DependencyGraph { granularity, nodes, edges }
+	}
+}
+
+fn node_label(name: &str) -> &str {
+	if name.is_empty() { "<unnamed>" } else { name }
+}
+
+impl DependencyGraph {
+
+	/// The dependencies of one node.
+	pub fn dependencies<'a>(&'a self, from: &'a str) -> impl Iterator<Item = &'a Dependency> {
+		self.edges.iter().filter(move |dependency| dependency.from == from)
+	}
+
+	/// Render in Graphviz DOT format. Analysed nodes are boxes and references to JDK-internal
+	/// classes are drawn in red.
+	pub fn to_dot(&self) -> String {
+		let mut names: BTreeSet<&str> = self.nodes.iter().map(String::as_str).collect();
+		names.extend(self.edges.iter().map(|dependency| dependency.to.as_str()));
+		let ids: BTreeMap<&str, usize> = names.into_iter().enumerate().map(|(id, name)| (name, id)).collect();
+		let mut dot = String::from("digraph dependencies {\n");
+		for (name, id) in &ids {
+			let shape = if self.nodes.contains(*name) { ", shape=box" } else { "" };
+			let _ = writeln!(dot, "\tn{} [label={}{}];", id, Json::from(node_label(name)), shape);
+		}
+		for dependency in &self.edges {
+			let colour = if dependency.jdk_internal { " [color=red]" } else { "" };
+			let _ = writeln!(dot, "\tn{} -> n{}{};", ids[dependency.from.as_str()], ids[dependency.to.as_str()], colour);
+		}
+		dot.push_str("}\n");
+		dot
+	}
+
+	pub fn to_json(&self) -> Json {
+		Json::object([
+			("granularity", Json::from(self.granularity.to_string())),
+			("nodes", Json::array(&self.nodes)),
+			("edges", Json::Array(self.edges.iter().map(|dependency| Json::object([
+				("from", Json::from(&dependency.from)),
+				("to", Json::from(&dependency.to)),
+				("jdk_internal", Json::from(dependency.jdk_internal)),
+			])).collect())),
+		])
+	}
+}
+
+impl Display for DependencyGraph {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		for node in &self.nodes {
+			writeln!(f, "{}", node_label(node))?;
+			for dependency in self.dependencies(node) {
+				let internal = if dependency.jdk_internal { " (JDK internal)" } else { "" };
+				writeln!(f, "   -> {}{}", node_label(&dependency.to), internal)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+
+	use crate::analysis::dependencies::{is_jdk_internal, signature_classes, Dependency, DependencyAnalysis, Granularity};
+
+	const RESOURCES: &str = "tests/resources/deps";
+
+	fn get_analysis() -> DependencyAnalysis {
+		let mut analysis = DependencyAnalysis::new();
+		for archive in ["core", "app"] {
+			analysis.add_path(&Path::new(RESOURCES).join(archive)).expect("Couldn't read classes");
+		}
+		analysis
+	}
+
+	fn dependency(from: &str, to: &str, jdk_internal: bool) -> Dependency {
+		Dependency { from: from.to_string(), to: to.to_string(), jdk_internal }
+	}
+
+	#[test]
+	fn test_signature_classes() {
+		assert_eq!(signature_classes("([Ljava/lang/String;IJ)V"), vec!["java/lang/String"]);
+		assert_eq!(signature_classes("[[I"), Vec::<String>::new());
+		assert_eq!(signature_classes("<T:Ljava/lang/Object;K::Ljava/lang/Comparable<-TK;>;>Ljava/util/AbstractMap<TK;TT;>;Ljava/io/Serializable;"),
+			vec!["java/lang/Object", "java/lang/Comparable", "java/util/AbstractMap", "java/io/Serializable"]);
+		assert_eq!(signature_classes("<E:Ljava/lang/Exception;>(Ljava/util/List<+Lmodel/Item;>;Ljava/util/Map<**>;)TE;^TE;^Ljava/io/IOException;"),
+			vec!["java/lang/Exception", "java/util/List", "model/Item", "java/util/Map", "java/io/IOException"]);
+		assert_eq!(signature_classes("Lcom/example/Outer<Ljava/lang/String;>.Inner<[Lmodel/Tag;>;"),
+			vec!["com/example/Outer", "java/lang/String", "com/example/Outer$Inner", "model/Tag"]);
+	}
+
+	#[test]
+	fn test_referenced_classes() {
+		let analysis = get_analysis();
+		let references = analysis.references("app/Main").unwrap();
+		// a generic signature, an annotation type, an annotation class value and a bootstrap argument
+		for class_name in ["model/Tag", "model/Marker", "model/Kind", "model/Item", "service/Service", "sun/misc/Unsafe", "java/lang/invoke/LambdaMetafactory"] {
+			assert!(references.contains(class_name), "{} missing", class_name);
+		}
+		assert!(!references.contains("app/Main"));
+		assert_eq!(analysis.references("service/Service").unwrap().iter().collect::<Vec<_>>(), vec!["java/lang/Object", "java/lang/String", "model/Item"]);
+		assert!(analysis.references("java/lang/String").is_none());
+	}
+
+	#[test]
+	fn test_package_graph() {
+		let graph = get_analysis().graph(Granularity::Package);
+		assert_eq!(graph.nodes.iter().collect::<Vec<_>>(), vec!["app", "model", "service"]);
+		let service: Vec<&Dependency> = graph.dependencies("service").collect();
+		assert_eq!(service, vec![&dependency("service", "java/lang", false), &dependency("service", "model", false)]);
+		assert!(graph.edges.contains(&dependency("app", "sun/misc", true)));
+		assert!(!graph.edges.iter().any(|dependency| dependency.from == dependency.to));
+	}
+
+	#[test]
+	fn test_archive_graph() {
+		let analysis = get_analysis();
+		assert_eq!(analysis.archive_of("model/Item"), "core");
+		assert_eq!(analysis.archive_of("java/util/List"), "JDK");
+		assert_eq!(analysis.archive_of("org/example/Missing"), "not found");
+		let graph = analysis.graph(Granularity::Archive);
+		assert_eq!(graph.edges, vec![dependency("app", "JDK", true), dependency("app", "core", false), dependency("core", "JDK", false)]);
+		assert_eq!(graph.to_dot(), "digraph dependencies {\n\tn0 [label=\"JDK\"];\n\tn1 [label=\"app\", shape=box];\n\tn2 [label=\"core\", shape=box];\n\tn1 -> n0 [color=red];\n\tn1 -> n2;\n\tn2 -> n0;\n}\n");
+		assert!(graph.to_json().to_string().starts_with("{\"granularity\":\"archive\",\"nodes\":[\"app\",\"core\"],\"edges\":[{\"from\":\"app\",\"to\":\"JDK\",\"jdk_internal\":true}"));
+	}
+
+	#[test]
+	fn test_jdk_internals() {
+		assert!(is_jdk_internal("sun/misc/Unsafe"));
+		assert!(is_jdk_internal("jdk/internal/misc/VM"));
+		assert!(!is_jdk_internal("com/sun/net/httpserver/HttpServer"));
+		assert!(!is_jdk_internal("java/lang/Object"));
+		assert_eq!(get_analysis().jdk_internal_references(), vec![("app/Main", "sun/misc/Unsafe")]);
+	}
+}
@@ -0,0 +1,588 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	fmt::{self, Display, Formatter}};
+
+use strum::IntoEnumIterator;
+use strum_macros::Display;
+
+use crate::{
+	analysis::json::Json,
+	class::{
+		access::{FieldAccessPropertyFlags, MethodAccessPropertyFlags},
+		attribute::{Annotation, Attribute, AttributeInfo, ElementValue},
+		class::Class},
+	error::DecodeError,
+	isa::{
+		instruction::{self, Instruction},
+		opcode::Opcode},
+};
+
+/// Above this many cells the instruction diff gives up on aligning the changed middle of a method
+/// and reports it as replaced wholesale.
+const MAX_ALIGNMENT_CELLS: usize = 16_000_000;
+
+/// A property of a class, field or method that differs between the two versions. Multi-valued
+/// properties (interfaces, annotations, exception handlers, ...) report each value added or
+/// removed on its own, with the other side `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyChange {
+	pub property: String,
+	pub old: Option<String>,
+	pub new: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Display, Eq, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum Status {
+	Added,
+	Removed,
+	Changed,
+}
+
+/// An instruction found in only one version of a method, at `index` among that version's instructions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstructionEdit {
+	/// `Added` or `Removed`.
+	pub status: Status,
+	pub index: usize,
+	pub instruction: String,
+}
+
+/// A field or method, identified by name and descriptor, that differs between the two versions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemberDiff {
+	pub name: String,
+	pub descriptor: String,
+	pub status: Status,
+	pub changes: Vec<PropertyChange>,
+	/// Instruction edits turning the old code into the new, for changed methods.
+	pub code: Vec<InstructionEdit>,
+}
+
+/// The semantic differences between two versions of a class.
+///
+/// Everything is compared by what constant pool entries resolve to rather than by index, so
+/// rebuilding the pool in another order makes no difference, and neither does declaring members in
+/// another order. Instructions are compared with branch targets as instruction indices and with
+/// `ldc_w`, `goto_w` and `wide` folded into their short forms, since which of them is needed depends
+/// only on layout. Line numbers, local variable tables and stack map frames are not compared.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassDiff {
+	pub class_name: String,
+	pub changes: Vec<PropertyChange>,
+	pub fields: Vec<MemberDiff>,
+	pub methods: Vec<MemberDiff>,
+}
+
+impl ClassDiff {
+
+	/// The differences between `old` and `new`, failing if the code of either cannot be decoded.
+	pub fn new(old: &Class, new: &Class) -> Result<ClassDiff, DecodeError> {
+		Ok(ClassDiff {
+			class_name: new.name(),
+			changes: compare(&class_properties(old), &class_properties(new)),
+			fields: diff_members(&fields(old)?, &fields(new)?),
+			methods: diff_members(&methods(old)?, &methods(new)?),
+		})
+	}
+
+	/// Whether the two versions are semantically the same.
+	pub fn is_empty(&self) -> bool {
+		self.changes.is_empty() && self.fields.is_empty() && self.methods.is_empty()
+	}
+
+	pub fn to_json(&self) -> Json {
+		let changes = |changes: &[PropertyChange]| Json::Array(changes.iter().map(|change| Json::object([
+			("property", Json::from(&change.property)),
+			("old", Json::from(change.old.as_ref())),
+			("new", Json::from(change.new.as_ref())),
+		])).collect());
+		let members = |members: &[MemberDiff]| Json::Array(members.iter().map(|member| Json::object([
+			("name", Json::from(&member.name)),
+			("descriptor", Json::from(&member.descriptor)),
+			("status", Json::from(member.status.to_string())),
+			("changes", changes(&member.changes)),
+			("code", Json::Array(member.code.iter().map(|edit| Json::object([
+				("status", Json::from(edit.status.to_string())),
+				("index", Json::from(edit.index)),
+				("instruction", Json::from(&edit.instruction)),
+			])).collect())),
+		])).collect());
+		Json::object([
+			("class", Json::from(&self.class_name)),
+			("changes", changes(&self.changes)),
+			("fields", members(&self.fields)),
+			("methods", members(&self.methods)),
+		])
+	}
+}
+
+impl Display for ClassDiff {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		if self.is_empty() {
+			return writeln!(f, "class {}: no differences", self.class_name);
+		}
+		writeln!(f, "class {}", self.class_name)?;
+		write_changes(f, &self.changes, "  ")?;
+		for (kind, members) in [("field", &self.fields), ("method", &self.methods)] {
+			for member in members {
+				let sign = match member.status {
+					Status::Added => '+',
+					Status::Removed => '-',
+					Status::Changed => '~',
+				};
+				match kind {
+					"field" => writeln!(f, "  {} field {} {}", sign, member.name, member.descriptor)?,
+					_ => writeln!(f, "  {} method {}{}", sign, member.name, member.descriptor)?,
+				}
+				write_changes(f, &member.changes, "      ")?;
+				for edit in &member.code {
+					let sign = if edit.status == Status::Added { '+' } else { '-' };
+					writeln!(f, "      {} {}: {}", sign, edit.index, edit.instruction)?;
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+fn write_changes(f: &mut Formatter<'_>, changes: &[PropertyChange], indent: &str) -> fmt::Result {
+	for change in changes {
+		match (&change.old, &change.new) {
+			(Some(old), Some(new)) => writeln!(f, "{}~ {}: {} -> {}", indent, change.property, old, new)?,
+			(Some(old), None) => writeln!(f, "{}- {}: {}", indent, change.property, old)?,
+			(None, Some(new)) => writeln!(f, "{}+ {}: {}", indent, change.property, new)?,
+			(None, None) => {}
+		}
+	}
+	Ok(())
+}
+
+/// A property name and a resolved value; a property may appear several times.
+type Properties = Vec<(String, String)>;
+
+/// A member with its resolved properties and, for methods with code, its rendered instructions.
+struct Member {
+	name: String,
+	descriptor: String,
+	properties: Properties,
+	code: Option<Vec<String>>,
+}
+
+fn class_properties(class: &Class) -> Properties {
+	let mut properties = vec![
+		("name".to_string(), class.name()),
+		("version".to_string(), format!("{}.{}", class.major_version, class.minor_version)),
+		("flags".to_string(), class.flags.iter().map(|flag| format!("{:?}", flag).to_lowercase()).collect::<Vec<_>>().join(" ")),
+		("super class".to_string(), class.super_class_name().unwrap_or_default()),
+	];
+	properties.extend(class.interface_names().into_iter().map(|name| ("interface".to_string(), name)));
+	properties.extend(attribute_properties(class, &class.attributes.attributes));
+	properties
+}
+
+fn field_flags(flags: u16) -> String {
+	FieldAccessPropertyFlags::iter()
+		.filter(|flag| flags & *flag as u16 != 0)
+		.map(|flag| format!("{:?}", flag).to_lowercase())
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+fn method_flags(flags: u16) -> String {
+	MethodAccessPropertyFlags::iter()
+		.filter(|flag| flags & *flag as u16 != 0)
+		.map(|flag| format!("{:?}", flag).to_lowercase())
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+fn fields(class: &Class) -> Result<Vec<Member>, DecodeError> {
+	class.fields.fields.iter()
+		.map(|field| member(class, field.name_index, field.descriptor_index, field_flags(field.access_flags), &field.attributes))
+		.collect()
+}
+
+fn methods(class: &Class) -> Result<Vec<Member>, DecodeError> {
+	class.methods.methods.iter()
+		.map(|method| member(class, method.name_index, method.descriptor_index, method_flags(method.access_flags), &method.attributes))
+		.collect()
+}
+
+fn member(class: &Class, name_index: u16, descriptor_index: u16, flags: String, attributes: &[Attribute]) -> Result<Member, DecodeError> {
+	let mut properties = vec![("flags".to_string(), flags)];
+	properties.extend(attribute_properties(class, attributes));
+	let mut code = None;
+	for attribute in attributes {
+		if let AttributeInfo::Code(body) = &attribute.attribute_info {
+			let instructions = instruction::decode(&body.code)?;
+			let indices: HashMap<u32, usize> = instructions.iter().enumerate().map(|(index, (pc, _))| (*pc, index)).collect();
+			let at = |pc: u16| indices.get(&u32::from(pc)).map_or("@end".to_string(), |index| format!("@{}", index));
+			properties.push(("max stack".to_string(), body.max_stack.to_string()));
+			properties.push(("max locals".to_string(), body.max_locals.to_string()));
+			for handler in &body.handlers {
+				let catch_type = match handler.catch_type_index {
+					0 => "any".to_string(),
+					index => class.render_constant(index),
+				};
+				properties.push(("exception handler".to_string(),
+					format!("{}..{} -> {} catch {}", at(handler.start_pc), at(handler.end_pc), at(handler.handler_pc), catch_type)));
+			}
+			properties.extend(attribute_properties(class, &body.attributes));
+			code = Some(render_instructions(class, &instructions));
+		}
+	}
+	Ok(Member {
+		name: class.get_utf8(name_index).unwrap_or_default(),
+		descriptor: class.get_utf8(descriptor_index).unwrap_or_default(),
+		properties,
+		code,
+	})
+}
+
+fn diff_members(old: &[Member], new: &[Member]) -> Vec<MemberDiff> {
+	let key = |member: &Member| (member.name.clone(), member.descriptor.clone());
+	let new_members: HashMap<(String, String), &Member> = new.iter().map(|member| (key(member), member)).collect();
+	let old_keys: Vec<(String, String)> = old.iter().map(key).collect();
+	let mut diffs = Vec::new();
+	for member in old {
+		let status = match new_members.get(&key(member)) {
+			None => Some((Status::Removed, vec![], vec![])),
+			Some(other) => {
+				let changes = compare(&member.properties, &other.properties);
+				let code = match (&member.code, &other.code) {
+					(Some(old_code), Some(new_code)) => diff_instructions(old_code, new_code),
+					_ => vec![],
+				};
+				(!changes.is_empty() || !code.is_empty() || member.code.is_some() != other.code.is_some())
+					.then_some((Status::Changed, changes, code))
+			}
+		};
+		if let Some((status, changes, code)) = status {
+			diffs.push(MemberDiff { name: member.name.clone(), descriptor: member.descriptor.clone(), status, changes, code });
+		}
+	}
+	for member in new {
+		if !old_keys.contains(&key(member)) {
+			diffs.push(MemberDiff { name: member.name.clone(), descriptor: member.descriptor.clone(), status: Status::Added, changes: vec![], code: vec![] });
+		}
+	}
+	diffs
+}
+
+/// Compare property lists, pairing up a single removed and a single added value of a property as
+/// one change.
+fn compare(old: &Properties, new: &Properties) -> Vec<PropertyChange> {
+	let mut order: Vec<&String> = Vec::new();
+	let mut values: BTreeMap<&String, (Vec<&String>, Vec<&String>)> = BTreeMap::new();
+	for (property, value) in old {
+		if !order.contains(&property) {
+			order.push(property);
+		}
+		values.entry(property).or_default().0.push(value);
+	}
+	for (property, value) in new {
+		if !order.contains(&property) {
+			order.push(property);
+		}
+		values.entry(property).or_default().1.push(value);
+	}
+	let mut changes = Vec::new();
+	for property in order {
+		let (old_values, new_values) = &values[property];
+		let mut removed: Vec<&String> = old_values.clone();
+		let mut added: Vec<&String> = Vec::new();
+		for value in new_values {
+			match removed.iter().position(|candidate| candidate == value) {
+				Some(position) => { removed.remove(position); }
+				None => added.push(value),
+			}
+		}
+		if removed.len() == 1 && added.len() == 1 {
+			changes.push(PropertyChange { property: property.clone(), old: Some(removed[0].clone()), new: Some(added[0].clone()) });
+			continue;
+		}
+		changes.extend(removed.into_iter().map(|value| PropertyChange { property: property.clone(), old: Some(value.clone()), new: None }));
+		changes.extend(added.into_iter().map(|value| PropertyChange { property: property.clone(), old: None, new: Some(value.clone()) }));
+	}
+	changes
+}
+
+/// The edits of a longest common subsequence alignment of two instruction listings.
+fn diff_instructions(old: &[String], new: &[String]) -> Vec<InstructionEdit> {
+	let prefix = old.iter().zip(new.iter()).take_while(|(a, b)| a == b).count();
+	let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+	let old_middle = &old[prefix..old.len() - suffix];
+	let new_middle = &new[prefix..new.len() - suffix];
+	let removed = |index: usize| InstructionEdit { status: Status::Removed, index: prefix + index, instruction: old_middle[index].clone() };
+	let added = |index: usize| InstructionEdit { status: Status::Added, index: prefix + index, instruction: new_middle[index].clone() };
+
+	let (n, m) = (old_middle.len(), new_middle.len());
+	if n * m > MAX_ALIGNMENT_CELLS {
+		return (0..n).map(removed).chain((0..m).map(added)).collect();
+	}
+	// lengths[i][j] is the length of the longest common subsequence of old_middle[i..] and new_middle[j..]
+	let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+	for i in (0..n).rev() {
+		for j in (0..m).rev() {
+			lengths[i][j] = if old_middle[i] == new_middle[j] {
+				lengths[i + 1][j + 1] + 1
+			} else {
+				lengths[i + 1][j].max(lengths[i][j + 1])
+			};
+		}
+	}
+	let mut edits = Vec::new();
+	let (mut i, mut j) = (0, 0);
+	while i < n || j < m {
+		if i < n && j < m && old_middle[i] == new_middle[j] {
+			i += 1;
+			j += 1;
+		} else if j == m || (i < n && lengths[i + 1][j] >= lengths[i][j + 1]) {
+			edits.push(removed(i));
+			i += 1;
+		} else {
+			edits.push(added(j));
+			j += 1;
+		}
+	}
+	edits
+}
+
+/// Resolve the attributes of a class, field, method or Code attribute to properties. Code is left
+/// to the caller; BootstrapMethods are compared through the `invokedynamic` instructions using them.
+fn attribute_properties(class: &Class, attributes: &[Attribute]) -> Properties {
+	let mut properties = Vec::new();
+	let mut push = |property: &str, value: String| properties.push((property.to_string(), value));
+	for attribute in attributes {
+		match &attribute.attribute_info {
+			AttributeInfo::Code(_) | AttributeInfo::BootstrapMethods(_) | AttributeInfo::StackMapTable(_) |
+			AttributeInfo::LineNumberTable(_) | AttributeInfo::LocalVariableTable(_) | AttributeInfo::LocalVariableTypeTable(_) => {}
+			AttributeInfo::AnnotationDefault(value) => push("annotation default", render_element_value(class, value)),
+			AttributeInfo::ConstantValue(value) => push("constant value", class.render_constant(value.constant_value_index)),
+			AttributeInfo::EnclosingMethod(enclosing) => push("enclosing method", match class.get_name_and_type(enclosing.method_index) {
+				Some((name, descriptor)) => format!("{}.{}{}", class.render_constant(enclosing.class_index), name, descriptor),
+				None => class.render_constant(enclosing.class_index),
+			}),
+			AttributeInfo::InnerClasses(inner_classes) => for inner in &inner_classes.classes {
+				let outer = match inner.outer_class_info_index {
+					0 => String::new(),
+					index => format!(" in {}", class.render_constant(index)),
+				};
+				push("inner class", format!("{}{} flags {:#06x}", class.render_constant(inner.inner_class_info_index), outer, inner.inner_class_access_flags));
+			},
+			AttributeInfo::NestHost(host) => push("nest host", class.render_constant(host.host_class_index)),
+			AttributeInfo::NestMembers(members) => for index in &members.classes {
+				push("nest member", class.render_constant(*index));
+			},
+			AttributeInfo::PermittedSubclasses(subclasses) => for index in &subclasses.classes {
+				push("permitted subclass", class.render_constant(*index));
+			},
+			AttributeInfo::RuntimeVisibleAnnotations(annotations) => for annotation in &annotations.annotations {
+				push("annotation", render_annotation(class, annotation));
+			},
+			AttributeInfo::RuntimeInvisibleAnnotations(annotations) => for annotation in &annotations.annotations {
+				push("invisible annotation", render_annotation(class, annotation));
+			},
+			AttributeInfo::RuntimeVisibleParameterAnnotations(parameters) | AttributeInfo::RuntimeInvisibleParameterAnnotations(parameters) => {
+				let visibility = if matches!(attribute.attribute_info, AttributeInfo::RuntimeVisibleParameterAnnotations(_)) { "" } else { "invisible " };
+				for (parameter, annotations) in parameters.parameter_annotations.iter().enumerate() {
+					for annotation in &annotations.annotations {
+						push(&format!("parameter {} {}annotation", parameter, visibility), render_annotation(class, annotation));
+					}
+				}
+			}
+			AttributeInfo::Signature(signature) => push("signature", class.get_utf8(signature.signature_index).unwrap_or_default()),
+			AttributeInfo::SourceFile(source_file) => push("source file", class.get_utf8(source_file.source_file_index).unwrap_or_default()),
+			AttributeInfo::UnrecognisedAttribute(unrecognised) => {
+				let name = String::from_utf8_lossy(&unrecognised.attribute_name).to_string();
+				let u2 = |offset: usize| unrecognised.info.get(offset..offset + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+				match name.as_str() {
+					"Deprecated" | "Synthetic" => push(&name.to_lowercase(), "yes".to_string()),
+					"Exceptions" => for index in (0..u2(0).unwrap_or(0) as usize).filter_map(|entry| u2(2 + 2 * entry)) {
+						push("throws", class.render_constant(index));
+					},
+					"SourceDebugExtension" => push("source debug extension", String::from_utf8_lossy(&unrecognised.info).to_string()),
+					// other attributes may hold constant pool indices, so only their presence and size compare
+					_ => push(&name, format!("{} bytes", unrecognised.info.len())),
+				}
+			}
+		}
+	}
+	properties
+}
+
+fn render_annotation(class: &Class, annotation: &Annotation) -> String {
+	let type_name = class.get_utf8(annotation.type_index).unwrap_or_default();
+	let type_name = type_name.strip_prefix('L').and_then(|name| name.strip_suffix(';')).unwrap_or(&type_name);
+	if annotation.element_value_pairs.is_empty() {
+		return format!("@{}", type_name);
+	}
+	let pairs: Vec<String> = annotation.element_value_pairs.iter()
+		.map(|pair| format!("{}={}", class.get_utf8(pair.element_name_index).unwrap_or_default(), render_element_value(class, &pair.value)))
+		.collect();
+	format!("@{}({})", type_name, pairs.join(", "))
+}
+
+fn render_element_value(class: &Class, value: &ElementValue) -> String {
+	match value {
+		ElementValue::Byte { const_value_index } | ElementValue::Char { const_value_index } |
+		ElementValue::Double { const_value_index } | ElementValue::Float { const_value_index } |
+		ElementValue::Int { const_value_index } | ElementValue::Long { const_value_index } |
+		ElementValue::Short { const_value_index } | ElementValue::Boolean { const_value_index } => class.render_constant(*const_value_index),
+		ElementValue::String { const_value_index } => format!("{:?}", class.get_utf8(*const_value_index).unwrap_or_default()),
+		ElementValue::Enum { type_name_index, const_name_index } => format!("{}.{}",
+			class.get_utf8(*type_name_index).unwrap_or_default(), class.get_utf8(*const_name_index).unwrap_or_default()),
+		ElementValue::Class { class_info_index } => format!("{}.class", class.get_utf8(*class_info_index).unwrap_or_default()),
+		ElementValue::Annotation(annotation) => render_annotation(class, annotation),
+		ElementValue::Array { values, .. } => format!("{{{}}}", values.iter().map(|value| render_element_value(class, value)).collect::<Vec<_>>().join(", ")),
+	}
+}
+
+/// Render decoded instructions with resolved operands and branch targets as instruction indices.
+///
+/// `ldc_w`, `goto_w`, `jsr_w` and `wide` are shown as their short forms, since which is used only
+/// depends on how large the constant pool or method is.
+pub fn render_instructions(class: &Class, instructions: &[(u32, Instruction)]) -> Vec<String> {
+	let indices: HashMap<u32, usize> = instructions.iter().enumerate().map(|(index, (pc, _))| (*pc, index)).collect();
+	let target = |pc: &u32| indices.get(pc).map_or(format!("@pc{}", pc), |index| format!("@{}", index));
+	instructions.iter().map(|(_, instruction)| match instruction {
+		Instruction::Branch { opcode, target: pc } => {
+			let opcode = match opcode {
+				Opcode::GotoW => Opcode::Goto,
+				Opcode::JsrW => Opcode::Jsr,
+				other => *other,
+			};
+			format!("{} {}", opcode, target(pc))
+		}
+		Instruction::TableSwitch { default, low, targets } => format!("tableswitch {{{}}} default {}",
+			targets.iter().enumerate().map(|(offset, pc)| format!("{}: {}", i64::from(*low) + offset as i64, target(pc))).collect::<Vec<_>>().join(", "),
+			target(default)),
+		Instruction::LookupSwitch { default, pairs } => format!("lookupswitch {{{}}} default {}",
+			pairs.iter().map(|(key, pc)| format!("{}: {}", key, target(pc))).collect::<Vec<_>>().join(", "),
+			target(default)),
+		Instruction::Plain { opcode: Opcode::Wide, operands } => {
+			let modified = Opcode::try_from(operands[0]).map(|opcode| opcode.to_string()).unwrap_or_default();
+			let index = u16::from_be_bytes([operands[1], operands[2]]);
+			match operands.get(3..5) {
+				Some(delta) => format!("{} {} {}", modified, index, i16::from_be_bytes([delta[0], delta[1]])),
+				None => format!("{} {}", modified, index),
+			}
+		}
+		Instruction::Plain { opcode, operands } => {
+			let opcode = if *opcode == Opcode::LdcW { Opcode::Ldc } else { *opcode };
+			match (opcode, instruction.constant_pool_index()) {
+				(Opcode::MultiANewArray, Some(index)) => format!("{} {} {}", opcode, class.render_constant(index), operands[2]),
+				(_, Some(index)) => format!("{} {}", opcode, class.render_constant(index)),
+				(Opcode::BIpush, None) => format!("{} {}", opcode, operands[0] as i8),
+				(Opcode::SIpush, None) => format!("{} {}", opcode, i16::from_be_bytes([operands[0], operands[1]])),
+				(Opcode::IInc, None) => format!("{} {} {}", opcode, operands[0], operands[1] as i8),
+				(Opcode::NewArray, None) => format!("{} {}", opcode, match operands[0] {
+					4 => "boolean",
+					5 => "char",
+					6 => "float",
+					7 => "double",
+					8 => "byte",
+					9 => "short",
+					10 => "int",
+					11 => "long",
+					_ => "unknown",
+				}),
+				(_, None) if !operands.is_empty() => format!("{} {}", opcode, operands[0]),
+				_ => opcode.to_string(),
+			}
+		}
+	}).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::File;
+
+	use crate::{
+		analysis::diff::{diff_instructions, ClassDiff, PropertyChange, Status},
+		class::class::Class,
+		isa::instruction,
+	};
+
+	const RESOURCES: &str = "tests/resources/diff";
+
+	fn get_class(version: &str) -> Class {
+		Class::new(File::open(format!("{}/{}/Widget.class", RESOURCES, version)).expect("Couldn't access class file"))
+	}
+
+	#[test]
+	fn test_reordering_is_ignored() {
+		let diff = ClassDiff::new(&get_class("old"), &get_class("reordered")).unwrap();
+		assert!(diff.is_empty(), "{}", diff);
+		assert_eq!(diff.to_string(), "class Widget: no differences\n");
+	}
+
+	#[test]
+	fn test_class_changes() {
+		let diff = ClassDiff::new(&get_class("old"), &get_class("new")).unwrap();
+		assert_eq!(diff.changes, vec![
+			PropertyChange { property: "flags".to_string(), old: Some("public super".to_string()), new: Some("public final super".to_string()) },
+			PropertyChange { property: "interface".to_string(), old: None, new: Some("java/io/Serializable".to_string()) },
+		]);
+		let fields: Vec<(&str, &str, Status)> = diff.fields.iter().map(|field| (field.name.as_str(), field.descriptor.as_str(), field.status)).collect();
+		assert_eq!(fields, vec![("count", "I", Status::Removed), ("name", "Ljava/lang/String;", Status::Changed), ("count", "J", Status::Added)]);
+		let methods: Vec<(&str, &str, Status)> = diff.methods.iter().map(|method| (method.name.as_str(), method.descriptor.as_str(), method.status)).collect();
+		assert_eq!(methods, vec![
+			("reset", "()V", Status::Changed),
+			("next", "()I", Status::Removed),
+			("label", "(I)Ljava/lang/String;", Status::Changed),
+			("next", "()J", Status::Added),
+			("thrice", "(I)I", Status::Added),
+		]);
+	}
+
+	#[test]
+	fn test_method_changes() {
+		let diff = ClassDiff::new(&get_class("old"), &get_class("new")).unwrap();
+		let reset = &diff.methods[0];
+		assert!(reset.changes.contains(&PropertyChange { property: "annotation".to_string(), old: Some("@java/lang/Deprecated".to_string()), new: None }));
+		let code: Vec<String> = reset.code.iter().map(|edit| format!("{} {}: {}", edit.status, edit.index, edit.instruction)).collect();
+		assert_eq!(code, vec![
+			"removed 1: iconst_0",
+			"removed 2: putfield Widget.count:I",
+			"added 1: lconst_0",
+			"added 2: putfield Widget.count:J",
+		]);
+		// the concatenation recipe is a bootstrap argument, resolved rather than compared by index
+		let label = &diff.methods[2];
+		assert_eq!(label.code.len(), 2);
+		assert!(label.code[1].instruction.ends_with("(\"\\u{1}=\\u{1}\")"));
+
+		let json = diff.to_json().to_string();
+		assert!(json.contains("{\"status\":\"added\",\"index\":1,\"instruction\":\"lconst_0\"}"));
+	}
+
+	#[test]
+	fn test_undecodable_code() {
+		let mut broken = get_class("new");
+		let code = broken.methods.methods.iter_mut().find_map(|method| method.code_mut()).unwrap();
+		// sipush without its operand
+		code.code.push(0x11);
+		assert!(ClassDiff::new(&get_class("old"), &broken).is_err());
+	}
+
+	#[test]
+	fn test_instruction_alignment() {
+		let lines = |text: &str| text.split(' ').map(str::to_string).collect::<Vec<_>>();
+		let edits = diff_instructions(&lines("a b c d e"), &lines("a c d x e"));
+		let edits: Vec<(Status, usize, &str)> = edits.iter().map(|edit| (edit.status, edit.index, edit.instruction.as_str())).collect();
+		assert_eq!(edits, vec![(Status::Removed, 1, "b"), (Status::Added, 3, "x")]);
+		assert!(diff_instructions(&lines("a b"), &lines("a b")).is_empty());
+	}
+
+	#[test]
+	fn test_render_instructions() {
+		let clazz = get_class("old");
+		let code = clazz.find_method("next", "()I").unwrap().code().unwrap();
+		let rendered = super::render_instructions(&clazz, &instruction::decode(&code.code).unwrap());
+		assert_eq!(rendered, vec![
+			"aload_0", "dup", "getfield Widget.count:I", "iconst_1", "iadd", "dup_x1", "putfield Widget.count:I", "ireturn",
+		]);
+	}
+}
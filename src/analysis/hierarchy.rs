@@ -0,0 +1,441 @@
+use std::{
+	collections::{BTreeSet, HashMap},
+	path::Path};
+
+use thiserror::Error;
+
+use crate::class::{
+	access::{ClassAccessPropertyFlags, MethodAccessPropertyFlags},
+	archive::{self, ArchiveError},
+	class::Class,
+	method::Method};
+
+/// The root of every class hierarchy. It is treated as present even when it has not been added.
+pub const OBJECT: &str = "java/lang/Object";
+
+/// The interfaces every array type implements (JLS17 4.10.3).
+pub const ARRAY_INTERFACES: [&str; 2] = ["java/lang/Cloneable", "java/io/Serializable"];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum HierarchyError {
+	#[error("class {0} is not in the hierarchy")]
+	MissingClass(String),
+	#[error("class {0} is its own supertype")]
+	Circularity(String),
+	#[error("incompatible class change: {0}")]
+	IncompatibleClassChange(String),
+}
+
+/// A method identified by its declaring class, name and descriptor.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MethodLocation {
+	pub class_name: String,
+	pub name: String,
+	pub descriptor: String,
+}
+
+impl MethodLocation {
+	pub fn new(class_name: &str, name: &str, descriptor: &str) -> MethodLocation {
+		MethodLocation { class_name: class_name.to_string(), name: name.to_string(), descriptor: descriptor.to_string() }
+	}
+}
+
+/// An index over a set of classes answering subtyping and method resolution questions.
+///
+/// Classes are keyed by internal name. Queries that need a class that was never added fail with
+/// `HierarchyError::MissingClass`, except for java/lang/Object which is assumed to exist.
+#[derive(Debug, Default)]
+pub struct ClassHierarchy {
+	classes: HashMap<String, Class>,
+	direct_subtypes: HashMap<String, BTreeSet<String>>,
+}
+
+fn has_flag(method: &Method, flag: MethodAccessPropertyFlags) -> bool {
+	method.access_flags & flag as u16 != 0
+}
+
+pub(crate) fn package_of(class_name: &str) -> &str {
+	class_name.rsplit_once('/').map(|(package, _)| package).unwrap_or("")
+}
+
+impl ClassHierarchy {
+
+	pub fn new() -> ClassHierarchy {
+		ClassHierarchy::default()
+	}
+
+	pub fn from_classes<I: IntoIterator<Item = Class>>(classes: I) -> ClassHierarchy {
+		let mut hierarchy = ClassHierarchy::new();
+		for class in classes {
+			hierarchy.add(class);
+		}
+		hierarchy
+	}
+
+	/// Build a hierarchy from a directory of class files or a JAR.
+	pub fn from_path(path: &Path) -> Result<ClassHierarchy, ArchiveError> {
+		Ok(ClassHierarchy::from_classes(archive::read_classes(path)?))
+	}
+
+	/// Add a class. As on a class path, the first class added under a name wins; returns whether
+	/// the class was added.
+	pub fn add(&mut self, class: Class) -> bool {
+		let name = class.name();
+		if self.classes.contains_key(&name) {
+			return false;
+		}
+		for supertype in class.super_class_name().into_iter().chain(class.interface_names()) {
+			self.direct_subtypes.entry(supertype).or_default().insert(name.clone());
+		}
+		self.classes.insert(name, class);
+		true
+	}
+
+	pub fn get(&self, name: &str) -> Option<&Class> {
+		self.classes.get(name)
+	}
+
+	pub fn contains(&self, name: &str) -> bool {
+		self.classes.contains_key(name)
+	}
+
+	pub fn class(&self, name: &str) -> Result<&Class, HierarchyError> {
+		self.classes.get(name).ok_or_else(|| HierarchyError::MissingClass(name.to_string()))
+	}
+
+	pub fn classes(&self) -> impl Iterator<Item = &Class> {
+		self.classes.values()
+	}
+
+	/// Supertypes named by added classes that have not been added themselves.
+	pub fn missing_classes(&self) -> BTreeSet<String> {
+		self.direct_subtypes.keys()
+			.filter(|name| !self.classes.contains_key(*name) && *name != OBJECT)
+			.cloned()
+			.collect()
+	}
+
+	/// The superclass chain of a class, nearest first and ending with java/lang/Object.
+	pub fn superclasses(&self, name: &str) -> Result<Vec<String>, HierarchyError> {
+		let mut chain: Vec<String> = Vec::new();
+		let mut current = self.class(name)?;
+		while let Some(super_name) = current.super_class_name() {
+			if super_name == name || chain.contains(&super_name) {
+				return Err(HierarchyError::Circularity(super_name));
+			}
+			chain.push(super_name.clone());
+			if super_name == OBJECT && !self.contains(OBJECT) {
+				break;
+			}
+			current = self.class(&super_name)?;
+		}
+		Ok(chain)
+	}
+
+	/// Every interface a class or interface implements, directly or through its supertypes.
+	pub fn interfaces(&self, name: &str) -> Result<BTreeSet<String>, HierarchyError> {
+		let mut interfaces: BTreeSet<String> = BTreeSet::new();
+		let mut pending: Vec<String> = vec![name.to_string()];
+		pending.extend(self.superclasses(name)?.into_iter().filter(|class| class != OBJECT || self.contains(OBJECT)));
+		while let Some(current) = pending.pop() {
+			for interface in self.class(&current)?.interface_names() {
+				if interface == name {
+					return Err(HierarchyError::Circularity(interface));
+				}
+				if interfaces.insert(interface.clone()) {
+					pending.push(interface);
+				}
+			}
+		}
+		Ok(interfaces)
+	}
+
+	/// Every proper supertype: the superclass chain followed by all interfaces.
+	pub fn supertypes(&self, name: &str) -> Result<BTreeSet<String>, HierarchyError> {
+		let mut supertypes: BTreeSet<String> = self.superclasses(name)?.into_iter().collect();
+		supertypes.extend(self.interfaces(name)?);
+		if self.class(name)?.is_interface() {
+			supertypes.insert(OBJECT.to_string());
+		}
+		Ok(supertypes)
+	}
+
+	/// Classes naming `name` as their superclass or as one of their interfaces.
+	pub fn direct_subtypes(&self, name: &str) -> BTreeSet<String> {
+		self.direct_subtypes.get(name).cloned().unwrap_or_default()
+	}
+
+	/// All known subclasses and implementors of `name`, transitively.
+	pub fn subtypes(&self, name: &str) -> BTreeSet<String> {
+		let mut subtypes: BTreeSet<String> = BTreeSet::new();
+		let mut pending: Vec<String> = vec![name.to_string()];
+		while let Some(current) = pending.pop() {
+			for subtype in self.direct_subtypes(&current) {
+				if subtypes.insert(subtype.clone()) {
+					pending.push(subtype);
+				}
+			}
+		}
+		subtypes
+	}
+
+	/// Whether a value of type `source` can be assigned to a variable of type `target`, in the
+	/// sense of `Class.isAssignableFrom`. Types are internal names, or descriptors for arrays.
+	pub fn is_assignable_from(&self, target: &str, source: &str) -> Result<bool, HierarchyError> {
+		if target == source || target == OBJECT {
+			return Ok(true);
+		}
+		match (target.strip_prefix('['), source.strip_prefix('[')) {
+			(Some(target_component), Some(source_component)) => {
+				match (Self::reference_component(target_component), Self::reference_component(source_component)) {
+					(Some(target_component), Some(source_component)) => self.is_assignable_from(target_component, source_component),
+					_ => Ok(false),
+				}
+			}
+			(None, Some(_)) => Ok(ARRAY_INTERFACES.contains(&target)),
+			(Some(_), None) => Ok(false),
+			(None, None) => Ok(self.supertypes(source)?.contains(target)),
+		}
+	}
+
+	/// The class or array name of an array component descriptor, or None for primitives.
+	fn reference_component(descriptor: &str) -> Option<&str> {
+		if descriptor.starts_with('[') {
+			Some(descriptor)
+		} else {
+			descriptor.strip_prefix('L').and_then(|name| name.strip_suffix(';'))
+		}
+	}
+
+	/// The most specific class that both types extend. As in the verifier, interfaces only have
+	/// java/lang/Object in common with anything other than themselves.
+	pub fn least_common_superclass(&self, a: &str, b: &str) -> Result<String, HierarchyError> {
+		if a == b {
+			return Ok(a.to_string());
+		}
+		if a.starts_with('[') || b.starts_with('[') || self.class(a)?.is_interface() || self.class(b)?.is_interface() {
+			return Ok(OBJECT.to_string());
+		}
+		let mut chain_a = vec![a.to_string()];
+		chain_a.extend(self.superclasses(a)?);
+		let mut chain_b: BTreeSet<String> = self.superclasses(b)?.into_iter().collect();
+		chain_b.insert(b.to_string());
+		Ok(chain_a.into_iter().find(|class| chain_b.contains(class)).unwrap_or_else(|| OBJECT.to_string()))
+	}
+
+	fn declared_method<'a>(&'a self, class_name: &str, name: &str, descriptor: &str) -> Result<Option<&'a Method>, HierarchyError> {
+		if class_name == OBJECT && !self.contains(OBJECT) {
+			return Ok(None);
+		}
+		Ok(self.class(class_name)?.find_method(name, descriptor))
+	}
+
+	/// The maximally-specific superinterface methods of a class (JVMS17 5.4.3.3).
+	fn maximally_specific_methods(&self, class_name: &str, name: &str, descriptor: &str) -> Result<Vec<(MethodLocation, bool)>, HierarchyError> {
+		let mut candidates: Vec<(String, bool)> = Vec::new();
+		for interface in self.interfaces(class_name)? {
+			if let Some(method) = self.declared_method(&interface, name, descriptor)?
+				&& !has_flag(method, MethodAccessPropertyFlags::Private)
+				&& !has_flag(method, MethodAccessPropertyFlags::Static) {
+				candidates.push((interface, has_flag(method, MethodAccessPropertyFlags::Abstract)));
+			}
+		}
+		let mut maximal = Vec::new();
+		for (interface, is_abstract) in &candidates {
+			let mut overridden = false;
+			for (other, _) in &candidates {
+				if other != interface && self.interfaces(other)?.contains(interface) {
+					overridden = true;
+				}
+			}
+			if !overridden {
+				maximal.push((MethodLocation::new(interface, name, descriptor), *is_abstract));
+			}
+		}
+		Ok(maximal)
+	}
+
+	/// Resolve a field reference to the class declaring the field (JVMS17 5.4.3.2).
+	pub fn resolve_field(&self, class_name: &str, name: &str, descriptor: &str) -> Result<Option<String>, HierarchyError> {
+		if class_name == OBJECT && !self.contains(OBJECT) {
+			return Ok(None);
+		}
+		let class = self.class(class_name)?;
+		if class.find_field(name, descriptor).is_some() {
+			return Ok(Some(class_name.to_string()));
+		}
+		for interface in class.interface_names() {
+			if let Some(declaring_class) = self.resolve_field(&interface, name, descriptor)? {
+				return Ok(Some(declaring_class));
+			}
+		}
+		match class.super_class_name() {
+			Some(super_class) => self.resolve_field(&super_class, name, descriptor),
+			None => Ok(None),
+		}
+	}
+
+	/// Resolve a method reference whose owner is a class (JVMS17 5.4.3.3).
+	pub fn resolve_method(&self, class_name: &str, name: &str, descriptor: &str) -> Result<Option<MethodLocation>, HierarchyError> {
+		if self.class(class_name)?.is_interface() {
+			return Err(HierarchyError::IncompatibleClassChange(format!("{} is an interface", class_name)));
+		}
+		let mut chain = vec![class_name.to_string()];
+		chain.extend(self.superclasses(class_name)?);
+		for class in chain {
+			if self.declared_method(&class, name, descriptor)?.is_some() {
+				return Ok(Some(MethodLocation::new(&class, name, descriptor)));
+			}
+		}
+		self.resolve_in_superinterfaces(class_name, name, descriptor)
+	}
+
+	/// Resolve a method reference whose owner is an interface (JVMS17 5.4.3.4).
+	pub fn resolve_interface_method(&self, interface_name: &str, name: &str, descriptor: &str) -> Result<Option<MethodLocation>, HierarchyError> {
+		if !self.class(interface_name)?.is_interface() {
+			return Err(HierarchyError::IncompatibleClassChange(format!("{} is not an interface", interface_name)));
+		}
+		if self.declared_method(interface_name, name, descriptor)?.is_some() {
+			return Ok(Some(MethodLocation::new(interface_name, name, descriptor)));
+		}
+		if let Some(method) = self.declared_method(OBJECT, name, descriptor)?
+			&& has_flag(method, MethodAccessPropertyFlags::Public)
+			&& !has_flag(method, MethodAccessPropertyFlags::Static) {
+			return Ok(Some(MethodLocation::new(OBJECT, name, descriptor)));
+		}
+		self.resolve_in_superinterfaces(interface_name, name, descriptor)
+	}
+
+	fn resolve_in_superinterfaces(&self, class_name: &str, name: &str, descriptor: &str) -> Result<Option<MethodLocation>, HierarchyError> {
+		let maximal = self.maximally_specific_methods(class_name, name, descriptor)?;
+		let concrete: Vec<&MethodLocation> = maximal.iter().filter(|(_, is_abstract)| !is_abstract).map(|(location, _)| location).collect();
+		if concrete.len() == 1 {
+			return Ok(Some(concrete[0].clone()));
+		}
+		Ok(maximal.into_iter().next().map(|(location, _)| location))
+	}
+
+	/// Whether a method declared in `class_name` overrides the resolved method (JVMS17 5.4.5).
+	fn can_override(&self, class_name: &str, method: &Method, resolved: &MethodLocation) -> Result<bool, HierarchyError> {
+		if has_flag(method, MethodAccessPropertyFlags::Private) || has_flag(method, MethodAccessPropertyFlags::Static) {
+			return Ok(false);
+		}
+		let Some(resolved_method) = self.declared_method(&resolved.class_name, &resolved.name, &resolved.descriptor)? else {
+			return Ok(true);
+		};
+		let package_private = resolved_method.access_flags & (MethodAccessPropertyFlags::Public as u16
+			| MethodAccessPropertyFlags::Protected as u16
+			| MethodAccessPropertyFlags::Private as u16) == 0;
+		Ok(!package_private || package_of(class_name) == package_of(&resolved.class_name))
+	}
+
+	/// Select the method invoked on a receiver of class `receiver` for a resolved method (JVMS17 5.4.6).
+	pub fn select_method(&self, receiver: &str, resolved: &MethodLocation) -> Result<Option<MethodLocation>, HierarchyError> {
+		if let Some(method) = self.declared_method(&resolved.class_name, &resolved.name, &resolved.descriptor)?
+			&& has_flag(method, MethodAccessPropertyFlags::Private) {
+			return Ok(Some(resolved.clone()));
+		}
+		let mut chain = vec![receiver.to_string()];
+		chain.extend(self.superclasses(receiver)?);
+		for class in chain {
+			if let Some(method) = self.declared_method(&class, &resolved.name, &resolved.descriptor)?
+				&& self.can_override(&class, method, resolved)? {
+				return Ok(Some(MethodLocation::new(&class, &resolved.name, &resolved.descriptor)));
+			}
+		}
+		let maximal = self.maximally_specific_methods(receiver, &resolved.name, &resolved.descriptor)?;
+		let concrete: Vec<MethodLocation> = maximal.into_iter().filter(|(_, is_abstract)| !is_abstract).map(|(location, _)| location).collect();
+		Ok(if concrete.len() == 1 { concrete.into_iter().next() } else { None })
+	}
+
+	/// The methods in supertypes of `class_name` that its declaration of name/descriptor overrides.
+	pub fn overridden_methods(&self, class_name: &str, name: &str, descriptor: &str) -> Result<Vec<MethodLocation>, HierarchyError> {
+		let Some(method) = self.declared_method(class_name, name, descriptor)? else {
+			return Ok(vec![]);
+		};
+		let mut overridden = Vec::new();
+		for supertype in self.superclasses(class_name)?.into_iter().chain(self.interfaces(class_name)?) {
+			let location = MethodLocation::new(&supertype, name, descriptor);
+			if let Some(candidate) = self.declared_method(&supertype, name, descriptor)?
+				&& !has_flag(candidate, MethodAccessPropertyFlags::Private)
+				&& !has_flag(candidate, MethodAccessPropertyFlags::Static)
+				&& self.can_override(class_name, method, &location)? {
+				overridden.push(location);
+			}
+		}
+		Ok(overridden)
+	}
+
+	/// Every method a virtual or interface call to owner.name(descriptor) may dispatch to, using
+	/// class hierarchy analysis: the selected method for each non-abstract subtype of the owner.
+	pub fn implementations(&self, owner: &str, name: &str, descriptor: &str) -> Result<BTreeSet<MethodLocation>, HierarchyError> {
+		let resolved = if self.class(owner)?.is_interface() {
+			self.resolve_interface_method(owner, name, descriptor)?
+		} else {
+			self.resolve_method(owner, name, descriptor)?
+		};
+		let Some(resolved) = resolved else {
+			return Ok(BTreeSet::new());
+		};
+		let mut implementations = BTreeSet::new();
+		for receiver in std::iter::once(owner.to_string()).chain(self.subtypes(owner)) {
+			let class = self.class(&receiver)?;
+			if class.is_interface() || class.flags.contains(&ClassAccessPropertyFlags::Abstract) {
+				continue;
+			}
+			if let Some(selected) = self.select_method(&receiver, &resolved)? {
+				implementations.insert(selected);
+			}
+		}
+		Ok(implementations)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::BTreeSet, path::Path};
+
+	use crate::analysis::hierarchy::{ClassHierarchy, HierarchyError, MethodLocation, OBJECT};
+
+	fn get_hierarchy() -> ClassHierarchy {
+		ClassHierarchy::from_path(Path::new("tests/resources/hierarchy")).expect("Couldn't read classes")
+	}
+
+	#[test]
+	fn test_supertypes() {
+		let hierarchy = get_hierarchy();
+		assert_eq!(hierarchy.superclasses("Square").unwrap(), vec!["Polygon", OBJECT]);
+		assert_eq!(hierarchy.interfaces("Square").unwrap(), BTreeSet::from(["Named".to_string(), "Shape".to_string()]));
+		assert_eq!(hierarchy.subtypes("Shape"), BTreeSet::from(["Circle".to_string(), "Polygon".to_string(), "Square".to_string()]));
+		assert_eq!(hierarchy.missing_classes(), BTreeSet::from(["java/lang/Comparable".to_string()]));
+		assert_eq!(hierarchy.interfaces("Circle"), Err(HierarchyError::MissingClass("java/lang/Comparable".to_string())));
+		assert_eq!(hierarchy.superclasses("Triangle"), Err(HierarchyError::MissingClass("Triangle".to_string())));
+	}
+
+	#[test]
+	fn test_assignability() {
+		let hierarchy = get_hierarchy();
+		assert!(hierarchy.is_assignable_from("Shape", "Square").unwrap());
+		assert!(hierarchy.is_assignable_from("Polygon", "Square").unwrap());
+		assert!(!hierarchy.is_assignable_from("Square", "Polygon").unwrap());
+		assert!(!hierarchy.is_assignable_from("Named", "Polygon").unwrap());
+		assert!(hierarchy.is_assignable_from("[LShape;", "[LSquare;").unwrap());
+		assert!(hierarchy.is_assignable_from(OBJECT, "[I").unwrap());
+		assert!(!hierarchy.is_assignable_from("[J", "[I").unwrap());
+		assert_eq!(hierarchy.least_common_superclass("Square", "Polygon").unwrap(), "Polygon");
+		assert_eq!(hierarchy.least_common_superclass("Square", "Shape").unwrap(), OBJECT);
+	}
+
+	#[test]
+	fn test_method_resolution() {
+		let hierarchy = get_hierarchy();
+		assert_eq!(hierarchy.resolve_method("Square", "describe", "()Ljava/lang/String;").unwrap(), Some(MethodLocation::new("Polygon", "describe", "()Ljava/lang/String;")));
+		assert_eq!(hierarchy.resolve_interface_method("Shape", "area", "()D").unwrap(), Some(MethodLocation::new("Shape", "area", "()D")));
+		assert!(hierarchy.resolve_method("Shape", "area", "()D").is_err());
+
+		let resolved = MethodLocation::new("Shape", "describe", "()Ljava/lang/String;");
+		assert_eq!(hierarchy.select_method("Square", &resolved).unwrap(), Some(MethodLocation::new("Polygon", "describe", "()Ljava/lang/String;")));
+		assert_eq!(hierarchy.overridden_methods("Square", "area", "()D").unwrap(), vec![MethodLocation::new("Shape", "area", "()D")]);
+		assert_eq!(hierarchy.implementations("Polygon", "area", "()D").unwrap(), BTreeSet::from([MethodLocation::new("Square", "area", "()D")]));
+	}
+}
@@ -0,0 +1,391 @@
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap},
+	fmt::Write as _,
+	fs, io,
+	path::Path};
+
+use thiserror::Error;
+
+use crate::{
+	analysis::hierarchy::MethodLocation,
+	class::{
+		access::ClassAccessPropertyFlags,
+		archive::{self, ArchiveError},
+		attribute::Attribute,
+		class::{Class, MemberRef}},
+};
+
+/// The first line of a saved index, naming the format version.
+const HEADER: &str = "steele class index 1";
+
+#[derive(Error, Debug)]
+pub enum IndexError {
+	#[error("I/O error: {0}")]
+	Io(#[from] io::Error),
+	#[error("line {line}: {message}")]
+	Parse { line: usize, message: String },
+}
+
+/// A field or method as recorded in a `ClassIndex`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedMember {
+	pub name: String,
+	pub descriptor: String,
+	pub access_flags: u16,
+	/// Internal names of the annotation types, visible and invisible.
+	pub annotations: Vec<String>,
+}
+
+/// The outline of a class as recorded in a `ClassIndex`: everything needed to answer type and
+/// annotation queries, without the constant pool or code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedClass {
+	pub name: String,
+	/// The directory or JAR the class was found in.
+	pub archive: String,
+	pub access_flags: u16,
+	pub super_class: Option<String>,
+	pub interfaces: Vec<String>,
+	pub annotations: Vec<String>,
+	pub fields: Vec<IndexedMember>,
+	pub methods: Vec<IndexedMember>,
+}
+
+impl IndexedClass {
+	pub fn new(class: &Class, archive: &str) -> IndexedClass {
+		let member = |name_index: u16, descriptor_index: u16, access_flags: u16, attributes: &[Attribute]| Some(IndexedMember {
+			name: class.get_utf8(name_index)?,
+			descriptor: class.get_utf8(descriptor_index)?,
+			access_flags,
+			annotations: class.get_annotation_types(attributes),
+		});
+		IndexedClass {
+			name: class.name(),
+			archive: archive.to_string(),
+			access_flags: class.access_flags(),
+			super_class: class.super_class_name(),
+			interfaces: class.interface_names(),
+			annotations: class.get_annotation_types(&class.attributes.attributes),
+			fields: class.fields.fields.iter()
+				.filter_map(|field| member(field.name_index, field.descriptor_index, field.access_flags, &field.attributes))
+				.collect(),
+			methods: class.methods.methods.iter()
+				.filter_map(|method| member(method.name_index, method.descriptor_index, method.access_flags, &method.attributes))
+				.collect(),
+		}
+	}
+
+	pub fn has_flag(&self, flag: ClassAccessPropertyFlags) -> bool {
+		self.access_flags & flag as u16 != 0
+	}
+
+	pub fn is_interface(&self) -> bool {
+		self.has_flag(ClassAccessPropertyFlags::Interface)
+	}
+}
+
+/// An index over the classes and resources of a class path, answering supertype and annotation
+/// queries without keeping (or loading) the classes themselves.
+///
+/// Names are internal (`com/example/Main`); queries also accept `.` as the package separator.
+/// Annotations are matched as written on each class or member: `@Inherited` is not applied.
+#[derive(Debug, Default, PartialEq)]
+pub struct ClassIndex {
+	classes: BTreeMap<String, IndexedClass>,
+	/// Non-class entries, mapped to the archive they were found in.
+	resources: BTreeMap<String, String>,
+	direct_subtypes: HashMap<String, BTreeSet<String>>,
+}
+
+fn internal_name(name: &str) -> String {
+	name.replace('.', "/")
+}
+
+impl ClassIndex {
+
+	pub fn new() -> ClassIndex {
+		ClassIndex::default()
+	}
+
+	/// Index every class and resource in a sequence of directories and JARs, in class path order.
+	pub fn from_paths<P: AsRef<Path>, I: IntoIterator<Item = P>>(paths: I) -> Result<ClassIndex, ArchiveError> {
+		let mut index = ClassIndex::new();
+		for path in paths {
+			index.add_path(path.as_ref())?;
+		}
+		Ok(index)
+	}
+
+	/// Index a directory or JAR, naming the archive after its last path component.
+	pub fn add_path(&mut self, path: &Path) -> Result<(), ArchiveError> {
+		let archive = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| path.display().to_string());
+		for entry in archive::read_entries(path)? {
+			if entry.is_class() {
+				self.add(IndexedClass::new(&entry.parse()?, &archive));
+			} else {
+				self.resources.entry(entry.name).or_insert_with(|| archive.clone());
+			}
+		}
+		Ok(())
+	}
+
+	/// Add a class. As on a class path, the first class added under a name wins; returns whether
+	/// the class was added.
+	pub fn add(&mut self, class: IndexedClass) -> bool {
+		if self.classes.contains_key(&class.name) {
+			return false;
+		}
+		for supertype in class.super_class.iter().chain(&class.interfaces) {
+			self.direct_subtypes.entry(supertype.clone()).or_default().insert(class.name.clone());
+		}
+		self.classes.insert(class.name.clone(), class);
+		true
+	}
+
+	pub fn add_resource(&mut self, name: &str, archive: &str) {
+		self.resources.entry(name.to_string()).or_insert_with(|| archive.to_string());
+	}
+
+	pub fn get(&self, name: &str) -> Option<&IndexedClass> {
+		self.classes.get(&internal_name(name))
+	}
+
+	pub fn classes(&self) -> impl Iterator<Item = &IndexedClass> {
+		self.classes.values()
+	}
+
+	/// Every class that extends or implements `name`, directly or transitively.
+	pub fn subtypes(&self, name: &str) -> BTreeSet<String> {
+		let mut subtypes: BTreeSet<String> = BTreeSet::new();
+		let mut pending: Vec<String> = vec![internal_name(name)];
+		while let Some(current) = pending.pop() {
+			for subtype in self.direct_subtypes.get(&current).into_iter().flatten() {
+				if subtypes.insert(subtype.clone()) {
+					pending.push(subtype.clone());
+				}
+			}
+		}
+		subtypes
+	}
+
+	/// The subtypes of `name` that can be instantiated: neither interfaces nor abstract classes.
+	pub fn implementations(&self, name: &str) -> BTreeSet<String> {
+		self.subtypes(name).into_iter()
+			.filter(|subtype| self.classes.get(subtype).is_some_and(|class| !class.is_interface() && !class.has_flag(ClassAccessPropertyFlags::Abstract)))
+			.collect()
+	}
+
+	/// Classes annotated with `annotation`.
+	pub fn classes_annotated_with(&self, annotation: &str) -> Vec<&IndexedClass> {
+		let annotation = internal_name(annotation);
+		self.classes.values().filter(|class| class.annotations.contains(&annotation)).collect()
+	}
+
+	/// Methods annotated with `annotation`, by declaring class.
+	pub fn methods_annotated_with(&self, annotation: &str) -> Vec<MethodLocation> {
+		let annotation = internal_name(annotation);
+		self.classes.values()
+			.flat_map(|class| class.methods.iter()
+				.filter(|method| method.annotations.contains(&annotation))
+				.map(|method| MethodLocation::new(&class.name, &method.name, &method.descriptor)))
+			.collect()
+	}
+
+	/// Fields annotated with `annotation`, by declaring class.
+	pub fn fields_annotated_with(&self, annotation: &str) -> Vec<MemberRef> {
+		let annotation = internal_name(annotation);
+		self.classes.values()
+			.flat_map(|class| class.fields.iter()
+				.filter(|field| field.annotations.contains(&annotation))
+				.map(|field| MemberRef { class_name: class.name.clone(), name: field.name.clone(), descriptor: field.descriptor.clone() }))
+			.collect()
+	}
+
+	/// Resources whose names start with `prefix` (such as `META-INF/services/`), with the archive
+	/// each was found in.
+	pub fn resources(&self, prefix: &str) -> impl Iterator<Item = (&str, &str)> {
+		self.resources.range(prefix.to_string()..)
+			.take_while(move |(name, _)| name.starts_with(prefix))
+			.map(|(name, archive)| (name.as_str(), archive.as_str()))
+	}
+
+	/// Serialise the index as tab-separated lines, nested by indentation.
+	pub fn to_text(&self) -> String {
+		let mut text = format!("{}\n", HEADER);
+		let annotations = |text: &mut String, depth: &str, annotations: &[String]| for annotation in annotations {
+			let _ = writeln!(text, "{}annotation\t{}", depth, annotation);
+		};
+		for class in self.classes.values() {
+			let _ = writeln!(text, "class\t{}\t{:#06x}\t{}", class.name, class.access_flags, class.archive);
+			if let Some(super_class) = &class.super_class {
+				let _ = writeln!(text, "\tsuper\t{}", super_class);
+			}
+			for interface in &class.interfaces {
+				let _ = writeln!(text, "\tinterface\t{}", interface);
+			}
+			annotations(&mut text, "\t", &class.annotations);
+			for (kind, members) in [("field", &class.fields), ("method", &class.methods)] {
+				for member in members {
+					let _ = writeln!(text, "\t{}\t{}\t{}\t{:#06x}", kind, member.name, member.descriptor, member.access_flags);
+					annotations(&mut text, "\t\t", &member.annotations);
+				}
+			}
+		}
+		for (name, archive) in &self.resources {
+			let _ = writeln!(text, "resource\t{}\t{}", name, archive);
+		}
+		text
+	}
+
+	/// Read an index written by `to_text`.
+	pub fn from_text(text: &str) -> Result<ClassIndex, IndexError> {
+		let mut lines = text.lines().enumerate();
+		if lines.next().map(|(_, line)| line) != Some(HEADER) {
+			return Err(IndexError::Parse { line: 1, message: format!("expected '{}'", HEADER) });
+		}
+		let mut index = ClassIndex::new();
+		let mut current: Option<IndexedClass> = None;
+		for (number, line) in lines {
+			let error = |message: &str| IndexError::Parse { line: number + 1, message: message.to_string() };
+			let flags = |text: &str| u16::from_str_radix(text.trim_start_matches("0x"), 16).map_err(|_| error(&format!("bad access flags {}", text)));
+			let depth = line.len() - line.trim_start_matches('\t').len();
+			let fields: Vec<&str> = line[depth..].split('\t').collect();
+			match (depth, &fields[..]) {
+				(0, ["class", name, access_flags, archive]) => {
+					if let Some(class) = current.take() {
+						index.add(class);
+					}
+					current = Some(IndexedClass {
+						name: name.to_string(),
+						archive: archive.to_string(),
+						access_flags: flags(access_flags)?,
+						super_class: None,
+						interfaces: Vec::new(),
+						annotations: Vec::new(),
+						fields: Vec::new(),
+						methods: Vec::new(),
+					});
+				}
+				(0, ["resource", name, archive]) => index.add_resource(name, archive),
+				(0, _) => return Err(error("expected a class or resource")),
+				(_, _) => {
+					let class = current.as_mut().ok_or_else(|| error("member outside a class"))?;
+					match (depth, &fields[..]) {
+						(1, ["super", name]) => class.super_class = Some(name.to_string()),
+						(1, ["interface", name]) => class.interfaces.push(name.to_string()),
+						(1, ["annotation", name]) => class.annotations.push(name.to_string()),
+						(1, [kind @ ("field" | "method"), name, descriptor, access_flags]) => {
+							let member = IndexedMember { name: name.to_string(), descriptor: descriptor.to_string(), access_flags: flags(access_flags)?, annotations: Vec::new() };
+							if *kind == "field" { class.fields.push(member) } else { class.methods.push(member) }
+						}
+						(2, ["annotation", name]) => {
+							let member = if class.methods.is_empty() { class.fields.last_mut() } else { class.methods.last_mut() };
+							member.ok_or_else(|| error("annotation outside a member"))?.annotations.push(name.to_string());
+						}
+						_ => return Err(error("unrecognised line")),
+					}
+				}
+			}
+		}
+		if let Some(class) = current {
+			index.add(class);
+		}
+		Ok(index)
+	}
+
+	pub fn save(&self, path: &Path) -> Result<(), IndexError> {
+		Ok(fs::write(path, self.to_text())?)
+	}
+
+	pub fn load(path: &Path) -> Result<ClassIndex, IndexError> {
+		ClassIndex::from_text(&fs::read_to_string(path)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeSet;
+
+	use crate::{
+		analysis::{
+			hierarchy::MethodLocation,
+			index::{ClassIndex, IndexError}},
+		class::class::MemberRef};
+
+	const RESOURCES: &str = "tests/resources/index";
+
+	fn get_index() -> ClassIndex {
+		ClassIndex::from_paths([RESOURCES]).expect("Couldn't index classes")
+	}
+
+	fn names(names: &[&str]) -> BTreeSet<String> {
+		names.iter().map(|name| name.to_string()).collect()
+	}
+
+	#[test]
+	fn test_outline() {
+		let index = get_index();
+		let class = index.get("app.UserHandler").unwrap();
+		assert_eq!(class.archive, "index");
+		assert_eq!(class.super_class.as_deref(), Some("app/BaseHandler"));
+		assert_eq!(class.annotations, vec!["fw/Component"]);
+		assert_eq!(class.methods.iter().map(|method| method.name.as_str()).collect::<Vec<_>>(), vec!["<init>", "handle", "count"]);
+		assert!(index.get("fw/Handler").unwrap().is_interface());
+		assert!(index.get("java/lang/Object").is_none());
+	}
+
+	#[test]
+	fn test_subtypes() {
+		let index = get_index();
+		assert_eq!(index.subtypes("fw/Handler"), names(&["fw/JsonHandler", "app/BaseHandler", "app/UserHandler"]));
+		assert_eq!(index.implementations("fw.Handler"), names(&["app/UserHandler"]));
+		assert_eq!(index.implementations("java/lang/Runnable"), names(&["app/Plain"]));
+		assert!(index.subtypes("app/UserHandler").is_empty());
+	}
+
+	#[test]
+	fn test_annotations() {
+		let index = get_index();
+		let classes: Vec<&str> = index.classes_annotated_with("fw.Component").iter().map(|class| class.name.as_str()).collect();
+		assert_eq!(classes, vec!["app/UserHandler"]);
+		assert_eq!(index.methods_annotated_with("fw/Route"), vec![
+			MethodLocation::new("app/UserHandler", "handle", "(Ljava/lang/String;)V"),
+			MethodLocation::new("app/UserHandler", "count", "()I"),
+		]);
+		// class-retention annotations are recorded too
+		assert_eq!(index.fields_annotated_with("fw/Inject"), vec![
+			MemberRef { class_name: "app/UserHandler".to_string(), name: "repository".to_string(), descriptor: "Ljava/lang/Object;".to_string() },
+		]);
+		assert!(index.methods_annotated_with("fw/Component").is_empty());
+	}
+
+	#[test]
+	fn test_resources() {
+		let index = get_index();
+		assert_eq!(index.resources("META-INF/services/").collect::<Vec<_>>(), vec![("META-INF/services/fw.Handler", "index")]);
+		assert_eq!(index.resources("app/config").count(), 1);
+		assert_eq!(index.resources("missing/").count(), 0);
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let index = get_index();
+		let text = index.to_text();
+		assert!(text.starts_with("steele class index 1\nclass\tapp/BaseHandler\t0x0421\tindex\n\tsuper\tjava/lang/Object\n\tinterface\tfw/JsonHandler\n"));
+		assert!(text.contains("\tfield\trepository\tLjava/lang/Object;\t0x0002\n\t\tannotation\tfw/Inject\n"));
+		let reread = ClassIndex::from_text(&text).unwrap();
+		assert_eq!(reread, index);
+		assert_eq!(reread.implementations("fw/Handler"), names(&["app/UserHandler"]));
+	}
+
+	#[test]
+	fn test_parse_errors() {
+		let error = |text: &str| match ClassIndex::from_text(text) {
+			Err(IndexError::Parse { line, message }) => (line, message),
+			other => panic!("expected a parse error, got {:?}", other),
+		};
+		assert_eq!(error("not an index\n"), (1, "expected 'steele class index 1'".to_string()));
+		assert_eq!(error("steele class index 1\n\tsuper\tjava/lang/Object\n"), (2, "member outside a class".to_string()));
+		assert_eq!(error("steele class index 1\nclass\ta/B\tzz\tx\n"), (2, "bad access flags zz".to_string()));
+	}
+}
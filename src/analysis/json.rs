@@ -0,0 +1,166 @@
+use std::fmt::{self, Display, Formatter, Write};
+
+/// A minimal JSON document model used by the analysis reports.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Int(i64),
+	Float(f64),
+	String(String),
+	Array(Vec<Json>),
+	/// Members are kept in insertion order.
+	Object(Vec<(String, Json)>),
+}
+
+impl Json {
+	pub fn object<K: Into<String>, I: IntoIterator<Item = (K, Json)>>(members: I) -> Json {
+		Json::Object(members.into_iter().map(|(key, value)| (key.into(), value)).collect())
+	}
+
+	pub fn array<T: Into<Json>, I: IntoIterator<Item = T>>(items: I) -> Json {
+		Json::Array(items.into_iter().map(Into::into).collect())
+	}
+
+	/// Render with two-space indentation and one member per line.
+	pub fn to_pretty_string(&self) -> String {
+		let mut out = String::new();
+		self.write_pretty(&mut out, 0);
+		out
+	}
+
+	fn write_pretty(&self, out: &mut String, depth: usize) {
+		let indent = |out: &mut String, depth: usize| out.push_str(&"  ".repeat(depth));
+		match self {
+			Json::Array(items) if !items.is_empty() => {
+				out.push_str("[\n");
+				for (index, item) in items.iter().enumerate() {
+					indent(out, depth + 1);
+					item.write_pretty(out, depth + 1);
+					out.push_str(if index + 1 < items.len() { ",\n" } else { "\n" });
+				}
+				indent(out, depth);
+				out.push(']');
+			}
+			Json::Object(members) if !members.is_empty() => {
+				out.push_str("{\n");
+				for (index, (key, value)) in members.iter().enumerate() {
+					indent(out, depth + 1);
+					let _ = write!(out, "{}: ", Json::String(key.clone()));
+					value.write_pretty(out, depth + 1);
+					out.push_str(if index + 1 < members.len() { ",\n" } else { "\n" });
+				}
+				indent(out, depth);
+				out.push('}');
+			}
+			other => { let _ = write!(out, "{}", other); }
+		}
+	}
+}
+
+impl Display for Json {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Json::Null => write!(f, "null"),
+			Json::Bool(value) => write!(f, "{}", value),
+			Json::Int(value) => write!(f, "{}", value),
+			Json::Float(value) if value.is_finite() => write!(f, "{}", value),
+			Json::Float(_) => write!(f, "null"),
+			Json::String(value) => {
+				f.write_char('"')?;
+				for ch in value.chars() {
+					match ch {
+						'"' => f.write_str("\\\"")?,
+						'\\' => f.write_str("\\\\")?,
+						'\n' => f.write_str("\\n")?,
+						'\r' => f.write_str("\\r")?,
+						'\t' => f.write_str("\\t")?,
+						ch if (ch as u32) < 0x20 => write!(f, "\\u{:04x}", ch as u32)?,
+						ch => f.write_char(ch)?,
+					}
+				}
+				f.write_char('"')
+			}
+			Json::Array(items) => {
+				f.write_char('[')?;
+				for (index, item) in items.iter().enumerate() {
+					if index > 0 {
+						f.write_char(',')?;
+					}
+					write!(f, "{}", item)?;
+				}
+				f.write_char(']')
+			}
+			Json::Object(members) => {
+				f.write_char('{')?;
+				for (index, (key, value)) in members.iter().enumerate() {
+					if index > 0 {
+						f.write_char(',')?;
+					}
+					write!(f, "{}:{}", Json::String(key.clone()), value)?;
+				}
+				f.write_char('}')
+			}
+		}
+	}
+}
+
+impl From<&str> for Json {
+	fn from(value: &str) -> Self {
+		Json::String(value.to_string())
+	}
+}
+
+impl From<String> for Json {
+	fn from(value: String) -> Self {
+		Json::String(value)
+	}
+}
+
+impl From<&String> for Json {
+	fn from(value: &String) -> Self {
+		Json::String(value.clone())
+	}
+}
+
+impl From<bool> for Json {
+	fn from(value: bool) -> Self {
+		Json::Bool(value)
+	}
+}
+
+impl From<i64> for Json {
+	fn from(value: i64) -> Self {
+		Json::Int(value)
+	}
+}
+
+impl From<u32> for Json {
+	fn from(value: u32) -> Self {
+		Json::Int(i64::from(value))
+	}
+}
+
+impl From<u16> for Json {
+	fn from(value: u16) -> Self {
+		Json::Int(i64::from(value))
+	}
+}
+
+impl From<usize> for Json {
+	fn from(value: usize) -> Self {
+		Json::Int(value as i64)
+	}
+}
+
+impl From<f64> for Json {
+	fn from(value: f64) -> Self {
+		Json::Float(value)
+	}
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+	fn from(value: Option<T>) -> Self {
+		value.map(Into::into).unwrap_or(Json::Null)
+	}
+}
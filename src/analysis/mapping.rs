@@ -0,0 +1,353 @@
+use std::{
+	collections::HashMap,
+	sync::LazyLock};
+
+use regex::Regex;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+#[error("line {line}: {message}")]
+pub struct MappingError {
+	pub line: usize,
+	pub message: String,
+}
+
+/// A renaming of classes and members, read from a ProGuard/R8 mapping file or built up by hand.
+///
+/// Names are held in internal form (`com/example/Main`) and descriptors use the names on the
+/// `original` side. Package relocations apply to every class in a package (and its subpackages)
+/// without an explicit class mapping.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mapping {
+	pub classes: Vec<ClassMapping>,
+	pub relocations: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassMapping {
+	pub original: String,
+	pub renamed: String,
+	pub fields: Vec<FieldMapping>,
+	pub methods: Vec<MethodMapping>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldMapping {
+	pub original: String,
+	pub descriptor: String,
+	pub renamed: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MethodMapping {
+	pub original: String,
+	pub descriptor: String,
+	pub renamed: String,
+	/// The line range of the method's code in the renamed class.
+	pub lines: Option<(u32, u32)>,
+	/// The line range that `lines` maps to in the original source.
+	pub original_lines: Option<(u32, u32)>,
+	/// For R8 inlining entries, the class the inlined method was originally declared in.
+	pub original_class: Option<String>,
+}
+
+impl MethodMapping {
+	/// Whether this entry describes a method inlined into the renamed method, rather than the
+	/// renamed method itself.
+	pub fn is_inlined(&self) -> bool {
+		self.original_class.is_some()
+	}
+
+	/// The original line for a line in the renamed method's code.
+	fn original_line(&self, line: u32) -> u32 {
+		match (self.lines, self.original_lines) {
+			(Some((start, _)), Some((original_start, original_end))) if original_start != original_end =>
+				original_start + line.saturating_sub(start),
+			(_, Some((original_start, _))) => original_start,
+			_ => line,
+		}
+	}
+}
+
+/// Convert a source-level type such as `java.lang.String[]` to a field descriptor.
+pub fn type_to_descriptor(java_type: &str) -> String {
+	let dimensions = java_type.matches("[]").count();
+	let element = java_type.trim_end_matches("[]");
+	let descriptor = match element {
+		"boolean" => "Z".to_string(),
+		"byte" => "B".to_string(),
+		"char" => "C".to_string(),
+		"short" => "S".to_string(),
+		"int" => "I".to_string(),
+		"long" => "J".to_string(),
+		"float" => "F".to_string(),
+		"double" => "D".to_string(),
+		"void" => "V".to_string(),
+		class_name => format!("L{};", class_name.replace('.', "/")),
+	};
+	format!("{}{}", "[".repeat(dimensions), descriptor)
+}
+
+fn parse_range(text: &str, line: usize) -> Result<u32, MappingError> {
+	text.parse().map_err(|_| MappingError { line, message: format!("bad line number {}", text) })
+}
+
+impl Mapping {
+	pub fn new() -> Mapping {
+		Mapping::default()
+	}
+
+	/// Parse a ProGuard or R8 mapping file. Comments and R8's JSON metadata lines are skipped.
+	pub fn from_proguard(text: &str) -> Result<Mapping, MappingError> {
+		let mut mapping = Mapping::new();
+		for (number, raw_line) in text.lines().enumerate() {
+			let line = number + 1;
+			let content = raw_line.split_once('#').map(|(content, _)| content).unwrap_or(raw_line);
+			if content.trim().is_empty() {
+				continue;
+			}
+			let error = |message: &str| MappingError { line, message: message.to_string() };
+			let (left, right) = content.trim().split_once(" -> ").ok_or_else(|| error("expected ' -> '"))?;
+			if !raw_line.starts_with(char::is_whitespace) {
+				let renamed = right.strip_suffix(':').ok_or_else(|| error("expected ':' after class mapping"))?;
+				mapping.add_class(&left.replace('.', "/"), &renamed.replace('.', "/"));
+				continue;
+			}
+			let class = mapping.classes.last_mut().ok_or_else(|| error("member mapping outside a class"))?;
+			let renamed = right.trim().to_string();
+
+			let (left, lines) = match left.splitn(3, ':').collect::<Vec<_>>()[..] {
+				[start, end, rest] if start.chars().all(|c| c.is_ascii_digit()) => (rest, Some((parse_range(start, line)?, parse_range(end, line)?))),
+				_ => (left, None),
+			};
+			let (java_type, member) = left.split_once(' ').ok_or_else(|| error("expected a type and a name"))?;
+			let Some((name, rest)) = member.split_once('(') else {
+				class.fields.push(FieldMapping { original: member.to_string(), descriptor: type_to_descriptor(java_type), renamed });
+				continue;
+			};
+			let (arguments, original_lines) = rest.split_once(')').ok_or_else(|| error("expected ')'"))?;
+			let original_lines = match original_lines.split(':').skip(1).collect::<Vec<_>>()[..] {
+				[] => None,
+				[start] => Some((parse_range(start, line)?, parse_range(start, line)?)),
+				[start, end, ..] => Some((parse_range(start, line)?, parse_range(end, line)?)),
+			};
+			let descriptor = format!("({}){}",
+				arguments.split(',').filter(|argument| !argument.is_empty()).map(type_to_descriptor).collect::<String>(),
+				type_to_descriptor(java_type));
+			let (original_class, name) = match name.rsplit_once('.') {
+				Some((original_class, name)) => (Some(original_class.replace('.', "/")), name),
+				None => (None, name),
+			};
+			class.methods.push(MethodMapping { original: name.to_string(), descriptor, renamed, lines, original_lines, original_class });
+		}
+		Ok(mapping)
+	}
+
+	pub fn add_class(&mut self, original: &str, renamed: &str) -> &mut ClassMapping {
+		self.classes.push(ClassMapping { original: original.to_string(), renamed: renamed.to_string(), fields: Vec::new(), methods: Vec::new() });
+		self.classes.last_mut().expect("class was just added")
+	}
+
+	/// Move every class in package `from` (and its subpackages) without its own mapping to `to`.
+	pub fn relocate(&mut self, from: &str, to: &str) -> &mut Mapping {
+		let package = |name: &str| format!("{}/", name.replace('.', "/").trim_end_matches('/'));
+		self.relocations.push((package(from), package(to)));
+		self
+	}
+
+	/// The new name for a class, if the mapping renames it.
+	pub fn map_class(&self, class_name: &str) -> Option<String> {
+		if let Some(class) = self.classes.iter().find(|class| class.original == class_name) {
+			return Some(class.renamed.clone());
+		}
+		self.relocations.iter()
+			.filter(|(from, _)| class_name.starts_with(from.as_str()))
+			.max_by_key(|(from, _)| from.len())
+			.map(|(from, to)| format!("{}{}", to, &class_name[from.len()..]))
+	}
+
+	/// The inverse mapping, for deobfuscation. Descriptors are rewritten in terms of the renamed
+	/// classes and line ranges swapped; R8 inlining entries are dropped since they don't
+	/// correspond to members of the renamed class.
+	pub fn reversed(&self) -> Mapping {
+		let classes = self.classes.iter().map(|class| ClassMapping {
+			original: class.renamed.clone(),
+			renamed: class.original.clone(),
+			fields: class.fields.iter().map(|field| FieldMapping {
+				original: field.renamed.clone(),
+				descriptor: self.map_descriptor(&field.descriptor),
+				renamed: field.original.clone(),
+			}).collect(),
+			methods: class.methods.iter().filter(|method| !method.is_inlined()).map(|method| MethodMapping {
+				original: method.renamed.clone(),
+				descriptor: self.map_descriptor(&method.descriptor),
+				renamed: method.original.clone(),
+				lines: method.original_lines,
+				original_lines: method.lines,
+				original_class: None,
+			}).collect(),
+		}).collect();
+		let relocations = self.relocations.iter().map(|(from, to)| (to.clone(), from.clone())).collect();
+		Mapping { classes, relocations }
+	}
+
+	/// Rewrite the class names in a descriptor.
+	pub fn map_descriptor(&self, descriptor: &str) -> String {
+		let mut mapped = String::with_capacity(descriptor.len());
+		let mut rest = descriptor;
+		while let Some(start) = rest.find('L') {
+			let Some(end) = rest[start..].find(';') else {
+				break;
+			};
+			let class_name = &rest[start + 1..start + end];
+			mapped.push_str(&rest[..=start]);
+			mapped.push_str(&self.map_class(class_name).unwrap_or_else(|| class_name.to_string()));
+			mapped.push(';');
+			rest = &rest[start + end + 1..];
+		}
+		mapped.push_str(rest);
+		mapped
+	}
+
+	/// Translate an obfuscated stack trace back to original names, like ProGuard's ReTrace.
+	///
+	/// This mapping must be the one used to obfuscate, i.e. the one read from the mapping file.
+	/// Frames whose method was renamed ambiguously and can't be told apart by line number list
+	/// the candidates separated by `|`; frames R8 inlined several methods into are expanded to
+	/// one frame per inlined method.
+	pub fn retrace(&self, trace: &str) -> String {
+		static FRAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\s*at\s+)((?:[^\s(/]+/)?)([\w$.]+)\.([\w$<>]+)\(([^)]*)\)(.*)$").expect("valid regex"));
+		static THROWABLE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"^(\s*(?:Caused by: |Suppressed: |Exception in thread "[^"]*" )?)([\w$]+(?:\.[\w$]+)*)(:.*)?$"#).expect("valid regex"));
+
+		let by_renamed: HashMap<&str, &ClassMapping> = self.classes.iter().map(|class| (class.renamed.as_str(), class)).collect();
+		let original_class = |dotted: &str| by_renamed.get(dotted.replace('.', "/").as_str()).copied();
+		let mut output = Vec::new();
+		for line in trace.lines() {
+			if let Some(frame) = FRAME.captures(line) {
+				let (prefix, module, class_name, method_name, location, suffix) = (&frame[1], &frame[2], &frame[3], &frame[4], &frame[5], &frame[6]);
+				let Some(class) = original_class(class_name) else {
+					output.push(line.to_string());
+					continue;
+				};
+				let number = location.rsplit_once(':').and_then(|(_, number)| number.parse::<u32>().ok());
+				let given_file = location.split_once(':').map(|(file, _)| file).unwrap_or(location);
+				let file = |class_name: &str| match given_file {
+					"SourceFile" | "Unknown Source" | "" => {
+						let simple_name = class_name.rsplit('/').next().unwrap_or(class_name);
+						format!("{}.java", simple_name.split('$').next().unwrap_or(simple_name))
+					}
+					file => file.to_string(),
+				};
+				let candidates: Vec<&MethodMapping> = class.methods.iter().filter(|method| method.renamed == method_name).collect();
+				let by_line: Vec<&MethodMapping> = candidates.iter().copied()
+					.filter(|method| matches!((method.lines, number), (Some((start, end)), Some(number)) if (start..=end).contains(&number)))
+					.collect();
+				let declaring_class = |method: &MethodMapping| method.original_class.clone().unwrap_or_else(|| class.original.clone());
+				let location = |method: Option<&MethodMapping>| {
+					let file = file(&method.map(declaring_class).unwrap_or_else(|| class.original.clone()));
+					match (number, method) {
+						(Some(number), Some(method)) => format!("{}:{}", file, method.original_line(number)),
+						(Some(number), None) => format!("{}:{}", file, number),
+						(None, _) => file,
+					}
+				};
+				if !by_line.is_empty() {
+					for method in by_line {
+						output.push(format!("{}{}{}.{}({}){}", prefix, module, declaring_class(method).replace('/', "."), method.original, location(Some(method)), suffix));
+					}
+				} else if !candidates.is_empty() {
+					let mut names: Vec<&str> = Vec::new();
+					for method in candidates.iter().filter(|method| !method.is_inlined()) {
+						if !names.contains(&method.original.as_str()) {
+							names.push(&method.original);
+						}
+					}
+					let method = (names.len() == 1).then_some(candidates[0]);
+					output.push(format!("{}{}{}.{}({}){}", prefix, module, class.original.replace('/', "."), names.join("|"), location(method), suffix));
+				} else {
+					output.push(format!("{}{}{}.{}({}){}", prefix, module, class.original.replace('/', "."), method_name, location(None), suffix));
+				}
+			} else if let Some(throwable) = THROWABLE.captures(line)
+				&& let Some(class) = original_class(&throwable[2]) {
+				output.push(format!("{}{}{}", &throwable[1], class.original.replace('/', "."), throwable.get(3).map(|m| m.as_str()).unwrap_or("")));
+			} else {
+				output.push(line.to_string());
+			}
+		}
+		output.join("\n")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::analysis::mapping::{type_to_descriptor, Mapping, MappingError};
+
+	const MAPPING: &str = "\
+# compiler: R8
+com.example.Main -> a:
+    java.lang.String name -> a
+    int[][] grid -> b
+    1:1:void <init>():3:3 -> <init>
+    4:6:void run(java.lang.String,int):10:12 -> a
+    7:7:void helper():20:20 -> a
+    7:7:void run():30 -> a
+    8:9:void com.example.Util.log(java.lang.Object):5:6 -> b
+    8:9:void report():40 -> b
+com.example.Util -> b:
+    void log(java.lang.Object) -> a
+";
+
+	#[test]
+	fn test_parse() {
+		let mapping = Mapping::from_proguard(MAPPING).expect("Couldn't parse mapping");
+		assert_eq!(mapping.classes.len(), 2);
+		let main = &mapping.classes[0];
+		assert_eq!((main.original.as_str(), main.renamed.as_str()), ("com/example/Main", "a"));
+		assert_eq!(main.fields[1].descriptor, "[[I");
+		assert_eq!(main.methods[1].descriptor, "(Ljava/lang/String;I)V");
+		assert_eq!(main.methods[1].lines, Some((4, 6)));
+		assert_eq!(main.methods[1].original_lines, Some((10, 12)));
+		assert_eq!(main.methods[4].original_class.as_deref(), Some("com/example/Util"));
+		assert_eq!(type_to_descriptor("java.util.Map$Entry[]"), "[Ljava/util/Map$Entry;");
+
+		assert_eq!(Mapping::from_proguard("a -> b:\n    int x\n"), Err(MappingError { line: 2, message: "expected ' -> '".to_string() }));
+		assert!(Mapping::from_proguard("    int x -> y\n").is_err());
+	}
+
+	#[test]
+	fn test_map_class() {
+		let mut mapping = Mapping::from_proguard(MAPPING).expect("Couldn't parse mapping");
+		mapping.relocate("com.google", "shaded.com.google").relocate("com/google/common", "shaded/guava");
+		assert_eq!(mapping.map_class("com/example/Main").as_deref(), Some("a"));
+		assert_eq!(mapping.map_class("com/google/gson/Gson").as_deref(), Some("shaded/com/google/gson/Gson"));
+		assert_eq!(mapping.map_class("com/google/common/base/Strings").as_deref(), Some("shaded/guava/base/Strings"));
+		assert_eq!(mapping.map_class("com/googlex/Other"), None);
+		assert_eq!(mapping.map_descriptor("(Lcom/example/Main;[Lcom/example/Util;)V"), "(La;[Lb;)V");
+
+		let reversed = mapping.reversed();
+		assert_eq!(reversed.map_class("a").as_deref(), Some("com/example/Main"));
+		assert_eq!(reversed.classes[0].methods[1].descriptor, "(Ljava/lang/String;I)V");
+		assert_eq!(reversed.classes[1].methods[0].descriptor, "(Ljava/lang/Object;)V");
+		assert_eq!(reversed.map_class("shaded/guava/base/Strings").as_deref(), Some("com/google/common/base/Strings"));
+	}
+
+	#[test]
+	fn test_retrace() {
+		let mapping = Mapping::from_proguard(MAPPING).expect("Couldn't parse mapping");
+		let trace = "\
+Exception in thread \"main\" a: boom
+\tat a.a(SourceFile:5)
+\tat a.b(SourceFile:8)
+\tat a.a(Unknown Source)
+\tat java.base/java.lang.Thread.run(Thread.java:833)
+Caused by: b: inner";
+		assert_eq!(mapping.retrace(trace), "\
+Exception in thread \"main\" com.example.Main: boom
+\tat com.example.Main.run(Main.java:11)
+\tat com.example.Util.log(Util.java:5)
+\tat com.example.Main.report(Main.java:40)
+\tat com.example.Main.run|helper(Main.java)
+\tat java.base/java.lang.Thread.run(Thread.java:833)
+Caused by: com.example.Util: inner");
+	}
+}
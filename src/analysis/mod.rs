@@ -0,0 +1,12 @@
+pub mod call_graph;
+pub mod compatibility;
+pub mod dependencies;
+pub mod diff;
+pub mod hierarchy;
+pub mod index;
+pub mod json;
+pub mod mapping;
+pub mod query;
+pub mod remapper;
+pub mod shrinker;
+pub mod stats;
\ No newline at end of file
@@ -0,0 +1,345 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+	analysis::{
+		call_graph::dynamic_target,
+		diff::render_instructions,
+		hierarchy::{ClassHierarchy, MethodLocation}},
+	class::{
+		attribute::{AttributeInfo, Code},
+		class::{Class, MemberRef},
+		constant_pool::ConstantPoolItem},
+	error::DecodeError,
+	isa::{
+		instruction::{self, Instruction},
+		opcode::Opcode},
+};
+
+/// In a pattern, stands for any number of instructions (including none).
+pub const GAP: &str = "...";
+
+/// Which accesses of a field a `Query::FieldAccess` finds.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Access {
+	Read,
+	Write,
+	Any,
+}
+
+/// Something to search a class path's code for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Query {
+	/// Calls that may reach a method: calls resolving to it (including through subclasses that
+	/// inherit it), virtual and interface calls to a method it overrides on a receiver type it can
+	/// be dispatched from, and `invokedynamic` sites using it as a method reference.
+	Calls(MethodLocation),
+	/// Reads and/or writes of a field, after resolution, so that accesses through a subclass are
+	/// found. Without a descriptor any field of that name matches.
+	FieldAccess { class_name: String, name: String, descriptor: Option<String>, access: Access },
+	/// `new` of a class.
+	New(String),
+	/// `ldc` of a string constant.
+	Ldc(String),
+	/// A sequence of instructions, each element a wildcard pattern (`*` any text, `?` any one
+	/// character) over the instruction as `diff::render_instructions` renders it, or `GAP`.
+	Pattern(Vec<String>),
+}
+
+impl Query {
+	pub fn calls(class_name: &str, name: &str, descriptor: &str) -> Query {
+		Query::Calls(MethodLocation::new(class_name, name, descriptor))
+	}
+
+	pub fn reads(class_name: &str, name: &str) -> Query {
+		Query::FieldAccess { class_name: class_name.to_string(), name: name.to_string(), descriptor: None, access: Access::Read }
+	}
+
+	pub fn writes(class_name: &str, name: &str) -> Query {
+		Query::FieldAccess { class_name: class_name.to_string(), name: name.to_string(), descriptor: None, access: Access::Write }
+	}
+
+	/// A pattern written with its elements separated by `;` or new lines, such as
+	/// `new java/lang/StringBuilder; dup; ...; invokevirtual java/lang/StringBuilder.toString*`.
+	pub fn pattern(text: &str) -> Query {
+		Query::Pattern(text.split([';', '\n']).map(str::trim).filter(|element| !element.is_empty()).map(str::to_string).collect())
+	}
+}
+
+/// A place a query matched: the method, the pc of the (first) matching instruction and the source
+/// line it belongs to, if the method has a LineNumberTable.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryMatch {
+	pub method: MethodLocation,
+	pub pc: u32,
+	pub line: Option<u16>,
+	/// The matching instructions, rendered.
+	pub instructions: Vec<String>,
+}
+
+impl Display for QueryMatch {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{}.{}{} pc {}", self.method.class_name, self.method.name, self.method.descriptor, self.pc)?;
+		if let Some(line) = self.line {
+			write!(f, " line {}", line)?;
+		}
+		write!(f, ": {}", self.instructions.join("; "))
+	}
+}
+
+/// Whether `text` matches a wildcard pattern in which `*` matches any text and `?` any one character.
+/// On a mismatch after a `*`, the star is made to match one more character and matching resumes
+/// from there, so the time taken is at most the product of the lengths.
+fn wildcard_matches(pattern: &str, text: &str) -> bool {
+	let (pattern, text): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+	let (mut p, mut t) = (0, 0);
+	// The position of the last star seen, and of the text it was first tried against.
+	let mut star = None;
+	while t < text.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				star = Some((p, t));
+				p += 1;
+			}
+			Some(&expected) if expected == '?' || expected == text[t] => {
+				p += 1;
+				t += 1;
+			}
+			_ => match star {
+				Some((star_p, star_t)) => {
+					star = Some((star_p, star_t + 1));
+					p = star_p + 1;
+					t = star_t + 1;
+				}
+				None => return false,
+			},
+		}
+	}
+	pattern[p..].iter().all(|&remaining| remaining == '*')
+}
+
+/// The length of the run of instructions from the start of `instructions` matching `pattern`, if any.
+fn sequence_matches(pattern: &[String], instructions: &[String]) -> Option<usize> {
+	match pattern {
+		[] => Some(0),
+		[gap, rest @ ..] if gap == GAP => (0..=instructions.len())
+			.find_map(|skip| sequence_matches(rest, &instructions[skip..]).map(|length| skip + length)),
+		[element, rest @ ..] => {
+			let first = instructions.first()?;
+			if !wildcard_matches(element, first) {
+				return None;
+			}
+			sequence_matches(rest, &instructions[1..]).map(|length| length + 1)
+		}
+	}
+}
+
+/// The source line of the instruction at `pc`, from the Code attribute's LineNumberTables.
+fn line_at(code: &Code, pc: u32) -> Option<u16> {
+	code.attributes.iter()
+		.filter_map(|attribute| match &attribute.attribute_info {
+			AttributeInfo::LineNumberTable(table) => Some(table),
+			_ => None,
+		})
+		.flat_map(|table| table.lines.iter())
+		.filter(|line| u32::from(line.start_pc) <= pc)
+		.max_by_key(|line| line.start_pc)
+		.map(|line| line.line_number)
+}
+
+/// Searches the code of every class in a hierarchy. The hierarchy is used to resolve method and
+/// field references; references to classes outside it are taken as written.
+pub struct QueryEngine<'a> {
+	hierarchy: &'a ClassHierarchy,
+}
+
+impl<'a> QueryEngine<'a> {
+
+	pub fn new(hierarchy: &'a ClassHierarchy) -> QueryEngine<'a> {
+		QueryEngine { hierarchy }
+	}
+
+	/// Every match of `query`, ordered by class, then method, then pc.
+	pub fn find(&self, query: &Query) -> Result<Vec<QueryMatch>, DecodeError> {
+		let mut classes: Vec<&Class> = self.hierarchy.classes().collect();
+		classes.sort_by_key(|class| class.name());
+		let overridden = match query {
+			Query::Calls(target) => self.hierarchy.overridden_methods(&target.class_name, &target.name, &target.descriptor).unwrap_or_default(),
+			_ => Vec::new(),
+		};
+		let mut matches = Vec::new();
+		for class in classes {
+			let class_name = class.name();
+			for method in &class.methods.methods {
+				let (Some(code), Some(name), Some(descriptor)) = (method.code(), class.get_utf8(method.name_index), class.get_utf8(method.descriptor_index)) else {
+					continue;
+				};
+				let location = MethodLocation::new(&class_name, &name, &descriptor);
+				let decoded = instruction::decode(&code.code)?;
+				let found = |pc: u32, instructions: Vec<String>| QueryMatch { method: location.clone(), pc, line: line_at(code, pc), instructions };
+				if let Query::Pattern(pattern) = query {
+					let rendered = render_instructions(class, &decoded);
+					let mut index = 0;
+					while index < decoded.len() {
+						match sequence_matches(pattern, &rendered[index..]) {
+							Some(length) if length > 0 => {
+								matches.push(found(decoded[index].0, rendered[index..index + length].to_vec()));
+								index += length;
+							}
+							_ => index += 1,
+						}
+					}
+					continue;
+				}
+				// matching instructions take constant pool operands, not branch targets, so they render alone
+				for (index, (pc, instruction)) in decoded.iter().enumerate() {
+					if self.instruction_matches(class, instruction, query, &overridden) {
+						matches.push(found(*pc, render_instructions(class, &decoded[index..=index])));
+					}
+				}
+			}
+		}
+		Ok(matches)
+	}
+
+	fn instruction_matches(&self, class: &Class, instruction: &Instruction, query: &Query, overridden: &[MethodLocation]) -> bool {
+		let Some(index) = instruction.constant_pool_index() else {
+			return false;
+		};
+		let opcode = instruction.opcode();
+		match query {
+			Query::Calls(target) => match opcode {
+				Opcode::InvokeVirtual | Opcode::InvokeInterface | Opcode::InvokeSpecial | Opcode::InvokeStatic => class.get_member_ref(index)
+					.is_some_and(|method| self.call_matches(&method, opcode, target, overridden)),
+				Opcode::InvokeDynamic => dynamic_target(class, index).as_ref() == Some(target),
+				_ => false,
+			},
+			Query::FieldAccess { class_name, name, descriptor, access } => {
+				let accessed = match opcode {
+					Opcode::GetField | Opcode::GetStatic => Access::Read,
+					Opcode::PutField | Opcode::PutStatic => Access::Write,
+					_ => return false,
+				};
+				let Some(field) = class.get_member_ref(index) else {
+					return false;
+				};
+				(*access == Access::Any || *access == accessed) && field.name == *name &&
+					descriptor.as_ref().is_none_or(|descriptor| *descriptor == field.descriptor) &&
+					self.resolve_field(&field) == *class_name
+			}
+			Query::New(class_name) => opcode == Opcode::New && class.get_class_name_at(index).as_ref() == Some(class_name),
+			Query::Ldc(string) => matches!(opcode, Opcode::Ldc | Opcode::LdcW) && match class.constant_pool.get(&index) {
+				Some(ConstantPoolItem::String(constant)) => class.get_utf8(constant.index).as_ref() == Some(string),
+				_ => false,
+			},
+			Query::Pattern(_) => false,
+		}
+	}
+
+	fn call_matches(&self, method: &MemberRef, opcode: Opcode, target: &MethodLocation, overridden: &[MethodLocation]) -> bool {
+		if method.name != target.name || method.descriptor != target.descriptor {
+			return false;
+		}
+		let resolved = self.resolve_method(method);
+		if resolved == *target {
+			return true;
+		}
+		// a virtual call to a method the target overrides reaches it if the receiver may be a target
+		matches!(opcode, Opcode::InvokeVirtual | Opcode::InvokeInterface) && overridden.contains(&resolved) &&
+			self.hierarchy.is_assignable_from(&method.class_name, &target.class_name).unwrap_or(false)
+	}
+
+	/// The declaration a method reference resolves to, or the reference itself if it can't be resolved.
+	fn resolve_method(&self, method: &MemberRef) -> MethodLocation {
+		let resolved = match self.hierarchy.get(&method.class_name) {
+			Some(class) if class.is_interface() => self.hierarchy.resolve_interface_method(&method.class_name, &method.name, &method.descriptor),
+			Some(_) => self.hierarchy.resolve_method(&method.class_name, &method.name, &method.descriptor),
+			None => Ok(None),
+		};
+		resolved.ok().flatten().unwrap_or_else(|| MethodLocation::new(&method.class_name, &method.name, &method.descriptor))
+	}
+
+	/// The class declaring a referenced field, or the reference's class if it can't be resolved.
+	fn resolve_field(&self, field: &MemberRef) -> String {
+		self.hierarchy.resolve_field(&field.class_name, &field.name, &field.descriptor).ok().flatten().unwrap_or_else(|| field.class_name.clone())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+
+	use crate::analysis::{
+		hierarchy::ClassHierarchy,
+		query::{sequence_matches, wildcard_matches, Access, Query, QueryEngine, QueryMatch}};
+
+	const RESOURCES: &str = "tests/resources/query";
+
+	fn get_hierarchy() -> ClassHierarchy {
+		ClassHierarchy::from_path(Path::new(RESOURCES)).expect("Couldn't read classes")
+	}
+
+	/// Each match as `Class.method pc line`.
+	fn sites(matches: &[QueryMatch]) -> Vec<String> {
+		matches.iter().map(|found| format!("{}.{} {} {}", found.method.class_name, found.method.name, found.pc, found.line.unwrap_or_default())).collect()
+	}
+
+	#[test]
+	fn test_calls() {
+		let hierarchy = get_hierarchy();
+		let engine = QueryEngine::new(&hierarchy);
+		// Special.total overrides Item.total, so a call on an Item may reach it
+		let special = engine.find(&Query::calls("shop/Special", "total", "()I")).unwrap();
+		assert_eq!(sites(&special), vec!["shop/Cart.sum 1 7", "shop/Cart.sum 9 8"]);
+		// Plain inherits Item.total; the method reference in label() is a call site too
+		let item = engine.find(&Query::calls("shop/Item", "total", "()I")).unwrap();
+		assert_eq!(sites(&item), vec!["shop/Cart.sum 1 7", "shop/Cart.sum 18 9", "shop/Cart.label 0 26"]);
+		assert_eq!(item[1].instructions, vec!["invokevirtual shop/Plain.total()I"]);
+		let other = engine.find(&Query::calls("shop/Other", "total", "()I")).unwrap();
+		assert_eq!(sites(&other), vec!["shop/Cart.sum 28 10"]);
+		// constructors chain through invokespecial
+		assert_eq!(sites(&engine.find(&Query::calls("shop/Item", "<init>", "()V")).unwrap()), vec!["shop/Cart.create 16 22", "shop/Plain.<init> 1 3", "shop/Special.<init> 1 3"]);
+	}
+
+	#[test]
+	fn test_field_access() {
+		let hierarchy = get_hierarchy();
+		let engine = QueryEngine::new(&hierarchy);
+		// Special.total reads the field through its own class
+		assert_eq!(sites(&engine.find(&Query::reads("shop/Item", "price")).unwrap()), vec!["shop/Cart.reprice 2 15", "shop/Item.total 1 7", "shop/Special.total 1 6"]);
+		assert_eq!(sites(&engine.find(&Query::writes("shop/Item", "price")).unwrap()), vec!["shop/Cart.reprice 7 15"]);
+		let any = Query::FieldAccess { class_name: "shop/Item".to_string(), name: "price".to_string(), descriptor: Some("J".to_string()), access: Access::Any };
+		assert!(engine.find(&any).unwrap().is_empty());
+	}
+
+	#[test]
+	fn test_new_and_ldc() {
+		let hierarchy = get_hierarchy();
+		let engine = QueryEngine::new(&hierarchy);
+		assert_eq!(sites(&engine.find(&Query::New("shop/Special".to_string())).unwrap()), vec!["shop/Cart.create 4 20"]);
+		let ldc = engine.find(&Query::Ldc("discount".to_string())).unwrap();
+		assert_eq!(ldc[0].to_string(), "shop/Cart.label(Lshop/Item;)Ljava/lang/String; pc 6 line 27: ldc \"discount\"");
+		assert!(engine.find(&Query::Ldc("missing".to_string())).unwrap().is_empty());
+	}
+
+	#[test]
+	fn test_patterns() {
+		let hierarchy = get_hierarchy();
+		let engine = QueryEngine::new(&hierarchy);
+		let query = Query::pattern("aload*; invokevirtual *.total()I\n iadd");
+		assert_eq!(query, Query::Pattern(vec!["aload*".to_string(), "invokevirtual *.total()I".to_string(), "iadd".to_string()]));
+		assert_eq!(sites(&engine.find(&query).unwrap()), vec!["shop/Cart.sum 8 8", "shop/Cart.sum 17 9", "shop/Cart.sum 26 10"]);
+		let constructions = engine.find(&Query::pattern("new shop/*; dup; ...; areturn")).unwrap();
+		assert_eq!(constructions.len(), 2);
+		assert_eq!(constructions[1].instructions, vec!["new shop/Item", "dup", "invokespecial shop/Item.<init>()V", "areturn"]);
+
+		let instructions = |text: &str| text.split(' ').map(str::to_string).collect::<Vec<_>>();
+		assert_eq!(sequence_matches(&instructions("a ... c"), &instructions("a b b c d")), Some(4));
+		assert_eq!(sequence_matches(&instructions("a ... c"), &instructions("a b d")), None);
+		assert_eq!(sequence_matches(&instructions("a? *"), &instructions("ab x")), Some(2));
+
+		assert!(wildcard_matches("ldc ?", "ldc é"));
+		assert!(wildcard_matches("*.*()*", "invokevirtual shop/Cart.total()I"));
+		assert!(!wildcard_matches("a*b*c*d", "abcab"));
+		assert!(!wildcard_matches(&"*a".repeat(20), &"a".repeat(19)));
+	}
+}
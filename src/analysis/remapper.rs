@@ -0,0 +1,487 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	path::Path};
+
+use thiserror::Error;
+
+use crate::{
+	analysis::mapping::Mapping,
+	class::{
+		archive::{self, ArchiveError, Entry},
+		attribute::{Annotation, Attribute, AttributeInfo, ElementValue},
+		class::Class,
+		constant_pool::{self, ConstantPoolItem},
+		constant_pool_builder::ConstantPoolBuilder,
+		signature},
+	error::EncodeError};
+
+const LAMBDA_METAFACTORY: &str = "java/lang/invoke/LambdaMetafactory";
+const SERVICES: &str = "META-INF/services/";
+const VERSIONS: &str = "META-INF/versions/";
+
+#[derive(Error, Debug)]
+pub enum RemapError {
+	#[error(transparent)]
+	Archive(#[from] ArchiveError),
+	#[error("could not remap {class_name}: {source}")]
+	Class { class_name: String, source: EncodeError },
+}
+
+/// Applies a `Mapping` to class files, rewriting every place a class or member name appears:
+/// Class, member reference, MethodType and invokedynamic constants, the class's own members,
+/// descriptors and generic signatures, annotations, InnerClasses and EnclosingMethod, local
+/// variable tables, and String constants holding a qualified class name.
+///
+/// Member mappings are looked up on the referenced class and then its supertypes, so references
+/// through a subclass pick up the mapping of the declaring class. Utf8 constants may be shared,
+/// so renamed values are added as new constants rather than edited in place.
+pub struct Remapper<'a> {
+	mapping: &'a Mapping,
+	fields: HashMap<(String, String, String), String>,
+	methods: HashMap<(String, String, String), String>,
+	supertypes: HashMap<String, Vec<String>>,
+}
+
+impl<'a> Remapper<'a> {
+	/// Create a remapper for `classes`, whose supertypes are used to find inherited member mappings.
+	pub fn new(mapping: &'a Mapping, classes: &[Class]) -> Remapper<'a> {
+		let mut fields = HashMap::new();
+		let mut methods = HashMap::new();
+		for class in &mapping.classes {
+			for field in &class.fields {
+				fields.entry((class.original.clone(), field.original.clone(), field.descriptor.clone())).or_insert_with(|| field.renamed.clone());
+			}
+			for method in class.methods.iter().filter(|method| !method.is_inlined()) {
+				methods.entry((class.original.clone(), method.original.clone(), method.descriptor.clone())).or_insert_with(|| method.renamed.clone());
+			}
+		}
+		let supertypes = classes.iter()
+			.map(|class| (class.name(), class.super_class_name().into_iter().chain(class.interface_names()).collect()))
+			.collect();
+		Remapper { mapping, fields, methods, supertypes }
+	}
+
+	/// The new name of a class or array class.
+	pub fn map_class(&self, class_name: &str) -> String {
+		if class_name.starts_with('[') {
+			return self.map_signature(class_name);
+		}
+		self.mapping.map_class(class_name).unwrap_or_else(|| class_name.to_string())
+	}
+
+	fn map_member(&self, table: &HashMap<(String, String, String), String>, owner: &str, name: &str, descriptor: &str) -> Option<String> {
+		let mut pending = vec![owner.to_string()];
+		let mut index = 0;
+		while let Some(class_name) = pending.get(index).cloned() {
+			index += 1;
+			if let Some(renamed) = table.get(&(class_name.clone(), name.to_string(), descriptor.to_string())) {
+				return Some(renamed.clone());
+			}
+			for supertype in self.supertypes.get(&class_name).into_iter().flatten() {
+				if !pending.contains(supertype) {
+					pending.push(supertype.clone());
+				}
+			}
+		}
+		None
+	}
+
+	pub fn map_field(&self, owner: &str, name: &str, descriptor: &str) -> String {
+		self.map_member(&self.fields, owner, name, descriptor).unwrap_or_else(|| name.to_string())
+	}
+
+	pub fn map_method(&self, owner: &str, name: &str, descriptor: &str) -> String {
+		if name.starts_with('<') {
+			return name.to_string();
+		}
+		self.map_member(&self.methods, owner, name, descriptor).unwrap_or_else(|| name.to_string())
+	}
+
+	/// Annotation elements are methods of the annotation type taking no arguments.
+	fn map_annotation_element(&self, annotation_type: &str, name: &str) -> String {
+		self.mapping.classes.iter()
+			.filter(|class| class.original == annotation_type)
+			.flat_map(|class| class.methods.iter())
+			.find(|method| method.original == name && method.descriptor.starts_with("()"))
+			.map(|method| method.renamed.clone())
+			.unwrap_or_else(|| name.to_string())
+	}
+
+	/// Rewrite the class names in a descriptor or generic signature (JVMS17 4.3, 4.7.9.1). Malformed
+	/// signatures are returned unchanged.
+	pub fn map_signature(&self, signature: &str) -> String {
+		signature::map_classes(signature, |class_name| self.map_class(class_name)).unwrap_or_else(|| signature.to_string())
+	}
+
+	/// The new value of a String constant if it holds a class name, dotted or in internal form.
+	/// Unqualified names are left alone as they are too likely to be ordinary text.
+	fn map_class_string(&self, value: &str) -> Option<String> {
+		let is_name = !value.is_empty() && value.chars().all(|c| c.is_alphanumeric() || "_$./".contains(c));
+		if !is_name || value.contains("..") || !value.contains(['.', '/']) {
+			return None;
+		}
+		if value.contains('/') {
+			return self.mapping.map_class(value);
+		}
+		self.mapping.map_class(&value.replace('.', "/")).map(|mapped| mapped.replace('/', "."))
+	}
+
+	/// A copy of `class` with every name rewritten.
+	pub fn remap_class(&self, class: &Class) -> Result<Class, EncodeError> {
+		let mut remapped = class.clone();
+		let this_class = class.name();
+		let mut pool = ConstantPoolBuilder::from(class.constant_pool.clone());
+
+		// Constants are rewritten in place once every new Utf8 and NameAndType constant is added.
+		// The pool builder only hands back existing constants of those two kinds, which are never
+		// rewritten, so no index it returns is replaced.
+		let mut rewritten = BTreeMap::new();
+		for (index, item) in &class.constant_pool {
+			let item = match item {
+				ConstantPoolItem::Class(constant) => {
+					let name_index = self.utf8(class, &mut pool, constant.index, |name| self.map_class(name))?;
+					ConstantPoolItem::Class(constant_pool::Class { index: name_index })
+				}
+				ConstantPoolItem::String(constant) => {
+					let value = class.get_utf8(constant.index).unwrap_or_default();
+					match self.map_class_string(&value) {
+						Some(mapped) => ConstantPoolItem::String(constant_pool::String { index: pool.add_utf8(&mapped)? }),
+						None => continue,
+					}
+				}
+				ConstantPoolItem::MethodType(constant) => {
+					let descriptor_index = self.utf8(class, &mut pool, constant.descriptor_index, |descriptor| self.map_signature(descriptor))?;
+					ConstantPoolItem::MethodType(constant_pool::MethodType { descriptor_index })
+				}
+				ConstantPoolItem::FieldRef(reference) => {
+					let name_and_type_index = self.member_name_and_type(class, &mut pool, reference.class_index, reference.name_and_type_index, true)?;
+					ConstantPoolItem::FieldRef(constant_pool::FieldRef { name_and_type_index, ..*reference })
+				}
+				ConstantPoolItem::MethodRef(reference) => {
+					let name_and_type_index = self.member_name_and_type(class, &mut pool, reference.class_index, reference.name_and_type_index, false)?;
+					ConstantPoolItem::MethodRef(constant_pool::MethodRef { name_and_type_index, ..*reference })
+				}
+				ConstantPoolItem::InterfaceMethodRef(reference) => {
+					let name_and_type_index = self.member_name_and_type(class, &mut pool, reference.class_index, reference.name_and_type_index, false)?;
+					ConstantPoolItem::InterfaceMethodRef(constant_pool::InterfaceMethodRef { name_and_type_index, ..*reference })
+				}
+				ConstantPoolItem::InvokeDynamic(call_site) => {
+					let Some((name, descriptor)) = class.get_name_and_type(call_site.name_and_type_index) else {
+						continue;
+					};
+					let name = self.lambda_method_name(class, call_site.bootstrap_method_attr_index, &name, &descriptor);
+					let name_and_type_index = self.name_and_type(class, &mut pool, call_site.name_and_type_index, &name, &self.map_signature(&descriptor))?;
+					ConstantPoolItem::InvokeDynamic(constant_pool::InvokeDynamic { name_and_type_index, ..*call_site })
+				}
+				ConstantPoolItem::Dynamic(constant) => {
+					let Some((name, descriptor)) = class.get_name_and_type(constant.name_and_type_index) else {
+						continue;
+					};
+					let name_and_type_index = self.name_and_type(class, &mut pool, constant.name_and_type_index, &name, &self.map_signature(&descriptor))?;
+					ConstantPoolItem::Dynamic(constant_pool::Dynamic { name_and_type_index, ..*constant })
+				}
+				_ => continue,
+			};
+			rewritten.insert(*index, item);
+		}
+
+		for field in &mut remapped.fields.fields {
+			let descriptor = class.get_utf8(field.descriptor_index).unwrap_or_default();
+			field.name_index = self.utf8(class, &mut pool, field.name_index, |name| self.map_field(&this_class, name, &descriptor))?;
+			field.descriptor_index = self.utf8(class, &mut pool, field.descriptor_index, |descriptor| self.map_signature(descriptor))?;
+			self.remap_attributes(class, &mut pool, &mut field.attributes)?;
+		}
+
+		for method in &mut remapped.methods.methods {
+			let descriptor = class.get_utf8(method.descriptor_index).unwrap_or_default();
+			method.name_index = self.utf8(class, &mut pool, method.name_index, |name| self.map_method(&this_class, name, &descriptor))?;
+			method.descriptor_index = self.utf8(class, &mut pool, method.descriptor_index, |descriptor| self.map_signature(descriptor))?;
+			self.remap_attributes(class, &mut pool, &mut method.attributes)?;
+		}
+
+		self.remap_attributes(class, &mut pool, &mut remapped.attributes.attributes)?;
+
+		remapped.constant_pool = pool.into_constants();
+		remapped.constant_pool.extend(rewritten);
+
+		// this_class, super_class and interfaces name Class constants, which now point elsewhere.
+		let renamed_class = |remapped: &Class, constant: &constant_pool::Class| -> constant_pool::Class {
+			match class.get_class_index(constant).and_then(|index| remapped.constant_pool.get(&index)) {
+				Some(ConstantPoolItem::Class(renamed)) => *renamed,
+				_ => *constant,
+			}
+		};
+		remapped.this_class = renamed_class(&remapped, &class.this_class);
+		remapped.super_class = class.super_class.as_ref().map(|constant| renamed_class(&remapped, constant));
+		remapped.interfaces = class.interfaces.iter().map(|constant| renamed_class(&remapped, constant)).collect();
+		Ok(remapped)
+	}
+
+	/// The index of a Utf8 constant holding the mapped value of the one at `index`.
+	fn utf8<F: FnOnce(&str) -> String>(&self, class: &Class, pool: &mut ConstantPoolBuilder, index: u16, map: F) -> Result<u16, EncodeError> {
+		let Some(value) = class.get_utf8(index) else {
+			return Ok(index);
+		};
+		let mapped = map(&value);
+		if mapped == value {
+			Ok(index)
+		} else {
+			pool.add_utf8(&mapped)
+		}
+	}
+
+	fn name_and_type(&self, class: &Class, pool: &mut ConstantPoolBuilder, index: u16, name: &str, descriptor: &str) -> Result<u16, EncodeError> {
+		if class.get_name_and_type(index).is_some_and(|(old_name, old_descriptor)| old_name == name && old_descriptor == descriptor) {
+			return Ok(index);
+		}
+		pool.add_name_and_type(name, descriptor)
+	}
+
+	fn member_name_and_type(&self, class: &Class, pool: &mut ConstantPoolBuilder, class_index: u16, name_and_type_index: u16, is_field: bool) -> Result<u16, EncodeError> {
+		let (Some(owner), Some((name, descriptor))) = (class.get_class_name_at(class_index), class.get_name_and_type(name_and_type_index)) else {
+			return Ok(name_and_type_index);
+		};
+		let name = if is_field { self.map_field(&owner, &name, &descriptor) } else { self.map_method(&owner, &name, &descriptor) };
+		self.name_and_type(class, pool, name_and_type_index, &name, &self.map_signature(&descriptor))
+	}
+
+	/// An invokedynamic call site bootstrapped by LambdaMetafactory is named after the method it
+	/// implements, which must be renamed along with the functional interface's method.
+	fn lambda_method_name(&self, class: &Class, bootstrap_index: u16, name: &str, descriptor: &str) -> String {
+		let Some(bootstrap) = class.bootstrap_methods().and_then(|attribute| attribute.bootstrap_methods.get(bootstrap_index as usize)) else {
+			return name.to_string();
+		};
+		let is_metafactory = match class.constant_pool.get(&bootstrap.bootstrap_method_ref) {
+			Some(ConstantPoolItem::MethodHandle(handle)) => class.get_member_ref(handle.reference_index).is_some_and(|method| method.class_name == LAMBDA_METAFACTORY),
+			_ => false,
+		};
+		let interface = descriptor.rsplit_once(")L").and_then(|(_, interface)| interface.strip_suffix(';'));
+		let method_type = bootstrap.bootstrap_arguments.first().and_then(|index| match class.constant_pool.get(index) {
+			Some(ConstantPoolItem::MethodType(method_type)) => class.get_utf8(method_type.descriptor_index),
+			_ => None,
+		});
+		match (is_metafactory, interface, method_type) {
+			(true, Some(interface), Some(method_type)) => self.map_method(interface, name, &method_type),
+			_ => name.to_string(),
+		}
+	}
+
+	fn remap_attributes(&self, class: &Class, pool: &mut ConstantPoolBuilder, attributes: &mut [Attribute]) -> Result<(), EncodeError> {
+		for attribute in attributes {
+			match &mut attribute.attribute_info {
+				AttributeInfo::Code(code) => self.remap_attributes(class, pool, &mut code.attributes)?,
+				AttributeInfo::Signature(signature) => {
+					signature.signature_index = self.utf8(class, pool, signature.signature_index, |signature| self.map_signature(signature))?;
+				}
+				AttributeInfo::LocalVariableTable(table) => {
+					for variable in &mut table.local_variables {
+						variable.descriptor_index = self.utf8(class, pool, variable.descriptor_index, |descriptor| self.map_signature(descriptor))?;
+					}
+				}
+				AttributeInfo::LocalVariableTypeTable(table) => {
+					for variable in &mut table.local_variable_types {
+						variable.signature_index = self.utf8(class, pool, variable.signature_index, |signature| self.map_signature(signature))?;
+					}
+				}
+				AttributeInfo::RuntimeVisibleAnnotations(annotations) | AttributeInfo::RuntimeInvisibleAnnotations(annotations) => {
+					for annotation in &mut annotations.annotations {
+						self.remap_annotation(class, pool, annotation)?;
+					}
+				}
+				AttributeInfo::RuntimeVisibleParameterAnnotations(parameters) | AttributeInfo::RuntimeInvisibleParameterAnnotations(parameters) => {
+					for annotation in parameters.parameter_annotations.iter_mut().flat_map(|annotations| annotations.annotations.iter_mut()) {
+						self.remap_annotation(class, pool, annotation)?;
+					}
+				}
+				AttributeInfo::AnnotationDefault(value) => self.remap_element_value(class, pool, value)?,
+				AttributeInfo::InnerClasses(inner_classes) => {
+					for inner_class in &mut inner_classes.classes {
+						if inner_class.inner_name_index == 0 {
+							continue;
+						}
+						let Some(inner_name) = class.get_class_name_at(inner_class.inner_class_info_index) else {
+							continue;
+						};
+						let mapped = self.map_class(&inner_name);
+						if mapped != inner_name {
+							let simple_name = mapped.rsplit(['$', '/']).next().unwrap_or(&mapped).to_string();
+							inner_class.inner_name_index = self.utf8(class, pool, inner_class.inner_name_index, |_| simple_name)?;
+						}
+					}
+				}
+				AttributeInfo::EnclosingMethod(enclosing_method) => {
+					if enclosing_method.method_index == 0 {
+						continue;
+					}
+					let (Some(owner), Some((name, descriptor))) = (class.get_class_name_at(enclosing_method.class_index), class.get_name_and_type(enclosing_method.method_index)) else {
+						continue;
+					};
+					let name = self.map_method(&owner, &name, &descriptor);
+					enclosing_method.method_index = self.name_and_type(class, pool, enclosing_method.method_index, &name, &self.map_signature(&descriptor))?;
+				}
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+
+	fn remap_annotation(&self, class: &Class, pool: &mut ConstantPoolBuilder, annotation: &mut Annotation) -> Result<(), EncodeError> {
+		let descriptor = class.get_utf8(annotation.type_index).unwrap_or_default();
+		let annotation_type = descriptor.trim_start_matches('L').trim_end_matches(';').to_string();
+		annotation.type_index = self.utf8(class, pool, annotation.type_index, |descriptor| self.map_signature(descriptor))?;
+		for pair in &mut annotation.element_value_pairs {
+			pair.element_name_index = self.utf8(class, pool, pair.element_name_index, |name| self.map_annotation_element(&annotation_type, name))?;
+			self.remap_element_value(class, pool, &mut pair.value)?;
+		}
+		Ok(())
+	}
+
+	fn remap_element_value(&self, class: &Class, pool: &mut ConstantPoolBuilder, value: &mut ElementValue) -> Result<(), EncodeError> {
+		match value {
+			ElementValue::Enum { type_name_index, const_name_index } => {
+				let descriptor = class.get_utf8(*type_name_index).unwrap_or_default();
+				let enum_type = descriptor.trim_start_matches('L').trim_end_matches(';').to_string();
+				*const_name_index = self.utf8(class, pool, *const_name_index, |name| self.map_field(&enum_type, name, &descriptor))?;
+				*type_name_index = self.utf8(class, pool, *type_name_index, |descriptor| self.map_signature(descriptor))?;
+			}
+			ElementValue::Class { class_info_index } => {
+				*class_info_index = self.utf8(class, pool, *class_info_index, |descriptor| self.map_signature(descriptor))?;
+			}
+			ElementValue::Annotation(annotation) => self.remap_annotation(class, pool, annotation)?,
+			ElementValue::Array { values, .. } => {
+				for value in values {
+					self.remap_element_value(class, pool, value)?;
+				}
+			}
+			_ => {}
+		}
+		Ok(())
+	}
+
+	/// Remap the entries of an archive: classes are rewritten and renamed, resources under
+	/// relocated packages moved, and service provider files renamed and rewritten.
+	pub fn remap_entries(&self, entries: &[Entry]) -> Result<Vec<Entry>, RemapError> {
+		let mut remapped = Vec::with_capacity(entries.len());
+		for entry in entries {
+			if entry.is_class() {
+				let class = entry.parse()?;
+				let class_name = class.name();
+				let prefix = entry.name.strip_suffix(&format!("{}.class", class_name)).unwrap_or("");
+				let renamed = self.remap_class(&class).map_err(|source| RemapError::Class { class_name: class_name.clone(), source })?;
+				let bytes = renamed.to_bytes().map_err(|source| ArchiveError::Write { name: entry.name.clone(), source })?;
+				remapped.push(Entry { name: format!("{}{}.class", prefix, renamed.name()), bytes });
+			} else if let Some(service) = entry.name.strip_prefix(SERVICES) {
+				let map_line = |line: &str| self.map_class_string(line.trim()).unwrap_or_else(|| line.to_string());
+				let contents = String::from_utf8_lossy(&entry.bytes).lines().map(map_line).collect::<Vec<_>>().join("\n");
+				remapped.push(Entry { name: format!("{}{}", SERVICES, map_line(service)), bytes: format!("{}\n", contents).into_bytes() });
+			} else if entry.name.starts_with("META-INF/") && !entry.name.starts_with(VERSIONS) {
+				remapped.push(entry.clone());
+			} else {
+				remapped.push(Entry { name: self.mapping.map_class(&entry.name).unwrap_or_else(|| entry.name.clone()), bytes: entry.bytes.clone() });
+			}
+		}
+		Ok(remapped)
+	}
+}
+
+/// Read the classes and resources at `input` (a directory or JAR), remap them and write a JAR to `output`.
+pub fn remap_archive(mapping: &Mapping, input: &Path, output: &Path) -> Result<(), RemapError> {
+	let entries = archive::read_entries(input)?;
+	let classes = entries.iter().filter(|entry| entry.is_class()).map(Entry::parse).collect::<Result<Vec<_>, _>>()?;
+	let remapped = Remapper::new(mapping, &classes).remap_entries(&entries)?;
+	Ok(archive::write_jar(output, &remapped)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+
+	use crate::{
+		analysis::{
+			hierarchy::ClassHierarchy,
+			mapping::Mapping,
+			remapper::{remap_archive, Remapper}},
+		class::{
+			attribute::{AttributeInfo, ElementValue},
+			class::Class,
+			constant_pool::ConstantPoolItem}};
+
+	const RESOURCES: &str = "tests/resources/remap";
+
+	fn get_mapping() -> Mapping {
+		Mapping::from_proguard(&std::fs::read_to_string(Path::new(RESOURCES).join("mapping.txt")).expect("Couldn't read mapping")).expect("Couldn't parse mapping")
+	}
+
+	#[test]
+	fn test_signatures() {
+		let mut mapping = get_mapping();
+		mapping.relocate("java/util", "shaded/util");
+		let remapper = Remapper::new(&mapping, &[]);
+		assert_eq!(remapper.map_signature("(Lcom/example/Main;[[ILjava/lang/String;)Lcom/example/Greeter;"), "(La/a;[[ILjava/lang/String;)La/c;");
+		assert_eq!(remapper.map_signature("Ljava/util/List<Lcom/example/Greeter;>;"), "Lshaded/util/List<La/c;>;");
+		assert_eq!(remapper.map_signature("<L:Lcom/example/Kind;T::Ljava/lang/Comparable<-TT;>;>(TL;)Ljava/util/Map<+TT;*>.Entry<TL;>;^Lcom/example/Main;"),
+			"<L:La/d;T::Ljava/lang/Comparable<-TT;>;>(TL;)Lshaded/util/Map<+TT;*>.Entry<TL;>;^La/a;");
+		assert_eq!(remapper.map_signature("<Été:Ljava/lang/Object;>(TÉté;)Lcom/example/Main;"), "<Été:Ljava/lang/Object;>(TÉté;)La/a;");
+		assert_eq!(remapper.map_signature("Lcom/example/Main.Inner;"), "La/a.b;");
+		assert_eq!(remapper.map_signature("[Lcom/example/Kind;"), "[La/d;");
+		assert_eq!(remapper.map_signature("Lbroken"), "Lbroken");
+	}
+
+	#[test]
+	fn test_remap_classes() {
+		let mapping = get_mapping();
+		let output = std::env::temp_dir().join(format!("steele-remapped-{}.jar", std::process::id()));
+		remap_archive(&mapping, Path::new(RESOURCES), &output).expect("Couldn't remap classes");
+		let hierarchy = ClassHierarchy::from_path(&output).expect("Couldn't read remapped jar");
+		std::fs::remove_file(&output).expect("Couldn't remove jar");
+
+		let names: Vec<String> = hierarchy.classes().map(Class::name).collect::<std::collections::BTreeSet<_>>().into_iter().collect();
+		assert_eq!(names, vec!["a/a", "a/b", "a/c", "a/d", "a/e"]);
+
+		let main = hierarchy.get("a/a").expect("Main was not renamed");
+		assert!(main.find_field("a", "Ljava/util/List;").is_some());
+		let method = main.find_method("main", "([Ljava/lang/String;)V").expect("main was renamed");
+		let strings: Vec<String> = main.constant_pool.values()
+			.filter_map(|item| match item { ConstantPoolItem::String(string) => main.get_utf8(string.index), _ => None })
+			.collect();
+		assert!(strings.contains(&"a.b".to_string()));
+		assert!(strings.contains(&"main".to_string()));
+		let references: Vec<_> = (1..main.constant_pool_count()).filter_map(|index| main.get_member_ref(index)).collect();
+		assert!(references.iter().any(|reference| reference.class_name == "a/c" && reference.name == "a"));
+		assert!(references.iter().any(|reference| reference.class_name == "a/a" && reference.name == "a" && reference.descriptor == "Ljava/util/List;"));
+
+		let annotation = method.attributes.iter().find_map(|attribute| match &attribute.attribute_info {
+			AttributeInfo::RuntimeVisibleAnnotations(annotations) => annotations.annotations.first().cloned(),
+			_ => None,
+		}).expect("main lost its annotation");
+		assert_eq!(main.get_utf8(annotation.type_index).as_deref(), Some("La/e;"));
+		let elements: Vec<(String, ElementValue)> = annotation.element_value_pairs.iter()
+			.map(|pair| (main.get_utf8(pair.element_name_index).unwrap_or_default(), pair.value.clone()))
+			.collect();
+		assert_eq!(elements[0].0, "a");
+		let ElementValue::Enum { type_name_index, const_name_index } = elements[0].1 else {
+			panic!("Expected an enum element");
+		};
+		assert_eq!((main.get_utf8(type_name_index).as_deref(), main.get_utf8(const_name_index).as_deref()), (Some("La/d;"), Some("LOUD")));
+		assert_eq!(elements[1].0, "b");
+
+		let inner = hierarchy.get("a/b").expect("Main$Inner was not renamed");
+		assert_eq!(inner.interface_names(), vec!["a/c"]);
+		assert!(inner.find_method("a", "(Ljava/lang/String;)Ljava/lang/String;").is_some());
+		let inner_name = inner.attributes.attributes.iter().find_map(|attribute| match &attribute.attribute_info {
+			AttributeInfo::InnerClasses(inner_classes) => inner_classes.classes.first().and_then(|class| inner.get_utf8(class.inner_name_index)),
+			_ => None,
+		});
+		assert_eq!(inner_name.as_deref(), Some("b"));
+	}
+
+	#[test]
+	fn test_over_long_name() {
+		let hierarchy = ClassHierarchy::from_path(Path::new(RESOURCES)).expect("Couldn't read classes");
+		let main = hierarchy.get("com/example/Main").expect("Main is missing");
+		let mut mapping = get_mapping();
+		mapping.relocate("java/lang", &"x".repeat(usize::from(u16::MAX)));
+		let error = Remapper::new(&mapping, &[]).remap_class(main).unwrap_err();
+		assert!(error.msg.ends_with("is too long for a Utf8 constant"), "{}", error.msg);
+	}
+}
@@ -0,0 +1,584 @@
+use std::{
+	collections::BTreeSet,
+	fmt::{self, Display, Formatter},
+	path::Path};
+
+use crate::{
+	analysis::{
+		call_graph::{Algorithm, CallGraphBuilder},
+		hierarchy::{ClassHierarchy, MethodLocation, OBJECT},
+		json::Json},
+	class::{
+		access::MethodAccessPropertyFlags,
+		attribute::{Attribute, AttributeInfo},
+		archive::{self, ArchiveError, Entry},
+		class::{Class, MemberRef},
+		constant_pool::ConstantPoolItem,
+		constant_pool_builder::ConstantPoolBuilder,
+		method::Method},
+	error::DecodeError,
+	isa::{instruction, opcode::Opcode}};
+
+const MAIN_NAME: &str = "main";
+const MAIN_DESCRIPTOR: &str = "([Ljava/lang/String;)V";
+
+/// Methods of java/lang/Object that library code calls on arbitrary objects.
+const OBJECT_METHODS: [(&str, &str); 5] = [
+	("equals", "(Ljava/lang/Object;)Z"),
+	("hashCode", "()I"),
+	("toString", "()Ljava/lang/String;"),
+	("clone", "()Ljava/lang/Object;"),
+	("finalize", "()V"),
+];
+
+/// Finds the classes, methods and fields of a hierarchy that can't be used from a set of roots.
+///
+/// Roots are main methods, explicit entry points, every member of classes matching a keep
+/// pattern, and members (or whole classes) carrying a keep annotation. From the roots the call
+/// graph is followed; every class, field and method named by reachable code is used, as are the
+/// supertypes of used classes. Methods of instantiated classes that may override a library
+/// method are kept too, since library code may call them.
+pub struct Shrinker<'a> {
+	hierarchy: &'a ClassHierarchy,
+	algorithm: Algorithm,
+	keep_main_methods: bool,
+	entry_points: Vec<MethodLocation>,
+	class_patterns: Vec<String>,
+	annotations: Vec<String>,
+}
+
+/// The outcome of a `Shrinker` run. Unused methods and fields are only listed for used classes;
+/// the members of an unused class are implied.
+#[derive(Debug, Default)]
+pub struct UsageReport {
+	pub classes: BTreeSet<String>,
+	pub methods: BTreeSet<MethodLocation>,
+	pub fields: BTreeSet<MemberRef>,
+	pub unused_classes: BTreeSet<String>,
+	pub unused_methods: BTreeSet<MethodLocation>,
+	pub unused_fields: BTreeSet<MemberRef>,
+}
+
+/// Whether a class name matches a keep pattern. Either `.` or `/` may separate packages; `*`
+/// matches within a package, `**` across packages and `?` any one character.
+pub fn matches_pattern(pattern: &str, class_name: &str) -> bool {
+	fn matches(pattern: &[u8], name: &[u8]) -> bool {
+		match pattern {
+			[] => name.is_empty(),
+			[b'*', b'*', rest @ ..] => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+			[b'*', rest @ ..] => (0..=name.len())
+				.take_while(|skip| *skip == 0 || name[skip - 1] != b'/')
+				.any(|skip| matches(rest, &name[skip..])),
+			[b'?', rest @ ..] => !name.is_empty() && name[0] != b'/' && matches(rest, &name[1..]),
+			[expected, rest @ ..] => name.first() == Some(expected) && matches(rest, &name[1..]),
+		}
+	}
+	matches(pattern.replace('.', "/").as_bytes(), class_name.as_bytes())
+}
+
+/// The internal names of the classes mentioned in a field or method descriptor.
+pub(crate) fn descriptor_classes(descriptor: &str) -> Vec<String> {
+	let mut classes = Vec::new();
+	let mut rest = descriptor;
+	while let Some(start) = rest.find('L') {
+		let Some(end) = rest[start..].find(';') else {
+			break;
+		};
+		classes.push(rest[start + 1..start + end].to_string());
+		rest = &rest[start + end + 1..];
+	}
+	classes
+}
+
+fn method_location(class: &Class, method: &Method) -> Option<MethodLocation> {
+	Some(MethodLocation::new(&class.name(), &class.get_utf8(method.name_index)?, &class.get_utf8(method.descriptor_index)?))
+}
+
+fn is_virtual(method: &Method, name: &str) -> bool {
+	method.access_flags & (MethodAccessPropertyFlags::Static as u16 | MethodAccessPropertyFlags::Private as u16) == 0
+		&& !name.starts_with('<')
+}
+
+impl<'a> Shrinker<'a> {
+	pub fn new(hierarchy: &'a ClassHierarchy) -> Shrinker<'a> {
+		Shrinker {
+			hierarchy,
+			algorithm: Algorithm::Rta,
+			keep_main_methods: true,
+			entry_points: Vec::new(),
+			class_patterns: Vec::new(),
+			annotations: Vec::new(),
+		}
+	}
+
+	/// The call graph algorithm, RTA by default.
+	pub fn algorithm(mut self, algorithm: Algorithm) -> Shrinker<'a> {
+		self.algorithm = algorithm;
+		self
+	}
+
+	/// Whether `public static void main(String[])` methods are roots, which they are by default.
+	pub fn keep_main_methods(mut self, keep: bool) -> Shrinker<'a> {
+		self.keep_main_methods = keep;
+		self
+	}
+
+	pub fn entry_point(mut self, method: MethodLocation) -> Shrinker<'a> {
+		self.entry_points.push(method);
+		self
+	}
+
+	/// Keep every member of the classes matching `pattern` (see `matches_pattern`).
+	pub fn keep_classes(mut self, pattern: &str) -> Shrinker<'a> {
+		self.class_patterns.push(pattern.to_string());
+		self
+	}
+
+	/// Keep members annotated with the annotation `annotation`, and every member of classes annotated with it.
+	pub fn keep_annotated(mut self, annotation: &str) -> Shrinker<'a> {
+		self.annotations.push(annotation.replace('.', "/"));
+		self
+	}
+
+	fn is_keep_annotated(&self, class: &Class, attributes: &[Attribute]) -> bool {
+		class.get_annotation_types(attributes).iter().any(|annotation| self.annotations.contains(annotation))
+	}
+
+	/// The roots of the analysis: methods to start the call graph from, fields and classes kept outright.
+	fn roots(&self) -> (BTreeSet<MethodLocation>, BTreeSet<MemberRef>, BTreeSet<String>) {
+		let mut methods: BTreeSet<MethodLocation> = self.entry_points.iter().cloned().collect();
+		let mut fields = BTreeSet::new();
+		let mut classes = BTreeSet::new();
+		for class in self.hierarchy.classes() {
+			let class_name = class.name();
+			let keep_all = self.class_patterns.iter().any(|pattern| matches_pattern(pattern, &class_name))
+				|| self.is_keep_annotated(class, &class.attributes.attributes);
+			if keep_all {
+				classes.insert(class_name.clone());
+			}
+			for method in &class.methods.methods {
+				let Some(location) = method_location(class, method) else {
+					continue;
+				};
+				let is_main = self.keep_main_methods && location.name == MAIN_NAME && location.descriptor == MAIN_DESCRIPTOR
+					&& method.access_flags & MethodAccessPropertyFlags::Static as u16 != 0;
+				if keep_all || is_main || self.is_keep_annotated(class, &method.attributes) {
+					methods.insert(location);
+				}
+			}
+			for field in &class.fields.fields {
+				if !keep_all && !self.is_keep_annotated(class, &field.attributes) {
+					continue;
+				}
+				if let (Some(name), Some(descriptor)) = (class.get_utf8(field.name_index), class.get_utf8(field.descriptor_index)) {
+					fields.insert(MemberRef { class_name: class_name.clone(), name, descriptor });
+				}
+			}
+		}
+		(methods, fields, classes)
+	}
+
+	/// The virtual methods of `class_name` and its supertypes that library code might call on
+	/// an instance: overrides of java/lang/Object methods, and every virtual method if the class
+	/// has a supertype outside the hierarchy, whose methods are unknown.
+	fn library_callbacks(&self, class_name: &str) -> BTreeSet<MethodLocation> {
+		let mut callbacks = BTreeSet::new();
+		let mut program_types = vec![class_name.to_string()];
+		let mut extends_library = false;
+		let mut index = 0;
+		while let Some(type_name) = program_types.get(index).cloned() {
+			index += 1;
+			let Some(class) = self.hierarchy.get(&type_name) else {
+				continue;
+			};
+			for supertype in class.super_class_name().into_iter().chain(class.interface_names()) {
+				if self.hierarchy.contains(&supertype) {
+					if !program_types.contains(&supertype) {
+						program_types.push(supertype);
+					}
+				} else if supertype != OBJECT {
+					extends_library = true;
+				}
+			}
+		}
+		for class_name in program_types {
+			let Some(class) = self.hierarchy.get(&class_name) else {
+				continue;
+			};
+			for method in &class.methods.methods {
+				let Some(location) = method_location(class, method) else {
+					continue;
+				};
+				let overrides_object = OBJECT_METHODS.contains(&(location.name.as_str(), location.descriptor.as_str()));
+				if is_virtual(method, &location.name) && (extends_library || overrides_object) {
+					callbacks.insert(location);
+				}
+			}
+		}
+		callbacks
+	}
+
+	pub fn analyse(&self) -> Result<UsageReport, DecodeError> {
+		let (mut root_methods, root_fields, root_classes) = self.roots();
+		let mut report = UsageReport { classes: root_classes, fields: root_fields, ..UsageReport::default() };
+		if root_methods.is_empty() && report.classes.is_empty() && report.fields.is_empty() {
+			return Ok(report.with_unused(self.hierarchy));
+		}
+
+		// Library callbacks depend on which classes are instantiated, which depends on what is
+		// reachable; grow the roots until they stop changing.
+		let graph = loop {
+			let graph = CallGraphBuilder::new(self.hierarchy)
+				.algorithm(self.algorithm)
+				.entry_points(root_methods.iter().cloned())
+				.build()?;
+			let mut instantiated: BTreeSet<String> = report.classes.clone();
+			for method in graph.nodes() {
+				self.scan_instantiations(method, &mut instantiated)?;
+			}
+			let callbacks: BTreeSet<MethodLocation> = instantiated.iter().flat_map(|class_name| self.library_callbacks(class_name)).collect();
+			if callbacks.is_subset(&root_methods) {
+				break graph;
+			}
+			root_methods.extend(callbacks);
+		};
+
+		for method in graph.nodes() {
+			if self.hierarchy.contains(&method.class_name) {
+				report.methods.insert(method.clone());
+			}
+			self.scan_references(method, &mut report)?;
+		}
+		let members: Vec<(String, String)> = report.methods.iter().map(|method| (method.class_name.clone(), method.descriptor.clone()))
+			.chain(report.fields.iter().map(|field| (field.class_name.clone(), field.descriptor.clone())))
+			.collect();
+		for (class_name, descriptor) in members {
+			report.classes.insert(class_name);
+			report.classes.extend(descriptor_classes(&descriptor));
+		}
+		let mut supertypes = BTreeSet::new();
+		for class_name in &report.classes {
+			supertypes.extend(self.hierarchy.superclasses(class_name).unwrap_or_default());
+			supertypes.extend(self.hierarchy.interfaces(class_name).unwrap_or_default());
+		}
+		report.classes.extend(supertypes);
+		report.classes.retain(|class_name| self.hierarchy.contains(class_name));
+		Ok(report.with_unused(self.hierarchy))
+	}
+
+	fn scan_instantiations(&self, method: &MethodLocation, instantiated: &mut BTreeSet<String>) -> Result<(), DecodeError> {
+		let Some(class) = self.hierarchy.get(&method.class_name) else {
+			return Ok(());
+		};
+		let Some(code) = class.find_method(&method.name, &method.descriptor).and_then(Method::code) else {
+			return Ok(());
+		};
+		for (_, instruction) in instruction::decode(&code.code)? {
+			if instruction.opcode() == Opcode::New
+				&& let Some(class_name) = instruction.constant_pool_index().and_then(|index| class.get_class_name_at(index)) {
+				instantiated.insert(class_name);
+			}
+		}
+		Ok(())
+	}
+
+	/// Record the classes, fields and resolved methods named by a reachable method's code. The
+	/// resolved methods matter even where dispatch never reaches them: removing an abstract
+	/// declaration would make the call site fail to link.
+	fn scan_references(&self, method: &MethodLocation, report: &mut UsageReport) -> Result<(), DecodeError> {
+		let Some(class) = self.hierarchy.get(&method.class_name) else {
+			return Ok(());
+		};
+		let Some(code) = class.find_method(&method.name, &method.descriptor).and_then(Method::code) else {
+			return Ok(());
+		};
+		for handler in &code.handlers {
+			report.classes.extend(class.get_class_name_at(handler.catch_type_index));
+		}
+		for (_, instruction) in instruction::decode(&code.code)? {
+			let Some(index) = instruction.constant_pool_index() else {
+				continue;
+			};
+			match class.constant_pool.get(&index) {
+				Some(ConstantPoolItem::Class(_)) => {
+					if let Some(class_name) = class.get_class_name_at(index) {
+						let element = class_name.trim_start_matches('[');
+						report.classes.extend(descriptor_classes(element));
+						report.classes.insert(class_name);
+					}
+				}
+				Some(ConstantPoolItem::FieldRef(_)) => {
+					let Some(field) = class.get_member_ref(index) else {
+						continue;
+					};
+					if let Ok(Some(declaring_class)) = self.hierarchy.resolve_field(&field.class_name, &field.name, &field.descriptor) {
+						report.fields.insert(MemberRef { class_name: declaring_class, ..field.clone() });
+					}
+					report.classes.insert(field.class_name);
+				}
+				Some(ConstantPoolItem::MethodRef(_) | ConstantPoolItem::InterfaceMethodRef(_)) => {
+					let Some(reference) = class.get_member_ref(index) else {
+						continue;
+					};
+					let resolved = match self.hierarchy.get(&reference.class_name) {
+						Some(owner) if owner.is_interface() => self.hierarchy.resolve_interface_method(&reference.class_name, &reference.name, &reference.descriptor),
+						Some(_) => self.hierarchy.resolve_method(&reference.class_name, &reference.name, &reference.descriptor),
+						None => Ok(None),
+					};
+					if let Ok(Some(resolved)) = resolved {
+						report.methods.insert(resolved);
+					}
+					report.classes.insert(reference.class_name);
+				}
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+}
+
+impl UsageReport {
+	fn with_unused(mut self, hierarchy: &ClassHierarchy) -> UsageReport {
+		for class in hierarchy.classes() {
+			let class_name = class.name();
+			if !self.classes.contains(&class_name) {
+				self.unused_classes.insert(class_name);
+				continue;
+			}
+			for method in &class.methods.methods {
+				if let Some(location) = method_location(class, method)
+					&& !self.methods.contains(&location) {
+					self.unused_methods.insert(location);
+				}
+			}
+			for field in &class.fields.fields {
+				if let (Some(name), Some(descriptor)) = (class.get_utf8(field.name_index), class.get_utf8(field.descriptor_index)) {
+					let field = MemberRef { class_name: class_name.clone(), name, descriptor };
+					if !self.fields.contains(&field) {
+						self.unused_fields.insert(field);
+					}
+				}
+			}
+		}
+		self
+	}
+
+	/// A copy of `class` without its unused methods and fields, or None if the class is unused.
+	///
+	/// NestMembers and InnerClasses entries naming unused classes are dropped too, and so are the
+	/// constants nothing refers to any more. A class carrying an attribute whose constants cannot be
+	/// renumbered (see [ConstantPoolBuilder::compact]) keeps its whole constant pool.
+	pub fn shrink(&self, class: &Class) -> Option<Class> {
+		let class_name = class.name();
+		if !self.classes.contains(&class_name) {
+			return None;
+		}
+		let mut shrunken = class.clone();
+		shrunken.methods.methods.retain(|method| method_location(class, method).is_none_or(|location| !self.unused_methods.contains(&location)));
+		shrunken.methods.method_count = shrunken.methods.methods.len() as u16;
+		shrunken.fields.fields.retain(|field| {
+			match (class.get_utf8(field.name_index), class.get_utf8(field.descriptor_index)) {
+				(Some(name), Some(descriptor)) => !self.unused_fields.contains(&MemberRef { class_name: class_name.clone(), name, descriptor }),
+				_ => true,
+			}
+		});
+		shrunken.fields.fields_count = shrunken.fields.fields.len() as u16;
+
+		let removed = |index: u16| class.get_class_name_at(index).is_some_and(|name| self.unused_classes.contains(&name));
+		for attribute in &mut shrunken.attributes.attributes {
+			match &mut attribute.attribute_info {
+				AttributeInfo::NestMembers(members) => {
+					members.classes.retain(|index| !removed(*index));
+					members.number_of_classes = members.classes.len() as u16;
+					attribute.length = 2 + 2 * u32::from(members.number_of_classes);
+				}
+				AttributeInfo::InnerClasses(inner_classes) => {
+					inner_classes.classes.retain(|inner| !removed(inner.inner_class_info_index) && !removed(inner.outer_class_info_index));
+					inner_classes.number_of_classes = inner_classes.classes.len() as u16;
+					attribute.length = 2 + 8 * u32::from(inner_classes.number_of_classes);
+				}
+				_ => {}
+			}
+		}
+
+		let mut compacted = shrunken.clone();
+		Some(match ConstantPoolBuilder::compact(&mut compacted) {
+			Ok(()) => compacted,
+			Err(_) => shrunken,
+		})
+	}
+
+	/// Write a copy of an archive's entries to a new JAR with unused classes and members removed.
+	///
+	/// Other resources are copied unchanged, except for JAR signature files, which no longer
+	/// match the contents.
+	pub fn write_shrunken_jar(&self, entries: &[Entry], output: &Path) -> Result<(), ArchiveError> {
+		let mut shrunken = Vec::new();
+		for entry in entries {
+			if entry.is_class() {
+				let Some(class) = self.shrink(&entry.parse()?) else {
+					continue;
+				};
+				let bytes = class.to_bytes().map_err(|source| ArchiveError::Write { name: entry.name.clone(), source })?;
+				shrunken.push(Entry { name: entry.name.clone(), bytes });
+			} else if !is_signature_file(&entry.name) {
+				shrunken.push(entry.clone());
+			}
+		}
+		archive::write_jar(output, &shrunken)
+	}
+
+	pub fn to_json(&self) -> Json {
+		let methods = |methods: &BTreeSet<MethodLocation>| Json::array(methods.iter().map(|method| format!("{}.{}{}", method.class_name, method.name, method.descriptor)));
+		let fields = |fields: &BTreeSet<MemberRef>| Json::array(fields.iter().map(|field| format!("{}.{}:{}", field.class_name, field.name, field.descriptor)));
+		Json::object([
+			("used", Json::object([
+				("classes", Json::array(&self.classes)),
+				("methods", methods(&self.methods)),
+				("fields", fields(&self.fields)),
+			])),
+			("unused", Json::object([
+				("classes", Json::array(&self.unused_classes)),
+				("methods", methods(&self.unused_methods)),
+				("fields", fields(&self.unused_fields)),
+			])),
+		])
+	}
+}
+
+fn is_signature_file(name: &str) -> bool {
+	let upper = name.to_ascii_uppercase();
+	upper.starts_with("META-INF/") && [".SF", ".RSA", ".DSA", ".EC"].iter().any(|extension| upper.ends_with(extension))
+}
+
+impl Display for UsageReport {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{} classes, {} methods and {} fields used", self.classes.len(), self.methods.len(), self.fields.len())?;
+		for class_name in &self.unused_classes {
+			writeln!(f, "unused class {}", class_name)?;
+		}
+		for method in &self.unused_methods {
+			writeln!(f, "unused method {}.{}{}", method.class_name, method.name, method.descriptor)?;
+		}
+		for field in &self.unused_fields {
+			writeln!(f, "unused field {}.{}:{}", field.class_name, field.name, field.descriptor)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::BTreeSet, path::Path};
+
+	use crate::{
+		analysis::{
+			hierarchy::{ClassHierarchy, MethodLocation},
+			shrinker::{matches_pattern, Shrinker}},
+		class::{
+			archive,
+			attribute::{Attribute, AttributeInfo, InnerClass, InnerClasses, NestMembers},
+			class::MemberRef,
+			constant_pool_builder::ConstantPoolBuilder}};
+
+	const RESOURCES: &str = "tests/resources/shrink";
+
+	fn get_hierarchy() -> ClassHierarchy {
+		ClassHierarchy::from_path(Path::new(RESOURCES)).expect("Couldn't read classes")
+	}
+
+	#[test]
+	fn test_patterns() {
+		assert!(matches_pattern("com.example.*", "com/example/Main"));
+		assert!(!matches_pattern("com.example.*", "com/example/impl/Main"));
+		assert!(matches_pattern("com/**", "com/example/impl/Main"));
+		assert!(matches_pattern("**Test", "com/example/MainTest"));
+		assert!(matches_pattern("Ma?n", "Main"));
+		assert!(!matches_pattern("Main", "Maine"));
+	}
+
+	#[test]
+	fn test_unused() {
+		let hierarchy = get_hierarchy();
+		let report = Shrinker::new(&hierarchy).keep_annotated("Keep").analyse().expect("Couldn't analyse");
+		assert_eq!(report.unused_classes, BTreeSet::from(["Keep".to_string(), "Orphan".to_string()]));
+		assert_eq!(report.unused_methods, BTreeSet::from([
+			MethodLocation::new("Api", "<init>", "()V"),
+			MethodLocation::new("Api", "internal", "()V"),
+			MethodLocation::new("Main", "<init>", "()V"),
+			MethodLocation::new("Main", "neverCalled", "()V"),
+			MethodLocation::new("Printer", "unusedMethod", "()V"),
+			MethodLocation::new("Task", "cancel", "()V"),
+			MethodLocation::new("Worker", "cancel", "()V"),
+			MethodLocation::new("Worker", "helper", "()V"),
+		]));
+		assert_eq!(report.unused_fields, BTreeSet::from([MemberRef { class_name: "Worker".to_string(), name: "unusedField".to_string(), descriptor: "I".to_string() }]));
+		assert!(report.methods.contains(&MethodLocation::new("Printer", "toString", "()Ljava/lang/String;")));
+		assert!(report.methods.contains(&MethodLocation::new("Plugin", "start", "()V")));
+		assert!(report.methods.contains(&MethodLocation::new("Task", "execute", "()V")));
+
+		let report = Shrinker::new(&hierarchy).keep_main_methods(false).keep_classes("Orph*").analyse().expect("Couldn't analyse");
+		assert_eq!(report.classes, BTreeSet::from(["Orphan".to_string()]));
+	}
+
+	#[test]
+	fn test_shrunken_jar() {
+		let hierarchy = get_hierarchy();
+		let report = Shrinker::new(&hierarchy).analyse().expect("Couldn't analyse");
+		let entries = archive::read_entries(Path::new(RESOURCES)).expect("Couldn't read entries");
+		let output = std::env::temp_dir().join(format!("steele-shrunken-{}.jar", std::process::id()));
+		report.write_shrunken_jar(&entries, &output).expect("Couldn't write jar");
+
+		let shrunken = ClassHierarchy::from_path(&output).expect("Couldn't read shrunken jar");
+		std::fs::remove_file(&output).expect("Couldn't remove jar");
+		assert!(shrunken.get("Orphan").is_none());
+		let worker = shrunken.get("Worker").expect("Worker was removed");
+		assert!(worker.find_method("helper", "()V").is_none());
+		assert!(worker.find_method("execute", "()V").is_some());
+		assert!(worker.find_field("unusedField", "I").is_none());
+		assert!(worker.find_field("count", "I").is_some());
+		let names: Vec<String> = worker.constant_pool.keys().filter_map(|index| worker.get_utf8(*index)).collect();
+		assert!(names.contains(&"count".to_string()));
+		assert!(!names.contains(&"helper".to_string()) && !names.contains(&"unusedField".to_string()));
+	}
+
+	#[test]
+	fn test_shrunken_nest() {
+		let hierarchy = get_hierarchy();
+		let report = Shrinker::new(&hierarchy).analyse().expect("Couldn't analyse");
+		let mut main = hierarchy.get("Main").expect("Main is missing").clone();
+		let mut pool = ConstantPoolBuilder::from(main.constant_pool.clone());
+		let (printer, orphan) = (pool.add_class("Printer").unwrap(), pool.add_class("Orphan").unwrap());
+		let inner = |inner_class_info_index| InnerClass { inner_class_info_index, outer_class_info_index: 0, inner_name_index: 0, inner_class_access_flags: 0 };
+		let attributes = [
+			("NestMembers", 6, AttributeInfo::NestMembers(NestMembers { number_of_classes: 2, classes: vec![printer, orphan] })),
+			("InnerClasses", 18, AttributeInfo::InnerClasses(InnerClasses { number_of_classes: 2, classes: vec![inner(printer), inner(orphan)] })),
+		];
+		for (name, length, attribute_info) in attributes {
+			let name_index = pool.add_utf8(name).unwrap();
+			main.attributes.attributes.push(Attribute { name_index, length, attribute_info });
+		}
+		main.attributes.attribute_count = main.attributes.attributes.len() as u16;
+		main.constant_pool = pool.into_constants();
+
+		let shrunken = report.shrink(&main).expect("Main was removed");
+		let names: Vec<String> = shrunken.constant_pool.keys().filter_map(|index| shrunken.get_class_name_at(*index)).collect();
+		assert!(names.contains(&"Printer".to_string()) && !names.contains(&"Orphan".to_string()));
+		assert!(shrunken.constant_pool.len() < main.constant_pool.len());
+		for attribute in &shrunken.attributes.attributes {
+			match &attribute.attribute_info {
+				AttributeInfo::NestMembers(members) => {
+					assert_eq!(members.classes.iter().filter_map(|index| shrunken.get_class_name_at(*index)).collect::<Vec<_>>(), ["Printer"]);
+					assert_eq!(attribute.length, 4);
+				}
+				AttributeInfo::InnerClasses(inner_classes) => {
+					assert_eq!(inner_classes.classes.iter().filter_map(|inner| shrunken.get_class_name_at(inner.inner_class_info_index)).collect::<Vec<_>>(), ["Printer"]);
+					assert_eq!(attribute.length, 10);
+				}
+				_ => {}
+			}
+		}
+		let entry = archive::Entry { name: "Main.class".to_string(), bytes: shrunken.to_bytes().unwrap() };
+		assert_eq!(entry.parse().unwrap().to_bytes().unwrap(), entry.bytes);
+	}
+}
@@ -0,0 +1,390 @@
+use std::{
+	collections::BTreeMap,
+	fmt::{self, Display, Formatter},
+	path::Path};
+
+use thiserror::Error;
+
+use crate::{
+	analysis::{hierarchy::MethodLocation, json::Json},
+	class::{
+		archive::{self, ArchiveError},
+		attribute::{Attribute, AttributeInfo},
+		class::Class},
+	error::DecodeError,
+	isa::{instruction, opcode::Opcode}};
+
+/// The most bytes of code a method can have (JVMS17 4.7.3).
+pub const MAX_CODE_LENGTH: u32 = 65535;
+
+/// Methods with at least this much code are reported by default: 90% of the limit.
+const LARGE_METHOD_THRESHOLD: u32 = MAX_CODE_LENGTH / 10 * 9;
+
+/// How many of the largest classes are reported by default.
+const LARGEST_CLASSES: usize = 10;
+
+/// The width of the longest bar in the opcode histogram.
+const HISTOGRAM_WIDTH: u64 = 40;
+
+#[derive(Error, Debug)]
+pub enum StatisticsError {
+	#[error(transparent)]
+	Archive(#[from] ArchiveError),
+	#[error("could not decode {name}: {source}")]
+	Decode { name: String, source: DecodeError },
+}
+
+/// A summary of a set of sizes. Buckets count the sizes up to each power of two (the key), so a
+/// size of 100 falls in bucket 128.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Distribution {
+	pub count: usize,
+	pub total: u64,
+	pub min: u32,
+	pub max: u32,
+	pub median: u32,
+	pub p90: u32,
+	pub buckets: BTreeMap<u32, usize>,
+}
+
+impl Distribution {
+	pub fn new(values: &[u32]) -> Distribution {
+		if values.is_empty() {
+			return Distribution::default();
+		}
+		let mut sorted = values.to_vec();
+		sorted.sort_unstable();
+		let percentile = |percent: usize| sorted[(sorted.len() - 1) * percent / 100];
+		let mut buckets: BTreeMap<u32, usize> = BTreeMap::new();
+		for value in &sorted {
+			*buckets.entry(value.next_power_of_two()).or_default() += 1;
+		}
+		Distribution {
+			count: sorted.len(),
+			total: sorted.iter().map(|value| u64::from(*value)).sum(),
+			min: sorted[0],
+			max: sorted[sorted.len() - 1],
+			median: percentile(50),
+			p90: percentile(90),
+			buckets,
+		}
+	}
+
+	pub fn mean(&self) -> f64 {
+		if self.count == 0 { 0.0 } else { self.total as f64 / self.count as f64 }
+	}
+
+	fn to_json(&self) -> Json {
+		Json::object([
+			("count", Json::from(self.count)),
+			("total", Json::Int(self.total as i64)),
+			("min", Json::from(self.min)),
+			("max", Json::from(self.max)),
+			("mean", Json::from(self.mean())),
+			("median", Json::from(self.median)),
+			("p90", Json::from(self.p90)),
+			("buckets", Json::object(self.buckets.iter().map(|(bound, count)| (bound.to_string(), Json::from(*count))))),
+		])
+	}
+}
+
+impl Display for Distribution {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "count {}, min {}, median {}, mean {:.1}, p90 {}, max {}", self.count, self.min, self.median, self.mean(), self.p90, self.max)
+	}
+}
+
+/// Size and content statistics over a set of class files: versions, constant pool and code
+/// sizes, an opcode histogram and attribute usage.
+#[derive(Debug)]
+pub struct ClassStatistics {
+	large_method_threshold: u32,
+	largest_count: usize,
+	versions: BTreeMap<u16, usize>,
+	pool_sizes: Vec<u32>,
+	code_sizes: Vec<u32>,
+	large_methods: Vec<(MethodLocation, u32)>,
+	opcodes: [u64; 256],
+	attributes: BTreeMap<String, usize>,
+	class_sizes: Vec<(String, usize)>,
+}
+
+impl Default for ClassStatistics {
+	fn default() -> Self {
+		ClassStatistics {
+			large_method_threshold: LARGE_METHOD_THRESHOLD,
+			largest_count: LARGEST_CLASSES,
+			versions: BTreeMap::new(),
+			pool_sizes: Vec::new(),
+			code_sizes: Vec::new(),
+			large_methods: Vec::new(),
+			opcodes: [0; 256],
+			attributes: BTreeMap::new(),
+			class_sizes: Vec::new(),
+		}
+	}
+}
+
+impl ClassStatistics {
+
+	pub fn new() -> ClassStatistics {
+		ClassStatistics::default()
+	}
+
+	/// Report methods with at least this many bytes of code.
+	pub fn large_method_threshold(mut self, threshold: u32) -> ClassStatistics {
+		self.large_method_threshold = threshold;
+		self
+	}
+
+	/// Report this many of the largest classes.
+	pub fn largest_class_count(mut self, count: usize) -> ClassStatistics {
+		self.largest_count = count;
+		self
+	}
+
+	/// Add every class file in a directory or JAR.
+	pub fn add_path(&mut self, path: &Path) -> Result<(), StatisticsError> {
+		for entry in archive::read_entries(path)?.iter().filter(|entry| entry.is_class()) {
+			self.add(&entry.parse()?, entry.bytes.len())
+				.map_err(|source| StatisticsError::Decode { name: entry.name.clone(), source })?;
+		}
+		Ok(())
+	}
+
+	/// Add a class whose class file is `size` bytes long. Nothing is added if any of its code can't
+	/// be decoded.
+	pub fn add(&mut self, class: &Class, size: usize) -> Result<(), DecodeError> {
+		let mut opcodes = [0u64; 256];
+		for code in class.methods.methods.iter().filter_map(|method| method.code()) {
+			for (_, instruction) in instruction::decode(&code.code)? {
+				opcodes[u8::from(instruction.opcode()) as usize] += 1;
+			}
+		}
+		for (total, count) in self.opcodes.iter_mut().zip(opcodes) {
+			*total += count;
+		}
+		let class_name = class.name();
+		*self.versions.entry(class.major_version).or_default() += 1;
+		self.pool_sizes.push(u32::from(class.constant_pool_count().saturating_sub(1)));
+		self.class_sizes.push((class_name.clone(), size));
+		self.count_attributes(class, &class.attributes.attributes);
+		for field in &class.fields.fields {
+			self.count_attributes(class, &field.attributes);
+		}
+		for method in &class.methods.methods {
+			self.count_attributes(class, &method.attributes);
+			let Some(code) = method.code() else {
+				continue;
+			};
+			let size = code.code.len() as u32;
+			self.code_sizes.push(size);
+			if size >= self.large_method_threshold {
+				let name = class.get_utf8(method.name_index).unwrap_or_default();
+				let descriptor = class.get_utf8(method.descriptor_index).unwrap_or_default();
+				self.large_methods.push((MethodLocation::new(&class_name, &name, &descriptor), size));
+			}
+		}
+		Ok(())
+	}
+
+	fn count_attributes(&mut self, class: &Class, attributes: &[Attribute]) {
+		for attribute in attributes {
+			let name = class.get_utf8(attribute.name_index).unwrap_or_else(|| format!("#{}", attribute.name_index));
+			*self.attributes.entry(name).or_default() += 1;
+			if let AttributeInfo::Code(code) = &attribute.attribute_info {
+				self.count_attributes(class, &code.attributes);
+			}
+		}
+	}
+
+	pub fn class_count(&self) -> usize {
+		self.class_sizes.len()
+	}
+
+	/// The number of classes for each major version.
+	pub fn versions(&self) -> &BTreeMap<u16, usize> {
+		&self.versions
+	}
+
+	/// The number of entries in each class's constant pool.
+	pub fn constant_pool_sizes(&self) -> Distribution {
+		Distribution::new(&self.pool_sizes)
+	}
+
+	/// The length in bytes of each method's code.
+	pub fn code_sizes(&self) -> Distribution {
+		Distribution::new(&self.code_sizes)
+	}
+
+	/// Methods at or over the large method threshold, largest first.
+	pub fn large_methods(&self) -> Vec<&(MethodLocation, u32)> {
+		let mut methods: Vec<&(MethodLocation, u32)> = self.large_methods.iter().collect();
+		methods.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+		methods
+	}
+
+	/// How often each opcode occurs, most frequent first. Opcodes that never occur are left out.
+	pub fn opcode_histogram(&self) -> Vec<(Opcode, u64)> {
+		let mut histogram: Vec<(Opcode, u64)> = self.opcodes.iter().enumerate()
+			.filter(|(_, count)| **count > 0)
+			.filter_map(|(opcode, count)| Some((Opcode::try_from(opcode as u8).ok()?, *count)))
+			.collect();
+		histogram.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| u8::from(a.0).cmp(&u8::from(b.0))));
+		histogram
+	}
+
+	pub fn opcode_count(&self, opcode: Opcode) -> u64 {
+		self.opcodes[u8::from(opcode) as usize]
+	}
+
+	/// How many times each attribute occurs, at any level (class, field, method or code).
+	pub fn attributes(&self) -> &BTreeMap<String, usize> {
+		&self.attributes
+	}
+
+	/// The largest class files, largest first, with their sizes in bytes.
+	pub fn largest_classes(&self) -> Vec<(&str, usize)> {
+		let mut classes: Vec<(&str, usize)> = self.class_sizes.iter().map(|(name, size)| (name.as_str(), *size)).collect();
+		classes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+		classes.truncate(self.largest_count);
+		classes
+	}
+
+	pub fn to_json(&self) -> Json {
+		Json::object([
+			("classes", Json::from(self.class_count())),
+			("versions", Json::object(self.versions.iter().map(|(version, count)| (version.to_string(), Json::from(*count))))),
+			("constant_pool_sizes", self.constant_pool_sizes().to_json()),
+			("code_sizes", self.code_sizes().to_json()),
+			("large_methods", Json::Array(self.large_methods().into_iter().map(|(method, size)| Json::object([
+				("class", Json::from(&method.class_name)),
+				("name", Json::from(&method.name)),
+				("descriptor", Json::from(&method.descriptor)),
+				("size", Json::from(*size)),
+			])).collect())),
+			("opcodes", Json::object(self.opcode_histogram().into_iter().map(|(opcode, count)| (opcode.to_string(), Json::Int(count as i64))))),
+			("attributes", Json::object(self.attributes.iter().map(|(name, count)| (name.as_str(), Json::from(*count))))),
+			("largest_classes", Json::Array(self.largest_classes().into_iter().map(|(name, size)| Json::object([
+				("class", Json::from(name)),
+				("size", Json::from(size)),
+			])).collect())),
+		])
+	}
+}
+
+impl Display for ClassStatistics {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{} classes", self.class_count())?;
+		for (version, count) in &self.versions {
+			writeln!(f, "   major version {}: {}", version, count)?;
+		}
+		writeln!(f, "constant pool entries: {}", self.constant_pool_sizes())?;
+		writeln!(f, "method code bytes: {}", self.code_sizes())?;
+		for (method, size) in self.large_methods() {
+			writeln!(f, "   {} bytes ({}% of limit): {}.{}{}", size, size * 100 / MAX_CODE_LENGTH, method.class_name, method.name, method.descriptor)?;
+		}
+		let histogram = self.opcode_histogram();
+		let total: u64 = histogram.iter().map(|(_, count)| count).sum();
+		let most = histogram.first().map_or(1, |(_, count)| *count);
+		writeln!(f, "opcodes: {} instructions", total)?;
+		for (opcode, count) in &histogram {
+			let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(most) as usize);
+			writeln!(f, "   {:<16}{:>10} {:>6.2}% {}", opcode.to_string(), count, *count as f64 * 100.0 / total as f64, bar)?;
+		}
+		writeln!(f, "attributes:")?;
+		for (name, count) in &self.attributes {
+			writeln!(f, "   {:<40}{:>10}", name, count)?;
+		}
+		writeln!(f, "largest classes:")?;
+		for (name, size) in self.largest_classes() {
+			writeln!(f, "   {:>10} {}", size, name)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::BTreeMap, path::Path};
+
+	use crate::{
+		analysis::stats::{ClassStatistics, Distribution},
+		class::{access::MethodAccessPropertyFlags, builder::ClassBuilder},
+		isa::opcode::Opcode};
+
+	const RESOURCES: &str = "tests/resources/query";
+
+	fn get_statistics(statistics: ClassStatistics) -> ClassStatistics {
+		let mut statistics = statistics;
+		statistics.add_path(Path::new(RESOURCES)).expect("Couldn't read classes");
+		statistics
+	}
+
+	#[test]
+	fn test_distribution() {
+		let distribution = Distribution::new(&[100, 3, 7, 1, 64, 9, 12, 2, 5, 40]);
+		assert_eq!((distribution.count, distribution.min, distribution.max, distribution.median, distribution.p90), (10, 1, 100, 7, 64));
+		assert_eq!(distribution.mean(), 24.3);
+		assert_eq!(distribution.buckets, BTreeMap::from([(1, 1), (2, 1), (4, 1), (8, 2), (16, 2), (64, 2), (128, 1)]));
+		assert_eq!(Distribution::new(&[]), Distribution::default());
+	}
+
+	#[test]
+	fn test_statistics() {
+		let statistics = get_statistics(ClassStatistics::new());
+		assert_eq!(statistics.class_count(), 5);
+		assert_eq!(statistics.versions(), &BTreeMap::from([(61, 5)]));
+		let pool = statistics.constant_pool_sizes();
+		assert_eq!((pool.count, pool.min, pool.max), (5, 12, 83));
+		let code = statistics.code_sizes();
+		assert_eq!((code.count, code.total, code.max), (12, 130, 37));
+		assert!(statistics.large_methods().is_empty());
+		assert_eq!(statistics.attributes()["LineNumberTable"], 12);
+		assert_eq!(statistics.attributes()["BootstrapMethods"], 1);
+		assert_eq!(statistics.largest_classes()[0], ("shop/Cart", 1692));
+	}
+
+	#[test]
+	fn test_opcode_histogram() {
+		let statistics = get_statistics(ClassStatistics::new());
+		let histogram = statistics.opcode_histogram();
+		assert_eq!(histogram[..2], [(Opcode::ALoad0, 7), (Opcode::InvokeSpecial, 7)]);
+		assert_eq!(histogram.iter().map(|(_, count)| count).sum::<u64>(), 72);
+		assert_eq!(statistics.opcode_count(Opcode::InvokeDynamic), 2);
+		assert_eq!(statistics.opcode_count(Opcode::Goto), 0);
+		assert!(!histogram.iter().any(|(opcode, _)| *opcode == Opcode::Goto));
+	}
+
+	#[test]
+	fn test_undecodable_code() {
+		let mut statistics = get_statistics(ClassStatistics::new());
+		// A bipush that ends before its operand, after a method that decodes.
+		let public = &[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Static];
+		let mut class = ClassBuilder::new("demo/Broken")
+			.method(public, "fine", "()V", |method| { method.op(Opcode::Return); })
+			.method(public, "broken", "()I", |method| { method.int(Opcode::BIpush, 5).op(Opcode::IReturn); })
+			.build().unwrap();
+		let broken = class.methods.methods.iter().position(|method| class.get_utf8(method.name_index).as_deref() == Some("broken")).unwrap();
+		class.methods.methods[broken].code_mut().unwrap().code.truncate(1);
+
+		let before = (statistics.class_count(), statistics.versions().clone(), statistics.code_sizes(), statistics.attributes().clone(), statistics.opcode_histogram());
+		assert!(statistics.add(&class, 100).is_err());
+		assert_eq!((statistics.class_count(), statistics.versions().clone(), statistics.code_sizes(), statistics.attributes().clone(), statistics.opcode_histogram()), before);
+	}
+
+	#[test]
+	fn test_report() {
+		let statistics = get_statistics(ClassStatistics::new().large_method_threshold(30).largest_class_count(2));
+		let large: Vec<String> = statistics.large_methods().iter().map(|(method, size)| format!("{}.{} {}", method.class_name, method.name, size)).collect();
+		assert_eq!(large, vec!["shop/Cart.sum 37"]);
+		assert_eq!(statistics.largest_classes().len(), 2);
+		let report = statistics.to_string();
+		assert!(report.starts_with("5 classes\n   major version 61: 5\n"));
+		assert!(report.contains("\n   37 bytes (0% of limit): shop/Cart.sum(Lshop/Item;Lshop/Special;Lshop/Plain;Lshop/Other;)I\n"));
+		assert!(report.contains("\n   aload_0                  7   9.72% ########################################\n"));
+		let json = statistics.to_json().to_string();
+		assert!(json.contains("\"versions\":{\"61\":5}"));
+		assert!(json.contains("\"opcodes\":{\"aload_0\":7,\"invokespecial\":7,"));
+	}
+}
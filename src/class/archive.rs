@@ -0,0 +1,106 @@
+use std::{
+	fs::{self, File},
+	io::{self, Cursor, Read, Write},
+	path::{Path, PathBuf}};
+
+use binrw::BinReaderExt;
+use thiserror::Error;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::class::{class::Class, jimage::JImageError};
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+	#[error("I/O error: {0}")]
+	Io(#[from] io::Error),
+	#[error("archive error: {0}")]
+	Zip(#[from] zip::result::ZipError),
+	#[error("could not parse {name}: {source}")]
+	Parse { name: String, source: binrw::Error },
+	#[error("could not write {name}: {source}")]
+	Write { name: String, source: binrw::Error },
+	#[error("runtime image error: {0}")]
+	Image(#[from] JImageError),
+}
+
+/// A file found in a directory tree or archive, named relative to its root with `/` separators.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+	pub name: String,
+	pub bytes: Vec<u8>,
+}
+
+impl Entry {
+	pub fn is_class(&self) -> bool {
+		self.name.ends_with(".class")
+	}
+
+	pub fn parse(&self) -> Result<Class, ArchiveError> {
+		Cursor::new(&self.bytes).read_be().map_err(|source| ArchiveError::Parse { name: self.name.clone(), source })
+	}
+}
+
+/// Whether a path names a JAR or ZIP archive rather than a directory or class file.
+pub fn is_archive(path: &Path) -> bool {
+	path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("jar") || extension.eq_ignore_ascii_case("zip"))
+}
+
+/// Read every file under `path`, which may be a directory, a .jar/.zip archive or a single file.
+pub fn read_entries(path: &Path) -> Result<Vec<Entry>, ArchiveError> {
+	if path.is_dir() {
+		let mut entries = Vec::new();
+		read_directory(path, path, &mut entries)?;
+		entries.sort_by(|a, b| a.name.cmp(&b.name));
+		Ok(entries)
+	} else if is_archive(path) {
+		let mut archive = ZipArchive::new(File::open(path)?)?;
+		let mut entries = Vec::new();
+		for index in 0..archive.len() {
+			let mut file = archive.by_index(index)?;
+			if file.is_dir() {
+				continue;
+			}
+			let mut bytes = Vec::with_capacity(file.size() as usize);
+			file.read_to_end(&mut bytes)?;
+			entries.push(Entry { name: file.name().to_string(), bytes });
+		}
+		Ok(entries)
+	} else {
+		let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+		Ok(vec![Entry { name, bytes: fs::read(path)? }])
+	}
+}
+
+fn read_directory(root: &Path, directory: &Path, entries: &mut Vec<Entry>) -> Result<(), ArchiveError> {
+	for item in fs::read_dir(directory)? {
+		let path: PathBuf = item?.path();
+		if path.is_dir() {
+			read_directory(root, &path, entries)?;
+		} else {
+			let relative = path.strip_prefix(root).unwrap_or(&path);
+			let name = relative.components()
+				.map(|component| component.as_os_str().to_string_lossy().into_owned())
+				.collect::<Vec<_>>()
+				.join("/");
+			entries.push(Entry { name, bytes: fs::read(&path)? });
+		}
+	}
+	Ok(())
+}
+
+/// Parse every class file under `path` (see `read_entries`).
+pub fn read_classes(path: &Path) -> Result<Vec<Class>, ArchiveError> {
+	read_entries(path)?.iter().filter(|entry| entry.is_class()).map(Entry::parse).collect()
+}
+
+/// Write entries to a new JAR at `path`, deflating each one, in the order given.
+pub fn write_jar(path: &Path, entries: &[Entry]) -> Result<(), ArchiveError> {
+	let mut jar = ZipWriter::new(File::create(path)?);
+	let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+	for entry in entries {
+		jar.start_file(entry.name.as_str(), options)?;
+		jar.write_all(&entry.bytes)?;
+	}
+	jar.finish()?;
+	Ok(())
+}
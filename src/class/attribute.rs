@@ -1,14 +1,16 @@
 use binrw::{
-	binread, binrw, BinRead};
+	binrw, BinRead, BinWrite};
 
 use crate::class::{
 	attribute, constant_pool::ConstantPoolRequiredArgs, modified_utf8::ModifiedUtf8String, verification::*};
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Attribute {
-	name_index: u16,
-	length: u32,
-	attribute_info: AttributeInfo,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub name_index: u16,
+	pub length: u32,
+	pub attribute_info: AttributeInfo,
 }
 
 impl BinRead for Attribute {
@@ -26,18 +28,30 @@ impl BinRead for Attribute {
 
 		let attribute_type = ModifiedUtf8String::new(attribute_type_constant.bytes).to_string();
 		let attribute_info: Result<AttributeInfo, binrw::Error> = match attribute_type.as_str() {
+			"AnnotationDefault" => Ok(AttributeInfo::AnnotationDefault(ElementValue::read_options(reader, endian, ())?)),
 			"BootstrapMethods" => Ok(AttributeInfo::BootstrapMethods(BootstrapMethods::read_options(reader, endian, ())?)),
 			"Code" => Ok(AttributeInfo::Code(Code::read_options(reader, endian, args.clone())?)),
 			"ConstantValue" => Ok(AttributeInfo::ConstantValue(ConstantValue::read_options(reader, endian, ())?)),
+			"EnclosingMethod" => Ok(AttributeInfo::EnclosingMethod(EnclosingMethod::read_options(reader, endian, ())?)),
+			"InnerClasses" => Ok(AttributeInfo::InnerClasses(InnerClasses::read_options(reader, endian, ())?)),
 			"LineNumberTable" => Ok(AttributeInfo::LineNumberTable(LineNumberTable::read_options(reader, endian, ())?)),
+			"LocalVariableTable" => Ok(AttributeInfo::LocalVariableTable(LocalVariableTable::read_options(reader, endian, ())?)),
+			"LocalVariableTypeTable" => Ok(AttributeInfo::LocalVariableTypeTable(LocalVariableTypeTable::read_options(reader, endian, ())?)),
 			"NestHost" => Ok(AttributeInfo::NestHost(NestHost::read_options(reader, endian, ())?)),
 			"NestMembers" => Ok(AttributeInfo::NestMembers(NestMembers::read_options(reader, endian, ())?)),
-			"PermittedSubclass" => Ok(AttributeInfo::PermittedSubclasses(PermittedSubclasses::read_options(reader, endian, ())?)),
+			"PermittedSubclasses" => Ok(AttributeInfo::PermittedSubclasses(PermittedSubclasses::read_options(reader, endian, ())?)),
+			"RuntimeInvisibleAnnotations" => Ok(AttributeInfo::RuntimeInvisibleAnnotations(Annotations::read_options(reader, endian, ())?)),
+			"RuntimeInvisibleParameterAnnotations" => Ok(AttributeInfo::RuntimeInvisibleParameterAnnotations(ParameterAnnotations::read_options(reader, endian, ())?)),
+			"RuntimeVisibleAnnotations" => Ok(AttributeInfo::RuntimeVisibleAnnotations(Annotations::read_options(reader, endian, ())?)),
+			"RuntimeVisibleParameterAnnotations" => Ok(AttributeInfo::RuntimeVisibleParameterAnnotations(ParameterAnnotations::read_options(reader, endian, ())?)),
+			"Signature" => Ok(AttributeInfo::Signature(Signature::read_options(reader, endian, ())?)),
 			"SourceFile" => Ok(AttributeInfo::SourceFile(SourceFile::read_options(reader, endian, ())?)),
 			"StackMapTable" => Ok(AttributeInfo::StackMapTable(StackMapTable::read_options(reader, endian, ())?)),
 			unrecognised => {
 				let name = String::from(unrecognised).into_bytes();
-				Ok(AttributeInfo::UnrecognisedAttribute(UnrecognisedAttribute { length: name.len() as u16, attribute_name: name }))
+				let mut info: Vec<u8> = vec![0u8; length as usize];
+				reader.read_exact(&mut info)?;
+				Ok(AttributeInfo::UnrecognisedAttribute(UnrecognisedAttribute { length: name.len() as u16, attribute_name: name, info }))
 			}
 		};
 		return Ok(Attribute {
@@ -48,40 +62,107 @@ impl BinRead for Attribute {
 	}
 }
 
-#[binread]
+impl BinWrite for Attribute {
+	type Args<'a> = ();
+
+	/// Writes the attribute with an attribute_length computed from its content, so edited
+	/// attributes don't need `length` kept up to date.
+	fn write_options<W: std::io::Write + std::io::Seek>(
+		&self,
+		writer: &mut W,
+		endian: binrw::Endian,
+		_args: (),
+	) -> binrw::BinResult<()> {
+		let mut info = binrw::io::Cursor::new(Vec::new());
+		self.attribute_info.write_options(&mut info, endian, ())?;
+		let info = info.into_inner();
+		self.name_index.write_options(writer, endian, ())?;
+		(info.len() as u32).write_options(writer, endian, ())?;
+		info.write_options(writer, endian, ())
+	}
+}
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum AttributeInfo {
+	AnnotationDefault(ElementValue),
 	BootstrapMethods(BootstrapMethods),
 	Code(Code),
 	ConstantValue(ConstantValue),
+	EnclosingMethod(EnclosingMethod),
+	InnerClasses(InnerClasses),
 	LineNumberTable(LineNumberTable),
+	LocalVariableTable(LocalVariableTable),
+	LocalVariableTypeTable(LocalVariableTypeTable),
 	NestHost(NestHost),
 	NestMembers(NestMembers),
 	PermittedSubclasses(PermittedSubclasses),
+	RuntimeInvisibleAnnotations(Annotations),
+	RuntimeInvisibleParameterAnnotations(ParameterAnnotations),
+	RuntimeVisibleAnnotations(Annotations),
+	RuntimeVisibleParameterAnnotations(ParameterAnnotations),
+	Signature(Signature),
 	SourceFile(SourceFile),
 	StackMapTable(StackMapTable),
 	UnrecognisedAttribute(UnrecognisedAttribute),
 }
 
-/// Dummy struct to represent unimplemented or unrecognised attributes.
-#[binrw]
-#[brw(big)]
+impl BinWrite for AttributeInfo {
+	type Args<'a> = ();
+
+	fn write_options<W: std::io::Write + std::io::Seek>(
+		&self,
+		writer: &mut W,
+		endian: binrw::Endian,
+		_args: (),
+	) -> binrw::BinResult<()> {
+		match self {
+			AttributeInfo::AnnotationDefault(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::BootstrapMethods(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::Code(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::ConstantValue(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::EnclosingMethod(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::InnerClasses(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::LineNumberTable(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::LocalVariableTable(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::LocalVariableTypeTable(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::NestHost(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::NestMembers(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::PermittedSubclasses(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::RuntimeInvisibleAnnotations(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::RuntimeInvisibleParameterAnnotations(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::RuntimeVisibleAnnotations(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::RuntimeVisibleParameterAnnotations(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::Signature(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::SourceFile(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::StackMapTable(info) => info.write_options(writer, endian, ()),
+			AttributeInfo::UnrecognisedAttribute(info) => info.info.write_options(writer, endian, ()),
+		}
+	}
+}
+
+/// Dummy struct to represent unimplemented or unrecognised attributes, whose contents are kept as-is.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct UnrecognisedAttribute {
 	pub length: u16,
-	#[br(count(length as u16))]
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::utf8"))]
 	pub attribute_name: Vec<u8>,
+	pub info: Vec<u8>,
 }
 
 /// An implementation of a ConstantValue attribute (JVMS17 4.7.2)
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ConstantValue {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub constant_value_index: u16
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Code {
 	pub max_stack: u16,
 	pub max_locals: u16,
@@ -93,14 +174,25 @@ pub struct Code {
 	pub attributes: Vec<Attribute>,
 }
 
+impl Code {
+	/// The attribute_length of a Code attribute with this content (JVMS17 4.7.3).
+	pub fn attribute_length(&self) -> u32 {
+		12 + self.code_length
+			+ 8 * self.handlers.len() as u32
+			+ self.attributes.iter().map(|attribute| 6 + attribute.length).sum::<u32>()
+	}
+}
+
 /// Implementation of an exception_table (JVMS17 4.7 p. 166)
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ExceptionHandler {
 	pub start_pc: u16,
 	pub end_pc: u16,
 	pub handler_pc: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub catch_type_index: u16,
 }
 
@@ -146,10 +238,32 @@ impl BinRead for Code {
 	}
 }
 
+impl BinWrite for Code {
+	type Args<'a> = ();
+
+	/// Writes the code with counts taken from the vectors rather than the count fields.
+	fn write_options<W: std::io::Write + std::io::Seek>(
+		&self,
+		writer: &mut W,
+		endian: binrw::Endian,
+		_args: (),
+	) -> binrw::BinResult<()> {
+		self.max_stack.write_options(writer, endian, ())?;
+		self.max_locals.write_options(writer, endian, ())?;
+		(self.code.len() as u32).write_options(writer, endian, ())?;
+		self.code.write_options(writer, endian, ())?;
+		(self.handlers.len() as u16).write_options(writer, endian, ())?;
+		self.handlers.write_options(writer, endian, ())?;
+		(self.attributes.len() as u16).write_options(writer, endian, ())?;
+		self.attributes.write_options(writer, endian, ())
+	}
+}
+
 
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct LineNumberTable {
 	pub table_length: u16,
 	#[br(count = table_length)]
@@ -159,30 +273,136 @@ pub struct LineNumberTable {
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Line {
 	pub start_pc: u16,
 	pub line_number: u16,
 }
 
+/// An implementation of a LocalVariableTable attribute (JVMS17 4.7.13)
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LocalVariableTable {
+	pub local_variable_table_length: u16,
+	#[br(count = local_variable_table_length)]
+	pub local_variables: Vec<LocalVariable>,
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LocalVariable {
+	pub start_pc: u16,
+	pub length: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub name_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub descriptor_index: u16,
+	pub index: u16,
+}
+
+/// An implementation of a LocalVariableTypeTable attribute (JVMS17 4.7.14)
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LocalVariableTypeTable {
+	pub local_variable_type_table_length: u16,
+	#[br(count = local_variable_type_table_length)]
+	pub local_variable_types: Vec<LocalVariableType>,
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LocalVariableType {
+	pub start_pc: u16,
+	pub length: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub name_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub signature_index: u16,
+	pub index: u16,
+}
+
+/// An implementation of a Signature attribute (JVMS17 4.7.9)
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Signature {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub signature_index: u16,
+}
+
+/// An implementation of an InnerClasses attribute (JVMS17 4.7.6)
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct InnerClasses {
+	pub number_of_classes: u16,
+	#[br(count = number_of_classes)]
+	pub classes: Vec<InnerClass>,
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct InnerClass {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub inner_class_info_index: u16,
+	/// Zero for local and anonymous classes.
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub outer_class_info_index: u16,
+	/// Zero for anonymous classes.
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub inner_name_index: u16,
+	pub inner_class_access_flags: u16,
+}
+
+/// An implementation of an EnclosingMethod attribute (JVMS17 4.7.7)
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EnclosingMethod {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub class_index: u16,
+	/// A NameAndType constant, or zero if the class is not enclosed by a method or constructor.
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub method_index: u16,
+}
+
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SourceFile {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub source_file_index: u16,
 }
 
+/// An implementation of a StackMapTable attribute (JVMS17 4.7.4)
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct StackMapTable {
-	number_of_entries: u8,
+	pub number_of_entries: u16,
 	#[br(count = number_of_entries)]
-	entries: Vec<StackMapFrame>
+	pub entries: Vec<StackMapFrame>
 }
 
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum StackMapFrame {
 	SameFrame(SameFrame),
 	SameLocals1StackItemFrame(SameLocals1StackItemFrame),
@@ -193,75 +413,134 @@ pub enum StackMapFrame {
 	FullFrame(FullFrame)
 }
 
+impl StackMapFrame {
+
+	/// The offset_delta of the frame, which for the compact frame types is folded into frame_type.
+	pub fn offset_delta(&self) -> u16 {
+		match self {
+			StackMapFrame::SameFrame(frame) => u16::from(frame.frame_type),
+			StackMapFrame::SameLocals1StackItemFrame(frame) => u16::from(frame.frame_type - 64),
+			StackMapFrame::SameLocals1StackItemFrameExtended(frame) => frame.offset_delta,
+			StackMapFrame::ChopFrame(frame) => frame.offset_delta,
+			StackMapFrame::SameFrameExtended(frame) => frame.offset_delta,
+			StackMapFrame::AppendFrame(frame) => frame.offset_delta,
+			StackMapFrame::FullFrame(frame) => frame.offset_delta,
+		}
+	}
+
+	/// Rebuild the frame with a new offset_delta, switching to the extended frame type when
+	/// the delta no longer fits in frame_type.
+	pub fn with_offset_delta(self, offset_delta: u16) -> StackMapFrame {
+		match self {
+			StackMapFrame::SameFrame(_) | StackMapFrame::SameFrameExtended(_) => {
+				if offset_delta <= 63 {
+					StackMapFrame::SameFrame(SameFrame { frame_type: offset_delta as u8 })
+				} else {
+					StackMapFrame::SameFrameExtended(SameFrameExtended { frame_type: 251, offset_delta })
+				}
+			}
+			StackMapFrame::SameLocals1StackItemFrame(SameLocals1StackItemFrame { verification_type_info, .. }) |
+			StackMapFrame::SameLocals1StackItemFrameExtended(SameLocals1StackItemFrameExtended { verification_type_info, .. }) => {
+				if offset_delta <= 63 {
+					StackMapFrame::SameLocals1StackItemFrame(SameLocals1StackItemFrame { frame_type: 64 + offset_delta as u8, verification_type_info })
+				} else {
+					StackMapFrame::SameLocals1StackItemFrameExtended(SameLocals1StackItemFrameExtended { frame_type: 247, offset_delta, verification_type_info })
+				}
+			}
+			StackMapFrame::ChopFrame(frame) => StackMapFrame::ChopFrame(ChopFrame { offset_delta, ..frame }),
+			StackMapFrame::AppendFrame(frame) => StackMapFrame::AppendFrame(AppendFrame { offset_delta, ..frame }),
+			StackMapFrame::FullFrame(frame) => StackMapFrame::FullFrame(FullFrame { offset_delta, ..frame }),
+		}
+	}
+
+	/// Every verification type mentioned by the frame, locals first.
+	pub fn verification_types_mut(&mut self) -> Vec<&mut VerificationTypeInfo> {
+		match self {
+			StackMapFrame::SameFrame(_) | StackMapFrame::ChopFrame(_) | StackMapFrame::SameFrameExtended(_) => vec![],
+			StackMapFrame::SameLocals1StackItemFrame(frame) => vec![&mut frame.verification_type_info],
+			StackMapFrame::SameLocals1StackItemFrameExtended(frame) => vec![&mut frame.verification_type_info],
+			StackMapFrame::AppendFrame(frame) => frame.locals.iter_mut().collect(),
+			StackMapFrame::FullFrame(frame) => frame.locals.iter_mut().chain(frame.stack.iter_mut()).collect(),
+		}
+	}
+}
+
 #[binrw]
 #[brw(big)]
 #[br(assert(frame_type <= 63))]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SameFrame {
-	frame_type: u8,
+	pub frame_type: u8,
 }
 
 #[binrw]
 #[brw(big)]
-#[br(assert((63..127).contains(&frame_type)))]
+#[br(assert((64..=127).contains(&frame_type)))]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SameLocals1StackItemFrame {
-	frame_type: u8,
-	verification_type_info: VerificationTypeInfo
+	pub frame_type: u8,
+	pub verification_type_info: VerificationTypeInfo
 }
 
 #[binrw]
 #[brw(big)]
 #[br(assert(frame_type == 247))]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SameLocals1StackItemFrameExtended {
-	frame_type: u8,
-	offset_delta: u16,
-	verification_type_info: VerificationTypeInfo
+	pub frame_type: u8,
+	pub offset_delta: u16,
+	pub verification_type_info: VerificationTypeInfo
 }
 
 #[binrw]
 #[brw(big)]
 #[br(assert((248..=250).contains(&frame_type)))]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ChopFrame {
-	frame_type: u8,
-	offset_delta: u16,
+	pub frame_type: u8,
+	pub offset_delta: u16,
 }
 
 #[binrw]
 #[brw(big)]
 #[br(assert(frame_type == 251))]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SameFrameExtended {
-	frame_type: u8,
-	offset_delta: u16
+	pub frame_type: u8,
+	pub offset_delta: u16
 }
 
 #[binrw]
 #[brw(big)]
-#[br(assert((252..254).contains(&frame_type)))]
+#[br(assert((252..=254).contains(&frame_type)))]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct AppendFrame {
-	frame_type: u8,
-	offset_delta: u16,
+	pub frame_type: u8,
+	pub offset_delta: u16,
 	#[br(count = frame_type - 251)]
-	locals: Vec<VerificationTypeInfo>
+	pub locals: Vec<VerificationTypeInfo>
 }
 
 #[binrw]
 #[brw(big)]
 #[br(assert(frame_type == 255))]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct FullFrame {
-	frame_type: u8,
-	offset_delta: u16,
-	number_of_locals: u16,
+	pub frame_type: u8,
+	pub offset_delta: u16,
+	pub number_of_locals: u16,
 	#[br(count = number_of_locals)]
-	locals: Vec<VerificationTypeInfo>,
-	number_of_stack_items: u16,
+	pub locals: Vec<VerificationTypeInfo>,
+	pub number_of_stack_items: u16,
 	#[br(count = number_of_stack_items)]
-	stack: Vec<VerificationTypeInfo>
+	pub stack: Vec<VerificationTypeInfo>
 }
 
 /// An implementation of BootstrapMethods_attribute (JVMS17 4.723).
@@ -276,10 +555,11 @@ pub struct FullFrame {
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BootstrapMethods {
-	num_bootstrap_methods: u16,
+	pub num_bootstrap_methods: u16,
 	#[br(count = num_bootstrap_methods)]
-	bootstrap_methods: Vec<BootstrapMethodEntry>,
+	pub bootstrap_methods: Vec<BootstrapMethodEntry>,
 }
 
 /// An implementation of BootstrapMethods_attribute.bootstrap_methods (JVMS17 4.7.23).
@@ -290,11 +570,14 @@ pub struct BootstrapMethods {
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct BootstrapMethodEntry {
-	bootstrap_method_ref: u16,
-	num_bootstrap_arguments: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub bootstrap_method_ref: u16,
+	pub num_bootstrap_arguments: u16,
 	#[br(count = num_bootstrap_arguments)]
-	bootstrap_arguments: Vec<u16>,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::indices"))]
+	pub bootstrap_arguments: Vec<u16>,
 }
 
 /// An implementation of NestHost (JVMS17 4.7.28).
@@ -306,8 +589,10 @@ pub struct BootstrapMethodEntry {
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NestHost {
-	host_class_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub host_class_index: u16,
 }
 
 /// An implementation of NestMembers (JVMS17 4.7.29).
@@ -322,18 +607,142 @@ pub struct NestHost {
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NestMembers {
-	number_of_classes: u16,
+	pub number_of_classes: u16,
 	#[br(count = number_of_classes)]
-	classes: Vec<u16>
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::indices"))]
+	pub classes: Vec<u16>
 }
 
 /// An implementation of PermittedSubclasses (JVMS17 4.7.31).
 #[binrw]
 #[brw(big)]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PermittedSubclasses {
-	number_of_classes: u16,
+	pub number_of_classes: u16,
 	#[br(count = number_of_classes)]
-	classes: Vec<u16>
-}
\ No newline at end of file
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::indices"))]
+	pub classes: Vec<u16>
+}
+
+/// An implementation of RuntimeVisibleAnnotations and RuntimeInvisibleAnnotations (JVMS17 4.7.16, 4.7.17).
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Annotations {
+	pub num_annotations: u16,
+	#[br(count = num_annotations)]
+	pub annotations: Vec<Annotation>,
+}
+
+/// An implementation of RuntimeVisibleParameterAnnotations and RuntimeInvisibleParameterAnnotations
+/// (JVMS17 4.7.18, 4.7.19).
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ParameterAnnotations {
+	pub num_parameters: u8,
+	#[br(count = num_parameters)]
+	pub parameter_annotations: Vec<Annotations>,
+}
+
+/// An implementation of an annotation structure (JVMS17 4.7.16).
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Annotation {
+	/// A Utf8 constant holding the annotation type as a field descriptor, e.g. `Ljava/lang/Deprecated;`.
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub type_index: u16,
+	pub num_element_value_pairs: u16,
+	#[br(count = num_element_value_pairs)]
+	pub element_value_pairs: Vec<ElementValuePair>,
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ElementValuePair {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub element_name_index: u16,
+	pub value: ElementValue,
+}
+
+/// An implementation of element_value (JVMS17 4.7.16.1), tagged by its first byte.
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ElementValue {
+	#[brw(magic(b'B'))]
+	Byte {
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		const_value_index: u16,
+	},
+	#[brw(magic(b'C'))]
+	Char {
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		const_value_index: u16,
+	},
+	#[brw(magic(b'D'))]
+	Double {
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		const_value_index: u16,
+	},
+	#[brw(magic(b'F'))]
+	Float {
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		const_value_index: u16,
+	},
+	#[brw(magic(b'I'))]
+	Int {
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		const_value_index: u16,
+	},
+	#[brw(magic(b'J'))]
+	Long {
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		const_value_index: u16,
+	},
+	#[brw(magic(b'S'))]
+	Short {
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		const_value_index: u16,
+	},
+	#[brw(magic(b'Z'))]
+	Boolean {
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		const_value_index: u16,
+	},
+	#[brw(magic(b's'))]
+	String {
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		const_value_index: u16,
+	},
+	#[brw(magic(b'e'))]
+	Enum {
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		type_name_index: u16,
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		const_name_index: u16,
+	},
+	#[brw(magic(b'c'))]
+	Class {
+		#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+		class_info_index: u16,
+	},
+	#[brw(magic(b'@'))]
+	Annotation(Annotation),
+	#[brw(magic(b'['))]
+	Array {
+		num_values: u16,
+		#[br(count = num_values)]
+		values: Vec<ElementValue>,
+	},
+}
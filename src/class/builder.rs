@@ -0,0 +1,819 @@
+use binrw::BinWrite;
+
+use crate::{
+	class::{
+		access::{ClassAccessPropertyFlags, FieldAccessPropertyFlags, MethodAccessPropertyFlags},
+		attribute::{Attribute, AttributeInfo, BootstrapMethodEntry, BootstrapMethods, Code, ConstantValue, LineNumberTable, LocalVariableTable, SourceFile, StackMapTable},
+		class::{Class, ClassAttributes, Fields, Methods},
+		constant_pool,
+		constant_pool_builder::ConstantPoolBuilder,
+		field::Field,
+		method::Method},
+	error::EncodeError,
+	isa::{
+		instruction::{self, Instruction, Label},
+		instruction_list::{Element, Handler, InstructionList, LineNumber, LocalVariableRange},
+		opcode::Opcode,
+		stack_map::{self, CommonSuperClass, FrameComputer}},
+};
+
+/// A loadable constant, as pushed by `ldc` or passed to a bootstrap method (JVMS17 4.4).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constant {
+	Integer(i32),
+	Float(f32),
+	Long(i64),
+	Double(f64),
+	String(String),
+	/// A class by internal name, or an array class by descriptor.
+	Class(String),
+	/// A method type by descriptor.
+	MethodType(String),
+	MethodHandle(Handle),
+}
+
+impl From<i32> for Constant {
+	fn from(value: i32) -> Self {
+		Constant::Integer(value)
+	}
+}
+
+impl From<f32> for Constant {
+	fn from(value: f32) -> Self {
+		Constant::Float(value)
+	}
+}
+
+impl From<i64> for Constant {
+	fn from(value: i64) -> Self {
+		Constant::Long(value)
+	}
+}
+
+impl From<f64> for Constant {
+	fn from(value: f64) -> Self {
+		Constant::Double(value)
+	}
+}
+
+impl From<&str> for Constant {
+	fn from(value: &str) -> Self {
+		Constant::String(value.to_string())
+	}
+}
+
+/// A method handle to a field or method (JVMS17 4.4.8).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Handle {
+	/// One of the `REF_` kinds below (JVMS17 Table 5.4.3.5-A).
+	pub reference_kind: u8,
+	pub owner: String,
+	pub name: String,
+	pub descriptor: String,
+	/// Whether `owner` is an interface, for handles to methods.
+	pub interface: bool,
+}
+
+impl Handle {
+	pub const REF_GET_FIELD: u8 = 1;
+	pub const REF_GET_STATIC: u8 = 2;
+	pub const REF_PUT_FIELD: u8 = 3;
+	pub const REF_PUT_STATIC: u8 = 4;
+	pub const REF_INVOKE_VIRTUAL: u8 = 5;
+	pub const REF_INVOKE_STATIC: u8 = 6;
+	pub const REF_INVOKE_SPECIAL: u8 = 7;
+	pub const REF_NEW_INVOKE_SPECIAL: u8 = 8;
+	pub const REF_INVOKE_INTERFACE: u8 = 9;
+
+	pub fn new(reference_kind: u8, owner: &str, name: &str, descriptor: &str, interface: bool) -> Handle {
+		Handle {
+			reference_kind,
+			owner: owner.to_string(),
+			name: name.to_string(),
+			descriptor: descriptor.to_string(),
+			interface,
+		}
+	}
+}
+
+/// Emits the code of one method, handed out by `ClassBuilder::method`.
+///
+/// Instructions are appended in order and branch to labels from `new_label`, which are placed with
+/// `label`. Constants are added to the class's pool as they are used. The first error (an operand out
+/// of range, say) is kept and reported by `ClassBuilder::build`, so calls can be chained freely.
+pub struct MethodBuilder<'p> {
+	pool: &'p mut ConstantPoolBuilder,
+	bootstrap_methods: &'p mut Vec<BootstrapMethodEntry>,
+	list: InstructionList,
+	error: Option<EncodeError>,
+}
+
+impl MethodBuilder<'_> {
+
+	/// Create a label to be placed later with `label`.
+	pub fn new_label(&mut self) -> Label {
+		self.list.new_label()
+	}
+
+	/// Place `label` before the next instruction.
+	pub fn label(&mut self, label: Label) -> &mut Self {
+		self.list.push(Element::Label(label));
+		self
+	}
+
+	/// Attribute the following instructions to a source line.
+	pub fn line(&mut self, line_number: u16) -> &mut Self {
+		let start = self.list.new_label();
+		self.list.push(Element::Label(start));
+		self.list.lines.push(LineNumber { start, line_number });
+		self
+	}
+
+	/// An instruction without operands, such as `iadd` or `areturn`.
+	pub fn op(&mut self, opcode: Opcode) -> &mut Self {
+		let takes_operands = instruction::operand_length(opcode) > 0 || instruction::is_branch(opcode)
+			|| matches!(opcode, Opcode::TableSwitch | Opcode::LookupSwitch | Opcode::Wide);
+		self.emit(match takes_operands {
+			false => Ok(Instruction::Plain { opcode, operands: vec![] }),
+			true => Err(EncodeError { msg: format!("{} takes operands", opcode) }),
+		})
+	}
+
+	/// `bipush`, `sipush` or `newarray` with its immediate operand; for `newarray` the operand is
+	/// the array type code (JVMS17 Table 6.5.newarray-A).
+	pub fn int(&mut self, opcode: Opcode, value: i32) -> &mut Self {
+		let operands = match opcode {
+			Opcode::BIpush => i8::try_from(value).ok().map(|value| value.to_be_bytes().to_vec()),
+			Opcode::SIpush => i16::try_from(value).ok().map(|value| value.to_be_bytes().to_vec()),
+			Opcode::NewArray => (4..=11).contains(&value).then(|| vec![value as u8]),
+			_ => return self.fail(format!("{} does not take an immediate operand", opcode)),
+		};
+		self.emit(operands
+			.map(|operands| Instruction::Plain { opcode, operands })
+			.ok_or_else(|| EncodeError { msg: format!("{} is out of range for {}", value, opcode) }))
+	}
+
+	/// Push an int constant using the shortest instruction that can.
+	pub fn push_int(&mut self, value: i32) -> &mut Self {
+		match value {
+			-1..=5 => {
+				let opcode = Opcode::try_from((i32::from(u8::from(Opcode::IConst0)) + value) as u8).expect("iconst opcodes are contiguous");
+				self.op(opcode)
+			}
+			_ if i8::try_from(value).is_ok() => self.int(Opcode::BIpush, value),
+			_ if i16::try_from(value).is_ok() => self.int(Opcode::SIpush, value),
+			_ => self.ldc(value),
+		}
+	}
+
+	/// A load or store of local `index`, or `ret`, using the short or `wide` form as needed.
+	pub fn var(&mut self, opcode: Opcode, index: u16) -> &mut Self {
+		let byte = u8::from(opcode);
+		let short_base = match opcode {
+			Opcode::ILoad | Opcode::LLoad | Opcode::FLoad | Opcode::DLoad | Opcode::ALoad => Some(u8::from(Opcode::ILoad0) + 4 * (byte - u8::from(Opcode::ILoad))),
+			Opcode::IStore | Opcode::LStore | Opcode::FStore | Opcode::DStore | Opcode::AStore => Some(u8::from(Opcode::IStore0) + 4 * (byte - u8::from(Opcode::IStore))),
+			Opcode::Ret => None,
+			_ => return self.fail(format!("{} does not take a local variable index", opcode)),
+		};
+		let instruction = match (short_base, u8::try_from(index)) {
+			(Some(base), _) if index <= 3 => Instruction::Plain {
+				opcode: Opcode::try_from(base + index as u8).expect("short load and store opcodes are contiguous"),
+				operands: vec![],
+			},
+			(_, Ok(index)) => Instruction::Plain { opcode, operands: vec![index] },
+			(_, Err(_)) => {
+				let [high, low] = index.to_be_bytes();
+				Instruction::Plain { opcode: Opcode::Wide, operands: vec![byte, high, low] }
+			}
+		};
+		self.emit(Ok(instruction))
+	}
+
+	/// Increment local `index` by `delta`, using `wide` as needed.
+	pub fn iinc(&mut self, index: u16, delta: i16) -> &mut Self {
+		let instruction = match (u8::try_from(index), i8::try_from(delta)) {
+			(Ok(index), Ok(delta)) => Instruction::Plain { opcode: Opcode::IInc, operands: vec![index, delta as u8] },
+			_ => {
+				let [index_high, index_low] = index.to_be_bytes();
+				let [delta_high, delta_low] = delta.to_be_bytes();
+				Instruction::Plain { opcode: Opcode::Wide, operands: vec![u8::from(Opcode::IInc), index_high, index_low, delta_high, delta_low] }
+			}
+		};
+		self.emit(Ok(instruction))
+	}
+
+	/// A conditional branch or `goto` to `target`. A `goto` found too far away becomes `goto_w` when
+	/// the code is laid out; a conditional branch that far is an error.
+	pub fn jump(&mut self, opcode: Opcode, target: Label) -> &mut Self {
+		self.emit(match instruction::is_branch(opcode) {
+			true => Ok(Instruction::Branch { opcode, target }),
+			false => Err(EncodeError { msg: format!("{} is not a branch", opcode) }),
+		})
+	}
+
+	/// A tableswitch jumping to `targets[n]` for the key `low + n`.
+	pub fn table_switch(&mut self, low: i32, default: Label, targets: &[Label]) -> &mut Self {
+		self.emit(match targets.is_empty() {
+			false => Ok(Instruction::TableSwitch { default, low, targets: targets.to_vec() }),
+			true => Err(EncodeError { msg: "tableswitch needs at least one target".to_string() }),
+		})
+	}
+
+	/// A lookupswitch; the pairs may be given in any order, but no key twice.
+	pub fn lookup_switch(&mut self, default: Label, pairs: &[(i32, Label)]) -> &mut Self {
+		let mut pairs = pairs.to_vec();
+		pairs.sort_by_key(|(key, _)| *key);
+		self.emit(match pairs.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+			None => Ok(Instruction::LookupSwitch { default, pairs }),
+			Some(pair) => Err(EncodeError { msg: format!("lookupswitch key {} is given more than once", pair[0].0) }),
+		})
+	}
+
+	/// Push a constant with `ldc`, `ldc_w` or `ldc2_w`, whichever fits.
+	pub fn ldc<C: Into<Constant>>(&mut self, constant: C) -> &mut Self {
+		let constant = constant.into();
+		let instruction = Self::constant_index(self.pool, &constant).map(|index| match constant {
+			Constant::Long(_) | Constant::Double(_) => Self::indexed(Opcode::Ldc2W, index),
+			_ => match u8::try_from(index) {
+				Ok(index) => Instruction::Plain { opcode: Opcode::Ldc, operands: vec![index] },
+				Err(_) => Self::indexed(Opcode::LdcW, index),
+			},
+		});
+		self.emit(instruction)
+	}
+
+	/// `new`, `anewarray`, `checkcast` or `instanceof` of a class by internal name, or of an array
+	/// class by descriptor.
+	pub fn type_op(&mut self, opcode: Opcode, class_name: &str) -> &mut Self {
+		if !matches!(opcode, Opcode::New | Opcode::ANewArray | Opcode::CheckCast | Opcode::InstanceOf) {
+			return self.fail(format!("{} does not take a class operand", opcode));
+		}
+		let instruction = self.pool.add_class(class_name).map(|index| Self::indexed(opcode, index));
+		self.emit(instruction)
+	}
+
+	/// Create a multidimensional array of the type `descriptor`, taking `dimensions` counts from the stack.
+	pub fn multi_anew_array(&mut self, descriptor: &str, dimensions: u8) -> &mut Self {
+		if dimensions == 0 {
+			return self.fail("multianewarray needs at least one dimension".to_string());
+		}
+		let instruction = self.pool.add_class(descriptor).map(|index| {
+			let [high, low] = index.to_be_bytes();
+			Instruction::Plain { opcode: Opcode::MultiANewArray, operands: vec![high, low, dimensions] }
+		});
+		self.emit(instruction)
+	}
+
+	/// `getstatic`, `putstatic`, `getfield` or `putfield`.
+	pub fn field(&mut self, opcode: Opcode, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+		if !matches!(opcode, Opcode::GetStatic | Opcode::PutStatic | Opcode::GetField | Opcode::PutField) {
+			return self.fail(format!("{} does not access a field", opcode));
+		}
+		let instruction = self.pool.add_field_ref(owner, name, descriptor).map(|index| Self::indexed(opcode, index));
+		self.emit(instruction)
+	}
+
+	/// `invokevirtual`, `invokespecial`, `invokestatic` or `invokeinterface`. `interface` says whether
+	/// `owner` is an interface, and must be set for `invokeinterface`.
+	pub fn invoke(&mut self, opcode: Opcode, owner: &str, name: &str, descriptor: &str, interface: bool) -> &mut Self {
+		if !matches!(opcode, Opcode::InvokeVirtual | Opcode::InvokeSpecial | Opcode::InvokeStatic | Opcode::InvokeInterface) {
+			return self.fail(format!("{} does not invoke a method", opcode));
+		}
+		if opcode == Opcode::InvokeInterface && !interface {
+			return self.fail(format!("invokeinterface of {}.{} needs an interface", owner, name));
+		}
+		let reference = match interface {
+			true => self.pool.add_interface_method_ref(owner, name, descriptor),
+			false => self.pool.add_method_ref(owner, name, descriptor),
+		};
+		let instruction = reference.and_then(|index| {
+			let mut instruction = Self::indexed(opcode, index);
+			if opcode == Opcode::InvokeInterface {
+				let slots = stack_map::parameter_slots(descriptor)
+					.ok_or_else(|| EncodeError { msg: format!("malformed method descriptor {}", descriptor) })?;
+				if let Instruction::Plain { operands, .. } = &mut instruction {
+					operands.extend([(slots + 1) as u8, 0]);
+				}
+			}
+			Ok(instruction)
+		});
+		self.emit(instruction)
+	}
+
+	/// `invokedynamic` of a call site bootstrapped by `bootstrap` with the given static arguments.
+	pub fn invoke_dynamic(&mut self, name: &str, descriptor: &str, bootstrap: &Handle, arguments: &[Constant]) -> &mut Self {
+		let instruction = self.bootstrap_method(bootstrap, arguments)
+			.and_then(|bootstrap_method| self.pool.add_invoke_dynamic(bootstrap_method, name, descriptor))
+			.map(|index| {
+				let [high, low] = index.to_be_bytes();
+				Instruction::Plain { opcode: Opcode::InvokeDynamic, operands: vec![high, low, 0, 0] }
+			});
+		self.emit(instruction)
+	}
+
+	/// Guard the instructions from `start` up to `end` with a handler at `handler`, catching
+	/// `catch_type` or, if `None`, everything.
+	pub fn try_catch(&mut self, start: Label, end: Label, handler: Label, catch_type: Option<&str>) -> &mut Self {
+		match catch_type.map(|class_name| self.pool.add_class(class_name)).transpose() {
+			Ok(catch_type_index) => self.list.handlers.push(Handler { start, end, handler, catch_type_index: catch_type_index.unwrap_or(0) }),
+			Err(e) => self.error = self.error.take().or(Some(e)),
+		}
+		self
+	}
+
+	/// Describe local `index` between `start` and `end` for debuggers.
+	pub fn local_variable(&mut self, name: &str, descriptor: &str, start: Label, end: Label, index: u16) -> &mut Self {
+		match self.pool.add_utf8(name).and_then(|name_index| Ok((name_index, self.pool.add_utf8(descriptor)?))) {
+			Ok((name_index, descriptor_index)) => self.list.local_variables.push(LocalVariableRange { start, end, name_index, descriptor_index, index }),
+			Err(e) => self.error = self.error.take().or(Some(e)),
+		}
+		self
+	}
+
+	fn emit(&mut self, instruction: Result<Instruction<Label>, EncodeError>) -> &mut Self {
+		match instruction {
+			Ok(instruction) => self.list.push(Element::Instruction(instruction)),
+			Err(e) => self.error = self.error.take().or(Some(e)),
+		}
+		self
+	}
+
+	fn fail(&mut self, msg: String) -> &mut Self {
+		self.emit(Err(EncodeError { msg }))
+	}
+
+	fn indexed(opcode: Opcode, index: u16) -> Instruction<Label> {
+		Instruction::Plain { opcode, operands: index.to_be_bytes().to_vec() }
+	}
+
+	fn constant_index(pool: &mut ConstantPoolBuilder, constant: &Constant) -> Result<u16, EncodeError> {
+		match constant {
+			Constant::Integer(value) => pool.add_integer(*value),
+			Constant::Float(value) => pool.add_float(*value),
+			Constant::Long(value) => pool.add_long(*value),
+			Constant::Double(value) => pool.add_double(*value),
+			Constant::String(value) => pool.add_string(value),
+			Constant::Class(name) => pool.add_class(name),
+			Constant::MethodType(descriptor) => pool.add_method_type(descriptor),
+			Constant::MethodHandle(handle) => {
+				let reference = match handle.reference_kind {
+					Handle::REF_GET_FIELD..=Handle::REF_PUT_STATIC => pool.add_field_ref(&handle.owner, &handle.name, &handle.descriptor)?,
+					_ if handle.interface => pool.add_interface_method_ref(&handle.owner, &handle.name, &handle.descriptor)?,
+					_ => pool.add_method_ref(&handle.owner, &handle.name, &handle.descriptor)?,
+				};
+				pool.add_method_handle(handle.reference_kind, reference)
+			}
+		}
+	}
+
+	/// The index of a BootstrapMethods entry, shared by every call site with the same bootstrap.
+	fn bootstrap_method(&mut self, bootstrap: &Handle, arguments: &[Constant]) -> Result<u16, EncodeError> {
+		let bootstrap_method_ref = Self::constant_index(self.pool, &Constant::MethodHandle(bootstrap.clone()))?;
+		let bootstrap_arguments = arguments.iter().map(|argument| Self::constant_index(self.pool, argument)).collect::<Result<Vec<_>, _>>()?;
+		let entry = BootstrapMethodEntry {
+			bootstrap_method_ref,
+			num_bootstrap_arguments: bootstrap_arguments.len() as u16,
+			bootstrap_arguments,
+		};
+		let index = match self.bootstrap_methods.iter().position(|existing| *existing == entry) {
+			Some(index) => index,
+			None => {
+				self.bootstrap_methods.push(entry);
+				self.bootstrap_methods.len() - 1
+			}
+		};
+		Ok(index as u16)
+	}
+}
+
+struct PendingMethod {
+	access_flags: u16,
+	name: String,
+	descriptor: String,
+	/// `None` for abstract and native methods.
+	code: Option<InstructionList>,
+}
+
+/// Assembles a class from Rust, in the manner of ASM's ClassWriter with COMPUTE_FRAMES.
+///
+/// Methods get max_stack, max_locals and a StackMapTable computed by `FrameComputer`, so code can
+/// be written without tracking any of them. The class defaults to a public subclass of
+/// `java/lang/Object` in version 61 (Java 17) format.
+pub struct ClassBuilder<'a> {
+	name: String,
+	flags: Vec<ClassAccessPropertyFlags>,
+	super_class: Option<String>,
+	interfaces: Vec<String>,
+	major_version: u16,
+	minor_version: u16,
+	source_file: Option<String>,
+	pool: ConstantPoolBuilder,
+	bootstrap_methods: Vec<BootstrapMethodEntry>,
+	fields: Vec<Field>,
+	methods: Vec<PendingMethod>,
+	common_super_class: Option<CommonSuperClass<'a>>,
+	error: Option<EncodeError>,
+}
+
+impl<'a> ClassBuilder<'a> {
+
+	/// Start a class with the given internal name.
+	pub fn new(name: &str) -> ClassBuilder<'a> {
+		ClassBuilder {
+			name: name.to_string(),
+			flags: vec![ClassAccessPropertyFlags::Public, ClassAccessPropertyFlags::Super],
+			super_class: Some("java/lang/Object".to_string()),
+			interfaces: vec![],
+			major_version: 61,
+			minor_version: 0,
+			source_file: None,
+			pool: ConstantPoolBuilder::new(),
+			bootstrap_methods: vec![],
+			fields: vec![],
+			methods: vec![],
+			common_super_class: None,
+			error: None,
+		}
+	}
+
+	pub fn flags(mut self, flags: &[ClassAccessPropertyFlags]) -> Self {
+		self.flags = flags.to_vec();
+		self
+	}
+
+	pub fn super_class(mut self, name: &str) -> Self {
+		self.super_class = Some(name.to_string());
+		self
+	}
+
+	pub fn interface(mut self, name: &str) -> Self {
+		self.interfaces.push(name.to_string());
+		self
+	}
+
+	/// The class file version; StackMapTable attributes are only written for major version 50 and up.
+	pub fn version(mut self, major_version: u16, minor_version: u16) -> Self {
+		self.major_version = major_version;
+		self.minor_version = minor_version;
+		self
+	}
+
+	pub fn source_file(mut self, name: &str) -> Self {
+		self.source_file = Some(name.to_string());
+		self
+	}
+
+	/// The least common superclass of two classes, used where control flow merges references of
+	/// different types. Without one, `java/lang/Object` is assumed.
+	pub fn common_super_class<F: Fn(&str, &str) -> String + 'a>(mut self, common_super_class: F) -> Self {
+		self.common_super_class = Some(Box::new(common_super_class));
+		self
+	}
+
+	pub fn field(self, flags: &[FieldAccessPropertyFlags], name: &str, descriptor: &str) -> Self {
+		self.add_field(flags, name, descriptor, None)
+	}
+
+	/// A field with a ConstantValue attribute (JVMS17 4.7.2), initialised before any code runs when static.
+	pub fn constant_field<C: Into<Constant>>(self, flags: &[FieldAccessPropertyFlags], name: &str, descriptor: &str, value: C) -> Self {
+		self.add_field(flags, name, descriptor, Some(value.into()))
+	}
+
+	/// Declare a method, with `body` emitting its code. The body is not called for abstract and
+	/// native methods, which have none.
+	pub fn method<F: FnOnce(&mut MethodBuilder)>(mut self, flags: &[MethodAccessPropertyFlags], name: &str, descriptor: &str, body: F) -> Self {
+		let access_flags = flags.iter().fold(0, |access_flags, flag| access_flags | *flag as u16);
+		let code = if flags.iter().any(|flag| matches!(flag, MethodAccessPropertyFlags::Abstract | MethodAccessPropertyFlags::Native)) {
+			None
+		} else {
+			let mut builder = MethodBuilder {
+				pool: &mut self.pool,
+				bootstrap_methods: &mut self.bootstrap_methods,
+				list: InstructionList::new(),
+				error: None,
+			};
+			body(&mut builder);
+			if let Some(e) = builder.error {
+				self.error = self.error.take().or(Some(EncodeError { msg: format!("{} in {}{}", e.msg, name, descriptor) }));
+			}
+			Some(builder.list)
+		};
+		self.methods.push(PendingMethod { access_flags, name: name.to_string(), descriptor: descriptor.to_string(), code });
+		self
+	}
+
+	/// Compute the frames of every method and assemble the class.
+	pub fn build(mut self) -> Result<Class, EncodeError> {
+		if let Some(e) = self.error.take() {
+			return Err(e);
+		}
+		let this_class = self.class(&self.name.clone())?;
+		let super_class = match self.super_class.clone() {
+			Some(_) if self.name == "java/lang/Object" => None,
+			Some(name) => Some(self.class(&name)?),
+			None => None,
+		};
+		let interfaces = self.interfaces.clone().iter().map(|name| self.class(name)).collect::<Result<Vec<_>, _>>()?;
+
+		let mut methods = Vec::new();
+		for pending in std::mem::take(&mut self.methods) {
+			let mut attributes = Vec::new();
+			if let Some(list) = pending.code {
+				let code = self.code(list, pending.access_flags, &pending.name, &pending.descriptor)?;
+				attributes.push(Attribute {
+					name_index: self.pool.add_utf8("Code")?,
+					length: code.attribute_length(),
+					attribute_info: AttributeInfo::Code(code),
+				});
+			}
+			methods.push(Method {
+				access_flags: pending.access_flags,
+				name_index: self.pool.add_utf8(&pending.name)?,
+				descriptor_index: self.pool.add_utf8(&pending.descriptor)?,
+				attributes_count: attributes.len() as u16,
+				attributes,
+			});
+		}
+
+		let mut attributes = Vec::new();
+		if let Some(source_file) = self.source_file.clone() {
+			let source_file_index = self.pool.add_utf8(&source_file)?;
+			attributes.push(Self::attribute(&mut self.pool, "SourceFile", AttributeInfo::SourceFile(SourceFile { source_file_index }))?);
+		}
+		if !self.bootstrap_methods.is_empty() {
+			let bootstrap_methods = std::mem::take(&mut self.bootstrap_methods);
+			attributes.push(Self::attribute(&mut self.pool, "BootstrapMethods", AttributeInfo::BootstrapMethods(BootstrapMethods {
+				num_bootstrap_methods: bootstrap_methods.len() as u16,
+				bootstrap_methods,
+			}))?);
+		}
+
+		Ok(Class {
+			major_version: self.major_version,
+			minor_version: self.minor_version,
+			constant_pool: self.pool.into_constants(),
+			flags: self.flags,
+			this_class,
+			super_class,
+			interfaces,
+			fields: Fields { fields_count: self.fields.len() as u16, fields: self.fields },
+			methods: Methods { method_count: methods.len() as u16, methods },
+			attributes: ClassAttributes { attribute_count: attributes.len() as u16, attributes },
+		})
+	}
+
+	fn add_field(mut self, flags: &[FieldAccessPropertyFlags], name: &str, descriptor: &str, value: Option<Constant>) -> Self {
+		let field = (|| {
+			let mut attributes = Vec::new();
+			if let Some(value) = value {
+				let constant_value_index = MethodBuilder::constant_index(&mut self.pool, &value)?;
+				attributes.push(Self::attribute(&mut self.pool, "ConstantValue", AttributeInfo::ConstantValue(ConstantValue { constant_value_index }))?);
+			}
+			Ok(Field {
+				access_flags: flags.iter().fold(0, |access_flags, flag| access_flags | *flag as u16),
+				name_index: self.pool.add_utf8(name)?,
+				descriptor_index: self.pool.add_utf8(descriptor)?,
+				attributes_count: attributes.len() as u16,
+				attributes,
+			})
+		})();
+		match field {
+			Ok(field) => self.fields.push(field),
+			Err(e) => self.error = self.error.take().or(Some(e)),
+		}
+		self
+	}
+
+	/// A Class constant as the class structure holds it, by the index of its name.
+	fn class(&mut self, name: &str) -> Result<constant_pool::Class, EncodeError> {
+		self.pool.add_class(name)?;
+		Ok(constant_pool::Class { index: self.pool.add_utf8(name)? })
+	}
+
+	fn code(&mut self, mut list: InstructionList, access_flags: u16, name: &str, descriptor: &str) -> Result<Code, EncodeError> {
+		let mut computer = FrameComputer::new(&self.name);
+		if let Some(common_super_class) = &self.common_super_class {
+			computer = computer.common_super_class(|a, b| common_super_class(a, b));
+		}
+		let limits = computer.compute(&mut list, &mut self.pool, access_flags, name, descriptor)?;
+
+		// commit only fills in the tables the Code attribute already has
+		let mut attributes = Vec::new();
+		if !list.lines.is_empty() {
+			attributes.push(Self::attribute(&mut self.pool, "LineNumberTable", AttributeInfo::LineNumberTable(LineNumberTable { table_length: 0, lines: vec![] }))?);
+		}
+		if !list.local_variables.is_empty() {
+			attributes.push(Self::attribute(&mut self.pool, "LocalVariableTable", AttributeInfo::LocalVariableTable(LocalVariableTable { local_variable_table_length: 0, local_variables: vec![] }))?);
+		}
+		if !list.frames.is_empty() && self.major_version >= 50 {
+			attributes.push(Self::attribute(&mut self.pool, "StackMapTable", AttributeInfo::StackMapTable(StackMapTable { number_of_entries: 0, entries: vec![] }))?);
+		}
+		let mut code = Code {
+			max_stack: limits.max_stack,
+			max_locals: limits.max_locals,
+			code_length: 0,
+			code: vec![],
+			handler_count: 0,
+			handlers: vec![],
+			attributes_count: attributes.len() as u16,
+			attributes,
+		};
+		list.commit(&mut code)?;
+		Ok(code)
+	}
+
+	fn attribute(pool: &mut ConstantPoolBuilder, name: &str, attribute_info: AttributeInfo) -> Result<Attribute, EncodeError> {
+		let mut encoded = binrw::io::Cursor::new(Vec::new());
+		attribute_info.write_options(&mut encoded, binrw::Endian::Big, ()).map_err(|e| EncodeError { msg: e.to_string() })?;
+		Ok(Attribute {
+			name_index: pool.add_utf8(name)?,
+			length: encoded.into_inner().len() as u32,
+			attribute_info,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use crate::{
+		class::{
+			access::{FieldAccessPropertyFlags, MethodAccessPropertyFlags},
+			attribute::AttributeInfo,
+			builder::{ClassBuilder, Constant, Handle, MethodBuilder},
+			class::Class,
+			constant_pool::ConstantPoolItem},
+		isa::{
+			instruction::{self, Instruction},
+			opcode::Opcode},
+	};
+
+	/// Emits the code of a method, less its final return.
+	type Body = fn(&mut MethodBuilder);
+
+	fn sample() -> ClassBuilder<'static> {
+		ClassBuilder::new("generated/Counter")
+			.source_file("Counter.java")
+			.constant_field(&[FieldAccessPropertyFlags::Public, FieldAccessPropertyFlags::Static, FieldAccessPropertyFlags::Final], "LIMIT", "J", 1i64 << 40)
+			.field(&[FieldAccessPropertyFlags::Private], "count", "I")
+			.method(&[MethodAccessPropertyFlags::Public], "<init>", "()V", |m| {
+				m.line(3)
+					.var(Opcode::ALoad, 0)
+					.invoke(Opcode::InvokeSpecial, "java/lang/Object", "<init>", "()V", false)
+					.op(Opcode::Return);
+			})
+			.method(&[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Static], "sum", "([I)I", |m| {
+				let (top, end) = (m.new_label(), m.new_label());
+				m.push_int(0).var(Opcode::IStore, 1)
+					.push_int(0).var(Opcode::IStore, 2)
+					.label(top)
+					.var(Opcode::ILoad, 2).var(Opcode::ALoad, 0).op(Opcode::ArrayLength).jump(Opcode::IfICmpGe, end)
+					.var(Opcode::ILoad, 1).var(Opcode::ALoad, 0).var(Opcode::ILoad, 2).op(Opcode::IALoad).op(Opcode::IAdd).var(Opcode::IStore, 1)
+					.iinc(2, 1)
+					.jump(Opcode::Goto, top)
+					.label(end)
+					.var(Opcode::ILoad, 1).op(Opcode::IReturn);
+			})
+			.method(&[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Static], "first", "([I)I", |m| {
+				let (start, end, handler) = (m.new_label(), m.new_label(), m.new_label());
+				m.label(start)
+					.var(Opcode::ALoad, 0).push_int(0).op(Opcode::IALoad)
+					.label(end)
+					.op(Opcode::IReturn)
+					.label(handler)
+					.op(Opcode::Pop).push_int(-1).op(Opcode::IReturn)
+					.try_catch(start, end, handler, Some("java/lang/ArrayIndexOutOfBoundsException"));
+			})
+			.method(&[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Abstract], "next", "()I", |_| {})
+	}
+
+	fn method_code(clazz: &Class, name: &str, descriptor: &str) -> crate::class::attribute::Code {
+		clazz.find_method(name, descriptor).and_then(|method| method.code()).cloned().expect("Expected method with code")
+	}
+
+	#[test]
+	fn test_build() {
+		let built = sample().build().unwrap();
+		let clazz = Class::new(Cursor::new(built.to_bytes().unwrap()));
+		assert_eq!(clazz.name(), "generated/Counter");
+		assert_eq!(clazz.super_class_name().as_deref(), Some("java/lang/Object"));
+		assert_eq!(clazz.major_version, 61);
+		assert!(clazz.find_field("count", "I").is_some());
+		assert!(clazz.find_method("next", "()I").unwrap().code().is_none());
+
+		let sum = method_code(&clazz, "sum", "([I)I");
+		assert_eq!((sum.max_stack, sum.max_locals), (3, 3));
+		let frames = sum.attributes.iter().find_map(|attribute| match &attribute.attribute_info {
+			AttributeInfo::StackMapTable(table) => Some(table.entries.len()),
+			_ => None,
+		});
+		assert_eq!(frames, Some(2));
+
+		let first = method_code(&clazz, "first", "([I)I");
+		assert_eq!(first.handlers.len(), 1);
+		assert_eq!(clazz.get_class_name_at(first.handlers[0].catch_type_index).as_deref(), Some("java/lang/ArrayIndexOutOfBoundsException"));
+		assert_eq!((first.max_stack, first.max_locals), (2, 1));
+
+		let field = clazz.find_field("LIMIT", "J").unwrap();
+		let Some(AttributeInfo::ConstantValue(value)) = field.attributes.first().map(|attribute| &attribute.attribute_info) else {
+			panic!("Expected a ConstantValue attribute");
+		};
+		assert!(matches!(clazz.constant_pool.get(&value.constant_value_index), Some(ConstantPoolItem::Long(_))));
+	}
+
+	#[test]
+	fn test_operand_forms() {
+		let built = ClassBuilder::new("Forms")
+			.method(&[MethodAccessPropertyFlags::Static], "forms", "()V", |m| {
+				m.push_int(5).op(Opcode::Pop)
+					.push_int(100).op(Opcode::Pop)
+					.push_int(1000).op(Opcode::Pop)
+					.push_int(100000).op(Opcode::Pop)
+					.ldc(2.5f64).op(Opcode::Pop2)
+					.push_int(1).var(Opcode::IStore, 3)
+					.push_int(1).var(Opcode::IStore, 300)
+					.iinc(300, 1000)
+					.op(Opcode::Return);
+			})
+			.build().unwrap();
+		let code = method_code(&built, "forms", "()V");
+		let opcodes: Vec<Opcode> = instruction::decode(&code.code).unwrap().into_iter().map(|(_, instruction)| instruction.opcode()).collect();
+		assert_eq!(opcodes, vec![
+			Opcode::IConst5, Opcode::Pop,
+			Opcode::BIpush, Opcode::Pop,
+			Opcode::SIpush, Opcode::Pop,
+			Opcode::Ldc, Opcode::Pop,
+			Opcode::Ldc2W, Opcode::Pop2,
+			Opcode::IConst1, Opcode::IStore3,
+			Opcode::IConst1, Opcode::Wide,
+			Opcode::Wide,
+			Opcode::Return,
+		]);
+		assert_eq!(code.max_locals, 301);
+		assert!(matches!(&instruction::decode(&code.code).unwrap()[14].1,
+			Instruction::Plain { opcode: Opcode::Wide, operands } if operands[0] == u8::from(Opcode::IInc)));
+	}
+
+	#[test]
+	fn test_invoke_dynamic() {
+		let bootstrap = Handle::new(Handle::REF_INVOKE_STATIC, "java/lang/invoke/StringConcatFactory", "makeConcatWithConstants",
+			"(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/invoke/MethodType;Ljava/lang/String;[Ljava/lang/Object;)Ljava/lang/invoke/CallSite;", false);
+		let built = ClassBuilder::new("Concat")
+			.method(&[MethodAccessPropertyFlags::Static], "twice", "(I)Ljava/lang/String;", |m| {
+				m.var(Opcode::ILoad, 0)
+					.invoke_dynamic("makeConcatWithConstants", "(I)Ljava/lang/String;", &bootstrap, &[Constant::from("\u{1}!")])
+					.var(Opcode::ILoad, 0)
+					.invoke_dynamic("makeConcatWithConstants", "(I)Ljava/lang/String;", &bootstrap, &[Constant::from("\u{1}!")])
+					.op(Opcode::Pop)
+					.op(Opcode::AReturn);
+			})
+			.build().unwrap();
+		// both call sites share one bootstrap method
+		assert_eq!(built.bootstrap_methods().unwrap().bootstrap_methods.len(), 1);
+	}
+
+	#[test]
+	fn test_errors() {
+		let result = ClassBuilder::new("Broken")
+			.method(&[MethodAccessPropertyFlags::Static], "broken", "()V", |m| {
+				m.int(Opcode::BIpush, 1000).op(Opcode::Return);
+			})
+			.build();
+		assert_eq!(result.unwrap_err().msg, "1000 is out of range for bipush in broken()V");
+
+		let result = ClassBuilder::new("Underflow")
+			.method(&[MethodAccessPropertyFlags::Static], "underflow", "()V", |m| {
+				m.op(Opcode::Pop).op(Opcode::Return);
+			})
+			.build();
+		assert!(result.unwrap_err().msg.starts_with("operand stack underflow"));
+
+		let invalid: [(&str, Body); 3] = [
+			("flat", |m| {
+				m.multi_anew_array("[[I", 0).op(Opcode::Pop);
+			}),
+			("interface", |m| {
+				m.op(Opcode::AConstNull).invoke(Opcode::InvokeInterface, "java/lang/Runnable", "run", "()V", false);
+			}),
+			("twice", |m| {
+				let (first, second) = (m.new_label(), m.new_label());
+				m.op(Opcode::IConst0).lookup_switch(first, &[(7, first), (3, second), (7, second)])
+					.label(first).label(second);
+			}),
+		];
+		let messages: Vec<String> = invalid.into_iter()
+			.map(|(name, body)| ClassBuilder::new("Invalid")
+				.method(&[MethodAccessPropertyFlags::Static], name, "()V", |m| {
+					body(m);
+					m.op(Opcode::Return);
+				})
+				.build().unwrap_err().msg)
+			.collect();
+		assert_eq!(messages, [
+			"multianewarray needs at least one dimension in flat()V",
+			"invokeinterface of java/lang/Runnable.run needs an interface in interface()V",
+			"lookupswitch key 7 is given more than once in twice()V",
+		]);
+	}
+}
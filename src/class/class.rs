@@ -3,28 +3,39 @@ use std::{collections::BTreeMap,
 	fmt::Display};
 
 use binrw::{
-	binrw, BinRead, BinReaderExt};
+	binrw, BinRead, BinReaderExt, BinWrite};
 use strum::IntoEnumIterator;
 
 use crate::class::{
-	access::{self, ClassAccessPropertyFlags}, attribute::Attribute, constant_pool::{self, ConstantPool, ConstantPoolItem, ConstantPoolRequiredArgs, RawConstantPool}, field::Field, method::Method};
+	access::{self, ClassAccessPropertyFlags}, attribute::{Attribute, AttributeInfo, BootstrapMethods}, constant_pool::{self, ConstantPool, ConstantPoolItem, ConstantPoolRequiredArgs, RawConstantPool}, field::Field, method::Method};
 
 /// A high-level container for class data.
 /// 
 /// This struct abstracts out the class file into a higher-level format that is a lot easier to work with.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Class {
 	pub major_version: u16,
 	pub minor_version: u16,
 	pub constant_pool: BTreeMap<u16, ConstantPoolItem>,
 	pub flags: Vec<ClassAccessPropertyFlags>,
 	pub this_class: constant_pool::Class,
-	pub super_class: constant_pool::Class,
+	/// The direct superclass, absent only for java/lang/Object and module-info.
+	pub super_class: Option<constant_pool::Class>,
+	pub interfaces: Vec<constant_pool::Class>,
 	pub fields: Fields,
 	pub methods: Methods,
 	pub attributes: ClassAttributes,
 }
 
+/// A field or method reference resolved to names.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MemberRef {
+	pub class_name: String,
+	pub name: String,
+	pub descriptor: String,
+}
+
 impl Display for Class {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		write!(f, "{:?}", self)
@@ -43,34 +54,282 @@ impl Class {
 	}
 
 	pub fn new<T: Read + Seek>(mut stream: T) -> Class {
-		let header: Header = stream.read_be().expect("Could not parse header");
-		let raw_constant_pool: RawConstantPool = stream.read_be().expect("Could not parse constant pool");
+		stream.read_be().expect("Could not parse class")
+	}
+
+	/// Look up a Utf8 constant and decode it.
+	pub fn get_utf8(&self, index: u16) -> Option<String> {
+		match self.constant_pool.get(&index) {
+			Some(ConstantPoolItem::Utf8(utf8)) => Some(utf8.to_string()),
+			_ => None,
+		}
+	}
+
+	/// The internal name (e.g. `java/lang/String`) named by a Class constant.
+	pub fn get_class_name(&self, class: &constant_pool::Class) -> Option<String> {
+		self.get_utf8(class.index)
+	}
+
+	/// The internal name of the Class constant at `index`.
+	pub fn get_class_name_at(&self, index: u16) -> Option<String> {
+		match self.constant_pool.get(&index) {
+			Some(ConstantPoolItem::Class(class)) => self.get_class_name(class),
+			_ => None,
+		}
+	}
+
+	/// The name and descriptor named by a NameAndType constant.
+	pub fn get_name_and_type(&self, index: u16) -> Option<(String, String)> {
+		match self.constant_pool.get(&index) {
+			Some(ConstantPoolItem::NameAndType(name_and_type)) => Some((self.get_utf8(name_and_type.name_index)?, self.get_utf8(name_and_type.type_index)?)),
+			_ => None,
+		}
+	}
+
+	/// Resolve a FieldRef, MethodRef or InterfaceMethodRef constant to names.
+	pub fn get_member_ref(&self, index: u16) -> Option<MemberRef> {
+		let (class_index, name_and_type_index) = match self.constant_pool.get(&index) {
+			Some(ConstantPoolItem::FieldRef(reference)) => (reference.class_index, reference.name_and_type_index),
+			Some(ConstantPoolItem::MethodRef(reference)) => (reference.class_index, reference.name_and_type_index),
+			Some(ConstantPoolItem::InterfaceMethodRef(reference)) => (reference.class_index, reference.name_and_type_index),
+			_ => return None,
+		};
+		let (name, descriptor) = self.get_name_and_type(name_and_type_index)?;
+		Some(MemberRef { class_name: self.get_class_name_at(class_index)?, name, descriptor })
+	}
+
+	/// The internal names of the annotation types in a class, field or method's
+	/// RuntimeVisibleAnnotations and RuntimeInvisibleAnnotations attributes.
+	pub fn get_annotation_types(&self, attributes: &[Attribute]) -> Vec<String> {
+		attributes.iter()
+			.filter_map(|attribute| match &attribute.attribute_info {
+				AttributeInfo::RuntimeVisibleAnnotations(annotations) | AttributeInfo::RuntimeInvisibleAnnotations(annotations) => Some(annotations),
+				_ => None,
+			})
+			.flat_map(|annotations| annotations.annotations.iter())
+			.filter_map(|annotation| self.get_utf8(annotation.type_index))
+			.map(|descriptor| descriptor.strip_prefix('L').and_then(|name| name.strip_suffix(';')).map(str::to_string).unwrap_or(descriptor))
+			.collect()
+	}
+
+	/// The class's BootstrapMethods attribute, if it has one.
+	pub fn bootstrap_methods(&self) -> Option<&BootstrapMethods> {
+		self.attributes.attributes.iter().find_map(|attribute| match &attribute.attribute_info {
+			AttributeInfo::BootstrapMethods(bootstrap_methods) => Some(bootstrap_methods),
+			_ => None,
+		})
+	}
+
+	/// The value a constant pool entry stands for, with every index it holds resolved.
+	pub fn render_constant(&self, index: u16) -> String {
+		let member = |class_index: u16, name_and_type_index: u16, separator: &str| match self.get_name_and_type(name_and_type_index) {
+			Some((name, descriptor)) => format!("{}.{}{}{}", self.render_constant(class_index), name, separator, descriptor),
+			None => format!("#{}", index),
+		};
+		let call_site = |bootstrap_method_attr_index: u16, name_and_type_index: u16| {
+			let (name, descriptor) = self.get_name_and_type(name_and_type_index).unwrap_or_default();
+			let bootstrap = self.bootstrap_methods()
+				.and_then(|bootstrap_methods| bootstrap_methods.bootstrap_methods.get(usize::from(bootstrap_method_attr_index)))
+				.map(|entry| format!("{}({})",
+					self.render_constant(entry.bootstrap_method_ref),
+					entry.bootstrap_arguments.iter().map(|argument| self.render_constant(*argument)).collect::<Vec<_>>().join(", ")))
+				.unwrap_or_else(|| format!("bootstrap #{}", bootstrap_method_attr_index));
+			format!("{}:{} {}", name, descriptor, bootstrap)
+		};
+		match self.constant_pool.get(&index) {
+			Some(ConstantPoolItem::Utf8(utf8)) => format!("{:?}", utf8.to_string()),
+			Some(ConstantPoolItem::Integer(integer)) => integer.value.to_string(),
+			Some(ConstantPoolItem::Float(float)) => format!("{:?}f", float.value),
+			Some(ConstantPoolItem::Long(long)) => format!("{}L", long.value),
+			Some(ConstantPoolItem::Double(double)) => format!("{:?}d", double.value),
+			Some(ConstantPoolItem::Class(class_constant)) => self.get_utf8(class_constant.index).unwrap_or_default(),
+			Some(ConstantPoolItem::String(string)) => format!("{:?}", self.get_utf8(string.index).unwrap_or_default()),
+			Some(ConstantPoolItem::FieldRef(reference)) => member(reference.class_index, reference.name_and_type_index, ":"),
+			Some(ConstantPoolItem::MethodRef(reference)) => member(reference.class_index, reference.name_and_type_index, ""),
+			Some(ConstantPoolItem::InterfaceMethodRef(reference)) => member(reference.class_index, reference.name_and_type_index, ""),
+			Some(ConstantPoolItem::NameAndType(name_and_type)) => format!("{}:{}",
+				self.get_utf8(name_and_type.name_index).unwrap_or_default(), self.get_utf8(name_and_type.type_index).unwrap_or_default()),
+			Some(ConstantPoolItem::MethodHandle(handle)) => {
+				let kind = match handle.reference_kind {
+					1 => "getField",
+					2 => "getStatic",
+					3 => "putField",
+					4 => "putStatic",
+					5 => "invokeVirtual",
+					6 => "invokeStatic",
+					7 => "invokeSpecial",
+					8 => "newInvokeSpecial",
+					9 => "invokeInterface",
+					_ => "unknown",
+				};
+				format!("{} {}", kind, self.render_constant(handle.reference_index))
+			}
+			Some(ConstantPoolItem::MethodType(method_type)) => self.get_utf8(method_type.descriptor_index).unwrap_or_default(),
+			Some(ConstantPoolItem::Dynamic(dynamic)) => call_site(dynamic.bootstrap_method_attr_index, dynamic.name_and_type_index),
+			Some(ConstantPoolItem::InvokeDynamic(dynamic)) => call_site(dynamic.bootstrap_method_attr_index, dynamic.name_and_type_index),
+			Some(ConstantPoolItem::Module(module)) => self.get_utf8(module.name_index).unwrap_or_default(),
+			Some(ConstantPoolItem::Package(package)) => self.get_utf8(package.name_index).unwrap_or_default(),
+			None => format!("#{}", index),
+		}
+	}
+
+	/// The constant pool index of the Class constant naming the same class as `class`.
+	pub fn get_class_index(&self, class: &constant_pool::Class) -> Option<u16> {
+		self.constant_pool.iter().find_map(|(index, item)| match item {
+			ConstantPoolItem::Class(candidate) if candidate == class => Some(*index),
+			_ => None,
+		})
+	}
+
+	/// The access_flags word of the class.
+	pub fn access_flags(&self) -> u16 {
+		self.flags.iter().fold(0, |flags, flag| flags | *flag as u16)
+	}
+
+	/// The constant_pool_count of the class: one more than the last index used, counting the
+	/// extra slot taken by a trailing long or double.
+	pub fn constant_pool_count(&self) -> u16 {
+		match self.constant_pool.last_key_value() {
+			Some((index, ConstantPoolItem::Long(_) | ConstantPoolItem::Double(_))) => index + 2,
+			Some((index, _)) => index + 1,
+			None => 1,
+		}
+	}
+
+	/// Serialise the class to class file format.
+	pub fn to_bytes(&self) -> binrw::BinResult<Vec<u8>> {
+		let mut writer = binrw::io::Cursor::new(Vec::new());
+		self.write_be(&mut writer)?;
+		Ok(writer.into_inner())
+	}
+
+	/// The internal name of this class.
+	pub fn name(&self) -> String {
+		self.get_class_name(&self.this_class).unwrap_or_default()
+	}
+
+	pub fn super_class_name(&self) -> Option<String> {
+		self.super_class.as_ref().and_then(|class| self.get_class_name(class))
+	}
+
+	pub fn interface_names(&self) -> Vec<String> {
+		self.interfaces.iter().filter_map(|class| self.get_class_name(class)).collect()
+	}
+
+	pub fn is_interface(&self) -> bool {
+		self.flags.contains(&ClassAccessPropertyFlags::Interface)
+	}
+
+	/// Find a method declared by this class by name and descriptor.
+	pub fn find_method(&self, name: &str, descriptor: &str) -> Option<&Method> {
+		self.methods.methods.iter().find(|method| {
+			self.get_utf8(method.name_index).as_deref() == Some(name) &&
+			self.get_utf8(method.descriptor_index).as_deref() == Some(descriptor)
+		})
+	}
+
+	/// Find a field declared by this class by name and descriptor.
+	pub fn find_field(&self, name: &str, descriptor: &str) -> Option<&Field> {
+		self.fields.fields.iter().find(|field| {
+			self.get_utf8(field.name_index).as_deref() == Some(name) &&
+			self.get_utf8(field.descriptor_index).as_deref() == Some(descriptor)
+		})
+	}
+}
+
+impl BinRead for Class {
+	type Args<'a> = ();
+
+	fn read_options<R: Read + Seek>(
+		reader: &mut R,
+		endian: binrw::Endian,
+		_args: (),
+	) -> binrw::BinResult<Self> {
+		let header = Header::read_options(reader, endian, ())?;
+		if header.magic != 0xCAFEBABE {
+			return Err(binrw::Error::BadMagic { pos: 0, found: Box::new(header.magic) });
+		}
+		let raw_constant_pool = RawConstantPool::read_options(reader, endian, ())?;
 		let constant_pool: ConstantPool = ConstantPool::from(raw_constant_pool);
-		let parameters: Parameters = stream.read_be().expect("Could not parse parameters");
-		let this_class = &constant_pool.get_class(parameters.this_class).unwrap();
-		let super_class = &constant_pool.get_class(parameters.super_class).unwrap();
+		let parameters_position = reader.stream_position()?;
+		let parameters = Parameters::read_options(reader, endian, ())?;
+		let class_at = |index: u16| constant_pool.get_class(index).map_err(|e| binrw::Error::AssertFail {
+			pos: parameters_position,
+			message: e.to_string(),
+		});
+		let this_class = class_at(parameters.this_class)?;
+		let super_class = match parameters.super_class {
+			0 => None,
+			index => Some(class_at(index)?),
+		};
+		let interfaces = parameters.interfaces.iter().map(|index| class_at(*index)).collect::<binrw::BinResult<Vec<_>>>()?;
 		let args = ConstantPoolRequiredArgs { constant_pool: constant_pool.clone() };
-		let fields: Fields = Fields::read_options(&mut stream, binrw::Endian::Big,  args.clone()).expect("Could not parse fields");
-		let methods: Methods = Methods::read_options(&mut stream, binrw::Endian::Big, args.clone()).expect("Could not parse methods");
-		let attributes: ClassAttributes = ClassAttributes::read_options(&mut stream, binrw::Endian::Big, args.clone()).expect("Could not parse class attributes");
+		let fields: Fields = Fields::read_options(reader, endian, args.clone())?;
+		let methods: Methods = Methods::read_options(reader, endian, args.clone())?;
+		let attributes: ClassAttributes = ClassAttributes::read_options(reader, endian, args.clone())?;
 
-		Class {
+		Ok(Class {
 			major_version: header.major_version,
 			minor_version: header.minor_version,
 			constant_pool: constant_pool.constants,
 			flags: Self::get_access_flags(parameters.access_flags),
-			this_class: *this_class,
-			super_class: *super_class,
-			fields: fields,
-			methods: methods,
-			attributes: attributes,
+			this_class,
+			super_class,
+			interfaces,
+			fields,
+			methods,
+			attributes,
+		})
+	}
+}
+
+impl BinWrite for Class {
+	type Args<'a> = ();
+
+	fn write_options<W: std::io::Write + Seek>(
+		&self,
+		writer: &mut W,
+		endian: binrw::Endian,
+		_args: (),
+	) -> binrw::BinResult<()> {
+		let assert_fail = |message: String| binrw::Error::AssertFail { pos: 0, message };
+		Header { magic: 0xCAFEBABE, minor_version: self.minor_version, major_version: self.major_version }.write_options(writer, endian, ())?;
+
+		self.constant_pool_count().write_options(writer, endian, ())?;
+		let mut expected: u16 = 1;
+		for (index, item) in &self.constant_pool {
+			if *index != expected {
+				return Err(assert_fail(format!("constant pool has no entry at {}", expected)));
+			}
+			item.write_options(writer, endian, ())?;
+			expected += match item {
+				ConstantPoolItem::Long(_) | ConstantPoolItem::Double(_) => 2,
+				_ => 1,
+			};
 		}
+
+		let class_index = |class: &constant_pool::Class| self.get_class_index(class)
+			.ok_or_else(|| assert_fail(format!("no Class constant names utf8 {}", class.index)));
+		let interfaces = self.interfaces.iter().map(class_index).collect::<binrw::BinResult<Vec<u16>>>()?;
+		Parameters {
+			access_flags: self.access_flags(),
+			this_class: class_index(&self.this_class)?,
+			super_class: match &self.super_class {
+				Some(super_class) => class_index(super_class)?,
+				None => 0,
+			},
+			interfaces_count: interfaces.len() as u16,
+			interfaces,
+		}.write_options(writer, endian, ())?;
+
+		self.fields.write_options(writer, endian, ())?;
+		self.methods.write_options(writer, endian, ())?;
+		self.attributes.write_options(writer, endian, ())
 	}
 }
 
 #[binrw]
 #[brw(big)]
-#[bw(magic = 0xCAFEBABEu32)]
 #[derive(Default)]
 pub struct Header {
 	/// The magic number 0xCAFEBABEu32.
@@ -98,6 +357,7 @@ pub struct Parameters {
 }
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Fields {
 	// The number of field entries.
 	pub fields_count: u16,
@@ -126,7 +386,22 @@ impl BinRead for Fields {
 	}
 }
 
+impl BinWrite for Fields {
+	type Args<'a> = ();
+
+	fn write_options<W: std::io::Write + Seek>(
+		&self,
+		writer: &mut W,
+		endian: binrw::Endian,
+		_args: (),
+	) -> binrw::BinResult<()> {
+		(self.fields.len() as u16).write_options(writer, endian, ())?;
+		self.fields.write_options(writer, endian, ())
+	}
+}
+
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Methods {
 	pub method_count: u16,
 	pub methods: Vec<Method>,
@@ -153,7 +428,22 @@ impl BinRead for Methods {
 	}
 }
 
+impl BinWrite for Methods {
+	type Args<'a> = ();
+
+	fn write_options<W: std::io::Write + Seek>(
+		&self,
+		writer: &mut W,
+		endian: binrw::Endian,
+		_args: (),
+	) -> binrw::BinResult<()> {
+		(self.methods.len() as u16).write_options(writer, endian, ())?;
+		self.methods.write_options(writer, endian, ())
+	}
+}
+
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ClassAttributes {
 	pub attribute_count: u16,
 	pub attributes: Vec<Attribute>,
@@ -180,6 +470,20 @@ impl BinRead for ClassAttributes {
 	}
 }
 
+impl BinWrite for ClassAttributes {
+	type Args<'a> = ();
+
+	fn write_options<W: std::io::Write + Seek>(
+		&self,
+		writer: &mut W,
+		endian: binrw::Endian,
+		_args: (),
+	) -> binrw::BinResult<()> {
+		(self.attributes.len() as u16).write_options(writer, endian, ())?;
+		self.attributes.write_options(writer, endian, ())
+	}
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -300,4 +604,13 @@ const CLASS_FILE_PATH: &str = "tests/resources/Sample.class";
 			}
 		}
 	} */
+
+	#[test]
+	fn test_write_round_trip() {
+		for path in [CLASS_FILE_PATH, "tests/resources/Branches.class", "tests/resources/calls/App.class"] {
+			let bytes = std::fs::read(path).expect("Couldn't access class file");
+			let class = Class::new(std::io::Cursor::new(&bytes));
+			assert_eq!(class.to_bytes().expect("Couldn't write class"), bytes, "{}", path);
+		}
+	}
 }
\ No newline at end of file
@@ -0,0 +1,418 @@
+use std::{
+	collections::HashMap,
+	fs::{self, File},
+	io::Read,
+	path::{Component, Path, PathBuf},
+	sync::Mutex};
+
+use zip::{result::ZipError, ZipArchive};
+
+use crate::class::{
+	archive::{self, ArchiveError, Entry},
+	class::Class,
+	jimage::{self, JImage}};
+
+/// The Java release whose classes are picked from multi-release JARs unless another is set.
+pub const DEFAULT_RELEASE: u32 = 17;
+
+const MANIFEST: &str = "META-INF/MANIFEST.MF";
+const VERSIONS: &str = "META-INF/versions/";
+/// Where a JMOD file keeps the classes and resources of its module.
+const JMOD_CLASSES: &str = "classes/";
+
+/// A JAR, ZIP or JMOD archive on the class path, kept open for lookups.
+#[derive(Debug)]
+pub struct ClassPathArchive {
+	pub path: PathBuf,
+	/// The directory resources are stored under, `classes/` for a JMOD file.
+	pub prefix: &'static str,
+	/// The releases with a `META-INF/versions/N/` directory, newest first; empty unless the
+	/// manifest declares `Multi-Release: true`.
+	pub versions: Vec<u32>,
+	archive: Mutex<ZipArchive<File>>,
+}
+
+impl ClassPathArchive {
+
+	fn read(&self, name: &str) -> Result<Option<Vec<u8>>, ArchiveError> {
+		let mut archive = self.archive.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+		let mut file = match archive.by_name(&format!("{}{}", self.prefix, name)) {
+			Ok(file) => file,
+			Err(ZipError::FileNotFound) => return Ok(None),
+			Err(error) => return Err(error.into()),
+		};
+		let mut bytes = Vec::with_capacity(file.size() as usize);
+		file.read_to_end(&mut bytes)?;
+		Ok(Some(bytes))
+	}
+}
+
+/// One element of a class path.
+#[derive(Debug)]
+pub enum ClassPathEntry {
+	/// An exploded directory, with resources stored under their names relative to it.
+	Directory(PathBuf),
+	Archive(ClassPathArchive),
+	/// A JDK runtime image, in which resources are found through the module of their package.
+	Image(JImage),
+	/// Resources held in memory, such as generated classes, under a path that only names them.
+	Memory { path: PathBuf, resources: HashMap<String, Vec<u8>> },
+}
+
+impl ClassPathEntry {
+
+	pub fn path(&self) -> &Path {
+		match self {
+			ClassPathEntry::Directory(path) => path,
+			ClassPathEntry::Archive(archive) => &archive.path,
+			ClassPathEntry::Image(image) => &image.path,
+			ClassPathEntry::Memory { path, .. } => path,
+		}
+	}
+
+	/// Read a resource, taking the newest version at or below `release` from a multi-release JAR
+	/// (JAR File Specification, "Multi-release JAR files"). The entry returned is named as requested.
+	fn find(&self, name: &str, release: u32) -> Result<Option<Entry>, ArchiveError> {
+		let bytes = match self {
+			ClassPathEntry::Directory(directory) => {
+				// Each component must name a file or directory within the one before, so no name
+				// reaches outside the directory.
+				let path = name.split('/').try_fold(directory.clone(), |path, component| {
+					let mut components = Path::new(component).components();
+					match (components.next(), components.next()) {
+						(Some(Component::Normal(normal)), None) if normal == component => Some(path.join(component)),
+						_ => None,
+					}
+				});
+				match path {
+					Some(path) if path.is_file() => Some(fs::read(path)?),
+					_ => None,
+				}
+			}
+			ClassPathEntry::Archive(archive) => {
+				let mut found = None;
+				for version in archive.versions.iter().filter(|version| **version <= release) {
+					found = archive.read(&format!("{}{}/{}", VERSIONS, version, name))?;
+					if found.is_some() {
+						break;
+					}
+				}
+				match found {
+					Some(bytes) => Some(bytes),
+					None => archive.read(name)?,
+				}
+			}
+			ClassPathEntry::Image(image) => image.read_resource(name)?,
+			ClassPathEntry::Memory { resources, .. } => resources.get(name).cloned(),
+		};
+		Ok(bytes.map(|bytes| Entry { name: name.to_string(), bytes }))
+	}
+}
+
+/// An ordered list of directories and archives searched for classes and resources by name, as
+/// with the `-cp` option of `java`.
+#[derive(Debug)]
+pub struct ClassPath {
+	entries: Vec<ClassPathEntry>,
+	release: u32,
+}
+
+impl Default for ClassPath {
+	fn default() -> Self {
+		ClassPath { entries: Vec::new(), release: DEFAULT_RELEASE }
+	}
+}
+
+impl ClassPath {
+
+	pub fn new() -> ClassPath {
+		ClassPath::default()
+	}
+
+	/// Parse a class path in `-cp` syntax: paths separated by `:` (`;` on Windows), where a
+	/// final `*` stands for every JAR in a directory.
+	pub fn parse(class_path: &str) -> Result<ClassPath, ArchiveError> {
+		let mut result = ClassPath::new();
+		for path in std::env::split_paths(class_path) {
+			if path.as_os_str().is_empty() {
+				continue;
+			}
+			if path.file_name().is_some_and(|name| name == "*") {
+				let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+				let mut jars: Vec<PathBuf> = fs::read_dir(if directory.as_os_str().is_empty() { Path::new(".") } else { &directory })?
+					.map(|item| item.map(|item| item.path()))
+					.collect::<Result<Vec<_>, _>>()?
+					.into_iter()
+					.filter(|path| archive::is_archive(path))
+					.collect();
+				jars.sort();
+				for jar in jars {
+					result.add(&jar)?;
+				}
+			} else {
+				result.add(&path)?;
+			}
+		}
+		Ok(result)
+	}
+
+	/// The release used to pick classes from multi-release JARs, `DEFAULT_RELEASE` by default.
+	pub fn release(mut self, release: u32) -> Self {
+		self.release = release;
+		self
+	}
+
+	/// Append a directory, archive, JMOD file or runtime image (`lib/modules`). An archive is
+	/// followed by the archives named in its manifest's `Class-Path` attribute. Paths already on
+	/// the class path are skipped, as are `Class-Path` entries that do not exist.
+	pub fn add(&mut self, path: &Path) -> Result<(), ArchiveError> {
+		if self.entries.iter().any(|entry| entry.path() == path) {
+			return Ok(());
+		}
+		if path.extension().is_some_and(|extension| extension == "jmod") {
+			// The ZIP content follows a 4-byte JMOD header, which the ZIP reader skips over.
+			let archive = Mutex::new(ZipArchive::new(File::open(path)?)?);
+			self.entries.push(ClassPathEntry::Archive(ClassPathArchive { path: path.to_path_buf(), prefix: JMOD_CLASSES, versions: Vec::new(), archive }));
+			return Ok(());
+		}
+		if path.is_file() && jimage::is_image(path) {
+			self.entries.push(ClassPathEntry::Image(JImage::open(path)?));
+			return Ok(());
+		}
+		if !archive::is_archive(path) {
+			self.entries.push(ClassPathEntry::Directory(path.to_path_buf()));
+			return Ok(());
+		}
+
+		let archive = ClassPathArchive { path: path.to_path_buf(), prefix: "", versions: Vec::new(), archive: Mutex::new(ZipArchive::new(File::open(path)?)?) };
+		let manifest = archive.read(MANIFEST)?.map(|bytes| manifest_attributes(&String::from_utf8_lossy(&bytes))).unwrap_or_default();
+		let versions = if manifest.get("Multi-Release").is_some_and(|value| value.eq_ignore_ascii_case("true")) {
+			let zip = archive.archive.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+			let mut versions: Vec<u32> = zip.file_names()
+				.filter_map(|name| name.strip_prefix(VERSIONS)?.split_once('/')?.0.parse().ok())
+				.filter(|version| *version >= 9)
+				.collect();
+			versions.sort_unstable_by(|a, b| b.cmp(a));
+			versions.dedup();
+			versions
+		} else {
+			Vec::new()
+		};
+		self.entries.push(ClassPathEntry::Archive(ClassPathArchive { versions, ..archive }));
+
+		let base = path.parent().unwrap_or(Path::new(""));
+		for relative in manifest.get("Class-Path").map(|value| value.split_whitespace().collect()).unwrap_or_else(Vec::new) {
+			let dependency = base.join(relative.split('/').collect::<PathBuf>());
+			if dependency.exists() {
+				self.add(&dependency)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Append the classes of a JDK installation: its runtime image, or its JMOD files if it has
+	/// none, as for a JDK being used to build another.
+	pub fn add_java_home(&mut self, java_home: &Path) -> Result<(), ArchiveError> {
+		let image = java_home.join("lib").join("modules");
+		if image.is_file() {
+			return self.add(&image);
+		}
+		let mut jmods: Vec<PathBuf> = fs::read_dir(java_home.join("jmods"))?
+			.map(|item| item.map(|item| item.path()))
+			.collect::<Result<Vec<_>, _>>()?;
+		jmods.retain(|path| path.extension().is_some_and(|extension| extension == "jmod"));
+		jmods.sort();
+		for jmod in jmods {
+			self.add(&jmod)?;
+		}
+		Ok(())
+	}
+
+	/// Append resources held in memory, named `path` though nothing is read from there. A path
+	/// already on the class path is skipped.
+	pub fn add_entries(&mut self, path: &Path, entries: Vec<Entry>) {
+		if self.entries.iter().any(|entry| entry.path() == path) {
+			return;
+		}
+		let resources = entries.into_iter().map(|entry| (entry.name, entry.bytes)).collect();
+		self.entries.push(ClassPathEntry::Memory { path: path.to_path_buf(), resources });
+	}
+
+	pub fn entries(&self) -> &[ClassPathEntry] {
+		&self.entries
+	}
+
+	/// Find a resource such as `META-INF/services/java.sql.Driver` in the first entry that has it.
+	pub fn find_resource(&self, name: &str) -> Result<Option<Entry>, ArchiveError> {
+		for entry in &self.entries {
+			if let Some(found) = entry.find(name, self.release)? {
+				return Ok(Some(found));
+			}
+		}
+		Ok(None)
+	}
+
+	/// Find the class file for an internal name such as `java/lang/String`.
+	pub fn find_class_bytes(&self, class_name: &str) -> Result<Option<Entry>, ArchiveError> {
+		self.find_resource(&format!("{}.class", class_name))
+	}
+
+	/// Find and parse the class with an internal name such as `java/lang/String`.
+	pub fn find_class(&self, class_name: &str) -> Result<Option<Class>, ArchiveError> {
+		self.find_class_bytes(class_name)?.map(|entry| entry.parse()).transpose()
+	}
+}
+
+/// The attributes of a manifest's main section (JAR File Specification, "Manifest specification"),
+/// joining continuation lines.
+fn manifest_attributes(manifest: &str) -> HashMap<String, String> {
+	let mut lines: Vec<String> = Vec::new();
+	for line in manifest.lines() {
+		if line.is_empty() {
+			break;
+		}
+		match (line.strip_prefix(' '), lines.last_mut()) {
+			(Some(continuation), Some(last)) => last.push_str(continuation),
+			_ => lines.push(line.to_string()),
+		}
+	}
+	lines.iter()
+		.filter_map(|line| line.split_once(':'))
+		.map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fs, path::{Path, PathBuf}};
+
+	use crate::class::{
+		archive::{write_jar, Entry},
+		class_path::{manifest_attributes, ClassPath, ClassPathEntry}};
+
+	fn class_bytes(path: &str) -> Vec<u8> {
+		fs::read(path).unwrap()
+	}
+
+	/// A directory holding `app.jar`, a multi-release JAR whose manifest puts `lib.jar` on the
+	/// class path.
+	fn jars(name: &str, multi_release: bool) -> PathBuf {
+		let directory = std::env::temp_dir().join(format!("steele-class-path-{}-{}", name, std::process::id()));
+		fs::create_dir_all(directory.join("lib")).unwrap();
+		let manifest = format!("Manifest-Version: 1.0\r\nMulti-Release: {}\r\nClass-Path: lib/lib.jar\r\n  missing.jar\r\n\r\n", multi_release);
+		write_jar(&directory.join("app.jar"), &[
+			Entry { name: "META-INF/MANIFEST.MF".to_string(), bytes: manifest.into_bytes() },
+			Entry { name: "Branches.class".to_string(), bytes: class_bytes("tests/resources/Branches.class") },
+			Entry { name: "META-INF/versions/11/Branches.class".to_string(), bytes: class_bytes("tests/resources/Sample.class") },
+			Entry { name: "META-INF/versions/21/Branches.class".to_string(), bytes: class_bytes("tests/resources/calls/App.class") },
+		]).unwrap();
+		write_jar(&directory.join("lib/lib.jar"), &[
+			Entry { name: "Greeter.class".to_string(), bytes: class_bytes("tests/resources/calls/Greeter.class") },
+			Entry { name: "Branches.class".to_string(), bytes: vec![] },
+		]).unwrap();
+		directory
+	}
+
+	#[test]
+	fn test_directory_and_manifest_class_path() {
+		let directory = jars("manifest", false);
+		let class_path = ClassPath::parse(&format!("tests/resources/hierarchy:{}", directory.join("app.jar").display())).unwrap();
+		let paths: Vec<&Path> = class_path.entries().iter().map(|entry| entry.path()).collect();
+		assert_eq!(paths, vec![Path::new("tests/resources/hierarchy"), &directory.join("app.jar"), &directory.join("lib/lib.jar")]);
+
+		assert_eq!(class_path.find_class("Circle").unwrap().unwrap().name(), "Circle");
+		assert_eq!(class_path.find_class("Greeter").unwrap().unwrap().name(), "Greeter");
+		// Earlier entries shadow later ones, and versioned classes are ignored without Multi-Release.
+		assert_eq!(class_path.find_class_bytes("Branches").unwrap().unwrap().bytes, class_bytes("tests/resources/Branches.class"));
+		assert!(class_path.find_class("java/lang/String").unwrap().is_none());
+		fs::remove_dir_all(directory).unwrap();
+	}
+
+	#[test]
+	fn test_directory_confinement() {
+		let class_path = ClassPath::parse("tests/resources/hierarchy").unwrap();
+		assert!(class_path.find_resource("Circle.class").unwrap().is_some());
+		// Each names Circle.class, or a file beside the directory, by leaving it or by a path that
+		// only the file system resolves.
+		for name in ["../hierarchy/Circle.class", "./Circle.class", "/Circle.class", "sub//../Circle.class", "../Branches.class"] {
+			assert!(class_path.find_resource(name).unwrap().is_none(), "{} was found", name);
+		}
+		let absolute = fs::canonicalize("tests/resources/Branches.class").unwrap();
+		assert!(class_path.find_resource(&absolute.display().to_string()).unwrap().is_none());
+	}
+
+	#[test]
+	fn test_multi_release() {
+		let directory = jars("versions", true);
+		let jar = directory.join("app.jar");
+		let find = |release: u32| {
+			let mut class_path = ClassPath::new().release(release);
+			class_path.add(&jar).unwrap();
+			class_path.find_class_bytes("Branches").unwrap().unwrap().bytes
+		};
+		assert_eq!(find(8), class_bytes("tests/resources/Branches.class"));
+		assert_eq!(find(17), class_bytes("tests/resources/Sample.class"));
+		assert_eq!(find(21), class_bytes("tests/resources/calls/App.class"));
+		fs::remove_dir_all(directory).unwrap();
+	}
+
+	#[test]
+	fn test_wildcard() {
+		let directory = jars("wildcard", false);
+		let class_path = ClassPath::parse(&format!("{}/*", directory.join("lib").display())).unwrap();
+		assert_eq!(class_path.entries().len(), 1);
+		assert!(class_path.find_class_bytes("Greeter").unwrap().is_some());
+		fs::remove_dir_all(directory).unwrap();
+	}
+
+	#[test]
+	fn test_jmod() {
+		let class_path = ClassPath::parse("tests/resources/jmod/demo.jmod").unwrap();
+		assert_eq!(class_path.find_class("demo/Hello").unwrap().unwrap().name(), "demo/Hello");
+		assert!(class_path.find_class_bytes("module-info").unwrap().is_some());
+		assert!(class_path.find_class_bytes("classes/demo/Hello").unwrap().is_none());
+	}
+
+	/// The JDK named by JAVA_HOME, or the one providing `java` on the PATH.
+	fn java_home() -> Option<PathBuf> {
+		if let Some(java_home) = std::env::var_os("JAVA_HOME") {
+			return Some(PathBuf::from(java_home));
+		}
+		let java = std::env::split_paths(&std::env::var_os("PATH")?).map(|directory| directory.join("java")).find(|java| java.is_file())?;
+		Some(fs::canonicalize(java).ok()?.parent()?.parent()?.to_path_buf())
+	}
+
+	/// Loads from the installed JDK's runtime image, and is skipped where there is none.
+	#[test]
+	fn test_java_home() {
+		let Some(java_home) = java_home().filter(|java_home| java_home.join("lib/modules").is_file()) else {
+			return;
+		};
+		let mut class_path = ClassPath::new();
+		class_path.add_java_home(&java_home).unwrap();
+		assert!(matches!(class_path.entries(), [ClassPathEntry::Image(_)]));
+		let string = class_path.find_class("java/lang/String").unwrap().unwrap();
+		assert_eq!(string.super_class_name().as_deref(), Some("java/lang/Object"));
+		assert!(class_path.find_class("java/sql/Connection").unwrap().is_some());
+		assert!(class_path.find_class("java/lang/Missing").unwrap().is_none());
+	}
+
+	#[test]
+	fn test_in_memory_entries() {
+		let mut class_path = ClassPath::new();
+		class_path.add_entries(Path::new("generated"), vec![Entry { name: "Greeter.class".to_string(), bytes: class_bytes("tests/resources/calls/Greeter.class") }]);
+		class_path.add_entries(Path::new("generated"), vec![]);
+		class_path.add(Path::new("tests/resources/calls")).unwrap();
+		assert_eq!(class_path.entries().len(), 2);
+		assert_eq!(class_path.find_class("Greeter").unwrap().unwrap().name(), "Greeter");
+		assert_eq!(class_path.find_class("App").unwrap().unwrap().name(), "App");
+		assert!(class_path.find_class("Missing").unwrap().is_none());
+	}
+
+	#[test]
+	fn test_manifest_attributes() {
+		let attributes = manifest_attributes("Manifest-Version: 1.0\nClass-Path: a.jar\n  b.jar\n\nName: x\nSealed: true\n");
+		assert_eq!(attributes["Class-Path"], "a.jar b.jar");
+		assert!(!attributes.contains_key("Sealed"));
+	}
+}
@@ -4,20 +4,45 @@ use std::{
 	fmt::{
 		self, Display, Formatter}};
 
-use binrw::binrw;
+use binrw::{binrw, BinRead};
 use strum_macros;
 
 use crate::class::modified_utf8::ModifiedUtf8String;
 
-#[binrw]
-#[brw(big)]
 #[derive(Default)]
 pub struct RawConstantPool {
 	pub constant_pool_count: u16,
-	#[br(count = constant_pool_count - 2)]
 	pub constants: Vec<ConstantPoolItem>,
 }
 
+impl BinRead for RawConstantPool {
+	type Args<'a> = ();
+
+	/// Reads entries until constant_pool_count - 1 slots are filled, bearing in mind that
+	/// doubles and longs take up two slots each (JVMS17 4.4.5).
+	fn read_options<R: std::io::Read + std::io::Seek>(
+		reader: &mut R,
+		endian: binrw::Endian,
+		_args: (),
+	) -> binrw::BinResult<Self> {
+		let constant_pool_count = u16::read_options(reader, endian, ())?;
+		let mut constants: Vec<ConstantPoolItem> = Vec::new();
+		let mut index: u16 = 1;
+		while index < constant_pool_count {
+			let item = ConstantPoolItem::read_options(reader, endian, ())?;
+			index += match item {
+				ConstantPoolItem::Double(_) | ConstantPoolItem::Long(_) => 2,
+				_ => 1,
+			};
+			constants.push(item);
+		}
+		Ok(RawConstantPool {
+			constant_pool_count,
+			constants,
+		})
+	}
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct ConstantPool {
 	pub length: u16,
@@ -34,6 +59,15 @@ impl From<RawConstantPool> for ConstantPool {
 	}
 }
 
+impl From<&crate::class::class::Class> for ConstantPool {
+	fn from(class: &crate::class::class::Class) -> Self {
+		Self {
+			length: class.constant_pool_count(),
+			constants: class.constant_pool.clone(),
+		}
+	}
+}
+
 #[derive(Debug)]
 pub struct TypeError {
 	pub wanted_type: std::string::String,
@@ -100,6 +134,10 @@ impl ConstantPool {
 	make_accessor!(get_method_ref, MethodRef, "MethodRef");
 	make_accessor!(get_interface_method_ref, InterfaceMethodRef, "InterfaceMethodRef");
 	make_accessor!(get_name_and_type, NameAndType, "NameAndType");
+	make_accessor!(get_method_handle, MethodHandle, "MethodHandle");
+	make_accessor!(get_method_type, MethodType, "MethodType");
+	make_accessor!(get_dynamic, Dynamic, "Dynamic");
+	make_accessor!(get_invoke_dynamic, InvokeDynamic, "InvokeDynamic");
 
 	/// Converts a raw constant pool to canonical form.
 	/// 
@@ -127,49 +165,119 @@ impl ConstantPool {
 /// A control enum used in polymorphic parsing of constant pool entries.
 #[binrw]
 #[derive(PartialEq, Debug, Clone, strum_macros::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ConstantPoolItem {
 	/// Tag for CONSTANT_Utf8 (JVMS17 4.4-B)
-	#[br(magic(1u8))]
+	#[brw(magic(1u8))]
 	Utf8(Utf8),
 	/// Tag for CONSTANT_Integer (JVMS17 4.4-B)
-	#[br(magic(3u8))]
+	#[brw(magic(3u8))]
 	Integer(Integer),
 	/// Tag for CONSTANT_Float (JVMS17 4.4-B)
-	#[br(magic(4u8))]
+	#[brw(magic(4u8))]
 	Float(Float),
 	/// Tag for CONSTANT_Long (JVMS17 4.4-B)
-	#[br(magic(5u8))]
+	#[brw(magic(5u8))]
 	Long(Long),
 	/// Tag for CONSTANT_Double (JVMS17 4.4-B)
-	#[br(magic(6u8))]
+	#[brw(magic(6u8))]
 	Double(Double),
 	/// Tag for CONSTANT_Class (JVMS17 4.4-B)
-	#[br(magic(7u8))]
+	#[brw(magic(7u8))]
 	Class(Class),
 	/// Tag for CONSTANT_String (JVMS17 4.4-B)
-	#[br(magic(8u8))]
+	#[brw(magic(8u8))]
 	String(String),
 	/// Tag for CONSTANT_Fieldref (JVMS17 4.4-B)
-	#[br(magic(9u8))]
+	#[brw(magic(9u8))]
 	FieldRef(FieldRef),
 	/// Tag for CONSTANT_Methodref (JVMS17 4.4-B)
-	#[br(magic(10u8))]
+	#[brw(magic(10u8))]
 	MethodRef(MethodRef),
 	/// Tag for CONSTANT_InterfaceMethod (JVMS17 4.4-B)
-	#[br(magic(11u8))]
+	#[brw(magic(11u8))]
 	InterfaceMethodRef(InterfaceMethodRef),
 	/// Tag for CONSTANT_NameAndType (JVMS17 4.4-B)
-	#[br(magic(12u8))]
+	#[brw(magic(12u8))]
 	NameAndType(NameAndType),
+	/// Tag for CONSTANT_MethodHandle (JVMS17 4.4-B)
+	#[brw(magic(15u8))]
+	MethodHandle(MethodHandle),
+	/// Tag for CONSTANT_MethodType (JVMS17 4.4-B)
+	#[brw(magic(16u8))]
+	MethodType(MethodType),
+	/// Tag for CONSTANT_Dynamic (JVMS17 4.4-B)
+	#[brw(magic(17u8))]
+	Dynamic(Dynamic),
+	/// Tag for CONSTANT_InvokeDynamic (JVMS17 4.4-B)
+	#[brw(magic(18u8))]
+	InvokeDynamic(InvokeDynamic),
+	/// Tag for CONSTANT_Module (JVMS17 4.4-B)
+	#[brw(magic(19u8))]
+	Module(Module),
+	/// Tag for CONSTANT_Package (JVMS17 4.4-B)
+	#[brw(magic(20u8))]
+	Package(Package),
+}
+
+impl ConstantPoolItem {
+
+	/// The tag byte this entry is written with.
+	pub fn tag(&self) -> ConstantTag {
+		match self {
+			ConstantPoolItem::Utf8(_) => ConstantTag::Utf8,
+			ConstantPoolItem::Integer(_) => ConstantTag::Integer,
+			ConstantPoolItem::Float(_) => ConstantTag::Float,
+			ConstantPoolItem::Long(_) => ConstantTag::Long,
+			ConstantPoolItem::Double(_) => ConstantTag::Double,
+			ConstantPoolItem::Class(_) => ConstantTag::Class,
+			ConstantPoolItem::String(_) => ConstantTag::String,
+			ConstantPoolItem::FieldRef(_) => ConstantTag::FieldRef,
+			ConstantPoolItem::MethodRef(_) => ConstantTag::MethodRef,
+			ConstantPoolItem::InterfaceMethodRef(_) => ConstantTag::InterfaceMethodRef,
+			ConstantPoolItem::NameAndType(_) => ConstantTag::NameAndType,
+			ConstantPoolItem::MethodHandle(_) => ConstantTag::MethodHandle,
+			ConstantPoolItem::MethodType(_) => ConstantTag::MethodType,
+			ConstantPoolItem::Dynamic(_) => ConstantTag::Dynamic,
+			ConstantPoolItem::InvokeDynamic(_) => ConstantTag::InvokeDynamic,
+			ConstantPoolItem::Module(_) => ConstantTag::Module,
+			ConstantPoolItem::Package(_) => ConstantTag::Package,
+		}
+	}
+}
+
+/// The tag byte identifying the kind of a constant pool entry (JVMS17 Table 4.4-B).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, strum_macros::Display)]
+#[repr(u8)]
+pub enum ConstantTag {
+	Utf8 = 1,
+	Integer = 3,
+	Float = 4,
+	Long = 5,
+	Double = 6,
+	Class = 7,
+	String = 8,
+	FieldRef = 9,
+	MethodRef = 10,
+	InterfaceMethodRef = 11,
+	NameAndType = 12,
+	MethodHandle = 15,
+	MethodType = 16,
+	Dynamic = 17,
+	InvokeDynamic = 18,
+	Module = 19,
+	Package = 20,
 }
 
 /// An implementation of CONSTANT_Utf8 (JVMS17 4.4-B)
 #[binrw]
 #[brw(big)]
 #[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Utf8 {
 	pub length: u16,
 	#[br(count = length)]
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::utf8"))]
 	pub bytes: Vec<u8>,
 }
 
@@ -183,6 +291,7 @@ impl fmt::Display for Utf8 {
 #[binrw]
 #[brw(big)]
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Integer {
 	pub value: i32,
 }
@@ -191,6 +300,7 @@ pub struct Integer {
 #[binrw]
 #[brw(big)]
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Float {
 	pub value: f32,
 }
@@ -199,6 +309,7 @@ pub struct Float {
 #[binrw]
 #[brw(big)]
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Long {
 	pub value: i64,
 }
@@ -207,6 +318,7 @@ pub struct Long {
 #[binrw]
 #[brw(big)]
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Double {
 	pub value: f64,
 }
@@ -215,7 +327,9 @@ pub struct Double {
 #[binrw]
 #[brw(big)]
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Class {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub index: u16,
 }
 
@@ -223,7 +337,9 @@ pub struct Class {
 #[binrw]
 #[brw(big)]
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct String {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub index: u16,
 }
 
@@ -231,8 +347,11 @@ pub struct String {
 #[binrw]
 #[brw(big)]
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct FieldRef {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub class_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub name_and_type_index: u16,
 }
 
@@ -240,18 +359,23 @@ pub struct FieldRef {
 #[binrw]
 #[brw(big)]
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct MethodRef {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub class_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub name_and_type_index: u16,
 }
 
 /// An implementation of CONSTANT_InterfaceMethodref (JVMS 4.4-B)
 #[binrw]
 #[brw(big)]
-#[bw(magic = 11u8)]
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct InterfaceMethodRef {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub class_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub name_and_type_index: u16,
 }
 
@@ -259,11 +383,78 @@ pub struct InterfaceMethodRef {
 #[binrw]
 #[brw(big)]
 #[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct NameAndType {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub name_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub type_index: u16,
 }
 
+/// An implementation of CONSTANT_MethodHandle (JVMS 4.4-B)
+#[binrw]
+#[brw(big)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MethodHandle {
+	/// The kind of handle, from REF_getField (1) to REF_invokeInterface (9) (JVMS17 5.4.3.5).
+	pub reference_kind: u8,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub reference_index: u16,
+}
+
+/// An implementation of CONSTANT_MethodType (JVMS 4.4-B)
+#[binrw]
+#[brw(big)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MethodType {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub descriptor_index: u16,
+}
+
+/// An implementation of CONSTANT_Dynamic (JVMS 4.4-B)
+#[binrw]
+#[brw(big)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Dynamic {
+	pub bootstrap_method_attr_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub name_and_type_index: u16,
+}
+
+/// An implementation of CONSTANT_InvokeDynamic (JVMS 4.4-B)
+#[binrw]
+#[brw(big)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct InvokeDynamic {
+	pub bootstrap_method_attr_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub name_and_type_index: u16,
+}
+
+/// An implementation of CONSTANT_Module (JVMS 4.4-B)
+#[binrw]
+#[brw(big)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Module {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub name_index: u16,
+}
+
+/// An implementation of CONSTANT_Package (JVMS 4.4-B)
+#[binrw]
+#[brw(big)]
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Package {
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub name_index: u16,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct ConstantPoolRequiredArgs {
 	pub constant_pool: ConstantPool,
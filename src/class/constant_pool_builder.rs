@@ -0,0 +1,558 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use binrw::{BinRead, BinWrite};
+
+use crate::{
+	class::{
+		attribute::{Annotation, Attribute, AttributeInfo, Code, ElementValue, UnrecognisedAttribute},
+		class::Class,
+		constant_pool::{self, ConstantPool, ConstantPoolItem},
+		modified_utf8::ModifiedUtf8String,
+		verification::VerificationTypeInfo},
+	error::EncodeError,
+	isa::{instruction::{self, Instruction}, opcode::Opcode},
+};
+
+/// Builds a constant pool one entry at a time, handing back the index of an equal entry instead of
+/// adding a duplicate.
+///
+/// Entries are compared by their encoded form, so `0.0` and `-0.0` (or two NaNs with different bits)
+/// stay distinct, as they must for `ldc` to load the right value.
+#[derive(Clone, Debug, Default)]
+pub struct ConstantPoolBuilder {
+	constants: BTreeMap<u16, ConstantPoolItem>,
+	indices: HashMap<Vec<u8>, u16>,
+}
+
+impl From<BTreeMap<u16, ConstantPoolItem>> for ConstantPoolBuilder {
+	fn from(constants: BTreeMap<u16, ConstantPoolItem>) -> Self {
+		let mut indices = HashMap::new();
+		for (index, item) in &constants {
+			// keep the first of any duplicates already in the pool
+			indices.entry(Self::key(item)).or_insert(*index);
+		}
+		ConstantPoolBuilder { constants, indices }
+	}
+}
+
+impl From<ConstantPool> for ConstantPoolBuilder {
+	fn from(constant_pool: ConstantPool) -> Self {
+		Self::from(constant_pool.constants)
+	}
+}
+
+impl ConstantPoolBuilder {
+
+	pub fn new() -> ConstantPoolBuilder {
+		ConstantPoolBuilder::default()
+	}
+
+	fn key(item: &ConstantPoolItem) -> Vec<u8> {
+		let mut writer = binrw::io::Cursor::new(Vec::new());
+		item.write_be(&mut writer).expect("constant pool entries always encode");
+		writer.into_inner()
+	}
+
+	fn slots(item: &ConstantPoolItem) -> u16 {
+		match item {
+			ConstantPoolItem::Long(_) | ConstantPoolItem::Double(_) => 2,
+			_ => 1,
+		}
+	}
+
+	/// The constant_pool_count of the pool built so far: one more than the last index used,
+	/// counting the extra slot taken by a trailing long or double (JVMS17 4.4.5).
+	pub fn constant_pool_count(&self) -> u16 {
+		match self.constants.last_key_value() {
+			Some((index, item)) => index + Self::slots(item),
+			None => 1,
+		}
+	}
+
+	pub fn get(&self, index: u16) -> Option<&ConstantPoolItem> {
+		self.constants.get(&index)
+	}
+
+	pub fn constants(&self) -> &BTreeMap<u16, ConstantPoolItem> {
+		&self.constants
+	}
+
+	/// The index of an entry equal to `item`, appending it if there is none. Fails once the pool
+	/// would need more than the 65535 slots a u2 constant_pool_count can describe.
+	pub fn add(&mut self, item: ConstantPoolItem) -> Result<u16, EncodeError> {
+		let key = Self::key(&item);
+		if let Some(index) = self.indices.get(&key) {
+			return Ok(*index);
+		}
+		let index = self.constant_pool_count();
+		if u32::from(index) + u32::from(Self::slots(&item)) > u32::from(u16::MAX) {
+			return Err(EncodeError { msg: format!("constant pool is full at {} entries", self.constants.len()) });
+		}
+		self.constants.insert(index, item);
+		self.indices.insert(key, index);
+		Ok(index)
+	}
+
+	pub fn add_utf8(&mut self, value: &str) -> Result<u16, EncodeError> {
+		let bytes = ModifiedUtf8String::encode(value);
+		let length = u16::try_from(bytes.len())
+			.map_err(|_| EncodeError { msg: format!("string of {} bytes is too long for a Utf8 constant", bytes.len()) })?;
+		self.add(ConstantPoolItem::Utf8(constant_pool::Utf8 { length, bytes }))
+	}
+
+	pub fn add_integer(&mut self, value: i32) -> Result<u16, EncodeError> {
+		self.add(ConstantPoolItem::Integer(constant_pool::Integer { value }))
+	}
+
+	pub fn add_float(&mut self, value: f32) -> Result<u16, EncodeError> {
+		self.add(ConstantPoolItem::Float(constant_pool::Float { value }))
+	}
+
+	pub fn add_long(&mut self, value: i64) -> Result<u16, EncodeError> {
+		self.add(ConstantPoolItem::Long(constant_pool::Long { value }))
+	}
+
+	pub fn add_double(&mut self, value: f64) -> Result<u16, EncodeError> {
+		self.add(ConstantPoolItem::Double(constant_pool::Double { value }))
+	}
+
+	/// A Class constant for an internal name such as `java/lang/String` or an array descriptor.
+	pub fn add_class(&mut self, name: &str) -> Result<u16, EncodeError> {
+		let index = self.add_utf8(name)?;
+		self.add(ConstantPoolItem::Class(constant_pool::Class { index }))
+	}
+
+	pub fn add_string(&mut self, value: &str) -> Result<u16, EncodeError> {
+		let index = self.add_utf8(value)?;
+		self.add(ConstantPoolItem::String(constant_pool::String { index }))
+	}
+
+	pub fn add_name_and_type(&mut self, name: &str, descriptor: &str) -> Result<u16, EncodeError> {
+		let name_index = self.add_utf8(name)?;
+		let type_index = self.add_utf8(descriptor)?;
+		self.add(ConstantPoolItem::NameAndType(constant_pool::NameAndType { name_index, type_index }))
+	}
+
+	pub fn add_field_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, EncodeError> {
+		let class_index = self.add_class(class)?;
+		let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+		self.add(ConstantPoolItem::FieldRef(constant_pool::FieldRef { class_index, name_and_type_index }))
+	}
+
+	pub fn add_method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, EncodeError> {
+		let class_index = self.add_class(class)?;
+		let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+		self.add(ConstantPoolItem::MethodRef(constant_pool::MethodRef { class_index, name_and_type_index }))
+	}
+
+	pub fn add_interface_method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, EncodeError> {
+		let class_index = self.add_class(class)?;
+		let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+		self.add(ConstantPoolItem::InterfaceMethodRef(constant_pool::InterfaceMethodRef { class_index, name_and_type_index }))
+	}
+
+	/// A MethodHandle constant of `reference_kind` (JVMS17 5.4.3.5) over an existing field or method reference.
+	pub fn add_method_handle(&mut self, reference_kind: u8, reference_index: u16) -> Result<u16, EncodeError> {
+		self.add(ConstantPoolItem::MethodHandle(constant_pool::MethodHandle { reference_kind, reference_index }))
+	}
+
+	pub fn add_method_type(&mut self, descriptor: &str) -> Result<u16, EncodeError> {
+		let descriptor_index = self.add_utf8(descriptor)?;
+		self.add(ConstantPoolItem::MethodType(constant_pool::MethodType { descriptor_index }))
+	}
+
+	pub fn add_dynamic(&mut self, bootstrap_method_attr_index: u16, name: &str, descriptor: &str) -> Result<u16, EncodeError> {
+		let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+		self.add(ConstantPoolItem::Dynamic(constant_pool::Dynamic { bootstrap_method_attr_index, name_and_type_index }))
+	}
+
+	pub fn add_invoke_dynamic(&mut self, bootstrap_method_attr_index: u16, name: &str, descriptor: &str) -> Result<u16, EncodeError> {
+		let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+		self.add(ConstantPoolItem::InvokeDynamic(constant_pool::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index }))
+	}
+
+	pub fn add_module(&mut self, name: &str) -> Result<u16, EncodeError> {
+		let name_index = self.add_utf8(name)?;
+		self.add(ConstantPoolItem::Module(constant_pool::Module { name_index }))
+	}
+
+	pub fn add_package(&mut self, name: &str) -> Result<u16, EncodeError> {
+		let name_index = self.add_utf8(name)?;
+		self.add(ConstantPoolItem::Package(constant_pool::Package { name_index }))
+	}
+
+	pub fn into_constants(self) -> BTreeMap<u16, ConstantPoolItem> {
+		self.constants
+	}
+
+	pub fn build(self) -> ConstantPool {
+		ConstantPool { length: self.constant_pool_count(), constants: self.constants }
+	}
+
+	/// Drop every constant the class no longer refers to and renumber the rest, keeping their
+	/// order so that no index grows and `ldc` operands still fit in a byte.
+	///
+	/// Fails if the class carries an attribute this crate does not parse and whose layout is not
+	/// known to be free of constant pool indices, since those references could not be updated.
+	pub fn compact(class: &mut Class) -> Result<(), EncodeError> {
+		// this_class, super_class and interfaces are kept as Class constants by name, so the
+		// constants naming them are roots alongside everything the class body refers to.
+		let mut live: BTreeSet<u16> = std::iter::once(&class.this_class)
+			.chain(class.super_class.iter())
+			.chain(class.interfaces.iter())
+			.filter_map(|constant| class.get_class_index(constant))
+			.collect();
+		visit_class(class, &mut |index| {
+			live.insert(*index);
+			Ok(())
+		})?;
+
+		let mut pending: Vec<u16> = live.iter().copied().collect();
+		while let Some(index) = pending.pop() {
+			let mut item = class.constant_pool.get(&index)
+				.ok_or_else(|| EncodeError { msg: format!("{} refers to missing constant {}", class.name(), index) })?
+				.clone();
+			visit_constant(&mut item, &mut |inner| {
+				if live.insert(*inner) {
+					pending.push(*inner);
+				}
+				Ok(())
+			})?;
+		}
+
+		let mut renumbered: HashMap<u16, u16> = HashMap::new();
+		let mut next: u16 = 1;
+		for index in &live {
+			let item = class.constant_pool.get(index)
+				.ok_or_else(|| EncodeError { msg: format!("{} refers to missing constant {}", class.name(), index) })?;
+			renumbered.insert(*index, next);
+			next += Self::slots(item);
+		}
+		let mut renumber = |index: &mut u16| -> Result<(), EncodeError> {
+			*index = *renumbered.get(index).ok_or_else(|| EncodeError { msg: format!("no constant at {}", index) })?;
+			Ok(())
+		};
+
+		let mut constants = BTreeMap::new();
+		for index in &live {
+			let mut item = class.constant_pool[index].clone();
+			visit_constant(&mut item, &mut renumber)?;
+			let mut new_index = *index;
+			renumber(&mut new_index)?;
+			constants.insert(new_index, item);
+		}
+		visit_class(class, &mut renumber)?;
+		class.constant_pool = constants;
+		Ok(())
+	}
+}
+
+/// Call `f` on each constant pool index held by a constant pool entry. Index zero is never passed.
+fn visit_constant<F: FnMut(&mut u16) -> Result<(), EncodeError>>(item: &mut ConstantPoolItem, f: &mut F) -> Result<(), EncodeError> {
+	let indices: Vec<&mut u16> = match item {
+		ConstantPoolItem::Utf8(_) | ConstantPoolItem::Integer(_) | ConstantPoolItem::Float(_) |
+		ConstantPoolItem::Long(_) | ConstantPoolItem::Double(_) => vec![],
+		ConstantPoolItem::Class(constant) => vec![&mut constant.index],
+		ConstantPoolItem::String(constant) => vec![&mut constant.index],
+		ConstantPoolItem::FieldRef(reference) => vec![&mut reference.class_index, &mut reference.name_and_type_index],
+		ConstantPoolItem::MethodRef(reference) => vec![&mut reference.class_index, &mut reference.name_and_type_index],
+		ConstantPoolItem::InterfaceMethodRef(reference) => vec![&mut reference.class_index, &mut reference.name_and_type_index],
+		ConstantPoolItem::NameAndType(name_and_type) => vec![&mut name_and_type.name_index, &mut name_and_type.type_index],
+		ConstantPoolItem::MethodHandle(handle) => vec![&mut handle.reference_index],
+		ConstantPoolItem::MethodType(method_type) => vec![&mut method_type.descriptor_index],
+		ConstantPoolItem::Dynamic(constant) => vec![&mut constant.name_and_type_index],
+		ConstantPoolItem::InvokeDynamic(call_site) => vec![&mut call_site.name_and_type_index],
+		ConstantPoolItem::Module(module) => vec![&mut module.name_index],
+		ConstantPoolItem::Package(package) => vec![&mut package.name_index],
+	};
+	visit_all(indices, f)
+}
+
+fn visit_all<'a, F: FnMut(&mut u16) -> Result<(), EncodeError>>(indices: impl IntoIterator<Item = &'a mut u16>, f: &mut F) -> Result<(), EncodeError> {
+	// zero stands for "none" wherever the class file format allows it
+	indices.into_iter().filter(|index| **index != 0).try_for_each(f)
+}
+
+/// Call `f` on each constant pool index held outside the pool: by the class's own parameters,
+/// its fields and methods, their attributes and their code.
+fn visit_class<F: FnMut(&mut u16) -> Result<(), EncodeError>>(class: &mut Class, f: &mut F) -> Result<(), EncodeError> {
+	// these hold the Utf8 index of the name rather than the Class constant itself
+	let names = std::iter::once(&mut class.this_class)
+		.chain(class.super_class.iter_mut())
+		.chain(class.interfaces.iter_mut())
+		.map(|constant| &mut constant.index);
+	visit_all(names, f)?;
+	for field in &mut class.fields.fields {
+		visit_all([&mut field.name_index, &mut field.descriptor_index], f)?;
+		visit_attributes(&mut field.attributes, f)?;
+	}
+	for method in &mut class.methods.methods {
+		visit_all([&mut method.name_index, &mut method.descriptor_index], f)?;
+		visit_attributes(&mut method.attributes, f)?;
+	}
+	visit_attributes(&mut class.attributes.attributes, f)
+}
+
+fn visit_attributes<F: FnMut(&mut u16) -> Result<(), EncodeError>>(attributes: &mut [Attribute], f: &mut F) -> Result<(), EncodeError> {
+	for attribute in attributes {
+		visit_all([&mut attribute.name_index], f)?;
+		match &mut attribute.attribute_info {
+			AttributeInfo::AnnotationDefault(value) => visit_element_value(value, f)?,
+			AttributeInfo::BootstrapMethods(bootstrap_methods) => {
+				for entry in &mut bootstrap_methods.bootstrap_methods {
+					visit_all(std::iter::once(&mut entry.bootstrap_method_ref).chain(entry.bootstrap_arguments.iter_mut()), f)?;
+				}
+			}
+			AttributeInfo::Code(code) => visit_code(code, f)?,
+			AttributeInfo::ConstantValue(constant_value) => visit_all([&mut constant_value.constant_value_index], f)?,
+			AttributeInfo::EnclosingMethod(enclosing_method) => visit_all([&mut enclosing_method.class_index, &mut enclosing_method.method_index], f)?,
+			AttributeInfo::InnerClasses(inner_classes) => {
+				for class in &mut inner_classes.classes {
+					visit_all([&mut class.inner_class_info_index, &mut class.outer_class_info_index, &mut class.inner_name_index], f)?;
+				}
+			}
+			AttributeInfo::LineNumberTable(_) => (),
+			AttributeInfo::LocalVariableTable(table) => {
+				for variable in &mut table.local_variables {
+					visit_all([&mut variable.name_index, &mut variable.descriptor_index], f)?;
+				}
+			}
+			AttributeInfo::LocalVariableTypeTable(table) => {
+				for variable in &mut table.local_variable_types {
+					visit_all([&mut variable.name_index, &mut variable.signature_index], f)?;
+				}
+			}
+			AttributeInfo::NestHost(nest_host) => visit_all([&mut nest_host.host_class_index], f)?,
+			AttributeInfo::NestMembers(nest_members) => visit_all(&mut nest_members.classes, f)?,
+			AttributeInfo::PermittedSubclasses(permitted) => visit_all(&mut permitted.classes, f)?,
+			AttributeInfo::RuntimeInvisibleAnnotations(annotations) | AttributeInfo::RuntimeVisibleAnnotations(annotations) => {
+				for annotation in &mut annotations.annotations {
+					visit_annotation(annotation, f)?;
+				}
+			}
+			AttributeInfo::RuntimeInvisibleParameterAnnotations(parameters) | AttributeInfo::RuntimeVisibleParameterAnnotations(parameters) => {
+				for annotation in parameters.parameter_annotations.iter_mut().flat_map(|annotations| annotations.annotations.iter_mut()) {
+					visit_annotation(annotation, f)?;
+				}
+			}
+			AttributeInfo::Signature(signature) => visit_all([&mut signature.signature_index], f)?,
+			AttributeInfo::SourceFile(source_file) => visit_all([&mut source_file.source_file_index], f)?,
+			AttributeInfo::StackMapTable(table) => {
+				for frame in &mut table.entries {
+					for verification_type in frame.verification_types_mut() {
+						if let VerificationTypeInfo::ObjectVariableInfo(object) = verification_type {
+							visit_all([&mut object.constant_pool_index], f)?;
+						}
+					}
+				}
+			}
+			AttributeInfo::UnrecognisedAttribute(unrecognised) => visit_unrecognised(unrecognised, f)?,
+		}
+	}
+	Ok(())
+}
+
+fn visit_annotation<F: FnMut(&mut u16) -> Result<(), EncodeError>>(annotation: &mut Annotation, f: &mut F) -> Result<(), EncodeError> {
+	visit_all([&mut annotation.type_index], f)?;
+	for pair in &mut annotation.element_value_pairs {
+		visit_all([&mut pair.element_name_index], f)?;
+		visit_element_value(&mut pair.value, f)?;
+	}
+	Ok(())
+}
+
+fn visit_element_value<F: FnMut(&mut u16) -> Result<(), EncodeError>>(value: &mut ElementValue, f: &mut F) -> Result<(), EncodeError> {
+	match value {
+		ElementValue::Byte { const_value_index } | ElementValue::Char { const_value_index } |
+		ElementValue::Double { const_value_index } | ElementValue::Float { const_value_index } |
+		ElementValue::Int { const_value_index } | ElementValue::Long { const_value_index } |
+		ElementValue::Short { const_value_index } | ElementValue::Boolean { const_value_index } |
+		ElementValue::String { const_value_index } => visit_all([const_value_index], f),
+		ElementValue::Enum { type_name_index, const_name_index } => visit_all([type_name_index, const_name_index], f),
+		ElementValue::Class { class_info_index } => visit_all([class_info_index], f),
+		ElementValue::Annotation(annotation) => visit_annotation(annotation, f),
+		ElementValue::Array { values, .. } => values.iter_mut().try_for_each(|value| visit_element_value(value, f)),
+	}
+}
+
+/// Visit the operands of instructions that name constants, rewriting them in place. Indices are
+/// patched without re-encoding, so an `ldc` operand must still fit in one byte afterwards.
+fn visit_code<F: FnMut(&mut u16) -> Result<(), EncodeError>>(code: &mut Code, f: &mut F) -> Result<(), EncodeError> {
+	let instructions = instruction::decode(&code.code).map_err(|e| EncodeError { msg: e.msg })?;
+	for (pc, instruction) in instructions {
+		let Some(mut index) = instruction.constant_pool_index() else {
+			continue;
+		};
+		visit_all([&mut index], f)?;
+		let operand = pc as usize + 1;
+		if let Instruction::Plain { opcode: Opcode::Ldc, .. } = instruction {
+			code.code[operand] = u8::try_from(index)
+				.map_err(|_| EncodeError { msg: format!("ldc at {} cannot refer to constant {}", pc, index) })?;
+		} else {
+			code.code[operand..operand + 2].copy_from_slice(&index.to_be_bytes());
+		}
+	}
+	for handler in &mut code.handlers {
+		visit_all([&mut handler.catch_type_index], f)?;
+	}
+	visit_attributes(&mut code.attributes, f)
+}
+
+/// Visit the indices inside attributes kept as raw bytes, for the layouts that are simple enough
+/// to patch in place (JVMS17 4.7.5, 4.7.20, 4.7.24, 4.7.26, 4.7.27).
+fn visit_unrecognised<F: FnMut(&mut u16) -> Result<(), EncodeError>>(attribute: &mut UnrecognisedAttribute, f: &mut F) -> Result<(), EncodeError> {
+	let name = String::from_utf8_lossy(&attribute.attribute_name).into_owned();
+	let info = &attribute.info;
+	let read_u16 = |offset: usize| info.get(offset..offset + 2).map(|bytes| usize::from(u16::from_be_bytes([bytes[0], bytes[1]])));
+	let offsets: Vec<usize> = match name.as_str() {
+		"Deprecated" | "Synthetic" | "SourceDebugExtension" => vec![],
+		"ModuleMainClass" => vec![0],
+		"Exceptions" | "ModulePackages" => (0..read_u16(0).unwrap_or(0)).map(|n| 2 + 2 * n).collect(),
+		"MethodParameters" => (0..usize::from(info.first().copied().unwrap_or(0))).map(|n| 1 + 4 * n).collect(),
+		"RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => return visit_type_annotations(&mut attribute.info, f)
+			.map_err(|e| EncodeError { msg: format!("{} attribute: {}", name, e) }),
+		_ => return Err(EncodeError { msg: format!("cannot renumber constants referred to by a {} attribute", name) }),
+	};
+	for offset in offsets {
+		let bytes = attribute.info.get_mut(offset..offset + 2)
+			.ok_or_else(|| EncodeError { msg: format!("{} attribute is truncated", name) })?;
+		let mut index = u16::from_be_bytes([bytes[0], bytes[1]]);
+		visit_all([&mut index], f)?;
+		bytes.copy_from_slice(&index.to_be_bytes());
+	}
+	Ok(())
+}
+
+/// Visit the indices of a RuntimeVisibleTypeAnnotations or RuntimeInvisibleTypeAnnotations attribute
+/// (JVMS17 4.7.20), skipping each target_info and type_path to reach the annotation proper.
+fn visit_type_annotations<F: FnMut(&mut u16) -> Result<(), EncodeError>>(info: &mut [u8], f: &mut F) -> Result<(), EncodeError> {
+	let truncated = || EncodeError { msg: "truncated".to_string() };
+	let byte = |info: &[u8], offset: usize| info.get(offset).copied().map(usize::from).ok_or_else(truncated);
+	let count = byte(info, 0)? << 8 | byte(info, 1)?;
+	let mut offset = 2;
+	for _ in 0..count {
+		let target_info = match byte(info, offset)? {
+			0x00 | 0x01 | 0x16 => 1,
+			0x10 | 0x11 | 0x12 | 0x17 | 0x42 | 0x43..=0x46 => 2,
+			0x13..=0x15 => 0,
+			0x40 | 0x41 => 2 + 6 * (byte(info, offset + 1)? << 8 | byte(info, offset + 2)?),
+			0x47..=0x4B => 3,
+			target_type => return Err(EncodeError { msg: format!("unknown target_type {:#04x}", target_type) }),
+		};
+		offset += 1 + target_info;
+		offset += 1 + 2 * byte(info, offset)?;
+
+		let mut cursor = binrw::io::Cursor::new(info.get(offset..).ok_or_else(truncated)?);
+		let mut annotation = Annotation::read_be(&mut cursor).map_err(|e| EncodeError { msg: e.to_string() })?;
+		let length = cursor.position() as usize;
+		visit_annotation(&mut annotation, f)?;
+		let mut writer = binrw::io::Cursor::new(Vec::new());
+		annotation.write_be(&mut writer).map_err(|e| EncodeError { msg: e.to_string() })?;
+		info[offset..offset + length].copy_from_slice(&writer.into_inner());
+		offset += length;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::File;
+
+	use crate::{
+		analysis::{mapping::Mapping, remapper::Remapper},
+		class::{
+			class::{Class, MemberRef},
+			constant_pool::ConstantPoolItem,
+			constant_pool_builder::ConstantPoolBuilder}};
+
+	#[test]
+	fn test_deduplication() {
+		let mut builder = ConstantPoolBuilder::new();
+		let method = builder.add_method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V").unwrap();
+		assert_eq!(builder.add_method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V").unwrap(), method);
+		let class = builder.add_class("java/io/PrintStream").unwrap();
+		assert_eq!(builder.add_utf8("java/io/PrintStream").unwrap(), 1);
+		assert_eq!(class, 2);
+		// an interface method with the same names is a different constant
+		assert_ne!(builder.add_interface_method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V").unwrap(), method);
+		assert_ne!(builder.add_float(0.0).unwrap(), builder.add_float(-0.0).unwrap());
+		assert_eq!(builder.add_double(f64::NAN).unwrap(), builder.add_double(f64::NAN).unwrap());
+	}
+
+	#[test]
+	fn test_wide_constants() {
+		let mut builder = ConstantPoolBuilder::new();
+		assert_eq!(builder.add_long(1).unwrap(), 1);
+		assert_eq!(builder.add_double(2.0).unwrap(), 3);
+		assert_eq!(builder.add_integer(3).unwrap(), 5);
+		assert_eq!(builder.constant_pool_count(), 6);
+		assert_eq!(builder.add_long(4).unwrap(), 6);
+		assert_eq!(builder.build().length, 8);
+	}
+
+	#[test]
+	fn test_limit() {
+		let mut builder = ConstantPoolBuilder::new();
+		for n in 1..u16::MAX {
+			assert_eq!(builder.add_integer(i32::from(n)).unwrap(), n);
+		}
+		assert_eq!(builder.constant_pool_count(), u16::MAX);
+		assert!(builder.add_integer(0).is_err());
+		assert!(builder.add_integer(1).is_ok());
+
+		let mut builder = ConstantPoolBuilder::new();
+		for n in 1..u16::MAX - 1 {
+			builder.add_integer(i32::from(n)).unwrap();
+		}
+		assert!(builder.add_long(0).is_err());
+		assert!(builder.add_integer(0).is_ok());
+	}
+
+	#[test]
+	fn test_compact() {
+		for path in ["tests/resources/Sample.class", "tests/resources/Branches.class", "tests/resources/calls/App.class", "tests/resources/remap/com/example/Main.class"] {
+			let bytes = std::fs::read(path).unwrap();
+			let class = Class::new(File::open(path).unwrap());
+
+			// javac leaves no unused constants, so compacting its output changes nothing
+			let mut compacted = class.clone();
+			ConstantPoolBuilder::compact(&mut compacted).unwrap();
+			assert_eq!(compacted.to_bytes().unwrap(), bytes, "{}", path);
+
+			// unused constants appended after javac's are dropped again
+			let mut builder = ConstantPoolBuilder::from(class.constant_pool.clone());
+			builder.add_long(0x1234_5678_9abc).unwrap();
+			builder.add_method_ref("unused/Owner", "unused", "()V").unwrap();
+			let mut padded = class.clone();
+			padded.constant_pool = builder.into_constants();
+			ConstantPoolBuilder::compact(&mut padded).unwrap();
+			assert_eq!(padded.to_bytes().unwrap(), bytes, "{}", path);
+		}
+	}
+
+	#[test]
+	fn test_compact_renumbers() {
+		let path = "tests/resources/remap/com/example/Main.class";
+		let class = Class::new(File::open(path).unwrap());
+		let mapping = Mapping::from_proguard(&std::fs::read_to_string("tests/resources/remap/mapping.txt").unwrap()).unwrap();
+		// remapping leaves the old names behind, interleaved with the constants still in use
+		let mut remapped = Remapper::new(&mapping, &[class]).remap_class(&Class::new(File::open(path).unwrap())).unwrap();
+		let references = |class: &Class| -> Vec<MemberRef> {
+			(1..class.constant_pool_count()).filter_map(|index| class.get_member_ref(index)).collect()
+		};
+		let before = references(&remapped);
+		let count = remapped.constant_pool.len();
+
+		ConstantPoolBuilder::compact(&mut remapped).unwrap();
+		assert!(remapped.constant_pool.len() < count);
+		assert_eq!(remapped.name(), "a/a");
+		assert!(remapped.constant_pool.values().all(|item| !matches!(item, ConstantPoolItem::Utf8(utf8) if utf8.to_string().contains("com/example"))));
+		let reparsed = Class::new(binrw::io::Cursor::new(remapped.to_bytes().unwrap()));
+		assert_eq!(references(&reparsed), before);
+		assert_eq!(reparsed.methods.methods.len(), remapped.methods.methods.len());
+
+		let mut again = reparsed.clone();
+		ConstantPoolBuilder::compact(&mut again).unwrap();
+		assert_eq!(again.to_bytes().unwrap(), reparsed.to_bytes().unwrap());
+	}
+}
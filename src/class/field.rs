@@ -4,16 +4,21 @@ use crate::{
 	class::{
 		attribute::Attribute},
 	generate_pool_context_read,
+	generate_pool_context_write,
 };
 
 /// An implementation of a field_info structure (JVMS17 4.5)
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Field {
 	pub access_flags: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub name_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub descriptor_index: u16,
 	pub attributes_count: u16,
 	pub attributes: Vec<Attribute>,
 }
 
-generate_pool_context_read!(Field, Field);
\ No newline at end of file
+generate_pool_context_read!(Field, Field);
+generate_pool_context_write!(Field);
\ No newline at end of file
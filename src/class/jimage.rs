@@ -0,0 +1,654 @@
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::{self, Read, Seek, SeekFrom},
+	path::{Path, PathBuf},
+	sync::Mutex};
+
+use flate2::read::ZlibDecoder;
+use thiserror::Error;
+
+use crate::class::modified_utf8::ModifiedUtf8String;
+
+const MAGIC: u32 = 0xCAFEDADA;
+const MAJOR_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 7 * 4;
+
+const ATTRIBUTE_END: u8 = 0;
+const ATTRIBUTE_MODULE: u8 = 1;
+const ATTRIBUTE_PARENT: u8 = 2;
+const ATTRIBUTE_BASE: u8 = 3;
+const ATTRIBUTE_EXTENSION: u8 = 4;
+const ATTRIBUTE_OFFSET: u8 = 5;
+const ATTRIBUTE_COMPRESSED: u8 = 6;
+const ATTRIBUTE_UNCOMPRESSED: u8 = 7;
+
+const COMPRESSED_MAGIC: u32 = 0xCAFEFAFA;
+const COMPRESSED_HEADER_SIZE: usize = 29;
+
+/// The multiplier and initial seed of the image's name hash.
+const HASH_MULTIPLIER: u32 = 0x01000193;
+
+/// Directories holding the image's synthesised module and package trees rather than resources.
+const PSEUDO_MODULES: [&str; 2] = ["modules", "packages"];
+
+#[derive(Error, Debug)]
+pub enum JImageError {
+	#[error("I/O error: {0}")]
+	Io(#[from] io::Error),
+	#[error("{0} is not a jimage")]
+	NotAnImage(PathBuf),
+	#[error("unsupported jimage version {major}.{minor}")]
+	Version { major: u32, minor: u32 },
+	#[error("malformed jimage: {0}")]
+	Malformed(String),
+	#[error("could not decompress {name}: {message}")]
+	Decompress { name: String, message: String },
+}
+
+/// The entry for one resource in the image's location table.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Location {
+	pub module: String,
+	pub parent: String,
+	pub base: String,
+	pub extension: String,
+	/// The offset of the content from the end of the index.
+	pub offset: u64,
+	/// The stored size, or zero if the content is not compressed.
+	pub compressed: u64,
+	pub uncompressed: u64,
+}
+
+impl Location {
+
+	/// The resource name, e.g. `/java.base/java/lang/String.class`.
+	pub fn full_name(&self) -> String {
+		let mut name = String::new();
+		if !self.module.is_empty() {
+			name.push('/');
+			name.push_str(&self.module);
+			name.push('/');
+		}
+		if !self.parent.is_empty() {
+			name.push_str(&self.parent);
+			name.push('/');
+		}
+		name.push_str(&self.base);
+		if !self.extension.is_empty() {
+			name.push('.');
+			name.push_str(&self.extension);
+		}
+		name
+	}
+}
+
+/// A reader for the jimage format of a JDK runtime image's `lib/modules` file.
+///
+/// The index (header, redirect table, location offsets, location attributes and strings) is read
+/// up front; resource content is read from the file on demand.
+#[derive(Debug)]
+pub struct JImage {
+	pub path: PathBuf,
+	big_endian: bool,
+	redirect: Vec<i32>,
+	offsets: Vec<u32>,
+	locations: Vec<u8>,
+	strings: Vec<u8>,
+	index_size: u64,
+	/// The length of the file, which every resource's content must lie within.
+	file_length: u64,
+	/// The module of every package, by internal package name.
+	packages: HashMap<String, String>,
+	file: Mutex<File>,
+}
+
+impl JImage {
+
+	pub fn open(path: &Path) -> Result<JImage, JImageError> {
+		let mut file = File::open(path)?;
+		let mut header = [0u8; HEADER_SIZE];
+		file.read_exact(&mut header).map_err(|_| JImageError::NotAnImage(path.to_path_buf()))?;
+		let big_endian = if u32::from_le_bytes(header[..4].try_into().unwrap()) == MAGIC {
+			false
+		} else if u32::from_be_bytes(header[..4].try_into().unwrap()) == MAGIC {
+			true
+		} else {
+			return Err(JImageError::NotAnImage(path.to_path_buf()));
+		};
+		let word = |index: usize| read_u32(&header, index * 4, big_endian);
+		let (major, minor) = (word(1) >> 16, word(1) & 0xffff);
+		if major != MAJOR_VERSION {
+			return Err(JImageError::Version { major, minor });
+		}
+		let table_length = word(4) as usize;
+		let locations_size = word(5) as usize;
+		let strings_size = word(6) as usize;
+
+		let file_length = file.metadata()?.len();
+		let index_size = table_length.checked_mul(8)
+			.and_then(|tables| tables.checked_add(locations_size)?.checked_add(strings_size))
+			.filter(|&size| (HEADER_SIZE + size) as u64 <= file_length)
+			.ok_or_else(|| JImageError::Malformed("the index is larger than the file".to_string()))?;
+		let mut index = vec![0u8; index_size];
+		file.read_exact(&mut index)?;
+		let redirect = (0..table_length).map(|slot| read_u32(&index, slot * 4, big_endian) as i32).collect();
+		let offsets = (0..table_length).map(|slot| read_u32(&index, (table_length + slot) * 4, big_endian)).collect();
+		let strings = index.split_off(table_length * 8 + locations_size);
+		let locations = index.split_off(table_length * 8);
+
+		let mut image = JImage {
+			path: path.to_path_buf(),
+			big_endian,
+			redirect,
+			offsets,
+			locations,
+			strings,
+			index_size: (HEADER_SIZE + index_size) as u64,
+			file_length,
+			packages: HashMap::new(),
+			file: Mutex::new(file),
+		};
+		let mut packages = HashMap::new();
+		for slot in 0..image.offsets.len() {
+			let location = image.location_at(slot)?;
+			if !location.module.is_empty() && !location.parent.is_empty() && !PSEUDO_MODULES.contains(&location.module.as_str()) {
+				packages.entry(location.parent).or_insert(location.module);
+			}
+		}
+		image.packages = packages;
+		Ok(image)
+	}
+
+	/// The number of entries in the location table.
+	pub fn len(&self) -> usize {
+		self.offsets.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.offsets.is_empty()
+	}
+
+	/// Every location in the image, in table order.
+	pub fn locations(&self) -> Result<Vec<Location>, JImageError> {
+		(0..self.offsets.len()).map(|slot| self.location_at(slot)).collect()
+	}
+
+	/// The module containing an internal package name such as `java/lang`.
+	pub fn module_of(&self, package: &str) -> Option<&str> {
+		self.packages.get(package).map(String::as_str)
+	}
+
+	/// Look up a resource by its full name, e.g. `/java.base/java/lang/String.class`.
+	pub fn find(&self, name: &str) -> Result<Option<Location>, JImageError> {
+		if self.redirect.is_empty() {
+			return Ok(None);
+		}
+		let length = self.redirect.len() as u32;
+		let slot = match self.redirect[(hash(name, HASH_MULTIPLIER) % length) as usize] {
+			value if value < 0 => (-1 - value) as usize,
+			value if value > 0 => (hash(name, value as u32) % length) as usize,
+			_ => return Ok(None),
+		};
+		if slot >= self.offsets.len() {
+			return Err(JImageError::Malformed(format!("redirect for {} leaves the location table", name)));
+		}
+		// The table is a perfect hash over the names in the image, so other names land on some entry too.
+		let location = self.location_at(slot)?;
+		Ok(if location.full_name() == name { Some(location) } else { None })
+	}
+
+	/// Read a resource's content by full name, decompressing it if needed.
+	pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>, JImageError> {
+		match self.find(name)? {
+			Some(location) => self.read_location(&location).map(Some),
+			None => Ok(None),
+		}
+	}
+
+	/// Read a resource from a package's module by its name within the module, e.g. `java/lang/String.class`.
+	pub fn read_resource(&self, name: &str) -> Result<Option<Vec<u8>>, JImageError> {
+		let Some((package, _)) = name.rsplit_once('/') else {
+			return Ok(None);
+		};
+		match self.module_of(package) {
+			Some(module) => self.read(&format!("/{}/{}", module, name)),
+			None => Ok(None),
+		}
+	}
+
+	pub fn read_location(&self, location: &Location) -> Result<Vec<u8>, JImageError> {
+		let size = if location.compressed != 0 { location.compressed } else { location.uncompressed };
+		let start = self.index_size.checked_add(location.offset)
+			.filter(|start| start.checked_add(size).is_some_and(|end| end <= self.file_length))
+			.ok_or_else(|| JImageError::Malformed(format!("the content of {} lies outside the file", location.full_name())))?;
+		let mut content = vec![0u8; size as usize];
+		{
+			let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+			file.seek(SeekFrom::Start(start))?;
+			file.read_exact(&mut content)?;
+		}
+		if location.compressed == 0 {
+			return Ok(content);
+		}
+		self.decompress(&location.full_name(), content)
+	}
+
+	/// Undo each layer of compression in turn (jdk.internal.jimage.decompressor.Decompressor).
+	fn decompress(&self, name: &str, mut content: Vec<u8>) -> Result<Vec<u8>, JImageError> {
+		let error = |message: String| JImageError::Decompress { name: name.to_string(), message };
+		while content.len() >= COMPRESSED_HEADER_SIZE && read_u32(&content, 0, self.big_endian) == COMPRESSED_MAGIC {
+			let uncompressed = read_u64(&content, 12, self.big_endian) as usize;
+			let decompressor = self.string(read_u32(&content, 20, self.big_endian))?;
+			let body = &content[COMPRESSED_HEADER_SIZE..];
+			content = match decompressor.as_str() {
+				"zip" => {
+					let mut inflated = Vec::new();
+					ZlibDecoder::new(body).read_to_end(&mut inflated).map_err(|source| error(source.to_string()))?;
+					inflated
+				}
+				"compact-cp" => self.share_strings(body).map_err(&error)?,
+				other => return Err(error(format!("unknown decompressor {}", other))),
+			};
+			if content.len() != uncompressed {
+				return Err(error(format!("expected {} bytes, got {}", uncompressed, content.len())));
+			}
+		}
+		Ok(content)
+	}
+
+	/// Restore a class file whose Utf8 constants were moved to the image's string table
+	/// (jdk.internal.jimage.decompressor.StringSharingDecompressor).
+	fn share_strings(&self, class: &[u8]) -> Result<Vec<u8>, String> {
+		/// Tags standing for a Utf8 constant held in the string table, whole or as a descriptor
+		/// whose class names are split into package and simple name.
+		const EXTERNALIZED_STRING: u8 = 23;
+		const EXTERNALIZED_STRING_DESCRIPTOR: u8 = 25;
+
+		let mut reader = ByteReader { bytes: class, position: 0 };
+		let mut out = Vec::with_capacity(class.len() * 2);
+		out.extend_from_slice(reader.take(8)?);
+		let count = reader.take(2)?;
+		out.extend_from_slice(count);
+		let count = u16::from_be_bytes([count[0], count[1]]);
+		let string = |index: i32| self.string_bytes(index as u32).map_err(|error| error.to_string());
+		let push_utf8 = |out: &mut Vec<u8>, bytes: &[u8]| {
+			out.push(1);
+			out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+			out.extend_from_slice(bytes);
+		};
+
+		let mut index = 1;
+		while index < count {
+			let tag = reader.take(1)?[0];
+			match tag {
+				1 => {
+					let length = reader.take(2)?;
+					let length = usize::from(u16::from_be_bytes([length[0], length[1]]));
+					out.push(tag);
+					out.extend_from_slice(&(length as u16).to_be_bytes());
+					out.extend_from_slice(reader.take(length)?);
+				}
+				EXTERNALIZED_STRING => push_utf8(&mut out, string(reader.compressed_int()?)?),
+				EXTERNALIZED_STRING_DESCRIPTOR => {
+					let descriptor = string(reader.compressed_int()?)?;
+					let length = reader.compressed_int()? as usize;
+					let mut indexes = ByteReader { bytes: reader.take(length)?, position: 0 };
+					let mut expanded = Vec::with_capacity(descriptor.len() * 2);
+					for byte in descriptor {
+						expanded.push(*byte);
+						if *byte == b'L' {
+							let package = string(indexes.compressed_int()?)?;
+							if !package.is_empty() {
+								expanded.extend_from_slice(package);
+								expanded.push(b'/');
+							}
+							expanded.extend_from_slice(string(indexes.compressed_int()?)?);
+						}
+					}
+					push_utf8(&mut out, &expanded);
+				}
+				_ => {
+					let size = match tag {
+						7 | 8 | 16 | 19 | 20 => 2,
+						15 => 3,
+						3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => 4,
+						5 | 6 => 8,
+						_ => return Err(format!("unknown constant tag {}", tag)),
+					};
+					if tag == 5 || tag == 6 {
+						index += 1;
+					}
+					out.push(tag);
+					out.extend_from_slice(reader.take(size)?);
+				}
+			}
+			index += 1;
+		}
+		out.extend_from_slice(&class[reader.position..]);
+		Ok(out)
+	}
+
+	/// Decode the attributes of the location in table slot `slot`.
+	fn location_at(&self, slot: usize) -> Result<Location, JImageError> {
+		let mut position = self.offsets[slot] as usize;
+		let mut attributes = [0u64; 8];
+		loop {
+			let byte = *self.locations.get(position)
+				.ok_or_else(|| JImageError::Malformed(format!("location {} is truncated", slot)))?;
+			let kind = byte >> 3;
+			if kind == ATTRIBUTE_END {
+				break;
+			}
+			if kind > ATTRIBUTE_UNCOMPRESSED {
+				return Err(JImageError::Malformed(format!("location {} has an attribute of unknown kind {}", slot, kind)));
+			}
+			let length = usize::from(byte & 7) + 1;
+			let value = self.locations.get(position + 1..position + 1 + length)
+				.ok_or_else(|| JImageError::Malformed(format!("location {} is truncated", slot)))?
+				.iter()
+				.fold(0u64, |value, byte| (value << 8) | u64::from(*byte));
+			attributes[usize::from(kind)] = value;
+			position += 1 + length;
+		}
+		Ok(Location {
+			module: self.string(attributes[usize::from(ATTRIBUTE_MODULE)] as u32)?,
+			parent: self.string(attributes[usize::from(ATTRIBUTE_PARENT)] as u32)?,
+			base: self.string(attributes[usize::from(ATTRIBUTE_BASE)] as u32)?,
+			extension: self.string(attributes[usize::from(ATTRIBUTE_EXTENSION)] as u32)?,
+			offset: attributes[usize::from(ATTRIBUTE_OFFSET)],
+			compressed: attributes[usize::from(ATTRIBUTE_COMPRESSED)],
+			uncompressed: attributes[usize::from(ATTRIBUTE_UNCOMPRESSED)],
+		})
+	}
+
+	/// The modified UTF-8 bytes of the NUL-terminated string at `offset` in the string table.
+	fn string_bytes(&self, offset: u32) -> Result<&[u8], JImageError> {
+		let rest = self.strings.get(offset as usize..)
+			.ok_or_else(|| JImageError::Malformed(format!("string offset {} is out of range", offset)))?;
+		Ok(&rest[..rest.iter().position(|byte| *byte == 0).unwrap_or(rest.len())])
+	}
+
+	fn string(&self, offset: u32) -> Result<String, JImageError> {
+		Ok(ModifiedUtf8String::new(self.string_bytes(offset)?.to_vec()).to_string())
+	}
+}
+
+/// The hash used to place names in the redirect table (jdk.internal.jimage.ImageStringsReader).
+fn hash(name: &str, seed: u32) -> u32 {
+	name.bytes().fold(seed, |hash, byte| hash.wrapping_mul(HASH_MULTIPLIER) ^ u32::from(byte)) & 0x7fffffff
+}
+
+fn read_u32(bytes: &[u8], offset: usize, big_endian: bool) -> u32 {
+	let word: [u8; 4] = bytes[offset..offset + 4].try_into().unwrap();
+	if big_endian { u32::from_be_bytes(word) } else { u32::from_le_bytes(word) }
+}
+
+fn read_u64(bytes: &[u8], offset: usize, big_endian: bool) -> u64 {
+	let word: [u8; 8] = bytes[offset..offset + 8].try_into().unwrap();
+	if big_endian { u64::from_be_bytes(word) } else { u64::from_le_bytes(word) }
+}
+
+struct ByteReader<'a> {
+	bytes: &'a [u8],
+	position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+
+	fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+		let taken = self.bytes.get(self.position..self.position + length)
+			.ok_or_else(|| format!("unexpected end of data at {}", self.position))?;
+		self.position += length;
+		Ok(taken)
+	}
+
+	/// Read an integer packed by jdk.internal.jimage.decompressor.CompressIndexes: a set high bit
+	/// marks a value of one to three bytes whose length is in bits 5 and 6, otherwise it takes four.
+	fn compressed_int(&mut self) -> Result<i32, String> {
+		let first = self.take(1)?[0];
+		let (length, value) = if first & 0x80 != 0 {
+			(usize::from((first >> 5) & 3), i32::from(first & 0x1f))
+		} else {
+			(4, i32::from(first))
+		};
+		let rest = self.take(length.saturating_sub(1))?;
+		Ok(rest.iter().fold(value, |value, byte| (value << 8) | i32::from(*byte)))
+	}
+}
+
+/// Whether the file at `path` starts with the jimage magic number.
+pub fn is_image(path: &Path) -> bool {
+	let mut magic = [0u8; 4];
+	File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok()
+		&& (u32::from_le_bytes(magic) == MAGIC || u32::from_be_bytes(magic) == MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::HashMap, fs, io::Write, path::PathBuf};
+
+	use flate2::{write::ZlibEncoder, Compression};
+
+	use crate::class::jimage::{hash, is_image, JImage, JImageError, Location, COMPRESSED_MAGIC, HASH_MULTIPLIER, HEADER_SIZE, MAGIC};
+
+	/// Builds a little-endian jimage, interning strings the way the image's string table does.
+	#[derive(Default)]
+	struct ImageWriter {
+		strings: Vec<u8>,
+		offsets: HashMap<String, u32>,
+	}
+
+	impl ImageWriter {
+
+		fn new() -> ImageWriter {
+			let mut writer = ImageWriter::default();
+			writer.string("");
+			writer
+		}
+
+		fn string(&mut self, value: &str) -> u32 {
+			if let Some(offset) = self.offsets.get(value) {
+				return *offset;
+			}
+			let offset = self.strings.len() as u32;
+			self.strings.extend_from_slice(value.as_bytes());
+			self.strings.push(0);
+			self.offsets.insert(value.to_string(), offset);
+			offset
+		}
+
+		fn zip(&mut self, content: &[u8]) -> Vec<u8> {
+			let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+			encoder.write_all(content).unwrap();
+			let deflated = encoder.finish().unwrap();
+			let mut compressed = Vec::new();
+			compressed.extend_from_slice(&COMPRESSED_MAGIC.to_le_bytes());
+			compressed.extend_from_slice(&(deflated.len() as u64).to_le_bytes());
+			compressed.extend_from_slice(&(content.len() as u64).to_le_bytes());
+			compressed.extend_from_slice(&self.string("zip").to_le_bytes());
+			compressed.extend_from_slice(&0u32.to_le_bytes());
+			compressed.push(1);
+			compressed.extend_from_slice(&deflated);
+			compressed
+		}
+
+		/// Write resources named like `/module/parent/base.extension`, each optionally compressed.
+		fn write(mut self, name: &str, resources: &[(&str, Vec<u8>, bool)]) -> PathBuf {
+			let mut locations = Vec::new();
+			let mut starts = Vec::new();
+			let mut content = Vec::new();
+			for (resource, bytes, compress) in resources {
+				let (module, path) = resource[1..].split_once('/').unwrap();
+				let (parent, file) = path.rsplit_once('/').unwrap_or(("", path));
+				let (base, extension) = file.rsplit_once('.').unwrap_or((file, ""));
+				let stored = if *compress { self.zip(bytes) } else { bytes.clone() };
+				let attributes = [
+					self.string(module) as u64, self.string(parent) as u64, self.string(base) as u64, self.string(extension) as u64,
+					content.len() as u64, if *compress { stored.len() as u64 } else { 0 }, bytes.len() as u64];
+				starts.push(locations.len() as u32);
+				for (kind, value) in attributes.iter().enumerate().filter(|(_, value)| **value != 0) {
+					let length = (8 - value.leading_zeros() as usize / 8).max(1);
+					locations.push((((kind + 1) << 3) | (length - 1)) as u8);
+					locations.extend_from_slice(&value.to_be_bytes()[8 - length..]);
+				}
+				locations.push(0);
+				content.extend_from_slice(&stored);
+			}
+
+			// A perfect hash: colliding names get a seed that separates them, the rest a direct slot.
+			let length = resources.len();
+			let mut buckets: Vec<Vec<usize>> = vec![Vec::new(); length];
+			for (index, (resource, _, _)) in resources.iter().enumerate() {
+				buckets[(hash(resource, HASH_MULTIPLIER) as usize) % length].push(index);
+			}
+			let mut redirect = vec![0i32; length];
+			let mut slots: Vec<Option<usize>> = vec![None; length];
+			let mut order: Vec<usize> = (0..length).collect();
+			order.sort_by_key(|bucket| std::cmp::Reverse(buckets[*bucket].len()));
+			for bucket in order.into_iter().filter(|bucket| buckets[*bucket].len() > 1) {
+				let seed = (1..).find(|seed| {
+					let mut placed: Vec<usize> = buckets[bucket].iter().map(|index| hash(resources[*index].0, *seed) as usize % length).collect();
+					placed.sort();
+					placed.dedup();
+					placed.len() == buckets[bucket].len() && placed.iter().all(|slot| slots[*slot].is_none())
+				}).unwrap();
+				for index in &buckets[bucket] {
+					slots[hash(resources[*index].0, seed) as usize % length] = Some(*index);
+				}
+				redirect[bucket] = seed as i32;
+			}
+			for bucket in 0..length {
+				if let [index] = buckets[bucket][..] {
+					let slot = slots.iter().position(Option::is_none).unwrap();
+					slots[slot] = Some(index);
+					redirect[bucket] = -1 - slot as i32;
+				}
+			}
+
+			let mut image = Vec::new();
+			for word in [MAGIC, 1 << 16, 0, length as u32, length as u32, locations.len() as u32, self.strings.len() as u32] {
+				image.extend_from_slice(&word.to_le_bytes());
+			}
+			redirect.iter().for_each(|value| image.extend_from_slice(&value.to_le_bytes()));
+			slots.iter().for_each(|index| image.extend_from_slice(&starts[index.unwrap()].to_le_bytes()));
+			image.extend_from_slice(&locations);
+			image.extend_from_slice(&self.strings);
+			image.extend_from_slice(&content);
+			let path = std::env::temp_dir().join(format!("steele-{}-{}.jimage", name, std::process::id()));
+			fs::write(&path, image).unwrap();
+			path
+		}
+	}
+
+	fn resources(compress: bool) -> Vec<(&'static str, Vec<u8>, bool)> {
+		vec![
+			("/demo/app/Main.class", fs::read("tests/resources/Branches.class").unwrap(), compress),
+			("/demo/app/config.properties", b"greeting=hello\n".to_vec(), compress),
+			("/demo/module-info.class", vec![0xca, 0xfe], false),
+			("/util/util/text/Strings.class", fs::read("tests/resources/Sample.class").unwrap(), compress),
+			("/packages/app/demo", vec![], false),
+		]
+	}
+
+	#[test]
+	fn test_lookup() {
+		let path = ImageWriter::new().write("lookup", &resources(false));
+		assert!(is_image(&path));
+		let image = JImage::open(&path).unwrap();
+		assert_eq!(image.len(), 5);
+		assert_eq!(image.module_of("app"), Some("demo"));
+		assert_eq!(image.module_of("util/text"), Some("util"));
+		for (name, bytes, _) in resources(false) {
+			assert_eq!(image.find(name).unwrap().unwrap().full_name(), name);
+			assert_eq!(image.read(name).unwrap().unwrap(), bytes);
+		}
+		assert_eq!(image.read_resource("util/text/Strings.class").unwrap().unwrap(), fs::read("tests/resources/Sample.class").unwrap());
+		assert!(image.read("/demo/app/Missing.class").unwrap().is_none());
+		assert!(image.read_resource("missing/Main.class").unwrap().is_none());
+		fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn test_malformed_index() {
+		let path = ImageWriter::new().write("malformed", &resources(false));
+		let image = fs::read(&path).unwrap();
+		let table_length = u32::from_le_bytes(image[16..20].try_into().unwrap()) as usize;
+
+		let mut corrupt = image.clone();
+		corrupt[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+		fs::write(&path, &corrupt).unwrap();
+		assert!(matches!(JImage::open(&path), Err(JImageError::Malformed(_))));
+
+		// The first attribute of the first location in the file, made a kind beyond the last one.
+		let mut corrupt = image;
+		corrupt[HEADER_SIZE + table_length * 8] = 31 << 3;
+		fs::write(&path, &corrupt).unwrap();
+		assert!(JImage::open(&path).unwrap_err().to_string().ends_with("has an attribute of unknown kind 31"));
+		fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn test_malformed_locations() {
+		let mut writer = ImageWriter::new();
+		let compressed = writer.zip(b"greeting=hello\n");
+		let path = writer.write("malformed-locations", &resources(false));
+		let image = JImage::open(&path).unwrap();
+		let location = image.find("/demo/app/config.properties").unwrap().unwrap();
+
+		// Content past the end of the file, or at an offset that overflows, is not read.
+		for (offset, uncompressed) in [(location.offset, u64::MAX), (u64::MAX, location.uncompressed), (location.offset, location.uncompressed + 1_000_000)] {
+			let bad = Location { offset, uncompressed, ..location.clone() };
+			assert!(matches!(image.read_location(&bad), Err(JImageError::Malformed(_))));
+		}
+
+		// Nor is a compressed resource's size taken on trust.
+		let mut bad = compressed.clone();
+		bad[12..20].copy_from_slice(&u64::MAX.to_le_bytes());
+		assert!(matches!(image.decompress("bad", bad), Err(JImageError::Decompress { .. })));
+		assert_eq!(image.decompress("good", compressed).unwrap(), b"greeting=hello\n");
+		fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn test_zip_compression() {
+		let path = ImageWriter::new().write("zip", &resources(true));
+		let image = JImage::open(&path).unwrap();
+		let location = image.find("/demo/app/Main.class").unwrap().unwrap();
+		assert!(location.compressed != 0);
+		assert_eq!(image.read_location(&location).unwrap(), fs::read("tests/resources/Branches.class").unwrap());
+		fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn test_shared_strings() {
+		let mut writer = ImageWriter::new();
+		let compressed_int = |value: u32| vec![0xc0 | (value >> 8) as u8, value as u8];
+		let mut class = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 61, 0, 6];
+		// #1 an inline Utf8, #2 an externalized string, #3 a Long, #5 an externalized descriptor
+		class.extend_from_slice(&[1, 0, 2, b'h', b'i']);
+		class.push(23);
+		class.extend(compressed_int(writer.string("greet")));
+		class.extend_from_slice(&[5, 0, 0, 0, 0, 0, 0, 0, 42]);
+		class.push(25);
+		class.extend(compressed_int(writer.string("(IL;[L;)V")));
+		let indexes: Vec<u8> = [writer.string("java/lang"), writer.string("String"), writer.string(""), writer.string("Local")]
+			.into_iter().flat_map(compressed_int).collect();
+		class.extend(compressed_int(indexes.len() as u32));
+		class.extend(indexes);
+		class.extend_from_slice(&[0, 1, 0xff]);
+		let path = writer.write("shared", &resources(false));
+		let image = JImage::open(&path).unwrap();
+
+		let mut expected = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 61, 0, 6];
+		expected.extend_from_slice(&[1, 0, 2, b'h', b'i']);
+		expected.extend_from_slice(&[1, 0, 5]);
+		expected.extend_from_slice(b"greet");
+		expected.extend_from_slice(&[5, 0, 0, 0, 0, 0, 0, 0, 42]);
+		expected.extend_from_slice(&[1, 0, 30]);
+		expected.extend_from_slice(b"(ILjava/lang/String;[LLocal;)V");
+		expected.extend_from_slice(&[0, 1, 0xff]);
+		assert_eq!(image.share_strings(&class).unwrap(), expected);
+		fs::remove_file(path).unwrap();
+	}
+}
@@ -32,4 +32,29 @@ macro_rules! generate_pool_context_read {
 			}
 		}
 	};
+}
+
+/// Generate a BinWrite implementation for use by methods and fields, the counterpart of
+/// `generate_pool_context_read`.
+#[macro_export]
+macro_rules! generate_pool_context_write {
+	($for_type: ty) => {
+		impl binrw::BinWrite for $for_type {
+
+			type Args<'a> = ();
+
+			fn write_options<W: std::io::Write + std::io::Seek>(
+				&self,
+				writer: &mut W,
+				endian: binrw::Endian,
+				_args: (),
+			) -> binrw::BinResult<()> {
+				self.access_flags.write_options(writer, endian, ())?;
+				self.name_index.write_options(writer, endian, ())?;
+				self.descriptor_index.write_options(writer, endian, ())?;
+				(self.attributes.len() as u16).write_options(writer, endian, ())?;
+				self.attributes.write_options(writer, endian, ())
+			}
+		}
+	};
 }
\ No newline at end of file
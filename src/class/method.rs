@@ -2,18 +2,40 @@ use binrw::BinRead;
 
 use crate::{
 	class::{
-		attribute::Attribute},
-	generate_pool_context_read
+		attribute::{Attribute, AttributeInfo, Code}},
+	generate_pool_context_read,
+	generate_pool_context_write
 };
 
 /// An implementation of a method_info structure (JVMS17 4.6)
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Method {
 	pub access_flags: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub name_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
 	pub descriptor_index: u16,
 	pub attributes_count: u16,
 	pub attributes: Vec<Attribute>,
 }
 
-generate_pool_context_read!(Method, Method);
\ No newline at end of file
+generate_pool_context_read!(Method, Method);
+generate_pool_context_write!(Method);
+
+impl Method {
+	/// The method's Code attribute, absent for abstract and native methods.
+	pub fn code(&self) -> Option<&Code> {
+		self.attributes.iter().find_map(|attribute| match &attribute.attribute_info {
+			AttributeInfo::Code(code) => Some(code),
+			_ => None,
+		})
+	}
+
+	pub fn code_mut(&mut self) -> Option<&mut Code> {
+		self.attributes.iter_mut().find_map(|attribute| match &mut attribute.attribute_info {
+			AttributeInfo::Code(code) => Some(code),
+			_ => None,
+		})
+	}
+}
\ No newline at end of file
@@ -1,9 +1,17 @@
 pub mod access;
+pub mod archive;
 pub mod attribute;
+pub mod builder;
 pub mod class;
+pub mod class_path;
 pub mod constant_pool;
+pub mod constant_pool_builder;
 pub mod field;
+pub mod jimage;
 pub mod macros;
 pub mod method;
 pub mod modified_utf8;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod signature;
 pub mod verification;
\ No newline at end of file
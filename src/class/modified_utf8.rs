@@ -18,6 +18,27 @@ impl ModifiedUtf8String {
 		Self { bytes: bytes, parsed: String::new() }
 	}
 
+	/// Encodes a string in "modified UTF-8" (JVMS17 4.4.7): NUL takes two bytes and supplementary
+	/// characters are written as a surrogate pair of three bytes each.
+	pub fn encode(value: &str) -> Vec<u8> {
+		let mut bytes = Vec::with_capacity(value.len());
+		for unit in value.encode_utf16() {
+			match unit {
+				0x0001..=0x007F => bytes.push(unit as u8),
+				0x0000 | 0x0080..=0x07FF => {
+					bytes.push(0b1100_0000 | (unit >> 6) as u8);
+					bytes.push(0b1000_0000 | (unit & 0x3F) as u8);
+				}
+				_ => {
+					bytes.push(0b1110_0000 | (unit >> 12) as u8);
+					bytes.push(0b1000_0000 | ((unit >> 6) & 0x3F) as u8);
+					bytes.push(0b1000_0000 | (unit & 0x3F) as u8);
+				}
+			}
+		}
+		bytes
+	}
+
 	/// See JVMS17 p. 4.4.7.
 	fn is_ascii_range(byte: u8) -> bool {
 		(byte > 0) && ((byte & 0b1000_0000) == 0)
@@ -57,11 +78,12 @@ impl ModifiedUtf8String {
 	fn get_supplementary(_u: u8, v: u8, w: u8, _x: u8, y: u8, z: u8) -> char {
 		let encoded_value: u32 = 
 			0x00010000 as u32 +
-			((u32::from((v & 0x0F) - 1) << 16)) +
+			((u32::from((v & 0x0F).wrapping_sub(1)) << 16)) +
 			((u32::from(w & 0x3F) << 10)) +
 			((u32::from(y & 0x0F) << 6)) +
 			(u32::from(z & 0x3F)) as u32;
-		let ch = char::from_u32(encoded_value).unwrap();
+		// lone or malformed surrogates have no char representation
+		let ch = char::from_u32(encoded_value).unwrap_or(char::REPLACEMENT_CHARACTER);
 		ch
 	}
 
@@ -164,4 +186,13 @@ mod tests {
 		let output = ModifiedUtf8String::new(input.to_vec()).to_string();
 		assert_eq!("$$£🂡£$$", output);
 	}
+
+	#[test]
+	fn test_encode() {
+		for input in ["abcde", "%£££$", "℻MARIO℻", "nul\0nul"] {
+			let encoded = ModifiedUtf8String::encode(input);
+			assert!(!encoded.contains(&0));
+			assert_eq!(ModifiedUtf8String::new(encoded).to_string(), input);
+		}
+	}
 }
\ No newline at end of file
@@ -0,0 +1,143 @@
+use std::{cell::RefCell, collections::BTreeMap};
+
+use serde::{ser::{SerializeSeq, SerializeStruct}, Serialize, Serializer};
+
+use crate::class::{class::Class, constant_pool::ConstantPoolItem, modified_utf8::ModifiedUtf8String};
+
+thread_local! {
+	/// The rendered constant pool of the class being serialised through [Resolved], if any.
+	static RESOLVED: RefCell<Option<BTreeMap<u16, String>>> = const { RefCell::new(None) };
+}
+
+/// Serialises a class with every constant pool index emitted as `{"index": 5, "value": "java/lang/Object"}`
+/// rather than a bare number, so the output can be read without chasing indices.
+///
+/// Serialising the `Class` directly emits raw indices only.
+pub struct Resolved<'a>(pub &'a Class);
+
+impl Serialize for Resolved<'_> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let class = self.0;
+		let names = class.constant_pool.iter().map(|(index, constant)| {
+			let value = match constant {
+				ConstantPoolItem::Utf8(utf8) => utf8.to_string(),
+				ConstantPoolItem::String(string) => class.get_utf8(string.index).unwrap_or_default(),
+				_ => class.render_constant(*index),
+			};
+			(*index, value)
+		}).collect();
+		let previous = RESOLVED.replace(Some(names));
+		let result = class.serialize(serializer);
+		RESOLVED.set(previous);
+		result
+	}
+}
+
+/// Serialise a constant pool index, with its resolved value when inside [Resolved]. The value of
+/// index 0, which stands for no entry, or of any other index missing from the pool is null.
+pub(crate) fn index<S: Serializer>(index: &u16, serializer: S) -> Result<S::Ok, S::Error> {
+	RESOLVED.with_borrow(|resolved| match resolved {
+		Some(names) => {
+			let mut state = serializer.serialize_struct("Index", 2)?;
+			state.serialize_field("index", index)?;
+			state.serialize_field("value", &names.get(index))?;
+			state.end()
+		}
+		None => serializer.serialize_u16(*index),
+	})
+}
+
+/// Serialise a table of constant pool indices, see [index].
+pub(crate) fn indices<S: Serializer>(indices: &[u16], serializer: S) -> Result<S::Ok, S::Error> {
+	struct Index(u16);
+
+	impl Serialize for Index {
+		fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+			index(&self.0, serializer)
+		}
+	}
+
+	let mut state = serializer.serialize_seq(Some(indices.len()))?;
+	for index in indices {
+		state.serialize_element(&Index(*index))?;
+	}
+	state.end()
+}
+
+/// Serialise modified UTF-8 bytes (JVMS17 4.4.7) as a string.
+pub(crate) fn utf8<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_str(&ModifiedUtf8String::new(bytes.to_vec()).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::File;
+
+	use serde_json::{json, Value};
+
+	use crate::{
+		class::{access::MethodAccessPropertyFlags, builder::ClassBuilder, class::Class, serialize::Resolved},
+		isa::opcode::Opcode};
+
+	fn get_class() -> Class {
+		Class::new(File::open("tests/resources/Branches.class").unwrap())
+	}
+
+	fn method<'a>(class: &'a Value, name: &str) -> &'a Value {
+		class["methods"]["methods"].as_array().unwrap().iter()
+			.find(|method| method["name_index"]["value"] == name)
+			.unwrap()
+	}
+
+	#[test]
+	fn test_raw_indices() {
+		let class = serde_json::to_value(get_class()).unwrap();
+		assert_eq!(class["major_version"], 61);
+		assert_eq!(class["flags"], json!(["Public", "Super"]));
+		assert!(class["this_class"]["index"].is_u64());
+		let this_class = class["this_class"]["index"].to_string();
+		assert_eq!(class["constant_pool"][this_class]["Utf8"]["bytes"], "Branches");
+	}
+
+	#[test]
+	fn test_resolved_names() {
+		let class = serde_json::to_value(Resolved(&get_class())).unwrap();
+		assert_eq!(class["this_class"]["index"]["value"], "Branches");
+		assert_eq!(class["super_class"]["index"]["value"], "java/lang/Object");
+		assert_eq!(class["fields"]["fields"][0]["name_index"]["value"], "total");
+		assert_eq!(class["fields"]["fields"][0]["descriptor_index"]["value"], "I");
+
+		let guarded = method(&class, "guarded");
+		assert_eq!(guarded["descriptor_index"]["value"], "([I)I");
+		let code = &guarded["attributes"][0];
+		assert_eq!(code["name_index"]["value"], "Code");
+		assert_eq!(code["attribute_info"]["Code"]["handlers"][0]["catch_type_index"]["value"],
+			"java/lang/ArrayIndexOutOfBoundsException");
+
+		// Raw serialisation is unaffected once the resolved class has been written.
+		let raw = serde_json::to_value(get_class()).unwrap();
+		assert!(raw["this_class"]["index"].is_u64());
+	}
+
+	#[test]
+	fn test_index_zero() {
+		let class = ClassBuilder::new("demo/Finally")
+			.method(&[MethodAccessPropertyFlags::Static], "run", "()V", |method| {
+				let (start, end, handler) = (method.new_label(), method.new_label(), method.new_label());
+				method.label(start).op(Opcode::Return).label(end)
+					.label(handler).op(Opcode::AThrow)
+					.try_catch(start, end, handler, None);
+			})
+			.build().unwrap();
+		let class = serde_json::to_value(Resolved(&class)).unwrap();
+		let handler = &method(&class, "run")["attributes"][0]["attribute_info"]["Code"]["handlers"][0];
+		assert_eq!(handler["catch_type_index"], json!({"index": 0, "value": null}));
+	}
+
+	#[test]
+	fn test_yaml() {
+		let yaml = serde_yaml::to_string(&Resolved(&get_class())).unwrap();
+		assert!(yaml.contains("value: java/lang/StringBuilder"));
+		assert!(yaml.contains("value: yes"));
+	}
+}
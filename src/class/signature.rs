@@ -0,0 +1,144 @@
+/// Rewrite the class names in a field descriptor, method descriptor or generic signature (JVMS17
+/// 4.3, 4.7.9.1), passing each to `map_class` in order of appearance. Nested classes of a
+/// parameterized type are passed with `$`, as in `Outer$Inner`, and written back with the part of
+/// their new name after their outer class's. None if the signature is malformed, though the names
+/// before the error have been passed.
+pub fn map_classes<F: FnMut(&str) -> String>(signature: &str, map_class: F) -> Option<String> {
+	let mut parser = SignatureParser { map_class, input: signature.as_bytes(), position: 0, output: String::with_capacity(signature.len()) };
+	parser.signature()?;
+	Some(parser.output)
+}
+
+/// A recursive descent parser over the signature grammar (JVMS17 4.7.9.1), which also accepts
+/// plain descriptors, copying its input and renaming classes as it goes.
+struct SignatureParser<'a, F: FnMut(&str) -> String> {
+	map_class: F,
+	input: &'a [u8],
+	position: usize,
+	output: String,
+}
+
+impl<F: FnMut(&str) -> String> SignatureParser<'_, F> {
+	fn peek(&self) -> Option<u8> {
+		self.input.get(self.position).copied()
+	}
+
+	fn copy(&mut self) -> Option<u8> {
+		let byte = self.peek()?;
+		self.output.push(byte as char);
+		self.position += 1;
+		Some(byte)
+	}
+
+	fn expect(&mut self, expected: u8) -> Option<()> {
+		(self.copy()? == expected).then_some(())
+	}
+
+	/// Read up to, but not including, the first of `terminators`.
+	fn identifier(&mut self, terminators: &[u8]) -> Option<String> {
+		let start = self.position;
+		while !terminators.contains(&self.peek()?) {
+			self.position += 1;
+		}
+		String::from_utf8(self.input[start..self.position].to_vec()).ok()
+	}
+
+	fn signature(&mut self) -> Option<()> {
+		if self.peek() == Some(b'<') {
+			self.type_parameters()?;
+		}
+		if self.peek() == Some(b'(') {
+			self.copy();
+			while self.peek()? != b')' {
+				self.type_signature()?;
+			}
+			self.copy();
+			self.type_signature()?;
+			while self.peek() == Some(b'^') {
+				self.copy();
+				self.type_signature()?;
+			}
+		} else {
+			while self.peek().is_some() {
+				self.type_signature()?;
+			}
+		}
+		(self.position == self.input.len()).then_some(())
+	}
+
+	fn type_parameters(&mut self) -> Option<()> {
+		self.expect(b'<')?;
+		while self.peek()? != b'>' {
+			let name = self.identifier(b":")?;
+			self.output.push_str(&name);
+			while self.peek() == Some(b':') {
+				self.copy();
+				if matches!(self.peek()?, b'L' | b'[' | b'T') {
+					self.type_signature()?;
+				}
+			}
+		}
+		self.expect(b'>')
+	}
+
+	fn type_signature(&mut self) -> Option<()> {
+		match self.peek()? {
+			b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b'V' => self.copy().map(|_| ()),
+			b'[' => {
+				self.copy();
+				self.type_signature()
+			}
+			b'T' => {
+				let variable = self.identifier(b";")?;
+				self.output.push_str(&variable);
+				self.expect(b';')
+			}
+			b'L' => self.class_type_signature(),
+			_ => None,
+		}
+	}
+
+	fn class_type_signature(&mut self) -> Option<()> {
+		self.expect(b'L')?;
+		let mut class_name = self.identifier(b"<.;")?;
+		let mut mapped = (self.map_class)(&class_name);
+		self.output.push_str(&mapped);
+		loop {
+			if self.peek()? == b'<' {
+				self.type_arguments()?;
+			}
+			match self.copy()? {
+				b';' => return Some(()),
+				b'.' => {
+					let inner = self.identifier(b"<.;")?;
+					class_name = format!("{}${}", class_name, inner);
+					let mapped_inner = (self.map_class)(&class_name);
+					let simple_name = match mapped_inner.strip_prefix(&format!("{}$", mapped)) {
+						Some(simple_name) => simple_name.to_string(),
+						None => mapped_inner.rsplit(['$', '/']).next().unwrap_or(&mapped_inner).to_string(),
+					};
+					self.output.push_str(&simple_name);
+					mapped = mapped_inner;
+				}
+				_ => return None,
+			}
+		}
+	}
+
+	fn type_arguments(&mut self) -> Option<()> {
+		self.expect(b'<')?;
+		while self.peek()? != b'>' {
+			match self.peek()? {
+				b'*' => {
+					self.copy();
+				}
+				b'+' | b'-' => {
+					self.copy();
+					self.type_signature()?;
+				}
+				_ => self.type_signature()?,
+			}
+		}
+		self.expect(b'>')
+	}
+}
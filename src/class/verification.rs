@@ -3,88 +3,83 @@ use binrw::binrw;
 /// An implementation of verification_type_info (JVMS17 4.74).
 #[binrw]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum VerificationTypeInfo {
-	#[br(magic(0u8))]
+	#[brw(magic(0u8))]
 	TopVariableInfo(TopVariableInfo),
-	#[br(magic(1u8))]
+	#[brw(magic(1u8))]
 	IntegerVariableInfo(IntegerVariableInfo),
-	#[br(magic(2u8))]
+	#[brw(magic(2u8))]
 	FloatVariableInfo(FloatVariableInfo),
-	#[br(magic(3u8))]
+	#[brw(magic(3u8))]
 	DoubleVariableInfo(DoubleVariableInfo),
-	#[br(magic(4u8))]
+	#[brw(magic(4u8))]
 	LongVariableInfo(LongVariableInfo),
-	#[br(magic(5u8))]
+	#[brw(magic(5u8))]
 	NullVariableInfo(NullVariableInfo),
-	#[br(magic(6u8))]
+	#[brw(magic(6u8))]
 	UninitializedThisVariableInfo(UninitializedThisVariableInfo),
-	#[br(magic(7u8))]
+	#[brw(magic(7u8))]
 	ObjectVariableInfo(ObjectVariableInfo),
-	#[br(magic(8u8))]
+	#[brw(magic(8u8))]
 	UninitializedVariableInfo(UninitializedVariableInfo)
 }
 
 /// See JVMS17 4.74 p. 119.
 #[binrw]
 #[derive(Clone, Debug, PartialEq)]
-pub struct TopVariableInfo {
-	tag: u8,
-}
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TopVariableInfo {}
 
 /// See JVMS17 4.74 p. 120.
 #[binrw]
 #[derive(Clone, Debug, PartialEq)]
-pub struct IntegerVariableInfo {
-	tag: u8,
-}
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct IntegerVariableInfo {}
 
 /// See JVMS17 4.74 p. 120.
 #[binrw]
 #[derive(Clone, Debug, PartialEq)]
-pub struct FloatVariableInfo {
-	tag: u8,
-}
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FloatVariableInfo {}
 
 /// See JVMS17 4.74 p. 121.
 #[binrw]
 #[derive(Clone, Debug, PartialEq)]
-pub struct DoubleVariableInfo {
-	tag: u8,
-}
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DoubleVariableInfo {}
 
 /// See JVMS17 4.74 p. 121.
 #[binrw]
 #[derive(Clone, Debug, PartialEq)]
-pub struct LongVariableInfo {
-	tag: u8,
-}
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LongVariableInfo {}
 
 /// See JVMS17 4.74 p. 120.
 #[binrw]
 #[derive(Clone, Debug, PartialEq)]
-pub struct NullVariableInfo {
-	tag: u8,
-}
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NullVariableInfo {}
 
 /// See JVMS17 4.74 p. 120.
 #[binrw]
 #[derive(Clone, Debug, PartialEq)]
-pub struct UninitializedThisVariableInfo {
-	tag: u8,
-}
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct UninitializedThisVariableInfo {}
 
 /// See JVMS17 4.74 p. 120.
 #[binrw]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ObjectVariableInfo {
-	tag: u8,
-	constant_pool_index: u16,
+	#[cfg_attr(feature = "serde", serde(serialize_with = "crate::class::serialize::index"))]
+	pub constant_pool_index: u16,
 }
 
 /// See JVMS17 4.74 p. 120.
 #[binrw]
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct UninitializedVariableInfo {
-	tag: u8,
-	offset: u16,
+	pub offset: u16,
 }
\ No newline at end of file
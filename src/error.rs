@@ -28,4 +28,5 @@ macro_rules! make_error {
 make_error!(StackError);
 make_error!(VariableError);
 make_error!(FetchError);
-make_error!(DecodeError);
\ No newline at end of file
+make_error!(DecodeError);
+make_error!(EncodeError);
@@ -0,0 +1,281 @@
+use crate::{
+	error::{DecodeError, EncodeError},
+	isa::{
+		metadata::OperandFormat,
+		opcode::Opcode},
+};
+
+/// A position in a method's code that survives edits to the surrounding instructions.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Label(pub usize);
+
+/// A single JVM instruction together with its operands.
+///
+/// Branch targets are kept apart from the other operands so that code can be decoded with absolute
+/// targets (`Instruction<u32>`) or edited with symbolic ones (`Instruction<Label>`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction<T = u32> {
+	/// Any instruction that does not refer to another position in the code. Operands are kept as the
+	/// raw bytes following the opcode; for `wide` they include the modified opcode.
+	Plain { opcode: Opcode, operands: Vec<u8> },
+	/// A conditional branch, `goto`, `jsr` or one of their wide forms.
+	Branch { opcode: Opcode, target: T },
+	/// A tableswitch (JVMS17 6.5); `targets[n]` is taken for the key `low + n`.
+	TableSwitch { default: T, low: i32, targets: Vec<T> },
+	/// A lookupswitch (JVMS17 6.5), with match-offset pairs sorted by key.
+	LookupSwitch { default: T, pairs: Vec<(i32, T)> },
+}
+
+impl<T> Instruction<T> {
+
+	pub fn opcode(&self) -> Opcode {
+		match self {
+			Instruction::Plain { opcode, .. } => *opcode,
+			Instruction::Branch { opcode, .. } => *opcode,
+			Instruction::TableSwitch { .. } => Opcode::TableSwitch,
+			Instruction::LookupSwitch { .. } => Opcode::LookupSwitch,
+		}
+	}
+
+	/// Every position this instruction may transfer control to, other than the next instruction.
+	pub fn targets(&self) -> Vec<&T> {
+		match self {
+			Instruction::Plain { .. } => vec![],
+			Instruction::Branch { target, .. } => vec![target],
+			Instruction::TableSwitch { default, targets, .. } => std::iter::once(default).chain(targets.iter()).collect(),
+			Instruction::LookupSwitch { default, pairs } => std::iter::once(default).chain(pairs.iter().map(|(_, target)| target)).collect(),
+		}
+	}
+
+	/// Convert the branch targets of this instruction, leaving every other operand intact.
+	pub fn map_targets<U, F: FnMut(&T) -> U>(&self, mut f: F) -> Instruction<U> {
+		match self {
+			Instruction::Plain { opcode, operands } => Instruction::Plain { opcode: *opcode, operands: operands.clone() },
+			Instruction::Branch { opcode, target } => Instruction::Branch { opcode: *opcode, target: f(target) },
+			Instruction::TableSwitch { default, low, targets } => Instruction::TableSwitch {
+				default: f(default),
+				low: *low,
+				targets: targets.iter().map(&mut f).collect(),
+			},
+			Instruction::LookupSwitch { default, pairs } => Instruction::LookupSwitch {
+				default: f(default),
+				pairs: pairs.iter().map(|(key, target)| (*key, f(target))).collect(),
+			},
+		}
+	}
+
+	/// Convert the branch targets like [Instruction::map_targets], failing with the first target
+	/// `f` cannot convert.
+	pub fn try_map_targets<U, E, F: FnMut(&T) -> Result<U, E>>(&self, mut f: F) -> Result<Instruction<U>, E> {
+		Ok(match self {
+			Instruction::Plain { opcode, operands } => Instruction::Plain { opcode: *opcode, operands: operands.clone() },
+			Instruction::Branch { opcode, target } => Instruction::Branch { opcode: *opcode, target: f(target)? },
+			Instruction::TableSwitch { default, low, targets } => Instruction::TableSwitch {
+				default: f(default)?,
+				low: *low,
+				targets: targets.iter().map(&mut f).collect::<Result<_, _>>()?,
+			},
+			Instruction::LookupSwitch { default, pairs } => Instruction::LookupSwitch {
+				default: f(default)?,
+				pairs: pairs.iter().map(|(key, target)| Ok((*key, f(target)?))).collect::<Result<_, _>>()?,
+			},
+		})
+	}
+
+	/// The constant pool index named by the first two operand bytes, for instructions that have one.
+	pub fn constant_pool_index(&self) -> Option<u16> {
+		match self {
+			Instruction::Plain { opcode: Opcode::Ldc, operands } => Some(u16::from(operands[0])),
+			Instruction::Plain { opcode, operands } if opcode.info().operands.has_constant() => Some(u16::from_be_bytes([operands[0], operands[1]])),
+			_ => None,
+		}
+	}
+
+	/// The number of bytes this instruction occupies when it starts at `pc`.
+	pub fn encoded_length(&self, pc: u32) -> u32 {
+		match self {
+			Instruction::Plain { operands, .. } => 1 + operands.len() as u32,
+			Instruction::Branch { opcode, .. } => if is_wide_branch(*opcode) { 5 } else { 3 },
+			Instruction::TableSwitch { targets, .. } => 1 + switch_padding(pc) + 12 + 4 * targets.len() as u32,
+			Instruction::LookupSwitch { pairs, .. } => 1 + switch_padding(pc) + 8 + 8 * pairs.len() as u32,
+		}
+	}
+}
+
+impl Instruction<u32> {
+
+	/// Encode this instruction at `pc`, converting absolute targets back to relative offsets.
+	pub fn encode(&self, pc: u32, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+		let relative = |target: &u32| i64::from(*target) - i64::from(pc);
+		out.push(u8::from(self.opcode()));
+		match self {
+			Instruction::Plain { operands, .. } => out.extend_from_slice(operands),
+			Instruction::Branch { opcode, target } => {
+				let offset = relative(target);
+				if is_wide_branch(*opcode) {
+					out.extend_from_slice(&(offset as i32).to_be_bytes());
+				} else {
+					let offset = i16::try_from(offset)
+						.map_err(|_| EncodeError { msg: format!("branch offset {} at {} does not fit {}", offset, pc, opcode) })?;
+					out.extend_from_slice(&offset.to_be_bytes());
+				}
+			}
+			Instruction::TableSwitch { default, low, targets } => {
+				let high = i32::try_from(targets.len()).ok()
+					.filter(|count| *count > 0)
+					.and_then(|count| low.checked_add(count - 1))
+					.ok_or_else(|| EncodeError { msg: format!("tableswitch at {} has {} targets from low {}", pc, targets.len(), low) })?;
+				out.resize(out.len() + switch_padding(pc) as usize, 0);
+				out.extend_from_slice(&(relative(default) as i32).to_be_bytes());
+				out.extend_from_slice(&low.to_be_bytes());
+				out.extend_from_slice(&high.to_be_bytes());
+				for target in targets {
+					out.extend_from_slice(&(relative(target) as i32).to_be_bytes());
+				}
+			}
+			Instruction::LookupSwitch { default, pairs } => {
+				out.resize(out.len() + switch_padding(pc) as usize, 0);
+				out.extend_from_slice(&(relative(default) as i32).to_be_bytes());
+				out.extend_from_slice(&(pairs.len() as i32).to_be_bytes());
+				for (key, target) in pairs {
+					out.extend_from_slice(&key.to_be_bytes());
+					out.extend_from_slice(&(relative(target) as i32).to_be_bytes());
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Number of zero bytes between a switch opcode at `pc` and its 4-byte aligned operands.
+pub fn switch_padding(pc: u32) -> u32 {
+	(4 - ((pc + 1) % 4)) % 4
+}
+
+/// Whether the opcode is one of the branches carrying a 32-bit offset.
+pub fn is_wide_branch(opcode: Opcode) -> bool {
+	matches!(opcode, Opcode::GotoW | Opcode::JsrW)
+}
+
+pub(crate) fn is_branch(opcode: Opcode) -> bool {
+	matches!(opcode.info().operands, OperandFormat::Branch | OperandFormat::WideBranch)
+}
+
+/// Number of operand bytes following a fixed-length opcode (everything except switches and `wide`).
+pub(crate) fn operand_length(opcode: Opcode) -> usize {
+	opcode.info().operands.length().unwrap_or(0)
+}
+
+fn read_bytes<const N: usize>(code: &[u8], pc: usize) -> Result<[u8; N], DecodeError> {
+	code.get(pc..pc + N)
+		.and_then(|bytes| bytes.try_into().ok())
+		.ok_or_else(|| DecodeError { msg: format!("premature end of code at {}", pc) })
+}
+
+fn read_i32(code: &[u8], pc: usize) -> Result<i32, DecodeError> {
+	Ok(i32::from_be_bytes(read_bytes(code, pc)?))
+}
+
+/// Decode the instruction starting at `pc`, returning it with absolute branch targets and its length.
+pub fn decode_at(code: &[u8], pc: u32) -> Result<(Instruction, u32), DecodeError> {
+	let start = pc as usize;
+	let byte = *code.get(start).ok_or_else(|| DecodeError { msg: format!("premature end of code at {}", pc) })?;
+	let opcode = Opcode::try_from(byte).map_err(|_| DecodeError { msg: format!("illegal opcode {:#04x} at {}", byte, pc) })?;
+	let target = |offset: i64| -> Result<u32, DecodeError> {
+		u32::try_from(i64::from(pc) + offset).map_err(|_| DecodeError { msg: format!("branch at {} leaves the method", pc) })
+	};
+
+	let instruction = match opcode {
+		Opcode::TableSwitch | Opcode::LookupSwitch => {
+			let mut cursor = start + 1 + switch_padding(pc) as usize;
+			let default = target(i64::from(read_i32(code, cursor)?))?;
+			cursor += 4;
+			if opcode == Opcode::TableSwitch {
+				let low = read_i32(code, cursor)?;
+				let high = read_i32(code, cursor + 4)?;
+				cursor += 8;
+				if high < low {
+					return Err(DecodeError { msg: format!("tableswitch at {} has high {} below low {}", pc, high, low) });
+				}
+				let mut targets = Vec::new();
+				for _ in low..=high {
+					targets.push(target(i64::from(read_i32(code, cursor)?))?);
+					cursor += 4;
+				}
+				Instruction::TableSwitch { default, low, targets }
+			} else {
+				let npairs = read_i32(code, cursor)?;
+				cursor += 4;
+				let mut pairs = Vec::new();
+				for _ in 0..npairs.max(0) {
+					let key = read_i32(code, cursor)?;
+					pairs.push((key, target(i64::from(read_i32(code, cursor + 4)?))?));
+					cursor += 8;
+				}
+				Instruction::LookupSwitch { default, pairs }
+			}
+		}
+		Opcode::Wide => {
+			let [modified] = read_bytes::<1>(code, start + 1)?;
+			let length = if modified == u8::from(Opcode::IInc) { 5 } else { 3 };
+			Instruction::Plain { opcode, operands: code.get(start + 1..start + 1 + length)
+				.ok_or_else(|| DecodeError { msg: format!("premature end of code at {}", pc) })?.to_vec() }
+		}
+		opcode if is_wide_branch(opcode) => Instruction::Branch { opcode, target: target(i64::from(read_i32(code, start + 1)?))? },
+		opcode if is_branch(opcode) => Instruction::Branch { opcode, target: target(i64::from(i16::from_be_bytes(read_bytes(code, start + 1)?)))? },
+		opcode => {
+			let length = operand_length(opcode);
+			Instruction::Plain { opcode, operands: code.get(start + 1..start + 1 + length)
+				.ok_or_else(|| DecodeError { msg: format!("premature end of code at {}", pc) })?.to_vec() }
+		}
+	};
+	let length = instruction.encoded_length(pc);
+	Ok((instruction, length))
+}
+
+/// Decode a whole code array into instructions keyed by their offset.
+pub fn decode(code: &[u8]) -> Result<Vec<(u32, Instruction)>, DecodeError> {
+	let mut instructions = Vec::new();
+	let mut pc = 0u32;
+	while (pc as usize) < code.len() {
+		let (instruction, length) = decode_at(code, pc)?;
+		instructions.push((pc, instruction));
+		pc += length;
+	}
+	Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::isa::{
+		instruction::{decode, Instruction},
+		opcode::Opcode};
+
+	#[test]
+	fn test_round_trip() {
+		let code: Vec<u8> = vec![
+			Opcode::ILoad0 as u8, // 0
+			Opcode::TableSwitch as u8, 0, 0, // 1, padded to 4
+			0, 0, 0, 31, // default -> 32
+			0, 0, 0, 1, // low
+			0, 0, 0, 2, // high
+			0, 0, 0, 27, // 1 -> 28
+			0, 0, 0, 31, // 2 -> 32
+			Opcode::Goto as u8, 0xff, 0xe8, // 24 -> 0
+			Opcode::Nop as u8, // 27
+			Opcode::IInc as u8, 1, 0xff, // 28
+			Opcode::Return as u8, // 31
+			Opcode::Return as u8, // 32
+		];
+		let instructions = decode(&code).unwrap();
+		assert_eq!(instructions[1], (1, Instruction::TableSwitch { default: 32, low: 1, targets: vec![28, 32] }));
+		assert_eq!(instructions[2], (24, Instruction::Branch { opcode: Opcode::Goto, target: 0 }));
+		assert_eq!(instructions[4], (28, Instruction::Plain { opcode: Opcode::IInc, operands: vec![1, 0xff] }));
+
+		let mut encoded = Vec::new();
+		for (pc, instruction) in &instructions {
+			instruction.encode(*pc, &mut encoded).unwrap();
+		}
+		assert_eq!(encoded, code);
+	}
+}
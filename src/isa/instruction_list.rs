@@ -0,0 +1,669 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use binrw::BinWrite;
+
+use crate::{
+	class::{
+		attribute::{AttributeInfo, Code, ExceptionHandler, Line, LocalVariable, LocalVariableType, StackMapFrame},
+		verification::{UninitializedVariableInfo, VerificationTypeInfo}},
+	error::{DecodeError, EncodeError},
+	isa::{
+		instruction::{self, Instruction, Label},
+		opcode::Opcode},
+};
+
+/// The largest code array a method may have (JVMS17 4.7.3).
+pub const MAX_CODE_LENGTH: u32 = 65535;
+
+/// One entry of an instruction list: either an instruction or a label marking the position before
+/// the next instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Element {
+	Label(Label),
+	Instruction(Instruction<Label>),
+}
+
+/// An exception table entry with its code positions held as labels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Handler {
+	pub start: Label,
+	pub end: Label,
+	pub handler: Label,
+	pub catch_type_index: u16,
+}
+
+/// A LineNumberTable entry with its start held as a label.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineNumber {
+	pub start: Label,
+	pub line_number: u16,
+}
+
+/// A LocalVariableTable or LocalVariableTypeTable entry with its live range held as labels.
+/// For type table entries `descriptor_index` holds the signature index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalVariableRange {
+	pub start: Label,
+	pub end: Label,
+	pub name_index: u16,
+	pub descriptor_index: u16,
+	pub index: u16,
+}
+
+/// A StackMapTable entry attached to a label instead of an offset delta.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+	pub label: Label,
+	pub frame: StackMapFrame,
+}
+
+/// An editable view of a method's bytecode.
+///
+/// Branch targets and every table in the Code attribute that points into the bytecode refer to
+/// labels rather than offsets, so instructions can be inserted, removed and replaced freely.
+/// `commit` lays the code out again and writes the result back into a `Code` attribute.
+#[derive(Clone, Debug, Default)]
+pub struct InstructionList {
+	pub elements: Vec<Element>,
+	pub handlers: Vec<Handler>,
+	pub lines: Vec<LineNumber>,
+	pub local_variables: Vec<LocalVariableRange>,
+	pub local_variable_types: Vec<LocalVariableRange>,
+	pub frames: Vec<Frame>,
+	/// Labels of the `new` instructions named by uninitialized verification types, by original offset.
+	uninitialized: BTreeMap<u16, Label>,
+	/// The offsets standing in for `new` instructions that the original code did not name, by
+	/// label. They count down from the top of the offset space, clear of the original offsets.
+	fresh_uninitialized: HashMap<Label, u16>,
+	next_label: usize,
+}
+
+impl InstructionList {
+
+	pub fn new() -> InstructionList {
+		InstructionList::default()
+	}
+
+	/// Decode the bytecode of a Code attribute, creating a label for every offset it refers to.
+	pub fn from_code(code: &Code) -> Result<InstructionList, DecodeError> {
+		let instructions = instruction::decode(&code.code)?;
+		let mut list = InstructionList::new();
+		let mut labels: BTreeMap<u32, Label> = BTreeMap::new();
+		let mut label_at = |list: &mut InstructionList, offset: u32| -> Label {
+			*labels.entry(offset).or_insert_with(|| list.new_label())
+		};
+
+		let mut body: Vec<(u32, Instruction<Label>)> = Vec::new();
+		for (pc, instruction) in &instructions {
+			body.push((*pc, instruction.map_targets(|target| label_at(&mut list, *target))));
+		}
+		for handler in &code.handlers {
+			let entry = Handler {
+				start: label_at(&mut list, u32::from(handler.start_pc)),
+				end: label_at(&mut list, u32::from(handler.end_pc)),
+				handler: label_at(&mut list, u32::from(handler.handler_pc)),
+				catch_type_index: handler.catch_type_index,
+			};
+			list.handlers.push(entry);
+		}
+		for attribute in &code.attributes {
+			match &attribute.attribute_info {
+				AttributeInfo::LineNumberTable(table) => {
+					for line in &table.lines {
+						let start = label_at(&mut list, u32::from(line.start_pc));
+						list.lines.push(LineNumber { start, line_number: line.line_number });
+					}
+				}
+				AttributeInfo::LocalVariableTable(table) => {
+					for variable in &table.local_variables {
+						let start = label_at(&mut list, u32::from(variable.start_pc));
+						let end = label_at(&mut list, u32::from(variable.start_pc) + u32::from(variable.length));
+						list.local_variables.push(LocalVariableRange {
+							start,
+							end,
+							name_index: variable.name_index,
+							descriptor_index: variable.descriptor_index,
+							index: variable.index,
+						});
+					}
+				}
+				AttributeInfo::LocalVariableTypeTable(table) => {
+					for variable in &table.local_variable_types {
+						let start = label_at(&mut list, u32::from(variable.start_pc));
+						let end = label_at(&mut list, u32::from(variable.start_pc) + u32::from(variable.length));
+						list.local_variable_types.push(LocalVariableRange {
+							start,
+							end,
+							name_index: variable.name_index,
+							descriptor_index: variable.signature_index,
+							index: variable.index,
+						});
+					}
+				}
+				AttributeInfo::StackMapTable(table) => {
+					let mut offset: Option<u32> = None;
+					for frame in &table.entries {
+						let position = match offset {
+							None => u32::from(frame.offset_delta()),
+							Some(previous) => previous + u32::from(frame.offset_delta()) + 1,
+						};
+						offset = Some(position);
+						let mut frame = frame.clone();
+						for verification_type in frame.verification_types_mut() {
+							if let VerificationTypeInfo::UninitializedVariableInfo(info) = verification_type {
+								let label = label_at(&mut list, u32::from(info.offset));
+								list.uninitialized.insert(info.offset, label);
+							}
+						}
+						let label = label_at(&mut list, position);
+						list.frames.push(Frame { label, frame });
+					}
+				}
+				_ => {}
+			}
+		}
+
+		for (pc, instruction) in body {
+			if let Some(label) = labels.remove(&pc) {
+				list.elements.push(Element::Label(label));
+			}
+			list.elements.push(Element::Instruction(instruction));
+		}
+		if let Some(label) = labels.remove(&code.code_length) {
+			list.elements.push(Element::Label(label));
+		}
+		if let Some((offset, _)) = labels.first_key_value() {
+			return Err(DecodeError { msg: format!("offset {} does not start an instruction", offset) });
+		}
+		Ok(list)
+	}
+
+	/// Create a label that is not yet placed in the list.
+	pub fn new_label(&mut self) -> Label {
+		let label = Label(self.next_label);
+		self.next_label += 1;
+		label
+	}
+
+	/// An uninitialized verification type for the object created by the `new` instruction placed
+	/// after `label`, for use in `frames`; `commit` fills in the instruction's final offset.
+	pub fn uninitialized(&mut self, label: Label) -> Result<UninitializedVariableInfo, EncodeError> {
+		if let Some((offset, _)) = self.uninitialized.iter().find(|(_, placed)| **placed == label) {
+			return Ok(UninitializedVariableInfo { offset: *offset });
+		}
+		if let Some(offset) = self.fresh_uninitialized.get(&label) {
+			return Ok(UninitializedVariableInfo { offset: *offset });
+		}
+		let offset = u16::try_from(self.fresh_uninitialized.len()).ok()
+			.map(|count| u16::MAX - count)
+			.filter(|offset| !self.uninitialized.contains_key(offset))
+			.ok_or_else(|| EncodeError { msg: "too many uninitialized types to tell apart".to_string() })?;
+		self.fresh_uninitialized.insert(label, offset);
+		Ok(UninitializedVariableInfo { offset })
+	}
+
+	pub fn len(&self) -> usize {
+		self.elements.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.elements.is_empty()
+	}
+
+	pub fn push(&mut self, element: Element) {
+		self.elements.push(element);
+	}
+
+	pub fn insert(&mut self, index: usize, element: Element) {
+		self.elements.insert(index, element);
+	}
+
+	/// Insert a sequence of elements so that the first of them ends up at `index`.
+	pub fn insert_all(&mut self, index: usize, elements: Vec<Element>) {
+		self.elements.splice(index..index, elements);
+	}
+
+	pub fn remove(&mut self, index: usize) -> Element {
+		self.elements.remove(index)
+	}
+
+	pub fn replace(&mut self, index: usize, element: Element) -> Element {
+		std::mem::replace(&mut self.elements[index], element)
+	}
+
+	/// The index of the element placing a label, if it has been placed.
+	pub fn position_of(&self, label: Label) -> Option<usize> {
+		self.elements.iter().position(|element| *element == Element::Label(label))
+	}
+
+	/// Iterate over the instructions along with their index in the element list.
+	pub fn instructions(&self) -> impl Iterator<Item = (usize, &Instruction<Label>)> {
+		self.elements.iter().enumerate().filter_map(|(index, element)| match element {
+			Element::Instruction(instruction) => Some((index, instruction)),
+			Element::Label(_) => None,
+		})
+	}
+
+	/// Lay the instructions out again and write them, together with every table referring to
+	/// code positions, back into `code`.
+	///
+	/// Branches whose displacement no longer fits 16 bits are widened: `goto` and `jsr` become
+	/// `goto_w` and `jsr_w`, and conditional branches are inverted around a `goto_w`. The latter
+	/// introduces new branch targets, so it is refused for code carrying a StackMapTable.
+	/// Attributes are only rewritten if `code` already has them, as naming a new one needs the
+	/// constant pool: line numbers or local variables without a table to hold them are an error.
+	/// The length of the enclosing Code attribute is left to the caller (see `Code::attribute_length`).
+	pub fn commit(&self, code: &mut Code) -> Result<(), EncodeError> {
+		let has = |table: fn(&AttributeInfo) -> bool| code.attributes.iter().any(|attribute| table(&attribute.attribute_info));
+		let missing = if !self.lines.is_empty() && !has(|info| matches!(info, AttributeInfo::LineNumberTable(_))) {
+			Some("LineNumberTable")
+		} else if !self.local_variables.is_empty() && !has(|info| matches!(info, AttributeInfo::LocalVariableTable(_))) {
+			Some("LocalVariableTable")
+		} else if !self.local_variable_types.is_empty() && !has(|info| matches!(info, AttributeInfo::LocalVariableTypeTable(_))) {
+			Some("LocalVariableTypeTable")
+		} else {
+			None
+		};
+		if let Some(name) = missing {
+			return Err(EncodeError { msg: format!("the code has no {} to hold the entries of the list", name) });
+		}
+		let has_frames = has(|info| matches!(info, AttributeInfo::StackMapTable(_)));
+		let mut widened: HashSet<usize> = HashSet::new();
+		let (positions, offsets) = loop {
+			let (positions, offsets, code_length) = self.layout(&widened);
+			if code_length > MAX_CODE_LENGTH {
+				return Err(EncodeError { msg: format!("code length {} exceeds {}", code_length, MAX_CODE_LENGTH) });
+			}
+			let mut changed = false;
+			for (index, element) in self.elements.iter().enumerate() {
+				if let Element::Instruction(Instruction::Branch { opcode, target }) = element {
+					if instruction::is_wide_branch(*opcode) || widened.contains(&index) {
+						continue;
+					}
+					let displacement = i64::from(Self::position(&positions, *target)?) - i64::from(offsets[index]);
+					if i16::try_from(displacement).is_err() {
+						if has_frames && !matches!(opcode, Opcode::Goto | Opcode::Jsr) {
+							return Err(EncodeError { msg: format!("{} at {} is out of range and the StackMapTable would need recomputing", opcode, offsets[index]) });
+						}
+						widened.insert(index);
+						changed = true;
+					}
+				}
+			}
+			if !changed {
+				break (positions, offsets);
+			}
+		};
+
+		let mut bytes: Vec<u8> = Vec::new();
+		for (index, element) in self.elements.iter().enumerate() {
+			let Element::Instruction(instruction) = element else { continue };
+			let pc = offsets[index];
+			let resolved = instruction.try_map_targets(|label| Self::position(&positions, *label))?;
+			match resolved {
+				Instruction::Branch { opcode, target } if widened.contains(&index) => match opcode {
+					Opcode::Goto => Instruction::Branch { opcode: Opcode::GotoW, target }.encode(pc, &mut bytes),
+					Opcode::Jsr => Instruction::Branch { opcode: Opcode::JsrW, target }.encode(pc, &mut bytes),
+					condition => Instruction::Branch { opcode: Self::invert(condition), target: pc + 8 }.encode(pc, &mut bytes)
+						.and_then(|_| Instruction::Branch { opcode: Opcode::GotoW, target }.encode(pc + 3, &mut bytes)),
+				},
+				other => other.encode(pc, &mut bytes),
+			}?;
+		}
+
+		let pc = |label: Label| -> Result<u16, EncodeError> { Ok(Self::position(&positions, label)? as u16) };
+		let length = |variable: &LocalVariableRange| -> Result<u16, EncodeError> {
+			pc(variable.end)?.checked_sub(pc(variable.start)?)
+				.ok_or_else(|| EncodeError { msg: format!("local variable {} in slot {} ends before it starts", variable.name_index, variable.index) })
+		};
+		code.handlers = self.handlers.iter().map(|handler| Ok(ExceptionHandler {
+			start_pc: pc(handler.start)?,
+			end_pc: pc(handler.end)?,
+			handler_pc: pc(handler.handler)?,
+			catch_type_index: handler.catch_type_index,
+		})).collect::<Result<Vec<_>, EncodeError>>()?;
+		code.handler_count = code.handlers.len() as u16;
+		code.code_length = bytes.len() as u32;
+		code.code = bytes;
+
+		for attribute in code.attributes.iter_mut() {
+			match &mut attribute.attribute_info {
+				AttributeInfo::LineNumberTable(table) => {
+					table.lines = self.lines.iter()
+						.map(|line| Ok(Line { start_pc: pc(line.start)?, line_number: line.line_number }))
+						.collect::<Result<Vec<_>, EncodeError>>()?;
+					table.table_length = table.lines.len() as u16;
+					attribute.length = 2 + 4 * u32::from(table.table_length);
+				}
+				AttributeInfo::LocalVariableTable(table) => {
+					table.local_variables = self.local_variables.iter()
+						.map(|variable| Ok(LocalVariable {
+							start_pc: pc(variable.start)?,
+							length: length(variable)?,
+							name_index: variable.name_index,
+							descriptor_index: variable.descriptor_index,
+							index: variable.index,
+						}))
+						.collect::<Result<Vec<_>, EncodeError>>()?;
+					table.local_variable_table_length = table.local_variables.len() as u16;
+					attribute.length = 2 + 10 * u32::from(table.local_variable_table_length);
+				}
+				AttributeInfo::LocalVariableTypeTable(table) => {
+					table.local_variable_types = self.local_variable_types.iter()
+						.map(|variable| Ok(LocalVariableType {
+							start_pc: pc(variable.start)?,
+							length: length(variable)?,
+							name_index: variable.name_index,
+							signature_index: variable.descriptor_index,
+							index: variable.index,
+						}))
+						.collect::<Result<Vec<_>, EncodeError>>()?;
+					table.local_variable_type_table_length = table.local_variable_types.len() as u16;
+					attribute.length = 2 + 10 * u32::from(table.local_variable_type_table_length);
+				}
+				AttributeInfo::StackMapTable(table) => {
+					let mut frames: Vec<(u16, StackMapFrame)> = Vec::new();
+					for frame in &self.frames {
+						frames.push((pc(frame.label)?, self.relocate_uninitialized(frame.frame.clone(), &positions)?));
+					}
+					frames.sort_by_key(|(offset, _)| *offset);
+					let mut previous: Option<u16> = None;
+					table.entries.clear();
+					for (offset, frame) in frames {
+						let delta = match previous {
+							None => offset,
+							Some(previous) if offset > previous => offset - previous - 1,
+							Some(_) => return Err(EncodeError { msg: format!("more than one stack map frame at {}", offset) }),
+						};
+						previous = Some(offset);
+						table.entries.push(frame.with_offset_delta(delta));
+					}
+					table.number_of_entries = table.entries.len() as u16;
+					let mut encoded = binrw::io::Cursor::new(Vec::new());
+					table.write_options(&mut encoded, binrw::Endian::Big, ()).map_err(|e| EncodeError { msg: e.to_string() })?;
+					attribute.length = encoded.into_inner().len() as u32;
+				}
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+
+	/// Assign an offset to every element, returning label positions, per-element offsets and the
+	/// total code length.
+	fn layout(&self, widened: &HashSet<usize>) -> (HashMap<Label, u32>, Vec<u32>, u32) {
+		let mut positions: HashMap<Label, u32> = HashMap::new();
+		let mut offsets: Vec<u32> = Vec::with_capacity(self.elements.len());
+		let mut pc: u32 = 0;
+		for (index, element) in self.elements.iter().enumerate() {
+			offsets.push(pc);
+			match element {
+				Element::Label(label) => { positions.insert(*label, pc); }
+				Element::Instruction(instruction) => {
+					pc += match instruction {
+						Instruction::Branch { opcode: Opcode::Goto | Opcode::Jsr, .. } if widened.contains(&index) => 5,
+						Instruction::Branch { .. } if widened.contains(&index) => 8,
+						other => other.encoded_length(pc),
+					};
+				}
+			}
+		}
+		(positions, offsets, pc)
+	}
+
+	fn position(positions: &HashMap<Label, u32>, label: Label) -> Result<u32, EncodeError> {
+		positions.get(&label).copied().ok_or_else(|| EncodeError { msg: format!("label {} is not placed", label.0) })
+	}
+
+	fn relocate_uninitialized(&self, mut frame: StackMapFrame, positions: &HashMap<Label, u32>) -> Result<StackMapFrame, EncodeError> {
+		let fresh: HashMap<u16, Label> = self.fresh_uninitialized.iter().map(|(label, offset)| (*offset, *label)).collect();
+		for verification_type in frame.verification_types_mut() {
+			if let VerificationTypeInfo::UninitializedVariableInfo(info) = verification_type
+				&& let Some(label) = self.uninitialized.get(&info.offset).or_else(|| fresh.get(&info.offset)) {
+				*info = UninitializedVariableInfo { offset: Self::position(positions, *label)? as u16 };
+			}
+		}
+		Ok(frame)
+	}
+
+	/// The conditional branch taken exactly when `opcode` is not.
+	fn invert(opcode: Opcode) -> Opcode {
+		match opcode {
+			Opcode::IfEq => Opcode::IfNe,
+			Opcode::IfNe => Opcode::IfEq,
+			Opcode::IfLt => Opcode::IfGe,
+			Opcode::IfGe => Opcode::IfLt,
+			Opcode::IfGt => Opcode::IfLe,
+			Opcode::IfLe => Opcode::IfGt,
+			Opcode::IfICmpEq => Opcode::IfICmpNe,
+			Opcode::IfICmpNe => Opcode::IfICmpEq,
+			Opcode::IfICmpLt => Opcode::IfICmpGe,
+			Opcode::IfICmpGe => Opcode::IfICmpLt,
+			Opcode::IfICmpGt => Opcode::IfICmpLe,
+			Opcode::IfICmpLe => Opcode::IfICmpGt,
+			Opcode::IfACmpEq => Opcode::IfACmpNe,
+			Opcode::IfACmpNe => Opcode::IfACmpEq,
+			Opcode::IfNull => Opcode::IfNonNull,
+			Opcode::IfNonNull => Opcode::IfNull,
+			other => other,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::File;
+
+	use crate::{
+		class::{
+			attribute::{AttributeInfo, Code},
+			class::Class,
+			constant_pool::ConstantPoolItem,
+			verification::VerificationTypeInfo},
+		isa::{
+			instruction::{self, Instruction},
+			instruction_list::{Element, InstructionList},
+			opcode::Opcode},
+	};
+
+	const CLASS_FILE_PATH: &str = "tests/resources/Branches.class";
+
+	fn get_code(method_name: &str) -> Code {
+		let clazz = Class::new(File::open(CLASS_FILE_PATH).expect("Couldn't access class file"));
+		for method in clazz.methods.methods {
+			let name = match clazz.constant_pool.get(&method.name_index) {
+				Some(ConstantPoolItem::Utf8(utf8)) => utf8.to_string(),
+				_ => panic!("Expected method name index to point to a Utf8 constant"),
+			};
+			if name != method_name {
+				continue;
+			}
+			for attribute in method.attributes {
+				if let AttributeInfo::Code(code) = attribute.attribute_info {
+					return code;
+				}
+			}
+		}
+		panic!("No code for method {}", method_name);
+	}
+
+	fn nop() -> Element {
+		Element::Instruction(Instruction::Plain { opcode: Opcode::Nop, operands: vec![] })
+	}
+
+	fn line_starts(code: &Code) -> Vec<u16> {
+		code.attributes.iter().find_map(|attribute| match &attribute.attribute_info {
+			AttributeInfo::LineNumberTable(table) => Some(table.lines.iter().map(|line| line.start_pc).collect()),
+			_ => None,
+		}).unwrap()
+	}
+
+	#[test]
+	fn test_unchanged_round_trip() {
+		for name in ["<init>", "loop", "dense", "sparse", "guarded", "choose"] {
+			let original = get_code(name);
+			let mut code = original.clone();
+			InstructionList::from_code(&code).unwrap().commit(&mut code).unwrap();
+			assert_eq!(code, original, "method {}", name);
+		}
+	}
+
+	#[test]
+	fn test_insert_at_entry() {
+		let original = get_code("loop");
+		let mut code = original.clone();
+		let mut list = InstructionList::from_code(&code).unwrap();
+		list.insert_all(0, vec![nop(), nop(), nop()]);
+		list.commit(&mut code).unwrap();
+
+		assert_eq!(code.code_length, original.code_length + 3);
+		assert_eq!(code.attribute_length(), original.attribute_length() + 3);
+		let instructions = instruction::decode(&code.code).unwrap();
+		assert!(instructions.contains(&(9, Instruction::Branch { opcode: Opcode::IfICmpGe, target: 22 })));
+		assert!(instructions.contains(&(19, Instruction::Branch { opcode: Opcode::Goto, target: 7 })));
+		let shifted: Vec<u16> = line_starts(&original).iter().map(|start| start + 3).collect();
+		assert_eq!(line_starts(&code), shifted);
+	}
+
+	#[test]
+	fn test_switch_padding() {
+		let mut code = get_code("dense");
+		let mut list = InstructionList::from_code(&code).unwrap();
+		list.insert(0, nop());
+		list.commit(&mut code).unwrap();
+
+		let instructions = instruction::decode(&code.code).unwrap();
+		let Instruction::TableSwitch { default, low, targets } = &instructions[2].1 else { panic!("expected tableswitch") };
+		assert_eq!(instructions[2].0, 2);
+		assert_eq!(*low, 1);
+		for target in targets.iter().chain(std::iter::once(default)) {
+			assert!(instructions.iter().any(|(pc, _)| pc == target));
+		}
+		assert_eq!(code.code_length, get_code("dense").code_length);
+	}
+
+	#[test]
+	fn test_unplaced_and_reversed_labels() {
+		let mut code = get_code("dense");
+		let mut list = InstructionList::from_code(&code).unwrap();
+		let unplaced = list.new_label();
+		for element in list.elements.iter_mut() {
+			if let Element::Instruction(Instruction::TableSwitch { targets, .. }) = element {
+				targets[0] = unplaced;
+			}
+		}
+		assert_eq!(list.commit(&mut code.clone()).unwrap_err().msg, format!("label {} is not placed", unplaced.0));
+
+		let mut list = InstructionList::from_code(&code).unwrap();
+		let variable = (0..list.local_variables.len())
+			.find(|index| list.position_of(list.local_variables[*index].start) < list.position_of(list.local_variables[*index].end))
+			.unwrap();
+		let variable = &mut list.local_variables[variable];
+		std::mem::swap(&mut variable.start, &mut variable.end);
+		assert!(list.commit(&mut code).unwrap_err().msg.ends_with("ends before it starts"));
+	}
+
+	#[test]
+	fn test_switch_ranges() {
+		let code = get_code("dense");
+		for (low, count) in [(1, 0), (i32::MAX, 2)] {
+			let mut list = InstructionList::from_code(&code).unwrap();
+			for element in list.elements.iter_mut() {
+				if let Element::Instruction(Instruction::TableSwitch { low: switch_low, targets, .. }) = element {
+					*switch_low = low;
+					targets.resize(count, targets[0]);
+				}
+			}
+			assert_eq!(list.commit(&mut code.clone()).unwrap_err().msg, format!("tableswitch at 1 has {} targets from low {}", count, low));
+		}
+	}
+
+	#[test]
+	fn test_missing_tables() {
+		let mut code = get_code("loop");
+		let list = InstructionList::from_code(&code).unwrap();
+		code.attributes.retain(|attribute| !matches!(attribute.attribute_info, AttributeInfo::LineNumberTable(_)));
+		assert_eq!(list.commit(&mut code.clone()).unwrap_err().msg, "the code has no LineNumberTable to hold the entries of the list");
+
+		let mut list = InstructionList::from_code(&code).unwrap();
+		list.local_variable_types.push(list.local_variables[0].clone());
+		assert_eq!(list.commit(&mut code).unwrap_err().msg, "the code has no LocalVariableTypeTable to hold the entries of the list");
+	}
+
+	#[test]
+	fn test_uninitialized_keys() {
+		let code = get_code("choose");
+		let mut list = InstructionList::from_code(&code).unwrap();
+		let original = *list.uninitialized.values().next().unwrap();
+		let (first, second) = (list.new_label(), list.new_label());
+		assert_eq!(list.uninitialized(original).unwrap().offset, *list.uninitialized.keys().next().unwrap());
+		assert_eq!(list.uninitialized(first).unwrap().offset, u16::MAX);
+		assert_eq!(list.uninitialized(second).unwrap().offset, u16::MAX - 1);
+		assert_eq!(list.uninitialized(first).unwrap().offset, u16::MAX);
+	}
+
+	#[test]
+	fn test_goto_widening() {
+		let original = get_code("loop");
+		let mut code = original.clone();
+		let mut list = InstructionList::from_code(&code).unwrap();
+		// Pad the loop header between its label and the if_icmpge, which only the goto spans.
+		let header = list.instructions().find_map(|(_, instruction)| match instruction {
+			Instruction::Branch { opcode: Opcode::Goto, target } => Some(*target),
+			_ => None,
+		}).unwrap();
+		let header = list.position_of(header).unwrap();
+		list.insert_all(header + 1, vec![nop(); 40000]);
+		list.commit(&mut code).unwrap();
+
+		let instructions = instruction::decode(&code.code).unwrap();
+		let (_, back_edge) = instructions.iter().find(|(_, instruction)| instruction.opcode() == Opcode::GotoW).unwrap();
+		assert_eq!(*back_edge, Instruction::Branch { opcode: Opcode::GotoW, target: 4 });
+		assert!(instructions.iter().all(|(_, instruction)| instruction.opcode() != Opcode::Goto));
+		assert_eq!(code.code_length, original.code_length + 40000 + 2);
+	}
+
+	#[test]
+	fn test_conditional_widening() {
+		let mut code = get_code("loop");
+		let mut list = InstructionList::from_code(&code).unwrap();
+		let body = list.elements.iter().position(|element| matches!(element, Element::Instruction(Instruction::Branch { opcode: Opcode::IfICmpGe, .. }))).unwrap();
+		list.insert_all(body + 1, vec![nop(); 40000]);
+		assert!(list.commit(&mut code.clone()).is_err());
+
+		code.attributes.retain(|attribute| !matches!(attribute.attribute_info, AttributeInfo::StackMapTable(_)));
+		list.commit(&mut code).unwrap();
+		let instructions = instruction::decode(&code.code).unwrap();
+		assert_eq!(instructions[6], (6, Instruction::Branch { opcode: Opcode::IfICmpLt, target: 14 }));
+		assert_eq!(instructions[7], (9, Instruction::Branch { opcode: Opcode::GotoW, target: 40026 }));
+	}
+
+	#[test]
+	fn test_handlers_and_frames() {
+		let mut code = get_code("guarded");
+		let mut list = InstructionList::from_code(&code).unwrap();
+		list.insert(0, nop());
+		list.commit(&mut code).unwrap();
+		let handler = &code.handlers[0];
+		assert_eq!((handler.start_pc, handler.end_pc, handler.handler_pc), (1, 4, 5));
+
+		let mut code = get_code("choose");
+		let mut list = InstructionList::from_code(&code).unwrap();
+		list.insert(0, nop());
+		list.commit(&mut code).unwrap();
+		let table = code.attributes.iter().find_map(|attribute| match &attribute.attribute_info {
+			AttributeInfo::StackMapTable(table) => Some(table.clone()),
+			_ => None,
+		}).unwrap();
+		let mut uninitialized = Vec::new();
+		for mut frame in table.entries {
+			for verification_type in frame.verification_types_mut() {
+				if let VerificationTypeInfo::UninitializedVariableInfo(info) = verification_type {
+					uninitialized.push(info.offset);
+				}
+			}
+		}
+		assert!(!uninitialized.is_empty());
+		assert!(uninitialized.iter().all(|offset| *offset == 1));
+	}
+}
@@ -0,0 +1,493 @@
+use crate::{
+	class::constant_pool::ConstantTag,
+	isa::opcode::Opcode,
+};
+
+use self::OperandFormat::*;
+
+/// The layout of the operands following an opcode in the code array (JVMS17 6.5).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OperandFormat {
+	NoOperands,
+	/// A signed byte, as pushed by `bipush`.
+	Byte,
+	/// A signed 16-bit value, as pushed by `sipush`.
+	Short,
+	/// The array type code of `newarray` (JVMS17 Table 6.5.newarray-A).
+	ArrayType,
+	/// A local variable index, one byte wide or two when modified by `wide`.
+	Local,
+	/// The local variable index and signed increment of `iinc`, each widened to two bytes by `wide`.
+	LocalIncrement,
+	/// A one-byte constant pool index, used only by `ldc`.
+	NarrowConstant,
+	/// A two-byte constant pool index.
+	Constant,
+	/// A two-byte constant pool index, an argument count and a zero byte.
+	InvokeInterface,
+	/// A two-byte constant pool index followed by two zero bytes.
+	InvokeDynamic,
+	/// A two-byte constant pool index and the number of dimensions to allocate.
+	MultiANewArray,
+	/// A signed 16-bit branch offset.
+	Branch,
+	/// A signed 32-bit branch offset.
+	WideBranch,
+	/// Padding to a 4-byte boundary, then a default offset and a range of jump offsets.
+	TableSwitch,
+	/// Padding to a 4-byte boundary, then a default offset and sorted match-offset pairs.
+	LookupSwitch,
+	/// The modified opcode followed by its widened operands.
+	Wide,
+}
+
+impl OperandFormat {
+
+	/// The number of operand bytes, or `None` for the variable-length switches and `wide`.
+	pub fn length(&self) -> Option<usize> {
+		match self {
+			NoOperands => Some(0),
+			Byte | ArrayType | Local | NarrowConstant => Some(1),
+			Short | LocalIncrement | Constant | Branch => Some(2),
+			MultiANewArray => Some(3),
+			InvokeInterface | InvokeDynamic | WideBranch => Some(4),
+			TableSwitch | LookupSwitch | Wide => None,
+		}
+	}
+
+	/// Whether the operands start with a constant pool index.
+	pub fn has_constant(&self) -> bool {
+		matches!(self, NarrowConstant | Constant | InvokeInterface | InvokeDynamic | MultiANewArray)
+	}
+}
+
+/// A value an instruction takes from or leaves on the operand stack.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StackValue {
+	Int,
+	Long,
+	Float,
+	Double,
+	Reference,
+	ReturnAddress,
+	/// Any category 1 value, for `ldc` and the stack manipulation instructions.
+	Category1,
+	/// Any category 2 value, for `ldc2_w`.
+	Category2,
+	/// A single stack slot, holding either a category 1 value or half of a category 2 value, as
+	/// moved by `pop2` and the `dup2` forms.
+	Slot,
+}
+
+impl StackValue {
+
+	/// The number of stack slots the value occupies (JVMS17 2.11.1).
+	pub fn size(&self) -> usize {
+		match self {
+			StackValue::Long | StackValue::Double | StackValue::Category2 => 2,
+			_ => 1,
+		}
+	}
+}
+
+/// How an instruction changes the operand stack.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StackEffect {
+	/// Values are listed from the deepest to the top of the stack, as in the JVMS instruction
+	/// descriptions.
+	Fixed { pops: &'static [StackValue], pushes: &'static [StackValue] },
+	/// The effect depends on a descriptor (field access, invocation, `multianewarray`) or on the
+	/// modified instruction (`wide`).
+	Variable,
+}
+
+impl StackEffect {
+
+	/// The number of slots popped and pushed, if the effect is fixed.
+	pub fn slots(&self) -> Option<(usize, usize)> {
+		match self {
+			StackEffect::Fixed { pops, pushes } => Some((
+				pops.iter().map(StackValue::size).sum(),
+				pushes.iter().map(StackValue::size).sum())),
+			StackEffect::Variable => None,
+		}
+	}
+}
+
+/// Where control goes after an instruction completes normally.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ControlFlow {
+	/// To the next instruction.
+	Next,
+	/// To the branch target or the next instruction.
+	Branch,
+	/// Unconditionally to the branch target.
+	Goto,
+	/// To one of the switch targets.
+	Switch,
+	/// To a subroutine, which returns to the next instruction (`jsr`, `jsr_w`).
+	Subroutine,
+	/// To the return address held in a local variable (`ret`).
+	SubroutineReturn,
+	/// Out of the method.
+	Return,
+	/// To an exception handler or out of the method (`athrow`).
+	Throw,
+}
+
+impl ControlFlow {
+
+	/// Whether the next instruction may execute after this one.
+	pub fn falls_through(&self) -> bool {
+		matches!(self, ControlFlow::Next | ControlFlow::Branch | ControlFlow::Subroutine)
+	}
+}
+
+/// Static properties of an opcode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OpcodeInfo {
+	pub opcode: Opcode,
+	pub mnemonic: &'static str,
+	pub operands: OperandFormat,
+	pub stack: StackEffect,
+	pub control_flow: ControlFlow,
+	/// Whether the instruction may raise an exception, including linkage errors during resolution
+	/// and `IllegalMonitorStateException` from the return instructions.
+	pub can_throw: bool,
+	/// The constant kinds the constant pool operand may refer to, empty if there is none.
+	pub constants: &'static [ConstantTag],
+}
+
+impl OpcodeInfo {
+
+	const fn fixed(opcode: Opcode, mnemonic: &'static str, operands: OperandFormat, pops: &'static [StackValue], pushes: &'static [StackValue]) -> OpcodeInfo {
+		OpcodeInfo {
+			opcode,
+			mnemonic,
+			operands,
+			stack: StackEffect::Fixed { pops, pushes },
+			control_flow: ControlFlow::Next,
+			can_throw: false,
+			constants: &[],
+		}
+	}
+
+	const fn variable(opcode: Opcode, mnemonic: &'static str, operands: OperandFormat) -> OpcodeInfo {
+		OpcodeInfo { stack: StackEffect::Variable, ..OpcodeInfo::fixed(opcode, mnemonic, operands, &[], &[]) }
+	}
+
+	const fn flow(self, control_flow: ControlFlow) -> OpcodeInfo {
+		OpcodeInfo { control_flow, ..self }
+	}
+
+	const fn throws(self) -> OpcodeInfo {
+		OpcodeInfo { can_throw: true, ..self }
+	}
+
+	const fn constants(self, constants: &'static [ConstantTag]) -> OpcodeInfo {
+		OpcodeInfo { constants, ..self }
+	}
+
+	/// Whether the instruction may transfer control somewhere other than the next instruction.
+	pub fn is_branch(&self) -> bool {
+		matches!(self.control_flow, ControlFlow::Branch | ControlFlow::Goto | ControlFlow::Switch | ControlFlow::Subroutine)
+	}
+
+	pub fn is_return(&self) -> bool {
+		self.control_flow == ControlFlow::Return
+	}
+
+	/// Whether the instruction is the last of its basic block, not counting edges to exception handlers.
+	pub fn ends_basic_block(&self) -> bool {
+		self.control_flow != ControlFlow::Next
+	}
+}
+
+impl Opcode {
+
+	/// The static properties of this opcode.
+	///
+	/// For `wide` these describe only the prefix; the properties of the modified instruction are
+	/// those of the opcode that follows it.
+	pub fn info(self) -> &'static OpcodeInfo {
+		let byte = usize::from(u8::from(self));
+		// The table skips the unassigned opcodes between breakpoint and impdep1.
+		let index = if byte <= usize::from(u8::from(Opcode::Breakpoint)) { byte } else { byte - 0xfe + 0xcb };
+		&OPCODES[index]
+	}
+}
+
+const I: StackValue = StackValue::Int;
+const J: StackValue = StackValue::Long;
+const F: StackValue = StackValue::Float;
+const D: StackValue = StackValue::Double;
+const A: StackValue = StackValue::Reference;
+const R: StackValue = StackValue::ReturnAddress;
+const C1: StackValue = StackValue::Category1;
+const C2: StackValue = StackValue::Category2;
+const W: StackValue = StackValue::Slot;
+
+/// Constants loadable by `ldc` and `ldc_w` (JVMS17 4.4).
+const LOADABLE: &[ConstantTag] = &[
+	ConstantTag::Integer, ConstantTag::Float, ConstantTag::String, ConstantTag::Class,
+	ConstantTag::MethodType, ConstantTag::MethodHandle, ConstantTag::Dynamic];
+const LOADABLE_WIDE: &[ConstantTag] = &[ConstantTag::Long, ConstantTag::Double, ConstantTag::Dynamic];
+const CLASS: &[ConstantTag] = &[ConstantTag::Class];
+const FIELD: &[ConstantTag] = &[ConstantTag::FieldRef];
+const METHOD: &[ConstantTag] = &[ConstantTag::MethodRef];
+/// `invokespecial` and `invokestatic` may name interface methods from class file version 52.
+const ANY_METHOD: &[ConstantTag] = &[ConstantTag::MethodRef, ConstantTag::InterfaceMethodRef];
+const INTERFACE_METHOD: &[ConstantTag] = &[ConstantTag::InterfaceMethodRef];
+const CALL_SITE: &[ConstantTag] = &[ConstantTag::InvokeDynamic];
+
+/// Every opcode in order of its byte value (JVMS17 7).
+static OPCODES: [OpcodeInfo; 205] = [
+	OpcodeInfo::fixed(Opcode::Nop, "nop", NoOperands, &[], &[]),
+	OpcodeInfo::fixed(Opcode::AConstNull, "aconst_null", NoOperands, &[], &[A]),
+	OpcodeInfo::fixed(Opcode::IConstM1, "iconst_m1", NoOperands, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::IConst0, "iconst_0", NoOperands, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::IConst1, "iconst_1", NoOperands, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::IConst2, "iconst_2", NoOperands, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::IConst3, "iconst_3", NoOperands, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::IConst4, "iconst_4", NoOperands, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::IConst5, "iconst_5", NoOperands, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::LConst0, "lconst_0", NoOperands, &[], &[J]),
+	OpcodeInfo::fixed(Opcode::LConst1, "lconst_1", NoOperands, &[], &[J]),
+	OpcodeInfo::fixed(Opcode::FConst0, "fconst_0", NoOperands, &[], &[F]),
+	OpcodeInfo::fixed(Opcode::FConst1, "fconst_1", NoOperands, &[], &[F]),
+	OpcodeInfo::fixed(Opcode::FConst2, "fconst_2", NoOperands, &[], &[F]),
+	OpcodeInfo::fixed(Opcode::DConst0, "dconst_0", NoOperands, &[], &[D]),
+	OpcodeInfo::fixed(Opcode::DConst1, "dconst_1", NoOperands, &[], &[D]),
+	OpcodeInfo::fixed(Opcode::BIpush, "bipush", Byte, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::SIpush, "sipush", Short, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::Ldc, "ldc", NarrowConstant, &[], &[C1]).throws().constants(LOADABLE),
+	OpcodeInfo::fixed(Opcode::LdcW, "ldc_w", Constant, &[], &[C1]).throws().constants(LOADABLE),
+	OpcodeInfo::fixed(Opcode::Ldc2W, "ldc2_w", Constant, &[], &[C2]).throws().constants(LOADABLE_WIDE),
+	OpcodeInfo::fixed(Opcode::ILoad, "iload", Local, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::LLoad, "lload", Local, &[], &[J]),
+	OpcodeInfo::fixed(Opcode::FLoad, "fload", Local, &[], &[F]),
+	OpcodeInfo::fixed(Opcode::DLoad, "dload", Local, &[], &[D]),
+	OpcodeInfo::fixed(Opcode::ALoad, "aload", Local, &[], &[A]),
+	OpcodeInfo::fixed(Opcode::ILoad0, "iload_0", NoOperands, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::ILoad1, "iload_1", NoOperands, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::ILoad2, "iload_2", NoOperands, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::ILoad3, "iload_3", NoOperands, &[], &[I]),
+	OpcodeInfo::fixed(Opcode::LLoad0, "lload_0", NoOperands, &[], &[J]),
+	OpcodeInfo::fixed(Opcode::LLoad1, "lload_1", NoOperands, &[], &[J]),
+	OpcodeInfo::fixed(Opcode::LLoad2, "lload_2", NoOperands, &[], &[J]),
+	OpcodeInfo::fixed(Opcode::LLoad3, "lload_3", NoOperands, &[], &[J]),
+	OpcodeInfo::fixed(Opcode::FLoad0, "fload_0", NoOperands, &[], &[F]),
+	OpcodeInfo::fixed(Opcode::FLoad1, "fload_1", NoOperands, &[], &[F]),
+	OpcodeInfo::fixed(Opcode::FLoad2, "fload_2", NoOperands, &[], &[F]),
+	OpcodeInfo::fixed(Opcode::FLoad3, "fload_3", NoOperands, &[], &[F]),
+	OpcodeInfo::fixed(Opcode::DLoad0, "dload_0", NoOperands, &[], &[D]),
+	OpcodeInfo::fixed(Opcode::DLoad1, "dload_1", NoOperands, &[], &[D]),
+	OpcodeInfo::fixed(Opcode::DLoad2, "dload_2", NoOperands, &[], &[D]),
+	OpcodeInfo::fixed(Opcode::DLoad3, "dload_3", NoOperands, &[], &[D]),
+	OpcodeInfo::fixed(Opcode::ALoad0, "aload_0", NoOperands, &[], &[A]),
+	OpcodeInfo::fixed(Opcode::ALoad1, "aload_1", NoOperands, &[], &[A]),
+	OpcodeInfo::fixed(Opcode::ALoad2, "aload_2", NoOperands, &[], &[A]),
+	OpcodeInfo::fixed(Opcode::ALoad3, "aload_3", NoOperands, &[], &[A]),
+	OpcodeInfo::fixed(Opcode::IALoad, "iaload", NoOperands, &[A, I], &[I]).throws(),
+	OpcodeInfo::fixed(Opcode::LALoad, "laload", NoOperands, &[A, I], &[J]).throws(),
+	OpcodeInfo::fixed(Opcode::FALoad, "faload", NoOperands, &[A, I], &[F]).throws(),
+	OpcodeInfo::fixed(Opcode::DALoad, "daload", NoOperands, &[A, I], &[D]).throws(),
+	OpcodeInfo::fixed(Opcode::AALoad, "aaload", NoOperands, &[A, I], &[A]).throws(),
+	OpcodeInfo::fixed(Opcode::BALoad, "baload", NoOperands, &[A, I], &[I]).throws(),
+	OpcodeInfo::fixed(Opcode::CALoad, "caload", NoOperands, &[A, I], &[I]).throws(),
+	OpcodeInfo::fixed(Opcode::SALoad, "saload", NoOperands, &[A, I], &[I]).throws(),
+	OpcodeInfo::fixed(Opcode::IStore, "istore", Local, &[I], &[]),
+	OpcodeInfo::fixed(Opcode::LStore, "lstore", Local, &[J], &[]),
+	OpcodeInfo::fixed(Opcode::FStore, "fstore", Local, &[F], &[]),
+	OpcodeInfo::fixed(Opcode::DStore, "dstore", Local, &[D], &[]),
+	OpcodeInfo::fixed(Opcode::AStore, "astore", Local, &[A], &[]),
+	OpcodeInfo::fixed(Opcode::IStore0, "istore_0", NoOperands, &[I], &[]),
+	OpcodeInfo::fixed(Opcode::IStore1, "istore_1", NoOperands, &[I], &[]),
+	OpcodeInfo::fixed(Opcode::IStore2, "istore_2", NoOperands, &[I], &[]),
+	OpcodeInfo::fixed(Opcode::IStore3, "istore_3", NoOperands, &[I], &[]),
+	OpcodeInfo::fixed(Opcode::LStore0, "lstore_0", NoOperands, &[J], &[]),
+	OpcodeInfo::fixed(Opcode::LStore1, "lstore_1", NoOperands, &[J], &[]),
+	OpcodeInfo::fixed(Opcode::LStore2, "lstore_2", NoOperands, &[J], &[]),
+	OpcodeInfo::fixed(Opcode::LStore3, "lstore_3", NoOperands, &[J], &[]),
+	OpcodeInfo::fixed(Opcode::FStore0, "fstore_0", NoOperands, &[F], &[]),
+	OpcodeInfo::fixed(Opcode::FStore1, "fstore_1", NoOperands, &[F], &[]),
+	OpcodeInfo::fixed(Opcode::FStore2, "fstore_2", NoOperands, &[F], &[]),
+	OpcodeInfo::fixed(Opcode::FStore3, "fstore_3", NoOperands, &[F], &[]),
+	OpcodeInfo::fixed(Opcode::DStore0, "dstore_0", NoOperands, &[D], &[]),
+	OpcodeInfo::fixed(Opcode::DStore1, "dstore_1", NoOperands, &[D], &[]),
+	OpcodeInfo::fixed(Opcode::DStore2, "dstore_2", NoOperands, &[D], &[]),
+	OpcodeInfo::fixed(Opcode::DStore3, "dstore_3", NoOperands, &[D], &[]),
+	OpcodeInfo::fixed(Opcode::AStore0, "astore_0", NoOperands, &[A], &[]),
+	OpcodeInfo::fixed(Opcode::AStore1, "astore_1", NoOperands, &[A], &[]),
+	OpcodeInfo::fixed(Opcode::AStore2, "astore_2", NoOperands, &[A], &[]),
+	OpcodeInfo::fixed(Opcode::AStore3, "astore_3", NoOperands, &[A], &[]),
+	OpcodeInfo::fixed(Opcode::IAStore, "iastore", NoOperands, &[A, I, I], &[]).throws(),
+	OpcodeInfo::fixed(Opcode::LAStore, "lastore", NoOperands, &[A, I, J], &[]).throws(),
+	OpcodeInfo::fixed(Opcode::FAStore, "fastore", NoOperands, &[A, I, F], &[]).throws(),
+	OpcodeInfo::fixed(Opcode::DAStore, "dastore", NoOperands, &[A, I, D], &[]).throws(),
+	OpcodeInfo::fixed(Opcode::AAStore, "aastore", NoOperands, &[A, I, A], &[]).throws(),
+	OpcodeInfo::fixed(Opcode::BAStore, "bastore", NoOperands, &[A, I, I], &[]).throws(),
+	OpcodeInfo::fixed(Opcode::CAStore, "castore", NoOperands, &[A, I, I], &[]).throws(),
+	OpcodeInfo::fixed(Opcode::SAStore, "sastore", NoOperands, &[A, I, I], &[]).throws(),
+	OpcodeInfo::fixed(Opcode::Pop, "pop", NoOperands, &[C1], &[]),
+	OpcodeInfo::fixed(Opcode::Pop2, "pop2", NoOperands, &[W, W], &[]),
+	OpcodeInfo::fixed(Opcode::Dup, "dup", NoOperands, &[C1], &[C1, C1]),
+	OpcodeInfo::fixed(Opcode::DupX1, "dup_x1", NoOperands, &[C1, C1], &[C1, C1, C1]),
+	OpcodeInfo::fixed(Opcode::DupX2, "dup_x2", NoOperands, &[W, W, C1], &[C1, W, W, C1]),
+	OpcodeInfo::fixed(Opcode::Dup2, "dup2", NoOperands, &[W, W], &[W, W, W, W]),
+	OpcodeInfo::fixed(Opcode::Dup2X1, "dup2_x1", NoOperands, &[C1, W, W], &[W, W, C1, W, W]),
+	OpcodeInfo::fixed(Opcode::Dup2X2, "dup2_x2", NoOperands, &[W, W, W, W], &[W, W, W, W, W, W]),
+	OpcodeInfo::fixed(Opcode::Swap, "swap", NoOperands, &[C1, C1], &[C1, C1]),
+	OpcodeInfo::fixed(Opcode::IAdd, "iadd", NoOperands, &[I, I], &[I]),
+	OpcodeInfo::fixed(Opcode::LAdd, "ladd", NoOperands, &[J, J], &[J]),
+	OpcodeInfo::fixed(Opcode::FAdd, "fadd", NoOperands, &[F, F], &[F]),
+	OpcodeInfo::fixed(Opcode::DAdd, "dadd", NoOperands, &[D, D], &[D]),
+	OpcodeInfo::fixed(Opcode::ISub, "isub", NoOperands, &[I, I], &[I]),
+	OpcodeInfo::fixed(Opcode::LSub, "lsub", NoOperands, &[J, J], &[J]),
+	OpcodeInfo::fixed(Opcode::FSub, "fsub", NoOperands, &[F, F], &[F]),
+	OpcodeInfo::fixed(Opcode::DSub, "dsub", NoOperands, &[D, D], &[D]),
+	OpcodeInfo::fixed(Opcode::IMul, "imul", NoOperands, &[I, I], &[I]),
+	OpcodeInfo::fixed(Opcode::LMul, "lmul", NoOperands, &[J, J], &[J]),
+	OpcodeInfo::fixed(Opcode::FMul, "fmul", NoOperands, &[F, F], &[F]),
+	OpcodeInfo::fixed(Opcode::DMul, "dmul", NoOperands, &[D, D], &[D]),
+	OpcodeInfo::fixed(Opcode::IDiv, "idiv", NoOperands, &[I, I], &[I]).throws(),
+	OpcodeInfo::fixed(Opcode::LDiv, "ldiv", NoOperands, &[J, J], &[J]).throws(),
+	OpcodeInfo::fixed(Opcode::FDiv, "fdiv", NoOperands, &[F, F], &[F]),
+	OpcodeInfo::fixed(Opcode::DDiv, "ddiv", NoOperands, &[D, D], &[D]),
+	OpcodeInfo::fixed(Opcode::IRem, "irem", NoOperands, &[I, I], &[I]).throws(),
+	OpcodeInfo::fixed(Opcode::LRem, "lrem", NoOperands, &[J, J], &[J]).throws(),
+	OpcodeInfo::fixed(Opcode::FRem, "frem", NoOperands, &[F, F], &[F]),
+	OpcodeInfo::fixed(Opcode::DRem, "drem", NoOperands, &[D, D], &[D]),
+	OpcodeInfo::fixed(Opcode::INeg, "ineg", NoOperands, &[I], &[I]),
+	OpcodeInfo::fixed(Opcode::LNeg, "lneg", NoOperands, &[J], &[J]),
+	OpcodeInfo::fixed(Opcode::FNeg, "fneg", NoOperands, &[F], &[F]),
+	OpcodeInfo::fixed(Opcode::DNeg, "dneg", NoOperands, &[D], &[D]),
+	OpcodeInfo::fixed(Opcode::IShl, "ishl", NoOperands, &[I, I], &[I]),
+	OpcodeInfo::fixed(Opcode::LShl, "lshl", NoOperands, &[J, I], &[J]),
+	OpcodeInfo::fixed(Opcode::IShr, "ishr", NoOperands, &[I, I], &[I]),
+	OpcodeInfo::fixed(Opcode::LShr, "lshr", NoOperands, &[J, I], &[J]),
+	OpcodeInfo::fixed(Opcode::IUShr, "iushr", NoOperands, &[I, I], &[I]),
+	OpcodeInfo::fixed(Opcode::LUShr, "lushr", NoOperands, &[J, I], &[J]),
+	OpcodeInfo::fixed(Opcode::IAnd, "iand", NoOperands, &[I, I], &[I]),
+	OpcodeInfo::fixed(Opcode::LAnd, "land", NoOperands, &[J, J], &[J]),
+	OpcodeInfo::fixed(Opcode::IOr, "ior", NoOperands, &[I, I], &[I]),
+	OpcodeInfo::fixed(Opcode::LOr, "lor", NoOperands, &[J, J], &[J]),
+	OpcodeInfo::fixed(Opcode::IXor, "ixor", NoOperands, &[I, I], &[I]),
+	OpcodeInfo::fixed(Opcode::LXor, "lxor", NoOperands, &[J, J], &[J]),
+	OpcodeInfo::fixed(Opcode::IInc, "iinc", LocalIncrement, &[], &[]),
+	OpcodeInfo::fixed(Opcode::I2L, "i2l", NoOperands, &[I], &[J]),
+	OpcodeInfo::fixed(Opcode::I2F, "i2f", NoOperands, &[I], &[F]),
+	OpcodeInfo::fixed(Opcode::I2D, "i2d", NoOperands, &[I], &[D]),
+	OpcodeInfo::fixed(Opcode::L2I, "l2i", NoOperands, &[J], &[I]),
+	OpcodeInfo::fixed(Opcode::L2F, "l2f", NoOperands, &[J], &[F]),
+	OpcodeInfo::fixed(Opcode::L2D, "l2d", NoOperands, &[J], &[D]),
+	OpcodeInfo::fixed(Opcode::F2I, "f2i", NoOperands, &[F], &[I]),
+	OpcodeInfo::fixed(Opcode::F2L, "f2l", NoOperands, &[F], &[J]),
+	OpcodeInfo::fixed(Opcode::F2D, "f2d", NoOperands, &[F], &[D]),
+	OpcodeInfo::fixed(Opcode::D2I, "d2i", NoOperands, &[D], &[I]),
+	OpcodeInfo::fixed(Opcode::D2L, "d2l", NoOperands, &[D], &[J]),
+	OpcodeInfo::fixed(Opcode::D2F, "d2f", NoOperands, &[D], &[F]),
+	OpcodeInfo::fixed(Opcode::I2B, "i2b", NoOperands, &[I], &[I]),
+	OpcodeInfo::fixed(Opcode::I2C, "i2c", NoOperands, &[I], &[I]),
+	OpcodeInfo::fixed(Opcode::I2S, "i2s", NoOperands, &[I], &[I]),
+	OpcodeInfo::fixed(Opcode::LCmp, "lcmp", NoOperands, &[J, J], &[I]),
+	OpcodeInfo::fixed(Opcode::FCmpL, "fcmpl", NoOperands, &[F, F], &[I]),
+	OpcodeInfo::fixed(Opcode::FCmpG, "fcmpg", NoOperands, &[F, F], &[I]),
+	OpcodeInfo::fixed(Opcode::DCmpL, "dcmpl", NoOperands, &[D, D], &[I]),
+	OpcodeInfo::fixed(Opcode::DCmpG, "dcmpg", NoOperands, &[D, D], &[I]),
+	OpcodeInfo::fixed(Opcode::IfEq, "ifeq", Branch, &[I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfNe, "ifne", Branch, &[I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfLt, "iflt", Branch, &[I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfGe, "ifge", Branch, &[I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfGt, "ifgt", Branch, &[I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfLe, "ifle", Branch, &[I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfICmpEq, "if_icmpeq", Branch, &[I, I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfICmpNe, "if_icmpne", Branch, &[I, I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfICmpLt, "if_icmplt", Branch, &[I, I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfICmpGe, "if_icmpge", Branch, &[I, I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfICmpGt, "if_icmpgt", Branch, &[I, I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfICmpLe, "if_icmple", Branch, &[I, I], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfACmpEq, "if_acmpeq", Branch, &[A, A], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfACmpNe, "if_acmpne", Branch, &[A, A], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::Goto, "goto", Branch, &[], &[]).flow(ControlFlow::Goto),
+	OpcodeInfo::fixed(Opcode::Jsr, "jsr", Branch, &[], &[R]).flow(ControlFlow::Subroutine),
+	OpcodeInfo::fixed(Opcode::Ret, "ret", Local, &[], &[]).flow(ControlFlow::SubroutineReturn),
+	OpcodeInfo::fixed(Opcode::TableSwitch, "tableswitch", TableSwitch, &[I], &[]).flow(ControlFlow::Switch),
+	OpcodeInfo::fixed(Opcode::LookupSwitch, "lookupswitch", LookupSwitch, &[I], &[]).flow(ControlFlow::Switch),
+	OpcodeInfo::fixed(Opcode::IReturn, "ireturn", NoOperands, &[I], &[]).flow(ControlFlow::Return).throws(),
+	OpcodeInfo::fixed(Opcode::LReturn, "lreturn", NoOperands, &[J], &[]).flow(ControlFlow::Return).throws(),
+	OpcodeInfo::fixed(Opcode::FReturn, "freturn", NoOperands, &[F], &[]).flow(ControlFlow::Return).throws(),
+	OpcodeInfo::fixed(Opcode::DReturn, "dreturn", NoOperands, &[D], &[]).flow(ControlFlow::Return).throws(),
+	OpcodeInfo::fixed(Opcode::AReturn, "areturn", NoOperands, &[A], &[]).flow(ControlFlow::Return).throws(),
+	OpcodeInfo::fixed(Opcode::Return, "return", NoOperands, &[], &[]).flow(ControlFlow::Return).throws(),
+	OpcodeInfo::variable(Opcode::GetStatic, "getstatic", Constant).throws().constants(FIELD),
+	OpcodeInfo::variable(Opcode::PutStatic, "putstatic", Constant).throws().constants(FIELD),
+	OpcodeInfo::variable(Opcode::GetField, "getfield", Constant).throws().constants(FIELD),
+	OpcodeInfo::variable(Opcode::PutField, "putfield", Constant).throws().constants(FIELD),
+	OpcodeInfo::variable(Opcode::InvokeVirtual, "invokevirtual", Constant).throws().constants(METHOD),
+	OpcodeInfo::variable(Opcode::InvokeSpecial, "invokespecial", Constant).throws().constants(ANY_METHOD),
+	OpcodeInfo::variable(Opcode::InvokeStatic, "invokestatic", Constant).throws().constants(ANY_METHOD),
+	OpcodeInfo::variable(Opcode::InvokeInterface, "invokeinterface", InvokeInterface).throws().constants(INTERFACE_METHOD),
+	OpcodeInfo::variable(Opcode::InvokeDynamic, "invokedynamic", InvokeDynamic).throws().constants(CALL_SITE),
+	OpcodeInfo::fixed(Opcode::New, "new", Constant, &[], &[A]).throws().constants(CLASS),
+	OpcodeInfo::fixed(Opcode::NewArray, "newarray", ArrayType, &[I], &[A]).throws(),
+	OpcodeInfo::fixed(Opcode::ANewArray, "anewarray", Constant, &[I], &[A]).throws().constants(CLASS),
+	OpcodeInfo::fixed(Opcode::ArrayLength, "arraylength", NoOperands, &[A], &[I]).throws(),
+	OpcodeInfo::fixed(Opcode::AThrow, "athrow", NoOperands, &[A], &[]).flow(ControlFlow::Throw).throws(),
+	OpcodeInfo::fixed(Opcode::CheckCast, "checkcast", Constant, &[A], &[A]).throws().constants(CLASS),
+	OpcodeInfo::fixed(Opcode::InstanceOf, "instanceof", Constant, &[A], &[I]).throws().constants(CLASS),
+	OpcodeInfo::fixed(Opcode::MonitorEnter, "monitorenter", NoOperands, &[A], &[]).throws(),
+	OpcodeInfo::fixed(Opcode::MonitorExit, "monitorexit", NoOperands, &[A], &[]).throws(),
+	OpcodeInfo::variable(Opcode::Wide, "wide", Wide),
+	OpcodeInfo::variable(Opcode::MultiANewArray, "multianewarray", MultiANewArray).throws().constants(CLASS),
+	OpcodeInfo::fixed(Opcode::IfNull, "ifnull", Branch, &[A], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::IfNonNull, "ifnonnull", Branch, &[A], &[]).flow(ControlFlow::Branch),
+	OpcodeInfo::fixed(Opcode::GotoW, "goto_w", WideBranch, &[], &[]).flow(ControlFlow::Goto),
+	OpcodeInfo::fixed(Opcode::JsrW, "jsr_w", WideBranch, &[], &[R]).flow(ControlFlow::Subroutine),
+	OpcodeInfo::variable(Opcode::Breakpoint, "breakpoint", NoOperands),
+	OpcodeInfo::variable(Opcode::Impdep1, "impdep1", NoOperands),
+	OpcodeInfo::variable(Opcode::Impdep2, "impdep2", NoOperands),
+];
+
+#[cfg(test)]
+mod tests {
+	use strum::IntoEnumIterator;
+
+	use crate::isa::{
+		metadata::{ControlFlow, OperandFormat, StackEffect, StackValue},
+		opcode::Opcode};
+
+	#[test]
+	fn test_table_order() {
+		for opcode in Opcode::iter() {
+			assert_eq!(opcode.info().opcode, opcode);
+		}
+	}
+
+	#[test]
+	fn test_stack_effects() {
+		assert_eq!(Opcode::LAdd.info().stack.slots(), Some((4, 2)));
+		assert_eq!(Opcode::Dup2X1.info().stack.slots(), Some((3, 5)));
+		assert_eq!(Opcode::IAStore.info().stack, StackEffect::Fixed { pops: &[StackValue::Reference, StackValue::Int, StackValue::Int], pushes: &[] });
+		assert_eq!(Opcode::InvokeVirtual.info().stack, StackEffect::Variable);
+	}
+
+	#[test]
+	fn test_control_flow() {
+		assert!(Opcode::IfNull.info().is_branch());
+		assert!(Opcode::LookupSwitch.info().ends_basic_block());
+		assert!(!Opcode::Goto.info().control_flow.falls_through());
+		assert!(Opcode::AThrow.info().can_throw);
+		assert_eq!(Opcode::AThrow.info().control_flow, ControlFlow::Throw);
+		assert!(!Opcode::IAdd.info().can_throw && Opcode::IDiv.info().can_throw);
+		assert!(!Opcode::IInc.info().ends_basic_block());
+	}
+
+	#[test]
+	fn test_operands() {
+		assert_eq!(Opcode::GotoW.info().operands.length(), Some(4));
+		assert_eq!(Opcode::TableSwitch.info().operands.length(), None);
+		assert_eq!(Opcode::Ldc.info().operands, OperandFormat::NarrowConstant);
+		assert!(Opcode::MultiANewArray.info().operands.has_constant());
+	}
+}
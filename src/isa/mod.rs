@@ -1 +1,5 @@
-pub mod opcode;
\ No newline at end of file
+pub mod instruction;
+pub mod instruction_list;
+pub mod metadata;
+pub mod opcode;
+pub mod stack_map;
\ No newline at end of file
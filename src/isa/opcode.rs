@@ -1,23 +1,26 @@
 extern crate strum;
 
-use std::fmt;
-use std::sync::LazyLock;
+use std::{
+	collections::HashMap,
+	fmt,
+	str::FromStr,
+	sync::LazyLock};
 use num_enum::{
 	IntoPrimitive,
 	TryFromPrimitive};
-use regex::Regex;
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
+use thiserror::Error;
 
-static MATCH_XOP_N: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(?<type>[ILFDA])(?<operation>Const|Load|Store|)(?<index>[[:digit:]]|M1|Null)$").unwrap());
-static MATCH_DUPX_XX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(?<operation>Dup2{0,1})X(?<index>[[:digit:]])$").unwrap());
-static MATCH_IF_XCMP: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^If(?<type>[IA])(?<operation>Cmp[A-Za-z]{2})$").unwrap());
-static MATCH_X_W: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(?<operation>.*)W$").unwrap());
+static MNEMONICS: LazyLock<HashMap<&'static str, Opcode>> = LazyLock::new(|| Opcode::iter().map(|opcode| (opcode.info().mnemonic, opcode)).collect());
 
 #[derive(
 	Clone,
+	Copy,
 	Debug,
 	EnumIter,
 	Eq,
+	Hash,
 	IntoPrimitive,
 	PartialEq,
 	TryFromPrimitive)]
@@ -243,28 +246,38 @@ pub enum Opcode {
 
 impl fmt::Display for Opcode {
 	fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-		let constant_name = format!("{:?}", self);
-		let is_xload_n = MATCH_XOP_N.is_match(&constant_name);
-		let is_dupx_xx = MATCH_DUPX_XX.is_match(&constant_name);
-		let is_if_xcmp = MATCH_IF_XCMP.is_match(&constant_name);
-		let is_x_w = MATCH_X_W.is_match(&constant_name);
+		write!(formatter, "{}", self.info().mnemonic)
+	}
+}
+
+/// An unknown instruction mnemonic.
+#[derive(Debug, Error)]
+#[error("unknown mnemonic {0:?}")]
+pub struct UnknownMnemonic(pub String);
+
+impl FromStr for Opcode {
+	type Err = UnknownMnemonic;
+
+	/// Parse a mnemonic as written in the JVMS, e.g. `iconst_m1` or `invokevirtual`, ignoring case.
+	fn from_str(mnemonic: &str) -> Result<Opcode, UnknownMnemonic> {
+		MNEMONICS.get(mnemonic.to_ascii_lowercase().as_str()).copied().ok_or_else(|| UnknownMnemonic(mnemonic.to_string()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use strum::IntoEnumIterator;
 
-		let mnemonic: String = if is_xload_n {
-			let captures = MATCH_XOP_N.captures(&constant_name).unwrap();
-			format!("{}{}_{}", &captures["type"], &captures["operation"], &captures["index"])
-		} else if is_dupx_xx {
-			let captures = MATCH_DUPX_XX.captures(&constant_name).unwrap();
-			format!("{}_x{}", &captures["operation"], &captures["index"])
-		} else if is_if_xcmp {
-			let captures = MATCH_IF_XCMP.captures(&constant_name).unwrap();
-			format!("if_{}{}", &captures["type"], &captures["operation"])
-		} else if is_x_w {
-			let captures = MATCH_X_W.captures(&constant_name).unwrap();
-			format!("{}_W", &captures["operation"])
+	use crate::isa::opcode::Opcode;
+
+	#[test]
+	fn test_mnemonics() {
+		for opcode in Opcode::iter() {
+			assert_eq!(opcode.to_string().parse::<Opcode>().unwrap(), opcode);
 		}
-		else {
-			constant_name
-		};
-		return write!(formatter, "{}", mnemonic.to_ascii_lowercase());
+		assert_eq!(Opcode::IConstM1.to_string(), "iconst_m1");
+		assert_eq!(Opcode::IfACmpNe.to_string(), "if_acmpne");
+		assert_eq!("LDC2_W".parse::<Opcode>().unwrap(), Opcode::Ldc2W);
+		assert!("iload_4".parse::<Opcode>().is_err());
 	}
-}
\ No newline at end of file
+}
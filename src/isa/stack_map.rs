@@ -0,0 +1,764 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+	class::{
+		access::MethodAccessPropertyFlags,
+		attribute::{AppendFrame, ChopFrame, FullFrame, SameFrame, SameLocals1StackItemFrame, StackMapFrame},
+		constant_pool::ConstantPoolItem,
+		constant_pool_builder::ConstantPoolBuilder,
+		verification::*},
+	error::EncodeError,
+	isa::{
+		instruction::{Instruction, Label},
+		instruction_list::{Element, Frame, Handler, InstructionList},
+		opcode::Opcode},
+};
+
+/// The type of a local variable or operand stack entry during frame computation (JVMS17 4.10.1.2).
+#[derive(Clone, Debug, PartialEq)]
+enum Type {
+	Top,
+	Integer,
+	Float,
+	Long,
+	Double,
+	Null,
+	UninitializedThis,
+	/// An object created by the `new` instruction following the label, naming its class.
+	Uninitialized(Label, String),
+	/// A class by internal name, or an array by descriptor.
+	Object(String),
+}
+
+impl Type {
+
+	fn size(&self) -> usize {
+		match self {
+			Type::Long | Type::Double => 2,
+			_ => 1,
+		}
+	}
+
+	/// The type of a value with the given field descriptor, or `None` for `V`.
+	fn from_descriptor(descriptor: &str) -> Option<Type> {
+		match descriptor.as_bytes().first()? {
+			b'B' | b'C' | b'I' | b'S' | b'Z' => Some(Type::Integer),
+			b'F' => Some(Type::Float),
+			b'J' => Some(Type::Long),
+			b'D' => Some(Type::Double),
+			b'L' => Some(Type::Object(descriptor[1..descriptor.len() - 1].to_string())),
+			b'[' => Some(Type::Object(descriptor.to_string())),
+			_ => None,
+		}
+	}
+}
+
+/// Split a method descriptor into its parameter descriptors and return descriptor (JVMS17 4.3.3).
+pub fn split_method_descriptor(descriptor: &str) -> Option<(Vec<&str>, &str)> {
+	let body = descriptor.strip_prefix('(')?;
+	let (parameters, return_type) = body.split_once(')')?;
+	let mut result = Vec::new();
+	let mut rest = parameters;
+	while !rest.is_empty() {
+		let dimensions = rest.len() - rest.trim_start_matches('[').len();
+		let length = match rest.as_bytes().get(dimensions)? {
+			b'L' => rest.find(';')? + 1,
+			_ => dimensions + 1,
+		};
+		result.push(&rest[..length]);
+		rest = &rest[length..];
+	}
+	Some((result, return_type))
+}
+
+/// The number of local variable slots taken by the parameters of a method descriptor.
+pub fn parameter_slots(descriptor: &str) -> Option<u16> {
+	let (parameters, _) = split_method_descriptor(descriptor)?;
+	Some(parameters.iter().map(|parameter| if matches!(*parameter, "J" | "D") { 2 } else { 1 }).sum())
+}
+
+/// The max_stack and max_locals of a Code attribute (JVMS17 4.7.3).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CodeLimits {
+	pub max_stack: u16,
+	pub max_locals: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct State {
+	locals: Vec<Type>,
+	stack: Vec<Type>,
+}
+
+/// Finds the least common superclass of two classes by internal name.
+pub type CommonSuperClass<'a> = Box<dyn Fn(&str, &str) -> String + 'a>;
+
+/// Infers the types of locals and stack entries throughout a method by data-flow analysis, much as
+/// the type checking verifier does (JVMS17 4.10.1), and derives from them the method's
+/// StackMapTable frames and its max_stack and max_locals.
+///
+/// Where control flow merges two different reference types the result is their least common
+/// superclass, which by default is assumed to be `java/lang/Object`; supply
+/// `common_super_class` when that is too coarse for the code to verify.
+pub struct FrameComputer<'a> {
+	class_name: String,
+	common_super_class: CommonSuperClass<'a>,
+}
+
+impl<'a> FrameComputer<'a> {
+
+	pub fn new(class_name: &str) -> FrameComputer<'a> {
+		FrameComputer {
+			class_name: class_name.to_string(),
+			common_super_class: Box::new(|_, _| "java/lang/Object".to_string()),
+		}
+	}
+
+	pub fn common_super_class<F: Fn(&str, &str) -> String + 'a>(mut self, common_super_class: F) -> Self {
+		self.common_super_class = Box::new(common_super_class);
+		self
+	}
+
+	/// Compute the frames of the code in `list`, replacing `list.frames`, and return its limits.
+	///
+	/// Unreachable instructions are removed, along with exception handlers left guarding nothing,
+	/// since the verifier would otherwise need frames for code that can never run. Class constants
+	/// for the types mentioned by frames are added to `pool`. `jsr` and `ret` are refused, as they
+	/// cannot appear in class files that need frames.
+	pub fn compute(&self, list: &mut InstructionList, pool: &mut ConstantPoolBuilder, access_flags: u16, name: &str, descriptor: &str) -> Result<CodeLimits, EncodeError> {
+		let initial = self.initial_state(access_flags, name, descriptor)?;
+		let mut limits = CodeLimits {
+			max_stack: 0,
+			max_locals: initial.locals.len() as u16,
+		};
+		Self::label_allocations(list);
+
+		let mut states: HashMap<usize, State> = HashMap::new();
+		let mut targets: BTreeSet<usize> = BTreeSet::new();
+		let mut pending: Vec<usize> = Vec::new();
+		let first = Self::next_instruction(list, 0).ok_or_else(|| EncodeError { msg: format!("{}{} has no code", name, descriptor) })?;
+		states.insert(first, initial.clone());
+		pending.push(first);
+
+		let handlers = self.handler_ranges(list, pool)?;
+		while let Some(index) = pending.pop() {
+			let before = states[&index].clone();
+			let Element::Instruction(instruction) = &list.elements[index] else {
+				unreachable!("states are only kept for instructions");
+			};
+			let mut after = before.clone();
+			let label = Self::label_before(list, index);
+			self.execute(instruction, label, &mut after, pool)
+				.map_err(|e| EncodeError { msg: format!("{} at instruction {} of {}{}", e.msg, index, name, descriptor) })?;
+
+			limits.max_locals = limits.max_locals.max(after.locals.len() as u16);
+			limits.max_stack = limits.max_stack.max(Self::depth(&after.stack) as u16);
+
+			let mut successors: Vec<(usize, State)> = Vec::new();
+			for target in instruction.targets() {
+				let target = self.position(list, *target)?;
+				targets.insert(target);
+				successors.push((target, after.clone()));
+			}
+			if Self::falls_through(instruction.opcode()) {
+				let next = Self::next_instruction(list, index + 1)
+					.ok_or_else(|| EncodeError { msg: format!("execution can fall off the end of {}{}", name, descriptor) })?;
+				successors.push((next, after.clone()));
+			}
+			for (range, handler, catch_type) in &handlers {
+				if range.contains(&index) {
+					targets.insert(*handler);
+					// the handler may be reached with the locals from before or after the instruction
+					for locals in [&before.locals, &after.locals] {
+						successors.push((*handler, State { locals: locals.clone(), stack: vec![Type::Object(catch_type.clone())] }));
+					}
+					limits.max_stack = limits.max_stack.max(1);
+				}
+			}
+
+			for (successor, state) in successors {
+				let merged = match states.get(&successor) {
+					None => state,
+					Some(existing) => self.merge(existing, &state)
+						.map_err(|msg| EncodeError { msg: format!("{} at instruction {} of {}{}", msg, successor, name, descriptor) })?,
+				};
+				if states.get(&successor) != Some(&merged) {
+					states.insert(successor, merged);
+					if !pending.contains(&successor) {
+						pending.push(successor);
+					}
+				}
+			}
+		}
+
+		let mut frames = Vec::new();
+		let mut previous = Self::verification_locals(&initial.locals, list, pool)?;
+		for target in targets {
+			let state = &states[&target];
+			let label = Self::label_before(list, target)
+				.ok_or_else(|| EncodeError { msg: format!("no label before instruction {}", target) })?;
+			let locals = Self::verification_locals(&state.locals, list, pool)?;
+			let stack = state.stack.iter().map(|value| Self::verification_type(value, list, pool)).collect::<Result<Vec<_>, _>>()?;
+			frames.push(Frame { label, frame: Self::compress(&previous, locals.clone(), stack) });
+			previous = locals;
+		}
+		list.frames = frames;
+		Self::remove_unreachable(list, &states);
+		Ok(limits)
+	}
+
+	fn initial_state(&self, access_flags: u16, name: &str, descriptor: &str) -> Result<State, EncodeError> {
+		let malformed = || EncodeError { msg: format!("malformed method descriptor {}", descriptor) };
+		let (parameters, _) = split_method_descriptor(descriptor).ok_or_else(malformed)?;
+		let mut locals = Vec::new();
+		if access_flags & MethodAccessPropertyFlags::Static as u16 == 0 {
+			locals.push(if name == "<init>" && self.class_name != "java/lang/Object" {
+				Type::UninitializedThis
+			} else {
+				Type::Object(self.class_name.clone())
+			});
+		}
+		for parameter in parameters {
+			let value = Type::from_descriptor(parameter).ok_or_else(malformed)?;
+			let index = locals.len();
+			Self::set_local(&mut locals, index, value);
+		}
+		Ok(State { locals, stack: vec![] })
+	}
+
+	/// Give every `new` instruction a label of its own, so uninitialized types can name it.
+	fn label_allocations(list: &mut InstructionList) {
+		let mut index = 0;
+		while index < list.elements.len() {
+			if matches!(&list.elements[index], Element::Instruction(Instruction::Plain { opcode: Opcode::New, .. }))
+				&& Self::label_before(list, index).is_none() {
+				let label = list.new_label();
+				list.insert(index, Element::Label(label));
+				index += 1;
+			}
+			index += 1;
+		}
+	}
+
+	/// A label placed immediately before the instruction at `index`, with only labels in between.
+	fn label_before(list: &InstructionList, index: usize) -> Option<Label> {
+		list.elements[..index].iter().rev()
+			.map_while(|element| match element {
+				Element::Label(label) => Some(*label),
+				Element::Instruction(_) => None,
+			})
+			.last()
+	}
+
+	fn next_instruction(list: &InstructionList, from: usize) -> Option<usize> {
+		(from..list.elements.len()).find(|index| matches!(list.elements[*index], Element::Instruction(_)))
+	}
+
+	/// The index of the instruction a label marks.
+	fn position(&self, list: &InstructionList, label: Label) -> Result<usize, EncodeError> {
+		let placed = list.position_of(label).ok_or_else(|| EncodeError { msg: format!("label {} is not placed", label.0) })?;
+		Self::next_instruction(list, placed).ok_or_else(|| EncodeError { msg: format!("label {} is at the end of the code", label.0) })
+	}
+
+	/// For each exception handler, the indices of the instructions it guards, its entry and the type it catches.
+	fn handler_ranges(&self, list: &InstructionList, pool: &ConstantPoolBuilder) -> Result<Vec<(std::ops::Range<usize>, usize, String)>, EncodeError> {
+		list.handlers.iter().map(|handler| {
+			let placed = |label: Label| list.position_of(label).ok_or_else(|| EncodeError { msg: format!("label {} is not placed", label.0) });
+			let catch_type = match handler.catch_type_index {
+				0 => "java/lang/Throwable".to_string(),
+				index => Self::class_name(pool, index)?,
+			};
+			Ok((placed(handler.start)?..placed(handler.end)?, self.position(list, handler.handler)?, catch_type))
+		}).collect()
+	}
+
+	fn falls_through(opcode: Opcode) -> bool {
+		!matches!(opcode,
+			Opcode::Goto | Opcode::GotoW | Opcode::TableSwitch | Opcode::LookupSwitch | Opcode::AThrow |
+			Opcode::IReturn | Opcode::LReturn | Opcode::FReturn | Opcode::DReturn | Opcode::AReturn | Opcode::Return)
+	}
+
+	fn depth(stack: &[Type]) -> usize {
+		stack.iter().map(Type::size).sum()
+	}
+
+	fn class_name(pool: &ConstantPoolBuilder, index: u16) -> Result<String, EncodeError> {
+		match pool.get(index) {
+			Some(ConstantPoolItem::Class(class)) => Self::utf8(pool, class.index),
+			_ => Err(EncodeError { msg: format!("constant {} is not a Class", index) }),
+		}
+	}
+
+	fn utf8(pool: &ConstantPoolBuilder, index: u16) -> Result<String, EncodeError> {
+		match pool.get(index) {
+			Some(ConstantPoolItem::Utf8(utf8)) => Ok(utf8.to_string()),
+			_ => Err(EncodeError { msg: format!("constant {} is not a Utf8", index) }),
+		}
+	}
+
+	/// The class, name and descriptor of a member reference, or the name and descriptor of a call site.
+	fn member(pool: &ConstantPoolBuilder, index: u16) -> Result<(Option<String>, String, String), EncodeError> {
+		let (class_index, name_and_type_index) = match pool.get(index) {
+			Some(ConstantPoolItem::FieldRef(reference)) => (Some(reference.class_index), reference.name_and_type_index),
+			Some(ConstantPoolItem::MethodRef(reference)) => (Some(reference.class_index), reference.name_and_type_index),
+			Some(ConstantPoolItem::InterfaceMethodRef(reference)) => (Some(reference.class_index), reference.name_and_type_index),
+			Some(ConstantPoolItem::InvokeDynamic(call_site)) => (None, call_site.name_and_type_index),
+			Some(ConstantPoolItem::Dynamic(constant)) => (None, constant.name_and_type_index),
+			_ => return Err(EncodeError { msg: format!("constant {} is not a member reference", index) }),
+		};
+		let Some(ConstantPoolItem::NameAndType(name_and_type)) = pool.get(name_and_type_index) else {
+			return Err(EncodeError { msg: format!("constant {} is not a NameAndType", name_and_type_index) });
+		};
+		let class_name = class_index.map(|index| Self::class_name(pool, index)).transpose()?;
+		Ok((class_name, Self::utf8(pool, name_and_type.name_index)?, Self::utf8(pool, name_and_type.type_index)?))
+	}
+
+	fn set_local(locals: &mut Vec<Type>, index: usize, value: Type) {
+		let size = value.size();
+		if locals.len() < index + size {
+			locals.resize(index + size, Type::Top);
+		}
+		// overwriting the second half of a long or double invalidates the first
+		if index > 0 && locals[index - 1].size() == 2 {
+			locals[index - 1] = Type::Top;
+		}
+		locals[index] = value;
+		if size == 2 {
+			locals[index + 1] = Type::Top;
+		}
+	}
+
+	fn pop(stack: &mut Vec<Type>) -> Result<Type, EncodeError> {
+		stack.pop().ok_or_else(|| EncodeError { msg: "operand stack underflow".to_string() })
+	}
+
+	fn pop_n(stack: &mut Vec<Type>, count: usize) -> Result<(), EncodeError> {
+		for _ in 0..count {
+			Self::pop(stack)?;
+		}
+		Ok(())
+	}
+
+	/// Pop values totalling `size` slots, as the untyped stack instructions do.
+	fn pop_slots(stack: &mut Vec<Type>, size: usize) -> Result<Vec<Type>, EncodeError> {
+		let mut popped = Vec::new();
+		let mut taken = 0;
+		while taken < size {
+			let value = Self::pop(stack)?;
+			taken += value.size();
+			popped.insert(0, value);
+		}
+		if taken != size {
+			return Err(EncodeError { msg: "stack instruction splits a long or double".to_string() });
+		}
+		Ok(popped)
+	}
+
+	fn local_index(instruction: &Instruction<Label>) -> usize {
+		match instruction {
+			Instruction::Plain { opcode: Opcode::Wide, operands } => usize::from(u16::from_be_bytes([operands[1], operands[2]])),
+			Instruction::Plain { operands, .. } => operands.first().copied().map(usize::from).unwrap_or_default(),
+			_ => 0,
+		}
+	}
+
+	/// The type effect of a single instruction on `state`.
+	fn execute(&self, instruction: &Instruction<Label>, label: Option<Label>, state: &mut State, pool: &ConstantPoolBuilder) -> Result<(), EncodeError> {
+		use Opcode::*;
+		let opcode = match instruction {
+			Instruction::Plain { opcode: Wide, operands } => Opcode::try_from(operands[0])
+				.map_err(|_| EncodeError { msg: "malformed wide instruction".to_string() })?,
+			other => other.opcode(),
+		};
+		let index = Self::local_index(instruction);
+		let constant = instruction.constant_pool_index().unwrap_or(0);
+		let stack = &mut state.stack;
+		let locals = &mut state.locals;
+		let byte = u8::from(opcode);
+		match opcode {
+			Nop | IInc | Goto | GotoW | Return => {}
+			AConstNull => stack.push(Type::Null),
+			IConstM1 | IConst0 | IConst1 | IConst2 | IConst3 | IConst4 | IConst5 | BIpush | SIpush => stack.push(Type::Integer),
+			LConst0 | LConst1 => stack.push(Type::Long),
+			FConst0 | FConst1 | FConst2 => stack.push(Type::Float),
+			DConst0 | DConst1 => stack.push(Type::Double),
+			Ldc | LdcW | Ldc2W => stack.push(match pool.get(constant) {
+				Some(ConstantPoolItem::Integer(_)) => Type::Integer,
+				Some(ConstantPoolItem::Float(_)) => Type::Float,
+				Some(ConstantPoolItem::Long(_)) => Type::Long,
+				Some(ConstantPoolItem::Double(_)) => Type::Double,
+				Some(ConstantPoolItem::String(_)) => Type::Object("java/lang/String".to_string()),
+				Some(ConstantPoolItem::Class(_)) => Type::Object("java/lang/Class".to_string()),
+				Some(ConstantPoolItem::MethodType(_)) => Type::Object("java/lang/invoke/MethodType".to_string()),
+				Some(ConstantPoolItem::MethodHandle(_)) => Type::Object("java/lang/invoke/MethodHandle".to_string()),
+				Some(ConstantPoolItem::Dynamic(_)) => Type::from_descriptor(&Self::member(pool, constant)?.2)
+					.ok_or_else(|| EncodeError { msg: format!("dynamic constant {} has no type", constant) })?,
+				_ => return Err(EncodeError { msg: format!("constant {} cannot be loaded by {}", constant, opcode) }),
+			}),
+			_ if (u8::from(ILoad)..=u8::from(ALoad3)).contains(&byte) => {
+				// iload to aload take an operand; the four short forms of each follow them in order
+				let (kind, index) = match byte.checked_sub(u8::from(ILoad0)) {
+					Some(offset) => (offset / 4, usize::from(offset % 4)),
+					None => (byte - u8::from(ILoad), index),
+				};
+				let value = match kind {
+					0 => Type::Integer,
+					1 => Type::Long,
+					2 => Type::Float,
+					3 => Type::Double,
+					_ => locals.get(index).cloned().ok_or_else(|| EncodeError { msg: format!("local {} is not set", index) })?,
+				};
+				stack.push(value);
+			}
+			_ if (u8::from(IStore)..=u8::from(AStore3)).contains(&byte) => {
+				let index = match byte.checked_sub(u8::from(IStore0)) {
+					Some(offset) => usize::from(offset % 4),
+					None => index,
+				};
+				let value = Self::pop(stack)?;
+				Self::set_local(locals, index, value);
+			}
+			IALoad | BALoad | CALoad | SALoad => { Self::pop_n(stack, 2)?; stack.push(Type::Integer); }
+			LALoad => { Self::pop_n(stack, 2)?; stack.push(Type::Long); }
+			FALoad => { Self::pop_n(stack, 2)?; stack.push(Type::Float); }
+			DALoad => { Self::pop_n(stack, 2)?; stack.push(Type::Double); }
+			AALoad => {
+				Self::pop(stack)?;
+				let element = match Self::pop(stack)? {
+					Type::Object(array) if array.starts_with('[') => Type::from_descriptor(&array[1..]).unwrap_or(Type::Null),
+					_ => Type::Null,
+				};
+				stack.push(element);
+			}
+			IAStore | LAStore | FAStore | DAStore | AAStore | BAStore | CAStore | SAStore => Self::pop_n(stack, 3)?,
+			Pop => { Self::pop_slots(stack, 1)?; }
+			Pop2 => { Self::pop_slots(stack, 2)?; }
+			Dup | DupX1 | DupX2 | Dup2 | Dup2X1 | Dup2X2 => {
+				let (copied, skipped) = match opcode {
+					Dup => (1, 0),
+					DupX1 => (1, 1),
+					DupX2 => (1, 2),
+					Dup2 => (2, 0),
+					Dup2X1 => (2, 1),
+					_ => (2, 2),
+				};
+				let top = Self::pop_slots(stack, copied)?;
+				let under = Self::pop_slots(stack, skipped)?;
+				stack.extend(top.iter().cloned());
+				stack.extend(under);
+				stack.extend(top);
+			}
+			Swap => {
+				let top = Self::pop(stack)?;
+				let under = Self::pop(stack)?;
+				stack.push(top);
+				stack.push(under);
+			}
+			IAdd | ISub | IMul | IDiv | IRem | IShl | IShr | IUShr | IAnd | IOr | IXor |
+			LCmp | FCmpL | FCmpG | DCmpL | DCmpG => { Self::pop_n(stack, 2)?; stack.push(Type::Integer); }
+			LAdd | LSub | LMul | LDiv | LRem | LAnd | LOr | LXor | LShl | LShr | LUShr => { Self::pop_n(stack, 2)?; stack.push(Type::Long); }
+			FAdd | FSub | FMul | FDiv | FRem => { Self::pop_n(stack, 2)?; stack.push(Type::Float); }
+			DAdd | DSub | DMul | DDiv | DRem => { Self::pop_n(stack, 2)?; stack.push(Type::Double); }
+			INeg | L2I | F2I | D2I | I2B | I2C | I2S | ArrayLength | InstanceOf => { Self::pop(stack)?; stack.push(Type::Integer); }
+			LNeg | I2L | F2L | D2L => { Self::pop(stack)?; stack.push(Type::Long); }
+			FNeg | I2F | L2F | D2F => { Self::pop(stack)?; stack.push(Type::Float); }
+			DNeg | I2D | L2D | F2D => { Self::pop(stack)?; stack.push(Type::Double); }
+			IfEq | IfNe | IfLt | IfGe | IfGt | IfLe | IfNull | IfNonNull | TableSwitch | LookupSwitch |
+			IReturn | LReturn | FReturn | DReturn | AReturn | AThrow | MonitorEnter | MonitorExit | PutStatic => { Self::pop(stack)?; }
+			IfICmpEq | IfICmpNe | IfICmpLt | IfICmpGe | IfICmpGt | IfICmpLe | IfACmpEq | IfACmpNe | PutField => Self::pop_n(stack, 2)?,
+			GetStatic | GetField => {
+				if opcode == GetField {
+					Self::pop(stack)?;
+				}
+				let (_, _, descriptor) = Self::member(pool, constant)?;
+				stack.push(Type::from_descriptor(&descriptor).ok_or_else(|| EncodeError { msg: format!("malformed field descriptor {}", descriptor) })?);
+			}
+			InvokeVirtual | InvokeSpecial | InvokeStatic | InvokeInterface | InvokeDynamic => {
+				let (class_name, name, descriptor) = Self::member(pool, constant)?;
+				let (parameters, return_type) = split_method_descriptor(&descriptor)
+					.ok_or_else(|| EncodeError { msg: format!("malformed method descriptor {}", descriptor) })?;
+				Self::pop_n(stack, parameters.len())?;
+				if !matches!(opcode, InvokeStatic | InvokeDynamic) {
+					let receiver = Self::pop(stack)?;
+					if name == "<init>" {
+						let initialised = match &receiver {
+							Type::UninitializedThis => Type::Object(self.class_name.clone()),
+							Type::Uninitialized(_, class_name) => Type::Object(class_name.clone()),
+							_ => return Err(EncodeError { msg: format!("{}.<init> called on an initialised {:?}", class_name.unwrap_or_default(), receiver) }),
+						};
+						for value in locals.iter_mut().chain(stack.iter_mut()) {
+							if *value == receiver {
+								*value = initialised.clone();
+							}
+						}
+					}
+				}
+				if let Some(value) = Type::from_descriptor(return_type) {
+					stack.push(value);
+				}
+			}
+			New => {
+				let label = label.ok_or_else(|| EncodeError { msg: "new has no label".to_string() })?;
+				stack.push(Type::Uninitialized(label, Self::class_name(pool, constant)?));
+			}
+			NewArray => {
+				Self::pop(stack)?;
+				let element = match index {
+					4 => "Z",
+					5 => "C",
+					6 => "F",
+					7 => "D",
+					8 => "B",
+					9 => "S",
+					10 => "I",
+					11 => "J",
+					other => return Err(EncodeError { msg: format!("invalid newarray type {}", other) }),
+				};
+				stack.push(Type::Object(format!("[{}", element)));
+			}
+			ANewArray => {
+				Self::pop(stack)?;
+				let class_name = Self::class_name(pool, constant)?;
+				stack.push(Type::Object(if class_name.starts_with('[') { format!("[{}", class_name) } else { format!("[L{};", class_name) }));
+			}
+			CheckCast => {
+				Self::pop(stack)?;
+				stack.push(Type::Object(Self::class_name(pool, constant)?));
+			}
+			MultiANewArray => {
+				let dimensions = match instruction {
+					Instruction::Plain { operands, .. } => usize::from(operands[2]),
+					_ => 0,
+				};
+				Self::pop_n(stack, dimensions)?;
+				stack.push(Type::Object(Self::class_name(pool, constant)?));
+			}
+			Jsr | JsrW | Ret => return Err(EncodeError { msg: format!("{} cannot be used in code with stack map frames", opcode) }),
+			_ => return Err(EncodeError { msg: format!("{} cannot appear in code", opcode) }),
+		}
+		Ok(())
+	}
+
+	/// The type of a location reached by two paths with types `a` and `b`.
+	fn merge_type(&self, a: &Type, b: &Type) -> Type {
+		match (a, b) {
+			_ if a == b => a.clone(),
+			(Type::Null, other @ Type::Object(_)) | (other @ Type::Object(_), Type::Null) => other.clone(),
+			(Type::Object(a), Type::Object(b)) => Type::Object(self.common_reference(a, b)),
+			_ => Type::Top,
+		}
+	}
+
+	fn common_reference(&self, a: &str, b: &str) -> String {
+		match (a.strip_prefix('['), b.strip_prefix('[')) {
+			(Some(a), Some(b)) => match (Type::from_descriptor(a), Type::from_descriptor(b)) {
+				(Some(Type::Object(a)), Some(Type::Object(b))) => match self.common_reference(&a, &b) {
+					common if common.starts_with('[') => format!("[{}", common),
+					common => format!("[L{};", common),
+				},
+				_ => "java/lang/Object".to_string(),
+			},
+			(None, None) => (self.common_super_class)(a, b),
+			_ => "java/lang/Object".to_string(),
+		}
+	}
+
+	fn merge(&self, existing: &State, incoming: &State) -> Result<State, String> {
+		if existing.stack.len() != incoming.stack.len() {
+			return Err(format!("stack heights {} and {} differ", Self::depth(&existing.stack), Self::depth(&incoming.stack)));
+		}
+		let stack = existing.stack.iter().zip(incoming.stack.iter())
+			.map(|(a, b)| match self.merge_type(a, b) {
+				Type::Top => Err(format!("incompatible stack entries {:?} and {:?}", a, b)),
+				merged => Ok(merged),
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+		let length = existing.locals.len().min(incoming.locals.len());
+		let mut locals: Vec<Type> = existing.locals[..length].iter().zip(incoming.locals[..length].iter())
+			.map(|(a, b)| self.merge_type(a, b))
+			.collect();
+		// a long or double whose halves no longer line up is unusable
+		for index in 0..locals.len() {
+			if locals[index].size() == 2 && locals.get(index + 1) != Some(&Type::Top) {
+				locals[index] = Type::Top;
+			}
+		}
+		Ok(State { locals, stack })
+	}
+
+	/// Drop instructions no path reaches, and handlers that guard nothing but them.
+	fn remove_unreachable(list: &mut InstructionList, states: &HashMap<usize, State>) {
+		let live: Vec<bool> = (0..list.elements.len())
+			.map(|index| !matches!(list.elements[index], Element::Instruction(_)) || states.contains_key(&index))
+			.collect();
+		let handlers = std::mem::take(&mut list.handlers);
+		list.handlers = handlers.into_iter().filter(|handler: &Handler| {
+			match (list.position_of(handler.start), list.position_of(handler.end)) {
+				(Some(start), Some(end)) => (start..end).any(|index| matches!(list.elements[index], Element::Instruction(_)) && live[index]),
+				_ => true,
+			}
+		}).collect();
+		let mut index = 0;
+		list.elements.retain(|_| {
+			index += 1;
+			live[index - 1]
+		});
+	}
+
+	fn verification_type(value: &Type, list: &mut InstructionList, pool: &mut ConstantPoolBuilder) -> Result<VerificationTypeInfo, EncodeError> {
+		Ok(match value {
+			Type::Top => VerificationTypeInfo::TopVariableInfo(TopVariableInfo {}),
+			Type::Integer => VerificationTypeInfo::IntegerVariableInfo(IntegerVariableInfo {}),
+			Type::Float => VerificationTypeInfo::FloatVariableInfo(FloatVariableInfo {}),
+			Type::Long => VerificationTypeInfo::LongVariableInfo(LongVariableInfo {}),
+			Type::Double => VerificationTypeInfo::DoubleVariableInfo(DoubleVariableInfo {}),
+			Type::Null => VerificationTypeInfo::NullVariableInfo(NullVariableInfo {}),
+			Type::UninitializedThis => VerificationTypeInfo::UninitializedThisVariableInfo(UninitializedThisVariableInfo {}),
+			Type::Uninitialized(label, _) => VerificationTypeInfo::UninitializedVariableInfo(list.uninitialized(*label)?),
+			Type::Object(name) => VerificationTypeInfo::ObjectVariableInfo(ObjectVariableInfo { constant_pool_index: pool.add_class(name)? }),
+		})
+	}
+
+	/// Locals as a frame lists them: one entry per long or double rather than two, without trailing tops.
+	fn verification_locals(locals: &[Type], list: &mut InstructionList, pool: &mut ConstantPoolBuilder) -> Result<Vec<VerificationTypeInfo>, EncodeError> {
+		let mut result = Vec::new();
+		let mut index = 0;
+		while index < locals.len() {
+			result.push(Self::verification_type(&locals[index], list, pool)?);
+			index += locals[index].size();
+		}
+		while result.last() == Some(&VerificationTypeInfo::TopVariableInfo(TopVariableInfo {})) {
+			result.pop();
+		}
+		Ok(result)
+	}
+
+	/// The most compact frame type describing `locals` and `stack` relative to the previous frame
+	/// (JVMS17 4.7.4). The offset delta is filled in when the code is laid out.
+	fn compress(previous: &[VerificationTypeInfo], locals: Vec<VerificationTypeInfo>, mut stack: Vec<VerificationTypeInfo>) -> StackMapFrame {
+		if locals == previous && stack.is_empty() {
+			return StackMapFrame::SameFrame(SameFrame { frame_type: 0 });
+		}
+		if locals == previous && stack.len() == 1 {
+			return StackMapFrame::SameLocals1StackItemFrame(SameLocals1StackItemFrame { frame_type: 64, verification_type_info: stack.remove(0) });
+		}
+		if stack.is_empty() && locals.len() < previous.len() && previous.len() - locals.len() <= 3 && previous.starts_with(&locals) {
+			return StackMapFrame::ChopFrame(ChopFrame { frame_type: (251 - (previous.len() - locals.len())) as u8, offset_delta: 0 });
+		}
+		if stack.is_empty() && locals.len() > previous.len() && locals.len() - previous.len() <= 3 && locals.starts_with(previous) {
+			let appended = locals[previous.len()..].to_vec();
+			return StackMapFrame::AppendFrame(AppendFrame { frame_type: (251 + appended.len()) as u8, offset_delta: 0, locals: appended });
+		}
+		StackMapFrame::FullFrame(FullFrame {
+			frame_type: 255,
+			offset_delta: 0,
+			number_of_locals: locals.len() as u16,
+			locals,
+			number_of_stack_items: stack.len() as u16,
+			stack,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs::File;
+
+	use crate::{
+		class::{
+			attribute::{AttributeInfo, Code},
+			class::Class,
+			constant_pool_builder::ConstantPoolBuilder,
+			verification::{UninitializedVariableInfo, VerificationTypeInfo}},
+		isa::{
+			instruction_list::InstructionList,
+			stack_map::{parameter_slots, split_method_descriptor, FrameComputer}},
+	};
+
+	const CLASS_FILE_PATH: &str = "tests/resources/Branches.class";
+
+	#[test]
+	fn test_split_method_descriptor() {
+		assert_eq!(split_method_descriptor("(I[JLjava/lang/String;[[Ljava/lang/Object;D)V"),
+			Some((vec!["I", "[J", "Ljava/lang/String;", "[[Ljava/lang/Object;", "D"], "V")));
+		assert_eq!(split_method_descriptor("()Ljava/lang/Object;"), Some((vec![], "Ljava/lang/Object;")));
+		assert_eq!(split_method_descriptor("(Ljava/lang/String"), None);
+		assert_eq!(parameter_slots("(IJ[DD)V"), Some(6));
+	}
+
+	#[test]
+	fn test_recompute_frames() {
+		let clazz = Class::new(File::open(CLASS_FILE_PATH).expect("Couldn't access class file"));
+		for method in &clazz.methods.methods {
+			let name = clazz.get_utf8(method.name_index).unwrap();
+			let descriptor = clazz.get_utf8(method.descriptor_index).unwrap();
+			let original = method.code().unwrap();
+			let mut list = InstructionList::from_code(original).unwrap();
+			list.frames.clear();
+			let mut pool = ConstantPoolBuilder::from(clazz.constant_pool.clone());
+			let limits = FrameComputer::new("Branches")
+				.compute(&mut list, &mut pool, method.access_flags, &name, &descriptor)
+				.unwrap();
+			assert_eq!((limits.max_stack, limits.max_locals), (original.max_stack, original.max_locals), "{}", name);
+
+			// javac ends the scope of dead locals where data flow keeps them, so the frames may
+			// differ in content but not in position
+			let mut code = original.clone();
+			list.commit(&mut code).unwrap();
+			assert_eq!(code.code, original.code, "{}", name);
+			assert_eq!(frame_offsets(&code), frame_offsets(original), "{}", name);
+		}
+	}
+
+	fn frame_offsets(code: &Code) -> Vec<u32> {
+		let entries = code.attributes.iter().find_map(|attribute| match &attribute.attribute_info {
+			AttributeInfo::StackMapTable(table) => Some(table.entries.clone()),
+			_ => None,
+		}).unwrap_or_default();
+		let mut offset: Option<u32> = None;
+		entries.iter().map(|frame| {
+			let next = offset.map_or(0, |offset| offset + 1) + u32::from(frame.offset_delta());
+			offset = Some(next);
+			next
+		}).collect()
+	}
+
+	#[test]
+	fn test_unreachable_code_is_removed() {
+		let clazz = Class::new(File::open(CLASS_FILE_PATH).expect("Couldn't access class file"));
+		let method = clazz.find_method("loop", "(I)I").unwrap();
+		let mut list = InstructionList::from_code(method.code().unwrap()).unwrap();
+		let before = list.instructions().count();
+		// dead code after the final ireturn
+		list.push(crate::isa::instruction_list::Element::Instruction(crate::isa::instruction::Instruction::Plain {
+			opcode: crate::isa::opcode::Opcode::Nop,
+			operands: vec![],
+		}));
+		let mut pool = ConstantPoolBuilder::from(clazz.constant_pool.clone());
+		FrameComputer::new("Branches").compute(&mut list, &mut pool, method.access_flags, "loop", "(I)I").unwrap();
+		assert_eq!(list.instructions().count(), before);
+	}
+
+	#[test]
+	fn test_uninitialized_across_branch() {
+		let clazz = Class::new(File::open(CLASS_FILE_PATH).expect("Couldn't access class file"));
+		let method = clazz.find_method("choose", "(Z)Ljava/lang/Object;").unwrap();
+		let mut list = InstructionList::from_code(method.code().unwrap()).unwrap();
+		list.frames.clear();
+		let mut pool = ConstantPoolBuilder::from(clazz.constant_pool.clone());
+		FrameComputer::new("Branches").compute(&mut list, &mut pool, method.access_flags, "choose", "(Z)Ljava/lang/Object;").unwrap();
+		let mut code = method.code().unwrap().clone();
+		list.commit(&mut code).unwrap();
+		let Some(AttributeInfo::StackMapTable(table)) = code.attributes.iter().map(|attribute| &attribute.attribute_info)
+			.find(|info| matches!(info, AttributeInfo::StackMapTable(_))) else {
+			panic!("Expected a StackMapTable");
+		};
+		// both frames hold the two copies of the StringBuilder made by the new at offset 0
+		for mut frame in table.entries.clone() {
+			let uninitialized = frame.verification_types_mut().into_iter()
+				.filter(|info| **info == VerificationTypeInfo::UninitializedVariableInfo(UninitializedVariableInfo { offset: 0 }))
+				.count();
+			assert_eq!(uninitialized, 2);
+		}
+	}
+}
@@ -1,5 +1,6 @@
 #![feature(macro_metavar_expr_concat)]
 
+pub mod analysis;
 pub mod error;
 pub mod class;
 pub mod isa;
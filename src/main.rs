@@ -1,13 +1,301 @@
 extern crate regex;
 extern crate strum;
 
+use std::{env, fs, io::Cursor, path::Path, process, sync::Arc};
+
+use binrw::BinReaderExt;
 use strum::IntoEnumIterator;
 
-use steele::isa::opcode::Opcode;
+use steele::{
+	analysis::{
+		compatibility::CompatibilityReport,
+		dependencies::{DependencyAnalysis, Granularity},
+		diff::ClassDiff,
+		hierarchy::ClassHierarchy,
+		query::{Query, QueryEngine},
+		stats::ClassStatistics},
+	class::{archive, class::Class, class_path::ClassPath},
+	isa::opcode::Opcode,
+	vm::{
+		class_loader::{ClassLoaders, LoaderId},
+		gc::HeapOptions,
+		heap::Heap,
+		interpreter::Interpreter,
+		reference::ReferenceHandler,
+		types::{ArrayReference, ClassReference, Variable}}};
+
+const DIFF_USAGE: &str = "usage: steele diff [--json] <old.class> <new.class>";
+const COMPAT_USAGE: &str = "usage: steele compat [--json] <old.jar|dir> <new.jar|dir>";
+const FIND_USAGE: &str = "usage: steele find (--calls <owner.name(descriptor)> | --reads <owner.field> | --writes <owner.field> | --new <class> | --ldc <string> | --pattern <pattern>) <jar|dir>...";
+const STATS_USAGE: &str = "usage: steele stats [--json] <jar|dir>...";
+const DEPS_USAGE: &str = "usage: steele deps [--package|--archive] [--dot|--json|--jdk-internals] <jar|dir>...";
+const RUN_USAGE: &str = "usage: steele run [-Xms<size>] [-Xmx<size>] [-Xmn<size>] [-XX:<option>]... [-Xlog:gc] [-cp <class path>] <main class> [args]...";
 
 fn main() {
-	let it = Opcode::iter();
-	for op in it {
-		println!("{}", op);
+	let args: Vec<String> = env::args().skip(1).collect();
+	match args.first().map(String::as_str) {
+		Some("diff") => process::exit(diff(&args[1..])),
+		Some("compat") => process::exit(compat(&args[1..])),
+		Some("deps") => process::exit(deps(&args[1..])),
+		Some("find") => process::exit(find(&args[1..])),
+		Some("stats") => process::exit(stats(&args[1..])),
+		Some("run") => process::exit(run(&args[1..])),
+		_ => {
+			for op in Opcode::iter() {
+				println!("{}", op);
+			}
+		}
+	}
+}
+
+/// Print the semantic differences between two class files, exiting like diff(1): 0 when they are
+/// the same, 1 when they differ and 2 on error.
+fn diff(args: &[String]) -> i32 {
+	let json = args.iter().any(|arg| arg == "--json");
+	let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--json").collect();
+	let [old, new] = paths[..] else {
+		eprintln!("{}", DIFF_USAGE);
+		return 2;
+	};
+	let (old, new) = match (read_class(old), read_class(new)) {
+		(Ok(old), Ok(new)) => (old, new),
+		(Err(e), _) | (_, Err(e)) => {
+			eprintln!("{}", e);
+			return 2;
+		}
+	};
+	let diff = match ClassDiff::new(&old, &new) {
+		Ok(diff) => diff,
+		Err(e) => {
+			eprintln!("{}", e);
+			return 2;
+		}
+	};
+	if json {
+		println!("{}", diff.to_json().to_pretty_string());
+	} else {
+		print!("{}", diff);
+	}
+	if diff.is_empty() { 0 } else { 1 }
+}
+
+/// Report the binary-incompatible changes between two versions of a library, exiting with 1 when
+/// there are any and 2 on error.
+fn compat(args: &[String]) -> i32 {
+	let json = args.iter().any(|arg| arg == "--json");
+	let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--json").collect();
+	let [old, new] = paths[..] else {
+		eprintln!("{}", COMPAT_USAGE);
+		return 2;
+	};
+	let report = match CompatibilityReport::from_paths(Path::new(old), Path::new(new)) {
+		Ok(report) => report,
+		Err(e) => {
+			eprintln!("{}", e);
+			return 2;
+		}
+	};
+	if json {
+		println!("{}", report.to_json().to_pretty_string());
+	} else {
+		print!("{}", report);
 	}
+	if report.is_compatible() { 0 } else { 1 }
+}
+
+/// Print the dependencies of the classes in one or more JARs or directories.
+fn deps(args: &[String]) -> i32 {
+	let mut granularity = Granularity::Class;
+	let mut format = "text";
+	let mut paths: Vec<&String> = Vec::new();
+	for arg in args {
+		match arg.as_str() {
+			"--package" => granularity = Granularity::Package,
+			"--archive" => granularity = Granularity::Archive,
+			"--dot" | "--json" | "--jdk-internals" => format = &arg[2..],
+			_ if arg.starts_with("--") => {
+				eprintln!("{}", DEPS_USAGE);
+				return 2;
+			}
+			_ => paths.push(arg),
+		}
+	}
+	if paths.is_empty() {
+		eprintln!("{}", DEPS_USAGE);
+		return 2;
+	}
+	let mut analysis = DependencyAnalysis::new();
+	for path in paths {
+		if let Err(e) = analysis.add_path(Path::new(path)) {
+			eprintln!("{}: {}", path, e);
+			return 2;
+		}
+	}
+	match format {
+		"jdk-internals" => for (from, to) in analysis.jdk_internal_references() {
+			println!("{} -> {}", from, to);
+		},
+		"dot" => print!("{}", analysis.graph(granularity).to_dot()),
+		"json" => println!("{}", analysis.graph(granularity).to_json().to_pretty_string()),
+		_ => print!("{}", analysis.graph(granularity)),
+	}
+	0
+}
+
+/// Search the code of one or more JARs or directories, exiting like grep(1): 0 when there were
+/// matches, 1 when there were none and 2 on error.
+fn find(args: &[String]) -> i32 {
+	let (Some(option), Some(argument)) = (args.first(), args.get(1)) else {
+		eprintln!("{}", FIND_USAGE);
+		return 2;
+	};
+	let member = |text: &str| text.replace('.', "/").rsplit_once('/').map(|(owner, name)| (owner.to_string(), name.to_string()));
+	let query = match option.as_str() {
+		"--calls" => argument.split_once('(')
+			.and_then(|(owner_and_name, descriptor)| Some((member(owner_and_name)?, descriptor)))
+			.map(|((owner, name), descriptor)| Query::calls(&owner, &name, &format!("({}", descriptor.replace('.', "/")))),
+		"--reads" => member(argument).map(|(owner, name)| Query::reads(&owner, &name)),
+		"--writes" => member(argument).map(|(owner, name)| Query::writes(&owner, &name)),
+		"--new" => Some(Query::New(argument.replace('.', "/"))),
+		"--ldc" => Some(Query::Ldc(argument.to_string())),
+		"--pattern" => Some(Query::pattern(argument)),
+		_ => None,
+	};
+	let (Some(query), paths @ [_, ..]) = (query, &args[2..]) else {
+		eprintln!("{}", FIND_USAGE);
+		return 2;
+	};
+	let mut classes = Vec::new();
+	for path in paths {
+		match archive::read_classes(Path::new(path)) {
+			Ok(found) => classes.extend(found),
+			Err(e) => {
+				eprintln!("{}: {}", path, e);
+				return 2;
+			}
+		}
+	}
+	let hierarchy = ClassHierarchy::from_classes(classes);
+	match QueryEngine::new(&hierarchy).find(&query) {
+		Ok(matches) => {
+			for found in &matches {
+				println!("{}", found);
+			}
+			if matches.is_empty() { 1 } else { 0 }
+		}
+		Err(e) => {
+			eprintln!("{}", e);
+			2
+		}
+	}
+}
+
+/// Print size and opcode statistics for the classes in one or more JARs or directories.
+fn stats(args: &[String]) -> i32 {
+	let json = args.iter().any(|arg| arg == "--json");
+	let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--json").collect();
+	if paths.is_empty() {
+		eprintln!("{}", STATS_USAGE);
+		return 2;
+	}
+	let mut statistics = ClassStatistics::new();
+	for path in paths {
+		if let Err(e) = statistics.add_path(Path::new(path)) {
+			eprintln!("{}: {}", path, e);
+			return 2;
+		}
+	}
+	if json {
+		println!("{}", statistics.to_json().to_pretty_string());
+	} else {
+		print!("{}", statistics);
+	}
+	0
+}
+
+/// Run the `main` method of a class as `java` does, with the JDK in `JAVA_HOME` on the boot class
+/// path and the heap sized and logged by the VM options before the class name. Exits with 1 when
+/// the VM cannot start or main throws.
+fn run(args: &[String]) -> i32 {
+	let mut vm_options = Vec::new();
+	let mut class_path = env::var("CLASSPATH").unwrap_or_else(|_| ".".to_string());
+	let mut rest = args.iter();
+	let main_class = loop {
+		match rest.next().map(String::as_str) {
+			Some("-cp" | "-classpath" | "--class-path") => match rest.next() {
+				Some(path) => class_path = path.clone(),
+				None => {
+					eprintln!("{}", RUN_USAGE);
+					return 2;
+				}
+			},
+			Some(option) if option.starts_with('-') => vm_options.push(option),
+			Some(main_class) => break main_class.replace('.', "/"),
+			None => {
+				eprintln!("{}", RUN_USAGE);
+				return 2;
+			}
+		}
+	};
+	let options = match HeapOptions::parse(&vm_options) {
+		Ok(options) => options,
+		Err(e) => {
+			eprintln!("{}\nError: Could not create the Java Virtual Machine.", e);
+			return 1;
+		}
+	};
+	let Some(java_home) = env::var_os("JAVA_HOME") else {
+		eprintln!("Error: JAVA_HOME is not set");
+		return 1;
+	};
+	let mut boot_class_path = ClassPath::new();
+	let class_path = boot_class_path.add_java_home(Path::new(&java_home)).and_then(|_| ClassPath::parse(&class_path));
+	let class_path = match class_path {
+		Ok(class_path) => class_path,
+		Err(e) => {
+			eprintln!("Error: {}", e);
+			return 1;
+		}
+	};
+
+	let loaders = Arc::new(ClassLoaders::new(boot_class_path, class_path));
+	let heap = Arc::new(Heap::new(loaders.clone(), options));
+	let initialize = |class: &_| loaders.initialize_class(class, &|class| Interpreter::run_initializer(&loaders, &heap, class));
+	let main = match loaders.load_class(LoaderId::APPLICATION, &main_class) {
+		Ok(main) => main,
+		Err(e) => {
+			eprintln!("Error: Could not find or load main class {}\nCaused by: {}", main_class.replace('/', "."), e);
+			return 1;
+		}
+	};
+	let handler = ReferenceHandler::start(loaders.clone(), heap.clone());
+	let result = initialize(&main).map_err(Into::into).and_then(|_| {
+		let strings = loaders.load_class(LoaderId::BOOTSTRAP, "[Ljava/lang/String;")?;
+		let array = heap.allocate_array(&strings, rest.len() as u32)?;
+		// The array stays published while its strings are made and main runs.
+		heap.push_frame(vec![array]);
+		let result = rest.enumerate().try_for_each(|(index, argument)| {
+			let string = heap.intern(argument)?;
+			heap.put_element(array, index as u32, &Variable::ClassReference(ClassReference { value: string }));
+			Ok(())
+		}).and_then(|_| {
+			let arguments = vec![Variable::ArrayReference(ArrayReference { value: array })];
+			Interpreter::for_method(loaders.clone(), heap.clone(), main.clone(), "main", "([Ljava/lang/String;)V", arguments)?.execute()
+		});
+		heap.pop_frame();
+		result
+	});
+	handler.stop();
+	match result {
+		Ok(_) => 0,
+		Err(e) => {
+			eprintln!("Exception in thread \"main\" {}", e);
+			1
+		}
+	}
+}
+
+fn read_class(path: &str) -> Result<Class, String> {
+	let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+	Cursor::new(bytes).read_be().map_err(|e| format!("{}: {}", path, e))
 }
@@ -0,0 +1,713 @@
+use std::{
+	collections::{BTreeSet, HashMap, HashSet},
+	fmt,
+	io::Cursor,
+	sync::{Arc, Mutex, RwLock, Weak},
+	thread::{self, ThreadId}};
+
+use binrw::BinReaderExt;
+
+use crate::{
+	analysis::{
+		hierarchy::{ARRAY_INTERFACES, OBJECT},
+		shrinker::descriptor_classes},
+	class::{access::ClassAccessPropertyFlags, builder::ClassBuilder, class::Class, class_path::ClassPath},
+	vm::{
+		errors::LinkageError,
+		heap::{GlobalRef, Heap, Reference},
+		interpreter::Interpreter,
+		method_area::{MethodArea, RuntimeClass}}};
+
+/// The oldest and newest class file major versions this VM loads (Java 1.1 to Java 17).
+const MIN_MAJOR_VERSION: u16 = 45;
+pub const MAX_MAJOR_VERSION: u16 = 61;
+
+/// Identifies a class loader: the bootstrap loader built into the VM, or an instance of
+/// `java.lang.ClassLoader`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LoaderId(pub u32);
+
+impl LoaderId {
+	pub const BOOTSTRAP: LoaderId = LoaderId(0);
+	pub const PLATFORM: LoaderId = LoaderId(1);
+	pub const APPLICATION: LoaderId = LoaderId(2);
+}
+
+/// A user-defined class loader, which decides for itself how to load a class.
+///
+/// `load_class` stands for an invocation of a loader's `loadClass(String)` method. It typically
+/// delegates to its parent with [ClassLoaders::load_class] and defines the classes it finds itself
+/// with [ClassLoaders::define_class], the native behind `ClassLoader.defineClass`. Loaders
+/// implemented in Java are registered with [ClassLoaders::add_java_loader], which runs that
+/// method in the interpreter; other implementations let the VM load classes its own way.
+pub trait UserDefinedLoader: Send + Sync {
+	fn load_class(&self, loaders: &ClassLoaders, loader: LoaderId, name: &str) -> Result<Arc<RuntimeClass>, LinkageError>;
+}
+
+/// A class loader implemented in Java: an instance of a subclass of `java.lang.ClassLoader`, whose
+/// `loadClass(String)` method the interpreter runs on the loading thread. The classes it defines
+/// itself reach [ClassLoaders::define_class] through the static `ClassLoader.defineClass1` native,
+/// and it may ask the bootstrap loader with `ClassLoader.findBootstrapClass` and for the classes it
+/// has loaded with `ClassLoader.findLoadedClass0`.
+struct JavaLoader {
+	loaders: Weak<ClassLoaders>,
+	heap: Weak<Heap>,
+	/// The ClassLoader object, kept alive as long as the loader.
+	object: GlobalRef,
+}
+
+const LOAD_CLASS: &str = "(Ljava/lang/String;)Ljava/lang/Class;";
+
+impl UserDefinedLoader for JavaLoader {
+	fn load_class(&self, _: &ClassLoaders, _: LoaderId, name: &str) -> Result<Arc<RuntimeClass>, LinkageError> {
+		let (Some(loaders), Some(heap)) = (self.loaders.upgrade(), self.heap.upgrade()) else {
+			return Err(LinkageError::Internal("the VM of a Java class loader has shut down".to_string()));
+		};
+		let object = heap.global_ref(self.object).ok_or_else(|| LinkageError::Internal("the ClassLoader object was deleted".to_string()))?;
+		let class = heap.class_of(object).ok_or_else(|| LinkageError::Internal("the ClassLoader object is not on the heap".to_string()))?;
+		let mut candidate = Some(class.clone());
+		let declaring = loop {
+			match candidate {
+				Some(declaring) if declaring.class.find_method("loadClass", LOAD_CLASS).is_some_and(|method| method.code().is_some()) => break declaring,
+				Some(superclass) => candidate = superclass.super_class.clone(),
+				None => return Err(LinkageError::NoSuchMethod(format!("{}.loadClass{}", class.name, LOAD_CLASS))),
+			}
+		};
+		let dotted = name.replace('/', ".");
+		let result = heap.intern(&dotted).and_then(|string| {
+			let arguments = vec![heap.variable(object), heap.variable(string)];
+			Interpreter::for_method(loaders.clone(), heap.clone(), declaring, "loadClass", LOAD_CLASS, arguments)?.execute()
+		});
+		let mirror = match result {
+			Ok(mirror) => mirror.reference(),
+			Err(error) => return Err(match error.downcast::<LinkageError>() {
+				Ok(error) => *error,
+				Err(error) => LinkageError::Internal(error.to_string()),
+			}),
+		};
+		mirror.and_then(|mirror| heap.mirrored_class(mirror)).ok_or(LinkageError::ClassNotFound(dotted))
+	}
+}
+
+enum Delegate {
+	ClassPath(ClassPath),
+	UserDefined(Box<dyn UserDefinedLoader>),
+}
+
+struct Loader {
+	name: String,
+	parent: Option<LoaderId>,
+	delegate: Delegate,
+}
+
+/// A loading constraint (JVMS17 5.3.4): every loader in `loaders` must load the same class for `name`.
+#[derive(Debug)]
+struct LoadingConstraint {
+	name: String,
+	loaders: BTreeSet<LoaderId>,
+}
+
+/// The class loaders of a VM and the method area they load into (JVMS17 5.3).
+///
+/// The built-in loaders search class paths with parent delegation: the application loader asks the
+/// platform loader, which asks the bootstrap loader, before searching its own class path.
+/// User-defined loaders decide for themselves.
+pub struct ClassLoaders {
+	loaders: RwLock<Vec<Arc<Loader>>>,
+	method_area: MethodArea,
+	constraints: Mutex<Vec<LoadingConstraint>>,
+	/// The classes each thread is deriving, to catch a class that is its own supertype.
+	placeholders: Mutex<HashSet<(ThreadId, LoaderId, String)>>,
+	/// The loaders implemented in Java, by ClassLoader object.
+	java_loaders: RwLock<HashMap<Reference, LoaderId>>,
+}
+
+impl fmt::Debug for ClassLoaders {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let names: Vec<String> = self.loaders.read().unwrap().iter().map(|loader| loader.name.clone()).collect();
+		f.debug_struct("ClassLoaders").field("loaders", &names).field("method_area", &self.method_area).finish()
+	}
+}
+
+impl ClassLoaders {
+
+	/// The built-in loaders: the bootstrap loader over `boot_class_path`, usually a JDK runtime image,
+	/// a platform loader with an empty class path and the application loader over `class_path`.
+	pub fn new(boot_class_path: ClassPath, class_path: ClassPath) -> ClassLoaders {
+		let loaders = vec![
+			Arc::new(Loader { name: "bootstrap".to_string(), parent: None, delegate: Delegate::ClassPath(boot_class_path) }),
+			Arc::new(Loader { name: "platform".to_string(), parent: Some(LoaderId::BOOTSTRAP), delegate: Delegate::ClassPath(ClassPath::new()) }),
+			Arc::new(Loader { name: "app".to_string(), parent: Some(LoaderId::PLATFORM), delegate: Delegate::ClassPath(class_path) }),
+		];
+		ClassLoaders {
+			loaders: RwLock::new(loaders),
+			method_area: MethodArea::new(),
+			constraints: Mutex::new(Vec::new()),
+			placeholders: Mutex::new(HashSet::new()),
+			java_loaders: RwLock::new(HashMap::new()),
+		}
+	}
+
+	pub fn platform_class_path(mut self, class_path: ClassPath) -> Self {
+		self.loaders.get_mut().unwrap()[LoaderId::PLATFORM.0 as usize] = Arc::new(Loader {
+			name: "platform".to_string(),
+			parent: Some(LoaderId::BOOTSTRAP),
+			delegate: Delegate::ClassPath(class_path),
+		});
+		self
+	}
+
+	/// Register a user-defined loader whose parent is `parent`, as constructing a ClassLoader does.
+	pub fn add_user_defined<L: UserDefinedLoader + 'static>(&self, name: &str, parent: LoaderId, loader: L) -> LoaderId {
+		let mut loaders = self.loaders.write().unwrap();
+		loaders.push(Arc::new(Loader { name: name.to_string(), parent: Some(parent), delegate: Delegate::UserDefined(Box::new(loader)) }));
+		LoaderId(loaders.len() as u32 - 1)
+	}
+
+	/// Register the ClassLoader object `object` on `heap` as a loader implemented in Java, whose
+	/// parent is `parent`. A global handle keeps the object alive as long as the loader.
+	pub fn add_java_loader(self: &Arc<Self>, heap: &Arc<Heap>, name: &str, parent: LoaderId, object: Reference) -> LoaderId {
+		let loader = JavaLoader { loaders: Arc::downgrade(self), heap: Arc::downgrade(heap), object: heap.new_global_ref(object) };
+		let id = self.add_user_defined(name, parent, loader);
+		self.java_loaders.write().unwrap().insert(object, id);
+		id
+	}
+
+	/// The loader a ClassLoader object was registered as with [ClassLoaders::add_java_loader].
+	pub fn java_loader(&self, object: Reference) -> Option<LoaderId> {
+		self.java_loaders.read().unwrap().get(&object).copied()
+	}
+
+	pub fn method_area(&self) -> &MethodArea {
+		&self.method_area
+	}
+
+	pub fn name(&self, loader: LoaderId) -> String {
+		self.loader(loader).map(|loader| loader.name.clone()).unwrap_or_else(|_| format!("#{}", loader.0))
+	}
+
+	/// The parent a loader delegates to, absent for the bootstrap loader.
+	pub fn parent(&self, loader: LoaderId) -> Option<LoaderId> {
+		self.loader(loader).ok()?.parent
+	}
+
+	fn loader(&self, loader: LoaderId) -> Result<Arc<Loader>, LinkageError> {
+		self.loaders.read().unwrap().get(loader.0 as usize).cloned()
+			.ok_or_else(|| LinkageError::Internal(format!("no class loader #{}", loader.0)))
+	}
+
+	/// Load the class named `name` with `loader` as the initiating loader (JVMS17 5.3.1, 5.3.2),
+	/// returning the class it loaded before if there is one.
+	pub fn load_class(&self, loader: LoaderId, name: &str) -> Result<Arc<RuntimeClass>, LinkageError> {
+		if let Some(class) = self.method_area.find(loader, name) {
+			return Ok(class);
+		}
+		if name.starts_with('[') {
+			return self.load_array_class(loader, name);
+		}
+		let initiating = self.loader(loader)?;
+		let class = match &initiating.delegate {
+			Delegate::ClassPath(class_path) => self.load_from_class_path(loader, initiating.parent, class_path, name)?,
+			Delegate::UserDefined(user_defined) => user_defined.load_class(self, loader, name)?,
+		};
+		if class.name != name {
+			return Err(LinkageError::NoClassDefFound(format!("{} (wrong name: {})", name, class.name)));
+		}
+		self.record(loader, class)
+	}
+
+	fn load_from_class_path(&self, loader: LoaderId, parent: Option<LoaderId>, class_path: &ClassPath, name: &str) -> Result<Arc<RuntimeClass>, LinkageError> {
+		if let Some(parent) = parent {
+			match self.load_class(parent, name) {
+				Err(LinkageError::ClassNotFound(_)) => {},
+				result => return result,
+			}
+		}
+		let entry = class_path.find_class_bytes(name)
+			.map_err(|error| LinkageError::Internal(error.to_string()))?
+			.ok_or_else(|| LinkageError::ClassNotFound(name.replace('/', ".")))?;
+		match self.define_class(loader, Some(name), &entry.bytes) {
+			// Another thread defined the class first.
+			Err(error @ LinkageError::DuplicateClassDefinition { .. }) => self.method_area.find(loader, name).ok_or(error),
+			result => result,
+		}
+	}
+
+	/// Create the array class named `name` with `loader` as the initiating loader (JVMS17 5.3.3).
+	///
+	/// `loader` loads the component type, and the array class is defined by the component's defining
+	/// loader, or the bootstrap loader for arrays of primitives. Every array class extends
+	/// java/lang/Object and implements Cloneable and Serializable.
+	fn load_array_class(&self, loader: LoaderId, name: &str) -> Result<Arc<RuntimeClass>, LinkageError> {
+		let component_name = &name[1..];
+		let component = match component_name.as_bytes() {
+			[b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z'] => None,
+			[b'[', ..] => Some(self.load_class(loader, component_name)?),
+			[b'L', .., b';'] => Some(self.load_class(loader, &component_name[1..component_name.len() - 1])?),
+			_ => return Err(LinkageError::ClassNotFound(name.replace('/', "."))),
+		};
+		let defining = component.as_ref().map_or(LoaderId::BOOTSTRAP, |component| component.loader);
+		let class = match self.method_area.find(defining, name) {
+			Some(class) => class,
+			None => {
+				let super_class = self.load_referenced_class(LoaderId::BOOTSTRAP, OBJECT)?;
+				let interfaces = ARRAY_INTERFACES.iter()
+					.map(|interface| self.load_referenced_class(LoaderId::BOOTSTRAP, interface))
+					.collect::<Result<Vec<_>, _>>()?;
+				let mut flags = vec![ClassAccessPropertyFlags::Final, ClassAccessPropertyFlags::Abstract];
+				if component.as_ref().is_none_or(|component| component.is_public()) {
+					flags.push(ClassAccessPropertyFlags::Public);
+				}
+				let class = ARRAY_INTERFACES.iter().fold(ClassBuilder::new(name).flags(&flags), |builder, interface| builder.interface(interface))
+					.build()
+					.map_err(|error| LinkageError::Internal(error.msg))?;
+				self.record(defining, Arc::new(RuntimeClass::new_array(defining, class, super_class, interfaces, component)))?
+			}
+		};
+		self.record(loader, class)
+	}
+
+	/// Derive a class from class file bytes and define it with `loader` as the defining loader
+	/// (JVMS17 5.3.5), as `ClassLoader.defineClass` does. `name` is the name the class is expected
+	/// to have, if known.
+	pub fn define_class(&self, loader: LoaderId, name: Option<&str>, bytes: &[u8]) -> Result<Arc<RuntimeClass>, LinkageError> {
+		let class: Class = Cursor::new(bytes).read_be().map_err(|error| LinkageError::ClassFormat {
+			name: name.unwrap_or("<unknown>").to_string(),
+			message: error.to_string(),
+		})?;
+		let actual = class.name();
+		if !(MIN_MAJOR_VERSION..=MAX_MAJOR_VERSION).contains(&class.major_version) {
+			return Err(LinkageError::UnsupportedClassVersion { name: actual, major: class.major_version, minor: class.minor_version });
+		}
+		if let Some(name) = name && name != actual {
+			return Err(LinkageError::NoClassDefFound(format!("{} (wrong name: {})", name, actual)));
+		}
+		if self.method_area.find(loader, &actual).is_some() {
+			return Err(LinkageError::DuplicateClassDefinition { loader: self.name(loader), name: actual });
+		}
+		if actual.starts_with("java/") && loader != LoaderId::BOOTSTRAP && loader != LoaderId::PLATFORM {
+			return Err(LinkageError::ProhibitedPackage(actual.rsplit_once('/').map_or("", |(package, _)| package).replace('/', ".")));
+		}
+
+		let placeholder = (thread::current().id(), loader, actual.clone());
+		if !self.placeholders.lock().unwrap().insert(placeholder.clone()) {
+			return Err(LinkageError::ClassCircularity(actual));
+		}
+		let supertypes = self.load_supertypes(loader, &class);
+		self.placeholders.lock().unwrap().remove(&placeholder);
+		let (super_class, interfaces) = supertypes?;
+
+		self.record(loader, Arc::new(RuntimeClass::new(loader, class, super_class, interfaces)))
+	}
+
+	/// Load the direct superclass and superinterfaces of a class being derived, checking that each
+	/// is the right kind of class.
+	#[allow(clippy::type_complexity)]
+	fn load_supertypes(&self, loader: LoaderId, class: &Class) -> Result<(Option<Arc<RuntimeClass>>, Vec<Arc<RuntimeClass>>), LinkageError> {
+		let name = class.name();
+		let super_class = match class.super_class_name() {
+			Some(super_name) => {
+				let super_class = self.load_referenced_class(loader, &super_name)?;
+				if super_class.is_interface() {
+					return Err(LinkageError::IncompatibleClassChange(format!("class {} has interface {} as super class", name, super_name)));
+				}
+				if class.is_interface() && super_name != "java/lang/Object" {
+					return Err(LinkageError::ClassFormat { name, message: "interface must extend java/lang/Object".to_string() });
+				}
+				Some(super_class)
+			}
+			None if name == "java/lang/Object" => None,
+			None => return Err(LinkageError::ClassFormat { name, message: "missing super class".to_string() }),
+		};
+		let interfaces = class.interface_names().iter().map(|interface_name| {
+			let interface = self.load_referenced_class(loader, interface_name)?;
+			if !interface.is_interface() {
+				return Err(LinkageError::IncompatibleClassChange(format!("class {} can not implement {}, because it is not an interface", name, interface_name)));
+			}
+			Ok(interface)
+		}).collect::<Result<Vec<_>, _>>()?;
+		Ok((super_class, interfaces))
+	}
+
+	/// Load a class that another class refers to, reporting a missing one as NoClassDefFoundError
+	/// rather than ClassNotFoundException.
+	pub(crate) fn load_referenced_class(&self, loader: LoaderId, name: &str) -> Result<Arc<RuntimeClass>, LinkageError> {
+		self.load_class(loader, name).map_err(|error| match error {
+			LinkageError::ClassNotFound(_) => LinkageError::NoClassDefFound(name.to_string()),
+			error => error,
+		})
+	}
+
+	/// Record `class` in the method area under `loader`, provided no loading constraint on its name
+	/// ties `loader` to a loader that has loaded a different class.
+	fn record(&self, loader: LoaderId, class: Arc<RuntimeClass>) -> Result<Arc<RuntimeClass>, LinkageError> {
+		let constraints = self.constraints.lock().unwrap();
+		let constrained = constraints.iter()
+			.filter(|constraint| constraint.name == class.name && constraint.loaders.contains(&loader))
+			.flat_map(|constraint| constraint.loaders.iter());
+		for other in constrained {
+			if let Some(existing) = self.method_area.find(*other, &class.name) && existing != class {
+				return Err(self.violation(&class.name, loader, *other));
+			}
+		}
+		match self.method_area.record(loader, class.clone()) {
+			Ok(()) => Ok(class),
+			Err(existing) if existing == class => Ok(existing),
+			Err(_) => Err(LinkageError::DuplicateClassDefinition { loader: self.name(loader), name: class.name.clone() }),
+		}
+	}
+
+	/// Impose the loading constraint that `first` and `second` load the same class named `name`,
+	/// failing if they already load different ones.
+	pub fn add_loading_constraint(&self, name: &str, first: LoaderId, second: LoaderId) -> Result<(), LinkageError> {
+		if first == second {
+			return Ok(());
+		}
+		let mut constraints = self.constraints.lock().unwrap();
+		let touches = |constraint: &LoadingConstraint| constraint.name == name && (constraint.loaders.contains(&first) || constraint.loaders.contains(&second));
+		let mut loaders = BTreeSet::from([first, second]);
+		for constraint in constraints.iter().filter(|constraint| touches(constraint)) {
+			loaders.extend(constraint.loaders.iter());
+		}
+
+		let mut loaded: Option<(LoaderId, Arc<RuntimeClass>)> = None;
+		for loader in &loaders {
+			if let Some(class) = self.method_area.find(*loader, name) {
+				match &loaded {
+					Some((other, existing)) if *existing != class => return Err(self.violation(name, *other, *loader)),
+					Some(_) => {},
+					None => loaded = Some((*loader, class)),
+				}
+			}
+		}
+
+		constraints.retain(|constraint| !touches(constraint));
+		constraints.push(LoadingConstraint { name: name.to_string(), loaders });
+		Ok(())
+	}
+
+	/// Impose loading constraints between two loaders for every class named in a field or method
+	/// descriptor, as linking a member reference across loaders does (JVMS17 5.4.3.2, 5.4.3.3).
+	pub fn add_descriptor_constraints(&self, descriptor: &str, first: LoaderId, second: LoaderId) -> Result<(), LinkageError> {
+		descriptor_classes(descriptor).iter().try_for_each(|name| self.add_loading_constraint(name, first, second))
+	}
+
+	fn violation(&self, name: &str, first: LoaderId, second: LoaderId) -> LinkageError {
+		LinkageError::LoaderConstraintViolation(format!("loaders {} and {} have different classes for {}", self.name(first), self.name(second), name))
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use std::{collections::HashMap, path::Path, sync::Arc};
+
+	use crate::{
+		class::{
+			access::{ClassAccessPropertyFlags, FieldAccessPropertyFlags, MethodAccessPropertyFlags},
+			archive::Entry,
+			builder::ClassBuilder,
+			class_path::ClassPath},
+		isa::opcode::Opcode,
+		vm::{
+			class_loader::{ClassLoaders, LoaderId, UserDefinedLoader, LOAD_CLASS},
+			errors::LinkageError,
+			gc::HeapOptions,
+			heap::Heap,
+			interpreter::Interpreter,
+			method_area::RuntimeClass,
+			native::DEFINE_CLASS1,
+			types::*}};
+
+	/// A loader in the manner of a Java ClassLoader overriding `loadClass` child-first: it defines
+	/// the classes it holds and delegates everything else to `parent`.
+	struct MapLoader {
+		parent: LoaderId,
+		classes: HashMap<String, Vec<u8>>,
+	}
+
+	impl MapLoader {
+		fn new(parent: LoaderId, classes: &[(&str, Vec<u8>)]) -> MapLoader {
+			MapLoader { parent, classes: classes.iter().map(|(name, bytes)| (name.to_string(), bytes.clone())).collect() }
+		}
+	}
+
+	impl UserDefinedLoader for MapLoader {
+		fn load_class(&self, loaders: &ClassLoaders, loader: LoaderId, name: &str) -> Result<Arc<RuntimeClass>, LinkageError> {
+			match self.classes.get(name) {
+				Some(bytes) => loaders.define_class(loader, Some(name), bytes),
+				None => loaders.load_class(self.parent, name),
+			}
+		}
+	}
+
+	pub(crate) fn bytes(builder: ClassBuilder) -> Vec<u8> {
+		builder.build().unwrap().to_bytes().unwrap()
+	}
+
+	/// A class without a superclass, as only java/lang/Object may be.
+	pub(crate) fn root(builder: ClassBuilder) -> Vec<u8> {
+		let mut class = builder.build().unwrap();
+		class.super_class = None;
+		class.to_bytes().unwrap()
+	}
+
+	pub(crate) fn interface(name: &str) -> Vec<u8> {
+		bytes(ClassBuilder::new(name).flags(&[ClassAccessPropertyFlags::Public, ClassAccessPropertyFlags::Interface, ClassAccessPropertyFlags::Abstract]))
+	}
+
+	fn in_memory(name: &str, classes: &[(&str, Vec<u8>)]) -> ClassPath {
+		let entries = classes.iter().map(|(class_name, bytes)| Entry { name: format!("{}.class", class_name), bytes: bytes.clone() }).collect();
+		let mut class_path = ClassPath::new();
+		class_path.add_entries(Path::new(name), entries);
+		class_path
+	}
+
+	/// Loaders whose boot and application class paths hold the given classes in memory.
+	pub(crate) fn boot_loaders(name: &str, boot: &[(&str, Vec<u8>)], application: &[(&str, Vec<u8>)]) -> ClassLoaders {
+		ClassLoaders::new(in_memory(&format!("{}-boot", name), boot), in_memory(&format!("{}-app", name), application))
+	}
+
+	/// java/lang/Object, the interfaces of arrays and java/lang/String, with the fields the heap
+	/// stores a string's characters in.
+	fn core_classes() -> Vec<(&'static str, Vec<u8>)> {
+		let string = ClassBuilder::new("java/lang/String")
+			.field(&[FieldAccessPropertyFlags::Private, FieldAccessPropertyFlags::Final], "value", "[B")
+			.field(&[FieldAccessPropertyFlags::Private, FieldAccessPropertyFlags::Final], "coder", "B");
+		vec![
+			("java/lang/Object", root(ClassBuilder::new("java/lang/Object"))),
+			("java/lang/Cloneable", interface("java/lang/Cloneable")),
+			("java/io/Serializable", interface("java/io/Serializable")),
+			("java/lang/String", bytes(string)),
+		]
+	}
+
+	/// Loaders with only the core classes on the boot class path.
+	pub(crate) fn loaders(name: &str, application: &[(&str, Vec<u8>)]) -> ClassLoaders {
+		boot_loaders(name, &core_classes(), application)
+	}
+
+	#[test]
+	fn test_parent_delegation() {
+		let loaders = loaders("delegation", &[
+			("java/lang/Object", bytes(ClassBuilder::new("java/lang/Object"))),
+			("demo/Marker", interface("demo/Marker")),
+			("demo/Base", bytes(ClassBuilder::new("demo/Base"))),
+			("demo/Derived", bytes(ClassBuilder::new("demo/Derived").super_class("demo/Base").interface("demo/Marker"))),
+		]);
+		let derived = loaders.load_class(LoaderId::APPLICATION, "demo/Derived").unwrap();
+		assert_eq!(derived.loader, LoaderId::APPLICATION);
+		assert_eq!(derived.super_classes().map(|class| (class.name.as_str(), class.loader)).collect::<Vec<_>>(),
+			vec![("demo/Base", LoaderId::APPLICATION), ("java/lang/Object", LoaderId::BOOTSTRAP)]);
+		assert_eq!(derived.interfaces[0].name, "demo/Marker");
+		assert!(Arc::ptr_eq(&derived, &loaders.load_class(LoaderId::APPLICATION, "demo/Derived").unwrap()));
+
+		// The application's own java/lang/Object is never seen: the bootstrap loader is asked first.
+		let object = loaders.method_area().find(LoaderId::APPLICATION, "java/lang/Object").unwrap();
+		assert_eq!(object.loader, LoaderId::BOOTSTRAP);
+		assert_eq!(loaders.method_area().initiating_loaders(&object), vec![LoaderId::BOOTSTRAP, LoaderId::PLATFORM, LoaderId::APPLICATION]);
+		assert_eq!(loaders.method_area().classes().len(), 4);
+
+		assert_eq!(loaders.load_class(LoaderId::APPLICATION, "demo/Missing").unwrap_err(), LinkageError::ClassNotFound("demo.Missing".to_string()));
+		assert_eq!(loaders.load_class(LoaderId::BOOTSTRAP, "demo/Base").unwrap_err().exception_class(), "java/lang/ClassNotFoundException");
+	}
+
+	#[test]
+	fn test_user_defined_loaders() {
+		let base = bytes(ClassBuilder::new("demo/Base"));
+		let loaders = loaders("user-defined", &[("demo/Base", base.clone())]);
+		let first = loaders.add_user_defined("first", LoaderId::APPLICATION, MapLoader::new(LoaderId::APPLICATION, &[("demo/Base", base.clone())]));
+		let second = loaders.add_user_defined("second", LoaderId::APPLICATION, MapLoader::new(LoaderId::APPLICATION, &[("demo/Base", base.clone())]));
+		let delegating = loaders.add_user_defined("delegating", LoaderId::APPLICATION, MapLoader::new(LoaderId::APPLICATION, &[]));
+		assert_eq!(loaders.parent(first), Some(LoaderId::APPLICATION));
+
+		let first_base = loaders.load_class(first, "demo/Base").unwrap();
+		let second_base = loaders.load_class(second, "demo/Base").unwrap();
+		let application_base = loaders.load_class(delegating, "demo/Base").unwrap();
+		assert_eq!((first_base.loader, second_base.loader, application_base.loader), (first, second, LoaderId::APPLICATION));
+		assert_ne!(first_base, second_base);
+		assert_ne!(first_base.package(), second_base.package());
+		assert_eq!(first_base.package().name, "demo");
+		assert_eq!(first_base.super_class.as_ref().unwrap().loader, LoaderId::BOOTSTRAP);
+
+		assert!(matches!(loaders.define_class(first, None, &base), Err(LinkageError::DuplicateClassDefinition { .. })));
+		let evil = bytes(ClassBuilder::new("java/lang/Evil"));
+		assert_eq!(loaders.define_class(first, None, &evil).unwrap_err(), LinkageError::ProhibitedPackage("java.lang".to_string()));
+	}
+
+	#[test]
+	fn test_java_loaders() {
+		const LOAD: &str = "(Ljava/lang/String;Z)Ljava/lang/Class;";
+		const FIND: &str = "(Ljava/lang/String;)Ljava/lang/Class;";
+		const DEFINE: &str = "(Ljava/lang/String;[BII)Ljava/lang/Class;";
+		let statics = &[MethodAccessPropertyFlags::Protected, MethodAccessPropertyFlags::Static, MethodAccessPropertyFlags::Native];
+		let protected = &[MethodAccessPropertyFlags::Protected];
+		let finals = &[MethodAccessPropertyFlags::Protected, MethodAccessPropertyFlags::Final];
+		// The parts of the JDK's ClassLoader that loadClass runs, without the locking and the parent.
+		let class_loader = ClassBuilder::new("java/lang/ClassLoader")
+			.flags(&[ClassAccessPropertyFlags::Public, ClassAccessPropertyFlags::Abstract])
+			.method(&[MethodAccessPropertyFlags::Public], "loadClass", LOAD_CLASS, |method| {
+				method.var(Opcode::ALoad, 0).var(Opcode::ALoad, 1).int(Opcode::BIpush, 0)
+					.invoke(Opcode::InvokeVirtual, "java/lang/ClassLoader", "loadClass", LOAD, false).op(Opcode::AReturn);
+			})
+			.method(protected, "loadClass", LOAD, |method| {
+				let found = method.new_label();
+				method.var(Opcode::ALoad, 0).var(Opcode::ALoad, 1).invoke(Opcode::InvokeVirtual, "java/lang/ClassLoader", "findLoadedClass", FIND, false)
+					.op(Opcode::Dup).jump(Opcode::IfNonNull, found).op(Opcode::Pop)
+					.var(Opcode::ALoad, 1).invoke(Opcode::InvokeStatic, "java/lang/ClassLoader", "findBootstrapClass", FIND, false)
+					.op(Opcode::Dup).jump(Opcode::IfNonNull, found).op(Opcode::Pop)
+					.var(Opcode::ALoad, 0).var(Opcode::ALoad, 1).invoke(Opcode::InvokeVirtual, "java/lang/ClassLoader", "findClass", FIND, false)
+					.label(found).op(Opcode::AReturn);
+			})
+			.method(protected, "findClass", FIND, |method| {
+				method.op(Opcode::AConstNull).op(Opcode::AReturn);
+			})
+			.method(finals, "findLoadedClass", FIND, |method| {
+				method.var(Opcode::ALoad, 0).var(Opcode::ALoad, 1)
+					.invoke(Opcode::InvokeSpecial, "java/lang/ClassLoader", "findLoadedClass0", FIND, false).op(Opcode::AReturn);
+			})
+			.method(finals, "defineClass", DEFINE, |method| {
+				method.var(Opcode::ALoad, 0).var(Opcode::ALoad, 1).var(Opcode::ALoad, 2).var(Opcode::ILoad, 3).var(Opcode::ILoad, 4)
+					.op(Opcode::AConstNull).op(Opcode::AConstNull)
+					.invoke(Opcode::InvokeStatic, "java/lang/ClassLoader", "defineClass1", DEFINE_CLASS1, false).op(Opcode::AReturn);
+			})
+			.method(&[MethodAccessPropertyFlags::Private, MethodAccessPropertyFlags::Native], "findLoadedClass0", FIND, |_| {})
+			.method(statics, "defineClass1", DEFINE_CLASS1, |_| {})
+			.method(statics, "findBootstrapClass", FIND, |_| {});
+		let mut boot = core_classes();
+		boot.extend([("java/lang/Class", bytes(ClassBuilder::new("java/lang/Class"))), ("java/lang/ClassLoader", bytes(class_loader))]);
+		// An ordinary subclass: it finds classes by defining them from its bytes, and overrides
+		// loadClass only to call the inherited one.
+		let memory_loader = ClassBuilder::new("demo/MemoryLoader")
+			.super_class("java/lang/ClassLoader")
+			.field(&[FieldAccessPropertyFlags::Static], "bytes", "[B")
+			.method(protected, "loadClass", LOAD, |method| {
+				method.var(Opcode::ALoad, 0).var(Opcode::ALoad, 1).var(Opcode::ILoad, 2)
+					.invoke(Opcode::InvokeSpecial, "java/lang/ClassLoader", "loadClass", LOAD, false).op(Opcode::AReturn);
+			})
+			.method(protected, "findClass", FIND, |method| {
+				method.var(Opcode::ALoad, 0).var(Opcode::ALoad, 1).field(Opcode::GetStatic, "demo/MemoryLoader", "bytes", "[B")
+					.int(Opcode::BIpush, 0).field(Opcode::GetStatic, "demo/MemoryLoader", "bytes", "[B").op(Opcode::ArrayLength)
+					.invoke(Opcode::InvokeVirtual, "demo/MemoryLoader", "defineClass", DEFINE, false).op(Opcode::AReturn);
+			});
+		let widget = bytes(ClassBuilder::new("demo/Widget")
+			.method(&[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Static], "answer", "()I", |method| {
+				method.push_int(42).op(Opcode::IReturn);
+			}));
+		let loaders = Arc::new(boot_loaders("java-loaders", &boot, &[("demo/MemoryLoader", bytes(memory_loader))]));
+		let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions::default()));
+		let loader_class = loaders.load_class(LoaderId::APPLICATION, "demo/MemoryLoader").unwrap();
+		loaders.link_class(&loader_class).unwrap();
+		let array = heap.allocate_array(&loaders.load_class(LoaderId::BOOTSTRAP, "[B").unwrap(), widget.len() as u32).unwrap();
+		for (index, byte) in widget.iter().enumerate() {
+			heap.put_element(array, index as u32, &Variable::Byte(Byte { value: *byte as i8 }));
+		}
+		assert!(heap.put_static(&loader_class, "bytes", "[B", Variable::ArrayReference(ArrayReference { value: array })));
+		let object = heap.allocate(&loader_class).unwrap();
+		let memory = loaders.add_java_loader(&heap, "memory", LoaderId::APPLICATION, object);
+		assert_eq!(loaders.java_loader(object), Some(memory));
+
+		// Its loadClass method finds Object with the bootstrap loader and defines Widget itself.
+		let widget = loaders.load_class(memory, "demo/Widget").unwrap();
+		assert_eq!(widget.loader, memory);
+		assert_eq!(widget.super_class.as_ref().unwrap().loader, LoaderId::BOOTSTRAP);
+		assert!(Arc::ptr_eq(&loaders.load_class(memory, "java/lang/Object").unwrap(), widget.super_class.as_ref().unwrap()));
+		let answer = Interpreter::for_method(loaders.clone(), heap.clone(), widget.clone(), "answer", "()I", Vec::new()).unwrap().execute().unwrap();
+		assert_eq!(answer, Variable::Int(Int { value: 42 }));
+
+		// Asked again, findLoadedClass returns the class it defined rather than defining another.
+		let arguments = vec![heap.variable(object), heap.variable(heap.intern("demo.Widget").unwrap())];
+		let again = Interpreter::for_method(loaders.clone(), heap.clone(), loader_class.super_class.clone().unwrap(), "loadClass", LOAD_CLASS, arguments).unwrap().execute().unwrap();
+		assert_eq!(again.reference().and_then(|mirror| heap.mirrored_class(mirror)), Some(widget.clone()));
+
+		// The loader and the mirrors of the classes it returned outlive collections.
+		heap.collect();
+		let mirror = heap.mirror(&widget).unwrap();
+		assert_eq!(heap.mirrored_class(mirror), Some(widget.clone()));
+		assert_eq!(heap.class_of(object), Some(loader_class));
+		assert!(matches!(loaders.load_class(memory, "demo/Gadget").unwrap_err(), LinkageError::NoClassDefFound(_)));
+	}
+
+	#[test]
+	fn test_loading_constraints() {
+		let base = bytes(ClassBuilder::new("demo/Base"));
+		let loaders = loaders("constraints", &[("demo/Base", base.clone())]);
+		let first = loaders.add_user_defined("first", LoaderId::APPLICATION, MapLoader::new(LoaderId::APPLICATION, &[("demo/Base", base.clone())]));
+		let second = loaders.add_user_defined("second", LoaderId::APPLICATION, MapLoader::new(LoaderId::APPLICATION, &[("demo/Base", base.clone())]));
+		let delegating = loaders.add_user_defined("delegating", LoaderId::APPLICATION, MapLoader::new(LoaderId::APPLICATION, &[]));
+
+		// Both sides already loaded, with different classes.
+		loaders.load_class(first, "demo/Base").unwrap();
+		loaders.load_class(LoaderId::APPLICATION, "demo/Base").unwrap();
+		assert!(matches!(loaders.add_loading_constraint("demo/Base", first, LoaderId::APPLICATION), Err(LinkageError::LoaderConstraintViolation(_))));
+
+		// Constrained first, then a loader tries to load its own class for the name.
+		loaders.add_descriptor_constraints("(Ldemo/Base;[Ljava/lang/Object;)V", delegating, second).unwrap();
+		loaders.load_class(delegating, "demo/Base").unwrap();
+		assert!(matches!(loaders.load_class(second, "demo/Base"), Err(LinkageError::LoaderConstraintViolation(_))));
+		assert!(loaders.method_area().find(second, "demo/Base").is_none());
+		loaders.add_loading_constraint("java/lang/Object", first, second).unwrap();
+	}
+
+	#[test]
+	fn test_loading_errors() {
+		let loaders = loaders("errors", &[
+			("demo/A", bytes(ClassBuilder::new("demo/A").super_class("demo/B"))),
+			("demo/B", bytes(ClassBuilder::new("demo/B").super_class("demo/A"))),
+			("demo/Marker", interface("demo/Marker")),
+			("demo/Base", bytes(ClassBuilder::new("demo/Base"))),
+			("demo/ExtendsInterface", bytes(ClassBuilder::new("demo/ExtendsInterface").super_class("demo/Marker"))),
+			("demo/ImplementsClass", bytes(ClassBuilder::new("demo/ImplementsClass").interface("demo/Base"))),
+			("demo/Orphan", bytes(ClassBuilder::new("demo/Orphan").super_class("demo/Gone"))),
+			("demo/Future", bytes(ClassBuilder::new("demo/Future").version(62, 0))),
+			("demo/Renamed", bytes(ClassBuilder::new("demo/Base"))),
+			("demo/Garbage", vec![0xCA, 0xFE]),
+		]);
+		let load = |name: &str| loaders.load_class(LoaderId::APPLICATION, name).unwrap_err();
+		assert!(matches!(load("demo/A"), LinkageError::ClassCircularity(_)));
+		assert!(loaders.method_area().find(LoaderId::APPLICATION, "demo/B").is_none());
+		assert!(matches!(load("demo/ExtendsInterface"), LinkageError::IncompatibleClassChange(_)));
+		assert!(matches!(load("demo/ImplementsClass"), LinkageError::IncompatibleClassChange(_)));
+		assert_eq!(load("demo/Orphan"), LinkageError::NoClassDefFound("demo/Gone".to_string()));
+		assert_eq!(load("demo/Future"), LinkageError::UnsupportedClassVersion { name: "demo/Future".to_string(), major: 62, minor: 0 });
+		assert_eq!(load("demo/Renamed"), LinkageError::NoClassDefFound("demo/Renamed (wrong name: demo/Base)".to_string()));
+		assert_eq!(load("demo/Garbage").exception_class(), "java/lang/ClassFormatError");
+	}
+
+	#[test]
+	fn test_array_classes() {
+		let loaders = loaders("array-classes", &[
+			("demo/Shape", bytes(ClassBuilder::new("demo/Shape"))),
+			("demo/Hidden", bytes(ClassBuilder::new("demo/Hidden").flags(&[ClassAccessPropertyFlags::Super]))),
+		]);
+		let load = |name: &str| loaders.load_class(LoaderId::APPLICATION, name);
+
+		let ints = load("[I").unwrap();
+		assert_eq!((ints.loader, ints.is_public(), ints.component.is_none()), (LoaderId::BOOTSTRAP, true, true));
+		assert_eq!(ints.super_class.as_ref().unwrap().name, "java/lang/Object");
+		assert_eq!(ints.interfaces.iter().map(|interface| interface.name.as_str()).collect::<Vec<_>>(), vec!["java/lang/Cloneable", "java/io/Serializable"]);
+		assert_eq!(loaders.method_area().initiating_loaders(&ints), vec![LoaderId::BOOTSTRAP, LoaderId::APPLICATION]);
+
+		let matrix = load("[[Ldemo/Shape;").unwrap();
+		assert_eq!(matrix.loader, LoaderId::APPLICATION);
+		let shapes = matrix.component.clone().unwrap();
+		assert_eq!(shapes.name, "[Ldemo/Shape;");
+		assert_eq!(shapes.component.as_ref().unwrap().name, "demo/Shape");
+		assert!(Arc::ptr_eq(&shapes, &load("[Ldemo/Shape;").unwrap()));
+		assert!(!load("[Ldemo/Hidden;").unwrap().is_public());
+		let objects = load("[Ljava/lang/Object;").unwrap();
+		assert_eq!(objects.loader, LoaderId::BOOTSTRAP);
+
+		assert!(shapes.is_assignable_to(&objects));
+		assert!(matrix.is_assignable_to(&objects));
+		assert!(!objects.is_assignable_to(&shapes));
+		assert!(!ints.is_assignable_to(&objects));
+		assert!(ints.is_assignable_to(&load("[I").unwrap()));
+		assert!(!ints.is_assignable_to(&load("[J").unwrap()));
+		assert!(ints.is_assignable_to(ints.super_class.as_ref().unwrap()));
+		assert!(ints.is_assignable_to(&ints.interfaces[1]));
+		assert!(!shapes.component.as_ref().unwrap().is_assignable_to(&ints.interfaces[0]));
+
+		assert_eq!(load("[X").unwrap_err(), LinkageError::ClassNotFound("[X".to_string()));
+		assert_eq!(load("[Ldemo/Missing;").unwrap_err(), LinkageError::ClassNotFound("demo.Missing".to_string()));
+	}
+}
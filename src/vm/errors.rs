@@ -2,7 +2,7 @@ use thiserror::Error;
 
 use crate::vm::types::Type;
 
-#[derive(Error, Debug)]
+#[derive(Error, Clone, Debug, PartialEq)]
 pub enum ExecutionError {
 	#[error("illegal opcode: {0}")]
 	#[from(TryFromPrimitiveError)]
@@ -17,4 +17,107 @@ pub enum ExecutionError {
 	JumpOutOfBounds(u32, usize),
 	#[error("Attempt to return {0} from method type {1}")]
 	BadReturnType(Type, Type),
-}
\ No newline at end of file
+	#[error("java.lang.NullPointerException: {0}")]
+	NullPointer(String),
+	#[error("java.lang.ArrayIndexOutOfBoundsException: {0}")]
+	ArrayIndexOutOfBounds(String),
+	#[error("java.lang.NegativeArraySizeException: {0}")]
+	NegativeArraySize(String),
+	#[error("java.lang.ArrayStoreException: {0}")]
+	ArrayStore(String),
+	#[error("java.lang.OutOfMemoryError: {0}")]
+	OutOfMemory(String),
+}
+
+impl ExecutionError {
+	/// Whether Java code sees this as a java.lang.Error rather than an exception. The interpreter's
+	/// own faults are neither, since no Java code throws them.
+	pub fn is_error(&self) -> bool {
+		matches!(self, ExecutionError::StackOverflow | ExecutionError::OutOfMemory(_))
+	}
+}
+
+/// An error in the options a VM is started with, reported before it runs any code.
+#[derive(Error, Clone, Debug, PartialEq)]
+pub enum VmOptionError {
+	#[error("Unrecognized option: {0}")]
+	Unrecognized(String),
+	#[error("Improperly specified VM option '{0}'")]
+	Improper(String),
+	#[error("Invalid {kind} heap size: {option}")]
+	InvalidHeapSize { kind: &'static str, option: String },
+	#[error("Initial heap size set to a larger value than the maximum heap size")]
+	IncompatibleHeapSizes,
+}
+
+/// An error raised while loading, linking or initializing a class, reported to Java code as the
+/// exception it is named after (JVMS17 5.3, 5.4, 5.5).
+#[derive(Error, Clone, Debug, PartialEq)]
+pub enum LinkageError {
+	#[error("java.lang.ClassNotFoundException: {0}")]
+	ClassNotFound(String),
+	#[error("java.lang.NoClassDefFoundError: {0}")]
+	NoClassDefFound(String),
+	#[error("java.lang.ClassFormatError: {name}: {message}")]
+	ClassFormat { name: String, message: String },
+	#[error("java.lang.UnsupportedClassVersionError: {name} has class file version {major}.{minor}")]
+	UnsupportedClassVersion { name: String, major: u16, minor: u16 },
+	#[error("java.lang.ClassCircularityError: {0}")]
+	ClassCircularity(String),
+	#[error("java.lang.IncompatibleClassChangeError: {0}")]
+	IncompatibleClassChange(String),
+	#[error("java.lang.LinkageError: loader {loader} attempted duplicate class definition for {name}")]
+	DuplicateClassDefinition { loader: String, name: String },
+	#[error("java.lang.LinkageError: loader constraint violation: {0}")]
+	LoaderConstraintViolation(String),
+	#[error("java.lang.NoSuchFieldError: {0}")]
+	NoSuchField(String),
+	#[error("java.lang.NoSuchMethodError: {0}")]
+	NoSuchMethod(String),
+	#[error("java.lang.IllegalAccessError: {0}")]
+	IllegalAccess(String),
+	#[error("java.lang.InstantiationError: {0}")]
+	Instantiation(String),
+	#[error("java.lang.AbstractMethodError: {0}")]
+	AbstractMethod(String),
+	#[error("java.lang.ExceptionInInitializerError: {class}: {cause}")]
+	ExceptionInInitializer { class: String, cause: String },
+	/// An Error a class initializer threw, which initialization passes on as it is (JVMS17 5.5).
+	#[error("{0}")]
+	Error(ExecutionError),
+	#[error("java.lang.SecurityException: prohibited package name: {0}")]
+	ProhibitedPackage(String),
+	#[error("java.lang.InternalError: {0}")]
+	Internal(String),
+}
+
+impl LinkageError {
+	/// Whether Java code sees this as a java.lang.Error rather than an exception.
+	pub fn is_error(&self) -> bool {
+		!matches!(self, LinkageError::ClassNotFound(_) | LinkageError::ProhibitedPackage(_))
+	}
+
+	/// The internal name of the Java exception class that reports this error.
+	pub fn exception_class(&self) -> &'static str {
+		match self {
+			LinkageError::ClassNotFound(_) => "java/lang/ClassNotFoundException",
+			LinkageError::NoClassDefFound(_) => "java/lang/NoClassDefFoundError",
+			LinkageError::ClassFormat { .. } => "java/lang/ClassFormatError",
+			LinkageError::UnsupportedClassVersion { .. } => "java/lang/UnsupportedClassVersionError",
+			LinkageError::ClassCircularity(_) => "java/lang/ClassCircularityError",
+			LinkageError::IncompatibleClassChange(_) => "java/lang/IncompatibleClassChangeError",
+			LinkageError::DuplicateClassDefinition { .. } | LinkageError::LoaderConstraintViolation(_) => "java/lang/LinkageError",
+			LinkageError::NoSuchField(_) => "java/lang/NoSuchFieldError",
+			LinkageError::NoSuchMethod(_) => "java/lang/NoSuchMethodError",
+			LinkageError::IllegalAccess(_) => "java/lang/IllegalAccessError",
+			LinkageError::Instantiation(_) => "java/lang/InstantiationError",
+			LinkageError::AbstractMethod(_) => "java/lang/AbstractMethodError",
+			LinkageError::ExceptionInInitializer { .. } => "java/lang/ExceptionInInitializerError",
+			LinkageError::Error(ExecutionError::StackOverflow) => "java/lang/StackOverflowError",
+			LinkageError::Error(ExecutionError::OutOfMemory(_)) => "java/lang/OutOfMemoryError",
+			LinkageError::Error(_) => "java/lang/Error",
+			LinkageError::ProhibitedPackage(_) => "java/lang/SecurityException",
+			LinkageError::Internal(_) => "java/lang/InternalError",
+		}
+	}
+}
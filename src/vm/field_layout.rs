@@ -0,0 +1,125 @@
+use crate::class::{access::FieldAccessPropertyFlags, class::Class};
+
+/// The size of an object header: a lock word and identity hash in eight bytes, then a four byte
+/// class pointer.
+pub const HEADER_SIZE: u32 = 12;
+
+/// The size of an array header: an object header followed by a four byte length.
+pub const ARRAY_HEADER_SIZE: u32 = 16;
+
+/// Objects start on this boundary, so instance sizes are rounded up to it.
+pub const OBJECT_ALIGNMENT: u32 = 8;
+
+/// The number of bytes an instance field with the given descriptor takes, references included.
+pub fn field_size(descriptor: &str) -> u32 {
+	match descriptor.as_bytes().first() {
+		Some(b'Z' | b'B') => 1,
+		Some(b'C' | b'S') => 2,
+		Some(b'J' | b'D') => 8,
+		_ => 4,
+	}
+}
+
+/// Where an instance field lives within an object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldSlot {
+	/// The name of the class declaring the field.
+	pub class: String,
+	pub name: String,
+	pub descriptor: String,
+	/// The offset from the start of the object, past the header.
+	pub offset: u32,
+}
+
+impl FieldSlot {
+	pub fn size(&self) -> u32 {
+		field_size(&self.descriptor)
+	}
+}
+
+/// The instance fields of a class and where each lives in its objects.
+///
+/// Superclass fields come first, at the offsets they have in the superclass, so code compiled
+/// against a superclass finds them in objects of any subclass. A class lays out the fields it
+/// declares largest first, each aligned to its size, which leaves little padding between them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FieldLayout {
+	pub fields: Vec<FieldSlot>,
+	/// The offset just past the last field, before rounding to [OBJECT_ALIGNMENT].
+	pub end: u32,
+}
+
+impl FieldLayout {
+
+	/// The layout of objects of `class`, whose superclass has the layout `super_layout`.
+	pub fn new(super_layout: Option<&FieldLayout>, class: &Class) -> FieldLayout {
+		let mut layout = super_layout.cloned().unwrap_or(FieldLayout { fields: Vec::new(), end: HEADER_SIZE });
+		let class_name = class.name();
+		let mut declared: Vec<(String, String)> = class.fields.fields.iter()
+			.filter(|field| field.access_flags & FieldAccessPropertyFlags::Static as u16 == 0)
+			.filter_map(|field| Some((class.get_utf8(field.name_index)?, class.get_utf8(field.descriptor_index)?)))
+			.collect();
+		declared.sort_by_key(|(_, descriptor)| std::cmp::Reverse(field_size(descriptor)));
+		for (name, descriptor) in declared {
+			let size = field_size(&descriptor);
+			let offset = layout.end.next_multiple_of(size);
+			layout.end = offset + size;
+			layout.fields.push(FieldSlot { class: class_name.clone(), name, descriptor, offset });
+		}
+		layout
+	}
+
+	/// The size of an object with this layout, header included.
+	pub fn instance_size(&self) -> u32 {
+		self.end.next_multiple_of(OBJECT_ALIGNMENT)
+	}
+
+	/// The slot of the field declared by `class` with the given name and descriptor.
+	pub fn find(&self, class: &str, name: &str, descriptor: &str) -> Option<&FieldSlot> {
+		self.fields.iter().find(|slot| slot.class == class && slot.name == name && slot.descriptor == descriptor)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{
+		class::{access::FieldAccessPropertyFlags, builder::ClassBuilder},
+		vm::field_layout::{FieldLayout, HEADER_SIZE}};
+
+	#[test]
+	fn test_field_layout() {
+		let instance = &[FieldAccessPropertyFlags::Private];
+		let base = ClassBuilder::new("demo/Base")
+			.field(instance, "flag", "Z")
+			.field(instance, "total", "J")
+			.field(&[FieldAccessPropertyFlags::Static], "shared", "D")
+			.build().unwrap();
+		let derived = ClassBuilder::new("demo/Derived").super_class("demo/Base")
+			.field(instance, "letter", "C")
+			.field(instance, "next", "Ldemo/Base;")
+			.field(instance, "total", "J")
+			.build().unwrap();
+
+		let base_layout = FieldLayout::new(None, &base);
+		let offsets: Vec<(&str, u32)> = base_layout.fields.iter().map(|slot| (slot.name.as_str(), slot.offset)).collect();
+		assert_eq!(offsets, vec![("total", 16), ("flag", 24)]);
+		assert_eq!(base_layout.instance_size(), 32);
+
+		let layout = FieldLayout::new(Some(&base_layout), &derived);
+		let offsets: Vec<(&str, &str, u32)> = layout.fields.iter().map(|slot| (slot.class.as_str(), slot.name.as_str(), slot.offset)).collect();
+		assert_eq!(offsets, vec![
+			("demo/Base", "total", 16),
+			("demo/Base", "flag", 24),
+			("demo/Derived", "total", 32),
+			("demo/Derived", "next", 40),
+			("demo/Derived", "letter", 44),
+		]);
+		assert_eq!(layout.end, 46);
+		assert_eq!(layout.instance_size(), 48);
+		assert_eq!(layout.find("demo/Base", "total", "J").unwrap().offset, 16);
+		assert!(layout.find("demo/Derived", "flag", "Z").is_none());
+
+		let empty = FieldLayout::new(None, &ClassBuilder::new("demo/Empty").build().unwrap());
+		assert_eq!((empty.end, empty.instance_size()), (HEADER_SIZE, 16));
+	}
+}
@@ -33,7 +33,7 @@ impl StackFrame {
 			return_type: Type::V,
 			invoker: Option::None,
 			pc: 0,
-			operand_stack: OperandStack { max_depth: usize::MAX, stack: Vec::new() },
+			operand_stack: OperandStack::new(),
 			locals: Locals { variables: HashMap::new() },
 			constant_pool: ConstantPool::new(), code: Vec::new() };
 		frame
@@ -0,0 +1,240 @@
+use std::{fmt, time::Duration};
+
+use crate::vm::errors::VmOptionError;
+
+/// The heap size a VM starts with when `-Xms` does not say.
+pub const DEFAULT_INITIAL_SIZE: usize = 16 << 20;
+
+/// The size a VM's heap may grow to when `-Xmx` does not say.
+pub const DEFAULT_MAXIMUM_SIZE: usize = 256 << 20;
+
+/// The number of minor collections an object survives before promotion when
+/// `-XX:MaxTenuringThreshold` does not say. Ages take four bits of the header, so this is also
+/// the largest threshold.
+pub const MAX_TENURING_THRESHOLD: u8 = 15;
+
+/// How long a soft reference is kept after its last use, in milliseconds per free megabyte of
+/// heap, when `-XX:SoftRefLRUPolicyMSPerMB` does not say.
+pub const DEFAULT_SOFT_REF_LRU_POLICY_MS_PER_MB: u64 = 1000;
+
+/// How the heap collects garbage.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Collector {
+	/// One space, every collection marking and compacting all of it. Simple to follow, so useful
+	/// for debugging the collector.
+	MarkCompact,
+	/// A young generation, where objects are allocated and minor collections copy the survivors,
+	/// and an old generation they are promoted to, collected by marking and compacting the whole
+	/// heap.
+	Generational,
+}
+
+/// How large the heap is, how it collects and how its collector reports.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeapOptions {
+	/// The capacity of the heap before it first grows, from `-Xms`.
+	pub initial_size: usize,
+	/// The capacity the heap may grow to, from `-Xmx`. An allocation that does not fit after a
+	/// full collection at this size throws OutOfMemoryError.
+	pub maximum_size: usize,
+	/// Generational unless `-XX:-UseGenerationalGC` asks for the mark-compact collector.
+	pub collector: Collector,
+	/// The size of the young generation, from `-Xmn`. A third of the initial size when absent,
+	/// and at most half of it.
+	pub young_size: Option<usize>,
+	/// The number of minor collections an object survives before promotion, from
+	/// `-XX:MaxTenuringThreshold`.
+	pub tenuring_threshold: u8,
+	/// How many milliseconds after its last use a softly reachable referent is kept for each
+	/// megabyte the heap could still grow by, from `-XX:SoftRefLRUPolicyMSPerMB`.
+	pub soft_ref_lru_policy_ms_per_mb: u64,
+	/// Whether each collection is written to standard error, from `-Xlog:gc` or `-verbose:gc`.
+	pub log: bool,
+}
+
+impl Default for HeapOptions {
+	fn default() -> Self {
+		HeapOptions {
+			initial_size: DEFAULT_INITIAL_SIZE,
+			maximum_size: DEFAULT_MAXIMUM_SIZE,
+			collector: Collector::Generational,
+			young_size: None,
+			tenuring_threshold: MAX_TENURING_THRESHOLD,
+			soft_ref_lru_policy_ms_per_mb: DEFAULT_SOFT_REF_LRU_POLICY_MS_PER_MB,
+			log: false,
+		}
+	}
+}
+
+impl HeapOptions {
+
+	/// Options from `java` command line arguments: `-Xms<size>`, `-Xmx<size>` and `-Xmn<size>`,
+	/// with sizes in bytes or followed by `k`, `m` or `g`, `-XX:+UseGenerationalGC` or
+	/// `-XX:-UseGenerationalGC`, `-XX:MaxTenuringThreshold=<n>`, `-XX:SoftRefLRUPolicyMSPerMB=<n>`,
+	/// and `-Xlog:gc` or `-verbose:gc`.
+	pub fn parse<S: AsRef<str>>(arguments: &[S]) -> Result<HeapOptions, VmOptionError> {
+		let mut initial = None;
+		let mut maximum = None;
+		let mut options = HeapOptions::default();
+		for argument in arguments {
+			let argument = argument.as_ref();
+			if let Some(size) = argument.strip_prefix("-Xms") {
+				initial = Some(parse_size(size).ok_or_else(|| VmOptionError::InvalidHeapSize { kind: "initial", option: argument.to_string() })?);
+			} else if let Some(size) = argument.strip_prefix("-Xmx") {
+				maximum = Some(parse_size(size).ok_or_else(|| VmOptionError::InvalidHeapSize { kind: "maximum", option: argument.to_string() })?);
+			} else if let Some(size) = argument.strip_prefix("-Xmn") {
+				options.young_size = Some(parse_size(size).ok_or_else(|| VmOptionError::InvalidHeapSize { kind: "new generation", option: argument.to_string() })?);
+			} else if argument == "-XX:+UseGenerationalGC" {
+				options.collector = Collector::Generational;
+			} else if argument == "-XX:-UseGenerationalGC" {
+				options.collector = Collector::MarkCompact;
+			} else if let Some(threshold) = argument.strip_prefix("-XX:MaxTenuringThreshold=") {
+				options.tenuring_threshold = threshold.parse().ok().filter(|threshold| *threshold <= MAX_TENURING_THRESHOLD)
+					.ok_or_else(|| VmOptionError::Improper(argument.trim_start_matches("-XX:").to_string()))?;
+			} else if let Some(interval) = argument.strip_prefix("-XX:SoftRefLRUPolicyMSPerMB=") {
+				options.soft_ref_lru_policy_ms_per_mb = interval.parse()
+					.map_err(|_| VmOptionError::Improper(argument.trim_start_matches("-XX:").to_string()))?;
+			} else if argument == "-Xlog:gc" || argument == "-verbose:gc" {
+				options.log = true;
+			} else {
+				return Err(VmOptionError::Unrecognized(argument.to_string()));
+			}
+		}
+		(options.initial_size, options.maximum_size) = match (initial, maximum) {
+			(Some(initial), Some(maximum)) if initial > maximum => return Err(VmOptionError::IncompatibleHeapSizes),
+			(Some(initial), Some(maximum)) => (initial, maximum),
+			(Some(initial), None) => (initial, DEFAULT_MAXIMUM_SIZE.max(initial)),
+			(None, Some(maximum)) => (DEFAULT_INITIAL_SIZE.min(maximum), maximum),
+			(None, None) => (DEFAULT_INITIAL_SIZE, DEFAULT_MAXIMUM_SIZE),
+		};
+		Ok(options)
+	}
+}
+
+/// A size in bytes, or in kilobytes, megabytes or gigabytes with a `k`, `m` or `g` suffix.
+fn parse_size(text: &str) -> Option<usize> {
+	let (digits, shift) = match text.as_bytes().last()? {
+		b'k' | b'K' => (&text[..text.len() - 1], 10),
+		b'm' | b'M' => (&text[..text.len() - 1], 20),
+		b'g' | b'G' => (&text[..text.len() - 1], 30),
+		_ => (text, 0),
+	};
+	if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+		return None;
+	}
+	digits.parse::<usize>().ok()?.checked_mul(1 << shift)
+}
+
+/// How much of the heap a collection covers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GcKind {
+	/// A minor collection of the young generation.
+	Young,
+	/// A collection of the whole heap.
+	Full,
+}
+
+impl fmt::Display for GcKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			GcKind::Young => "Young",
+			GcKind::Full => "Full",
+		})
+	}
+}
+
+/// Why a collection ran.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GcCause {
+	/// An allocation did not fit in the heap.
+	AllocationFailure,
+	/// Java code asked for one, through `System.gc`.
+	SystemGc,
+}
+
+impl fmt::Display for GcCause {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			GcCause::AllocationFailure => "Allocation Failure",
+			GcCause::SystemGc => "System.gc()",
+		})
+	}
+}
+
+/// A collection, as `-Xlog:gc` reports it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GcEvent {
+	/// The number of collections before this one.
+	pub id: usize,
+	pub kind: GcKind,
+	pub cause: GcCause,
+	/// The bytes in use before and after the collection.
+	pub before: usize,
+	pub after: usize,
+	/// The capacity of the heap when the collection finished.
+	pub capacity: usize,
+	pub pause: Duration,
+}
+
+impl fmt::Display for GcEvent {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "GC({}) Pause {} ({}) {}->{}({}) {:.3}ms", self.id, self.kind, self.cause, ByteSize(self.before), ByteSize(self.after), ByteSize(self.capacity), self.pause.as_secs_f64() * 1000.0)
+	}
+}
+
+/// A size in the largest unit that keeps at least three digits, as HotSpot logs sizes.
+struct ByteSize(usize);
+
+impl fmt::Display for ByteSize {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.0 {
+			size if size >= 100 << 30 => write!(f, "{}G", size >> 30),
+			size if size >= 100 << 20 => write!(f, "{}M", size >> 20),
+			size if size >= 100 << 10 => write!(f, "{}K", size >> 10),
+			size => write!(f, "{}B", size),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use crate::vm::{
+		errors::VmOptionError,
+		gc::{Collector, GcCause, GcEvent, GcKind, HeapOptions, DEFAULT_INITIAL_SIZE, DEFAULT_MAXIMUM_SIZE}};
+
+	#[test]
+	fn test_heap_options() {
+		assert_eq!(HeapOptions::parse::<&str>(&[]), Ok(HeapOptions::default()));
+		assert_eq!(HeapOptions::parse(&["-Xms2m", "-Xmx1G", "-Xlog:gc"]), Ok(HeapOptions { initial_size: 2 << 20, maximum_size: 1 << 30, log: true, ..HeapOptions::default() }));
+		assert_eq!(HeapOptions::parse(&["-Xmx64k", "-verbose:gc"]), Ok(HeapOptions { initial_size: 64 << 10, maximum_size: 64 << 10, log: true, ..HeapOptions::default() }));
+		assert_eq!(HeapOptions::parse(&["-Xmn4m", "-XX:MaxTenuringThreshold=3", "-XX:-UseGenerationalGC"]),
+			Ok(HeapOptions { young_size: Some(4 << 20), tenuring_threshold: 3, collector: Collector::MarkCompact, ..HeapOptions::default() }));
+		assert_eq!(HeapOptions::parse(&["-XX:-UseGenerationalGC", "-XX:+UseGenerationalGC"]).unwrap().collector, Collector::Generational);
+		assert_eq!(HeapOptions::parse(&["-Xms1g"]).unwrap().maximum_size, 1 << 30);
+		assert_eq!(HeapOptions::parse(&["-Xmx4096"]).unwrap().initial_size, 4096);
+		assert_eq!(HeapOptions::parse(&["-Xmx1g"]).unwrap().initial_size, DEFAULT_INITIAL_SIZE);
+		assert_eq!(HeapOptions::parse(&["-Xms32m"]).unwrap().maximum_size, DEFAULT_MAXIMUM_SIZE);
+
+		assert_eq!(HeapOptions::parse(&["-Xmx12q"]), Err(VmOptionError::InvalidHeapSize { kind: "maximum", option: "-Xmx12q".to_string() }));
+		assert_eq!(HeapOptions::parse(&["-Xms"]).unwrap_err().to_string(), "Invalid initial heap size: -Xms");
+		assert_eq!(HeapOptions::parse(&["-Xms-1m"]).unwrap_err().to_string(), "Invalid initial heap size: -Xms-1m");
+		assert_eq!(HeapOptions::parse(&["-Xms8m", "-Xmx4m"]), Err(VmOptionError::IncompatibleHeapSizes));
+		assert_eq!(HeapOptions::parse(&["-Xmn1x"]).unwrap_err().to_string(), "Invalid new generation heap size: -Xmn1x");
+		assert_eq!(HeapOptions::parse(&["-XX:MaxTenuringThreshold=16"]).unwrap_err().to_string(), "Improperly specified VM option 'MaxTenuringThreshold=16'");
+		assert_eq!(HeapOptions::parse(&["-XX:SoftRefLRUPolicyMSPerMB=0"]).unwrap().soft_ref_lru_policy_ms_per_mb, 0);
+		assert_eq!(HeapOptions::parse(&["-XX:SoftRefLRUPolicyMSPerMB=-1"]).unwrap_err().to_string(), "Improperly specified VM option 'SoftRefLRUPolicyMSPerMB=-1'");
+		assert_eq!(HeapOptions::parse(&["-Xss1m"]), Err(VmOptionError::Unrecognized("-Xss1m".to_string())));
+	}
+
+	#[test]
+	fn test_gc_event() {
+		let event = GcEvent { id: 3, kind: GcKind::Full, cause: GcCause::AllocationFailure, before: 300 << 10, after: 12 << 10, capacity: 512 << 20, pause: Duration::from_micros(1250) };
+		assert_eq!(event.to_string(), "GC(3) Pause Full (Allocation Failure) 300K->12288B(512M) 1.250ms");
+		let event = GcEvent { cause: GcCause::SystemGc, before: 200 << 30, ..event };
+		assert_eq!(event.to_string(), "GC(3) Pause Full (System.gc()) 200G->12288B(512M) 1.250ms");
+		let event = GcEvent { kind: GcKind::Young, before: 150 << 20, after: 2 << 20, ..event };
+		assert_eq!(event.to_string(), "GC(3) Pause Young (System.gc()) 150M->2048K(512M) 1.250ms");
+	}
+}
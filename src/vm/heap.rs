@@ -0,0 +1,1722 @@
+use std::{
+	collections::{HashMap, VecDeque},
+	error::Error,
+	num::NonZeroU32,
+	sync::{atomic::{AtomicBool, Ordering}, Arc, Condvar, Mutex, MutexGuard},
+	thread::ThreadId,
+	time::Instant};
+
+use crate::vm::{
+	class_loader::{ClassLoaders, LoaderId},
+	errors::{ExecutionError, LinkageError},
+	field_layout::{field_size, FieldSlot, ARRAY_HEADER_SIZE, HEADER_SIZE, OBJECT_ALIGNMENT},
+	gc::{Collector, GcCause, GcEvent, GcKind, HeapOptions},
+	method_area::RuntimeClass,
+	reference::ReferenceKind,
+	types::*};
+
+/// The coders of java/lang/String, saying how its bytes hold its characters.
+const LATIN1: i8 = 0;
+const UTF16: i8 = 1;
+
+/// A handle to an object on the heap. Null is not a reference, so zero stands for it wherever
+/// references are stored as raw values, on the operand stack and in fields.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Reference(NonZeroU32);
+
+impl Reference {
+	/// The reference a raw value stands for, or None for null.
+	pub fn from_raw(raw: u32) -> Option<Reference> {
+		NonZeroU32::new(raw).map(Reference)
+	}
+
+	pub fn raw(self) -> u32 {
+		self.0.get()
+	}
+}
+
+/// The state of an object's monitor.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LockWord {
+	#[default]
+	Unlocked,
+	/// Held by `owner`, which has entered it `count` times.
+	Locked { owner: ThreadId, count: u32 },
+}
+
+/// The header every object starts with.
+#[derive(Clone, Debug)]
+pub struct Header {
+	pub class: Arc<RuntimeClass>,
+	/// The identity hash, assigned when first asked for.
+	pub hash: Option<i32>,
+	pub lock: LockWord,
+}
+
+/// The generation an object belongs to. Without the generational collector every object is old.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Generation {
+	Young,
+	#[default]
+	Old,
+}
+
+/// An object on the heap: an instance of a class, with the bytes of its instance fields, or an
+/// array, with its elements packed at the size of their type and booleans taking a byte each.
+#[derive(Clone, Debug)]
+pub struct Object {
+	pub header: Header,
+	/// The number of elements of an array, absent for other objects.
+	pub length: Option<u32>,
+	/// Where the object is, in bytes from the start of its space. Collections move objects.
+	pub address: usize,
+	pub generation: Generation,
+	/// The number of minor collections the object has survived in the young generation.
+	pub age: u8,
+	data: Vec<u8>,
+}
+
+impl Object {
+
+	/// An object of `class` with every field holding its default value, which is all zeros.
+	pub fn new(class: Arc<RuntimeClass>) -> Object {
+		let size = class.field_layout().instance_size() - HEADER_SIZE;
+		Object { header: Header { class, hash: None, lock: LockWord::Unlocked }, length: None, address: 0, generation: Generation::Old, age: 0, data: vec![0; size as usize] }
+	}
+
+	/// An array of the array class `class` with `length` elements, each holding its default value.
+	pub fn new_array(class: Arc<RuntimeClass>, length: u32) -> Object {
+		let size = length as usize * class.component_descriptor().map_or(0, field_size) as usize;
+		Object { header: Header { class, hash: None, lock: LockWord::Unlocked }, length: Some(length), address: 0, generation: Generation::Old, age: 0, data: vec![0; size] }
+	}
+
+	/// The number of bytes an array of `class` with `length` elements takes on the heap.
+	pub fn array_size(class: &RuntimeClass, length: u32) -> usize {
+		let data = length as usize * class.component_descriptor().map_or(0, field_size) as usize;
+		(ARRAY_HEADER_SIZE as usize + data).next_multiple_of(OBJECT_ALIGNMENT as usize)
+	}
+
+	/// The number of bytes the object takes on the heap, header included.
+	pub fn size(&self) -> usize {
+		let header = if self.length.is_some() { ARRAY_HEADER_SIZE } else { HEADER_SIZE };
+		(header as usize + self.data.len()).next_multiple_of(OBJECT_ALIGNMENT as usize)
+	}
+
+	/// The objects this one refers to through its fields or elements, each with the offset from
+	/// the start of the object it is stored at.
+	fn references(&self) -> Vec<(usize, Reference)> {
+		let at = |offset: usize, header: u32| {
+			let start = offset - header as usize;
+			Some((offset, Reference::from_raw(u32::from_le_bytes(self.data[start..start + 4].try_into().unwrap()))?))
+		};
+		match (self.length, self.header.class.component_descriptor()) {
+			(Some(length), Some(descriptor)) if descriptor.starts_with(['L', '[']) =>
+				(0..length as usize).filter_map(|index| at(ARRAY_HEADER_SIZE as usize + index * 4, ARRAY_HEADER_SIZE)).collect(),
+			(Some(_), _) => Vec::new(),
+			(None, _) => self.header.class.field_layout().fields.iter()
+				.filter(|slot| slot.descriptor.starts_with(['L', '[']))
+				.filter_map(|slot| at(slot.offset as usize, HEADER_SIZE))
+				.collect(),
+		}
+	}
+
+	/// The referent field of a reference object, None for other objects.
+	fn referent_slot(&self) -> Option<&FieldSlot> {
+		self.header.class.reference_kind()?;
+		self.header.class.field_layout().find("java/lang/ref/Reference", "referent", "Ljava/lang/Object;")
+	}
+
+	/// The value of a field, with references to objects of any kind as class references.
+	fn get(&self, slot: &FieldSlot) -> Variable {
+		let start = (slot.offset - HEADER_SIZE) as usize;
+		decode(&slot.descriptor, &self.data[start..start + slot.size() as usize])
+	}
+
+	fn put(&mut self, slot: &FieldSlot, value: &Variable) -> bool {
+		let Some(bytes) = encode(&slot.descriptor, value) else {
+			return false;
+		};
+		let start = (slot.offset - HEADER_SIZE) as usize;
+		self.data[start..start + bytes.len()].copy_from_slice(&bytes);
+		true
+	}
+
+	/// The descriptor and byte range of an array element, if the index is in bounds.
+	fn element_range(&self, index: u32) -> Option<(&str, std::ops::Range<usize>)> {
+		if index >= self.length? {
+			return None;
+		}
+		let descriptor = self.header.class.component_descriptor()?;
+		let size = field_size(descriptor) as usize;
+		let start = index as usize * size;
+		Some((descriptor, start..start + size))
+	}
+
+	fn element(&self, index: u32) -> Option<Variable> {
+		let (descriptor, range) = self.element_range(index)?;
+		Some(decode(descriptor, &self.data[range]))
+	}
+
+	fn put_element(&mut self, index: u32, value: &Variable) -> bool {
+		let Some((descriptor, range)) = self.element_range(index) else {
+			return false;
+		};
+		let Some(bytes) = encode(descriptor, value) else {
+			return false;
+		};
+		self.data[range].copy_from_slice(&bytes);
+		true
+	}
+}
+
+/// A value of the type of a field descriptor from its stored bytes.
+fn decode(descriptor: &str, bytes: &[u8]) -> Variable {
+	match descriptor.as_bytes()[0] {
+		b'Z' => Variable::Boolean(Boolean { value: bytes[0] != 0 }),
+		b'B' => Variable::Byte(Byte { value: bytes[0] as i8 }),
+		b'C' => Variable::Char(Char { value: u16::from_le_bytes([bytes[0], bytes[1]]) as i32 }),
+		b'S' => Variable::Short(Short { value: i16::from_le_bytes([bytes[0], bytes[1]]) }),
+		b'I' => Variable::Int(Int { value: i32::from_le_bytes(bytes.try_into().unwrap()) }),
+		b'F' => Variable::Float(Float { value: f32::from_le_bytes(bytes.try_into().unwrap()) }),
+		b'J' => Variable::Long(Long { value: i64::from_le_bytes(bytes.try_into().unwrap()) }),
+		b'D' => Variable::Double(Double { value: f64::from_le_bytes(bytes.try_into().unwrap()) }),
+		_ => match Reference::from_raw(u32::from_le_bytes(bytes.try_into().unwrap())) {
+			Some(reference) => Variable::ClassReference(ClassReference { value: reference }),
+			None => NULL,
+		},
+	}
+}
+
+/// The bytes that store a value of the type of a field descriptor, or None if the value is not of
+/// that type.
+fn encode(descriptor: &str, value: &Variable) -> Option<Vec<u8>> {
+	Some(match (descriptor.as_bytes()[0], value) {
+		(b'Z', Variable::Boolean(boolean)) => vec![boolean.value as u8],
+		(b'B', Variable::Byte(byte)) => byte.value.to_le_bytes().to_vec(),
+		(b'C', Variable::Char(char)) => (char.value as u16).to_le_bytes().to_vec(),
+		(b'S', Variable::Short(short)) => short.value.to_le_bytes().to_vec(),
+		(b'I', Variable::Int(int)) => int.value.to_le_bytes().to_vec(),
+		(b'F', Variable::Float(float)) => float.value.to_le_bytes().to_vec(),
+		(b'J', Variable::Long(long)) => long.value.to_le_bytes().to_vec(),
+		(b'D', Variable::Double(double)) => double.value.to_le_bytes().to_vec(),
+		(b'L' | b'[', Variable::Null(..)) => vec![0; 4],
+		(b'L' | b'[', reference) => reference.reference()?.raw().to_le_bytes().to_vec(),
+		_ => return None,
+	})
+}
+
+/// A contiguous space that objects are allocated in by bumping a pointer.
+#[derive(Debug, Default)]
+struct Space {
+	/// The slots of the objects in the space, in address order.
+	order: Vec<usize>,
+	/// The address of the next object: the number of bytes in use.
+	top: usize,
+	capacity: usize,
+}
+
+impl Space {
+	fn with_capacity(capacity: usize) -> Space {
+		Space { capacity, ..Space::default() }
+	}
+
+	fn fits(&self, size: usize) -> bool {
+		self.top + size <= self.capacity
+	}
+
+	/// Put the object in `slot` at the top of the space.
+	fn place(&mut self, slot: usize, object: &mut Object) {
+		object.address = self.top;
+		self.top += object.size();
+		self.order.push(slot);
+	}
+}
+
+/// The objects on the heap and the spaces they take up.
+///
+/// References index a table of objects rather than being addresses, so a collection can move an
+/// object without finding and updating every reference to it. Addresses only place objects in
+/// their space.
+///
+/// Young objects are allocated in eden. A minor collection copies those still reachable to a
+/// survivor space, or promotes them to the old generation once old enough or when the survivor
+/// space is full, and empties eden. The old generation is a single space, which the mark-compact
+/// collector uses for every object.
+#[derive(Debug, Default)]
+struct Objects {
+	/// Objects by reference, less one.
+	slots: Vec<Option<Object>>,
+	/// Slots emptied by collections, reused before the table grows.
+	free: Vec<usize>,
+	eden: Space,
+	/// The survivor space holding young objects between minor collections. A minor collection
+	/// copies into a second space of the same size, which then takes this one's place.
+	survivor: Space,
+	old: Space,
+	/// For each card of the old generation, whether a reference stored in it may refer to a young
+	/// object, so that minor collections need only scan the objects on dirty cards.
+	cards: Vec<bool>,
+	/// The classes whose static fields may refer to young objects.
+	remembered: Vec<Arc<RuntimeClass>>,
+	/// The objects with finalizers that no collection has found unreachable yet. These are not
+	/// roots.
+	finalizable: Vec<Reference>,
+	/// Unreachable objects waiting for the finalizer thread, kept alive until their finalize
+	/// methods return, oldest first.
+	finalization: VecDeque<Reference>,
+	/// References collections have cleared, waiting for the reference handler thread to add them
+	/// to their queues.
+	pending: Vec<Reference>,
+	/// Cleanables collections have cleared, waiting for the reference handler thread to run their
+	/// cleaning actions, kept alive until it has, oldest first.
+	cleaning: VecDeque<Reference>,
+	/// Whether the reference handler and finalizer threads are to stop once nothing is left for
+	/// them.
+	stopping: bool,
+	/// Interned strings by value.
+	strings: HashMap<String, Reference>,
+	/// The java/lang/Class objects made so far, with the class each stands for.
+	mirrors: HashMap<Reference, Arc<RuntimeClass>>,
+	/// The objects global JNI handles refer to, by handle.
+	handles: Vec<Option<Reference>>,
+	/// Every collection so far.
+	events: Vec<GcEvent>,
+	/// The state of the identity hash generator.
+	seed: u32,
+}
+
+impl Objects {
+	fn get(&self, reference: Reference) -> Option<&Object> {
+		self.slots.get(reference.raw() as usize - 1)?.as_ref()
+	}
+
+	fn get_mut(&mut self, reference: Reference) -> Option<&mut Object> {
+		self.slots.get_mut(reference.raw() as usize - 1)?.as_mut()
+	}
+
+	/// A value read from a field or element, with a reference to an array as an array reference.
+	fn typed(&self, value: Variable) -> Variable {
+		match value {
+			Variable::ClassReference(reference) if self.get(reference.value).is_some_and(|object| object.length.is_some()) =>
+				Variable::ArrayReference(ArrayReference { value: reference.value }),
+			value => value,
+		}
+	}
+
+	fn is_young(&self, reference: Reference) -> bool {
+		self.get(reference).is_some_and(|object| object.generation == Generation::Young)
+	}
+
+	fn used(&self) -> usize {
+		self.eden.top + self.survivor.top + self.old.top
+	}
+
+	/// The capacity of the heap, counting both survivor spaces.
+	fn capacity(&self) -> usize {
+		self.eden.capacity + 2 * self.survivor.capacity + self.old.capacity
+	}
+
+	/// Place an object at the top of eden, for a young object, or of the old generation.
+	fn push(&mut self, mut object: Object, generation: Generation) -> Reference {
+		let slot = self.free.pop().unwrap_or_else(|| {
+			self.slots.push(None);
+			self.slots.len() - 1
+		});
+		object.generation = generation;
+		match generation {
+			Generation::Young => self.eden.place(slot, &mut object),
+			Generation::Old => self.old.place(slot, &mut object),
+		}
+		self.slots[slot] = Some(object);
+		Reference::from_raw(slot as u32 + 1).unwrap()
+	}
+
+	/// Store a value in a field of an object, through the write barrier.
+	fn put_field(&mut self, reference: Reference, slot: &FieldSlot, value: &Variable) -> bool {
+		let stored = self.get_mut(reference).is_some_and(|object| object.put(slot, value));
+		if stored {
+			self.write_barrier(reference, slot.offset as usize, value);
+		}
+		stored
+	}
+
+	/// The write barrier: having stored `value` at `offset` in `holder`, dirty the card it was
+	/// stored in if an old object now refers to a young one.
+	fn write_barrier(&mut self, holder: Reference, offset: usize, value: &Variable) {
+		let Some(object) = self.get(holder) else {
+			return;
+		};
+		if object.generation == Generation::Old && value.reference().is_some_and(|reference| self.is_young(reference)) {
+			self.dirty((object.address + offset) / CARD_SIZE);
+		}
+	}
+
+	fn dirty(&mut self, card: usize) {
+		if card >= self.cards.len() {
+			self.cards.resize(card + 1, false);
+		}
+		self.cards[card] = true;
+	}
+
+	/// Dirty the cards of the fields of the old object in `slot` that refer to young objects.
+	fn remember(&mut self, slot: usize) {
+		let Some(object) = &self.slots[slot] else {
+			return;
+		};
+		let cards: Vec<usize> = object.references().into_iter()
+			.filter(|(_, reference)| self.is_young(*reference))
+			.map(|(offset, _)| (object.address + offset) / CARD_SIZE)
+			.collect();
+		for card in cards {
+			self.dirty(card);
+		}
+	}
+
+	/// The slots of the old objects on dirty cards.
+	fn dirty_objects(&self) -> Vec<usize> {
+		let mut slots = Vec::new();
+		for card in self.cards.iter().enumerate().filter(|(_, dirty)| **dirty).map(|(card, _)| card) {
+			let (start, end) = (card * CARD_SIZE, (card + 1) * CARD_SIZE);
+			let object = |slot: &usize| self.slots[*slot].as_ref().unwrap();
+			let first = self.old.order.partition_point(|slot| object(slot).address + object(slot).size() <= start);
+			slots.extend(self.old.order[first..].iter().take_while(|slot| object(slot).address < end));
+		}
+		slots.dedup();
+		slots
+	}
+
+	/// The references the object in `slot` holds that keep what they refer to alive. The referent
+	/// of a reference object is left out, and the object added to `discovered` instead.
+	fn strong_references(&self, slot: usize, discovered: &mut Vec<usize>) -> Vec<Reference> {
+		let object = self.slots[slot].as_ref().unwrap();
+		let mut references = object.references();
+		let referent = object.referent_slot().and_then(|referent| references.iter().position(|(offset, _)| *offset == referent.offset as usize));
+		if let Some(position) = referent {
+			references.swap_remove(position);
+			discovered.push(slot);
+		}
+		references.into_iter().map(|(_, reference)| reference).collect()
+	}
+
+	/// Keep every object strongly reachable from `pending`, discovering the reference objects on
+	/// the way.
+	fn trace<T: Tracer>(&mut self, tracer: &mut T, mut pending: Vec<Reference>, discovered: &mut Vec<usize>) {
+		while let Some(reference) = pending.pop() {
+			let slot = reference.raw() as usize - 1;
+			if self.slots.get(slot).is_none_or(Option::is_none) || tracer.is_kept(self, slot) {
+				continue;
+			}
+			tracer.keep(self, slot);
+			pending.extend(self.strong_references(slot, discovered));
+		}
+	}
+
+	/// The referent of the reference object in `slot`, if the collection has not kept it.
+	fn unkept_referent<T: Tracer>(&self, tracer: &T, slot: usize) -> Option<Reference> {
+		let object = self.slots[slot].as_ref()?;
+		let referent = object.get(object.referent_slot()?).reference()?;
+		(!tracer.is_kept(self, referent.raw() as usize - 1)).then_some(referent)
+	}
+
+	/// Clear the referent of the reference object in `slot` and leave it pending.
+	fn clear_referent(&mut self, slot: usize) {
+		let Some(object) = self.slots[slot].as_mut() else {
+			return;
+		};
+		if let Some(referent) = object.referent_slot().cloned() {
+			object.put(&referent, &NULL);
+			self.pend(slot);
+		}
+	}
+
+	/// Leave the reference object in `slot` for the reference handler, if it was registered with a
+	/// queue.
+	fn pend(&mut self, slot: usize) {
+		let object = self.slots[slot].as_ref().unwrap();
+		let queued = object.header.class.field_layout().find("java/lang/ref/Reference", "queue", "Ljava/lang/ref/ReferenceQueue;")
+			.is_some_and(|queue| object.get(queue).reference().is_some());
+		let reference = Reference::from_raw(slot as u32 + 1).unwrap();
+		if queued && !self.pending.contains(&reference) {
+			self.pending.push(reference);
+		}
+	}
+
+	/// Process the reference objects a trace discovered, once it has kept everything strongly
+	/// reachable, strongest first. Soft references keep their referents while `keep_soft` says so.
+	/// Soft and weak references to referents still not kept are cleared. Then objects with
+	/// finalizers that are not kept are kept for the finalizer thread, with whatever they refer to,
+	/// and last, phantom references to what is left are cleared. The objects kept along the way
+	/// may hold more reference objects, which are processed in turn.
+	fn process_references<T: Tracer>(&mut self, tracer: &mut T, mut discovered: Vec<usize>, keep_soft: &dyn Fn(&Object) -> bool) {
+		let kind = |objects: &Objects, slot: usize| objects.slots[slot].as_ref().and_then(|object| object.header.class.reference_kind());
+		let mut phantoms = Vec::new();
+		let mut finalizing = true;
+		while !discovered.is_empty() || finalizing {
+			let batch = std::mem::take(&mut discovered);
+			for &slot in &batch {
+				if kind(self, slot) == Some(ReferenceKind::Soft) && let Some(referent) = self.unkept_referent(tracer, slot)
+					&& keep_soft(self.slots[slot].as_ref().unwrap()) {
+					self.trace(tracer, vec![referent], &mut discovered);
+				}
+			}
+			for &slot in &batch {
+				if matches!(kind(self, slot), Some(ReferenceKind::Soft | ReferenceKind::Weak)) && self.unkept_referent(tracer, slot).is_some() {
+					self.clear_referent(slot);
+				}
+			}
+			if finalizing {
+				finalizing = false;
+				let (unreachable, reachable): (Vec<Reference>, Vec<Reference>) = std::mem::take(&mut self.finalizable).into_iter()
+					.partition(|object| !tracer.is_kept(self, object.raw() as usize - 1));
+				self.finalizable = reachable;
+				self.trace(tracer, unreachable.clone(), &mut discovered);
+				self.finalization.extend(unreachable);
+			}
+			for &slot in &batch {
+				if kind(self, slot) == Some(ReferenceKind::Final) && let Some(referent) = self.unkept_referent(tracer, slot) {
+					self.trace(tracer, vec![referent], &mut discovered);
+					self.pend(slot);
+				}
+			}
+			phantoms.extend(batch.into_iter().filter(|slot| kind(self, *slot) == Some(ReferenceKind::Phantom)));
+		}
+		for slot in phantoms {
+			if self.unkept_referent(tracer, slot).is_some() {
+				self.clear_referent(slot);
+			}
+		}
+	}
+
+	/// Free every object that cannot be reached from `roots`, then slide the others down to the
+	/// start of the old generation in the order they were, old objects first, so allocation can
+	/// carry on from the top. Young objects are promoted while the old generation stays within
+	/// `old_limit` bytes; any left over are compacted in eden.
+	fn collect_full(&mut self, roots: Vec<Reference>, old_limit: usize, keep_soft: &dyn Fn(&Object) -> bool) {
+		let mut marker = Marker { marked: vec![false; self.slots.len()] };
+		let mut discovered = Vec::new();
+		self.trace(&mut marker, roots, &mut discovered);
+		self.process_references(&mut marker, discovered, keep_soft);
+		let marked = marker.marked;
+		let mut old = Space::with_capacity(self.old.capacity);
+		let mut eden = Space::with_capacity(self.eden.capacity);
+		let order = [&mut self.old, &mut self.eden, &mut self.survivor].map(|space| std::mem::take(&mut space.order));
+		for slot in order.into_iter().flatten() {
+			match self.slots[slot].as_mut() {
+				Some(object) if marked[slot] => {
+					if object.generation == Generation::Old || old.top + object.size() <= old_limit {
+						object.generation = Generation::Old;
+						old.place(slot, object);
+					} else {
+						eden.place(slot, object);
+					}
+				}
+				_ => {
+					self.slots[slot] = None;
+					self.free.push(slot);
+				}
+			}
+		}
+		old.capacity = old.capacity.max(old.top);
+		self.old = old;
+		self.eden = eden;
+		self.survivor = Space::with_capacity(self.survivor.capacity);
+		self.cards.clear();
+		if !self.eden.order.is_empty() {
+			for slot in self.old.order.clone() {
+				self.remember(slot);
+			}
+		}
+	}
+
+	/// Copy the young objects reachable from `roots` or from old objects on dirty cards to a new
+	/// survivor space, promoting those that have survived `tenuring_threshold` collections or do
+	/// not fit, and free the rest. The old generation must have room for every young object.
+	fn collect_young(&mut self, roots: Vec<Reference>, tenuring_threshold: u8, keep_soft: &dyn Fn(&Object) -> bool) {
+		let scanned = self.dirty_objects();
+		let mut discovered = Vec::new();
+		let mut pending = roots;
+		for slot in &scanned {
+			pending.extend(self.strong_references(*slot, &mut discovered));
+		}
+		let mut copier = Copier { copied: vec![false; self.slots.len()], to: Space::with_capacity(self.survivor.capacity), promoted: Vec::new(), tenuring_threshold };
+		self.trace(&mut copier, pending, &mut discovered);
+		self.process_references(&mut copier, discovered, keep_soft);
+		let Copier { copied, to, promoted, .. } = copier;
+		let order = [&mut self.eden, &mut self.survivor].map(|space| std::mem::take(&mut space.order));
+		for slot in order.into_iter().flatten() {
+			if !copied[slot] {
+				self.slots[slot] = None;
+				self.free.push(slot);
+			}
+		}
+		self.eden.top = 0;
+		self.survivor = to;
+		// Old objects that still refer to young ones keep their cards dirty.
+		self.cards.clear();
+		for slot in scanned.into_iter().chain(promoted) {
+			self.remember(slot);
+		}
+	}
+
+	/// Add the reference object `reference` to the ReferenceQueue it was registered with, as
+	/// ReferenceQueue.enqueue does, unless that is one of `sentinels`: the queues
+	/// ReferenceQueue.NULL and ENQUEUED, which stand for no queue and being enqueued already.
+	fn enqueue(&mut self, reference: Reference, sentinels: [Option<Reference>; 2]) -> bool {
+		let Some(object) = self.get(reference) else {
+			return false;
+		};
+		let layout = object.header.class.field_layout();
+		let (Some(queue_slot), Some(next_slot)) = (layout.find("java/lang/ref/Reference", "queue", "Ljava/lang/ref/ReferenceQueue;").cloned(),
+			layout.find("java/lang/ref/Reference", "next", "Ljava/lang/ref/Reference;").cloned()) else {
+			return false;
+		};
+		let Some(queue) = object.get(&queue_slot).reference().filter(|queue| !sentinels.contains(&Some(*queue))) else {
+			return false;
+		};
+		let Some(queue_object) = self.get(queue) else {
+			return false;
+		};
+		let layout = queue_object.header.class.field_layout();
+		let (Some(head_slot), Some(length_slot)) = (layout.find("java/lang/ref/ReferenceQueue", "head", "Ljava/lang/ref/Reference;").cloned(),
+			layout.find("java/lang/ref/ReferenceQueue", "queueLength", "J").cloned()) else {
+			return false;
+		};
+		let head = queue_object.get(&head_slot).reference();
+		let Variable::Long(Long { value: length }) = queue_object.get(&length_slot) else {
+			return false;
+		};
+		let object = |reference: Reference| Variable::ClassReference(ClassReference { value: reference });
+		self.put_field(reference, &queue_slot, &sentinels[1].map_or(NULL, object));
+		self.put_field(reference, &next_slot, &object(head.unwrap_or(reference)));
+		self.put_field(queue, &head_slot, &object(reference));
+		self.put_field(queue, &length_slot, &Variable::Long(Long { value: length + 1 }));
+		true
+	}
+}
+
+/// How a collection keeps the objects it reaches.
+trait Tracer {
+	/// Whether the object in `slot` is kept, having been reached already or being one the
+	/// collection does not free.
+	fn is_kept(&self, objects: &Objects, slot: usize) -> bool;
+
+	/// Keep the object in `slot`, which is not kept yet.
+	fn keep(&mut self, objects: &mut Objects, slot: usize);
+}
+
+/// A full collection marks the objects it keeps, then compacts them.
+struct Marker {
+	marked: Vec<bool>,
+}
+
+impl Tracer for Marker {
+	fn is_kept(&self, _: &Objects, slot: usize) -> bool {
+		self.marked[slot]
+	}
+
+	fn keep(&mut self, _: &mut Objects, slot: usize) {
+		self.marked[slot] = true;
+	}
+}
+
+/// A minor collection keeps every old object and copies the young ones it reaches to a new
+/// survivor space, promoting those that have survived `tenuring_threshold` collections or do not
+/// fit.
+struct Copier {
+	copied: Vec<bool>,
+	to: Space,
+	promoted: Vec<usize>,
+	tenuring_threshold: u8,
+}
+
+impl Tracer for Copier {
+	fn is_kept(&self, objects: &Objects, slot: usize) -> bool {
+		self.copied[slot] || objects.slots[slot].as_ref().is_none_or(|object| object.generation == Generation::Old)
+	}
+
+	fn keep(&mut self, objects: &mut Objects, slot: usize) {
+		let object = objects.slots[slot].as_mut().unwrap();
+		self.copied[slot] = true;
+		if object.age < self.tenuring_threshold && self.to.fits(object.size()) {
+			object.age += 1;
+			self.to.place(slot, object);
+		} else {
+			object.generation = Generation::Old;
+			objects.old.place(slot, object);
+			self.promoted.push(slot);
+		}
+	}
+}
+
+/// The number of bytes of the old generation each card covers.
+const CARD_SIZE: usize = 512;
+
+/// Eden is this many times the size of each survivor space.
+const SURVIVOR_RATIO: usize = 8;
+
+/// The threads that may hold references, as collections see them.
+#[derive(Debug, Default)]
+struct Threads {
+	/// The references held by the frames each thread has published, innermost last.
+	frames: HashMap<ThreadId, Vec<Vec<Reference>>>,
+	/// The number of interpreters each thread has running. A thread is at a safepoint while it has
+	/// published a frame for each, since its references are then all in published frames.
+	running: HashMap<ThreadId, usize>,
+	/// The thread that has stopped the others to collect.
+	collector: Option<ThreadId>,
+}
+
+impl Threads {
+	fn at_safepoint(&self, thread: ThreadId) -> bool {
+		self.running.get(&thread).is_none_or(|running| *running <= self.frames.get(&thread).map_or(0, Vec::len))
+	}
+
+	/// Whether a thread other than the current one is collecting.
+	fn stopped(&self) -> bool {
+		self.collector.is_some_and(|collector| collector != std::thread::current().id())
+	}
+}
+
+/// Every thread but the collecting one stopped at a safepoint, until dropped.
+struct StoppedWorld<'a>(&'a Heap);
+
+impl Drop for StoppedWorld<'_> {
+	fn drop(&mut self) {
+		let mut threads = self.0.threads.lock().unwrap();
+		threads.collector = None;
+		self.0.stopping.store(false, Ordering::Release);
+		self.0.safepoints.notify_all();
+	}
+}
+
+/// A global JNI handle, which keeps the object it refers to alive until deleted.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct GlobalRef(usize);
+
+/// The heap that objects are allocated on, shared by every thread of a VM.
+///
+/// Objects are allocated by bumping a pointer through a space. With the mark-compact collector,
+/// an allocation that does not fit runs a full collection, which frees whatever the roots do not
+/// reach and compacts what is left; if that is not enough the heap grows, up to its maximum size,
+/// and beyond that the allocation throws OutOfMemoryError.
+///
+/// With the generational collector objects are allocated in the young generation, and a minor
+/// collection empties it when it fills up. Stores of references into old objects and static
+/// fields go through a write barrier, which remembers where old objects may refer to young ones,
+/// so a minor collection does not scan the old generation. Objects too large for eden are
+/// allocated in the old generation, and when it cannot take every young object a full collection
+/// runs instead.
+///
+/// The roots are static fields, interned strings, class mirrors, global JNI handles and the frames
+/// of every thread. A collection first stops the other threads running Java code at a safepoint,
+/// where they publish their frames: between instructions, or around anything that may allocate or
+/// call another method. Objects allocated at a safepoint join the innermost frame published.
+///
+/// Collections discover the reference objects they reach (java.lang.ref) and do not trace their
+/// referents. Once everything strongly reachable is kept, softly reachable referents are kept
+/// while recently used, by HotSpot's policy: for as many milliseconds since the reference's
+/// timestamp as `-XX:SoftRefLRUPolicyMSPerMB` gives for each megabyte the heap could still grow
+/// by. When an allocation still fails after a full collection, one more clears every soft
+/// reference before OutOfMemoryError is thrown. Objects whose class overrides finalize are
+/// registered when allocated, and a collection that finds one unreachable keeps it for the
+/// finalizer thread; weak references to it are cleared first, and phantom references only once
+/// it is unreachable after finalization.
+#[derive(Debug)]
+pub struct Heap {
+	/// The loaders whose classes' static fields are roots.
+	loaders: Arc<ClassLoaders>,
+	options: HeapOptions,
+	/// The size the old generation may grow to.
+	old_limit: usize,
+	objects: Mutex<Objects>,
+	threads: Mutex<Threads>,
+	/// Notified when a thread reaches or leaves a safepoint, and when a collection is over.
+	safepoints: Condvar,
+	/// Whether a collection is waiting for the threads running Java code to reach a safepoint.
+	stopping: AtomicBool,
+	/// Notified when a collection leaves references pending or objects to finalize, and when
+	/// the reference handler and finalizer threads are to stop.
+	handling: Condvar,
+	/// When the heap was created, which the soft reference clock counts from.
+	started: Instant,
+}
+
+impl Heap {
+
+	pub fn new(loaders: Arc<ClassLoaders>, options: HeapOptions) -> Heap {
+		let young = match options.collector {
+			Collector::MarkCompact => 0,
+			Collector::Generational => options.young_size.unwrap_or(options.initial_size / 3).min(options.initial_size / 2),
+		};
+		let survivor = young / (SURVIVOR_RATIO + 2);
+		let objects = Objects {
+			eden: Space::with_capacity(young - 2 * survivor),
+			survivor: Space::with_capacity(survivor),
+			old: Space::with_capacity(options.initial_size - young),
+			..Objects::default()
+		};
+		let old_limit = options.maximum_size - young;
+		Heap { loaders, options, old_limit, objects: Mutex::new(objects), threads: Mutex::default(), safepoints: Condvar::new(), stopping: AtomicBool::new(false), handling: Condvar::new(), started: Instant::now() }
+	}
+
+	/// Allocate an object of `class` with its fields set to their default values.
+	pub fn allocate(&self, class: &Arc<RuntimeClass>) -> Result<Reference, ExecutionError> {
+		let size = class.field_layout().instance_size() as usize;
+		self.place(size, class.has_finalizer(), || Object::new(class.clone()))
+	}
+
+	/// Allocate an array of the array class `class` with its elements set to their default values.
+	pub fn allocate_array(&self, class: &Arc<RuntimeClass>, length: u32) -> Result<Reference, ExecutionError> {
+		let size = Object::array_size(class, length);
+		self.place(size, false, || Object::new_array(class.clone(), length))
+	}
+
+	/// Place an object of `size` bytes on the heap, collecting or growing the heap if it does not
+	/// fit, and made only once it does. A `finalizable` object is registered for finalization.
+	fn place<F: FnOnce() -> Object>(&self, size: usize, finalizable: bool, make: F) -> Result<Reference, ExecutionError> {
+		let mut objects = self.objects.lock().unwrap();
+		let fits = match size <= objects.eden.capacity {
+			true => objects.eden.fits(size),
+			false => objects.old.fits(size),
+		};
+		let _stopped = match fits {
+			true => None,
+			false => {
+				// The other threads may need the lock to reach a safepoint.
+				drop(objects);
+				let stopped = self.stop_world();
+				objects = self.objects.lock().unwrap();
+				Some(stopped)
+			}
+		};
+		let mut generation = match size <= objects.eden.capacity {
+			true => Generation::Young,
+			false => Generation::Old,
+		};
+		let mut collected = false;
+		if generation == Generation::Young && !objects.eden.fits(size) {
+			let young = objects.eden.top + objects.survivor.top;
+			if self.grow_old(&mut objects, young) {
+				self.collect_locked(&mut objects, GcKind::Young, GcCause::AllocationFailure, false);
+			} else {
+				self.collect_locked(&mut objects, GcKind::Full, GcCause::AllocationFailure, false);
+				collected = true;
+			}
+			// Only young objects a full collection could not promote leave eden short of room.
+			if !objects.eden.fits(size) {
+				generation = Generation::Old;
+			}
+		}
+		if generation == Generation::Old && !objects.old.fits(size) {
+			if !collected {
+				self.collect_locked(&mut objects, GcKind::Full, GcCause::AllocationFailure, false);
+			}
+			if !self.grow_old(&mut objects, size) {
+				self.collect_locked(&mut objects, GcKind::Full, GcCause::AllocationFailure, true);
+				if !self.grow_old(&mut objects, size) {
+					return Err(ExecutionError::OutOfMemory("Java heap space".to_string()));
+				}
+			}
+		}
+		let reference = objects.push(make(), generation);
+		if finalizable {
+			objects.finalizable.push(reference);
+		}
+		self.publish(reference);
+		Ok(reference)
+	}
+
+	/// Make room for `size` more bytes in the old generation, growing it if need be. False if it
+	/// cannot grow that far.
+	fn grow_old(&self, objects: &mut Objects, size: usize) -> bool {
+		let needed = objects.old.top + size;
+		if needed > self.old_limit {
+			return false;
+		}
+		if needed > objects.old.capacity {
+			objects.old.capacity = needed.max(objects.old.capacity * 2).min(self.old_limit);
+		}
+		true
+	}
+
+	/// Run a full collection, as `System.gc` asks for.
+	pub fn collect(&self) {
+		let _stopped = self.stop_world();
+		let mut objects = self.objects.lock().unwrap();
+		self.collect_locked(&mut objects, GcKind::Full, GcCause::SystemGc, false);
+	}
+
+	/// Run a collection, clearing every soft reference if `clear_soft`.
+	fn collect_locked(&self, objects: &mut Objects, kind: GcKind, cause: GcCause, clear_soft: bool) {
+		let start = Instant::now();
+		let before = objects.used();
+		let clock = self.started.elapsed().as_millis() as i64;
+		let interval = (self.options.maximum_size.saturating_sub(before) >> 20) as i64 * self.options.soft_ref_lru_policy_ms_per_mb as i64;
+		let keep_soft = |reference: &Object| !clear_soft && reference.header.class.field_layout()
+			.find("java/lang/ref/SoftReference", "timestamp", "J")
+			.is_none_or(|timestamp| matches!(reference.get(timestamp), Variable::Long(Long { value }) if clock - value <= interval));
+		match kind {
+			GcKind::Young => {
+				let roots = self.roots(objects, &objects.remembered);
+				objects.collect_young(roots, self.options.tenuring_threshold, &keep_soft);
+			}
+			GcKind::Full => {
+				let roots = self.roots(objects, &self.loaders.method_area().classes());
+				objects.collect_full(roots, self.old_limit, &keep_soft);
+			}
+		}
+		// Java code sets the timestamp of a soft reference from this clock whenever it is used.
+		if let Some(soft) = self.loaders.method_area().find(LoaderId::BOOTSTRAP, "java/lang/ref/SoftReference") {
+			soft.put_static("clock", "J", Variable::Long(Long { value: clock }));
+		}
+		if !objects.pending.is_empty() || !objects.finalization.is_empty() {
+			self.handling.notify_all();
+		}
+		let remembered = std::mem::take(&mut objects.remembered);
+		objects.remembered = remembered.into_iter()
+			.filter(|class| class.static_references().into_iter().any(|reference| objects.is_young(reference)))
+			.collect();
+		let event = GcEvent { id: objects.events.len(), kind, cause, before, after: objects.used(), capacity: objects.capacity(), pause: start.elapsed() };
+		if self.options.log {
+			eprintln!("[gc] {}", event);
+		}
+		objects.events.push(event);
+	}
+
+	/// The references a collection starts from, with the static fields of `classes`.
+	fn roots(&self, objects: &Objects, classes: &[Arc<RuntimeClass>]) -> Vec<Reference> {
+		let mut roots: Vec<Reference> = self.threads.lock().unwrap().frames.values().flatten().flatten().copied().collect();
+		for class in classes {
+			roots.extend(class.static_references());
+		}
+		roots.extend(objects.strings.values());
+		roots.extend(objects.mirrors.keys());
+		roots.extend(objects.handles.iter().flatten());
+		roots.extend(&objects.pending);
+		roots.extend(&objects.finalization);
+		roots.extend(&objects.cleaning);
+		roots
+	}
+
+	/// Stop every other thread running Java code at a safepoint, waiting first for any collection
+	/// another thread has started. The current thread must not be running Java code itself, or
+	/// must have published its frame.
+	fn stop_world(&self) -> StoppedWorld<'_> {
+		let id = std::thread::current().id();
+		let mut threads = self.safepoints.wait_while(self.threads.lock().unwrap(), |threads| threads.stopped()).unwrap();
+		threads.collector = Some(id);
+		self.stopping.store(true, Ordering::Release);
+		let _threads = self.safepoints.wait_while(threads, |threads| threads.running.keys().any(|thread| *thread != id && !threads.at_safepoint(*thread))).unwrap();
+		StoppedWorld(self)
+	}
+
+	/// Count the current thread as running Java code until [Heap::exit_java], once any collection
+	/// it would have to stop for is over. It then keeps its references to itself, and stops for
+	/// collections at [Heap::poll] or by publishing a frame.
+	pub fn enter_java(&self) {
+		let mut threads = self.safepoints.wait_while(self.threads.lock().unwrap(), |threads| threads.stopped()).unwrap();
+		*threads.running.entry(std::thread::current().id()).or_default() += 1;
+	}
+
+	/// Stop counting the current thread as running the Java code of [Heap::enter_java].
+	pub fn exit_java(&self) {
+		let mut threads = self.threads.lock().unwrap();
+		let id = std::thread::current().id();
+		if let Some(running) = threads.running.get_mut(&id) {
+			*running -= 1;
+			if *running == 0 {
+				threads.running.remove(&id);
+			}
+		}
+		self.safepoints.notify_all();
+	}
+
+	/// Stop at a safepoint if another thread is waiting to collect, with the references `roots`
+	/// gives published until it is done. The interpreter polls between instructions.
+	pub fn poll<F: FnOnce() -> Vec<Reference>>(&self, roots: F) {
+		if self.stopping.load(Ordering::Acquire) {
+			self.push_frame(roots());
+			self.pop_frame();
+		}
+	}
+
+	/// Publish the references a frame of the current thread holds, keeping the objects alive until
+	/// [Heap::pop_frame]. The interpreter publishes its frame before anything that may allocate.
+	pub fn push_frame(&self, references: Vec<Reference>) {
+		self.threads.lock().unwrap().frames.entry(std::thread::current().id()).or_default().push(references);
+		self.safepoints.notify_all();
+	}
+
+	/// Keep `reference` alive with the frame the current thread published last, as a method's
+	/// result is until its invoker has it. Nothing is kept if the thread has published no frame.
+	pub fn publish(&self, reference: Reference) {
+		if let Some(frame) = self.threads.lock().unwrap().frames.get_mut(&std::thread::current().id()).and_then(|frames| frames.last_mut()) {
+			frame.push(reference);
+		}
+	}
+
+	/// Withdraw the frame the current thread published last, once any collection another thread
+	/// is running is over.
+	pub fn pop_frame(&self) {
+		let mut threads = self.safepoints.wait_while(self.threads.lock().unwrap(), |threads| threads.stopped()).unwrap();
+		let id = std::thread::current().id();
+		if let Some(frames) = threads.frames.get_mut(&id) {
+			frames.pop();
+			if frames.is_empty() {
+				threads.frames.remove(&id);
+			}
+		}
+	}
+
+	/// Wait until collections leave references pending, then add each to its queue, as the
+	/// reference handler thread does. Cleanables are left for [Heap::next_cleanable] instead. False
+	/// once handling has stopped and none are left.
+	pub fn enqueue_pending(&self) -> bool {
+		let mut objects = self.wait_for_handling(|objects| !objects.pending.is_empty());
+		if objects.pending.is_empty() {
+			return false;
+		}
+		let queues = self.loaders.method_area().find(LoaderId::BOOTSTRAP, "java/lang/ref/ReferenceQueue");
+		let sentinel = |name: &str| queues.as_ref()?.get_static(name, "Ljava/lang/ref/ReferenceQueue;")?.reference();
+		let sentinels = [sentinel("NULL"), sentinel("ENQUEUED")];
+		for reference in std::mem::take(&mut objects.pending) {
+			match objects.get(reference).is_some_and(|object| object.header.class.is_cleanable()) {
+				true => objects.cleaning.push_back(reference),
+				false => { objects.enqueue(reference, sentinels); }
+			}
+		}
+		true
+	}
+
+	/// The cleanable that has waited longest for the reference handler to run its cleaning action,
+	/// if any. It stays alive until [Heap::cleaned].
+	pub fn next_cleanable(&self) -> Option<Reference> {
+		self.objects.lock().unwrap().cleaning.front().copied()
+	}
+
+	/// Let a cleanable whose cleaning action has run be freed once unreachable.
+	pub fn cleaned(&self, cleanable: Reference) {
+		let mut objects = self.objects.lock().unwrap();
+		if let Some(index) = objects.cleaning.iter().position(|waiting| *waiting == cleanable) {
+			objects.cleaning.remove(index);
+		}
+	}
+
+	/// Wait until a collection finds an object with a finalizer unreachable, as the finalizer
+	/// thread does, and return the one waiting longest. It stays alive until [Heap::finalized].
+	/// None once handling has stopped and no object is waiting.
+	pub fn next_finalizable(&self) -> Option<Reference> {
+		self.wait_for_handling(|objects| !objects.finalization.is_empty()).finalization.front().copied()
+	}
+
+	/// Let an object whose finalize method has run be freed once unreachable again. It is not
+	/// finalized a second time.
+	pub fn finalized(&self, object: Reference) {
+		let mut objects = self.objects.lock().unwrap();
+		if let Some(index) = objects.finalization.iter().position(|waiting| *waiting == object) {
+			objects.finalization.remove(index);
+		}
+	}
+
+	/// Have the reference handler and finalizer threads stop once nothing is left for them.
+	pub fn stop_reference_handling(&self) {
+		self.objects.lock().unwrap().stopping = true;
+		self.handling.notify_all();
+	}
+
+	fn wait_for_handling<F: Fn(&Objects) -> bool>(&self, ready: F) -> MutexGuard<'_, Objects> {
+		let objects = self.objects.lock().unwrap();
+		self.handling.wait_while(objects, |objects| !ready(objects) && !objects.stopping).unwrap()
+	}
+
+	/// The interned java/lang/String with the given value, allocated the first time it is asked for
+	/// (JVMS17 5.1). Its value is Latin-1 bytes when every character fits and UTF-16 otherwise.
+	pub fn intern(&self, value: &str) -> Result<Reference, Box<dyn Error>> {
+		if let Some(string) = self.objects.lock().unwrap().strings.get(value) {
+			return Ok(*string);
+		}
+		let string_class = self.loaders.load_class(LoaderId::BOOTSTRAP, "java/lang/String")?;
+		let field = |name: &str, descriptor: &str| string_class.field_layout().find("java/lang/String", name, descriptor).cloned()
+			.ok_or_else(|| LinkageError::NoSuchField(format!("java.lang.String.{}", name)));
+		let (value_slot, coder_slot) = (field("value", "[B")?, field("coder", "B")?);
+		let (bytes, coder) = match value.chars().all(|char| (char as u32) < 0x100) {
+			true => (value.chars().map(|char| char as u8).collect::<Vec<u8>>(), LATIN1),
+			false => (value.encode_utf16().flat_map(u16::to_le_bytes).collect(), UTF16),
+		};
+		let bytes_class = self.loaders.load_class(LoaderId::BOOTSTRAP, "[B")?;
+		// The array and string join this frame, which keeps them until the string is interned.
+		self.push_frame(Vec::new());
+		let string = self.allocate_array(&bytes_class, bytes.len() as u32).and_then(|array| {
+			self.with_object(array, |object| object.data.copy_from_slice(&bytes));
+			let string = self.allocate(&string_class)?;
+			self.put_field(string, &value_slot, &Variable::ArrayReference(ArrayReference { value: array }));
+			self.put_field(string, &coder_slot, &Variable::Byte(Byte { value: coder }));
+			Ok(*self.objects.lock().unwrap().strings.entry(value.to_string()).or_insert(string))
+		});
+		self.pop_frame();
+		Ok(string?)
+	}
+
+	/// The java/lang/Class object standing for `class`, allocated the first time it is asked for
+	/// and kept as long as the heap.
+	pub fn mirror(&self, class: &Arc<RuntimeClass>) -> Result<Reference, Box<dyn Error>> {
+		let find = |objects: &Objects| objects.mirrors.iter().find(|(_, mirrored)| Arc::ptr_eq(mirrored, class)).map(|(mirror, _)| *mirror);
+		if let Some(mirror) = find(&self.objects.lock().unwrap()) {
+			return Ok(mirror);
+		}
+		let class_class = self.loaders.load_class(LoaderId::BOOTSTRAP, "java/lang/Class")?;
+		// The mirror joins this frame, which keeps it until it is recorded.
+		self.push_frame(Vec::new());
+		let mirror = self.allocate(&class_class).map(|mirror| {
+			let mut objects = self.objects.lock().unwrap();
+			find(&objects).unwrap_or_else(|| {
+				objects.mirrors.insert(mirror, class.clone());
+				mirror
+			})
+		});
+		self.pop_frame();
+		Ok(mirror?)
+	}
+
+	/// The class a java/lang/Class object from [Heap::mirror] stands for.
+	pub fn mirrored_class(&self, mirror: Reference) -> Option<Arc<RuntimeClass>> {
+		self.objects.lock().unwrap().mirrors.get(&mirror).cloned()
+	}
+
+	/// The value of a java/lang/String.
+	pub fn string_value(&self, reference: Reference) -> Option<String> {
+		let objects = self.objects.lock().unwrap();
+		let string = objects.get(reference)?;
+		let layout = string.header.class.field_layout();
+		let value = string.get(layout.find("java/lang/String", "value", "[B")?).reference()?;
+		let coder = string.get(layout.find("java/lang/String", "coder", "B")?);
+		let bytes = &objects.get(value)?.data;
+		match coder {
+			Variable::Byte(Byte { value: LATIN1 }) => Some(bytes.iter().map(|byte| *byte as char).collect()),
+			_ => String::from_utf16(&bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect::<Vec<u16>>()).ok(),
+		}
+	}
+
+	/// A global JNI handle to an object (JNI NewGlobalRef).
+	pub fn new_global_ref(&self, reference: Reference) -> GlobalRef {
+		let handles = &mut self.objects.lock().unwrap().handles;
+		match handles.iter().position(Option::is_none) {
+			Some(index) => {
+				handles[index] = Some(reference);
+				GlobalRef(index)
+			}
+			None => {
+				handles.push(Some(reference));
+				GlobalRef(handles.len() - 1)
+			}
+		}
+	}
+
+	/// The object a global JNI handle refers to, None once it is deleted.
+	pub fn global_ref(&self, handle: GlobalRef) -> Option<Reference> {
+		*self.objects.lock().unwrap().handles.get(handle.0)?
+	}
+
+	/// Delete a global JNI handle, letting its object be collected (JNI DeleteGlobalRef).
+	pub fn delete_global_ref(&self, handle: GlobalRef) {
+		if let Some(slot) = self.objects.lock().unwrap().handles.get_mut(handle.0) {
+			*slot = None;
+		}
+	}
+
+	/// The number of bytes objects take up.
+	pub fn used(&self) -> usize {
+		self.objects.lock().unwrap().used()
+	}
+
+	/// The number of bytes objects may take up before the next collection.
+	pub fn capacity(&self) -> usize {
+		self.objects.lock().unwrap().capacity()
+	}
+
+	/// Every collection so far, oldest first.
+	pub fn gc_events(&self) -> Vec<GcEvent> {
+		self.objects.lock().unwrap().events.clone()
+	}
+
+	/// Run `f` on the object `reference` refers to, if it is still on the heap.
+	pub fn with_object<R, F: FnOnce(&mut Object) -> R>(&self, reference: Reference, f: F) -> Option<R> {
+		self.objects.lock().unwrap().get_mut(reference).map(f)
+	}
+
+	/// A variable holding `reference`, an array reference if it refers to an array.
+	pub fn variable(&self, reference: Reference) -> Variable {
+		self.objects.lock().unwrap().typed(Variable::ClassReference(ClassReference { value: reference }))
+	}
+
+	/// The value of a field of an object.
+	pub fn get_field(&self, reference: Reference, slot: &FieldSlot) -> Option<Variable> {
+		let objects = self.objects.lock().unwrap();
+		let value = objects.get(reference)?.get(slot);
+		Some(objects.typed(value))
+	}
+
+	/// Store a value in a field of an object, returning false if there is no such object or the
+	/// value is not of the field's type.
+	pub fn put_field(&self, reference: Reference, slot: &FieldSlot, value: &Variable) -> bool {
+		self.objects.lock().unwrap().put_field(reference, slot, value)
+	}
+
+	/// The length of an array, None if `reference` does not refer to one.
+	pub fn array_length(&self, reference: Reference) -> Option<u32> {
+		self.objects.lock().unwrap().get(reference)?.length
+	}
+
+	/// An element of an array, None if the index is out of bounds.
+	pub fn get_element(&self, reference: Reference, index: u32) -> Option<Variable> {
+		let objects = self.objects.lock().unwrap();
+		let value = objects.get(reference)?.element(index)?;
+		Some(objects.typed(value))
+	}
+
+	/// Store a value in an array, returning false if the index is out of bounds or the value is not
+	/// of the component type.
+	pub fn put_element(&self, reference: Reference, index: u32, value: &Variable) -> bool {
+		let mut objects = self.objects.lock().unwrap();
+		let Some(object) = objects.get_mut(reference) else {
+			return false;
+		};
+		let Some((_, range)) = object.element_range(index) else {
+			return false;
+		};
+		let offset = ARRAY_HEADER_SIZE as usize + range.start;
+		let stored = object.put_element(index, value);
+		if stored {
+			objects.write_barrier(reference, offset, value);
+		}
+		stored
+	}
+
+	/// Store a value in a static field of `class`, as putstatic does, returning false if the class
+	/// has no such field. This is the write barrier for static fields: a class whose statics may
+	/// refer to young objects is remembered, and minor collections take only the statics of
+	/// remembered classes as roots.
+	pub fn put_static(&self, class: &Arc<RuntimeClass>, name: &str, descriptor: &str, value: Variable) -> bool {
+		let mut objects = self.objects.lock().unwrap();
+		let young = value.reference().is_some_and(|reference| objects.is_young(reference));
+		if !class.put_static(name, descriptor, value) {
+			return false;
+		}
+		if young && !objects.remembered.iter().any(|remembered| Arc::ptr_eq(remembered, class)) {
+			objects.remembered.push(class.clone());
+		}
+		true
+	}
+
+	/// The class of the object `reference` refers to.
+	pub fn class_of(&self, reference: Reference) -> Option<Arc<RuntimeClass>> {
+		self.with_object(reference, |object| object.header.class.clone())
+	}
+
+	/// The identity hash of an object, as `System.identityHashCode` returns it: assigned on first
+	/// request and the same for the rest of the object's life.
+	pub fn identity_hash(&self, reference: Reference) -> Option<i32> {
+		let mut objects = self.objects.lock().unwrap();
+		if let Some(hash) = objects.get(reference)?.header.hash {
+			return Some(hash);
+		}
+		// Marsaglia's xorshift, kept to 31 bits and never zero, as HotSpot does.
+		let mut seed = if objects.seed == 0 { 0x2545_F491 } else { objects.seed };
+		loop {
+			seed ^= seed << 13;
+			seed ^= seed >> 17;
+			seed ^= seed << 5;
+			if seed & 0x7FFF_FFFF != 0 {
+				break;
+			}
+		}
+		objects.seed = seed;
+		let hash = (seed & 0x7FFF_FFFF) as i32;
+		objects.get_mut(reference)?.header.hash = Some(hash);
+		Some(hash)
+	}
+
+	/// The number of objects on the heap.
+	pub fn object_count(&self) -> usize {
+		self.objects.lock().unwrap().slots.iter().filter(|slot| slot.is_some()).count()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+
+	use crate::{
+		class::{
+			access::{FieldAccessPropertyFlags, MethodAccessPropertyFlags},
+			builder::ClassBuilder},
+		isa::opcode::Opcode,
+		vm::{
+			class_loader::{tests::{bytes, loaders}, LoaderId},
+			errors::{ExecutionError, LinkageError},
+			gc::{Collector, GcCause, GcKind, HeapOptions},
+			heap::{Generation, Heap, LockWord, Reference},
+			interpreter::Interpreter,
+			types::*}};
+
+	#[test]
+	fn test_objects() {
+		let instance = &[FieldAccessPropertyFlags::Public];
+		let loaders = Arc::new(loaders("heap-objects", &[("demo/Point", bytes(ClassBuilder::new("demo/Point")
+			.field(instance, "x", "I")
+			.field(instance, "weight", "D")
+			.field(instance, "visible", "Z")
+			.field(instance, "tag", "C")
+			.field(instance, "next", "Ldemo/Point;")))]));
+		let point = loaders.load_class(LoaderId::APPLICATION, "demo/Point").unwrap();
+		let layout = point.field_layout();
+		let slot = |name: &str| layout.fields.iter().find(|slot| slot.name == name).unwrap().clone();
+
+		let heap = Heap::new(loaders.clone(), HeapOptions::default());
+		let first = heap.allocate(&point).unwrap();
+		let second = heap.allocate(&point).unwrap();
+		assert_ne!(first, second);
+		assert_eq!(heap.object_count(), 2);
+		assert_eq!(heap.class_of(first), Some(point.clone()));
+
+		assert_eq!(heap.with_object(first, |object| object.header.lock), Some(LockWord::Unlocked));
+		assert_eq!(heap.get_field(first, &slot("x")), Some(Variable::Int(Int { value: 0 })));
+		assert_eq!(heap.get_field(first, &slot("next")), Some(NULL));
+		assert!(heap.put_field(first, &slot("x"), &Variable::Int(Int { value: -7 })));
+		assert!(heap.put_field(first, &slot("weight"), &Variable::Double(Double { value: 2.5 })));
+		assert!(heap.put_field(first, &slot("visible"), &Variable::Boolean(Boolean { value: true })));
+		assert!(heap.put_field(first, &slot("tag"), &Variable::Char(Char { value: 0xFFFF })));
+		assert!(heap.put_field(first, &slot("next"), &Variable::ClassReference(ClassReference { value: second })));
+		assert!(!heap.put_field(first, &slot("x"), &Variable::Long(Long { value: 1 })));
+		assert_eq!(heap.get_field(first, &slot("x")), Some(Variable::Int(Int { value: -7 })));
+		assert_eq!(heap.get_field(first, &slot("weight")), Some(Variable::Double(Double { value: 2.5 })));
+		assert_eq!(heap.get_field(first, &slot("visible")), Some(Variable::Boolean(Boolean { value: true })));
+		assert_eq!(heap.get_field(first, &slot("tag")), Some(Variable::Char(Char { value: 0xFFFF })));
+		assert_eq!(heap.get_field(first, &slot("next")), Some(Variable::ClassReference(ClassReference { value: second })));
+		assert_eq!(heap.get_field(second, &slot("x")), Some(Variable::Int(Int { value: 0 })));
+
+		let hash = heap.identity_hash(first).unwrap();
+		assert!(hash > 0);
+		assert_eq!(heap.identity_hash(first), Some(hash));
+		assert_ne!(heap.identity_hash(second), Some(hash));
+		assert_eq!(heap.identity_hash(Reference::from_raw(99).unwrap()), None);
+		assert_eq!(Reference::from_raw(0), None);
+	}
+
+	#[test]
+	fn test_instance_fields() {
+		let point = ClassBuilder::new("demo/Point")
+			.field(&[FieldAccessPropertyFlags::Public], "x", "I")
+			.field(&[FieldAccessPropertyFlags::Public], "next", "Ldemo/Point;")
+			.field(&[FieldAccessPropertyFlags::Public, FieldAccessPropertyFlags::Final], "id", "J");
+		let statics = &[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Static];
+		let main = ClassBuilder::new("demo/Main")
+			.method(statics, "make", "()Ldemo/Point;", |method| {
+				method.type_op(Opcode::New, "demo/Point").var(Opcode::AStore, 0)
+					.var(Opcode::ALoad, 0).push_int(42).field(Opcode::PutField, "demo/Point", "x", "I")
+					.var(Opcode::ALoad, 0).var(Opcode::ALoad, 0).field(Opcode::PutField, "demo/Point", "next", "Ldemo/Point;")
+					.var(Opcode::ALoad, 0).op(Opcode::AReturn);
+			})
+			.method(statics, "read", "()I", |method| {
+				method.invoke(Opcode::InvokeStatic, "demo/Main", "make", "()Ldemo/Point;", false)
+					.field(Opcode::GetField, "demo/Point", "next", "Ldemo/Point;")
+					.field(Opcode::GetField, "demo/Point", "x", "I").op(Opcode::IReturn);
+			})
+			.method(statics, "dereference", "()I", |method| {
+				method.op(Opcode::AConstNull).field(Opcode::GetField, "demo/Point", "x", "I").op(Opcode::IReturn);
+			})
+			.method(statics, "overwrite", "()V", |method| {
+				method.invoke(Opcode::InvokeStatic, "demo/Main", "make", "()Ldemo/Point;", false)
+					.push_int(7).op(Opcode::I2L).field(Opcode::PutField, "demo/Point", "id", "J").op(Opcode::Return);
+			});
+		let loaders = Arc::new(loaders("instance-fields", &[("demo/Point", bytes(point)), ("demo/Main", bytes(main))]));
+		let main = loaders.load_class(LoaderId::APPLICATION, "demo/Main").unwrap();
+		let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions::default()));
+		let call = |name: &str, descriptor: &str| Interpreter::for_method(loaders.clone(), heap.clone(), main.clone(), name, descriptor, Vec::new()).unwrap().execute();
+
+		let Variable::ClassReference(made) = call("make", "()Ldemo/Point;").unwrap() else {
+			panic!("make returned no object");
+		};
+		assert_eq!(heap.class_of(made.value).unwrap().name, "demo/Point");
+		let slot = heap.class_of(made.value).unwrap().field_layout().find("demo/Point", "next", "Ldemo/Point;").unwrap().clone();
+		assert_eq!(heap.get_field(made.value, &slot), Some(Variable::ClassReference(made.clone())));
+		assert_eq!(call("read", "()I").unwrap(), Variable::Int(Int { value: 42 }));
+		assert_eq!(heap.object_count(), 2);
+
+		let error = call("dereference", "()I").unwrap_err().downcast::<ExecutionError>().unwrap();
+		assert_eq!(error.to_string(), "java.lang.NullPointerException: Cannot read field \"x\" because value is null");
+		let error = call("overwrite", "()V").unwrap_err().downcast::<LinkageError>().unwrap();
+		assert!(matches!(*error, LinkageError::IllegalAccess(_)));
+	}
+
+	#[test]
+	fn test_arrays() {
+		let statics = &[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Static];
+		let main = ClassBuilder::new("demo/Main")
+			.method(statics, "ints", "()I", |method| {
+				method.push_int(8).int(Opcode::NewArray, 10).var(Opcode::AStore, 0)
+					.var(Opcode::ALoad, 0).push_int(7).push_int(42).op(Opcode::IAStore)
+					.var(Opcode::ALoad, 0).push_int(7).op(Opcode::IALoad)
+					.var(Opcode::ALoad, 0).op(Opcode::ArrayLength).op(Opcode::IAdd).op(Opcode::IReturn);
+			})
+			.method(statics, "narrow", "()[B", |method| {
+				method.push_int(8).int(Opcode::NewArray, 8).var(Opcode::AStore, 0)
+					.var(Opcode::ALoad, 0).push_int(6).push_int(100).push_int(100).op(Opcode::IAdd).op(Opcode::BAStore)
+					.var(Opcode::ALoad, 0).op(Opcode::AReturn);
+			})
+			.method(statics, "flags", "()[Z", |method| {
+				method.push_int(8).int(Opcode::NewArray, 4).var(Opcode::AStore, 0)
+					.var(Opcode::ALoad, 0).push_int(6).push_int(7).op(Opcode::BAStore)
+					.var(Opcode::ALoad, 0).op(Opcode::AReturn);
+			})
+			.method(statics, "chars", "()I", |method| {
+				method.push_int(8).int(Opcode::NewArray, 5).var(Opcode::AStore, 0)
+					.var(Opcode::ALoad, 0).push_int(6).push_int(7).op(Opcode::INeg).op(Opcode::CAStore)
+					.var(Opcode::ALoad, 0).push_int(6).op(Opcode::CALoad).op(Opcode::IReturn);
+			})
+			.method(statics, "outside", "()I", |method| {
+				method.push_int(8).int(Opcode::NewArray, 10).push_int(8).op(Opcode::IALoad).op(Opcode::IReturn);
+			})
+			.method(statics, "negative", "()[J", |method| {
+				method.push_int(7).op(Opcode::INeg).int(Opcode::NewArray, 11).op(Opcode::AReturn);
+			})
+			.method(statics, "missing", "()I", |method| {
+				method.op(Opcode::AConstNull).op(Opcode::ArrayLength).op(Opcode::IReturn);
+			})
+			.method(statics, "shapes", "()[Ldemo/Shape;", |method| {
+				method.push_int(7).type_op(Opcode::ANewArray, "demo/Shape").var(Opcode::AStore, 0)
+					.var(Opcode::ALoad, 0).push_int(6).type_op(Opcode::New, "demo/Circle").op(Opcode::AAStore)
+					.var(Opcode::ALoad, 0).op(Opcode::AReturn);
+			})
+			.method(statics, "mismatch", "()V", |method| {
+				method.push_int(7).type_op(Opcode::ANewArray, "demo/Shape")
+					.push_int(6).type_op(Opcode::New, "demo/Main").op(Opcode::AAStore).op(Opcode::Return);
+			})
+			.method(statics, "matrix", "()[[I", |method| {
+				method.push_int(7).push_int(6).multi_anew_array("[[I", 2).op(Opcode::AReturn);
+			})
+			.method(statics, "rows", "()[[I", |method| {
+				method.push_int(7).multi_anew_array("[[I", 1).op(Opcode::AReturn);
+			});
+		let loaders = Arc::new(loaders("arrays", &[
+			("demo/Shape", bytes(ClassBuilder::new("demo/Shape"))),
+			("demo/Circle", bytes(ClassBuilder::new("demo/Circle").super_class("demo/Shape"))),
+			("demo/Main", bytes(main)),
+		]));
+		let main = loaders.load_class(LoaderId::APPLICATION, "demo/Main").unwrap();
+		let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions::default()));
+		let call = |name: &str, descriptor: &str| Interpreter::for_method(loaders.clone(), heap.clone(), main.clone(), name, descriptor, Vec::new()).unwrap().execute();
+		let array = |name: &str, descriptor: &str| match call(name, descriptor).unwrap() {
+			Variable::ArrayReference(array) => array.value,
+			other => panic!("{} returned {:?}", name, other),
+		};
+		let error = |name: &str, descriptor: &str| call(name, descriptor).unwrap_err().to_string();
+
+		assert_eq!(call("ints", "()I").unwrap(), Variable::Int(Int { value: 50 }));
+		let bytes = array("narrow", "()[B");
+		assert_eq!(heap.get_element(bytes, 6), Some(Variable::Byte(Byte { value: -56 })));
+		assert_eq!(heap.get_element(bytes, 8), None);
+		let flags = array("flags", "()[Z");
+		assert_eq!(heap.class_of(flags).unwrap().name, "[Z");
+		assert_eq!(heap.get_element(flags, 6), Some(Variable::Boolean(Boolean { value: true })));
+		assert_eq!(heap.get_element(flags, 7), Some(Variable::Boolean(Boolean { value: false })));
+		assert_eq!(call("chars", "()I").unwrap(), Variable::Int(Int { value: 0xFFF9 }));
+
+		assert_eq!(error("outside", "()I"), "java.lang.ArrayIndexOutOfBoundsException: Index 8 out of bounds for length 8");
+		assert_eq!(error("negative", "()[J"), "java.lang.NegativeArraySizeException: -7");
+		assert_eq!(error("missing", "()I"), "java.lang.NullPointerException: Cannot read the array length because value is null");
+		assert_eq!(error("mismatch", "()V"), "java.lang.ArrayStoreException: demo.Main");
+
+		let shapes = array("shapes", "()[Ldemo/Shape;");
+		assert_eq!(heap.array_length(shapes), Some(7));
+		let Some(Variable::ClassReference(circle)) = heap.get_element(shapes, 6) else {
+			panic!("no circle stored");
+		};
+		assert_eq!(heap.class_of(circle.value).unwrap().name, "demo/Circle");
+		assert_eq!(heap.get_element(shapes, 0), Some(NULL));
+
+		let matrix = array("matrix", "()[[I");
+		assert_eq!(heap.array_length(matrix), Some(7));
+		let Some(Variable::ArrayReference(row)) = heap.get_element(matrix, 6) else {
+			panic!("no row allocated");
+		};
+		assert_eq!((heap.class_of(row.value).unwrap().name.as_str(), heap.array_length(row.value)), ("[I", Some(6)));
+		let rows = array("rows", "()[[I");
+		assert_eq!(heap.get_element(rows, 6), Some(NULL));
+	}
+
+	#[test]
+	fn test_zero_dimensions() {
+		// The builder refuses a multianewarray of 0 dimensions, so clear the operand of a 1-dimension one.
+		let mut main = ClassBuilder::new("demo/Main")
+			.method(&[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Static], "flat", "()[[I", |method| {
+				method.push_int(7).multi_anew_array("[[I", 1).op(Opcode::AReturn);
+			})
+			.build().unwrap();
+		let flat = main.methods.methods.iter().position(|method| main.get_utf8(method.name_index).as_deref() == Some("flat")).unwrap();
+		main.methods.methods[flat].code_mut().unwrap().code[5] = 0;
+		let loaders = Arc::new(loaders("zero-dimensions", &[("demo/Main", main.to_bytes().unwrap())]));
+		let main = loaders.load_class(LoaderId::APPLICATION, "demo/Main").unwrap();
+		let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions::default()));
+		let error = Interpreter::for_method(loaders.clone(), heap.clone(), main, "flat", "()[[I", Vec::new()).unwrap().execute().unwrap_err();
+		assert_eq!(error.to_string(), "java.lang.InternalError: multianewarray of 0 dimensions");
+		assert_eq!(heap.object_count(), 0);
+	}
+
+	#[test]
+	fn test_garbage_collection() {
+		let instance = &[FieldAccessPropertyFlags::Public];
+		let loaders = Arc::new(loaders("garbage-collection", &[
+			("demo/Node", bytes(ClassBuilder::new("demo/Node").field(instance, "next", "Ldemo/Node;").field(instance, "value", "I"))),
+			("demo/Holder", bytes(ClassBuilder::new("demo/Holder").field(&[FieldAccessPropertyFlags::Static], "kept", "Ldemo/Node;"))),
+		]));
+		let node = loaders.load_class(LoaderId::APPLICATION, "demo/Node").unwrap();
+		let next = node.field_layout().find("demo/Node", "next", "Ldemo/Node;").unwrap().clone();
+		let value = node.field_layout().find("demo/Node", "value", "I").unwrap().clone();
+		let heap = Heap::new(loaders.clone(), HeapOptions { initial_size: 256, maximum_size: 512, collector: Collector::MarkCompact, ..HeapOptions::default() });
+		let new_node = |number: i32| heap.allocate(&node).inspect(|reference| {
+			heap.put_field(*reference, &value, &Variable::Int(Int { value: number }));
+		});
+
+		// Only what a root reaches survives, slid down to the start of the heap.
+		let garbage = new_node(0).unwrap();
+		let first = new_node(1).unwrap();
+		let second = new_node(2).unwrap();
+		heap.put_field(first, &next, &Variable::ClassReference(ClassReference { value: second }));
+		let handle = heap.new_global_ref(first);
+		assert_eq!(heap.used(), 72);
+		heap.collect();
+		assert_eq!(heap.object_count(), 2);
+		assert_eq!(heap.class_of(garbage), None);
+		assert_eq!((heap.with_object(first, |object| object.address), heap.with_object(second, |object| object.address)), (Some(0), Some(24)));
+		assert_eq!(heap.get_field(second, &value), Some(Variable::Int(Int { value: 2 })));
+		let event = heap.gc_events().pop().unwrap();
+		assert_eq!((event.id, event.cause, event.before, event.after, event.capacity), (0, GcCause::SystemGc, 72, 48, 256));
+
+		// Static fields, interned strings and published frames are roots as well.
+		let holder = loaders.load_class(LoaderId::APPLICATION, "demo/Holder").unwrap();
+		holder.prepare();
+		let held = new_node(3).unwrap();
+		assert_eq!(held, garbage);
+		assert!(heap.put_static(&holder, "kept", "Ldemo/Node;", Variable::ClassReference(ClassReference { value: held })));
+		let framed = new_node(4).unwrap();
+		heap.push_frame(vec![framed]);
+		let string = heap.intern("héllo").unwrap();
+		assert_eq!(heap.intern("héllo").unwrap(), string);
+		let wide = heap.intern("π").unwrap();
+		heap.delete_global_ref(handle);
+		assert_eq!(heap.global_ref(handle), None);
+		heap.collect();
+		assert_eq!((heap.class_of(first), heap.class_of(second)), (None, None));
+		assert_eq!(heap.get_field(held, &value), Some(Variable::Int(Int { value: 3 })));
+		assert_eq!(heap.get_field(framed, &value), Some(Variable::Int(Int { value: 4 })));
+		assert_eq!(heap.string_value(string).as_deref(), Some("héllo"));
+		assert_eq!(heap.string_value(wide).as_deref(), Some("π"));
+		heap.pop_frame();
+		heap.collect();
+		assert_eq!(heap.class_of(framed), None);
+		assert_eq!(heap.used(), 120);
+
+		// An allocation that does not fit collects, then grows the heap up to its maximum size.
+		let handles: Vec<_> = (0..16).map(|number| heap.new_global_ref(new_node(number).unwrap())).collect();
+		assert_eq!((heap.used(), heap.capacity()), (504, 512));
+		assert_eq!(new_node(16).unwrap_err().to_string(), "java.lang.OutOfMemoryError: Java heap space");
+		let int_array = loaders.load_class(LoaderId::BOOTSTRAP, "[I").unwrap();
+		assert!(matches!(heap.allocate_array(&int_array, 1 << 30), Err(ExecutionError::OutOfMemory(_))));
+		for handle in handles {
+			heap.delete_global_ref(handle);
+		}
+		new_node(16).unwrap();
+		assert_eq!(heap.used(), 144);
+		let event = heap.gc_events().pop().unwrap();
+		assert_eq!((event.cause, event.before, event.after), (GcCause::AllocationFailure, 504, 120));
+	}
+
+	#[test]
+	fn test_generational_collection() {
+		let instance = &[FieldAccessPropertyFlags::Public];
+		let loaders = Arc::new(loaders("generational-collection", &[
+			("demo/Node", bytes(ClassBuilder::new("demo/Node").field(instance, "next", "Ldemo/Node;").field(instance, "value", "I"))),
+			("demo/Holder", bytes(ClassBuilder::new("demo/Holder").field(&[FieldAccessPropertyFlags::Static], "kept", "Ldemo/Node;"))),
+		]));
+		let node = loaders.load_class(LoaderId::APPLICATION, "demo/Node").unwrap();
+		let next = node.field_layout().find("demo/Node", "next", "Ldemo/Node;").unwrap().clone();
+		let value = node.field_layout().find("demo/Node", "value", "I").unwrap().clone();
+		let options = HeapOptions { initial_size: 1200, maximum_size: 2400, young_size: Some(400), tenuring_threshold: 2, ..HeapOptions::default() };
+		let heap = Heap::new(loaders.clone(), options);
+		let new_node = |number: i32| heap.allocate(&node).inspect(|reference| {
+			heap.put_field(*reference, &value, &Variable::Int(Int { value: number }));
+		});
+		let minor_collections = || heap.gc_events().iter().filter(|event| event.kind == GcKind::Young).count();
+		let collect_young = |count: usize| while minor_collections() < count {
+			new_node(0).unwrap();
+		};
+		let state = |reference: Reference| heap.with_object(reference, |object| (object.generation, object.age));
+
+		// Objects are allocated young, and promoted once they survive the tenuring threshold.
+		let kept = new_node(1).unwrap();
+		heap.new_global_ref(kept);
+		assert_eq!(state(kept), Some((Generation::Young, 0)));
+		collect_young(1);
+		let event = heap.gc_events().pop().unwrap();
+		assert_eq!((event.kind, event.cause, event.before, event.after, event.capacity), (GcKind::Young, GcCause::AllocationFailure, 312, 24, 1200));
+		assert_eq!(state(kept), Some((Generation::Young, 1)));
+		collect_young(2);
+		assert_eq!(state(kept), Some((Generation::Young, 2)));
+		collect_young(3);
+		assert_eq!(state(kept), Some((Generation::Old, 2)));
+
+		// The card an old object stores a young reference in keeps that object alive, until it is
+		// promoted too.
+		let child = new_node(7).unwrap();
+		assert!(heap.put_field(kept, &next, &Variable::ClassReference(ClassReference { value: child })));
+		assert!(heap.objects.lock().unwrap().cards.contains(&true));
+		collect_young(4);
+		assert_eq!(state(child), Some((Generation::Young, 1)));
+		assert_eq!(heap.get_field(child, &value), Some(Variable::Int(Int { value: 7 })));
+		collect_young(6);
+		assert_eq!(state(child), Some((Generation::Old, 2)));
+		assert!(!heap.objects.lock().unwrap().cards.contains(&true));
+
+		// So does a static field, through the class it belongs to.
+		let holder = loaders.load_class(LoaderId::APPLICATION, "demo/Holder").unwrap();
+		holder.prepare();
+		let held = new_node(9).unwrap();
+		assert!(heap.put_static(&holder, "kept", "Ldemo/Node;", Variable::ClassReference(ClassReference { value: held })));
+		assert_eq!(heap.objects.lock().unwrap().remembered.len(), 1);
+		collect_young(7);
+		assert_eq!(heap.get_field(held, &value), Some(Variable::Int(Int { value: 9 })));
+		assert!(heap.put_static(&holder, "kept", "Ldemo/Node;", NULL));
+		collect_young(8);
+		assert!(heap.objects.lock().unwrap().remembered.is_empty());
+
+		// Arrays too large for eden go straight to the old generation.
+		let int_array = loaders.load_class(LoaderId::BOOTSTRAP, "[I").unwrap();
+		let large = heap.allocate_array(&int_array, 100).unwrap();
+		assert_eq!(state(large), Some((Generation::Old, 0)));
+
+		// A full collection promotes every young object still reachable.
+		let young = new_node(5).unwrap();
+		heap.new_global_ref(young);
+		heap.collect();
+		assert_eq!(heap.gc_events().pop().unwrap().kind, GcKind::Full);
+		assert_eq!(state(young).unwrap().0, Generation::Old);
+		assert_eq!(state(large), None);
+		assert_eq!(heap.used(), 72);
+
+		// Once the old generation cannot grow, full collections run and allocation fails.
+		let error = (0..200).find_map(|_| new_node(0).map(|reference| heap.new_global_ref(reference)).err()).unwrap();
+		assert_eq!(error.to_string(), "java.lang.OutOfMemoryError: Java heap space");
+		assert!(heap.gc_events().iter().any(|event| event.kind == GcKind::Full && event.cause == GcCause::AllocationFailure));
+	}
+
+	#[test]
+	fn test_collection_during_execution() {
+		let instance = &[FieldAccessPropertyFlags::Public];
+		let statics = &[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Static];
+		let main = ClassBuilder::new("demo/Main")
+			.field(&[FieldAccessPropertyFlags::Public, FieldAccessPropertyFlags::Static], "cache", "Ldemo/Node;")
+			.method(statics, "garbage", "()V", |method| {
+				for _ in 0..4 {
+					method.type_op(Opcode::New, "demo/Node").op(Opcode::Pop);
+				}
+				method.op(Opcode::Return);
+			})
+			// Keeps nodes in a local variable, a static field and on the operand stack while the
+			// garbage it makes is collected.
+			.method(statics, "run", "()I", |method| {
+				method.type_op(Opcode::New, "demo/Node").var(Opcode::AStore, 0)
+					.var(Opcode::ALoad, 0).push_int(40).field(Opcode::PutField, "demo/Node", "value", "I")
+					.type_op(Opcode::New, "demo/Node").field(Opcode::PutStatic, "demo/Main", "cache", "Ldemo/Node;")
+					.field(Opcode::GetStatic, "demo/Main", "cache", "Ldemo/Node;").push_int(6).field(Opcode::PutField, "demo/Node", "value", "I")
+					.type_op(Opcode::New, "demo/Node").op(Opcode::Dup).push_int(30).field(Opcode::PutField, "demo/Node", "value", "I")
+					.type_op(Opcode::New, "demo/Node").op(Opcode::Dup).push_int(20).field(Opcode::PutField, "demo/Node", "value", "I")
+					.op(Opcode::Swap)
+					.invoke(Opcode::InvokeStatic, "demo/Main", "garbage", "()V", false)
+					.ldc("kept").op(Opcode::Pop)
+					.invoke(Opcode::InvokeStatic, "demo/Main", "garbage", "()V", false)
+					.field(Opcode::GetField, "demo/Node", "value", "I").op(Opcode::Swap)
+					.field(Opcode::GetField, "demo/Node", "value", "I").op(Opcode::IAdd)
+					.var(Opcode::ALoad, 0).field(Opcode::GetField, "demo/Node", "value", "I").op(Opcode::IAdd)
+					.field(Opcode::GetStatic, "demo/Main", "cache", "Ldemo/Node;").field(Opcode::GetField, "demo/Node", "value", "I").op(Opcode::IAdd)
+					.op(Opcode::IReturn);
+			})
+			.method(statics, "matrix", "()[[I", |method| {
+				method.push_int(6).push_int(6).multi_anew_array("[[I", 2).op(Opcode::AReturn);
+			})
+			.method(statics, "hoard", "()[I", |method| {
+				method.push_int(100).push_int(100).op(Opcode::IAdd).int(Opcode::NewArray, 10).op(Opcode::AReturn);
+			});
+		let loaders = Arc::new(loaders("collection-during-execution", &[
+			("demo/Node", bytes(ClassBuilder::new("demo/Node").field(instance, "value", "I"))),
+			("demo/Main", bytes(main)),
+		]));
+		let main = loaders.load_class(LoaderId::APPLICATION, "demo/Main").unwrap();
+		for collector in [Collector::MarkCompact, Collector::Generational] {
+			let options = HeapOptions { initial_size: 128, maximum_size: 512, collector, young_size: Some(64), ..HeapOptions::default() };
+			let heap = Arc::new(Heap::new(loaders.clone(), options));
+			let call = |name: &str, descriptor: &str| Interpreter::for_method(loaders.clone(), heap.clone(), main.clone(), name, descriptor, Vec::new()).unwrap().execute();
+
+			assert_eq!(call("run", "()I").unwrap(), Variable::Int(Int { value: 96 }));
+			assert!(heap.gc_events().iter().any(|event| event.cause == GcCause::AllocationFailure));
+			heap.collect();
+			// The node in the static field and the interned string with its bytes.
+			assert_eq!(heap.object_count(), 3);
+
+			let Variable::ArrayReference(matrix) = call("matrix", "()[[I").unwrap() else {
+				panic!("matrix returned no array");
+			};
+			for index in 0..6 {
+				let Some(Variable::ArrayReference(row)) = heap.get_element(matrix.value, index) else {
+					panic!("row {} was not kept", index);
+				};
+				assert_eq!(heap.array_length(row.value), Some(6));
+			}
+			assert_eq!(call("hoard", "()[I").unwrap_err().to_string(), "java.lang.OutOfMemoryError: Java heap space");
+		}
+	}
+
+	#[test]
+	fn test_collection_from_another_thread() {
+		let statics = &[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Static];
+		// Holds a new node on the operand stack alone, between safepoints.
+		let main = ClassBuilder::new("demo/Main")
+			.method(statics, "make", "()I", |method| {
+				method.type_op(Opcode::New, "demo/Node").op(Opcode::Dup).op(Opcode::Dup)
+					.push_int(40).field(Opcode::PutField, "demo/Node", "value", "I")
+					.field(Opcode::GetField, "demo/Node", "value", "I").op(Opcode::Swap)
+					.field(Opcode::GetField, "demo/Node", "value", "I").op(Opcode::IAdd)
+					.op(Opcode::IReturn);
+			});
+		let loaders = Arc::new(loaders("collection-from-another-thread", &[
+			("demo/Node", bytes(ClassBuilder::new("demo/Node").field(&[FieldAccessPropertyFlags::Public], "value", "I"))),
+			("demo/Main", bytes(main)),
+		]));
+		let main = loaders.load_class(LoaderId::APPLICATION, "demo/Main").unwrap();
+		for collector in [Collector::MarkCompact, Collector::Generational] {
+			let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions { collector, ..HeapOptions::default() }));
+			let done = AtomicBool::new(false);
+			let (results, collections) = std::thread::scope(|scope| {
+				let collecting = scope.spawn(|| {
+					let mut collections = 0;
+					while !done.load(Ordering::Acquire) {
+						heap.collect();
+						collections += 1;
+					}
+					collections
+				});
+				// Asserted once the collecting thread has stopped, so a failure does not leave it running.
+				let results: Vec<Result<Variable, String>> = (0..2000)
+					.map(|_| Interpreter::for_method(loaders.clone(), heap.clone(), main.clone(), "make", "()I", Vec::new()).unwrap().execute().map_err(|error| error.to_string()))
+					.collect();
+				done.store(true, Ordering::Release);
+				(results, collecting.join().unwrap())
+			});
+			for result in results {
+				assert_eq!(result, Ok(Variable::Int(Int { value: 80 })));
+			}
+			assert!(collections > 0);
+			assert_eq!(heap.gc_events().len(), collections);
+		}
+	}
+}
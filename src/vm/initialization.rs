@@ -0,0 +1,389 @@
+use std::{
+	error::Error,
+	sync::{Arc, Condvar, Mutex},
+	thread::{self, ThreadId}};
+
+use crate::{
+	class::access::MethodAccessPropertyFlags,
+	vm::{
+		class_loader::{ClassLoaders, LoaderId},
+		errors::{ExecutionError, LinkageError},
+		method_area::RuntimeClass}};
+
+/// Where a class is in its initialization (JVMS17 5.5).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InitializationState {
+	Uninitialized,
+	/// Being initialized by the given thread.
+	InProgress(ThreadId),
+	Initialized,
+	/// Initialization failed, and the class cannot be used.
+	Erroneous,
+}
+
+/// The initialization lock of a class, LC in JVMS17 5.5, which threads wait on while another
+/// thread initializes the class.
+#[derive(Debug)]
+pub struct InitializationLock {
+	state: Mutex<InitializationState>,
+	condition: Condvar,
+}
+
+impl Default for InitializationLock {
+	fn default() -> Self {
+		InitializationLock { state: Mutex::new(InitializationState::Uninitialized), condition: Condvar::new() }
+	}
+}
+
+impl InitializationLock {
+	pub fn state(&self) -> InitializationState {
+		*self.state.lock().unwrap()
+	}
+
+	/// Record the outcome of initialization and wake the threads waiting for it.
+	fn finish(&self, state: InitializationState) {
+		*self.state.lock().unwrap() = state;
+		self.condition.notify_all();
+	}
+}
+
+/// Class initialization (JVMS17 5.5).
+impl ClassLoaders {
+
+	/// Initialize a class, if no thread has, by running its superclass's and its own `<clinit>`
+	/// methods with `run_initializer`.
+	///
+	/// A thread that asks while another thread initializes the class waits for it to finish, and
+	/// one that asks while it is initializing the class itself returns at once. When an initializer
+	/// fails, the class becomes erroneous: the error is reported as an ExceptionInInitializerError
+	/// unless it is already an Error, such as OutOfMemoryError or a LinkageError, and later attempts
+	/// fail with NoClassDefFoundError.
+	pub fn initialize_class<F>(&self, class: &Arc<RuntimeClass>, run_initializer: &F) -> Result<(), LinkageError>
+	where F: Fn(&Arc<RuntimeClass>) -> Result<(), Box<dyn Error>> {
+		self.link_class(class)?;
+		let lock = &class.initialization;
+		let current = thread::current().id();
+		{
+			let mut state = lock.state.lock().unwrap();
+			loop {
+				match *state {
+					InitializationState::InProgress(thread) if thread != current => state = lock.condition.wait(state).unwrap(),
+					InitializationState::InProgress(_) | InitializationState::Initialized => return Ok(()),
+					InitializationState::Erroneous => return Err(LinkageError::NoClassDefFound(format!("Could not initialize class {}", class.name.replace('/', ".")))),
+					InitializationState::Uninitialized => break,
+				}
+			}
+			*state = InitializationState::InProgress(current);
+		}
+
+		if !class.is_interface() {
+			let supertypes = class.super_class.iter().cloned().chain(default_method_interfaces(class));
+			for supertype in supertypes {
+				if let Err(error) = self.initialize_class(&supertype, run_initializer) {
+					lock.finish(InitializationState::Erroneous);
+					return Err(error);
+				}
+			}
+		}
+
+		match run_initializer(class) {
+			Ok(()) => {
+				lock.finish(InitializationState::Initialized);
+				Ok(())
+			}
+			Err(error) => {
+				lock.finish(InitializationState::Erroneous);
+				let wrap = |error: &dyn Error| LinkageError::ExceptionInInitializer { class: class.name.clone(), cause: error.to_string() };
+				Err(match error.downcast::<LinkageError>() {
+					Ok(error) if error.is_error() => *error,
+					Ok(error) => wrap(&*error),
+					Err(error) => match error.downcast::<ExecutionError>() {
+						Ok(error) if error.is_error() => LinkageError::Error(*error),
+						Ok(error) => wrap(&*error),
+						Err(error) => wrap(&*error),
+					},
+				})
+			}
+		}
+	}
+
+	/// Load and link a class with `loader`, initializing it if asked, as `Class.forName` does.
+	pub fn for_name<F>(&self, loader: LoaderId, name: &str, initialize: bool, run_initializer: &F) -> Result<Arc<RuntimeClass>, LinkageError>
+	where F: Fn(&Arc<RuntimeClass>) -> Result<(), Box<dyn Error>> {
+		let class = self.load_class(loader, name)?;
+		self.link_class(&class)?;
+		if initialize {
+			self.initialize_class(&class, run_initializer)?;
+		}
+		Ok(class)
+	}
+}
+
+/// The superinterfaces a class initializes before itself: those declaring a method that is neither
+/// abstract nor static, each after its own superinterfaces, in the order they are declared
+/// (JVMS17 5.5 step 7).
+fn default_method_interfaces(class: &RuntimeClass) -> Vec<Arc<RuntimeClass>> {
+	fn visit(interface: &Arc<RuntimeClass>, found: &mut Vec<Arc<RuntimeClass>>) {
+		for super_interface in &interface.interfaces {
+			visit(super_interface, found);
+		}
+		let declares_default = interface.class.methods.methods.iter()
+			.any(|method| method.access_flags & (MethodAccessPropertyFlags::Abstract as u16 | MethodAccessPropertyFlags::Static as u16) == 0);
+		if declares_default && !found.contains(interface) {
+			found.push(interface.clone());
+		}
+	}
+
+	let mut found = Vec::new();
+	for interface in &class.interfaces {
+		visit(interface, &mut found);
+	}
+	found
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		error::Error,
+		sync::{Arc, Mutex},
+		thread,
+		time::Duration};
+
+	use crate::{
+		class::{
+			access::{ClassAccessPropertyFlags, FieldAccessPropertyFlags, MethodAccessPropertyFlags},
+			builder::ClassBuilder},
+		isa::opcode::Opcode,
+		vm::{
+			class_loader::{tests::{bytes, loaders}, ClassLoaders, LoaderId},
+			errors::{ExecutionError, LinkageError},
+			gc::HeapOptions,
+			heap::Heap,
+			initialization::InitializationState,
+			interpreter::Interpreter,
+			method_area::RuntimeClass,
+			types::*}};
+
+	const STATIC: &[MethodAccessPropertyFlags] = &[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Static];
+
+	fn interface(name: &str, default_method: bool) -> Vec<u8> {
+		let builder = ClassBuilder::new(name).flags(&[ClassAccessPropertyFlags::Public, ClassAccessPropertyFlags::Interface, ClassAccessPropertyFlags::Abstract]);
+		let builder = match default_method {
+			true => builder.method(&[MethodAccessPropertyFlags::Public], "greet", "()V", |method| { method.op(Opcode::Return); }),
+			false => builder.method(&[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Abstract], "greet", "()V", |_| {}),
+		};
+		bytes(builder)
+	}
+
+	fn load(loaders: &ClassLoaders, name: &str) -> Arc<RuntimeClass> {
+		loaders.load_class(LoaderId::APPLICATION, name).unwrap()
+	}
+
+	#[test]
+	fn test_initialization_order() {
+		let loaders = loaders("initialization-order", &[
+			("demo/Plain", interface("demo/Plain", false)),
+			("demo/Default", interface("demo/Default", true)),
+			("demo/Child", interface("demo/Child", false)),
+			("demo/Base", bytes(ClassBuilder::new("demo/Base"))),
+			("demo/Derived", bytes(ClassBuilder::new("demo/Derived").super_class("demo/Base").interface("demo/Plain").interface("demo/Default"))),
+		]);
+		let order = Mutex::new(Vec::new());
+		let run = |class: &Arc<RuntimeClass>| -> Result<(), Box<dyn Error>> {
+			order.lock().unwrap().push(class.name.clone());
+			Ok(())
+		};
+
+		let derived = load(&loaders, "demo/Derived");
+		assert_eq!(derived.initialization.state(), InitializationState::Uninitialized);
+		loaders.initialize_class(&derived, &run).unwrap();
+		loaders.initialize_class(&derived, &run).unwrap();
+		assert_eq!(*order.lock().unwrap(), vec!["java/lang/Object", "demo/Base", "demo/Default", "demo/Derived"]);
+		assert_eq!(derived.initialization.state(), InitializationState::Initialized);
+		assert!(derived.is_prepared());
+		assert_eq!(load(&loaders, "demo/Plain").initialization.state(), InitializationState::Uninitialized);
+
+		// Reflection initializes only when asked to.
+		let child = loaders.for_name(LoaderId::APPLICATION, "demo/Child", false, &run).unwrap();
+		assert_eq!(child.initialization.state(), InitializationState::Uninitialized);
+		loaders.for_name(LoaderId::APPLICATION, "demo/Child", true, &run).unwrap();
+		assert_eq!(order.lock().unwrap().last().unwrap(), "demo/Child");
+	}
+
+	#[test]
+	fn test_recursive_initialization() {
+		let loaders = loaders("recursive-initialization", &[
+			("demo/A", bytes(ClassBuilder::new("demo/A"))),
+			("demo/B", bytes(ClassBuilder::new("demo/B"))),
+		]);
+		let a = load(&loaders, "demo/A");
+		let b = load(&loaders, "demo/B");
+		let seen = Mutex::new(Vec::new());
+		fn run(loaders: &ClassLoaders, a: &Arc<RuntimeClass>, b: &Arc<RuntimeClass>, seen: &Mutex<Vec<(String, InitializationState)>>, class: &Arc<RuntimeClass>) -> Result<(), Box<dyn Error>> {
+			seen.lock().unwrap().push((class.name.clone(), a.initialization.state()));
+			// A's initializer uses B, whose initializer uses A again.
+			let next = match class.name.as_str() {
+				"demo/A" => b,
+				"demo/B" => a,
+				_ => return Ok(()),
+			};
+			loaders.initialize_class(next, &|class| run(loaders, a, b, seen, class))?;
+			Ok(())
+		}
+		loaders.initialize_class(&a, &|class| run(&loaders, &a, &b, &seen, class)).unwrap();
+		let seen = seen.into_inner().unwrap();
+		assert_eq!(seen.len(), 3);
+		assert_eq!(seen[2].0, "demo/B");
+		assert!(matches!(seen[2].1, InitializationState::InProgress(_)));
+		assert_eq!(a.initialization.state(), InitializationState::Initialized);
+		assert_eq!(b.initialization.state(), InitializationState::Initialized);
+	}
+
+	#[test]
+	fn test_waiting_threads() {
+		let loaders = Arc::new(loaders("waiting-threads", &[("demo/Slow", bytes(ClassBuilder::new("demo/Slow")))]));
+		let slow = load(&loaders, "demo/Slow");
+		let runs = Arc::new(Mutex::new(0));
+		let threads: Vec<_> = (0..4).map(|_| {
+			let (loaders, slow, runs) = (loaders.clone(), slow.clone(), runs.clone());
+			thread::spawn(move || {
+				loaders.initialize_class(&slow, &|class| {
+					if class.name == "demo/Slow" {
+						thread::sleep(Duration::from_millis(50));
+						*runs.lock().unwrap() += 1;
+					}
+					Ok(())
+				})
+			})
+		}).collect();
+		for thread in threads {
+			thread.join().unwrap().unwrap();
+		}
+		assert_eq!(*runs.lock().unwrap(), 1);
+		assert_eq!(slow.initialization.state(), InitializationState::Initialized);
+	}
+
+	#[test]
+	fn test_failed_initialization() {
+		let loaders = loaders("failed-initialization", &[
+			("demo/Broken", bytes(ClassBuilder::new("demo/Broken"))),
+			("demo/Sub", bytes(ClassBuilder::new("demo/Sub").super_class("demo/Broken"))),
+			("demo/Missing", bytes(ClassBuilder::new("demo/Missing"))),
+			("demo/Lookup", bytes(ClassBuilder::new("demo/Lookup"))),
+		]);
+		let run = |class: &Arc<RuntimeClass>| -> Result<(), Box<dyn Error>> {
+			match class.name.as_str() {
+				"demo/Broken" => Err(Box::new(ExecutionError::StackUnderflow)),
+				"demo/Missing" => Err(Box::new(LinkageError::NoSuchField("demo/Gone.field".to_string()))),
+				"demo/Lookup" => Err(Box::new(LinkageError::ClassNotFound("demo.Gone".to_string()))),
+				_ => Ok(()),
+			}
+		};
+
+		let sub = load(&loaders, "demo/Sub");
+		let error = loaders.initialize_class(&sub, &run).unwrap_err();
+		assert_eq!(error, LinkageError::ExceptionInInitializer { class: "demo/Broken".to_string(), cause: "stack underflow".to_string() });
+		assert_eq!(error.exception_class(), "java/lang/ExceptionInInitializerError");
+		assert_eq!(sub.initialization.state(), InitializationState::Erroneous);
+		let broken = sub.super_class.as_ref().unwrap();
+		assert_eq!(broken.initialization.state(), InitializationState::Erroneous);
+		assert_eq!(loaders.initialize_class(broken, &run), Err(LinkageError::NoClassDefFound("Could not initialize class demo.Broken".to_string())));
+		assert_eq!(loaders.initialize_class(&sub, &run), Err(LinkageError::NoClassDefFound("Could not initialize class demo.Sub".to_string())));
+
+		// Errors are thrown as they are.
+		let missing = load(&loaders, "demo/Missing");
+		assert_eq!(loaders.initialize_class(&missing, &run), Err(LinkageError::NoSuchField("demo/Gone.field".to_string())));
+
+		// Exceptions, such as ClassNotFoundException, are wrapped.
+		let lookup = load(&loaders, "demo/Lookup");
+		let cause = "java.lang.ClassNotFoundException: demo.Gone".to_string();
+		assert_eq!(loaders.initialize_class(&lookup, &run), Err(LinkageError::ExceptionInInitializer { class: "demo/Lookup".to_string(), cause }));
+	}
+
+	#[test]
+	fn test_interpreter_initialization() {
+		let counter = ClassBuilder::new("demo/Counter")
+			.field(&[FieldAccessPropertyFlags::Static], "count", "I")
+			.constant_field(&[FieldAccessPropertyFlags::Public, FieldAccessPropertyFlags::Static, FieldAccessPropertyFlags::Final], "LIMIT", "I", 7)
+			.method(&[MethodAccessPropertyFlags::Static], "<clinit>", "()V", |method| {
+				method.push_int(40).field(Opcode::PutStatic, "demo/Counter", "count", "I").op(Opcode::Return);
+			})
+			.method(STATIC, "next", "()I", |method| {
+				method.field(Opcode::GetStatic, "demo/Counter", "count", "I").push_int(6).op(Opcode::IAdd)
+					.field(Opcode::PutStatic, "demo/Counter", "count", "I")
+					.field(Opcode::GetStatic, "demo/Counter", "count", "I").op(Opcode::IReturn);
+			});
+		// The initializer of Broken returns an int from a void method.
+		let broken = ClassBuilder::new("demo/Broken")
+			.field(&[FieldAccessPropertyFlags::Static], "value", "I")
+			.method(&[MethodAccessPropertyFlags::Static], "<clinit>", "()V", |method| { method.push_int(6).op(Opcode::IReturn); });
+		let main = ClassBuilder::new("demo/Main")
+			.method(STATIC, "run", "()I", |method| {
+				method.invoke(Opcode::InvokeStatic, "demo/Counter", "next", "()I", false)
+					.invoke(Opcode::InvokeStatic, "demo/Counter", "next", "()I", false)
+					.op(Opcode::IAdd).op(Opcode::IReturn);
+			})
+			.method(STATIC, "limit", "()I", |method| {
+				method.field(Opcode::GetStatic, "demo/Counter", "LIMIT", "I").op(Opcode::IReturn);
+			})
+			.method(STATIC, "poke", "()V", |method| {
+				method.push_int(9).field(Opcode::PutStatic, "demo/Counter", "LIMIT", "I").op(Opcode::Return);
+			})
+			.method(STATIC, "broken", "()I", |method| {
+				method.field(Opcode::GetStatic, "demo/Broken", "value", "I").op(Opcode::IReturn);
+			})
+			.method(STATIC, "create", "()V", |method| {
+				method.type_op(Opcode::New, "demo/Shape").op(Opcode::Return);
+			});
+		let shape = ClassBuilder::new("demo/Shape").flags(&[ClassAccessPropertyFlags::Public, ClassAccessPropertyFlags::Abstract]);
+		let loaders = Arc::new(loaders("interpreter-initialization", &[
+			("demo/Counter", bytes(counter)),
+			("demo/Broken", bytes(broken)),
+			("demo/Main", bytes(main)),
+			("demo/Shape", bytes(shape)),
+		]));
+		let main = load(&loaders, "demo/Main");
+		let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions::default()));
+		let call = |name: &str, descriptor: &str| Interpreter::for_method(loaders.clone(), heap.clone(), main.clone(), name, descriptor, Vec::new()).unwrap().execute();
+		let linkage_error = |result: Result<Variable, Box<dyn Error>>| *result.unwrap_err().downcast::<LinkageError>().unwrap();
+
+		let counter = load(&loaders, "demo/Counter");
+		assert_eq!(call("limit", "()I").unwrap(), Variable::Int(Int { value: 7 }));
+		assert_eq!(counter.initialization.state(), InitializationState::Initialized);
+		assert_eq!(call("run", "()I").unwrap(), Variable::Int(Int { value: 98 }));
+		assert_eq!(counter.get_static("count", "I"), Some(Variable::Int(Int { value: 52 })));
+		assert!(matches!(linkage_error(call("poke", "()V")), LinkageError::IllegalAccess(_)));
+		assert!(matches!(linkage_error(call("create", "()V")), LinkageError::Instantiation(_)));
+
+		assert_eq!(linkage_error(call("broken", "()I")), LinkageError::ExceptionInInitializer { class: "demo/Broken".to_string(), cause: "Attempt to return I from method type V".to_string() });
+		assert_eq!(linkage_error(call("broken", "()I")), LinkageError::NoClassDefFound("Could not initialize class demo.Broken".to_string()));
+	}
+
+	#[test]
+	fn test_initializer_out_of_memory() {
+		let hoarder = ClassBuilder::new("demo/Hoarder")
+			.field(&[FieldAccessPropertyFlags::Static], "cache", "[I")
+			.method(&[MethodAccessPropertyFlags::Static], "<clinit>", "()V", |method| {
+				method.push_int(100).push_int(100).op(Opcode::IAdd).int(Opcode::NewArray, 10)
+					.field(Opcode::PutStatic, "demo/Hoarder", "cache", "[I").op(Opcode::Return);
+			});
+		let main = ClassBuilder::new("demo/Main")
+			.method(STATIC, "hoard", "()[I", |method| {
+				method.field(Opcode::GetStatic, "demo/Hoarder", "cache", "[I").op(Opcode::AReturn);
+			});
+		let loaders = Arc::new(loaders("initializer-out-of-memory", &[
+			("demo/Hoarder", bytes(hoarder)),
+			("demo/Main", bytes(main)),
+		]));
+		let main = load(&loaders, "demo/Main");
+		let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions { initial_size: 128, maximum_size: 512, ..HeapOptions::default() }));
+		let call = || Interpreter::for_method(loaders.clone(), heap.clone(), main.clone(), "hoard", "()[I", Vec::new()).unwrap().execute();
+
+		// OutOfMemoryError is an Error, so it is thrown as it is rather than wrapped.
+		let error = call().unwrap_err().downcast::<ExecutionError>().unwrap();
+		assert_eq!(*error, ExecutionError::OutOfMemory("Java heap space".to_string()));
+		assert_eq!(LinkageError::Error(*error).exception_class(), "java/lang/OutOfMemoryError");
+		assert_eq!(load(&loaders, "demo/Hoarder").initialization.state(), InitializationState::Erroneous);
+		assert_eq!(*call().unwrap_err().downcast::<LinkageError>().unwrap(), LinkageError::NoClassDefFound("Could not initialize class demo.Hoarder".to_string()));
+	}
+}
@@ -1,4 +1,7 @@
-use std::error::Error;
+use std::{
+	collections::HashMap,
+	error::Error,
+	sync::Arc};
 
 use crate::make_return;
 use crate::vm::{
@@ -7,7 +10,8 @@ use crate::vm::{
 };
 
 use crate::{
-	isa::opcode::Opcode,
+	class::constant_pool::{ConstantPool, ConstantPoolItem},
+	isa::{opcode::Opcode, stack_map::split_method_descriptor},
 	make_conditional_branches,
 	make_float_arithmetic,
 	make_float_comparisons,
@@ -15,19 +19,349 @@ use crate::{
 	make_pop_load_store,
 	make_push,
 	vm::{
+		class_loader::ClassLoaders,
+		errors::LinkageError,
+		field_layout::FieldSlot,
 		frame::StackFrame,
+		heap::{Heap, Reference},
+		local::Locals,
+		method_area::RuntimeClass,
+		native,
+		operand_stack::OperandStack,
+		runtime_constant_pool::ResolvedMember,
 		types::*}};
 
+/// The method an interpreter runs, with the loaders that resolve the references in its code and
+/// the heap its objects live on.
+#[derive(Clone, Debug)]
+struct MethodContext {
+	loaders: Arc<ClassLoaders>,
+	heap: Arc<Heap>,
+	/// The class declaring the method.
+	class: Arc<RuntimeClass>,
+	name: String,
+}
+
 #[derive(Debug, Default)]
 pub struct Interpreter {
 	frame: StackFrame,
+	context: Option<MethodContext>,
 }
 
 impl Interpreter {
 	pub fn new(frame: StackFrame) -> Interpreter {
 		Interpreter {
 			frame,
+			context: None,
+		}
+	}
+
+	/// An interpreter for the method of `class` with the given name and descriptor, its arguments in
+	/// the first local variables.
+	pub fn for_method(loaders: Arc<ClassLoaders>, heap: Arc<Heap>, class: Arc<RuntimeClass>, name: &str, descriptor: &str, arguments: Vec<Variable>) -> Result<Interpreter, Box<dyn Error>> {
+		let method_name = format!("{}.{}{}", class.name, name, descriptor);
+		let method = class.class.find_method(name, descriptor).ok_or_else(|| LinkageError::NoSuchMethod(method_name.clone()))?;
+		let code = method.code().ok_or_else(|| LinkageError::Internal(format!("{} has no code", method_name)))?;
+		let (_, return_descriptor) = split_method_descriptor(descriptor).ok_or_else(|| LinkageError::Internal(format!("bad descriptor for {}", method_name)))?;
+
+		let mut variables = HashMap::new();
+		let mut slot = 0;
+		for argument in arguments {
+			let size = if matches!(argument, Variable::Long(_) | Variable::Double(_)) { 2 } else { 1 };
+			variables.insert(slot, argument);
+			slot += size;
+		}
+		let frame = StackFrame {
+			invoker: None,
+			pc: 0,
+			operand_stack: OperandStack::new(),
+			locals: Locals { variables },
+			constant_pool: ConstantPool::from(&class.class),
+			code: code.code.clone(),
+			return_type: return_type(return_descriptor),
+		};
+		Ok(Interpreter { frame, context: Some(MethodContext { loaders, heap, class, name: name.to_string() }) })
+	}
+
+	/// Store the interned strings of the String constant fields of `class`, then run its `<clinit>`
+	/// method, if it has one. This is the initializer that [ClassLoaders::initialize_class] runs for
+	/// classes the interpreter initializes.
+	pub fn run_initializer(loaders: &Arc<ClassLoaders>, heap: &Arc<Heap>, class: &Arc<RuntimeClass>) -> Result<(), Box<dyn Error>> {
+		for (name, value) in class.string_constants() {
+			let string = heap.intern(&value)?;
+			class.put_static(&name, "Ljava/lang/String;", Variable::ClassReference(ClassReference { value: string }));
+		}
+		if class.class.find_method("<clinit>", "()V").is_none() {
+			return Ok(());
+		}
+		Interpreter::for_method(loaders.clone(), heap.clone(), class.clone(), "<clinit>", "()V", Vec::new())?.execute()?;
+		Ok(())
+	}
+
+	fn context(&self) -> Result<MethodContext, LinkageError> {
+		self.context.clone().ok_or_else(|| LinkageError::Internal("no class to resolve references against".to_string()))
+	}
+
+	/// Initialize `class` if no thread has (JVMS17 5.5), throwing an Error its initializer threw as
+	/// it is.
+	fn initialize(&self, class: &Arc<RuntimeClass>) -> Result<(), Box<dyn Error>> {
+		let MethodContext { loaders, heap, .. } = self.context()?;
+		match self.safepoint(&heap, || loaders.initialize_class(class, &|class| Interpreter::run_initializer(&loaders, &heap, class))) {
+			Ok(()) => Ok(()),
+			Err(LinkageError::Error(error)) => Err(Box::new(error)),
+			Err(error) => Err(Box::new(error)),
+		}
+	}
+
+	/// The references this frame holds, in its local variables and on its operand stack.
+	fn roots(&self) -> Vec<Reference> {
+		let locals = self.frame.locals.variables.values().filter_map(Variable::reference);
+		let stack = self.frame.operand_stack.references().filter_map(Reference::from_raw);
+		locals.chain(stack).collect()
+	}
+
+	/// Run `f`, which may allocate or call another method, with the references this frame holds
+	/// published to `heap`, so a collection meanwhile keeps them.
+	fn safepoint<R, F: FnOnce() -> R>(&self, heap: &Heap, f: F) -> R {
+		self.safepoint_holding(heap, Vec::new(), f)
+	}
+
+	/// A [Interpreter::safepoint] that also keeps `held`, references popped from the frame for `f`,
+	/// such as the arguments of a call.
+	fn safepoint_holding<R, F: FnOnce() -> R>(&self, heap: &Heap, held: Vec<Reference>, f: F) -> R {
+		heap.push_frame(self.roots().into_iter().chain(held).collect());
+		let result = f();
+		heap.pop_frame();
+		result
+	}
+
+	/// Resolve a symbolic reference at a safepoint, since a loader implemented in Java may run to
+	/// load a class.
+	fn resolve<T, F: FnOnce() -> Result<T, LinkageError>>(&self, context: &MethodContext, f: F) -> Result<T, LinkageError> {
+		self.safepoint(&context.heap, f)
+	}
+
+	/// Fetch a big-endian operand, such as a constant pool index.
+	fn fetch_u16(&mut self) -> Result<u16, Box<dyn Error>> {
+		Ok(u16::from_be_bytes([self.fetch()?, self.fetch()?]))
+	}
+
+	/// Push a variable, with booleans, bytes, chars and shorts as ints.
+	fn push_variable(&mut self, variable: &Variable) -> Result<(), Box<dyn Error>> {
+		match variable {
+			Variable::Boolean(boolean) => self.ipush(boolean.value as i32),
+			Variable::Byte(byte) => self.ipush(byte.value as i32),
+			Variable::Char(char) => self.ipush(char.value),
+			Variable::Short(short) => self.ipush(short.value as i32),
+			Variable::Int(int) => self.ipush(int.value),
+			Variable::Long(long) => self.lpush(long.value),
+			Variable::Float(float) => self.fpush(float.value),
+			Variable::Double(double) => self.dpush(double.value),
+			Variable::ClassReference(reference) => self.apush(Some(reference.value)),
+			Variable::ArrayReference(reference) => self.apush(Some(reference.value)),
+			Variable::Null(..) => self.apush(None),
+			other => return Err(Box::new(LinkageError::Internal(format!("cannot push {:?}", other)))),
+		}
+		Ok(())
+	}
+
+	/// Pop a value of the type of a field descriptor, narrowing ints as storing to a field of the
+	/// type does.
+	fn pop_variable(&mut self, descriptor: &str) -> Result<Variable, Box<dyn Error>> {
+		Ok(match descriptor.as_bytes().first() {
+			Some(b'Z') => Variable::Boolean(Boolean { value: self.ipop() & 1 != 0 }),
+			Some(b'B') => Variable::Byte(Byte { value: self.ipop() as i8 }),
+			Some(b'C') => Variable::Char(Char { value: self.ipop() & 0xFFFF }),
+			Some(b'S') => Variable::Short(Short { value: self.ipop() as i16 }),
+			Some(b'I') => Variable::Int(Int { value: self.ipop() }),
+			Some(b'J') => Variable::Long(Long { value: self.lpop() }),
+			Some(b'F') => Variable::Float(Float { value: self.fpop() }),
+			Some(b'D') => Variable::Double(Double { value: self.dpop() }),
+			_ => self.apop(),
+		})
+	}
+
+	/// Push a reference, or null, marked as a reference for the garbage collector.
+	fn apush(&mut self, reference: Option<Reference>) {
+		match reference {
+			Some(reference) => self.frame.operand_stack.push_reference(reference.raw()),
+			None => self.ipush(0),
+		}
+	}
+
+	/// Push a constant from the constant pool (JVMS17 6.5 ldc, ldc_w, ldc2_w).
+	fn load_constant(&mut self, index: u16) -> Result<(), Box<dyn Error>> {
+		let context = self.context()?;
+		match context.class.class.constant_pool.get(&index) {
+			Some(ConstantPoolItem::Integer(integer)) => self.ipush(integer.value),
+			Some(ConstantPoolItem::Float(float)) => self.fpush(float.value),
+			Some(ConstantPoolItem::Long(long)) => self.lpush(long.value),
+			Some(ConstantPoolItem::Double(double)) => self.dpush(double.value),
+			Some(ConstantPoolItem::String(string)) => {
+				let value = context.class.class.get_utf8(string.index)
+					.ok_or_else(|| LinkageError::Internal(format!("no string at constant {}", string.index)))?;
+				let reference = self.safepoint(&context.heap, || context.heap.intern(&value))?;
+				self.apush(Some(reference));
+			}
+			other => return Err(Box::new(LinkageError::Internal(format!("cannot load constant {:?}", other)))),
+		}
+		Ok(())
+	}
+
+	/// Pop a reference, which may be null.
+	fn apop(&mut self) -> Variable {
+		match (Reference::from_raw(self.ipop() as u32), &self.context) {
+			(Some(reference), Some(context)) => context.heap.variable(reference),
+			(Some(reference), None) => Variable::ClassReference(ClassReference { value: reference }),
+			(None, _) => NULL,
+		}
+	}
+
+	fn aload(&mut self, index: u32) -> Result<(), Box<dyn Error>> {
+		let variable = self.frame.locals.variables.get(&index).cloned()
+			.ok_or_else(|| LinkageError::Internal(format!("no reference in local variable {}", index)))?;
+		self.push_variable(&variable)
+	}
+
+	fn astore(&mut self, index: u32) {
+		let reference = self.apop();
+		self.frame.locals.variables.insert(index, reference);
+	}
+
+	/// Resolve the static field an instruction names (JVMS17 6.5 getstatic, putstatic).
+	fn static_field(&mut self) -> Result<ResolvedMember, Box<dyn Error>> {
+		let index = self.fetch_u16()?;
+		let context = self.context()?;
+		let field = self.resolve(&context, || context.loaders.resolve_field(&context.class, index))?;
+		if !field.is_static() {
+			return Err(Box::new(LinkageError::IncompatibleClassChange(format!("Expected static field {}.{}", field.class.name, field.name))));
+		}
+		Ok(field)
+	}
+
+	/// Resolve the instance field an instruction names (JVMS17 6.5 getfield, putfield).
+	fn instance_field(&mut self) -> Result<ResolvedMember, Box<dyn Error>> {
+		let index = self.fetch_u16()?;
+		let context = self.context()?;
+		let field = self.resolve(&context, || context.loaders.resolve_field(&context.class, index))?;
+		if field.is_static() {
+			return Err(Box::new(LinkageError::IncompatibleClassChange(format!("Expected non-static field {}.{}", field.class.name, field.name))));
+		}
+		Ok(field)
+	}
+
+	/// Resolve the method an invoke instruction names, with the index of its reference.
+	fn invoked_method(&mut self) -> Result<(u16, ResolvedMember), Box<dyn Error>> {
+		let index = self.fetch_u16()?;
+		let context = self.context()?;
+		let method = self.resolve(&context, || match context.class.constant_pool.method_ref(index) {
+			Some(_) => context.loaders.resolve_method(&context.class, index),
+			None => context.loaders.resolve_interface_method(&context.class, index),
+		})?;
+		Ok((index, method))
+	}
+
+	/// Pop the arguments of a call to `method`, preceded by the object it is invoked on for an
+	/// instance method, with its return descriptor. Booleans, bytes, chars and shorts are passed as
+	/// the ints their local variables hold (JVMS17 2.6.1).
+	fn pop_arguments(&mut self, method: &ResolvedMember, instance: bool) -> Result<(Vec<Variable>, String), Box<dyn Error>> {
+		let (parameters, return_descriptor) = split_method_descriptor(&method.descriptor)
+			.ok_or_else(|| LinkageError::Internal(format!("bad descriptor {}", method.descriptor)))?;
+		let mut arguments = Vec::new();
+		for parameter in parameters.iter().rev() {
+			let argument = match parameter.as_bytes().first() {
+				Some(b'Z' | b'B' | b'C' | b'S') => Variable::Int(Int { value: self.ipop() }),
+				_ => self.pop_variable(parameter)?,
+			};
+			arguments.insert(0, argument);
+		}
+		if instance {
+			arguments.insert(0, self.apop());
+		}
+		Ok((arguments, return_descriptor.to_string()))
+	}
+
+	/// Invoke an instance method on the object its arguments follow, running the method selected
+	/// for the object's class (JVMS17 6.5 invokeinterface, invokevirtual).
+	fn invoke_virtual(&mut self, method: &ResolvedMember) -> Result<(), Box<dyn Error>> {
+		if method.is_static() {
+			return Err(Box::new(LinkageError::IncompatibleClassChange(format!("Expecting non-static method {}.{}{}", method.class.name, method.name, method.descriptor))));
+		}
+		let (arguments, return_descriptor) = self.pop_arguments(method, true)?;
+		let context = self.context()?;
+		let receiver = context.heap.class_of(receiver(method, &arguments)?)
+			.ok_or_else(|| LinkageError::Internal(format!("no object to invoke {}.{} on", method.class.name, method.name)))?;
+		let selected = context.loaders.select_method(&receiver, method)?;
+		self.invoke(&selected, arguments, &return_descriptor)
+	}
+
+	/// Run the selected `method`, natively or in a new interpreter, and push what it returns.
+	fn invoke(&mut self, method: &ResolvedMember, arguments: Vec<Variable>, return_descriptor: &str) -> Result<(), Box<dyn Error>> {
+		let context = self.context()?;
+		let held = arguments.iter().filter_map(Variable::reference).collect();
+		let result = self.safepoint_holding(&context.heap, held, || match method.is_native() {
+			true => native::invoke(&context.loaders, &context.heap, method, &arguments),
+			false => Interpreter::for_method(context.loaders.clone(), context.heap.clone(), method.class.clone(), &method.name, &method.descriptor, arguments)
+				.and_then(|mut interpreter| interpreter.execute()),
+		})?;
+		if return_descriptor != "V" {
+			self.push_variable(&result)?;
+		}
+		Ok(())
+	}
+
+	/// Pop the object whose field an instruction accesses, throwing NullPointerException for null.
+	fn pop_object(&mut self, field: &ResolvedMember, action: &str) -> Result<Reference, Box<dyn Error>> {
+		match self.apop().reference() {
+			Some(reference) => Ok(reference),
+			None => Err(Box::new(ExecutionError::NullPointer(format!("Cannot {} field \"{}\" because value is null", action, field.name)))),
+		}
+	}
+
+	/// Pop the array an instruction accesses, throwing NullPointerException for null.
+	fn pop_array(&mut self, action: &str) -> Result<Reference, Box<dyn Error>> {
+		match self.apop().reference() {
+			Some(reference) => Ok(reference),
+			None => Err(Box::new(ExecutionError::NullPointer(format!("Cannot {} because value is null", action)))),
+		}
+	}
+
+	/// Load an element of an array (JVMS17 6.5 iaload and the like).
+	fn array_load(&mut self, kind: &str) -> Result<(), Box<dyn Error>> {
+		let index = self.ipop();
+		let array = self.pop_array(&format!("load from {} array", kind))?;
+		let heap = self.context()?.heap;
+		let index = array_index(&heap, array, index)?;
+		let value = heap.get_element(array, index).ok_or_else(|| LinkageError::Internal(format!("no element {} in array", index)))?;
+		self.push_variable(&value)
+	}
+
+	/// Store an element in an array (JVMS17 6.5 iastore and the like), taking a value of the type of
+	/// `descriptor` from the operand stack and narrowing ints to the component type.
+	fn array_store(&mut self, kind: &str, descriptor: &str) -> Result<(), Box<dyn Error>> {
+		let value = self.pop_variable(descriptor)?;
+		let index = self.ipop();
+		let array = self.pop_array(&format!("store to {} array", kind))?;
+		let heap = self.context()?.heap;
+		let index = array_index(&heap, array, index)?;
+		let class = heap.class_of(array).ok_or_else(|| LinkageError::Internal("array is not on the heap".to_string()))?;
+		let value = match (class.component_descriptor(), value) {
+			(Some("Z"), Variable::Int(int)) => Variable::Boolean(Boolean { value: int.value & 1 != 0 }),
+			(Some("B"), Variable::Int(int)) => Variable::Byte(Byte { value: int.value as i8 }),
+			(Some("C"), Variable::Int(int)) => Variable::Char(Char { value: int.value & 0xFFFF }),
+			(Some("S"), Variable::Int(int)) => Variable::Short(Short { value: int.value as i16 }),
+			(_, value) => value,
+		};
+		if let (Some(component), Some(reference)) = (&class.component, value.reference()) {
+			let value_class = heap.class_of(reference).ok_or_else(|| LinkageError::Internal("value is not on the heap".to_string()))?;
+			if !value_class.is_assignable_to(component) {
+				return Err(Box::new(ExecutionError::ArrayStore(value_class.name.replace('/', "."))));
+			}
 		}
+		if !heap.put_element(array, index, &value) {
+			return Err(Box::new(LinkageError::Internal(format!("cannot store {:?} in {}", value, class.name))));
+		}
+		Ok(())
 	}
 
 	pub fn fetch(&mut self) -> Result<u8, Box<dyn Error>> {
@@ -68,15 +402,33 @@ impl Interpreter {
 	make_return!(f, f32, Float, F);
 	make_return!(d, f64, Double, D);
 
+	/// Run the method to its return, counted as running Java code on the heap, which its result is
+	/// published to for the invoker.
 	pub fn execute(&mut self) -> Result<Variable, Box<dyn Error>> {
+		let Some(heap) = self.context.as_ref().map(|context| context.heap.clone()) else {
+			return self.run();
+		};
+		heap.enter_java();
+		let result = self.run();
+		if let Ok(Some(reference)) = result.as_ref().map(Variable::reference) {
+			heap.publish(reference);
+		}
+		heap.exit_java();
+		result
+	}
+
+	fn run(&mut self) -> Result<Variable, Box<dyn Error>> {
 		loop {
+			if let Some(context) = &self.context {
+				context.heap.poll(|| self.roots());
+			}
 			let byte = self.fetch()?;
 			let opcode = self.decode(byte);
 			match opcode {
 				Opcode::Nop => {
 					// do nothing
 				},
-				Opcode::AConstNull => todo!(),
+				Opcode::AConstNull => { self.ipush(0); }
 				Opcode::IConstM1 => todo!(),
 				Opcode::IConst0 => {
 					let item = self.frame.constant_pool.get_int(0)?;
@@ -141,12 +493,20 @@ impl Interpreter {
 					let short = i32::from(i16::from_be_bytes([short_high, short_low]));
 					self.ipush(short);
 				}
-				Opcode::Ldc => todo!(),
-				Opcode::LdcW => todo!(),
-				Opcode::Ldc2W => todo!(),
-				Opcode::ALoad => todo!(),
+				Opcode::Ldc => {
+					let index = u16::from(self.fetch()?);
+					self.load_constant(index)?;
+				}
+				Opcode::LdcW | Opcode::Ldc2W => {
+					let index = self.fetch_u16()?;
+					self.load_constant(index)?;
+				}
+				Opcode::ALoad => {
+					let index = u32::from(self.fetch()?);
+					self.aload(index)?;
+				}
 				Opcode::ILoad => {
-					let index: u32 = u32::from_be_bytes([0, 0, self.frame.operand_stack.pop(), self.frame.operand_stack.pop()]);
+					let index = u32::from(self.fetch()?);
 					self.iload(index)?;
 				}
 				Opcode::ILoad0 => { self.iload(0)?; }
@@ -154,7 +514,7 @@ impl Interpreter {
 				Opcode::ILoad2 => {	self.iload(2)?; }
 				Opcode::ILoad3 => {	self.iload(3)?; }
 				Opcode::LLoad => {
-					let index: u32 = u32::from_be_bytes([0, 0, self.frame.operand_stack.pop(), self.frame.operand_stack.pop()]);
+					let index = u32::from(self.fetch()?);
 					self.lload(index)?;
 				},
 				Opcode::LLoad0 => {	self.lload(0)?; },
@@ -162,7 +522,7 @@ impl Interpreter {
 				Opcode::LLoad2 => {	self.lload(2)?; },
 				Opcode::LLoad3 => { self.lload(3)?; },
 				Opcode::FLoad => {
-					let index: u32 = u32::from_be_bytes([0, 0, self.frame.operand_stack.pop(), self.frame.operand_stack.pop()]);
+					let index = u32::from(self.fetch()?);
 					self.fload(index)?;
 				},
 				Opcode::FLoad0 => {	self.dload(0)?; },
@@ -170,30 +530,33 @@ impl Interpreter {
 				Opcode::FLoad2 => {	self.dload(2)?; },
 				Opcode::FLoad3 => {	self.dload(3)?; },
 				Opcode::DLoad => {
-					let index: u32 = u32::from_be_bytes([0, 0, self.frame.operand_stack.pop(), self.frame.operand_stack.pop()]);
+					let index = u32::from(self.fetch()?);
 					self.dload(index)?;
 				},
 				Opcode::DLoad0 => { self.dload(0)?; },
 				Opcode::DLoad1 => {	self.dload(1)?; },
 				Opcode::DLoad2 => { self.dload(2)?; },
 				Opcode::DLoad3 => { self.dload(3)?; },
-				Opcode::ALoad0 => todo!(),
-				Opcode::ALoad1 => todo!(),
-				Opcode::ALoad2 => todo!(),
-				Opcode::ALoad3 => todo!(),
-				Opcode::IALoad => todo!(),
-				Opcode::LALoad => todo!(),
-				Opcode::FALoad => todo!(),
-				Opcode::DALoad => todo!(),
-				Opcode::AALoad => todo!(),
-				Opcode::BALoad => todo!(),
-				Opcode::CALoad => todo!(),
-				Opcode::SALoad => todo!(),
+				Opcode::ALoad0 => { self.aload(0)?; }
+				Opcode::ALoad1 => { self.aload(1)?; }
+				Opcode::ALoad2 => { self.aload(2)?; }
+				Opcode::ALoad3 => { self.aload(3)?; }
+				Opcode::IALoad => { self.array_load("int")?; }
+				Opcode::LALoad => { self.array_load("long")?; }
+				Opcode::FALoad => { self.array_load("float")?; }
+				Opcode::DALoad => { self.array_load("double")?; }
+				Opcode::AALoad => { self.array_load("object")?; }
+				Opcode::BALoad => { self.array_load("byte/boolean")?; }
+				Opcode::CALoad => { self.array_load("char")?; }
+				Opcode::SALoad => { self.array_load("short")?; }
 				Opcode::IStore => todo!(),
 				Opcode::LStore => todo!(),
 				Opcode::FStore => todo!(),
 				Opcode::DStore => todo!(),
-				Opcode::AStore => todo!(),
+				Opcode::AStore => {
+					let index = u32::from(self.fetch()?);
+					self.astore(index);
+				}
 				Opcode::IStore0 => {
 					let val = self.ipop();
 					self.istore(0, val);
@@ -258,18 +621,18 @@ impl Interpreter {
 					let val = self.dpop();
 					self.dstore(3, val);
 				}
-				Opcode::AStore0 => todo!(),
-				Opcode::AStore1 => todo!(),
-				Opcode::AStore2 => todo!(),
-				Opcode::AStore3 => todo!(),
-				Opcode::IAStore => todo!(),
-				Opcode::LAStore => todo!(),
-				Opcode::FAStore => todo!(),
-				Opcode::DAStore => todo!(),
-				Opcode::AAStore => todo!(),
-				Opcode::BAStore => todo!(),
-				Opcode::CAStore => todo!(),
-				Opcode::SAStore => todo!(),
+				Opcode::AStore0 => { self.astore(0); }
+				Opcode::AStore1 => { self.astore(1); }
+				Opcode::AStore2 => { self.astore(2); }
+				Opcode::AStore3 => { self.astore(3); }
+				Opcode::IAStore => { self.array_store("int", "I")?; }
+				Opcode::LAStore => { self.array_store("long", "J")?; }
+				Opcode::FAStore => { self.array_store("float", "F")?; }
+				Opcode::DAStore => { self.array_store("double", "D")?; }
+				Opcode::AAStore => { self.array_store("object", "L")?; }
+				Opcode::BAStore => { self.array_store("byte/boolean", "I")?; }
+				Opcode::CAStore => { self.array_store("char", "I")?; }
+				Opcode::SAStore => { self.array_store("short", "I")?; }
 				Opcode::Pop => {
 					self.ipop();
 				},
@@ -278,26 +641,26 @@ impl Interpreter {
 					self.ipop();
 				},
 				Opcode::Dup => {
-					let val = self.ipop();
-					self.ipush(val);
-					self.ipush(val);
+					let val = self.frame.operand_stack.pop_word();
+					self.frame.operand_stack.push_word(val);
+					self.frame.operand_stack.push_word(val);
 				}
 				Opcode::DupX1 => {
-					let value_1 = self.ipop();
-					let value_2 = self.ipop();
-					self.ipush(value_1);
-					self.ipush(value_2);
-					self.ipush(value_1);
+					let value_1 = self.frame.operand_stack.pop_word();
+					let value_2 = self.frame.operand_stack.pop_word();
+					self.frame.operand_stack.push_word(value_1);
+					self.frame.operand_stack.push_word(value_2);
+					self.frame.operand_stack.push_word(value_1);
 				},
 				Opcode::DupX2 => todo!(),
 				Opcode::Dup2 => todo!(),
 				Opcode::Dup2X1 => todo!(),
 				Opcode::Dup2X2 => todo!(),
 				Opcode::Swap => {
-					let value_2 = self.ipop();
-					let value_1 = self.ipop();
-					self.ipush(value_2);
-					self.ipush(value_1);
+					let value_2 = self.frame.operand_stack.pop_word();
+					let value_1 = self.frame.operand_stack.pop_word();
+					self.frame.operand_stack.push_word(value_2);
+					self.frame.operand_stack.push_word(value_1);
 				},
 				Opcode::IAdd => { self.iadd(); }
 				Opcode::LAdd => { self.ladd(); }
@@ -445,8 +808,9 @@ impl Interpreter {
 				Opcode::IfICmpGe => { self.if_icmpge(); }
 				Opcode::IfICmpGt => { self.if_icmpgt(); }
 				Opcode::IfICmpLe => { self.if_icmple(); }
-				Opcode::IfACmpEq => todo!(),
-				Opcode::IfACmpNe => todo!(),
+				// References are compared by their raw values, as ints are.
+				Opcode::IfACmpEq => { self.if_icmpeq(); }
+				Opcode::IfACmpNe => { self.if_icmpne(); }
 				Opcode::Goto => {
 					let offset = u32::from(u16::from_be_bytes([self.frame.code[self.frame.pc as usize], self.frame.code[(self.frame.pc + 1) as usize]]));
 					self.frame.pc += offset;
@@ -459,30 +823,161 @@ impl Interpreter {
 				Opcode::LReturn =>  { return self.lreturn(); } 
 				Opcode::FReturn => { return self.freturn(); }
 				Opcode::DReturn => { return self.lreturn(); }
-				Opcode::AReturn => todo!(),
-				Opcode::Return => todo!(),
-				Opcode::GetStatic => todo!(),
-				Opcode::PutStatic => todo!(),
-				Opcode::GetField => todo!(),
-				Opcode::PutField => todo!(),
-				Opcode::InvokeVirtual => todo!(),
-				Opcode::InvokeSpecial => todo!(),
-				Opcode::InvokeStatic => todo!(),
-				Opcode::InvokeInterface => todo!(),
+				Opcode::AReturn => {
+					if !matches!(self.frame.return_type, Type::L(_)) {
+						return Err(Box::new(ExecutionError::BadReturnType(Type::A, self.frame.return_type.clone())));
+					}
+					return Ok(self.apop());
+				}
+				Opcode::Return => { return Ok(NULL); }
+				Opcode::GetStatic => {
+					let field = self.static_field()?;
+					self.initialize(&field.class)?;
+					let value = field.class.get_static(&field.name, &field.descriptor)
+						.ok_or_else(|| LinkageError::Internal(format!("{}.{} is not prepared", field.class.name, field.name)))?;
+					self.push_variable(&value)?;
+				}
+				Opcode::PutStatic => {
+					let field = self.static_field()?;
+					let context = self.context()?;
+					if field.is_final() && (field.class != context.class || context.name != "<clinit>") {
+						return Err(Box::new(LinkageError::IllegalAccess(format!("Update to static final field {}.{} attempted from a different method ({}) than the initializer method <clinit>", field.class.name, field.name, context.name))));
+					}
+					self.initialize(&field.class)?;
+					let value = self.pop_variable(&field.descriptor)?;
+					context.heap.put_static(&field.class, &field.name, &field.descriptor, value);
+				}
+				Opcode::GetField => {
+					let field = self.instance_field()?;
+					let object = self.pop_object(&field, "read")?;
+					let heap = self.context()?.heap;
+					let value = field_slot(&heap, object, &field).and_then(|slot| heap.get_field(object, &slot))
+						.ok_or_else(|| LinkageError::Internal(format!("object has no field {}.{}", field.class.name, field.name)))?;
+					self.push_variable(&value)?;
+				}
+				Opcode::PutField => {
+					let field = self.instance_field()?;
+					let context = self.context()?;
+					if field.is_final() && (field.class != context.class || context.name != "<init>") {
+						return Err(Box::new(LinkageError::IllegalAccess(format!("Update to non-static final field {}.{} attempted from a different method ({}) than the initializer method <init>", field.class.name, field.name, context.name))));
+					}
+					let value = self.pop_variable(&field.descriptor)?;
+					let object = self.pop_object(&field, "assign")?;
+					let stored = field_slot(&context.heap, object, &field).is_some_and(|slot| context.heap.put_field(object, &slot, &value));
+					if !stored {
+						return Err(Box::new(LinkageError::Internal(format!("object has no field {}.{}", field.class.name, field.name))));
+					}
+				}
+				Opcode::InvokeVirtual => {
+					let (_, method) = self.invoked_method()?;
+					self.invoke_virtual(&method)?;
+				}
+				Opcode::InvokeSpecial => {
+					let (index, method) = self.invoked_method()?;
+					if method.is_static() {
+						return Err(Box::new(LinkageError::IncompatibleClassChange(format!("Expecting non-static method {}.{}{}", method.class.name, method.name, method.descriptor))));
+					}
+					let context = self.context()?;
+					let (class_index, _, _) = context.class.constant_pool.method_ref(index)
+						.or_else(|| context.class.constant_pool.interface_method_ref(index))
+						.ok_or_else(|| LinkageError::Internal(format!("no method reference at constant {}", index)))?;
+					let symbolic = self.resolve(&context, || context.loaders.resolve_class(&context.class, class_index))?;
+					let selected = context.loaders.select_special(&context.class, &symbolic, &method)?;
+					let (arguments, return_descriptor) = self.pop_arguments(&method, true)?;
+					receiver(&method, &arguments)?;
+					self.invoke(&selected, arguments, &return_descriptor)?;
+				}
+				Opcode::InvokeStatic => {
+					let (_, method) = self.invoked_method()?;
+					if !method.is_static() {
+						return Err(Box::new(LinkageError::IncompatibleClassChange(format!("Expected static method {}.{}{}", method.class.name, method.name, method.descriptor))));
+					}
+					self.initialize(&method.class)?;
+					let (arguments, return_descriptor) = self.pop_arguments(&method, false)?;
+					self.invoke(&method, arguments, &return_descriptor)?;
+				}
+				Opcode::InvokeInterface => {
+					let (_, method) = self.invoked_method()?;
+					// The count and zero operands say nothing the descriptor does not.
+					self.fetch()?;
+					self.fetch()?;
+					self.invoke_virtual(&method)?;
+				}
 				Opcode::InvokeDynamic => todo!(),
-				Opcode::New => todo!(),
-				Opcode::NewArray => todo!(),
-				Opcode::ANewArray => todo!(),
-				Opcode::ArrayLength => todo!(),
+				Opcode::New => {
+					let index = self.fetch_u16()?;
+					let context = self.context()?;
+					let class = self.resolve(&context, || context.loaders.resolve_class(&context.class, index))?;
+					if class.is_interface() || class.is_abstract() {
+						return Err(Box::new(LinkageError::Instantiation(class.name.clone())));
+					}
+					self.initialize(&class)?;
+					let reference = self.safepoint(&context.heap, || context.heap.allocate(&class))?;
+					self.apush(Some(reference));
+				}
+				Opcode::NewArray => {
+					let descriptor = match self.fetch()? {
+						4 => "Z",
+						5 => "C",
+						6 => "F",
+						7 => "D",
+						8 => "B",
+						9 => "S",
+						10 => "I",
+						11 => "J",
+						array_type => return Err(Box::new(LinkageError::Internal(format!("invalid array type {}", array_type)))),
+					};
+					let count = self.ipop();
+					let context = self.context()?;
+					let class = self.resolve(&context, || context.loaders.load_referenced_class(context.class.loader, &format!("[{}", descriptor)))?;
+					let length = array_length(count)?;
+					let array = self.safepoint(&context.heap, || context.heap.allocate_array(&class, length))?;
+					self.apush(Some(array));
+				}
+				Opcode::ANewArray => {
+					let index = self.fetch_u16()?;
+					let count = self.ipop();
+					let context = self.context()?;
+					let component = self.resolve(&context, || context.loaders.resolve_class(&context.class, index))?;
+					let name = match component.is_array() {
+						true => format!("[{}", component.name),
+						false => format!("[L{};", component.name),
+					};
+					let class = self.resolve(&context, || context.loaders.load_referenced_class(context.class.loader, &name))?;
+					let length = array_length(count)?;
+					let array = self.safepoint(&context.heap, || context.heap.allocate_array(&class, length))?;
+					self.apush(Some(array));
+				}
+				Opcode::ArrayLength => {
+					let array = self.pop_array("read the array length")?;
+					let length = self.context()?.heap.array_length(array).ok_or_else(|| LinkageError::Internal("not an array".to_string()))?;
+					self.ipush(length as i32);
+				}
 				Opcode::AThrow => todo!(),
 				Opcode::CheckCast => todo!(),
 				Opcode::InstanceOf => todo!(),
 				Opcode::MonitorEnter => todo!(),
 				Opcode::MonitorExit => todo!(),
 				Opcode::Wide => todo!(),
-				Opcode::MultiANewArray => todo!(),
-				Opcode::IfNull => todo!(),
-				Opcode::IfNonNull => todo!(),
+				Opcode::MultiANewArray => {
+					let index = self.fetch_u16()?;
+					let dimensions = match self.fetch()? {
+						0 => return Err(Box::new(LinkageError::Internal("multianewarray of 0 dimensions".to_string()))),
+						dimensions => dimensions as usize,
+					};
+					let context = self.context()?;
+					let class = self.resolve(&context, || context.loaders.resolve_class(&context.class, index))?;
+					let mut counts = vec![0; dimensions];
+					for count in counts.iter_mut().rev() {
+						*count = self.ipop();
+					}
+					let counts = counts.into_iter().map(array_length).collect::<Result<Vec<_>, _>>()?;
+					let array = self.safepoint(&context.heap, || new_multi_array(&context.heap, &class, &counts))?;
+					self.apush(Some(array));
+				}
+				// Null is zero on the operand stack.
+				Opcode::IfNull => { self.if_eq(); }
+				Opcode::IfNonNull => { self.if_ne(); }
 				Opcode::GotoW => todo!(),
 				Opcode::JsrW => todo!(),
 				Opcode::Breakpoint => {
@@ -499,6 +994,62 @@ impl Interpreter {
 	}
 }
 
+/// Where `field` lives in `object`.
+fn field_slot(heap: &Heap, object: Reference, field: &ResolvedMember) -> Option<FieldSlot> {
+	heap.class_of(object)?.field_layout().find(&field.class.name, &field.name, &field.descriptor).cloned()
+}
+
+/// The object an instance method is invoked on, the first of its arguments, throwing
+/// NullPointerException for null.
+fn receiver(method: &ResolvedMember, arguments: &[Variable]) -> Result<Reference, ExecutionError> {
+	arguments.first().and_then(Variable::reference)
+		.ok_or_else(|| ExecutionError::NullPointer(format!("Cannot invoke \"{}.{}{}\" because value is null", method.class.name.replace('/', "."), method.name, method.descriptor)))
+}
+
+/// The length of a new array, throwing NegativeArraySizeException for a negative count.
+fn array_length(count: i32) -> Result<u32, ExecutionError> {
+	u32::try_from(count).map_err(|_| ExecutionError::NegativeArraySize(count.to_string()))
+}
+
+/// An index into an array, throwing ArrayIndexOutOfBoundsException if it is out of bounds.
+fn array_index(heap: &Heap, array: Reference, index: i32) -> Result<u32, Box<dyn Error>> {
+	let length = heap.array_length(array).ok_or_else(|| LinkageError::Internal("not an array".to_string()))?;
+	match u32::try_from(index) {
+		Ok(index) if index < length => Ok(index),
+		_ => Err(Box::new(ExecutionError::ArrayIndexOutOfBounds(format!("Index {} out of bounds for length {}", index, length)))),
+	}
+}
+
+/// Allocate an array of the array class `class` with `counts[0]` elements, each an array of the
+/// component type allocated the same way from the remaining counts (JVMS17 6.5 multianewarray).
+fn new_multi_array(heap: &Heap, class: &Arc<RuntimeClass>, counts: &[u32]) -> Result<Reference, ExecutionError> {
+	let array = heap.allocate_array(class, counts[0])?;
+	if let (Some(component), [_, rest @ ..]) = (&class.component, counts) && !rest.is_empty() {
+		// The array is in no frame yet, so keep it while its elements are allocated.
+		heap.push_frame(vec![array]);
+		let elements = (0..counts[0]).try_for_each(|index| {
+			let element = new_multi_array(heap, component, rest)?;
+			heap.put_element(array, index, &Variable::ArrayReference(ArrayReference { value: element }));
+			Ok(())
+		});
+		heap.pop_frame();
+		elements?;
+	}
+	Ok(array)
+}
+
+/// The type a method with the given return descriptor leaves on its invoker's operand stack.
+fn return_type(descriptor: &str) -> Type {
+	match descriptor {
+		"V" => Type::V,
+		"Z" | "B" | "C" | "S" | "I" => Type::I,
+		"J" => Type::J,
+		"F" => Type::F,
+		"D" => Type::D,
+		reference => Type::L(reference.to_string()),
+	}
+}
+
 mod tests {
 	use std::{
 		collections::HashMap,
@@ -0,0 +1,467 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::{
+	class::access::MethodAccessPropertyFlags,
+	vm::{
+		class_loader::ClassLoaders,
+		errors::LinkageError,
+		method_area::RuntimeClass,
+		runtime_constant_pool::{Resolved, ResolvedMember}}};
+
+/// Linking (JVMS17 5.4): preparation, and resolution of the symbolic references in run-time
+/// constant pools. Classes are not verified.
+impl ClassLoaders {
+
+	/// Link a class after linking its superclass and superinterfaces: impose the loading constraints
+	/// its overriding methods need, then prepare it (JVMS17 5.4.2).
+	pub fn link_class(&self, class: &RuntimeClass) -> Result<(), LinkageError> {
+		if class.is_prepared() {
+			return Ok(());
+		}
+		for supertype in class.super_class.iter().chain(class.interfaces.iter()) {
+			self.link_class(supertype)?;
+		}
+		let interfaces = superinterfaces(class);
+		for method in &class.class.methods.methods {
+			let (Some(name), Some(descriptor)) = (class.class.get_utf8(method.name_index), class.class.get_utf8(method.descriptor_index)) else {
+				continue;
+			};
+			if method.access_flags & (MethodAccessPropertyFlags::Static as u16 | MethodAccessPropertyFlags::Private as u16) != 0 || name.starts_with('<') {
+				continue;
+			}
+			for overridden in class.super_classes().chain(interfaces.iter()) {
+				if overridden.loader != class.loader && overridden.class.find_method(&name, &descriptor).is_some() {
+					self.add_descriptor_constraints(&descriptor, class.loader, overridden.loader)?;
+				}
+			}
+		}
+		class.prepare();
+		Ok(())
+	}
+
+	/// Resolve the Class constant at `index` in `current`'s run-time constant pool (JVMS17 5.4.3.1).
+	pub fn resolve_class(&self, current: &RuntimeClass, index: u16) -> Result<Arc<RuntimeClass>, LinkageError> {
+		let name = current.constant_pool.class_name(index).ok_or_else(|| bad_constant(current, index, "Class"))?;
+		let resolved = current.constant_pool.resolve(index, || {
+			let class = self.load_referenced_class(current.loader, &name)?;
+			if !is_class_accessible(current, &class) {
+				return Err(LinkageError::IllegalAccess(format!("failed to access class {} from class {}", class.name, current.name)));
+			}
+			Ok(Resolved::Class(class))
+		})?;
+		match resolved {
+			Resolved::Class(class) => Ok(class),
+			_ => Err(bad_constant(current, index, "Class")),
+		}
+	}
+
+	/// Resolve the FieldRef constant at `index` in `current`'s run-time constant pool (JVMS17 5.4.3.2).
+	pub fn resolve_field(&self, current: &RuntimeClass, index: u16) -> Result<ResolvedMember, LinkageError> {
+		let (class_index, name, descriptor) = current.constant_pool.field_ref(index).ok_or_else(|| bad_constant(current, index, "FieldRef"))?;
+		let resolved = current.constant_pool.resolve(index, || {
+			let class = self.resolve_class(current, class_index)?;
+			let field = field_lookup(&class, &name, &descriptor)
+				.ok_or_else(|| LinkageError::NoSuchField(format!("{}.{}", class.name, name)))?;
+			self.check_member(current, &field)?;
+			Ok(Resolved::Field(field))
+		})?;
+		match resolved {
+			Resolved::Field(field) => Ok(field),
+			_ => Err(bad_constant(current, index, "FieldRef")),
+		}
+	}
+
+	/// Resolve the MethodRef constant at `index` in `current`'s run-time constant pool (JVMS17 5.4.3.3).
+	pub fn resolve_method(&self, current: &RuntimeClass, index: u16) -> Result<ResolvedMember, LinkageError> {
+		let (class_index, name, descriptor) = current.constant_pool.method_ref(index).ok_or_else(|| bad_constant(current, index, "MethodRef"))?;
+		let resolved = current.constant_pool.resolve(index, || {
+			let class = self.resolve_class(current, class_index)?;
+			if class.is_interface() {
+				return Err(LinkageError::IncompatibleClassChange(format!("found interface {}, but class was expected", class.name)));
+			}
+			let method = std::iter::once(&class).chain(class.super_classes())
+				.find_map(|class| declared_method(class, &name, &descriptor))
+				.or_else(|| superinterface_method(&class, &name, &descriptor))
+				.ok_or_else(|| LinkageError::NoSuchMethod(format!("{}.{}{}", class.name, name, descriptor)))?;
+			self.check_member(current, &method)?;
+			Ok(Resolved::Method(method))
+		})?;
+		match resolved {
+			Resolved::Method(method) => Ok(method),
+			_ => Err(bad_constant(current, index, "MethodRef")),
+		}
+	}
+
+	/// Resolve the InterfaceMethodRef constant at `index` in `current`'s run-time constant pool
+	/// (JVMS17 5.4.3.4).
+	pub fn resolve_interface_method(&self, current: &RuntimeClass, index: u16) -> Result<ResolvedMember, LinkageError> {
+		let (class_index, name, descriptor) = current.constant_pool.interface_method_ref(index).ok_or_else(|| bad_constant(current, index, "InterfaceMethodRef"))?;
+		let resolved = current.constant_pool.resolve(index, || {
+			let class = self.resolve_class(current, class_index)?;
+			if !class.is_interface() {
+				return Err(LinkageError::IncompatibleClassChange(format!("found class {}, but interface was expected", class.name)));
+			}
+			// The superclass of an interface is java/lang/Object, whose public instance methods it has.
+			let method = declared_method(&class, &name, &descriptor)
+				.or_else(|| class.super_class.as_ref()
+					.and_then(|object| declared_method(object, &name, &descriptor))
+					.filter(|method| method.is_public() && !method.is_static()))
+				.or_else(|| superinterface_method(&class, &name, &descriptor))
+				.ok_or_else(|| LinkageError::NoSuchMethod(format!("{}.{}{}", class.name, name, descriptor)))?;
+			self.check_member(current, &method)?;
+			Ok(Resolved::Method(method))
+		})?;
+		match resolved {
+			Resolved::Method(method) => Ok(method),
+			_ => Err(bad_constant(current, index, "InterfaceMethodRef")),
+		}
+	}
+
+	/// Select the method an invokevirtual of `resolved` runs on an object of class `receiver`
+	/// (JVMS17 5.4.6): `resolved` itself if it is private, otherwise the nearest method of the
+	/// receiver's class and superclasses that overrides it, or failing that a maximally-specific
+	/// superinterface method.
+	pub fn select_method(&self, receiver: &Arc<RuntimeClass>, resolved: &ResolvedMember) -> Result<ResolvedMember, LinkageError> {
+		if resolved.is_private() {
+			return Ok(resolved.clone());
+		}
+		let method = std::iter::once(receiver).chain(receiver.super_classes())
+			.find_map(|class| declared_method(class, &resolved.name, &resolved.descriptor).filter(|method| overrides(method, resolved)))
+			.or_else(|| superinterface_method(receiver, &resolved.name, &resolved.descriptor));
+		concrete(method, receiver, resolved)
+	}
+
+	/// Select the method an invokespecial of `resolved` runs from `current` (JVMS17 6.5
+	/// invokespecial): the nearest declared by the direct superclass of `current` and its
+	/// superclasses when `symbolic`, the class the reference names, is a superclass of `current`
+	/// and the method is not an instance initialization method, otherwise `resolved` itself.
+	pub fn select_special(&self, current: &RuntimeClass, symbolic: &Arc<RuntimeClass>, resolved: &ResolvedMember) -> Result<ResolvedMember, LinkageError> {
+		let start = match current.super_class.as_ref() {
+			Some(super_class) if resolved.name != "<init>" && !symbolic.is_interface() && current.super_classes().any(|class| class == symbolic) => super_class,
+			_ => return concrete(Some(resolved.clone()), symbolic, resolved),
+		};
+		let method = std::iter::once(start).chain(start.super_classes())
+			.find_map(|class| declared_method(class, &resolved.name, &resolved.descriptor))
+			.or_else(|| superinterface_method(start, &resolved.name, &resolved.descriptor));
+		concrete(method, start, resolved)
+	}
+
+	/// Check that a resolved member is accessible to `current`, and impose the loading constraints
+	/// that keep the classes in its descriptor the same on both sides.
+	fn check_member(&self, current: &RuntimeClass, member: &ResolvedMember) -> Result<(), LinkageError> {
+		if !is_member_accessible(current, member) {
+			let kind = if member.descriptor.starts_with('(') { "method" } else { "field" };
+			return Err(LinkageError::IllegalAccess(format!("class {} tried to access {} {}.{}", current.name, kind, member.class.name, member.name)));
+		}
+		self.add_descriptor_constraints(&member.descriptor, current.loader, member.class.loader)
+	}
+}
+
+fn bad_constant(current: &RuntimeClass, index: u16, kind: &str) -> LinkageError {
+	LinkageError::ClassFormat { name: current.name.clone(), message: format!("constant {} is not a valid {}", index, kind) }
+}
+
+fn declared_method(class: &Arc<RuntimeClass>, name: &str, descriptor: &str) -> Option<ResolvedMember> {
+	let method = class.class.find_method(name, descriptor)?;
+	Some(ResolvedMember { class: class.clone(), name: name.to_string(), descriptor: descriptor.to_string(), access_flags: method.access_flags })
+}
+
+/// Whether `method`, declared by a class, overrides `overridden` (JVMS17 5.4.5). The transitive
+/// case, through a method of an intermediate package-private class, is not followed.
+fn overrides(method: &ResolvedMember, overridden: &ResolvedMember) -> bool {
+	!method.is_static() && !method.is_private() &&
+		(method.class == overridden.class || overridden.is_public() || overridden.is_protected() || method.class.package() == overridden.class.package())
+}
+
+/// The method selected for an invocation, failing with AbstractMethodError when there is none or
+/// it has no code to run.
+fn concrete(method: Option<ResolvedMember>, class: &RuntimeClass, resolved: &ResolvedMember) -> Result<ResolvedMember, LinkageError> {
+	method.filter(|method| !method.is_abstract())
+		.ok_or_else(|| LinkageError::AbstractMethod(format!("Receiver class {} does not define or inherit an implementation of the resolved method '{}{}' of {}", class.name.replace('/', "."), resolved.name, resolved.descriptor, resolved.class.name.replace('/', "."))))
+}
+
+/// Look a field up in a class, then its direct superinterfaces and then its superclass, each
+/// searched the same way (JVMS17 5.4.3.2).
+fn field_lookup(class: &Arc<RuntimeClass>, name: &str, descriptor: &str) -> Option<ResolvedMember> {
+	if let Some(field) = class.class.find_field(name, descriptor) {
+		return Some(ResolvedMember { class: class.clone(), name: name.to_string(), descriptor: descriptor.to_string(), access_flags: field.access_flags });
+	}
+	class.interfaces.iter().find_map(|interface| field_lookup(interface, name, descriptor))
+		.or_else(|| class.super_class.as_ref().and_then(|super_class| field_lookup(super_class, name, descriptor)))
+}
+
+/// Every superinterface of a class, direct or inherited, each once and nearest first.
+fn superinterfaces(class: &RuntimeClass) -> Vec<Arc<RuntimeClass>> {
+	let mut pending: VecDeque<Arc<RuntimeClass>> = class.interfaces.iter()
+		.chain(class.super_classes().flat_map(|super_class| super_class.interfaces.iter()))
+		.cloned()
+		.collect();
+	let mut found: Vec<Arc<RuntimeClass>> = Vec::new();
+	while let Some(interface) = pending.pop_front() {
+		if !found.contains(&interface) {
+			pending.extend(interface.interfaces.iter().cloned());
+			found.push(interface);
+		}
+	}
+	found
+}
+
+/// The method a class gets from its superinterfaces (JVMS17 5.4.3.3): the maximally-specific one
+/// if exactly one of those is not abstract, otherwise any of them.
+fn superinterface_method(class: &RuntimeClass, name: &str, descriptor: &str) -> Option<ResolvedMember> {
+	let candidates: Vec<ResolvedMember> = superinterfaces(class).iter()
+		.filter_map(|interface| declared_method(interface, name, descriptor))
+		.filter(|method| !method.is_private() && !method.is_static())
+		.collect();
+	let maximally_specific: Vec<&ResolvedMember> = candidates.iter()
+		.filter(|method| !candidates.iter().any(|other| other.class != method.class && superinterfaces(&other.class).contains(&method.class)))
+		.collect();
+	match maximally_specific.iter().filter(|method| !method.is_abstract()).collect::<Vec<_>>()[..] {
+		[method] => Some((*method).clone()),
+		_ => maximally_specific.first().map(|method| (*method).clone()),
+	}
+}
+
+/// Whether `class` is accessible to `current` (JVMS17 5.4.4). Module readability is not checked.
+fn is_class_accessible(current: &RuntimeClass, class: &RuntimeClass) -> bool {
+	class.is_public() || class.package() == current.package()
+}
+
+/// Whether `member` is accessible to `current` (JVMS17 5.4.4), private members being accessible
+/// throughout their nest.
+fn is_member_accessible(current: &RuntimeClass, member: &ResolvedMember) -> bool {
+	let declaring = &member.class;
+	if member.is_public() {
+		true
+	} else if member.is_private() {
+		**declaring == *current || (declaring.loader == current.loader && declaring.nest_host_name() == current.nest_host_name())
+	} else {
+		declaring.package() == current.package() || (member.is_protected() && current.is_subclass_of(declaring))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fs, sync::Arc};
+
+	use crate::{
+		class::{
+			access::{ClassAccessPropertyFlags, FieldAccessPropertyFlags, MethodAccessPropertyFlags},
+			builder::{ClassBuilder, MethodBuilder},
+			class::Class,
+			constant_pool_builder::ConstantPoolBuilder},
+		isa::opcode::Opcode,
+		vm::{
+			class_loader::{tests::{boot_loaders, bytes, loaders, root}, LoaderId},
+			errors::LinkageError,
+			gc::HeapOptions,
+			heap::Heap,
+			interpreter::Interpreter,
+			types::*}};
+
+	const PUBLIC: &[MethodAccessPropertyFlags] = &[MethodAccessPropertyFlags::Public];
+	const ABSTRACT: &[MethodAccessPropertyFlags] = &[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Abstract];
+
+	/// Add a FieldRef, MethodRef or InterfaceMethodRef constant, chosen by an instruction that would
+	/// use it, returning its index. Constants already in the pool are reused.
+	fn reference(class: &mut Class, opcode: Opcode, owner: &str, name: &str, descriptor: &str) -> u16 {
+		let mut pool = ConstantPoolBuilder::from(std::mem::take(&mut class.constant_pool));
+		let index = match opcode {
+			Opcode::GetField | Opcode::GetStatic => pool.add_field_ref(owner, name, descriptor),
+			Opcode::InvokeInterface => pool.add_interface_method_ref(owner, name, descriptor),
+			_ => pool.add_method_ref(owner, name, descriptor),
+		}.unwrap();
+		class.constant_pool = pool.into_constants();
+		index
+	}
+
+	fn interface(name: &str) -> ClassBuilder<'static> {
+		ClassBuilder::new(name).flags(&[ClassAccessPropertyFlags::Public, ClassAccessPropertyFlags::Interface, ClassAccessPropertyFlags::Abstract])
+	}
+
+	#[test]
+	fn test_preparation() {
+		let flags = &[FieldAccessPropertyFlags::Public, FieldAccessPropertyFlags::Static, FieldAccessPropertyFlags::Final];
+		let loaders = Arc::new(loaders("preparation", &[
+			("demo/Base", bytes(ClassBuilder::new("demo/Base").field(&[FieldAccessPropertyFlags::Static], "count", "I"))),
+			("demo/Constants", bytes(ClassBuilder::new("demo/Constants")
+				.super_class("demo/Base")
+				.constant_field(flags, "ANSWER", "I", 42)
+				.constant_field(flags, "YES", "Z", 1)
+				.constant_field(flags, "LETTER", "C", 'x' as i32)
+				.constant_field(flags, "SMALL", "S", -7)
+				.constant_field(flags, "BIG", "J", 1i64 << 40)
+				.constant_field(flags, "HALF", "F", 0.5f32)
+				.constant_field(flags, "RATE", "D", 2.5)
+				.constant_field(flags, "NAME", "Ljava/lang/String;", "steele")
+				.field(&[FieldAccessPropertyFlags::Static], "numbers", "[I")
+				.field(&[FieldAccessPropertyFlags::Static], "ratio", "D")
+				.field(&[], "instance", "I"))),
+		]));
+		let constants = loaders.load_class(LoaderId::APPLICATION, "demo/Constants").unwrap();
+		assert!(!constants.is_prepared());
+		assert_eq!(constants.get_static("ANSWER", "I"), None);
+		loaders.link_class(&constants).unwrap();
+		assert!(constants.is_prepared() && constants.super_class.as_ref().unwrap().is_prepared());
+
+		assert_eq!(constants.get_static("ANSWER", "I"), Some(Variable::Int(Int { value: 42 })));
+		assert_eq!(constants.get_static("YES", "Z"), Some(Variable::Boolean(Boolean { value: true })));
+		assert_eq!(constants.get_static("LETTER", "C"), Some(Variable::Char(Char { value: 'x' as i32 })));
+		assert_eq!(constants.get_static("SMALL", "S"), Some(Variable::Short(Short { value: -7 })));
+		assert_eq!(constants.get_static("BIG", "J"), Some(Variable::Long(Long { value: 1 << 40 })));
+		assert_eq!(constants.get_static("HALF", "F"), Some(Variable::Float(Float { value: 0.5 })));
+		assert_eq!(constants.get_static("RATE", "D"), Some(Variable::Double(Double { value: 2.5 })));
+		assert_eq!(constants.get_static("NAME", "Ljava/lang/String;"), Some(NULL));
+		assert_eq!(constants.get_static("numbers", "[I"), Some(NULL));
+		assert_eq!(constants.get_static("ratio", "D"), Some(Variable::Double(Double { value: 0.0 })));
+		assert_eq!(constants.get_static("instance", "I"), None);
+
+		// String constants are stored once the class is initialized.
+		let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions::default()));
+		loaders.initialize_class(&constants, &|class| Interpreter::run_initializer(&loaders, &heap, class)).unwrap();
+		let name = constants.get_static("NAME", "Ljava/lang/String;").and_then(|name| name.reference()).unwrap();
+		assert_eq!(name, heap.intern("steele").unwrap());
+		assert_eq!(heap.string_value(name).as_deref(), Some("steele"));
+
+		assert!(constants.put_static("ANSWER", "I", Variable::Int(Int { value: 7 })));
+		assert!(!constants.put_static("missing", "I", Variable::Int(Int { value: 7 })));
+		assert_eq!(constants.get_static("ANSWER", "I"), Some(Variable::Int(Int { value: 7 })));
+	}
+
+	#[test]
+	fn test_field_resolution() {
+		let mut user = ClassBuilder::new("demo/User").build().unwrap();
+		let inherited = reference(&mut user, Opcode::GetField, "demo/Derived", "count", "I");
+		let from_interface = reference(&mut user, Opcode::GetStatic, "demo/Derived", "PREFIX", "Ljava/lang/String;");
+		let private = reference(&mut user, Opcode::GetField, "demo/Derived", "secret", "I");
+		let missing = reference(&mut user, Opcode::GetField, "demo/Derived", "missing", "I");
+		let mut stranger = ClassBuilder::new("other/Stranger").build().unwrap();
+		let package_private = reference(&mut stranger, Opcode::GetStatic, "demo/Base", "total", "I");
+		let protected = reference(&mut stranger, Opcode::GetField, "demo/Base", "count", "I");
+		let hidden_class = reference(&mut stranger, Opcode::GetStatic, "demo/Hidden", "value", "I");
+		let mut subclass = ClassBuilder::new("other/Subclass").super_class("demo/Base").build().unwrap();
+		let protected_from_subclass = reference(&mut subclass, Opcode::GetField, "demo/Base", "count", "I");
+
+		let static_final = &[FieldAccessPropertyFlags::Public, FieldAccessPropertyFlags::Static, FieldAccessPropertyFlags::Final];
+		let loaders = loaders("field-resolution", &[
+			("demo/Named", bytes(interface("demo/Named").constant_field(static_final, "PREFIX", "Ljava/lang/String;", "n"))),
+			("demo/Base", bytes(ClassBuilder::new("demo/Base")
+				.interface("demo/Named")
+				.field(&[FieldAccessPropertyFlags::Protected], "count", "I")
+				.field(&[FieldAccessPropertyFlags::Private], "secret", "I")
+				.field(&[FieldAccessPropertyFlags::Static], "total", "I"))),
+			("demo/Derived", bytes(ClassBuilder::new("demo/Derived").super_class("demo/Base"))),
+			("demo/Hidden", bytes(ClassBuilder::new("demo/Hidden").flags(&[ClassAccessPropertyFlags::Super]))),
+			("demo/User", user.to_bytes().unwrap()),
+			("other/Stranger", stranger.to_bytes().unwrap()),
+			("other/Subclass", subclass.to_bytes().unwrap()),
+		]);
+		let user = loaders.load_class(LoaderId::APPLICATION, "demo/User").unwrap();
+		assert!(!user.constant_pool.is_resolved(inherited));
+		let count = loaders.resolve_field(&user, inherited).unwrap();
+		assert_eq!((count.class.name.as_str(), count.name.as_str(), count.is_protected()), ("demo/Base", "count", true));
+		assert!(user.constant_pool.is_resolved(inherited));
+		assert_eq!(loaders.resolve_field(&user, from_interface).unwrap().class.name, "demo/Named");
+		assert!(matches!(loaders.resolve_field(&user, private), Err(LinkageError::IllegalAccess(_))));
+		assert_eq!(loaders.resolve_field(&user, missing), Err(LinkageError::NoSuchField("demo/Derived.missing".to_string())));
+		// The failure is kept.
+		assert!(user.constant_pool.is_resolved(missing));
+		assert_eq!(loaders.resolve_field(&user, missing), Err(LinkageError::NoSuchField("demo/Derived.missing".to_string())));
+
+		let stranger = loaders.load_class(LoaderId::APPLICATION, "other/Stranger").unwrap();
+		assert!(matches!(loaders.resolve_field(&stranger, package_private), Err(LinkageError::IllegalAccess(_))));
+		assert!(matches!(loaders.resolve_field(&stranger, protected), Err(LinkageError::IllegalAccess(_))));
+		let error = loaders.resolve_field(&stranger, hidden_class).unwrap_err();
+		assert_eq!(error, LinkageError::IllegalAccess("failed to access class demo/Hidden from class other/Stranger".to_string()));
+		let subclass = loaders.load_class(LoaderId::APPLICATION, "other/Subclass").unwrap();
+		assert!(loaders.resolve_field(&subclass, protected_from_subclass).is_ok());
+	}
+
+	#[test]
+	fn test_method_resolution() {
+		let mut user = ClassBuilder::new("demo/User").build().unwrap();
+		let inherited = reference(&mut user, Opcode::InvokeVirtual, "demo/Derived", "run", "()V");
+		let default = reference(&mut user, Opcode::InvokeVirtual, "demo/Derived", "greet", "()V");
+		let abstract_only = reference(&mut user, Opcode::InvokeVirtual, "demo/Derived", "name", "()Ljava/lang/String;");
+		let on_interface = reference(&mut user, Opcode::InvokeVirtual, "demo/Greeter", "greet", "()V");
+		let interface_method = reference(&mut user, Opcode::InvokeInterface, "demo/Loud", "greet", "()V");
+		let object_method = reference(&mut user, Opcode::InvokeInterface, "demo/Loud", "toString", "()Ljava/lang/String;");
+		let on_class = reference(&mut user, Opcode::InvokeInterface, "demo/Derived", "run", "()V");
+		let missing = reference(&mut user, Opcode::InvokeVirtual, "demo/Derived", "run", "(I)V");
+
+		let greet = |method: &mut MethodBuilder| { method.op(Opcode::Return); };
+		let object = ClassBuilder::new("java/lang/Object").method(PUBLIC, "toString", "()Ljava/lang/String;", |method| { method.op(Opcode::AConstNull).op(Opcode::AReturn); });
+		let loaders = boot_loaders("method-resolution", &[("java/lang/Object", root(object))], &[
+			("demo/Greeter", bytes(interface("demo/Greeter").method(PUBLIC, "greet", "()V", greet).method(ABSTRACT, "name", "()Ljava/lang/String;", |_| {}))),
+			("demo/Loud", bytes(interface("demo/Loud").interface("demo/Greeter").method(PUBLIC, "greet", "()V", greet))),
+			("demo/Base", bytes(ClassBuilder::new("demo/Base").interface("demo/Loud").method(PUBLIC, "run", "()V", greet))),
+			("demo/Derived", bytes(ClassBuilder::new("demo/Derived").super_class("demo/Base").interface("demo/Greeter"))),
+			("demo/User", user.to_bytes().unwrap()),
+		]);
+
+		let user = loaders.load_class(LoaderId::APPLICATION, "demo/User").unwrap();
+		assert_eq!(loaders.resolve_method(&user, inherited).unwrap().class.name, "demo/Base");
+		assert_eq!(loaders.resolve_method(&user, default).unwrap().class.name, "demo/Loud");
+		let name = loaders.resolve_method(&user, abstract_only).unwrap();
+		assert!(name.is_abstract() && name.class.name == "demo/Greeter");
+		assert!(matches!(loaders.resolve_method(&user, on_interface), Err(LinkageError::IncompatibleClassChange(_))));
+		assert_eq!(loaders.resolve_interface_method(&user, interface_method).unwrap().class.name, "demo/Loud");
+		assert_eq!(loaders.resolve_interface_method(&user, object_method).unwrap().class.name, "java/lang/Object");
+		assert!(matches!(loaders.resolve_interface_method(&user, on_class), Err(LinkageError::IncompatibleClassChange(_))));
+		assert_eq!(loaders.resolve_method(&user, missing), Err(LinkageError::NoSuchMethod("demo/Derived.run(I)V".to_string())));
+		assert!(matches!(loaders.resolve_field(&user, inherited), Err(LinkageError::ClassFormat { .. })));
+	}
+
+	#[test]
+	fn test_method_selection() {
+		let mut user = ClassBuilder::new("demo/User").build().unwrap();
+		let run = reference(&mut user, Opcode::InvokeVirtual, "demo/Base", "run", "()V");
+		let greet = reference(&mut user, Opcode::InvokeVirtual, "demo/Base", "greet", "()V");
+		let name = reference(&mut user, Opcode::InvokeVirtual, "demo/Base", "name", "()Ljava/lang/String;");
+
+		let body = |method: &mut MethodBuilder| { method.op(Opcode::Return); };
+		let loaders = loaders("method-selection", &[
+			("demo/Greeter", bytes(interface("demo/Greeter").method(PUBLIC, "greet", "()V", body).method(ABSTRACT, "name", "()Ljava/lang/String;", |_| {}))),
+			("demo/Base", bytes(ClassBuilder::new("demo/Base").flags(&[ClassAccessPropertyFlags::Public, ClassAccessPropertyFlags::Abstract])
+				.interface("demo/Greeter").method(PUBLIC, "run", "()V", body))),
+			("demo/Derived", bytes(ClassBuilder::new("demo/Derived").super_class("demo/Base").method(PUBLIC, "run", "()V", body))),
+			("demo/User", user.to_bytes().unwrap()),
+		]);
+		let user = loaders.load_class(LoaderId::APPLICATION, "demo/User").unwrap();
+		let base = loaders.load_class(LoaderId::APPLICATION, "demo/Base").unwrap();
+		let derived = loaders.load_class(LoaderId::APPLICATION, "demo/Derived").unwrap();
+		let (run, greet, name) = (loaders.resolve_method(&user, run).unwrap(), loaders.resolve_method(&user, greet).unwrap(), loaders.resolve_method(&user, name).unwrap());
+
+		// invokevirtual selects the override, or a default method when no class declares one.
+		assert_eq!(loaders.select_method(&derived, &run).unwrap().class, derived);
+		assert_eq!(loaders.select_method(&base, &run).unwrap().class, base);
+		assert_eq!(loaders.select_method(&derived, &greet).unwrap().class.name, "demo/Greeter");
+		assert_eq!(loaders.select_method(&derived, &name), Err(LinkageError::AbstractMethod(
+			"Receiver class demo.Derived does not define or inherit an implementation of the resolved method 'name()Ljava/lang/String;' of demo.Greeter".to_string())));
+
+		// invokespecial of a superclass method from a subclass skips the subclass's override.
+		let derived_run = loaders.select_method(&derived, &run).unwrap();
+		assert_eq!(loaders.select_special(&derived, &base, &run).unwrap().class, base);
+		assert_eq!(loaders.select_special(&derived, &derived, &derived_run).unwrap().class, derived);
+	}
+
+	#[test]
+	fn test_nestmates() {
+		let mut spy = ClassBuilder::new("Spy").build().unwrap();
+		let secret = reference(&mut spy, Opcode::GetField, "Outer", "secret", "I");
+		let loaders = loaders("nestmates", &[
+			("Outer", fs::read("tests/resources/linking/Outer.class").unwrap()),
+			("Outer$Inner", fs::read("tests/resources/linking/Outer$Inner.class").unwrap()),
+			("Spy", spy.to_bytes().unwrap()),
+		]);
+		let inner = loaders.load_class(LoaderId::APPLICATION, "Outer$Inner").unwrap();
+		assert_eq!(inner.nest_host_name(), "Outer");
+		let field = loaders.resolve_field(&inner, 13).unwrap();
+		assert!(field.is_private() && field.class.name == "Outer");
+		let method = loaders.resolve_method(&inner, 19).unwrap();
+		assert!(method.is_private() && method.is_static());
+
+		let spy = loaders.load_class(LoaderId::APPLICATION, "Spy").unwrap();
+		assert_eq!(loaders.resolve_field(&spy, secret), Err(LinkageError::IllegalAccess("class Spy tried to access field Outer.secret".to_string())));
+	}
+}
@@ -11,7 +11,7 @@ macro_rules! make_pop_load_store {
 	) => {
 		pub fn ${ concat($prefix, load) } (&mut self, index: u32) -> Result<$variable_type, Box<dyn Error>> {
 			let local = self.frame.locals.$getter(index)?;
-			self.frame.operand_stack.push(&local.value.to_be_bytes());
+			self.frame.operand_stack.push(&local.value.to_le_bytes());
 			Ok(local)
 		}
 
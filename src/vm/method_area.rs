@@ -0,0 +1,337 @@
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex, OnceLock, RwLock}};
+
+use crate::{
+	class::{
+		access::{ClassAccessPropertyFlags, FieldAccessPropertyFlags, MethodAccessPropertyFlags},
+		attribute::AttributeInfo,
+		class::Class,
+		constant_pool::{ConstantPool, ConstantPoolItem},
+		field::Field},
+	isa::opcode::Opcode,
+	vm::{
+		class_loader::LoaderId,
+		field_layout::FieldLayout,
+		heap::Reference,
+		initialization::InitializationLock,
+		reference::{ReferenceKind, CLEANABLE_CLASSES},
+		runtime_constant_pool::RuntimeConstantPool,
+		types::*}};
+
+/// A class loaded into the method area, identified by its name and defining loader (JVMS17 5.3).
+#[derive(Debug)]
+pub struct RuntimeClass {
+	/// The internal name, such as `java/lang/String`.
+	pub name: String,
+	/// The defining loader.
+	pub loader: LoaderId,
+	pub class: Class,
+	/// The loaded direct superclass, absent only for java/lang/Object.
+	pub super_class: Option<Arc<RuntimeClass>>,
+	/// The loaded direct superinterfaces, in declaration order.
+	pub interfaces: Vec<Arc<RuntimeClass>>,
+	/// The component type of an array class whose components are references.
+	pub component: Option<Arc<RuntimeClass>>,
+	pub constant_pool: RuntimeConstantPool,
+	/// Static field values by name and descriptor, created when the class is prepared.
+	statics: OnceLock<Mutex<HashMap<(String, String), Variable>>>,
+	pub initialization: InitializationLock,
+	/// Where the instance fields of objects of this class live, computed when first needed.
+	layout: OnceLock<FieldLayout>,
+	/// The kind of reference object instances are, worked out when first needed.
+	reference_kind: OnceLock<Option<ReferenceKind>>,
+	/// Whether instances need finalizing, worked out when first needed.
+	finalizable: OnceLock<bool>,
+}
+
+impl PartialEq for RuntimeClass {
+	fn eq(&self, other: &Self) -> bool {
+		self.name == other.name && self.loader == other.loader
+	}
+}
+
+impl Eq for RuntimeClass {}
+
+impl RuntimeClass {
+
+	pub fn new(loader: LoaderId, class: Class, super_class: Option<Arc<RuntimeClass>>, interfaces: Vec<Arc<RuntimeClass>>) -> RuntimeClass {
+		RuntimeClass {
+			name: class.name(),
+			loader,
+			constant_pool: RuntimeConstantPool::new(ConstantPool::from(&class)),
+			class,
+			super_class,
+			interfaces,
+			component: None,
+			statics: OnceLock::new(),
+			initialization: InitializationLock::default(),
+			layout: OnceLock::new(),
+			reference_kind: OnceLock::new(),
+			finalizable: OnceLock::new(),
+		}
+	}
+
+	/// An array class, whose component type is `component` unless that is a primitive type.
+	pub fn new_array(loader: LoaderId, class: Class, super_class: Arc<RuntimeClass>, interfaces: Vec<Arc<RuntimeClass>>, component: Option<Arc<RuntimeClass>>) -> RuntimeClass {
+		RuntimeClass { component, ..RuntimeClass::new(loader, class, Some(super_class), interfaces) }
+	}
+
+	/// The package part of the name, empty for the unnamed package.
+	pub fn package_name(&self) -> &str {
+		self.name.rsplit_once('/').map_or("", |(package, _)| package)
+	}
+
+	pub fn package(&self) -> RuntimePackage {
+		RuntimePackage { loader: self.loader, name: self.package_name().to_string() }
+	}
+
+	pub fn is_interface(&self) -> bool {
+		self.class.is_interface()
+	}
+
+	pub fn is_public(&self) -> bool {
+		self.class.flags.contains(&ClassAccessPropertyFlags::Public)
+	}
+
+	pub fn is_abstract(&self) -> bool {
+		self.class.flags.contains(&ClassAccessPropertyFlags::Abstract)
+	}
+
+	pub fn is_array(&self) -> bool {
+		self.name.starts_with('[')
+	}
+
+	/// The descriptor of the components of an array class.
+	pub fn component_descriptor(&self) -> Option<&str> {
+		self.name.strip_prefix('[')
+	}
+
+	/// The superclasses of this class, nearest first.
+	pub fn super_classes(&self) -> impl Iterator<Item = &Arc<RuntimeClass>> {
+		std::iter::successors(self.super_class.as_ref(), |class| class.super_class.as_ref())
+	}
+
+	/// Whether this class is `other` or one of its subclasses.
+	pub fn is_subclass_of(&self, other: &RuntimeClass) -> bool {
+		self == other || self.super_classes().any(|class| **class == *other)
+	}
+
+	/// Whether this class is `interface` or implements it, directly or through its superclasses and
+	/// superinterfaces.
+	pub fn implements(&self, interface: &RuntimeClass) -> bool {
+		self == interface || std::iter::once(self).chain(self.super_classes().map(|class| &**class))
+			.any(|class| class.interfaces.iter().any(|direct| direct.implements(interface)))
+	}
+
+	/// Whether a value of this class can be stored in a variable of type `target`, following the
+	/// rules of checkcast (JVMS17 6.5 checkcast).
+	pub fn is_assignable_to(&self, target: &RuntimeClass) -> bool {
+		if target.is_interface() {
+			return self.implements(target);
+		}
+		if !target.is_array() {
+			return self.is_subclass_of(target);
+		}
+		match (&self.component, &target.component) {
+			(Some(component), Some(target_component)) => component.is_assignable_to(target_component),
+			(None, None) => self.is_array() && self.name == target.name,
+			_ => false,
+		}
+	}
+
+	/// The name of the host of the nest this class belongs to (JVMS17 5.4.4), which is the class
+	/// itself unless it has a NestHost attribute.
+	pub fn nest_host_name(&self) -> String {
+		self.class.attributes.attributes.iter()
+			.find_map(|attribute| match &attribute.attribute_info {
+				AttributeInfo::NestHost(host) => self.class.get_class_name_at(host.host_class_index),
+				_ => None,
+			})
+			.unwrap_or_else(|| self.name.clone())
+	}
+
+	/// The layout of the instance fields of this class and its superclasses.
+	pub fn field_layout(&self) -> &FieldLayout {
+		self.layout.get_or_init(|| FieldLayout::new(self.super_class.as_ref().map(|class| class.field_layout()), &self.class))
+	}
+
+	/// The kind of reference object instances of this class are, from the nearest of the bootstrap
+	/// loader's java/lang/ref/SoftReference, WeakReference, FinalReference and PhantomReference it
+	/// is or extends, None if it is not a reference class.
+	pub fn reference_kind(&self) -> Option<ReferenceKind> {
+		*self.reference_kind.get_or_init(|| std::iter::once(self).chain(self.super_classes().map(|class| &**class))
+			.filter(|class| class.loader == LoaderId::BOOTSTRAP)
+			.find_map(|class| ReferenceKind::from_class_name(&class.name)))
+	}
+
+	/// Whether instances of this class are cleanables, the bootstrap loader's
+	/// jdk/internal/ref/Cleaner and PhantomCleanable or their subclasses, whose cleaning actions the
+	/// reference handler runs rather than enqueueing them.
+	pub fn is_cleanable(&self) -> bool {
+		std::iter::once(self).chain(self.super_classes().map(|class| &**class))
+			.any(|class| class.loader == LoaderId::BOOTSTRAP && CLEANABLE_CLASSES.contains(&class.name.as_str()))
+	}
+
+	/// The class declaring the finalize method instances of this class run once unreachable
+	/// (JLS17 12.6), None when that is java/lang/Object's or does nothing but return, so instances
+	/// need no finalizing.
+	pub fn finalizer(self: &Arc<Self>) -> Option<Arc<RuntimeClass>> {
+		std::iter::once(self).chain(self.super_classes())
+			.find_map(|class| Some((class, class.class.find_method("finalize", "()V")
+				.filter(|method| method.access_flags & MethodAccessPropertyFlags::Static as u16 == 0)?)))
+			.filter(|(class, method)| class.super_class.is_some() && method.code().is_some_and(|code| code.code != [Opcode::Return as u8]))
+			.map(|(class, _)| class.clone())
+	}
+
+	/// Whether instances of this class must be finalized before they are freed.
+	pub fn has_finalizer(self: &Arc<Self>) -> bool {
+		*self.finalizable.get_or_init(|| self.finalizer().is_some())
+	}
+
+	/// Prepare the class (JVMS17 5.4.2) by creating its static fields, each with its default value
+	/// or the primitive constant of its ConstantValue attribute. Preparing twice does nothing.
+	///
+	/// String constants need string objects, so those fields stay null until the class is
+	/// initialized and [RuntimeClass::string_constants] are stored in them.
+	pub fn prepare(&self) {
+		self.statics.get_or_init(|| {
+			let statics = self.class.fields.fields.iter()
+				.filter(|field| field.access_flags & FieldAccessPropertyFlags::Static as u16 != 0)
+				.filter_map(|field| {
+					let name = self.class.get_utf8(field.name_index)?;
+					let descriptor = self.class.get_utf8(field.descriptor_index)?;
+					let value = self.constant_value(field, &descriptor).unwrap_or_else(|| Variable::default_value(&descriptor));
+					Some(((name, descriptor), value))
+				})
+				.collect();
+			Mutex::new(statics)
+		});
+	}
+
+	pub fn is_prepared(&self) -> bool {
+		self.statics.get().is_some()
+	}
+
+	/// The value of a field's ConstantValue attribute (JVMS17 4.7.2) as a variable of its type.
+	fn constant_value(&self, field: &Field, descriptor: &str) -> Option<Variable> {
+		let index = field.attributes.iter().find_map(|attribute| match &attribute.attribute_info {
+			AttributeInfo::ConstantValue(constant_value) => Some(constant_value.constant_value_index),
+			_ => None,
+		})?;
+		match (self.class.constant_pool.get(&index)?, descriptor) {
+			(ConstantPoolItem::Integer(integer), "Z") => Some(Variable::Boolean(Boolean { value: integer.value != 0 })),
+			(ConstantPoolItem::Integer(integer), "B") => Some(Variable::Byte(Byte { value: integer.value as i8 })),
+			(ConstantPoolItem::Integer(integer), "C") => Some(Variable::Char(Char { value: integer.value & 0xFFFF })),
+			(ConstantPoolItem::Integer(integer), "S") => Some(Variable::Short(Short { value: integer.value as i16 })),
+			(ConstantPoolItem::Integer(integer), "I") => Some(Variable::Int(Int { value: integer.value })),
+			(ConstantPoolItem::Long(long), "J") => Some(Variable::Long(Long { value: long.value })),
+			(ConstantPoolItem::Float(float), "F") => Some(Variable::Float(Float { value: float.value })),
+			(ConstantPoolItem::Double(double), "D") => Some(Variable::Double(Double { value: double.value })),
+			_ => None,
+		}
+	}
+
+	/// The static fields of this class whose ConstantValue attribute is a String, with the value
+	/// each takes when the class is initialized (JVMS17 5.5 step 6).
+	pub fn string_constants(&self) -> Vec<(String, String)> {
+		self.class.fields.fields.iter()
+			.filter(|field| field.access_flags & FieldAccessPropertyFlags::Static as u16 != 0)
+			.filter_map(|field| {
+				let index = field.attributes.iter().find_map(|attribute| match &attribute.attribute_info {
+					AttributeInfo::ConstantValue(constant_value) => Some(constant_value.constant_value_index),
+					_ => None,
+				})?;
+				let ConstantPoolItem::String(string) = self.class.constant_pool.get(&index)? else {
+					return None;
+				};
+				Some((self.class.get_utf8(field.name_index)?, self.class.get_utf8(string.index)?))
+			})
+			.collect()
+	}
+
+	/// The objects the static fields of this class refer to, which the garbage collector keeps.
+	pub fn static_references(&self) -> Vec<Reference> {
+		self.statics.get().map_or_else(Vec::new, |statics| statics.lock().unwrap().values().filter_map(Variable::reference).collect())
+	}
+
+	/// The value of a static field declared by this class, once prepared.
+	pub fn get_static(&self, name: &str, descriptor: &str) -> Option<Variable> {
+		self.statics.get()?.lock().unwrap().get(&(name.to_string(), descriptor.to_string())).cloned()
+	}
+
+	/// Set a static field declared by this class, returning false if there is no such field or
+	/// the class is not prepared.
+	pub fn put_static(&self, name: &str, descriptor: &str, value: Variable) -> bool {
+		let Some(statics) = self.statics.get() else {
+			return false;
+		};
+		match statics.lock().unwrap().get_mut(&(name.to_string(), descriptor.to_string())) {
+			Some(slot) => {
+				*slot = value;
+				true
+			}
+			None => false,
+		}
+	}
+}
+
+/// A run-time package, identified by package name and defining loader (JVMS17 5.3): same-named
+/// packages from different loaders do not share package-private access.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RuntimePackage {
+	pub loader: LoaderId,
+	pub name: String,
+}
+
+/// The loaded classes of a VM, keyed by initiating loader and name.
+///
+/// A class is recorded under its defining loader and under every loader that returned it from
+/// `loadClass`, so each loader sees a consistent class for a name once it has loaded one.
+#[derive(Debug, Default)]
+pub struct MethodArea {
+	classes: RwLock<HashMap<(LoaderId, String), Arc<RuntimeClass>>>,
+}
+
+impl MethodArea {
+
+	pub fn new() -> MethodArea {
+		MethodArea::default()
+	}
+
+	/// The class named `name` that `loader` has loaded, if it has.
+	pub fn find(&self, loader: LoaderId, name: &str) -> Option<Arc<RuntimeClass>> {
+		self.classes.read().unwrap().get(&(loader, name.to_string())).cloned()
+	}
+
+	/// Record `class` as loaded by `loader`, returning the class already recorded for the name if
+	/// there is one.
+	pub(crate) fn record(&self, loader: LoaderId, class: Arc<RuntimeClass>) -> Result<(), Arc<RuntimeClass>> {
+		let mut classes = self.classes.write().unwrap();
+		match classes.get(&(loader, class.name.clone())) {
+			Some(existing) => Err(existing.clone()),
+			None => {
+				classes.insert((loader, class.name.clone()), class);
+				Ok(())
+			}
+		}
+	}
+
+	/// Every class defined so far, each once, in no particular order.
+	pub fn classes(&self) -> Vec<Arc<RuntimeClass>> {
+		self.classes.read().unwrap().iter()
+			.filter(|((loader, _), class)| *loader == class.loader)
+			.map(|(_, class)| class.clone())
+			.collect()
+	}
+
+	/// The loaders under which `class` is recorded, the defining loader among them.
+	pub fn initiating_loaders(&self, class: &RuntimeClass) -> Vec<LoaderId> {
+		let mut loaders: Vec<LoaderId> = self.classes.read().unwrap().iter()
+			.filter(|(_, recorded)| ***recorded == *class)
+			.map(|((loader, _), _)| *loader)
+			.collect();
+		loaders.sort();
+		loaders
+	}
+}
@@ -1,7 +1,17 @@
+pub mod class_loader;
 pub mod frame;
 pub mod errors;
+pub mod field_layout;
+pub mod gc;
+pub mod heap;
+pub mod initialization;
 pub mod interpreter;
+pub mod linker;
 pub mod macros;
 pub mod local;
+pub mod method_area;
+pub mod native;
 pub mod operand_stack;
+pub mod reference;
+pub mod runtime_constant_pool;
 pub mod types;
\ No newline at end of file
@@ -0,0 +1,88 @@
+use std::{
+	error::Error,
+	sync::Arc};
+
+use crate::vm::{
+	class_loader::{ClassLoaders, LoaderId},
+	errors::{ExecutionError, LinkageError},
+	heap::Heap,
+	runtime_constant_pool::ResolvedMember,
+	types::*};
+
+pub(crate) const DEFINE_CLASS1: &str = "(Ljava/lang/ClassLoader;Ljava/lang/String;[BIILjava/security/ProtectionDomain;Ljava/lang/String;)Ljava/lang/Class;";
+const FIND_BOOTSTRAP_CLASS: &str = "(Ljava/lang/String;)Ljava/lang/Class;";
+const FIND_LOADED_CLASS0: &str = "(Ljava/lang/String;)Ljava/lang/Class;";
+
+/// Run the native method `method` with `arguments`. Only the natives of java.lang.ClassLoader that
+/// define and find classes are implemented.
+pub fn invoke(loaders: &Arc<ClassLoaders>, heap: &Arc<Heap>, method: &ResolvedMember, arguments: &[Variable]) -> Result<Variable, Box<dyn Error>> {
+	match (method.class.name.as_str(), method.name.as_str(), method.descriptor.as_str(), arguments) {
+		("java/lang/ClassLoader", "defineClass1", DEFINE_CLASS1, [loader, name, bytes, offset, length, ..]) =>
+			define_class(loaders, heap, loader, name, bytes, offset, length),
+		("java/lang/ClassLoader", "findBootstrapClass", FIND_BOOTSTRAP_CLASS, [name]) => find_bootstrap_class(loaders, heap, name),
+		("java/lang/ClassLoader", "findLoadedClass0", FIND_LOADED_CLASS0, [loader, name]) => find_loaded_class(loaders, heap, loader, name),
+		_ => Err(Box::new(LinkageError::Internal(format!("no native implementation of {}.{}{}", method.class.name, method.name, method.descriptor)))),
+	}
+}
+
+/// The internal form of a binary class name held in a java/lang/String, None for null.
+fn class_name(heap: &Heap, name: &Variable) -> Result<Option<String>, Box<dyn Error>> {
+	let Some(reference) = name.reference() else {
+		return Ok(None);
+	};
+	let name = heap.string_value(reference).ok_or_else(|| LinkageError::Internal("the class name is not a string".to_string()))?;
+	Ok(Some(name.replace('.', "/")))
+}
+
+/// Define a class from `length` bytes of an array, starting at `offset`, with the loader a
+/// ClassLoader object was registered as (ClassLoader.defineClass1).
+fn define_class(loaders: &ClassLoaders, heap: &Heap, loader: &Variable, name: &Variable, bytes: &Variable, offset: &Variable, length: &Variable) -> Result<Variable, Box<dyn Error>> {
+	let loader = match loader.reference() {
+		Some(object) => loaders.java_loader(object).ok_or_else(|| LinkageError::Internal("the ClassLoader is not registered with the VM".to_string()))?,
+		None => LoaderId::BOOTSTRAP,
+	};
+	let name = class_name(heap, name)?;
+	let array = bytes.reference().ok_or_else(|| ExecutionError::NullPointer("Cannot read the class bytes because they are null".to_string()))?;
+	let (Variable::Int(Int { value: offset }), Variable::Int(Int { value: length })) = (offset, length) else {
+		return Err(Box::new(LinkageError::Internal("the offset and length of the class bytes are not ints".to_string())));
+	};
+	let available = heap.array_length(array).unwrap_or(0) as i64;
+	if *offset < 0 || *length < 0 || *offset as i64 + *length as i64 > available {
+		return Err(Box::new(ExecutionError::ArrayIndexOutOfBounds(format!("Range [{}, {} + {}) out of bounds for length {}", offset, offset, length, available))));
+	}
+	let bytes: Vec<u8> = (*offset..*offset + *length)
+		.map(|index| match heap.get_element(array, index as u32) {
+			Some(Variable::Byte(Byte { value })) => Ok(value as u8),
+			_ => Err(LinkageError::Internal("the class bytes are not a byte array".to_string())),
+		})
+		.collect::<Result<_, _>>()?;
+	let class = loaders.define_class(loader, name.as_deref(), &bytes)?;
+	Ok(Variable::ClassReference(ClassReference { value: heap.mirror(&class)? }))
+}
+
+/// The class the bootstrap loader loads for a name, or null if it finds none
+/// (ClassLoader.findBootstrapClass).
+fn find_bootstrap_class(loaders: &ClassLoaders, heap: &Heap, name: &Variable) -> Result<Variable, Box<dyn Error>> {
+	let Some(name) = class_name(heap, name)? else {
+		return Ok(NULL);
+	};
+	match loaders.load_class(LoaderId::BOOTSTRAP, &name) {
+		Ok(class) => Ok(Variable::ClassReference(ClassReference { value: heap.mirror(&class)? })),
+		Err(LinkageError::ClassNotFound(_) | LinkageError::NoClassDefFound(_)) => Ok(NULL),
+		Err(error) => Err(Box::new(error)),
+	}
+}
+
+/// The class a ClassLoader object has already loaded for a name, or null if it has loaded none
+/// (ClassLoader.findLoadedClass0).
+fn find_loaded_class(loaders: &ClassLoaders, heap: &Heap, loader: &Variable, name: &Variable) -> Result<Variable, Box<dyn Error>> {
+	let object = loader.reference().ok_or_else(|| ExecutionError::NullPointer("Cannot find a loaded class because the loader is null".to_string()))?;
+	let loader = loaders.java_loader(object).ok_or_else(|| LinkageError::Internal("the ClassLoader is not registered with the VM".to_string()))?;
+	let Some(name) = class_name(heap, name)? else {
+		return Ok(NULL);
+	};
+	match loaders.method_area().find(loader, &name) {
+		Some(class) => Ok(Variable::ClassReference(ClassReference { value: heap.mirror(&class)? })),
+		None => Ok(NULL),
+	}
+}
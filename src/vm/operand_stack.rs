@@ -4,12 +4,23 @@ use std::usize;
 pub struct OperandStack {
 	pub max_depth: usize,
 	pub stack: Vec<u8>,
-} 
+	/// Where each reference on the stack starts, lowest first, so the garbage collector can tell
+	/// references from other values of the same size.
+	references: Vec<usize>,
+}
+
+/// A four byte value taken off the operand stack by an instruction that moves values without
+/// knowing their types, such as dup or swap, and whether it is a reference.
+#[derive(Clone, Copy, Debug)]
+pub struct Word {
+	bytes: [u8; 4],
+	reference: bool,
+}
 
 impl OperandStack {
 
 	pub fn new() -> Self {
-		OperandStack { max_depth: usize::MAX, stack: Vec::new() }
+		OperandStack { max_depth: usize::MAX, stack: Vec::new(), references: Vec::new() }
 	}
 
 	pub fn push(&mut self, bytes: &[u8]) {
@@ -21,6 +32,37 @@ impl OperandStack {
 
 	pub fn pop(&mut self) -> u8 {
 		if self.stack.len() == 0 { panic!("Stack underflow"); }
-		self.stack.pop().unwrap()
+		let byte = self.stack.pop().unwrap();
+		if self.references.last().is_some_and(|start| *start >= self.stack.len()) {
+			self.references.pop();
+		}
+		byte
+	}
+
+	/// Push the raw value of a reference, marking it as one.
+	pub fn push_reference(&mut self, raw: u32) {
+		self.references.push(self.stack.len());
+		self.push(&raw.to_le_bytes());
+	}
+
+	pub fn pop_word(&mut self) -> Word {
+		let reference = self.references.last().is_some_and(|start| *start + 4 == self.stack.len());
+		let mut bytes = [0u8; 4];
+		for byte in bytes.iter_mut().rev() {
+			*byte = self.pop();
+		}
+		Word { bytes, reference }
+	}
+
+	pub fn push_word(&mut self, word: Word) {
+		if word.reference {
+			self.references.push(self.stack.len());
+		}
+		self.push(&word.bytes);
+	}
+
+	/// The raw values of the references on the stack, null included.
+	pub fn references(&self) -> impl Iterator<Item = u32> + '_ {
+		self.references.iter().map(|start| u32::from_le_bytes(self.stack[*start..*start + 4].try_into().unwrap()))
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,449 @@
+use std::{
+	sync::Arc,
+	thread::{self, JoinHandle}};
+
+use crate::vm::{
+	class_loader::ClassLoaders,
+	heap::{Heap, Reference},
+	interpreter::Interpreter};
+
+/// The kinds of reference object (java.lang.ref), strongest first, which say when a collection
+/// clears a reference whose referent nothing else keeps alive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReferenceKind {
+	/// Cleared when the referent is only softly reachable and the soft reference policy says so,
+	/// and always before OutOfMemoryError is thrown.
+	Soft,
+	/// Cleared as soon as the referent is only weakly reachable.
+	Weak,
+	/// Never cleared: the referent is kept for finalization and the reference enqueued, as
+	/// java.lang.ref.Finalizer expects.
+	Final,
+	/// Cleared once the referent is unreachable and finalized.
+	Phantom,
+}
+
+impl ReferenceKind {
+	/// The kind of the reference class `name`, None if it is not one of the classes each kind of
+	/// reference extends.
+	pub fn from_class_name(name: &str) -> Option<ReferenceKind> {
+		match name {
+			"java/lang/ref/SoftReference" => Some(ReferenceKind::Soft),
+			"java/lang/ref/WeakReference" => Some(ReferenceKind::Weak),
+			"java/lang/ref/FinalReference" => Some(ReferenceKind::Final),
+			"java/lang/ref/PhantomReference" => Some(ReferenceKind::Phantom),
+			_ => None,
+		}
+	}
+}
+
+/// The classes of cleanables: jdk.internal.ref.Cleaner, which the JDK's reference handler cleans
+/// itself, and the PhantomCleanable that java.lang.ref.Cleaner registers each action as.
+pub(crate) const CLEANABLE_CLASSES: [&str; 2] = ["jdk/internal/ref/Cleaner", "jdk/internal/ref/PhantomCleanable"];
+
+/// The reference handler and finalizer threads of a VM. The reference handler adds the references
+/// collections clear to the ReferenceQueue they were registered with, where Java code polls for
+/// them. The finalizer runs the finalize methods of unreachable objects one at a time; the
+/// collector keeps each object, and whatever it refers to, until its method returns. Like any
+/// thread running Java code, either is stopped at a safepoint for collections.
+///
+/// The interpreter has no monitors yet, so nothing wakes a thread blocked in
+/// `ReferenceQueue.remove`, as a java.lang.ref.Cleaner's own thread is. Instead the reference
+/// handler calls the `clean` method of each cleanable it is given in place of enqueueing it, which
+/// runs the cleaning action it was registered with.
+#[derive(Debug)]
+pub struct ReferenceHandler {
+	heap: Arc<Heap>,
+	threads: Vec<JoinHandle<()>>,
+}
+
+impl ReferenceHandler {
+
+	pub fn start(loaders: Arc<ClassLoaders>, heap: Arc<Heap>) -> ReferenceHandler {
+		let handler = {
+			let heap = heap.clone();
+			let loaders = loaders.clone();
+			thread::Builder::new().name("Reference Handler".to_string()).spawn(move || while heap.enqueue_pending() {
+				while let Some(cleanable) = heap.next_cleanable() {
+					clean(&loaders, &heap, cleanable);
+					heap.cleaned(cleanable);
+				}
+			}).unwrap()
+		};
+		let finalizer = {
+			let heap = heap.clone();
+			thread::Builder::new().name("Finalizer".to_string()).spawn(move || while let Some(object) = heap.next_finalizable() {
+				finalize(&loaders, &heap, object);
+				heap.finalized(object);
+			}).unwrap()
+		};
+		ReferenceHandler { heap, threads: vec![handler, finalizer] }
+	}
+
+	/// Stop both threads once nothing is left to enqueue or finalize, and wait for them.
+	pub fn stop(self) {
+		self.heap.stop_reference_handling();
+		for thread in self.threads {
+			thread.join().unwrap();
+		}
+	}
+}
+
+/// Run the finalize method of an object. As with java.lang.ref.Finalizer, whatever it throws is
+/// ignored.
+fn finalize(loaders: &Arc<ClassLoaders>, heap: &Arc<Heap>, object: Reference) {
+	let Some(class) = heap.class_of(object).and_then(|class| class.finalizer()) else {
+		return;
+	};
+	let _ = Interpreter::for_method(loaders.clone(), heap.clone(), class, "finalize", "()V", vec![heap.variable(object)])
+		.and_then(|mut interpreter| interpreter.execute());
+}
+
+/// Run the clean method of a cleanable. As with the JDK's cleaners, whatever it throws is ignored.
+fn clean(loaders: &Arc<ClassLoaders>, heap: &Arc<Heap>, cleanable: Reference) {
+	let Some(class) = heap.class_of(cleanable).and_then(|class| std::iter::once(&class).chain(class.super_classes())
+		.find(|class| class.class.find_method("clean", "()V").is_some_and(|method| method.code().is_some()))
+		.cloned()) else {
+		return;
+	};
+	let _ = Interpreter::for_method(loaders.clone(), heap.clone(), class, "clean", "()V", vec![heap.variable(cleanable)])
+		.and_then(|mut interpreter| interpreter.execute());
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+
+	use crate::{
+		class::{
+			access::{ClassAccessPropertyFlags, FieldAccessPropertyFlags, MethodAccessPropertyFlags},
+			builder::{ClassBuilder, MethodBuilder}},
+		isa::opcode::Opcode,
+		vm::{
+			class_loader::{tests::{boot_loaders, bytes, interface, root}, ClassLoaders, LoaderId},
+			gc::{Collector, GcCause, GcKind, HeapOptions},
+			heap::{Heap, Reference},
+			method_area::RuntimeClass,
+			reference::{ReferenceHandler, ReferenceKind},
+			types::*}};
+
+	/// Loaders with java/lang/ref's reference and queue classes, with the fields the collector and
+	/// reference handler use, and jdk/internal/ref's cleanables, on the boot class path.
+	fn reference_loaders(name: &str, application: &[(&str, Vec<u8>)]) -> Arc<ClassLoaders> {
+		let instance = &[FieldAccessPropertyFlags::Private];
+		let statics = &[FieldAccessPropertyFlags::Static];
+		let reference = ClassBuilder::new("java/lang/ref/Reference")
+			.field(instance, "referent", "Ljava/lang/Object;")
+			.field(instance, "queue", "Ljava/lang/ref/ReferenceQueue;")
+			.field(instance, "next", "Ljava/lang/ref/Reference;")
+			.field(instance, "discovered", "Ljava/lang/ref/Reference;");
+		let subclass = |name: &str| ClassBuilder::new(name).super_class("java/lang/ref/Reference");
+		let queue = ClassBuilder::new("java/lang/ref/ReferenceQueue")
+			.field(statics, "NULL", "Ljava/lang/ref/ReferenceQueue;")
+			.field(statics, "ENQUEUED", "Ljava/lang/ref/ReferenceQueue;")
+			.field(instance, "head", "Ljava/lang/ref/Reference;")
+			.field(instance, "queueLength", "J");
+		// Each cleanable runs its action, less the list of cleanables the JDK's keep alive.
+		let run = |method: &mut MethodBuilder, owner: &str, field: &str| {
+			method.var(Opcode::ALoad, 0).field(Opcode::GetField, owner, field, "Ljava/lang/Runnable;")
+				.invoke(Opcode::InvokeInterface, "java/lang/Runnable", "run", "()V", true).op(Opcode::Return);
+		};
+		let public = &[MethodAccessPropertyFlags::Public];
+		let cleaner = ClassBuilder::new("jdk/internal/ref/Cleaner").super_class("java/lang/ref/PhantomReference")
+			.field(instance, "thunk", "Ljava/lang/Runnable;")
+			.method(public, "clean", "()V", |method| run(method, "jdk/internal/ref/Cleaner", "thunk"));
+		let phantom_cleanable = ClassBuilder::new("jdk/internal/ref/PhantomCleanable").super_class("java/lang/ref/PhantomReference")
+			.flags(&[ClassAccessPropertyFlags::Public, ClassAccessPropertyFlags::Abstract])
+			.method(public, "clean", "()V", |method| {
+				method.var(Opcode::ALoad, 0).invoke(Opcode::InvokeVirtual, "jdk/internal/ref/PhantomCleanable", "performCleanup", "()V", false).op(Opcode::Return);
+			})
+			.method(&[MethodAccessPropertyFlags::Protected, MethodAccessPropertyFlags::Abstract], "performCleanup", "()V", |_| {});
+		let cleanable_ref = ClassBuilder::new("jdk/internal/ref/CleanerImpl$PhantomCleanableRef").super_class("jdk/internal/ref/PhantomCleanable")
+			.field(instance, "action", "Ljava/lang/Runnable;")
+			.method(&[MethodAccessPropertyFlags::Protected], "performCleanup", "()V", |method| run(method, "jdk/internal/ref/CleanerImpl$PhantomCleanableRef", "action"));
+		let runnable = ClassBuilder::new("java/lang/Runnable").flags(&[ClassAccessPropertyFlags::Public, ClassAccessPropertyFlags::Interface, ClassAccessPropertyFlags::Abstract])
+			.method(&[MethodAccessPropertyFlags::Public, MethodAccessPropertyFlags::Abstract], "run", "()V", |_| {});
+		Arc::new(boot_loaders(name, &[
+			("java/lang/Object", root(ClassBuilder::new("java/lang/Object"))),
+			("java/lang/Cloneable", interface("java/lang/Cloneable")),
+			("java/io/Serializable", interface("java/io/Serializable")),
+			("java/lang/ref/Reference", bytes(reference)),
+			("java/lang/ref/SoftReference", bytes(subclass("java/lang/ref/SoftReference").field(statics, "clock", "J").field(instance, "timestamp", "J"))),
+			("java/lang/ref/WeakReference", bytes(subclass("java/lang/ref/WeakReference"))),
+			("java/lang/ref/PhantomReference", bytes(subclass("java/lang/ref/PhantomReference"))),
+			("java/lang/ref/ReferenceQueue", bytes(queue)),
+			("java/lang/Runnable", bytes(runnable)),
+			("jdk/internal/ref/Cleaner", bytes(cleaner)),
+			("jdk/internal/ref/PhantomCleanable", bytes(phantom_cleanable)),
+			("jdk/internal/ref/CleanerImpl$PhantomCleanableRef", bytes(cleanable_ref)),
+		], application))
+	}
+
+	fn object(reference: Reference) -> Variable {
+		Variable::ClassReference(ClassReference { value: reference })
+	}
+
+	/// Builds reference objects and reads their fields.
+	struct References {
+		loaders: Arc<ClassLoaders>,
+		heap: Arc<Heap>,
+	}
+
+	impl References {
+		fn class(&self, name: &str) -> Arc<RuntimeClass> {
+			self.loaders.load_class(LoaderId::APPLICATION, name).unwrap()
+		}
+
+		fn get(&self, reference: Reference, owner: &str, name: &str, descriptor: &str) -> Variable {
+			let slot = self.class(owner).field_layout().find(owner, name, descriptor).unwrap().clone();
+			self.heap.get_field(reference, &slot).unwrap()
+		}
+
+		fn put(&self, reference: Reference, owner: &str, name: &str, descriptor: &str, value: Variable) {
+			let slot = self.class(owner).field_layout().find(owner, name, descriptor).unwrap().clone();
+			assert!(self.heap.put_field(reference, &slot, &value));
+		}
+
+		/// A reference object of `class` to `referent`, registered with `queue`, kept alive by a
+		/// global handle.
+		fn reference_object(&self, class: &str, referent: Reference, queue: Option<Reference>) -> Reference {
+			let reference = self.heap.allocate(&self.class(class)).unwrap();
+			self.heap.new_global_ref(reference);
+			self.put(reference, "java/lang/ref/Reference", "referent", "Ljava/lang/Object;", object(referent));
+			self.put(reference, "java/lang/ref/Reference", "queue", "Ljava/lang/ref/ReferenceQueue;", queue.map_or(NULL, object));
+			reference
+		}
+
+		fn referent(&self, reference: Reference) -> Option<Reference> {
+			self.get(reference, "java/lang/ref/Reference", "referent", "Ljava/lang/Object;").reference()
+		}
+
+		/// The references on a queue, most recently enqueued first.
+		fn queued(&self, queue: Reference) -> Vec<Reference> {
+			let Variable::Long(Long { value: length }) = self.get(queue, "java/lang/ref/ReferenceQueue", "queueLength", "J") else {
+				panic!("no queue length");
+			};
+			let head = self.get(queue, "java/lang/ref/ReferenceQueue", "head", "Ljava/lang/ref/Reference;").reference();
+			let queued: Vec<Reference> = std::iter::successors(head, |reference| {
+				let next = self.get(*reference, "java/lang/ref/Reference", "next", "Ljava/lang/ref/Reference;").reference().unwrap();
+				(next != *reference).then_some(next)
+			}).collect();
+			assert_eq!(queued.len() as i64, length);
+			queued
+		}
+	}
+
+	#[test]
+	fn test_reference_processing() {
+		let node = ClassBuilder::new("demo/Node").field(&[FieldAccessPropertyFlags::Public], "next", "Ljava/lang/Object;");
+		let loaders = reference_loaders("reference-processing", &[("demo/Node", bytes(node))]);
+		let node = loaders.load_class(LoaderId::APPLICATION, "demo/Node").unwrap();
+		assert_eq!(loaders.load_class(LoaderId::BOOTSTRAP, "java/lang/ref/WeakReference").unwrap().reference_kind(), Some(ReferenceKind::Weak));
+		assert_eq!(node.reference_kind(), None);
+
+		for collector in [Collector::MarkCompact, Collector::Generational] {
+			let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions { initial_size: 8192, maximum_size: 8192, collector, ..HeapOptions::default() }));
+			let references = References { loaders: loaders.clone(), heap: heap.clone() };
+			let new_node = || heap.allocate(&node).unwrap();
+			let queue = heap.allocate(&references.class("java/lang/ref/ReferenceQueue")).unwrap();
+			heap.new_global_ref(queue);
+
+			// Weak references are cleared once nothing else reaches their referents, soft ones
+			// when not used recently, and phantom ones at the same time.
+			let (held, lost) = (new_node(), new_node());
+			heap.new_global_ref(held);
+			let kept = references.reference_object("java/lang/ref/WeakReference", held, Some(queue));
+			let weak = references.reference_object("java/lang/ref/WeakReference", lost, Some(queue));
+			let (recent, stale) = (new_node(), new_node());
+			let soft = references.reference_object("java/lang/ref/SoftReference", recent, Some(queue));
+			references.put(soft, "java/lang/ref/SoftReference", "timestamp", "J", Variable::Long(Long { value: i64::MAX }));
+			let old_soft = references.reference_object("java/lang/ref/SoftReference", stale, None);
+			references.put(old_soft, "java/lang/ref/SoftReference", "timestamp", "J", Variable::Long(Long { value: -1000 }));
+			let (haunted, chained) = (new_node(), new_node());
+			references.put(haunted, "demo/Node", "next", "Ljava/lang/Object;", object(chained));
+			let phantom = references.reference_object("java/lang/ref/PhantomReference", haunted, Some(queue));
+			let weak_chained = references.reference_object("java/lang/ref/WeakReference", chained, None);
+			references.class("java/lang/ref/SoftReference").prepare();
+			heap.collect();
+			assert_eq!(references.referent(kept), Some(held));
+			assert_eq!(references.referent(soft), Some(recent));
+			assert_eq!((references.referent(weak), references.referent(old_soft), references.referent(phantom), references.referent(weak_chained)), (None, None, None, None));
+			assert_eq!((heap.class_of(lost), heap.class_of(stale), heap.class_of(haunted), heap.class_of(chained)), (None, None, None, None));
+			assert!(matches!(references.class("java/lang/ref/SoftReference").get_static("clock", "J"), Some(Variable::Long(_))));
+
+			// The reference handler adds the cleared references that have queues to them.
+			assert!(references.queued(queue).is_empty());
+			ReferenceHandler::start(loaders.clone(), heap.clone()).stop();
+			let mut queued = references.queued(queue);
+			queued.sort();
+			assert_eq!(queued, { let mut expected = vec![weak, phantom]; expected.sort(); expected });
+			assert_eq!(references.get(weak, "java/lang/ref/Reference", "queue", "Ljava/lang/ref/ReferenceQueue;"), NULL);
+
+			// Minor collections clear weak references to young objects as well.
+			if collector == Collector::Generational {
+				let young = references.reference_object("java/lang/ref/WeakReference", new_node(), None);
+				let collections = heap.gc_events().len();
+				while heap.gc_events().len() == collections {
+					new_node();
+				}
+				assert_eq!(heap.gc_events().last().unwrap().kind, GcKind::Young);
+				assert_eq!(references.referent(young), None);
+			}
+
+			// Before giving up on an allocation, a collection clears even recently used soft
+			// references.
+			let int_array = loaders.load_class(LoaderId::BOOTSTRAP, "[I").unwrap();
+			let cache = heap.allocate_array(&int_array, 1000).unwrap();
+			let soft = references.reference_object("java/lang/ref/SoftReference", cache, None);
+			references.put(soft, "java/lang/ref/SoftReference", "timestamp", "J", Variable::Long(Long { value: i64::MAX }));
+			heap.collect();
+			assert_eq!(references.referent(soft), Some(cache));
+			heap.allocate_array(&int_array, 1000).unwrap();
+			assert_eq!(references.referent(soft), None);
+			let events = heap.gc_events();
+			assert_eq!(events[events.len() - 2..].iter().map(|event| (event.kind, event.cause)).collect::<Vec<_>>(), vec![(GcKind::Full, GcCause::AllocationFailure); 2]);
+		}
+	}
+
+	#[test]
+	fn test_finalization() {
+		let statics = &[FieldAccessPropertyFlags::Public, FieldAccessPropertyFlags::Static];
+		// Counts its finalizations and saves the object being finalized, bringing it back to life.
+		let resource = ClassBuilder::new("demo/Resource")
+			.field(statics, "finalized", "I")
+			.field(statics, "saved", "Ldemo/Resource;")
+			.method(&[MethodAccessPropertyFlags::Protected], "finalize", "()V", |method| {
+				method.field(Opcode::GetStatic, "demo/Resource", "finalized", "I").push_int(7).op(Opcode::IAdd)
+					.field(Opcode::PutStatic, "demo/Resource", "finalized", "I")
+					.var(Opcode::ALoad, 0).field(Opcode::PutStatic, "demo/Resource", "saved", "Ldemo/Resource;")
+					.op(Opcode::Return);
+			});
+		let trivial = ClassBuilder::new("demo/Trivial")
+			.method(&[MethodAccessPropertyFlags::Protected], "finalize", "()V", |method| {
+				method.op(Opcode::Return);
+			});
+		let loaders = reference_loaders("finalization", &[
+			("demo/Resource", bytes(resource)),
+			("demo/File", bytes(ClassBuilder::new("demo/File").super_class("demo/Resource"))),
+			("demo/Trivial", bytes(trivial)),
+		]);
+		let class = |name: &str| loaders.load_class(LoaderId::APPLICATION, name).unwrap();
+		assert_eq!(class("demo/File").finalizer(), Some(class("demo/Resource")));
+		assert!(!class("demo/Trivial").has_finalizer());
+		assert!(!class("java/lang/Object").has_finalizer());
+
+		let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions::default()));
+		let references = References { loaders: loaders.clone(), heap: heap.clone() };
+		let queue = heap.allocate(&references.class("java/lang/ref/ReferenceQueue")).unwrap();
+		heap.new_global_ref(queue);
+		let file = heap.allocate(&class("demo/File")).unwrap();
+		let weak = references.reference_object("java/lang/ref/WeakReference", file, None);
+		let phantom = references.reference_object("java/lang/ref/PhantomReference", file, Some(queue));
+		heap.allocate(&class("demo/Trivial")).unwrap();
+
+		// An unreachable object with a finalizer is kept until it has run, and only weak
+		// references to it are cleared.
+		heap.collect();
+		assert_eq!(heap.class_of(file), Some(class("demo/File")));
+		assert_eq!((references.referent(weak), references.referent(phantom)), (None, Some(file)));
+		assert_eq!(heap.object_count(), 4);
+		ReferenceHandler::start(loaders.clone(), heap.clone()).stop();
+		let resource = class("demo/Resource");
+		assert_eq!(resource.get_static("finalized", "I"), Some(Variable::Int(Int { value: 7 })));
+		assert_eq!(resource.get_static("saved", "Ldemo/Resource;").and_then(|saved| saved.reference()), Some(file));
+
+		// Finalization brought it back to life, but it is not finalized again once unreachable.
+		heap.collect();
+		assert_eq!(references.referent(phantom), Some(file));
+		assert!(heap.put_static(&resource, "saved", "Ldemo/Resource;", NULL));
+		heap.collect();
+		assert_eq!(references.referent(phantom), None);
+		assert_eq!(heap.class_of(file), None);
+		ReferenceHandler::start(loaders.clone(), heap.clone()).stop();
+		assert_eq!(resource.get_static("finalized", "I"), Some(Variable::Int(Int { value: 7 })));
+		assert_eq!(references.queued(queue), vec![phantom]);
+	}
+
+	#[test]
+	fn test_cleaners() {
+		// Counts the times it runs.
+		let counter = ClassBuilder::new("demo/Counter")
+			.interface("java/lang/Runnable")
+			.field(&[FieldAccessPropertyFlags::Public, FieldAccessPropertyFlags::Static], "runs", "I")
+			.method(&[MethodAccessPropertyFlags::Public], "run", "()V", |method| {
+				method.field(Opcode::GetStatic, "demo/Counter", "runs", "I").int(Opcode::BIpush, 1).op(Opcode::IAdd)
+					.field(Opcode::PutStatic, "demo/Counter", "runs", "I")
+					.op(Opcode::Return);
+			});
+		let loaders = reference_loaders("cleaners", &[("demo/Counter", bytes(counter))]);
+		let counter = loaders.load_class(LoaderId::APPLICATION, "demo/Counter").unwrap();
+		assert!(loaders.load_class(LoaderId::BOOTSTRAP, "jdk/internal/ref/CleanerImpl$PhantomCleanableRef").unwrap().is_cleanable());
+		assert!(!loaders.load_class(LoaderId::BOOTSTRAP, "java/lang/ref/PhantomReference").unwrap().is_cleanable());
+
+		let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions::default()));
+		let references = References { loaders: loaders.clone(), heap: heap.clone() };
+		let queue = heap.allocate(&references.class("java/lang/ref/ReferenceQueue")).unwrap();
+		heap.new_global_ref(queue);
+		let plain = references.class("java/lang/Object");
+		let (lost, also_lost, held) = (heap.allocate(&plain).unwrap(), heap.allocate(&plain).unwrap(), heap.allocate(&plain).unwrap());
+		heap.new_global_ref(held);
+		// Registered as java.lang.ref.Cleaner.register and jdk.internal.ref.Cleaner.create do.
+		let registered = |class: &str, referent: Reference, field: &str| {
+			let cleanable = references.reference_object(class, referent, Some(queue));
+			references.put(cleanable, class, field, "Ljava/lang/Runnable;", object(heap.allocate(&counter).unwrap()));
+			cleanable
+		};
+		registered("jdk/internal/ref/CleanerImpl$PhantomCleanableRef", lost, "action");
+		registered("jdk/internal/ref/Cleaner", also_lost, "thunk");
+		let kept = registered("jdk/internal/ref/CleanerImpl$PhantomCleanableRef", held, "action");
+
+		// Once their referents are unreachable, the reference handler runs their actions rather
+		// than enqueueing them.
+		heap.collect();
+		ReferenceHandler::start(loaders.clone(), heap.clone()).stop();
+		assert_eq!(counter.get_static("runs", "I"), Some(Variable::Int(Int { value: 2 })));
+		assert!(references.queued(queue).is_empty());
+		assert_eq!(references.referent(kept), Some(held));
+	}
+
+	#[test]
+	fn test_finalizer_allocation() {
+		let statics = &[FieldAccessPropertyFlags::Public, FieldAccessPropertyFlags::Static];
+		// Allocates a node and holds it on the operand stack while the main thread collects.
+		let resource = ClassBuilder::new("demo/Resource")
+			.field(statics, "total", "I")
+			.field(statics, "last", "Ldemo/Node;")
+			.method(&[MethodAccessPropertyFlags::Protected], "finalize", "()V", |method| {
+				method.field(Opcode::GetStatic, "demo/Resource", "total", "I")
+					.type_op(Opcode::New, "demo/Node").op(Opcode::Dup).op(Opcode::Dup)
+					.push_int(40).field(Opcode::PutField, "demo/Node", "value", "I")
+					.field(Opcode::PutStatic, "demo/Resource", "last", "Ldemo/Node;")
+					.field(Opcode::GetField, "demo/Node", "value", "I").op(Opcode::IAdd)
+					.field(Opcode::PutStatic, "demo/Resource", "total", "I")
+					.op(Opcode::Return);
+			});
+		let loaders = reference_loaders("finalizer-allocation", &[
+			("demo/Node", bytes(ClassBuilder::new("demo/Node").field(&[FieldAccessPropertyFlags::Public], "value", "I"))),
+			("demo/Resource", bytes(resource)),
+		]);
+		let resource = loaders.load_class(LoaderId::APPLICATION, "demo/Resource").unwrap();
+		loaders.load_class(LoaderId::APPLICATION, "demo/Node").unwrap();
+		let heap = Arc::new(Heap::new(loaders.clone(), HeapOptions::default()));
+		for _ in 0..200 {
+			heap.allocate(&resource).unwrap();
+		}
+		heap.collect();
+
+		let done = AtomicBool::new(false);
+		std::thread::scope(|scope| {
+			scope.spawn(|| while !done.load(Ordering::Acquire) {
+				heap.collect();
+			});
+			ReferenceHandler::start(loaders.clone(), heap.clone()).stop();
+			done.store(true, Ordering::Release);
+		});
+		assert_eq!(resource.get_static("total", "I"), Some(Variable::Int(Int { value: 8000 })));
+		let last = resource.get_static("last", "Ldemo/Node;").and_then(|last| last.reference()).unwrap();
+		assert_eq!(heap.class_of(last).map(|class| class.name.clone()), Some("demo/Node".to_string()));
+	}
+}
@@ -0,0 +1,134 @@
+use std::sync::{Arc, OnceLock};
+
+use crate::{
+	class::{access::MethodAccessPropertyFlags, constant_pool::ConstantPool},
+	vm::{
+		errors::LinkageError,
+		method_area::RuntimeClass}};
+
+/// A field or method found by resolving a symbolic reference.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedMember {
+	/// The class that declares the member, which may be a superclass or superinterface of the
+	/// class the reference names.
+	pub class: Arc<RuntimeClass>,
+	pub name: String,
+	pub descriptor: String,
+	pub access_flags: u16,
+}
+
+impl ResolvedMember {
+	// Fields and methods share the bit values of these flags.
+	fn has_flag(&self, flag: MethodAccessPropertyFlags) -> bool {
+		self.access_flags & flag as u16 != 0
+	}
+
+	pub fn is_public(&self) -> bool {
+		self.has_flag(MethodAccessPropertyFlags::Public)
+	}
+
+	pub fn is_private(&self) -> bool {
+		self.has_flag(MethodAccessPropertyFlags::Private)
+	}
+
+	pub fn is_protected(&self) -> bool {
+		self.has_flag(MethodAccessPropertyFlags::Protected)
+	}
+
+	pub fn is_final(&self) -> bool {
+		self.has_flag(MethodAccessPropertyFlags::Final)
+	}
+
+	pub fn is_static(&self) -> bool {
+		self.has_flag(MethodAccessPropertyFlags::Static)
+	}
+
+	pub fn is_abstract(&self) -> bool {
+		self.has_flag(MethodAccessPropertyFlags::Abstract)
+	}
+
+	pub fn is_native(&self) -> bool {
+		self.has_flag(MethodAccessPropertyFlags::Native)
+	}
+}
+
+/// The outcome of resolving a symbolic reference.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Resolved {
+	Class(Arc<RuntimeClass>),
+	Field(ResolvedMember),
+	Method(ResolvedMember),
+}
+
+/// The run-time constant pool of a class (JVMS17 5.1): its class file constant pool, with each
+/// symbolic reference resolved on first use.
+///
+/// The outcome of resolving an entry is kept, failure included, so later uses of the entry see
+/// the same class or member, or the same error (JVMS17 5.4.3).
+#[derive(Debug)]
+pub struct RuntimeConstantPool {
+	pub constants: ConstantPool,
+	resolved: Vec<OnceLock<Result<Resolved, LinkageError>>>,
+}
+
+impl RuntimeConstantPool {
+
+	pub fn new(constants: ConstantPool) -> RuntimeConstantPool {
+		let resolved = (0..constants.length).map(|_| OnceLock::new()).collect();
+		RuntimeConstantPool { constants, resolved }
+	}
+
+	/// The name in the Class constant at `index`.
+	pub fn class_name(&self, index: u16) -> Option<String> {
+		let class = self.constants.get_class(index).ok()?;
+		Some(self.constants.get_utf8(class.index).ok()?.to_string())
+	}
+
+	/// The Class constant index, name and descriptor of the FieldRef constant at `index`.
+	pub fn field_ref(&self, index: u16) -> Option<(u16, String, String)> {
+		let reference = self.constants.get_field_ref(index).ok()?;
+		self.with_name_and_type(reference.class_index, reference.name_and_type_index)
+	}
+
+	/// The Class constant index, name and descriptor of the MethodRef constant at `index`.
+	pub fn method_ref(&self, index: u16) -> Option<(u16, String, String)> {
+		let reference = self.constants.get_method_ref(index).ok()?;
+		self.with_name_and_type(reference.class_index, reference.name_and_type_index)
+	}
+
+	/// The Class constant index, name and descriptor of the InterfaceMethodRef constant at `index`.
+	pub fn interface_method_ref(&self, index: u16) -> Option<(u16, String, String)> {
+		let reference = self.constants.get_interface_method_ref(index).ok()?;
+		self.with_name_and_type(reference.class_index, reference.name_and_type_index)
+	}
+
+	fn with_name_and_type(&self, class_index: u16, name_and_type_index: u16) -> Option<(u16, String, String)> {
+		let name_and_type = self.constants.get_name_and_type(name_and_type_index).ok()?;
+		let name = self.constants.get_utf8(name_and_type.name_index).ok()?.to_string();
+		let descriptor = self.constants.get_utf8(name_and_type.type_index).ok()?.to_string();
+		Some((class_index, name, descriptor))
+	}
+
+	/// Whether the entry at `index` has been resolved, successfully or not.
+	pub fn is_resolved(&self, index: u16) -> bool {
+		self.resolved.get(index as usize).is_some_and(|slot| slot.get().is_some())
+	}
+
+	/// The kept outcome of resolving the entry at `index`, calling `resolve` if there is none.
+	///
+	/// Threads racing to resolve an entry all see the outcome of the first to finish. Errors that
+	/// are not linkage errors, such as failing to read the class path, are not kept.
+	pub fn resolve<F: FnOnce() -> Result<Resolved, LinkageError>>(&self, index: u16, resolve: F) -> Result<Resolved, LinkageError> {
+		let Some(slot) = self.resolved.get(index as usize) else {
+			return Err(LinkageError::Internal(format!("constant pool index {} out of range", index)));
+		};
+		if let Some(outcome) = slot.get() {
+			return outcome.clone();
+		}
+		let outcome = resolve();
+		if matches!(outcome, Err(LinkageError::Internal(_))) {
+			return outcome;
+		}
+		slot.get_or_init(|| outcome).clone()
+	}
+}
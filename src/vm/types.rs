@@ -3,6 +3,7 @@ use std::str::FromStr;
 use strum_macros::Display;
 
 use crate::class::class::Class;
+use crate::vm::heap::Reference;
 
 #[derive(Clone, Debug, Default, Display, PartialEq)]
 pub enum Type {
@@ -42,6 +43,32 @@ impl Default for Variable {
 	}
 }
 
+impl Variable {
+	/// The default value of a variable with the given field descriptor (JVMS17 2.3, 2.4).
+	pub fn default_value(descriptor: &str) -> Variable {
+		match descriptor.as_bytes().first() {
+			Some(b'Z') => Variable::Boolean(Boolean { value: false }),
+			Some(b'B') => Variable::Byte(Byte { value: 0 }),
+			Some(b'C') => Variable::Char(Char { value: 0 }),
+			Some(b'D') => Variable::Double(Double { value: 0.0 }),
+			Some(b'F') => Variable::Float(Float { value: 0.0 }),
+			Some(b'I') => Variable::Int(Int { value: 0 }),
+			Some(b'J') => Variable::Long(Long { value: 0 }),
+			Some(b'S') => Variable::Short(Short { value: 0 }),
+			_ => NULL,
+		}
+	}
+
+	/// The object a reference variable refers to, None for null and for values of primitive types.
+	pub fn reference(&self) -> Option<Reference> {
+		match self {
+			Variable::ClassReference(reference) => Some(reference.value),
+			Variable::ArrayReference(reference) => Some(reference.value),
+			_ => None,
+		}
+	}
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Boolean {
 	pub value: bool,
@@ -84,13 +111,12 @@ pub struct Short {
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct ClassReference {
-	pub value: String,
+	pub value: Reference,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct ArrayReference {
-	pub dimensions: u32,
-	pub class_name: String,
+	pub value: Reference,
 }
 
 #[derive(Clone, Debug, PartialEq)]
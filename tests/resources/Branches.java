@@ -0,0 +1,41 @@
+public class Branches {
+	private int total;
+
+	public int loop(int n) {
+		int sum = 0;
+		for (int i = 0; i < n; i++) {
+			sum += i;
+		}
+		return sum;
+	}
+
+	public int dense(int key) {
+		switch (key) {
+			case 1: return 10;
+			case 2: return 20;
+			case 3: return 30;
+			default: return -1;
+		}
+	}
+
+	public int sparse(int key) {
+		switch (key) {
+			case 1: return 10;
+			case 1000: return 20;
+			case 100000: return 30;
+			default: return -1;
+		}
+	}
+
+	public int guarded(int[] values) {
+		try {
+			return values[0];
+		} catch (ArrayIndexOutOfBoundsException e) {
+			return -1;
+		}
+	}
+
+	public Object choose(boolean flag) {
+		return new StringBuilder(flag ? "yes" : "no");
+	}
+}
@@ -0,0 +1,20 @@
+import java.util.function.Supplier;
+
+public class App {
+	static final String NAME = "world";
+
+	public static void main(String[] args) {
+		Greeter greeter = new English();
+		System.out.println(greeter.greet(NAME));
+		Supplier<String> supplier = () -> shout(NAME);
+		System.out.println(supplier.get());
+	}
+
+	private static String shout(String text) {
+		return text.toUpperCase();
+	}
+
+	static void unused() {
+		new French().greet(NAME);
+	}
+}
@@ -0,0 +1,5 @@
+public class French implements Greeter {
+	public String greet(String name) {
+		return "Bonjour, " + name;
+	}
+}
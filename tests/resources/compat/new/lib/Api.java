@@ -0,0 +1,33 @@
+package lib;
+
+public class Api extends Base implements Runnable {
+	public static final int LIMIT = 20;
+	public static final String NAME = "api";
+	public long size;
+	public final String label;
+	long stamp;
+
+	public Api() {
+		label = null;
+	}
+
+	public void run() {
+	}
+
+	public void resize(long n) {
+	}
+
+	public static int count() {
+		return 0;
+	}
+
+	public static Api create() {
+		return new Api();
+	}
+
+	protected final void hook() {
+	}
+
+	public void extra() {
+	}
+}
@@ -0,0 +1,5 @@
+package lib;
+
+public abstract class Factory {
+	public abstract void make();
+}
@@ -0,0 +1,6 @@
+package lib;
+
+public final class Widget {
+	public void run() {
+	}
+}
@@ -0,0 +1,38 @@
+package lib;
+
+public class Api extends Base implements Runnable {
+	public static final int LIMIT = 10;
+	public static final String NAME = "api";
+	public int size;
+	public String label;
+	protected long stamp;
+
+	public Api() {
+	}
+
+	public void run() {
+	}
+
+	public void resize(int n) {
+	}
+
+	public int count() {
+		return size;
+	}
+
+	public static Api create() {
+		return new Api();
+	}
+
+	public void close() {
+	}
+
+	protected void hook() {
+	}
+
+	public void inherited() {
+	}
+
+	void internal() {
+	}
+}
@@ -0,0 +1,6 @@
+package lib;
+
+public class Factory {
+	public void make() {
+	}
+}
@@ -0,0 +1,6 @@
+package lib;
+
+class Hidden {
+	public void work() {
+	}
+}
@@ -0,0 +1,6 @@
+package lib;
+
+public class Widget extends Base implements Runnable {
+	public void run() {
+	}
+}
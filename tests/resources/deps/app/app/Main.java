@@ -0,0 +1,22 @@
+package app;
+
+import java.util.List;
+import java.util.function.Function;
+
+import model.Item;
+import model.Kind;
+import model.Marker;
+import model.Tag;
+import service.Service;
+
+@Marker(Kind.class)
+public class Main {
+	private List<Tag> tags;
+
+	public static void main(String[] args) throws Exception {
+		Function<Item, String> name = Item::name;
+		Service service = null;
+		System.out.println(name.apply(service.find(args[0])));
+		System.out.println(sun.misc.Unsafe.class);
+	}
+}
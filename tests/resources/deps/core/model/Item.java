@@ -0,0 +1,7 @@
+package model;
+
+public class Item {
+	public String name() {
+		return "item";
+	}
+}
@@ -0,0 +1,4 @@
+package model;
+
+public class Tag {
+}
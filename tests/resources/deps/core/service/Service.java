@@ -0,0 +1,7 @@
+package service;
+
+import model.Item;
+
+public interface Service {
+	Item find(String name);
+}
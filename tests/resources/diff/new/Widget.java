@@ -0,0 +1,26 @@
+import java.io.Serializable;
+
+public final class Widget implements Serializable {
+	private long count;
+	public String name = "widget";
+
+	public void reset() {
+		count = 0;
+	}
+
+	public long next() {
+		return ++count;
+	}
+
+	public String label(int n) {
+		return name + "=" + n;
+	}
+
+	public static int twice(int x) {
+		return x * 2;
+	}
+
+	public static int thrice(int x) {
+		return x * 3;
+	}
+}
@@ -0,0 +1,21 @@
+public class Widget {
+	private int count;
+	protected String name = "widget";
+
+	@Deprecated
+	public void reset() {
+		count = 0;
+	}
+
+	public int next() {
+		return ++count;
+	}
+
+	public String label(int n) {
+		return name + ":" + n;
+	}
+
+	public static int twice(int x) {
+		return x * 2;
+	}
+}
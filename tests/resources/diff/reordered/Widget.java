@@ -0,0 +1,21 @@
+public class Widget {
+	protected String name = "widget";
+	private int count;
+
+	public static int twice(int x) {
+		return x * 2;
+	}
+
+	public String label(int n) {
+		return name + ":" + n;
+	}
+
+	public int next() {
+		return ++count;
+	}
+
+	@Deprecated
+	public void reset() {
+		count = 0;
+	}
+}
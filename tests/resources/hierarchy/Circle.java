@@ -0,0 +1,15 @@
+public class Circle implements Shape, Comparable<Circle> {
+	private final double radius;
+
+	public Circle(double radius) {
+		this.radius = radius;
+	}
+
+	public double area() {
+		return Math.PI * radius * radius;
+	}
+
+	public int compareTo(Circle other) {
+		return Double.compare(radius, other.radius);
+	}
+}
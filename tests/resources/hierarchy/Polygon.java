@@ -0,0 +1,7 @@
+public abstract class Polygon implements Shape {
+	public abstract int sides();
+
+	public String describe() {
+		return "polygon";
+	}
+}
@@ -0,0 +1,7 @@
+public interface Shape {
+	double area();
+
+	default String describe() {
+		return "shape";
+	}
+}
@@ -0,0 +1,19 @@
+public class Square extends Polygon implements Named {
+	private final double side;
+
+	public Square(double side) {
+		this.side = side;
+	}
+
+	public double area() {
+		return side * side;
+	}
+
+	public int sides() {
+		return 4;
+	}
+
+	public String name() {
+		return "square";
+	}
+}
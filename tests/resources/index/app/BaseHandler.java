@@ -0,0 +1,6 @@
+package app;
+
+import fw.JsonHandler;
+
+public abstract class BaseHandler implements JsonHandler {
+}
@@ -0,0 +1,6 @@
+package app;
+
+public class Plain implements Runnable {
+	public void run() {
+	}
+}
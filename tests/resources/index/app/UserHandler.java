@@ -0,0 +1,20 @@
+package app;
+
+import fw.Component;
+import fw.Inject;
+import fw.Route;
+
+@Component
+public class UserHandler extends BaseHandler {
+	@Inject
+	private Object repository;
+
+	@Route("/users")
+	public void handle(String request) {
+	}
+
+	@Route("/users/count")
+	public int count() {
+		return 0;
+	}
+}
@@ -0,0 +1,5 @@
+package fw;
+
+public interface Handler {
+	void handle(String request);
+}
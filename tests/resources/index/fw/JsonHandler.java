@@ -0,0 +1,4 @@
+package fw;
+
+public interface JsonHandler extends Handler {
+}
@@ -0,0 +1,7 @@
+package demo;
+
+public class Hello {
+	public static String greet(String name) {
+		return "Hello, " + name;
+	}
+}
@@ -0,0 +1,13 @@
+public class Outer {
+	private int secret = 1;
+
+	private static int hidden() {
+		return 2;
+	}
+
+	class Inner {
+		int peek() {
+			return secret + hidden();
+		}
+	}
+}
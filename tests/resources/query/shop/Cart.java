@@ -0,0 +1,30 @@
+package shop;
+
+import java.util.function.ToIntFunction;
+
+public class Cart {
+	public int sum(Item item, Special special, Plain plain, Other other) {
+		int sum = item.total();
+		sum += special.total();
+		sum += plain.total();
+		sum += other.total();
+		return sum;
+	}
+
+	public void reprice(Item item) {
+		item.price = item.price + 1;
+	}
+
+	public Item create(boolean special) {
+		if (special) {
+			return new Special();
+		}
+		return new Item();
+	}
+
+	public String label(Item item) {
+		ToIntFunction<Item> total = Item::total;
+		String prefix = "discount";
+		return prefix + total.applyAsInt(item);
+	}
+}
@@ -0,0 +1,9 @@
+package shop;
+
+public class Item {
+	int price;
+
+	public int total() {
+		return price;
+	}
+}
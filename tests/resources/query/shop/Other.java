@@ -0,0 +1,7 @@
+package shop;
+
+public class Other {
+	public int total() {
+		return 0;
+	}
+}
@@ -0,0 +1,8 @@
+package shop;
+
+public class Special extends Item {
+	@Override
+	public int total() {
+		return price / 2;
+	}
+}
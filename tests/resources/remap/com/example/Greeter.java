@@ -0,0 +1,6 @@
+package com.example;
+
+@FunctionalInterface
+public interface Greeter {
+	String greet(String name);
+}
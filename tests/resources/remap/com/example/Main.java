@@ -0,0 +1,27 @@
+package com.example;
+
+import java.util.ArrayList;
+import java.util.List;
+
+public class Main {
+	private final List<Greeter> greeters = new ArrayList<>();
+
+	@Marker(kind = Kind.LOUD, type = Greeter.class)
+	public static void main(String[] args) throws Exception {
+		Main main = new Main();
+		main.greeters.add(name -> "Hello, " + name);
+		main.greeters.add(new Inner());
+		for (Greeter greeter : main.greeters) {
+			System.out.println(greeter.greet("world"));
+		}
+		System.out.println(Class.forName("com.example.Main$Inner").getSimpleName());
+		Marker marker = Main.class.getMethod("main", String[].class).getAnnotation(Marker.class);
+		System.out.println(marker.kind() + " " + marker.type().getName());
+	}
+
+	static class Inner implements Greeter {
+		public String greet(String name) {
+			return "Hi, " + name;
+		}
+	}
+}
@@ -0,0 +1,10 @@
+package com.example;
+
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+
+@Retention(RetentionPolicy.RUNTIME)
+public @interface Marker {
+	Kind kind() default Kind.QUIET;
+	Class<?> type();
+}
@@ -0,0 +1,8 @@
+public class Api {
+	@Keep
+	public void exported() {
+	}
+
+	public void internal() {
+	}
+}
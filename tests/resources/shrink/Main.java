@@ -0,0 +1,11 @@
+public class Main {
+	public static void main(String[] args) {
+		Task task = new Worker();
+		task.execute();
+		System.out.println(new Printer());
+	}
+
+	static void neverCalled() {
+		new Orphan();
+	}
+}
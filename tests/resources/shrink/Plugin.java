@@ -0,0 +1,5 @@
+@Keep
+public class Plugin {
+	void start() {
+	}
+}
@@ -0,0 +1,8 @@
+public class Printer {
+	public String toString() {
+		return "printer";
+	}
+
+	public void unusedMethod() {
+	}
+}
@@ -0,0 +1,4 @@
+public interface Task {
+	void execute();
+	void cancel();
+}
@@ -0,0 +1,15 @@
+public class Worker implements Task {
+	int count;
+	int unusedField;
+
+	public void execute() {
+		count++;
+	}
+
+	public void cancel() {
+		helper();
+	}
+
+	void helper() {
+	}
+}